@@ -0,0 +1,19 @@
+//! What every fuzz target does with a parsed transcription.
+
+use pegasus_core::input::transcript_format::TranscriptFormat;
+use pegasus_core::input::validation;
+
+/// Parses content in a format and runs everything the pipeline does with
+/// the transcription; an error is fine, a panic is a bug.
+pub fn parse(format: TranscriptFormat, content: &str) {
+  let Ok(mut transcription) = format.parse(content) else {
+    return;
+  };
+  let _ = validation::check_transcription(&transcription);
+  let _ = transcription.timeline_issues(1.0);
+  let _ = transcription.confidence_summary(0.5);
+  let _ = transcription.get_low_probability_words(0.5);
+  let _ = transcription.word_count();
+  let _ = transcription.full_text();
+  let _ = transcription.collapse_repeated_segments();
+}
@@ -0,0 +1,14 @@
+//! SRT and WebVTT cues, with and without the WebVTT header.
+
+#![no_main]
+
+mod common;
+
+use libfuzzer_sys::fuzz_target;
+use pegasus_core::input::transcript_format::TranscriptFormat;
+
+fuzz_target!(|data: &[u8]| {
+  let content = String::from_utf8_lossy(data);
+  common::parse(TranscriptFormat::Srt, &content);
+  common::parse(TranscriptFormat::Vtt, &format!("WEBVTT\n\n{}", content));
+});
@@ -0,0 +1,16 @@
+//! Any input, parsed in the detected format and in every other one.
+
+#![no_main]
+
+mod common;
+
+use libfuzzer_sys::fuzz_target;
+use pegasus_core::input::transcript_format::TranscriptFormat;
+
+fuzz_target!(|data: &[u8]| {
+  let content = String::from_utf8_lossy(data);
+  common::parse(TranscriptFormat::detect(&content), &content);
+  for format in TranscriptFormat::ALL {
+    common::parse(format, &content);
+  }
+});
@@ -0,0 +1,18 @@
+//! Whisper and whisper.cpp JSON, starting from any JSON value so the
+//! fuzzer spends its time on the shape of the segments.
+
+#![no_main]
+
+mod common;
+
+use libfuzzer_sys::fuzz_target;
+use pegasus_core::input::transcript_format::TranscriptFormat;
+
+fuzz_target!(|data: &[u8]| {
+  let content = String::from_utf8_lossy(data);
+  if serde_json::from_str::<serde_json::Value>(&content).is_err() {
+    return;
+  }
+  common::parse(TranscriptFormat::WhisperJson, &content);
+  common::parse(TranscriptFormat::WhisperCppJson, &content);
+});
@@ -0,0 +1,229 @@
+# English messages of the pegasus command-line tool.
+#
+# This catalog is built in and is the fallback for every message. To
+# translate Pegasus, copy it to
+# $XDG_DATA_HOME/pegasus/locales/<language>/pegasus.ftl and translate the
+# values. Command-line help can be translated with help-<command> and
+# help-<command>--<argument> messages, such as help-queue-add and
+# help-queue-add--force. Error messages are named error-<module>-<error>,
+# such as error-network-response.
+
+## Configuration
+
+config-error = Configuration Error: { $error }
+config-reset = Configuration has been reset to default values.
+config-reset-failed = Failed to reset configuration: { $error }
+config-up-to-date = Configuration is already up to date.
+config-migrated-notice = Migrated: { $notice }
+config-migrated = Configuration has been migrated.
+config-migrate-failed = Failed to migrate configuration: { $error }
+config-updated = Configuration has been updated.
+config-unchanged = Configuration is unchanged.
+config-edit-failed = Failed to edit configuration: { $error }
+
+## Platform support
+
+unsupported-daemon = The daemon is only supported on Unix systems.
+unsupported-tui = The review interface is only supported on Unix systems.
+unsupported-service = Installing a service is only supported on Unix systems.
+
+## Commands
+
+history-removed = Removed { $removed ->
+    [one] 1 line
+   *[other] { $removed } lines
+  } from the history
+wer-too-long = The texts are too long to align; printing the error rates only
+tui-written = Reviewed text written to { $path }
+service-written = Wrote { $path }
+service-enable = Enable it with: systemctl --user daemon-reload && systemctl --user enable --now { $unit }
+dictate-background = Dictating in the background as process { $pid }, logging to { $path }; stop it with: pegasus dictate --stop
+dictate-stopped = Stopped dictation (process { $pid })
+line-mode-failed = { $failed } of the { $unit }s could not be refined
+api-key-stdin-failed = Failed to read API key from stdin
+api-key-stored = API key stored in the system keyring.
+api-key-removed = API key removed from the system keyring.
+bench-failed = { $failed } of the { $cases } cases could not be refined
+
+## Interruption
+
+interrupted = Stopping; press Ctrl-C again to quit at once
+bench-cancelled = Cancelled after { $cases } benchmark cases
+queue-cancelled = Cancelled after refining { $done } files; the remaining jobs stay queued
+
+## Queue
+
+queue-added = Added { $added } of { $found } files to { $path } ({ $up_to_date } up to date)
+queue-refined = Refined { $done } files ({ $unchanged } unchanged)
+queue-failed = { $failed } of the { $attempted } files could not be refined ({ $refused } refused by the model); see `pegasus queue status`
+
+## Dictionary
+
+file-read-failed = Cannot read '{ $path }': { $error }
+dictionary-imported = Added { $added } of { $total } terms to { $path }
+dictionary-nothing-learned = No word was corrected { $min_count } times or more that is not in { $path }
+dictionary-learn-hint = Run with --add to add these terms to { $path }
+dictionary-learned = Added { $added } terms to { $path }
+
+## Interactive session
+
+repl-prompt = { "pegasus> " }
+repl-continuation-prompt = { "...> " }
+repl-welcome = Enter text to refine it, or :help for commands.
+repl-help =
+    Enter text to refine it; end a line with \ to continue the paragraph.
+
+      :dict                 list the dictionary words
+      :dict add <word>      add a word to the dictionary for this session
+      :dict remove <word>   remove a word from the dictionary for this session
+      :undo                 drop the last refined paragraph
+      :show                 print the refined text of the session
+      :write <path>         write the refined text of the session to a file
+      :help                 show this help
+      :quit                 end the session (also Ctrl-D)
+repl-cancelled = Cancelled
+repl-dropped = Dropped: { $paragraph }
+repl-nothing-to-undo = Nothing to undo
+repl-write-usage = Usage: :write <path>
+repl-written = Wrote { $path }
+repl-unknown-command = Unknown command :{ $name }; type :help for commands
+repl-dictionary-empty = The dictionary is empty
+repl-dictionary-usage = Usage: :dict add|remove <word>
+repl-word-added = Added { $word }
+repl-word-exists = { $word } is already in the dictionary
+repl-word-removed = Removed { $word }
+repl-word-missing = { $word } is not in the dictionary
+
+## Self-update
+
+update-current = Pegasus { $version } is up to date
+update-available = Pegasus { $latest } is available (running { $current })
+update-installed = Updated Pegasus from { $current } to { $latest } at { $path }
+
+## Errors
+
+error-app-input = Input Error: { $error }
+error-app-network = Network Error: { $error }
+error-app-refinement = Refinement Error: { $error }
+error-app-validation = Validation Error: { $error }
+error-app-refusal = Refusal Error: { $error }
+error-app-cancelled = Cancelled
+
+error-audio-read = Cannot read audio file '{ $path }': { $error }
+error-audio-network = Transcription service error: { $error }
+error-audio-split = Cannot split the recording: { $error }
+
+error-backend-spawn = Cannot start backend command '{ $command }': { $error }
+error-backend-exited = Backend command '{ $command }' exited with { $status } before it was ready
+error-backend-timeout = Backend at { $url } was not ready within { $seconds } seconds
+
+error-bench-read-directory = Cannot read benchmark directory '{ $path }': { $error }
+error-bench-no-cases = No benchmark cases in '{ $path }': add pairs of <name>.txt (or .json, .srt, .vtt) inputs and <name>.expected.txt references
+error-bench-read-reference = Cannot read reference '{ $path }': { $error }
+
+error-config-file-read = Cannot read configuration file: '{ $path }'. Please check file permissions and ensure the file exists.
+error-config-file-write = Cannot write configuration file: '{ $path }'.
+error-config-parse = Configuration file is invalid: '{ $error }'. Please check the syntax and ensure all required fields are present.
+error-config-invalid =
+    Found problems in the configuration:
+    { $problems }
+
+error-dictation-record = Failed to record with '{ $command }': { $error }
+error-dictation-signal = Failed to listen for signals: { $error }
+error-dictation-running = Dictation is already running
+error-dictation-not-running = Dictation is not running
+error-dictation-control = Failed to control dictation: { $error }
+
+error-dictionary-not-configured = No dictionary is configured; set [dictionary] path
+error-dictionary-read = Cannot read '{ $path }': { $error }
+error-dictionary-parse = Cannot parse '{ $path }': { $error }
+error-dictionary-invalid-column = Column { $column } does not exist; columns are numbered from 1
+
+error-files-read = Cannot read file '{ $path }'. Please check if the file exists and you have permission to access it.
+error-files-write = Cannot write file '{ $path }': { $error }
+error-files-create = Cannot create file '{ $path }': { $error }
+error-files-copy = Cannot copy file '{ $source }' to '{ $destination }': { $error }
+error-files-move = Cannot move file '{ $source }' to '{ $destination }': { $error }
+error-files-lock = Cannot lock file '{ $path }': { $error }
+error-files-locked = File '{ $path }' is locked by another process
+error-files-editor = Editor '{ $editor }' failed: { $error }
+
+error-input-file-read = Failed to read file '{ $path }': { $error }
+error-input-stdin-read = Failed to read standard input: { $error }
+error-input-empty = Input is empty
+error-input-no-input = No input provided: use --file or --text
+error-input-too-large = Input '{ $path }' is { $size } bytes, which exceeds the limit of { $limit } bytes (see [input] max_size)
+error-input-binary = Input '{ $path }' looks like a binary file, not a text transcript
+error-input-invalid-utf8 = Input '{ $path }' is not valid UTF-8 (invalid byte near offset { $offset }); use --encoding to set its encoding
+error-input-malformed-transcript = Failed to parse { $format } transcription: { $error }
+error-input-invalid-transcript =
+    Transcription failed strict validation:
+      - { $problems }
+error-input-unknown-encoding = Unknown encoding '{ $encoding }'
+error-input-invalid-range = Invalid range '{ $range }': { $error }
+error-input-range-not-in-input = Cannot select { $range } of the input: { $error }
+
+error-ipc-startup = Failed to start IPC server: { $error }
+error-ipc-io = IPC connection failed: { $error }
+error-ipc-cancelled = Cancelled
+
+error-llm-api-request = LLM API request failed: { $error }
+error-llm-invalid-response = Invalid API response: { $error }
+error-llm-refinement = Text refinement failed: { $error }
+error-llm-output-too-long = LLM output too long: { $error }
+error-llm-context-overflow = Prompt of about { $needed } tokens and an answer of about { $answer } tokens exceed the context window of { $window } tokens; { $advice }
+error-llm-shorten-input = shorten the input or use a model with a larger context window
+error-llm-lower-chunk-size = lower `[input] chunk_size` to split the input into smaller chunks
+error-llm-low-similarity = Refined text has a similarity of { $similarity } to its input, below `[llm] min_similarity` of { $threshold }; the model may have answered the transcript instead of refining it
+error-llm-refusal = The model refused to refine the text ({ $kind } refusal)
+
+error-metrics-bind = Cannot listen for metrics on '{ $address }': { $error }
+error-metrics-io = Metrics exporter failed: { $error }
+
+error-network-invalid-url = Invalid service URL: '{ $url }'. Please check your configuration file.
+error-network-invalid-proxy = Invalid proxy URL: '{ $url }'. Please check your configuration file.
+error-network-unsupported-socket = Unix domain socket URL '{ $url }' is not supported on this platform. Please use an http:// or https:// URL.
+error-network-client = Failed to set up the HTTP client: { $error }
+error-network-request = Failed to connect to service. Please verify the service is running and accessible.
+error-network-response = Service returned an error (HTTP { $status }). Please check the service logs and try again.
+error-network-decode = Failed to decode service response. The service may be experiencing issues or the format may be unsupported.
+error-network-circuit-open = Service at '{ $url }' is failing repeatedly. Requests are paused for { $seconds }s to let it recover.
+
+error-output-unknown-sink = Unknown output sink '{ $sink }', expected one of { $expected }
+error-output-invalid = Cannot use output sink '{ $sink }': { $error }
+error-output-write = Failed to write output to { $sink }: { $error }
+
+error-queue-location = Cannot create the queue file: { $error }
+error-queue-read = Cannot read the queue file '{ $path }': { $error }
+error-queue-corrupt = Queue file '{ $path }' is corrupt: { $error }
+error-queue-missing-input = Input file '{ $path }' does not exist
+error-queue-directory = '{ $path }' is a directory; add it with --recursive
+error-queue-output-collision = Both '{ $first }' and '{ $second }' would be refined to '{ $output }'; add them separately with --output-dir or narrow the files with --ext
+error-queue-busy = The queue is already being run by another process
+
+error-repl-editor = Line editor error: { $error }
+
+error-secrets-keyring = System keyring error: { $error }
+error-secrets-not-found = No API key is stored in the system keyring
+error-secrets-command = API key command '{ $command }' failed: { $error }
+error-secrets-empty-command-output = API key command '{ $command }' printed no key
+error-secrets-file-read = Cannot read API key file '{ $path }': { $error }
+error-secrets-empty-file = API key file '{ $path }' is empty
+
+error-systemd-executable = Cannot determine the path of the pegasus executable: { $error }
+error-systemd-directory = Cannot create the systemd user unit directory: { $error }
+error-systemd-exists = '{ $path }' already exists. Use --force to overwrite it.
+error-systemd-write = Cannot write the systemd unit file: { $error }
+
+error-tui-not-a-terminal = The review interface needs an interactive terminal
+error-tui-terminal = Terminal error: { $error }
+error-tui-record = Cannot record corrections: { $error }
+
+error-update-fetch = Cannot fetch the latest release from GitHub: { $error }
+error-update-missing-asset = Release { $release } has no '{ $asset }' asset for this platform
+error-update-checksum-mismatch = Checksum of '{ $asset }' does not match SHA256SUMS; the download was discarded
+error-update-invalid-signature = Signature of SHA256SUMS is invalid: { $error }
+error-update-replace = Cannot replace the binary at '{ $path }': { $error }
+
+error-usage-location = Cannot create the usage file: { $error }
+error-usage-read = Cannot read the usage file '{ $path }': { $error }
@@ -0,0 +1,122 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Application runtime errors.
+///
+/// Represents high-level errors that can occur during application workflows.
+#[derive(Error, Debug)]
+pub enum RuntimeError {
+  #[error("{}", t!("error-app-input", error = .0.as_str()))]
+  Input(String),
+
+  #[error("{}", t!("error-app-network", error = .0.as_str()))]
+  Network(String),
+
+  #[error("{}", t!("error-app-refinement", error = .0.as_str()))]
+  Refinement(String),
+
+  /// A configured value, such as the service URL or API key, is invalid.
+  #[error("{}", t!("error-app-validation", error = .0.as_str()))]
+  Validation(String),
+
+  /// The model refused to refine the text.
+  #[error("{}", t!("error-app-refusal", error = .0.as_str()))]
+  Refusal(String),
+
+  /// The run was cancelled; holds the text refined until then.
+  #[error("{}", t!("error-app-cancelled"))]
+  Cancelled(String),
+
+  /// An error reported by the daemon that is no failed refinement, such
+  /// as an invalid token.
+  #[error("{0}")]
+  Daemon(String),
+}
+
+impl RuntimeError {
+  /// Gets the category of the error.
+  ///
+  /// # Returns
+  ///
+  /// The `ErrorKind` of the error.
+  pub fn kind(&self) -> ErrorKind {
+    return match self {
+      RuntimeError::Input(_) => ErrorKind::Input,
+      RuntimeError::Network(_) => ErrorKind::Network,
+      RuntimeError::Refinement(_) => ErrorKind::Llm,
+      RuntimeError::Validation(_) => ErrorKind::Validation,
+      RuntimeError::Refusal(_) => ErrorKind::Refusal,
+      RuntimeError::Cancelled(_) => ErrorKind::Cancelled,
+      RuntimeError::Daemon(_) => ErrorKind::Other,
+    };
+  }
+}
+
+/// Category of a failure.
+///
+/// Each category exits the CLI with its own status, so scripts can branch
+/// on the type of failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+  /// The configuration could not be read, parsed or written
+  Config,
+  /// The input could not be read or parsed
+  Input,
+  /// The LLM service could not be reached
+  Network,
+  /// The LLM service returned an unusable response
+  Llm,
+  /// The model refused to refine the text
+  Refusal,
+  /// A configuration value or argument is invalid
+  Validation,
+  /// The run was cancelled, such as with Ctrl-C
+  Cancelled,
+  /// Any other failure
+  Other,
+}
+
+impl ErrorKind {
+  /// Gets the name of the category in machine-readable output.
+  ///
+  /// # Returns
+  ///
+  /// The category name.
+  pub fn name(self) -> &'static str {
+    return match self {
+      ErrorKind::Config => "config",
+      ErrorKind::Input => "input",
+      ErrorKind::Network => "network",
+      ErrorKind::Llm => "llm",
+      ErrorKind::Refusal => "refusal",
+      ErrorKind::Validation => "validation",
+      ErrorKind::Cancelled => "cancelled",
+      ErrorKind::Other => "other",
+    };
+  }
+
+  /// Gets the process exit status for the category.
+  ///
+  /// Status 2 is left to command-line usage errors; cancelled runs exit
+  /// with 130 like shells do for processes interrupted with Ctrl-C.
+  ///
+  /// # Returns
+  ///
+  /// The exit status.
+  pub fn exit_code(self) -> i32 {
+    return match self {
+      ErrorKind::Other => 1,
+      ErrorKind::Config => 3,
+      ErrorKind::Input => 4,
+      ErrorKind::Network => 5,
+      ErrorKind::Llm => 6,
+      ErrorKind::Validation => 7,
+      ErrorKind::Refusal => 8,
+      ErrorKind::Cancelled => 130,
+    };
+  }
+}
+
+/// Result type for application runtime operations.
+pub type RuntimeResult<T> = Result<T, RuntimeError>;
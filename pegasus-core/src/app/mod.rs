@@ -0,0 +1,1540 @@
+//! Application orchestration module for Pegasus.
+//!
+//! ## Main Components
+//!
+//! - [`App`]: The primary application orchestrator that manages all workflows
+//! - [`Refiner`]: Configuration-free refinement API for embedding
+//! - [`RuntimeError`]: Error types for application-level failures
+//! - [`RuntimeResult<T>`]: Result type alias for application operations
+
+pub mod errors;
+pub mod refiner;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::AsyncBufRead;
+use tokio_util::sync::CancellationToken;
+
+#[cfg(unix)]
+use crate::app::errors::ErrorKind;
+use crate::app::errors::{RuntimeError, RuntimeResult};
+use crate::app::refiner::Refiner;
+use crate::audio::AudioTranscriber;
+use crate::audio::errors::AudioError;
+use crate::audio::split::AudioSplitter;
+use crate::backend::ManagedBackend;
+use crate::config::Config;
+use crate::dictionary::spellcheck::Spellchecker;
+use crate::dictionary::{self, DictionaryEntry};
+use crate::files::operations;
+use crate::input::chunks::{ChunkReader, ChunkUnit};
+use crate::input::errors::InputError;
+use crate::input::range::InputRange;
+use crate::input::stream::{StreamUnit, UnitReader};
+use crate::input::transcript_format::TranscriptFormat;
+use crate::input::transcription::WhisperTranscription;
+use crate::input::{InputOptions, InputReader, validation};
+#[cfg(unix)]
+use crate::ipc::client::DaemonClient;
+#[cfg(unix)]
+use crate::ipc::errors::IpcError;
+#[cfg(unix)]
+use crate::ipc::protocol;
+use crate::llm::context::ConversationContext;
+use crate::llm::non_speech::NonSpeechFilter;
+use crate::llm::output_limit::OutputLimit;
+use crate::llm::tokenizer::Tokenizer;
+use crate::logging::request_id;
+use crate::network::HttpClient;
+use crate::network::circuit_breaker::CircuitBreaker;
+use crate::output::chapters;
+use crate::output::format::OutputFormat;
+use crate::output::html::SideBySide;
+use crate::output::readability::Readability;
+use crate::output::report::{ReportFormat, RunReport};
+use crate::output::sink::{self, OutputSink};
+use crate::secrets::{self, Secret};
+use crate::timing::{self, Phase};
+use crate::usage;
+use crate::{elog, logging, vlog};
+
+/// Main application orchestrator for Pegasus.
+///
+/// Coordinates text refinement operations using the provided configuration settings.
+pub struct App {
+  config: Config,
+  backend: Option<Arc<ManagedBackend>>,
+  input_encoding: Option<String>,
+  transcript_format: Option<TranscriptFormat>,
+  strict: bool,
+  with_summary: bool,
+  with_readability: bool,
+  report: Option<String>,
+  side_by_side: Option<String>,
+  range: Option<InputRange>,
+  cancellation: CancellationToken,
+}
+
+impl App {
+  /// Creates a new App instance with the given configuration.
+  ///
+  /// # Arguments
+  ///
+  /// * `config` - Configuration containing all application settings
+  ///
+  /// # Returns
+  ///
+  /// A new `App` instance.
+  pub fn new(config: Config) -> Self {
+    return App {
+      backend: App::create_backend(&config),
+      config,
+      input_encoding: None,
+      transcript_format: None,
+      strict: false,
+      with_summary: false,
+      with_readability: false,
+      report: None,
+      side_by_side: None,
+      range: None,
+      cancellation: CancellationToken::new(),
+    };
+  }
+
+  /// Sets the encoding of input files instead of detecting it.
+  ///
+  /// # Arguments
+  ///
+  /// * `encoding` - The encoding label (e.g. "windows-1252", "utf-16le")
+  ///
+  /// # Returns
+  ///
+  /// The `App` with the input encoding set.
+  pub fn with_input_encoding(mut self, encoding: Option<String>) -> Self {
+    self.input_encoding = encoding;
+    return self;
+  }
+
+  /// Sets the format of transcriptions instead of detecting it.
+  ///
+  /// # Arguments
+  ///
+  /// * `format` - The transcription format, or `None` to detect it
+  ///
+  /// # Returns
+  ///
+  /// The `App` with the transcription format set.
+  pub fn with_transcript_format(
+    mut self,
+    format: Option<TranscriptFormat>,
+  ) -> Self {
+    self.transcript_format = format;
+    return self;
+  }
+
+  /// Sets whether transcriptions are validated strictly before refinement.
+  ///
+  /// In strict mode, structural problems of a transcription (see
+  /// [`validation::check_transcription`]) are errors, and plain text is
+  /// rejected instead of being refined without timing data.
+  ///
+  /// # Arguments
+  ///
+  /// * `strict` - Whether to validate strictly
+  ///
+  /// # Returns
+  ///
+  /// The `App` with strict validation set.
+  pub fn with_strict(mut self, strict: bool) -> Self {
+    self.strict = strict;
+    return self;
+  }
+
+  /// Sets whether refinements are followed by a title and summary.
+  ///
+  /// The title and summary are generated with a second request and added
+  /// to JSON output, or prepended to text output as a Markdown header.
+  ///
+  /// # Arguments
+  ///
+  /// * `with_summary` - Whether to generate a title and summary
+  ///
+  /// # Returns
+  ///
+  /// The `App` with the summary setting applied.
+  pub fn with_summary(mut self, with_summary: bool) -> Self {
+    self.with_summary = with_summary;
+    return self;
+  }
+
+  /// Sets whether readability metrics of the output are reported.
+  ///
+  /// The metrics are added to JSON output, or printed to stderr after
+  /// text output.
+  ///
+  /// # Arguments
+  ///
+  /// * `with_readability` - Whether to report readability metrics
+  ///
+  /// # Returns
+  ///
+  /// The `App` with the readability setting applied.
+  pub fn with_readability(mut self, with_readability: bool) -> Self {
+    self.with_readability = with_readability;
+    return self;
+  }
+
+  /// Sets the path a run report is written to after each refinement.
+  ///
+  /// The report compares the input with the refined text and lists the
+  /// settings, phase durations and cost of the run (see [`RunReport`]);
+  /// its format follows the extension of the path. A report that cannot
+  /// be written is logged, and the refinement is output regardless.
+  ///
+  /// # Arguments
+  ///
+  /// * `report` - The report path, or `None` for no report
+  ///
+  /// # Returns
+  ///
+  /// The `App` with the report path set.
+  pub fn with_report(mut self, report: Option<String>) -> Self {
+    self.report = report;
+    return self;
+  }
+
+  /// Sets the path of an HTML page comparing the input with each
+  /// refinement side by side.
+  ///
+  /// Changed words are highlighted and low-confidence words of
+  /// transcriptions marked (see [`SideBySide`]). A page that cannot be
+  /// written is logged, and the refinement is output regardless.
+  ///
+  /// # Arguments
+  ///
+  /// * `side_by_side` - The page path, or `None` for no page
+  ///
+  /// # Returns
+  ///
+  /// The `App` with the page path set.
+  pub fn with_side_by_side(mut self, side_by_side: Option<String>) -> Self {
+    self.side_by_side = side_by_side;
+    return self;
+  }
+
+  /// Sets the range of plain text inputs to refine.
+  ///
+  /// The rest of the input is output as it was, around the refined range,
+  /// and the output settings (`[output] wrap`, ...) only apply to the
+  /// range. A summary covers the range too.
+  ///
+  /// # Arguments
+  ///
+  /// * `range` - The range, or `None` to refine the whole input
+  ///
+  /// # Returns
+  ///
+  /// The `App` with the range set.
+  pub fn with_range(mut self, range: Option<InputRange>) -> Self {
+    self.range = range;
+    return self;
+  }
+
+  /// Sets the token that cancels the application's runs.
+  ///
+  /// Cancelling it stops chunking, drops the requests in flight and skips
+  /// post-processing; runs then fail with `RuntimeError::Cancelled`,
+  /// which holds the text refined until then. Copies made with
+  /// [`App::with_config`] share the token.
+  ///
+  /// # Arguments
+  ///
+  /// * `cancellation` - The token, such as one cancelled on Ctrl-C
+  ///
+  /// # Returns
+  ///
+  /// The `App` with the cancellation token set.
+  pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+    self.cancellation = cancellation;
+    return self;
+  }
+
+  /// Creates a copy of the application with a different configuration.
+  ///
+  /// # Arguments
+  ///
+  /// * `config` - The new configuration
+  ///
+  /// # Returns
+  ///
+  /// A new `App` instance with the same input and output settings.
+  pub fn with_config(&self, config: Config) -> Self {
+    let backend = match &self.backend {
+      Some(backend)
+        if backend.command() == config.get_backend_command()
+          && backend.url() == config.get_llm_url() =>
+      {
+        Some(Arc::clone(backend))
+      }
+      _ => App::create_backend(&config),
+    };
+    return App {
+      config,
+      backend,
+      input_encoding: self.input_encoding.clone(),
+      transcript_format: self.transcript_format,
+      strict: self.strict,
+      with_summary: self.with_summary,
+      with_readability: self.with_readability,
+      report: self.report.clone(),
+      side_by_side: self.side_by_side.clone(),
+      range: self.range.clone(),
+      cancellation: self.cancellation.clone(),
+    };
+  }
+
+  /// Gets the configuration the application was created with.
+  ///
+  /// # Returns
+  ///
+  /// A reference to the `Config`.
+  pub fn config(&self) -> &Config {
+    return &self.config;
+  }
+
+  /// Gets the token that cancels the application's runs.
+  ///
+  /// # Returns
+  ///
+  /// A reference to the `CancellationToken`.
+  pub fn cancellation(&self) -> &CancellationToken {
+    return &self.cancellation;
+  }
+
+  /// Creates the LLM server to spawn on demand, if one is configured.
+  fn create_backend(config: &Config) -> Option<Arc<ManagedBackend>> {
+    let command = config.get_backend_command();
+    if command.is_empty() {
+      return None;
+    }
+    return Some(Arc::new(ManagedBackend::new(
+      command,
+      config.get_llm_url(),
+      Duration::from_secs(config.get_backend_ready_timeout_seconds()),
+    )));
+  }
+
+  /// Resolves the LLM API key from its configured source.
+  ///
+  /// The `PEGASUS_LLM_API_KEY` environment variable takes precedence over
+  /// every configured source.
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<String>` containing the API key (possibly empty) or an
+  /// error if the configured source could not provide one.
+  async fn resolve_api_key(&self) -> RuntimeResult<String> {
+    return secrets::resolve_api_key(
+      Secret::LlmApiKey,
+      self.config.get_llm_api_key_source(),
+      self.config.get_llm_api_key(),
+      &self.config.get_llm_api_key_command(),
+      &self.config.get_llm_api_key_file(),
+    )
+    .await
+    .map_err(|e| RuntimeError::Input(e.to_string()));
+  }
+
+  /// Resolves the speech-to-text API key from its configured source.
+  ///
+  /// The `PEGASUS_TRANSCRIPTION_API_KEY` environment variable takes
+  /// precedence over every configured source.
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<String>` containing the API key (possibly empty) or an
+  /// error if the configured source could not provide one.
+  async fn resolve_transcription_api_key(&self) -> RuntimeResult<String> {
+    return secrets::resolve_api_key(
+      Secret::TranscriptionApiKey,
+      self.config.get_transcription_api_key_source(),
+      self.config.get_transcription_api_key(),
+      &self.config.get_transcription_api_key_command(),
+      &self.config.get_transcription_api_key_file(),
+    )
+    .await
+    .map_err(|e| RuntimeError::Input(e.to_string()));
+  }
+
+  /// Creates a refiner configured with the current settings.
+  ///
+  /// Starts the configured LLM server first if it is not running yet.
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<Refiner>` containing the configured refiner or an
+  /// error.
+  pub async fn create_refiner(&self) -> RuntimeResult<Refiner> {
+    if let Some(backend) = &self.backend {
+      backend
+        .ensure_running()
+        .await
+        .map_err(|e| RuntimeError::Network(e.to_string()))?;
+    }
+
+    let dictionary_words = self.load_dictionary().await?;
+    let grammar = self.load_grammar().await?;
+    let spellchecker = self.load_spellchecker().await?;
+
+    let circuit_breaker = CircuitBreaker::new(
+      self.config.get_circuit_breaker_threshold(),
+      Duration::from_secs(self.config.get_circuit_breaker_window_seconds()),
+      Duration::from_secs(self.config.get_circuit_breaker_cooldown_seconds()),
+    );
+
+    return Refiner::builder()
+      .url(self.config.get_llm_url())
+      .model(self.config.get_llm_model())
+      .api_key(self.resolve_api_key().await?)
+      .fallback_url(self.config.get_llm_fallback_url())
+      .endpoints(self.config.get_llm_endpoints())
+      .balance(self.config.get_llm_balance())
+      .proxy(self.config.get_proxy(), self.config.get_no_proxy())
+      .circuit_breaker(circuit_breaker)
+      .request_id_header(self.config.get_request_id_header())
+      .retries(self.config.get_network_retries())
+      .dictionary(dictionary_words)
+      .dictionary_matching(self.config.get_dictionary_matching())
+      .spellchecker(spellchecker)
+      .glossary(self.config.get_dictionary_glossary())
+      .language(self.config.get_transcription_language())
+      .chunk_size(self.config.get_input_chunk_size())
+      .chunk_unit(self.config.get_input_chunk_unit())
+      .tokenizer(self.config.get_llm_tokenizer())
+      .context_window(self.config.get_llm_context_window())
+      .probe_capabilities(self.config.get_llm_probe_capabilities())
+      .chunk_overlap_sentences(self.config.get_input_chunk_overlap_sentences())
+      .probability_threshold(self.config.get_whisper_probability_threshold())
+      .deduplicate_segments(self.config.get_whisper_deduplicate_segments())
+      .target_reading_level(self.config.get_llm_target_reading_level())
+      .output_limit(OutputLimit {
+        max_characters: self.config.get_llm_max_output_characters(),
+        policy: self.config.get_llm_output_overflow(),
+      })
+      .stop_sequences(self.config.get_llm_stop())
+      .temperature(self.config.get_llm_temperature())
+      .api(self.config.get_llm_api())
+      .grammar(grammar)
+      .constrain_output(self.config.get_llm_constrain_output())
+      .logprob_threshold(self.config.get_whisper_logprob_threshold())
+      .keep_verbatim_tokens(self.config.get_llm_keep_verbatim_tokens())
+      .retry_answers(self.config.get_llm_retry_answers())
+      .refusal(self.config.get_llm_refusal())
+      .min_similarity(self.config.get_llm_min_similarity())
+      .embeddings_model(self.config.get_llm_embeddings_model())
+      .non_speech(NonSpeechFilter::new(
+        self.config.get_style_emoji(),
+        self.config.get_style_non_speech(),
+      ))
+      .cancellation(self.cancellation.clone())
+      .build();
+  }
+
+  /// Creates an empty conversation context with the configured limits.
+  ///
+  /// # Returns
+  ///
+  /// A new `ConversationContext`, which remembers nothing if conversation
+  /// context is disabled.
+  pub fn create_context(&self) -> ConversationContext {
+    return ConversationContext::new(
+      self.config.get_llm_context_paragraphs(),
+      self.config.get_llm_context_characters(),
+    );
+  }
+
+  /// Formats the refined text according to the specified output format.
+  ///
+  /// # Arguments
+  ///
+  /// * `refined_text` - The refined text to format
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<String>` containing the formatted output or an error.
+  pub fn format_output(
+    &self,
+    refined_text: String,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    return self.format_output_with(
+      refined_text,
+      format,
+      serde_json::Map::new(),
+    );
+  }
+
+  /// Formats the refined text, adding extra fields to JSON output.
+  ///
+  /// # Arguments
+  ///
+  /// * `refined_text` - The refined text to format
+  /// * `format` - The desired output format
+  /// * `fields` - Fields added next to `text` in JSON output
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<String>` containing the formatted output or an error.
+  pub fn format_output_with(
+    &self,
+    refined_text: String,
+    format: OutputFormat,
+    fields: serde_json::Map<String, serde_json::Value>,
+  ) -> RuntimeResult<String> {
+    let refined_text = self.post_process(&refined_text);
+    return self.format_processed(refined_text, format, fields);
+  }
+
+  /// Applies the configured punctuation style, wrapping and whitespace
+  /// policy to a refined text.
+  fn post_process(&self, refined_text: &str) -> String {
+    let _timer = timing::start(Phase::PostProcessing);
+    let refined_text = self
+      .config
+      .get_style_unicode_punctuation()
+      .apply(refined_text);
+    let refined_text = self.config.get_output_wrap().apply(&refined_text);
+    return self.config.get_output_whitespace().apply(&refined_text);
+  }
+
+  /// Formats a post-processed text, adding extra fields to JSON output.
+  fn format_processed(
+    &self,
+    refined_text: String,
+    format: OutputFormat,
+    fields: serde_json::Map<String, serde_json::Value>,
+  ) -> RuntimeResult<String> {
+    let readability = self
+      .with_readability
+      .then(|| Readability::measure(&refined_text));
+    return match format {
+      OutputFormat::Text => {
+        if let Some(readability) = readability {
+          elog!(logging::INFO, "{}", readability);
+        }
+        Ok(refined_text)
+      }
+      OutputFormat::Json => {
+        let mut json_output = serde_json::json!({ "text": refined_text });
+        for (key, value) in fields {
+          json_output[key] = value;
+        }
+        if let Some(id) = request_id::current() {
+          json_output["request_id"] = serde_json::Value::String(id);
+        }
+        if let Some(readability) = readability {
+          json_output["readability"] = to_json_value(readability)?;
+        }
+        if timing::is_enabled() {
+          json_output["timing"] = timing::to_json();
+        }
+        serde_json::to_string(&json_output).map_err(|e| {
+          RuntimeError::Refinement(format!("Failed to serialize JSON: {}", e))
+        })
+      }
+    };
+  }
+
+  /// Builds the input reading options from the configuration.
+  ///
+  /// # Returns
+  ///
+  /// The `InputOptions` to read input with.
+  fn input_options(&self) -> InputOptions {
+    return InputOptions {
+      max_size: self.config.get_input_max_size(),
+      encoding: self.input_encoding.clone(),
+    };
+  }
+
+  /// Opens the input as a stream of chunks of the configured size.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input
+  /// * `file_path` - The file path for input text
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<ChunkReader>` containing the chunk reader or an error.
+  pub async fn open_chunks(
+    &self,
+    input: Option<String>,
+    file_path: Option<String>,
+  ) -> RuntimeResult<ChunkReader> {
+    let chunk_size = self.config.get_input_chunk_size();
+    let chunks = InputReader::open_chunks(
+      input,
+      file_path,
+      chunk_size,
+      &self.input_options(),
+    )
+    .await
+    .map_err(|e| RuntimeError::Input(e.to_string()))?;
+
+    let tokenizer = (self.config.get_input_chunk_unit() == ChunkUnit::Tokens)
+      .then(|| {
+        Tokenizer::resolve(
+          &self.config.get_llm_tokenizer(),
+          &self.config.get_llm_model(),
+        )
+      });
+    return Ok(chunks.with_tokenizer(tokenizer));
+  }
+
+  /// Refines the input text using the LLM.
+  ///
+  /// The input is streamed in chunks of the configured size, each refined
+  /// with its own request, so arbitrarily long inputs stay within the
+  /// model's context and memory use stays bounded. With `[dictionary]
+  /// spellcheck` set or a report or side-by-side page requested, the input
+  /// is read at once instead, to compare the refined text with. With a
+  /// range set, only the range is refined and spliced back into the rest
+  /// of the input, which is output unchanged.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input
+  /// * `file_path` - The file path for input text
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if refinement fails.
+  pub async fn refine_text(
+    &self,
+    input: Option<String>,
+    file_path: Option<String>,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    let input_text = if self.config.get_dictionary_spellcheck().is_empty()
+      && self.report.is_none()
+      && self.side_by_side.is_none()
+      && self.range.is_none()
+    {
+      None
+    } else {
+      Some(
+        InputReader::read_input(
+          input.clone(),
+          file_path.clone(),
+          &self.input_options(),
+        )
+        .await
+        .map_err(|e| RuntimeError::Input(e.to_string()))?,
+      )
+    };
+    let splice = match (&self.range, &input_text) {
+      (Some(range), Some(input_text)) => Some(
+        range
+          .split(input_text)
+          .map_err(|e| RuntimeError::Input(e.to_string()))?,
+      ),
+      _ => None,
+    };
+    if let (Some(range), Some(splice)) = (&self.range, &splice) {
+      vlog!("Refining {} ({} bytes)", range, splice.slice.len());
+    }
+    let original = splice.map(|splice| splice.slice).or(input_text.as_deref());
+    let mut chunks = match original {
+      Some(original) => {
+        self.open_chunks(Some(original.to_string()), None).await?
+      }
+      None => self.open_chunks(input, file_path).await?,
+    };
+
+    let refiner = self.create_refiner().await?;
+    let refined_text = refiner.refine_chunks(&mut chunks).await?;
+
+    let mut fields = serde_json::Map::new();
+    if let Some(original) = original {
+      self
+        .add_spellcheck(
+          Some(refiner.dictionary()),
+          original,
+          &refined_text,
+          format,
+          &mut fields,
+        )
+        .await?;
+    }
+    let original = original.unwrap_or_default();
+    let report = self
+      .report
+      .is_some()
+      .then(|| RunReport::new(original, &refined_text));
+    let page = self
+      .side_by_side
+      .is_some()
+      .then(|| SideBySide::from_text(original, &refined_text));
+    let refined_text = self
+      .add_summary(&refiner, refined_text, format, &mut fields)
+      .await?;
+    let output = match splice {
+      Some(splice) => {
+        let refined_text = splice.join(&self.post_process(&refined_text));
+        self.format_processed(refined_text, format, fields)?
+      }
+      None => self.format_output_with(refined_text, format, fields)?,
+    };
+    self.write_report(report).await;
+    self.write_side_by_side(page).await;
+    return Ok(output);
+  }
+
+  /// Refines each unit of a stream on its own as soon as it is read.
+  ///
+  /// Each result is formatted (one JSON object per unit with
+  /// `--output-json`) and written to the sinks before the next unit is
+  /// read. A unit that fails to refine or deliver is logged and skipped.
+  /// With `[llm] context_paragraphs` set, earlier units are sent as
+  /// context.
+  ///
+  /// # Arguments
+  ///
+  /// * `reader` - The stream, usually standard input
+  /// * `unit` - What makes up one unit
+  /// * `format` - The desired output format
+  /// * `sinks` - Where each result is written
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<usize>` with the number of units that failed, or an
+  /// error if the stream cannot be read, no refiner can be created or the
+  /// run is cancelled.
+  pub async fn refine_stream(
+    &self,
+    reader: impl AsyncBufRead + Unpin,
+    unit: StreamUnit,
+    format: OutputFormat,
+    sinks: &[Box<dyn OutputSink>],
+  ) -> RuntimeResult<usize> {
+    let refiner = self.create_refiner().await?;
+    let mut context = self.create_context();
+    let mut units = UnitReader::new(reader, unit);
+    let mut failed = 0;
+
+    vlog!("Refining standard input {} by {}", unit, unit);
+    loop {
+      let Some(next) = self
+        .cancellation
+        .run_until_cancelled(units.next_unit())
+        .await
+      else {
+        return Err(RuntimeError::Cancelled(String::new()));
+      };
+      let Some(text) = next.map_err(|e| RuntimeError::Input(e.to_string()))?
+      else {
+        break;
+      };
+      let unit = self.refine_unit(&refiner, &mut context, text, format, sinks);
+      let unit = request_id::scope(request_id::generate(), unit);
+      if !usage::track(&self.config, "line-mode", unit).await {
+        failed += 1;
+      }
+    }
+    return Ok(failed);
+  }
+
+  /// Refines one unit of a stream and writes it to the sinks, logging any
+  /// failure with the request ID.
+  ///
+  /// # Returns
+  ///
+  /// `true` if the unit was refined and written.
+  async fn refine_unit(
+    &self,
+    refiner: &Refiner,
+    context: &mut ConversationContext,
+    text: String,
+    format: OutputFormat,
+    sinks: &[Box<dyn OutputSink>],
+  ) -> bool {
+    let result: Result<(), String> = async {
+      let refined = refiner
+        .refine_in_context(&text, context)
+        .await
+        .map_err(|e| e.to_string())?;
+      context.push(text, refined.clone());
+
+      let output = self
+        .format_output(refined, format)
+        .map_err(|e| e.to_string())?;
+      return sink::write_all(sinks, &output)
+        .await
+        .map_err(|e| e.to_string());
+    }
+    .await;
+    if let Err(e) = &result {
+      elog!(logging::ERROR, "{}", e);
+    }
+    return result.is_ok();
+  }
+
+  /// Reads and parses a transcription.
+  ///
+  /// The transcription may be Whisper JSON, whisper.cpp JSON, SRT, WebVTT
+  /// or plain text; its format is detected unless one was set with
+  /// [`App::with_transcript_format`].
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input of the transcription
+  /// * `file_path` - The file path to the transcription file
+  ///
+  /// # Returns
+  ///
+  /// The parsed transcription, or an error if it cannot be read or parsed.
+  pub async fn read_whisper_transcription(
+    &self,
+    input: Option<String>,
+    file_path: Option<String>,
+  ) -> RuntimeResult<WhisperTranscription> {
+    let (input_text, format) = self.read_transcript(input, file_path).await?;
+    return self.parse_transcript(&input_text, format);
+  }
+
+  /// Refines a Whisper JSON transcription using confidence scores.
+  ///
+  /// Parses the Whisper JSON, identifies low-confidence words,
+  /// and sends the transcription to the LLM for refinement with
+  /// confidence awareness to reduce hallucination. Plain text, which has
+  /// no timing or confidence data, is refined like `--input` instead.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input of the Whisper JSON
+  /// * `file_path` - The file path to the Whisper JSON file
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if refinement fails.
+  pub async fn refine_whisper_transcription(
+    &self,
+    input: Option<String>,
+    file_path: Option<String>,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    let (input_text, transcript_format) =
+      self.read_transcript(input, file_path).await?;
+    if transcript_format == TranscriptFormat::Text {
+      self.accept_plain_text()?;
+      return self.refine_text(Some(input_text), None, format).await;
+    }
+
+    let transcription =
+      self.parse_transcript(&input_text, transcript_format)?;
+    return self.refine_transcription(transcription, format).await;
+  }
+
+  /// Reads a transcription and determines its format.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input of the transcription
+  /// * `file_path` - The file path to the transcription file
+  ///
+  /// # Returns
+  ///
+  /// The transcription and its format, or an error if it cannot be read.
+  async fn read_transcript(
+    &self,
+    input: Option<String>,
+    file_path: Option<String>,
+  ) -> RuntimeResult<(String, TranscriptFormat)> {
+    let input_text =
+      InputReader::read_input(input, file_path, &self.input_options())
+        .await
+        .map_err(|e| RuntimeError::Input(e.to_string()))?;
+
+    let format = self
+      .transcript_format
+      .unwrap_or_else(|| TranscriptFormat::detect(&input_text));
+    vlog!("Reading the transcription as {}", format);
+    return Ok((input_text, format));
+  }
+
+  /// Parses a transcription in the given format.
+  ///
+  /// # Arguments
+  ///
+  /// * `input_text` - The transcription
+  /// * `format` - Its format
+  ///
+  /// # Returns
+  ///
+  /// The parsed transcription, or an error if it is malformed.
+  fn parse_transcript(
+    &self,
+    input_text: &str,
+    format: TranscriptFormat,
+  ) -> RuntimeResult<WhisperTranscription> {
+    let transcription = format
+      .parse(input_text)
+      .map_err(|e| RuntimeError::Input(e.to_string()))?;
+    if self.strict {
+      validation::check_transcription(&transcription)
+        .map_err(|e| RuntimeError::Input(e.to_string()))?;
+    }
+
+    let segment_count = transcription.segments.as_ref().map_or(0, |s| s.len());
+    vlog!(
+      "Loaded Whisper transcription: {} segments, {} words, duration: {:.1}s",
+      segment_count,
+      transcription.word_count(),
+      transcription.duration_or_default()
+    );
+
+    return Ok(transcription);
+  }
+
+  /// Tells the user that a transcription turned out to be plain text,
+  /// unless they said so with `--transcript-format text`.
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<()>` that fails in strict mode, where plain text is
+  /// not accepted as a transcription.
+  fn accept_plain_text(&self) -> RuntimeResult<()> {
+    if self.transcript_format.is_some() {
+      return Ok(());
+    }
+    if self.strict {
+      let problem =
+        String::from("the input is plain text, not a transcription");
+      return Err(RuntimeError::Input(
+        InputError::InvalidTranscript(vec![problem]).to_string(),
+      ));
+    }
+    elog!(
+      logging::WARNING,
+      "Input is plain text, not a transcription; refining it without \
+       timing or confidence data"
+    );
+    return Ok(());
+  }
+
+  /// Transcribes a recording and refines the transcription.
+  ///
+  /// The recording is sent to the configured speech-to-text service, and
+  /// the result is refined like a Whisper JSON transcription.
+  ///
+  /// # Arguments
+  ///
+  /// * `file_path` - The file path to the recording
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if transcription or refinement fails.
+  pub async fn transcribe_audio(
+    &self,
+    file_path: String,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    let transcriber = self.create_transcriber().await?;
+    let transcription = self
+      .cancellation
+      .run_until_cancelled(transcriber.transcribe(Path::new(&file_path)))
+      .await
+      .ok_or_else(|| RuntimeError::Cancelled(String::new()))?
+      .map_err(|e| match e {
+        AudioError::Read { .. } | AudioError::Split(_) => {
+          RuntimeError::Input(e.to_string())
+        }
+        AudioError::Network(_) => RuntimeError::Network(e.to_string()),
+      })?;
+
+    vlog!(
+      "Transcribed {}: {} words, duration: {:.1}s",
+      file_path,
+      transcription.word_count(),
+      transcription.duration_or_default()
+    );
+    return self.refine_transcription(transcription, format).await;
+  }
+
+  /// Creates a client for the configured speech-to-text service.
+  async fn create_transcriber(&self) -> RuntimeResult<AudioTranscriber> {
+    let mut http_client = HttpClient::new(self.config.get_transcription_url())
+      .map_err(|e| RuntimeError::Validation(e.to_string()))?
+      .with_request_id_header(self.config.get_request_id_header());
+    let proxy = self.config.get_proxy();
+    if !proxy.is_empty() {
+      http_client = http_client
+        .with_proxy(&proxy, &self.config.get_no_proxy())
+        .map_err(|e| RuntimeError::Network(e.to_string()))?;
+    }
+
+    return Ok(
+      AudioTranscriber::new(
+        http_client,
+        self.config.get_transcription_provider(),
+      )
+      .with_model(self.config.get_transcription_model())
+      .with_language(self.config.get_transcription_language())
+      .with_api_key(self.resolve_transcription_api_key().await?)
+      .with_splitting(
+        AudioSplitter::new(
+          self.config.get_transcription_silence_command(),
+          self.config.get_transcription_chunk_seconds(),
+        ),
+        self.config.get_transcription_max_upload_size(),
+        self.config.get_transcription_concurrency(),
+      ),
+    );
+  }
+
+  /// Refines a parsed transcription and formats the result.
+  async fn refine_transcription(
+    &self,
+    transcription: WhisperTranscription,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    let refiner = self.create_refiner().await?;
+    let refined_text = refiner.refine_whisper(&transcription).await?;
+
+    let mut fields = self.whisper_fields(&transcription, format)?;
+    let original = transcription.full_text();
+    self
+      .add_spellcheck(
+        Some(refiner.dictionary()),
+        &original,
+        &refined_text,
+        format,
+        &mut fields,
+      )
+      .await?;
+    let threshold = self.config.get_whisper_probability_threshold();
+    let report = self.report.is_some().then(|| {
+      RunReport::new(&original, &refined_text)
+        .with_transcription(&transcription, threshold)
+    });
+    let page = self.side_by_side.is_some().then(|| {
+      SideBySide::from_transcription(&transcription, &refined_text, threshold)
+    });
+    let refined_text = self
+      .add_summary(&refiner, refined_text, format, &mut fields)
+      .await?;
+    let output = self.format_output_with(refined_text, format, fields)?;
+    self.write_report(report).await;
+    self.write_side_by_side(page).await;
+    return Ok(output);
+  }
+
+  /// Splits a Whisper JSON transcription into refined, titled chapters.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input of the Whisper JSON
+  /// * `file_path` - The file path to the Whisper JSON file
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// The chapters as Markdown, or as JSON with the Markdown in `text` and
+  /// a `chapters` list, or an error if refinement fails.
+  pub async fn chapter_whisper_transcription(
+    &self,
+    input: Option<String>,
+    file_path: Option<String>,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    let transcription =
+      self.read_whisper_transcription(input, file_path).await?;
+
+    let refiner = self.create_refiner().await?;
+    let chapters = refiner.chapters(&transcription).await?;
+
+    let markdown = chapters::to_markdown(&chapters);
+    let mut fields = serde_json::Map::new();
+    let original = transcription.full_text();
+    let refined_text = chapters
+      .iter()
+      .map(|chapter| chapter.text.as_str())
+      .collect::<Vec<&str>>()
+      .join("\n\n");
+    self
+      .add_spellcheck(
+        Some(refiner.dictionary()),
+        &original,
+        &refined_text,
+        format,
+        &mut fields,
+      )
+      .await?;
+    fields.insert(String::from("chapters"), to_json_value(&chapters)?);
+    let output = self.format_output_with(markdown, format, fields)?;
+    let threshold = self.config.get_whisper_probability_threshold();
+    let report = self.report.is_some().then(|| {
+      RunReport::new(&original, &refined_text)
+        .with_transcription(&transcription, threshold)
+    });
+    let page = self.side_by_side.is_some().then(|| {
+      SideBySide::from_transcription(&transcription, &refined_text, threshold)
+    });
+    self.write_report(report).await;
+    self.write_side_by_side(page).await;
+    return Ok(output);
+  }
+
+  /// Probes the LLM endpoint for its context window and features.
+  ///
+  /// # Arguments
+  ///
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// The detected capabilities as a table or JSON object, or an error if
+  /// the endpoint could not be probed.
+  pub async fn probe_capabilities(
+    &self,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    let refiner = self.create_refiner().await?;
+    let capabilities = refiner.probe_capabilities().await.ok_or_else(|| {
+      RuntimeError::Network(format!(
+        "Could not detect the capabilities of {}",
+        self.config.get_llm_url()
+      ))
+    })?;
+    return match format {
+      OutputFormat::Text => Ok(capabilities.to_string()),
+      OutputFormat::Json => Ok(to_json_value(&capabilities)?.to_string()),
+    };
+  }
+
+  /// Refines the input text on a running daemon.
+  ///
+  /// The input is read locally, so relative paths and the `--encoding`
+  /// override behave as without a daemon.
+  ///
+  /// # Arguments
+  ///
+  /// * `client` - The daemon connection
+  /// * `input` - The inline text input
+  /// * `file_path` - The file path for input text
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if refinement fails.
+  #[cfg(unix)]
+  pub async fn refine_text_on_daemon(
+    &self,
+    client: &mut DaemonClient,
+    input: Option<String>,
+    file_path: Option<String>,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    let input_text =
+      InputReader::read_input(input, file_path, &self.input_options())
+        .await
+        .map_err(|e| RuntimeError::Input(e.to_string()))?;
+
+    let timer = timing::start(Phase::Network);
+    let refined_text = client
+      .refine(input_text.clone())
+      .await
+      .map_err(daemon_error)?;
+    drop(timer);
+
+    let mut fields = serde_json::Map::new();
+    self
+      .add_spellcheck(None, &input_text, &refined_text, format, &mut fields)
+      .await?;
+    return self.format_output_with(refined_text, format, fields);
+  }
+
+  /// Refines a Whisper JSON transcription on a running daemon.
+  ///
+  /// # Arguments
+  ///
+  /// * `client` - The daemon connection
+  /// * `input` - The inline text input of the Whisper JSON
+  /// * `file_path` - The file path to the Whisper JSON file
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if refinement fails.
+  #[cfg(unix)]
+  pub async fn refine_whisper_on_daemon(
+    &self,
+    client: &mut DaemonClient,
+    input: Option<String>,
+    file_path: Option<String>,
+    format: OutputFormat,
+  ) -> RuntimeResult<String> {
+    let (input_text, transcript_format) =
+      self.read_transcript(input, file_path).await?;
+    if transcript_format == TranscriptFormat::Text {
+      self.accept_plain_text()?;
+      let timer = timing::start(Phase::Network);
+      let refined_text = client
+        .refine(input_text.clone())
+        .await
+        .map_err(daemon_error)?;
+      drop(timer);
+      let mut fields = serde_json::Map::new();
+      self
+        .add_spellcheck(None, &input_text, &refined_text, format, &mut fields)
+        .await?;
+      return self.format_output_with(refined_text, format, fields);
+    }
+
+    let parsed = self.parse_transcript(&input_text, transcript_format)?;
+    let transcription = to_json_value(&parsed)?;
+    let mut fields = self.whisper_fields(&parsed, format)?;
+
+    let timer = timing::start(Phase::Network);
+    let refined_text = client
+      .refine_whisper(transcription)
+      .await
+      .map_err(daemon_error)?;
+    drop(timer);
+    self
+      .add_spellcheck(
+        None,
+        &parsed.full_text(),
+        &refined_text,
+        format,
+        &mut fields,
+      )
+      .await?;
+
+    return self.format_output_with(refined_text, format, fields);
+  }
+
+  /// Generates the title and summary of a refined text, if enabled.
+  ///
+  /// # Arguments
+  ///
+  /// * `refiner` - The refiner that refined the text
+  /// * `refined_text` - The refined text
+  /// * `format` - The desired output format
+  /// * `fields` - The extra JSON output fields, which receive the summary
+  ///
+  /// # Returns
+  ///
+  /// The text to output: for text output with a summary, the refined text
+  /// under a Markdown header; otherwise the refined text unchanged.
+  async fn add_summary(
+    &self,
+    refiner: &Refiner,
+    refined_text: String,
+    format: OutputFormat,
+    fields: &mut serde_json::Map<String, serde_json::Value>,
+  ) -> RuntimeResult<String> {
+    if !self.with_summary {
+      return Ok(refined_text);
+    }
+
+    let summary = refiner.summarize(&refined_text).await?;
+    return match format {
+      OutputFormat::Text => Ok(summary.to_markdown(&refined_text)),
+      OutputFormat::Json => {
+        fields.insert(String::from("title"), summary.title.into());
+        fields.insert(String::from("summary"), summary.summary.into());
+        Ok(refined_text)
+      }
+    };
+  }
+
+  /// Completes and writes the run report, if one was requested with
+  /// [`App::with_report`].
+  ///
+  /// The timings and cost are taken when the report is written, so they
+  /// cover the summary request and post-processing too.
+  ///
+  /// # Arguments
+  ///
+  /// * `report` - The comparison of the input with the refined text,
+  ///   built only if a report was requested
+  async fn write_report(&self, report: Option<RunReport>) {
+    let (Some(path), Some(report)) = (&self.report, report) else {
+      return;
+    };
+    let Some(report_format) = ReportFormat::from_path(path) else {
+      elog!(
+        logging::ERROR,
+        "Cannot write report '{}': use a .html, .md or .json file",
+        path
+      );
+      return;
+    };
+
+    let mut settings = vec![
+      ("llm.url", self.config.get_llm_url()),
+      ("llm.model", self.config.get_llm_model()),
+      ("llm.api", self.config.get_llm_api().to_string()),
+      (
+        "llm.temperature",
+        self
+          .config
+          .get_llm_temperature()
+          .map_or_else(|| String::from("service default"), |t| t.to_string()),
+      ),
+      (
+        "input.chunk_size",
+        format!(
+          "{} {}",
+          self.config.get_input_chunk_size(),
+          match self.config.get_input_chunk_unit() {
+            ChunkUnit::Characters => "characters",
+            ChunkUnit::Tokens => "tokens",
+          }
+        ),
+      ),
+    ];
+    let dictionary_path = self.config.get_custom_dictionary_path();
+    if !dictionary_path.is_empty() {
+      settings.push(("dictionary.path", dictionary_path));
+    }
+    if report.input.segments.is_some() {
+      settings.push((
+        "whisper.probability_threshold",
+        self.config.get_whisper_probability_threshold().to_string(),
+      ));
+    }
+    settings.push(("usage.profile", self.config.get_usage_profile()));
+
+    let report = report
+      .with_settings(settings)
+      .with_timings(timing::phases())
+      .with_cost(usage::current(&self.config));
+    match operations::write_string_atomic(path, &report.render(report_format))
+      .await
+    {
+      Ok(()) => vlog!("Wrote run report to {}", path),
+      Err(e) => elog!(logging::ERROR, "{}", e),
+    }
+  }
+
+  /// Writes the side-by-side page, if one was requested with
+  /// [`App::with_side_by_side`].
+  ///
+  /// # Arguments
+  ///
+  /// * `page` - The comparison of the input with the refined text, built
+  ///   only if a page was requested
+  async fn write_side_by_side(&self, page: Option<SideBySide>) {
+    let (Some(path), Some(page)) = (&self.side_by_side, page) else {
+      return;
+    };
+    match operations::write_string_atomic(path, &page.to_html()).await {
+      Ok(()) => vlog!("Wrote side-by-side comparison to {}", path),
+      Err(e) => elog!(logging::ERROR, "{}", e),
+    }
+  }
+
+  /// Reports the refined words no dictionary knows, if `[dictionary]
+  /// spellcheck` is set.
+  ///
+  /// The words are listed in JSON output and logged as warnings otherwise.
+  ///
+  /// # Arguments
+  ///
+  /// * `dictionary` - The custom dictionary entries, or `None` to load them
+  /// * `original` - The input text
+  /// * `refined_text` - The refined text
+  /// * `format` - The desired output format
+  /// * `fields` - The JSON output fields to list the words in
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<()>` indicating success, or an error if a dictionary
+  /// cannot be read.
+  async fn add_spellcheck(
+    &self,
+    dictionary: Option<&[DictionaryEntry]>,
+    original: &str,
+    refined_text: &str,
+    format: OutputFormat,
+    fields: &mut serde_json::Map<String, serde_json::Value>,
+  ) -> RuntimeResult<()> {
+    let Some(spellchecker) = self.load_spellchecker().await? else {
+      return Ok(());
+    };
+    let suspects = match dictionary {
+      Some(dictionary) => {
+        spellchecker.check(original, refined_text, dictionary)
+      }
+      None => spellchecker.check(
+        original,
+        refined_text,
+        &self.load_dictionary().await?,
+      ),
+    };
+    if format == OutputFormat::Text {
+      for suspect in &suspects {
+        elog!(
+          logging::WARNING,
+          "Possible hallucination: '{}' is neither in a dictionary nor in \
+           the input",
+          suspect.word
+        );
+      }
+      return Ok(());
+    }
+    fields.insert(
+      String::from("possible_hallucinations"),
+      to_json_value(suspects)?,
+    );
+    return Ok(());
+  }
+
+  /// Builds the extra JSON output fields of a Whisper refinement.
+  ///
+  /// Silent gaps and overlaps between segments are listed in JSON output
+  /// and logged as warnings otherwise.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The refined transcription
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// The fields with the confidence summary and the timeline issues of the
+  /// transcription, empty for text output.
+  fn whisper_fields(
+    &self,
+    transcription: &WhisperTranscription,
+    format: OutputFormat,
+  ) -> RuntimeResult<serde_json::Map<String, serde_json::Value>> {
+    let issues = transcription
+      .timeline_issues(self.config.get_whisper_gap_threshold_seconds());
+    let mut fields = serde_json::Map::new();
+
+    if format == OutputFormat::Text {
+      for issue in &issues {
+        elog!(logging::WARNING, "{}", issue);
+      }
+      return Ok(fields);
+    }
+
+    let summary = transcription
+      .confidence_summary(self.config.get_whisper_probability_threshold());
+    fields.insert(String::from("confidence"), to_json_value(summary)?);
+    fields.insert(String::from("timeline_issues"), to_json_value(issues)?);
+    return Ok(fields);
+  }
+
+  /// Loads dictionary words from the configured dictionary file.
+  ///
+  /// Reads the dictionary file and returns a list of words, one per line
+  /// (see [`dictionary::parse`]).
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<Vec<DictionaryEntry>>` containing the dictionary
+  /// entries or an error.
+  async fn load_dictionary(&self) -> RuntimeResult<Vec<DictionaryEntry>> {
+    let _timer = timing::start(Phase::Dictionary);
+    let dictionary_path = self.config.get_custom_dictionary_path();
+
+    if dictionary_path.is_empty() {
+      vlog!("No custom dictionary configured");
+      return Ok(Vec::new());
+    }
+
+    vlog!("Loading dictionary from: {}", dictionary_path);
+
+    let regex = self.config.get_dictionary_matching().regex;
+    let words = dictionary::load(&dictionary_path, regex)
+      .await
+      .map_err(|e| RuntimeError::Input(e.to_string()))?;
+
+    vlog!("Loaded {} dictionary words", words.len());
+
+    return Ok(words);
+  }
+
+  /// Loads the Hunspell dictionary of `[dictionary] spellcheck`.
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<Option<Arc<Spellchecker>>>` containing the
+  /// spellchecker, `None` if none is configured, or an error if a file
+  /// cannot be read.
+  async fn load_spellchecker(
+    &self,
+  ) -> RuntimeResult<Option<Arc<Spellchecker>>> {
+    let path = self.config.get_dictionary_spellcheck();
+    if path.is_empty() {
+      return Ok(None);
+    }
+
+    let spellchecker = Spellchecker::load(&path)
+      .await
+      .map_err(|e| RuntimeError::Input(e.to_string()))?;
+    return Ok(Some(Arc::new(spellchecker)));
+  }
+
+  /// Loads the GBNF grammar from the configured file.
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<String>` containing the grammar, empty if none is
+  /// configured, or an error if the file cannot be read.
+  async fn load_grammar(&self) -> RuntimeResult<String> {
+    let grammar_path = self.config.get_llm_grammar_file();
+    if grammar_path.is_empty() {
+      return Ok(String::new());
+    }
+
+    vlog!("Loading grammar from: {}", grammar_path);
+    return operations::read_to_string(&grammar_path)
+      .await
+      .map_err(|e| {
+        RuntimeError::Input(format!("Failed to read grammar: {}", e))
+      });
+  }
+}
+
+/// Serializes an output field.
+fn to_json_value(
+  value: impl serde::Serialize,
+) -> RuntimeResult<serde_json::Value> {
+  return serde_json::to_value(value).map_err(|e| {
+    RuntimeError::Refinement(format!("Failed to serialize JSON: {}", e))
+  });
+}
+
+/// Maps a daemon client error to a runtime error.
+///
+/// The code of an error response tells which kind of error it was.
+#[cfg(unix)]
+fn daemon_error(error: IpcError) -> RuntimeError {
+  let (code, message) = match error {
+    IpcError::Remote { code, message } => (code, message),
+    IpcError::Cancelled => return RuntimeError::Cancelled(String::new()),
+    error => return RuntimeError::Network(error.to_string()),
+  };
+
+  return match protocol::error_kind(code) {
+    Some(ErrorKind::Input) => RuntimeError::Input(message),
+    Some(ErrorKind::Network) => RuntimeError::Network(message),
+    Some(ErrorKind::Llm) => RuntimeError::Refinement(message),
+    Some(ErrorKind::Validation) => RuntimeError::Validation(message),
+    Some(ErrorKind::Refusal) => RuntimeError::Refusal(message),
+    Some(ErrorKind::Cancelled) => RuntimeError::Cancelled(String::new()),
+    _ => RuntimeError::Daemon(message),
+  };
+}
@@ -0,0 +1,1324 @@
+//! Programmatic refinement API.
+//!
+//! [`Refiner`] bundles an LLM client with the dictionary and refinement
+//! settings, so applications embedding Pegasus can refine text without
+//! going through configuration files:
+//!
+//! ```no_run
+//! use pegasus_core::Refiner;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let refined = Refiner::builder()
+//!   .url("http://127.0.0.1:8080")
+//!   .model("qwen2.5")
+//!   .dictionary(vec![String::from("Pegasus")])
+//!   .build()?
+//!   .refine("so um pegasus is a tool that fixes transcripts")
+//!   .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::app::errors::{RuntimeError, RuntimeResult};
+use crate::config::Config;
+use crate::dictionary::glossary::{Glossary, GlossaryMode};
+use crate::dictionary::spellcheck::Spellchecker;
+use crate::dictionary::{self, DictionaryEntry, MatchOptions};
+use crate::input::chunks::{ChunkReader, ChunkUnit};
+use crate::input::errors::InputError;
+use crate::input::transcription::WhisperTranscription;
+use crate::llm::capabilities::Capabilities;
+use crate::llm::client::{LLMApi, LLMClient};
+use crate::llm::context::{self, ConversationContext};
+use crate::llm::errors::LLMError;
+use crate::llm::non_speech::NonSpeechFilter;
+use crate::llm::output_limit::OutputLimit;
+use crate::llm::refusal::RefusalHandling;
+use crate::llm::tokenizer::Tokenizer;
+use crate::network::HttpClient;
+use crate::network::circuit_breaker::CircuitBreaker;
+use crate::network::errors::NetworkError;
+use crate::network::scheduler::BalanceStrategy;
+use crate::output::chapters::{self, Chapter};
+use crate::output::summary::Summary;
+use crate::timing::{self, Phase};
+use crate::{slog, vlog};
+
+/// Refines transcripts with an LLM.
+pub struct Refiner {
+  llm: LLMClient,
+  dictionary: Vec<DictionaryEntry>,
+  dictionary_matching: MatchOptions,
+  glossary: GlossaryMode,
+  language: String,
+  chunk_size: usize,
+  chunk_tokenizer: Option<Tokenizer>,
+  chunk_overlap_sentences: usize,
+  probability_threshold: f64,
+  deduplicate_segments: bool,
+  cancellation: CancellationToken,
+}
+
+impl Refiner {
+  /// Creates a builder with the default settings.
+  ///
+  /// # Returns
+  ///
+  /// A new `RefinerBuilder` instance.
+  pub fn builder() -> RefinerBuilder {
+    return RefinerBuilder::default();
+  }
+
+  /// Gets the dictionary entries the LLM should prefer.
+  ///
+  /// # Returns
+  ///
+  /// The dictionary entries.
+  pub fn dictionary(&self) -> &[DictionaryEntry] {
+    return &self.dictionary;
+  }
+
+  /// Adds a word to the dictionary for subsequent refinements.
+  ///
+  /// # Arguments
+  ///
+  /// * `word` - The dictionary word, with the markers of a dictionary file
+  ///   line like `Kubernetes!`, or a regex entry if they are enabled
+  ///
+  /// # Returns
+  ///
+  /// `true` if the word was added, `false` if it was already present.
+  pub fn add_dictionary_word(&mut self, word: String) -> bool {
+    let entry = dictionary::parse_line(&word, self.dictionary_matching.regex);
+    if self
+      .dictionary
+      .iter()
+      .any(|existing| existing.term == entry.term)
+    {
+      return false;
+    }
+    self.dictionary.push(entry);
+    return true;
+  }
+
+  /// Removes a word from the dictionary for subsequent refinements.
+  ///
+  /// # Arguments
+  ///
+  /// * `word` - The dictionary word
+  ///
+  /// # Returns
+  ///
+  /// `true` if the word was removed, `false` if it was not present.
+  pub fn remove_dictionary_word(&mut self, word: &str) -> bool {
+    let length = self.dictionary.len();
+    self.dictionary.retain(|existing| existing.term != word);
+    return self.dictionary.len() != length;
+  }
+
+  /// Refines text.
+  ///
+  /// Long texts are split into paragraph-aligned chunks of the configured
+  /// size, each refined with its own request. The last sentences of each
+  /// refined chunk are sent with the next one as context only.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The transcript text to refine
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if refinement fails.
+  pub async fn refine(&self, text: &str) -> RuntimeResult<String> {
+    let mut chunks = self.chunk_reader(text);
+    return self.refine_chunks(&mut chunks).await;
+  }
+
+  /// Refines text with earlier refinements of the session as context.
+  ///
+  /// The context is not updated; push the result to it once accepted.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The transcript text to refine
+  /// * `context` - The earlier refinements
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if refinement fails.
+  pub async fn refine_in_context(
+    &self,
+    text: &str,
+    context: &ConversationContext,
+  ) -> RuntimeResult<String> {
+    let mut chunks = self.chunk_reader(text);
+    return self
+      .refine_chunks_in_context(
+        &mut chunks,
+        context,
+        &self.cancellation,
+        |_| async {},
+      )
+      .await;
+  }
+
+  /// Creates a chunk reader over text with the configured chunk size.
+  fn chunk_reader(&self, text: &str) -> ChunkReader {
+    return ChunkReader::from_text(text.to_string(), self.chunk_size)
+      .with_tokenizer(self.chunk_tokenizer);
+  }
+
+  /// Refines every chunk produced by a chunk reader.
+  ///
+  /// # Arguments
+  ///
+  /// * `chunks` - The chunk reader to refine
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if reading or refinement fails.
+  pub async fn refine_chunks(
+    &self,
+    chunks: &mut ChunkReader,
+  ) -> RuntimeResult<String> {
+    return self.refine_chunks_with_progress(chunks, |_| async {}).await;
+  }
+
+  /// Refines every chunk produced by a chunk reader, reporting each refined
+  /// chunk as soon as it is available.
+  ///
+  /// # Arguments
+  ///
+  /// * `chunks` - The chunk reader to refine
+  /// * `on_chunk` - Called with the refined text of each chunk; the next
+  ///   chunk is not refined before the future it returns completes
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if reading or refinement fails.
+  pub async fn refine_chunks_with_progress<F: Future<Output = ()>>(
+    &self,
+    chunks: &mut ChunkReader,
+    on_chunk: impl FnMut(&str) -> F,
+  ) -> RuntimeResult<String> {
+    return self
+      .refine_chunks_in_context(
+        chunks,
+        &ConversationContext::default(),
+        &self.cancellation,
+        on_chunk,
+      )
+      .await;
+  }
+
+  /// Refines every chunk produced by a chunk reader with earlier
+  /// refinements of the session as context, reporting each refined chunk
+  /// as soon as it is available.
+  ///
+  /// Cancelling stops reading and drops the request in flight, closing
+  /// its connection; the chunks refined until then are returned in the
+  /// error.
+  ///
+  /// # Arguments
+  ///
+  /// * `chunks` - The chunk reader to refine
+  /// * `context` - The earlier refinements
+  /// * `cancellation` - Cancels the refinement, such as the refiner's own
+  ///   token or one per server request
+  /// * `on_chunk` - Called with the refined text of each chunk; the next
+  ///   chunk is not refined before the future it returns completes
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if reading or refinement fails or is
+  /// cancelled.
+  pub async fn refine_chunks_in_context<F: Future<Output = ()>>(
+    &self,
+    chunks: &mut ChunkReader,
+    context: &ConversationContext,
+    cancellation: &CancellationToken,
+    mut on_chunk: impl FnMut(&str) -> F,
+  ) -> RuntimeResult<String> {
+    let turns = context.turns();
+    let mut refined_text = String::new();
+    let mut chunk_count = 0;
+    let mut carryover = String::new();
+    let dictionary = self.select_dictionary(None);
+    let mut glossary = Glossary::new(&dictionary, self.glossary);
+
+    while let Some(chunk) =
+      until_cancelled(cancellation, &refined_text, async {
+        return chunks
+          .next_chunk()
+          .await
+          .map_err(|e| RuntimeError::Input(e.to_string()));
+      })
+      .await?
+    {
+      chunk_count += 1;
+      slog!(
+        "chunk",
+        number = chunk_count,
+        characters = chunk.text.chars().count(),
+        boundary = if chunk.ends_paragraph {
+          "paragraph"
+        } else if chunk.ends_line {
+          "line"
+        } else {
+          "sentence"
+        }
+      );
+
+      // Boxed, as the request is too large for a worker thread's stack in
+      // debug builds.
+      let refinement = Box::pin(self.llm.refine_text(
+        &chunk.text,
+        &dictionary,
+        &turns,
+        &carryover,
+      ));
+      let refined_chunk = until_cancelled(cancellation, &refined_text, async {
+        return refinement.await.map_err(llm_error);
+      })
+      .await?;
+
+      let _timer = timing::start(Phase::PostProcessing);
+      let refined_chunk = glossary.apply(&refined_chunk);
+      carryover =
+        context::carryover(&refined_chunk, self.chunk_overlap_sentences);
+      // A slow consumer of the chunks holds back the next request.
+      until_cancelled(cancellation, &refined_text, async {
+        on_chunk(&refined_chunk).await;
+        return Ok(());
+      })
+      .await?;
+      refined_text.push_str(&refined_chunk);
+      refined_text.push_str(if chunk.ends_paragraph {
+        "\n\n"
+      } else if chunk.ends_line {
+        "\n"
+      } else {
+        " "
+      });
+    }
+
+    if chunk_count == 0 {
+      return Err(RuntimeError::Input(InputError::EmptyInput.to_string()));
+    }
+
+    return Ok(refined_text.trim_end().to_string());
+  }
+
+  /// Refines a Whisper transcription using its confidence scores.
+  ///
+  /// Unless disabled, runs of near-duplicate segments left by Whisper
+  /// repetition loops are collapsed first.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The parsed Whisper transcription
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if refinement fails.
+  pub async fn refine_whisper(
+    &self,
+    transcription: &WhisperTranscription,
+  ) -> RuntimeResult<String> {
+    let transcription = self.prepare_whisper(transcription);
+    let dictionary = self.select_dictionary(transcription.language.as_deref());
+
+    let refinement = self.llm.refine_whisper_transcription(
+      &transcription,
+      &dictionary,
+      self.probability_threshold,
+    );
+    let refined = until_cancelled(&self.cancellation, "", async {
+      return refinement.await.map_err(llm_error);
+    })
+    .await?;
+    return Ok(Glossary::new(&dictionary, self.glossary).apply(&refined));
+  }
+
+  /// Splits a Whisper transcription into titled chapters.
+  ///
+  /// The LLM proposes where each chapter starts and what to call it; each
+  /// chapter is then refined with its own request.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The parsed Whisper transcription, with segments
+  ///
+  /// # Returns
+  ///
+  /// The refined chapters in order, or an error if the transcription has
+  /// no segments or refinement fails.
+  pub async fn chapters(
+    &self,
+    transcription: &WhisperTranscription,
+  ) -> RuntimeResult<Vec<Chapter>> {
+    let transcription = self.prepare_whisper(transcription);
+    let segments = transcription.segments.as_deref().unwrap_or_default();
+    if segments.is_empty() {
+      return Err(RuntimeError::Input(String::from(
+        "Chapters need a Whisper transcription with segments",
+      )));
+    }
+
+    let mut breaks = until_cancelled(&self.cancellation, "", async {
+      return self
+        .llm
+        .find_chapters(&transcription)
+        .await
+        .map_err(llm_error);
+    })
+    .await?;
+    breaks.retain(|chapter_break| chapter_break.segment < segments.len());
+    breaks.sort_by_key(|chapter_break| chapter_break.segment);
+    breaks.dedup_by_key(|chapter_break| chapter_break.segment);
+    match breaks.first_mut() {
+      // Segments before the first proposed break belong to the first chapter.
+      Some(first) => first.segment = 0,
+      None => {
+        return Err(RuntimeError::Refinement(String::from(
+          "LLM proposed no chapters",
+        )));
+      }
+    }
+
+    let mut chapters = Vec::with_capacity(breaks.len());
+    let dictionary = self.select_dictionary(transcription.language.as_deref());
+    let mut glossary = Glossary::new(&dictionary, self.glossary);
+    for (index, chapter_break) in breaks.iter().enumerate() {
+      let end = breaks
+        .get(index + 1)
+        .map_or(segments.len(), |next| next.segment);
+      vlog!(
+        "Refining chapter {}/{}: {}",
+        index + 1,
+        breaks.len(),
+        chapter_break.title
+      );
+
+      let chapter_transcription = WhisperTranscription {
+        text: None,
+        language: transcription.language.clone(),
+        duration: None,
+        segments: Some(segments[chapter_break.segment..end].to_vec()),
+      };
+      let refinement = self.llm.refine_whisper_transcription(
+        &chapter_transcription,
+        &dictionary,
+        self.probability_threshold,
+      );
+      let partial = chapters::to_markdown(&chapters);
+      let text = until_cancelled(&self.cancellation, &partial, async {
+        return refinement.await.map_err(llm_error);
+      })
+      .await?;
+
+      chapters.push(Chapter {
+        title: chapter_break.title.trim().to_string(),
+        start: segments[chapter_break.segment].start,
+        text: glossary.apply(&text),
+      });
+    }
+    return Ok(chapters);
+  }
+
+  /// Generates a title and short summary of a refined text.
+  ///
+  /// # Arguments
+  ///
+  /// * `refined_text` - The refined text
+  ///
+  /// # Returns
+  ///
+  /// The title and summary, or an error if the request fails.
+  pub async fn summarize(&self, refined_text: &str) -> RuntimeResult<Summary> {
+    return until_cancelled(&self.cancellation, refined_text, async {
+      return self.llm.summarize(refined_text).await.map_err(llm_error);
+    })
+    .await;
+  }
+
+  /// Probes the endpoint for its context window and features, refreshing
+  /// the cached result.
+  ///
+  /// # Returns
+  ///
+  /// The capabilities, or `None` if the endpoint could not be probed.
+  pub async fn probe_capabilities(&self) -> Option<Capabilities> {
+    return self.llm.probe_capabilities().await;
+  }
+
+  /// Selects the dictionary entries for a transcript.
+  ///
+  /// # Arguments
+  ///
+  /// * `detected` - The language the transcription service detected, if
+  ///   any; the declared language is used otherwise
+  ///
+  /// # Returns
+  ///
+  /// The entries used for every language and those of the transcript's
+  /// language.
+  fn select_dictionary(&self, detected: Option<&str>) -> Vec<DictionaryEntry> {
+    let language = detected
+      .filter(|language| !language.is_empty())
+      .unwrap_or(&self.language);
+    let entries = dictionary::select(&self.dictionary, Some(language));
+    if entries.len() < self.dictionary.len() {
+      vlog!(
+        "Using {} of {} dictionary entries for language {}",
+        entries.len(),
+        self.dictionary.len(),
+        language
+      );
+    }
+    return entries;
+  }
+
+  /// Prepares a Whisper transcription for refinement.
+  ///
+  /// Unless disabled, runs of near-duplicate segments are collapsed.
+  fn prepare_whisper(
+    &self,
+    transcription: &WhisperTranscription,
+  ) -> WhisperTranscription {
+    let mut transcription = transcription.clone();
+    if self.deduplicate_segments {
+      let collapsed = transcription.collapse_repeated_segments();
+      if collapsed > 0 {
+        vlog!("Collapsed {} repeated Whisper segments", collapsed);
+      }
+    }
+    return transcription;
+  }
+}
+
+/// Runs a step of a refinement until it completes or is cancelled.
+///
+/// # Arguments
+///
+/// * `cancellation` - Cancels the step
+/// * `partial` - The text refined before the step, kept in the error
+/// * `step` - The step, which is dropped when cancelled
+///
+/// # Returns
+///
+/// The result of the step, or `RuntimeError::Cancelled` with the partial
+/// text.
+async fn until_cancelled<T>(
+  cancellation: &CancellationToken,
+  partial: &str,
+  step: impl Future<Output = RuntimeResult<T>>,
+) -> RuntimeResult<T> {
+  return match cancellation.run_until_cancelled(step).await {
+    Some(result) => result,
+    None => Err(RuntimeError::Cancelled(partial.trim_end().to_string())),
+  };
+}
+
+/// Maps an LLM error to a runtime error.
+///
+/// A rejected URL or API key is a configuration problem, other client
+/// errors are reported by the service, and only failures that may pass
+/// on their own are network errors.
+fn llm_error(error: LLMError) -> RuntimeError {
+  return match error {
+    LLMError::ApiRequestFailed(ref e) => match e {
+      NetworkError::InvalidURL(_)
+      | NetworkError::InvalidProxy(_)
+      | NetworkError::ResponseError(401 | 403) => {
+        RuntimeError::Validation(error.to_string())
+      }
+      NetworkError::ResponseError(_) if !e.is_transient() => {
+        RuntimeError::Refinement(error.to_string())
+      }
+      _ => RuntimeError::Network(error.to_string()),
+    },
+    LLMError::ContextOverflow { .. } => RuntimeError::Input(error.to_string()),
+    LLMError::Refusal(_) => RuntimeError::Refusal(error.to_string()),
+    error => RuntimeError::Refinement(error.to_string()),
+  };
+}
+
+/// Builder for [`Refiner`].
+///
+/// Every setting defaults to the value of a default configuration file.
+pub struct RefinerBuilder {
+  url: String,
+  model: String,
+  api_key: String,
+  fallback_url: String,
+  endpoints: Vec<String>,
+  balance: BalanceStrategy,
+  proxy: String,
+  no_proxy: String,
+  circuit_breaker: CircuitBreaker,
+  request_id_header: bool,
+  retries: u32,
+  dictionary: Vec<DictionaryEntry>,
+  dictionary_matching: MatchOptions,
+  spellchecker: Option<Arc<Spellchecker>>,
+  glossary: GlossaryMode,
+  language: String,
+  chunk_size: usize,
+  chunk_unit: ChunkUnit,
+  tokenizer: String,
+  context_window: usize,
+  probe_capabilities: bool,
+  chunk_overlap_sentences: usize,
+  probability_threshold: f64,
+  deduplicate_segments: bool,
+  target_reading_level: f64,
+  output_limit: OutputLimit,
+  stop_sequences: Vec<String>,
+  temperature: Option<f64>,
+  api: LLMApi,
+  grammar: String,
+  constrain_output: bool,
+  logprob_threshold: f64,
+  keep_verbatim_tokens: bool,
+  non_speech: NonSpeechFilter,
+  min_similarity: f64,
+  embeddings_model: String,
+  retry_answers: bool,
+  refusal: RefusalHandling,
+  cancellation: CancellationToken,
+}
+
+impl Default for RefinerBuilder {
+  fn default() -> Self {
+    let defaults = Config::default();
+    return RefinerBuilder {
+      url: defaults.get_llm_url(),
+      model: defaults.get_llm_model(),
+      api_key: defaults.get_llm_api_key(),
+      fallback_url: defaults.get_llm_fallback_url(),
+      endpoints: defaults.get_llm_endpoints(),
+      balance: defaults.get_llm_balance(),
+      proxy: defaults.get_proxy(),
+      no_proxy: defaults.get_no_proxy(),
+      circuit_breaker: CircuitBreaker::new(
+        defaults.get_circuit_breaker_threshold(),
+        Duration::from_secs(defaults.get_circuit_breaker_window_seconds()),
+        Duration::from_secs(defaults.get_circuit_breaker_cooldown_seconds()),
+      ),
+      request_id_header: defaults.get_request_id_header(),
+      retries: defaults.get_network_retries(),
+      dictionary: Vec::new(),
+      dictionary_matching: defaults.get_dictionary_matching(),
+      spellchecker: None,
+      glossary: defaults.get_dictionary_glossary(),
+      language: defaults.get_transcription_language(),
+      chunk_size: defaults.get_input_chunk_size(),
+      chunk_unit: defaults.get_input_chunk_unit(),
+      tokenizer: defaults.get_llm_tokenizer(),
+      context_window: defaults.get_llm_context_window(),
+      probe_capabilities: defaults.get_llm_probe_capabilities(),
+      chunk_overlap_sentences: defaults.get_input_chunk_overlap_sentences(),
+      probability_threshold: defaults.get_whisper_probability_threshold(),
+      deduplicate_segments: defaults.get_whisper_deduplicate_segments(),
+      target_reading_level: defaults.get_llm_target_reading_level(),
+      output_limit: OutputLimit {
+        max_characters: defaults.get_llm_max_output_characters(),
+        policy: defaults.get_llm_output_overflow(),
+      },
+      stop_sequences: defaults.get_llm_stop(),
+      temperature: defaults.get_llm_temperature(),
+      api: defaults.get_llm_api(),
+      grammar: String::new(),
+      constrain_output: defaults.get_llm_constrain_output(),
+      logprob_threshold: defaults.get_whisper_logprob_threshold(),
+      keep_verbatim_tokens: defaults.get_llm_keep_verbatim_tokens(),
+      min_similarity: defaults.get_llm_min_similarity(),
+      embeddings_model: String::new(),
+      retry_answers: defaults.get_llm_retry_answers(),
+      refusal: defaults.get_llm_refusal(),
+      non_speech: NonSpeechFilter::new(
+        defaults.get_style_emoji(),
+        defaults.get_style_non_speech(),
+      ),
+      cancellation: CancellationToken::new(),
+    };
+  }
+}
+
+impl RefinerBuilder {
+  /// Sets the LLM server URL (`http://`, `https://` or `unix://`).
+  ///
+  /// # Arguments
+  ///
+  /// * `url` - The LLM server base URL
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn url(mut self, url: impl Into<String>) -> Self {
+    self.url = url.into();
+    return self;
+  }
+
+  /// Sets the model name sent with each request.
+  ///
+  /// # Arguments
+  ///
+  /// * `model` - The model name
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn model(mut self, model: impl Into<String>) -> Self {
+    self.model = model.into();
+    return self;
+  }
+
+  /// Sets the API key sent as a bearer token.
+  ///
+  /// # Arguments
+  ///
+  /// * `api_key` - The API key (empty for none)
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+    self.api_key = api_key.into();
+    return self;
+  }
+
+  /// Sets an endpoint to retry against when the primary one fails.
+  ///
+  /// # Arguments
+  ///
+  /// * `fallback_url` - Base URL of the fallback endpoint
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn fallback_url(mut self, fallback_url: impl Into<String>) -> Self {
+    self.fallback_url = fallback_url.into();
+    return self;
+  }
+
+  /// Sets further endpoints that share the load with the primary one.
+  ///
+  /// # Arguments
+  ///
+  /// * `endpoints` - Base URLs of endpoints equivalent to the primary one
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn endpoints(mut self, endpoints: Vec<String>) -> Self {
+    self.endpoints = endpoints;
+    return self;
+  }
+
+  /// Sets how requests are distributed over the endpoints.
+  ///
+  /// # Arguments
+  ///
+  /// * `balance` - The balancing strategy
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn balance(mut self, balance: BalanceStrategy) -> Self {
+    self.balance = balance;
+    return self;
+  }
+
+  /// Routes requests through a proxy, except for hosts in `no_proxy`.
+  ///
+  /// # Arguments
+  ///
+  /// * `proxy` - The proxy URL (`http://`, `https://` or `socks5://`)
+  /// * `no_proxy` - Comma-separated hosts to connect to directly
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn proxy(
+    mut self,
+    proxy: impl Into<String>,
+    no_proxy: impl Into<String>,
+  ) -> Self {
+    self.proxy = proxy.into();
+    self.no_proxy = no_proxy.into();
+    return self;
+  }
+
+  /// Sets the circuit breaker applied to each endpoint.
+  ///
+  /// # Arguments
+  ///
+  /// * `circuit_breaker` - The circuit breaker settings
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+    self.circuit_breaker = circuit_breaker;
+    return self;
+  }
+
+  /// Sets whether requests carry the request ID in an `X-Request-Id`
+  /// header.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to send the header
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn request_id_header(mut self, enabled: bool) -> Self {
+    self.request_id_header = enabled;
+    return self;
+  }
+
+  /// Sets how often a failed LLM request is retried.
+  ///
+  /// # Arguments
+  ///
+  /// * `retries` - How often a request failing with a connection error,
+  ///   HTTP 429 or a 5xx status is sent again
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn retries(mut self, retries: u32) -> Self {
+    self.retries = retries;
+    return self;
+  }
+
+  /// Sets the dictionary words the LLM should prefer.
+  ///
+  /// # Arguments
+  ///
+  /// * `words` - The dictionary words, as plain terms or entries
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn dictionary<E: Into<DictionaryEntry>>(mut self, words: Vec<E>) -> Self {
+    self.dictionary = words.into_iter().map(Into::into).collect();
+    return self;
+  }
+
+  /// Sets how dictionary terms are matched.
+  ///
+  /// # Arguments
+  ///
+  /// * `options` - The matching options of the dictionary
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn dictionary_matching(mut self, options: MatchOptions) -> Self {
+    self.dictionary_matching = options;
+    return self;
+  }
+
+  /// Sets the spellchecker whose words enforced dictionary terms never
+  /// replace.
+  ///
+  /// # Arguments
+  ///
+  /// * `spellchecker` - The spellchecker, or `None` for none
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn spellchecker(
+    mut self,
+    spellchecker: Option<Arc<Spellchecker>>,
+  ) -> Self {
+    self.spellchecker = spellchecker;
+    return self;
+  }
+
+  /// Sets how abbreviations of glossary entries are written.
+  ///
+  /// # Arguments
+  ///
+  /// * `mode` - How abbreviations are written
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn glossary(mut self, mode: GlossaryMode) -> Self {
+    self.glossary = mode;
+    return self;
+  }
+
+  /// Sets the language of transcripts, selecting the dictionary section
+  /// used where the transcription service did not detect one.
+  ///
+  /// # Arguments
+  ///
+  /// * `language` - An ISO 639-1 code, or empty if unknown
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn language(mut self, language: impl Into<String>) -> Self {
+    self.language = language.into();
+    return self;
+  }
+
+  /// Sets the target chunk size (0 disables chunking).
+  ///
+  /// # Arguments
+  ///
+  /// * `chunk_size` - Target chunk size in the chunk unit
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+    self.chunk_size = chunk_size;
+    return self;
+  }
+
+  /// Sets the unit the chunk size is counted in.
+  ///
+  /// # Arguments
+  ///
+  /// * `chunk_unit` - Characters, or tokens of the model
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn chunk_unit(mut self, chunk_unit: ChunkUnit) -> Self {
+    self.chunk_unit = chunk_unit;
+    return self;
+  }
+
+  /// Sets the tokenizer used to count tokens.
+  ///
+  /// # Arguments
+  ///
+  /// * `tokenizer` - A vocabulary name such as `cl100k_base`, `estimate`,
+  ///   or empty to pick the vocabulary of the model
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn tokenizer(mut self, tokenizer: impl Into<String>) -> Self {
+    self.tokenizer = tokenizer.into();
+    return self;
+  }
+
+  /// Sets the context window prompts are checked against before sending.
+  ///
+  /// # Arguments
+  ///
+  /// * `context_window` - The context window in tokens (0 disables the
+  ///   check)
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn context_window(mut self, context_window: usize) -> Self {
+    self.context_window = context_window;
+    return self;
+  }
+
+  /// Sets whether the endpoint is probed for its context window and
+  /// features before the first request.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to probe the endpoint
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn probe_capabilities(mut self, enabled: bool) -> Self {
+    self.probe_capabilities = enabled;
+    return self;
+  }
+
+  /// Sets how many sentences of a chunk are sent with the next chunk as
+  /// context (0 disables the carryover).
+  ///
+  /// # Arguments
+  ///
+  /// * `sentences` - Number of sentences to carry over
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn chunk_overlap_sentences(mut self, sentences: usize) -> Self {
+    self.chunk_overlap_sentences = sentences;
+    return self;
+  }
+
+  /// Sets the probability below which Whisper words are flagged.
+  ///
+  /// # Arguments
+  ///
+  /// * `probability_threshold` - Threshold between 0 and 1
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn probability_threshold(mut self, probability_threshold: f64) -> Self {
+    self.probability_threshold = probability_threshold;
+    return self;
+  }
+
+  /// Sets whether runs of repeated Whisper segments are collapsed.
+  ///
+  /// # Arguments
+  ///
+  /// * `deduplicate_segments` - Whether to keep only the first segment of
+  ///   each run of near-duplicates
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn deduplicate_segments(mut self, deduplicate_segments: bool) -> Self {
+    self.deduplicate_segments = deduplicate_segments;
+    return self;
+  }
+
+  /// Sets the Flesch-Kincaid grade level refinements should be written at.
+  ///
+  /// # Arguments
+  ///
+  /// * `grade_level` - The target grade level (0 keeps the speaker's
+  ///   level)
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn target_reading_level(mut self, grade_level: f64) -> Self {
+    self.target_reading_level = grade_level;
+    return self;
+  }
+
+  /// Sets the largest answer accepted from the LLM.
+  ///
+  /// # Arguments
+  ///
+  /// * `output_limit` - The size limit and what to do with longer answers
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn output_limit(mut self, output_limit: OutputLimit) -> Self {
+    self.output_limit = output_limit;
+    return self;
+  }
+
+  /// Sets sequences at which the LLM stops generating.
+  ///
+  /// # Arguments
+  ///
+  /// * `stop_sequences` - The stop sequences (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+    self.stop_sequences = stop_sequences;
+    return self;
+  }
+
+  /// Sets the sampling temperature.
+  ///
+  /// # Arguments
+  ///
+  /// * `temperature` - The temperature, or `None` for the service default
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn temperature(mut self, temperature: Option<f64>) -> Self {
+    self.temperature = temperature;
+    return self;
+  }
+
+  /// Sets the API requests are sent to.
+  ///
+  /// # Arguments
+  ///
+  /// * `api` - The OpenAI-compatible chat API or llama.cpp's completion API
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn api(mut self, api: LLMApi) -> Self {
+    self.api = api;
+    return self;
+  }
+
+  /// Sets a GBNF grammar constraining refined text.
+  ///
+  /// # Arguments
+  ///
+  /// * `grammar` - The grammar (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn grammar(mut self, grammar: impl Into<String>) -> Self {
+    self.grammar = grammar.into();
+    return self;
+  }
+
+  /// Constrains answers of llama.cpp with grammars generated from what
+  /// each request asks for.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to send generated grammars
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn constrain_output(mut self, enabled: bool) -> Self {
+    self.constrain_output = enabled;
+    return self;
+  }
+
+  /// Sets the model confidence needed to accept a correction of a flagged
+  /// Whisper word.
+  ///
+  /// # Arguments
+  ///
+  /// * `threshold` - The token probability (0 disables the check)
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn logprob_threshold(mut self, threshold: f64) -> Self {
+    self.logprob_threshold = threshold;
+    return self;
+  }
+
+  /// Sets whether timestamps, speaker tags and bracketed annotations are
+  /// kept verbatim in plain text.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to protect verbatim tokens
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn keep_verbatim_tokens(mut self, enabled: bool) -> Self {
+    self.keep_verbatim_tokens = enabled;
+    return self;
+  }
+
+  /// Sets the least semantic similarity refinements must keep to their
+  /// input, below which they are rejected.
+  ///
+  /// # Arguments
+  ///
+  /// * `min_similarity` - The least cosine similarity of the embeddings,
+  ///   or 0 to disable the check
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn min_similarity(mut self, min_similarity: f64) -> Self {
+    self.min_similarity = min_similarity;
+    return self;
+  }
+
+  /// Sets the model embeddings are computed with for the similarity check.
+  ///
+  /// # Arguments
+  ///
+  /// * `model` - The embeddings model name, or empty for the refinement
+  ///   model
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn embeddings_model(mut self, model: impl Into<String>) -> Self {
+    self.embeddings_model = model.into();
+    return self;
+  }
+
+  /// Sets whether refinements that look like answers to the transcript are
+  /// requested again with a reinforced prompt.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to retry such refinements
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn retry_answers(mut self, enabled: bool) -> Self {
+    self.retry_answers = enabled;
+    return self;
+  }
+
+  /// Sets what happens when the model refuses to refine a text.
+  ///
+  /// # Arguments
+  ///
+  /// * `handling` - Whether refusals are retried, failed or not looked for
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn refusal(mut self, handling: RefusalHandling) -> Self {
+    self.refusal = handling;
+    return self;
+  }
+
+  /// Sets what happens to emoji and non-speech markers.
+  ///
+  /// # Arguments
+  ///
+  /// * `non_speech` - The policies for emoji and non-speech markers
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn non_speech(mut self, non_speech: NonSpeechFilter) -> Self {
+    self.non_speech = non_speech;
+    return self;
+  }
+
+  /// Sets the token that cancels the refiner's runs.
+  ///
+  /// Once it is cancelled, every refinement stops at its next step with
+  /// `RuntimeError::Cancelled`, dropping the request in flight.
+  ///
+  /// # Arguments
+  ///
+  /// * `cancellation` - The token, usually the application's
+  ///
+  /// # Returns
+  ///
+  /// The updated `RefinerBuilder` instance.
+  pub fn cancellation(mut self, cancellation: CancellationToken) -> Self {
+    self.cancellation = cancellation;
+    return self;
+  }
+
+  /// Builds the refiner.
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<Refiner>` containing the refiner, or an error if the
+  /// network settings are invalid.
+  pub fn build(self) -> RuntimeResult<Refiner> {
+    vlog!("Initializing LLM client with model: {}", self.model);
+
+    let tokenizer = Tokenizer::resolve(&self.tokenizer, &self.model);
+    let chunk_tokenizer = if self.chunk_unit == ChunkUnit::Tokens {
+      if tokenizer.is_exact() {
+        vlog!("Counting chunk sizes in {} tokens", tokenizer.name());
+      } else {
+        vlog!("Estimating chunk sizes in tokens of model {}", self.model);
+      }
+      Some(tokenizer)
+    } else {
+      None
+    };
+
+    let llm = LLMClient::new(
+      self.create_http_client(self.url.clone())?,
+      self.model.clone(),
+      self.api_key.clone(),
+    )
+    .with_target_reading_level(self.target_reading_level)
+    .with_output_limit(self.output_limit)
+    .with_stop_sequences(self.stop_sequences.clone())
+    .with_temperature(self.temperature)
+    .with_api(self.api)
+    .with_grammar(self.grammar.clone())
+    .with_output_constraint(self.constrain_output)
+    .with_logprob_threshold(self.logprob_threshold)
+    .with_keep_verbatim_tokens(self.keep_verbatim_tokens)
+    .with_non_speech(self.non_speech)
+    .with_answer_retry(self.retry_answers)
+    .with_retries(self.retries)
+    .with_refusal_handling(self.refusal)
+    .with_similarity_check(
+      self.min_similarity,
+      if self.embeddings_model.is_empty() {
+        self.model.clone()
+      } else {
+        self.embeddings_model.clone()
+      },
+    )
+    .with_dictionary_matching(self.dictionary_matching)
+    .with_spellchecker(self.spellchecker.clone())
+    .with_glossary_mode(self.glossary)
+    .with_context_window(self.context_window, tokenizer)
+    .with_capability_probe(self.probe_capabilities);
+
+    let llm = if self.endpoints.is_empty() {
+      llm
+    } else {
+      vlog!(
+        "Balancing requests over {} endpoints ({})",
+        self.endpoints.len() + 1,
+        self.balance
+      );
+      let endpoints = self
+        .endpoints
+        .iter()
+        .map(|url| self.create_http_client(url.clone()))
+        .collect::<RuntimeResult<Vec<_>>>()?;
+      llm.with_endpoints(endpoints, self.balance)
+    };
+
+    let llm = if self.fallback_url.is_empty() {
+      llm
+    } else {
+      vlog!("Using fallback LLM endpoint: {}", self.fallback_url);
+      llm.with_fallback(self.create_http_client(self.fallback_url.clone())?)
+    };
+
+    return Ok(Refiner {
+      llm,
+      dictionary: self.dictionary,
+      dictionary_matching: self.dictionary_matching,
+      glossary: self.glossary,
+      language: self.language,
+      chunk_size: self.chunk_size,
+      chunk_tokenizer,
+      chunk_overlap_sentences: self.chunk_overlap_sentences,
+      probability_threshold: self.probability_threshold,
+      deduplicate_segments: self.deduplicate_segments,
+      cancellation: self.cancellation,
+    });
+  }
+
+  /// Creates an HTTP client for the given URL with the circuit breaker and
+  /// proxy settings.
+  fn create_http_client(&self, base_url: String) -> RuntimeResult<HttpClient> {
+    let http_client = HttpClient::new(base_url)
+      .map_err(|e| RuntimeError::Validation(e.to_string()))?
+      .with_circuit_breaker(self.circuit_breaker)
+      .with_request_id_header(self.request_id_header);
+
+    if self.proxy.is_empty() {
+      return Ok(http_client);
+    }
+
+    return http_client
+      .with_proxy(&self.proxy, &self.no_proxy)
+      .map_err(|e| RuntimeError::Validation(e.to_string()));
+  }
+}
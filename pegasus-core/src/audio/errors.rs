@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Audio transcription errors.
+///
+/// Represents errors that can occur while sending a recording to a
+/// transcription service.
+#[derive(Error, Debug)]
+pub enum AudioError {
+  #[error(
+    "{}",
+    t!("error-audio-read", path = .path.as_str(), error = .error.as_str())
+  )]
+  Read { path: String, error: String },
+
+  #[error("{}", t!("error-audio-network", error = .0.as_str()))]
+  Network(String),
+
+  #[error("{}", t!("error-audio-split", error = .0.as_str()))]
+  Split(String),
+}
+
+/// Result type for audio transcription operations.
+pub type AudioResult<T> = Result<T, AudioError>;
@@ -0,0 +1,378 @@
+//! Audio transcription before refinement.
+//!
+//! Sends a recording to a speech-to-text service and returns the result as
+//! a [`WhisperTranscription`], so it is refined with word confidences like
+//! a Whisper JSON file.
+//!
+//! ## Providers
+//!
+//! - `whisper-cpp`: a local whisper.cpp server, `POST /inference`
+//! - `openai-audio`: the OpenAI API or a compatible service,
+//!   `POST /v1/audio/transcriptions`
+//!
+//! Both are asked for `verbose_json`. whisper.cpp answers in the Whisper
+//! JSON layout; OpenAI responses are converted by [`openai`].
+//!
+//! Recordings larger than the upload limit are cut at pauses by [`split`],
+//! transcribed concurrently and merged back into one transcription.
+
+pub mod errors;
+pub mod openai;
+pub mod split;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::audio::errors::{AudioError, AudioResult};
+use crate::audio::openai::VerboseTranscription;
+use crate::audio::split::{AudioRange, AudioSplitter};
+use crate::input::transcription::WhisperTranscription;
+use crate::logging::request_id;
+use crate::network::HttpClient;
+use crate::network::multipart::MultipartForm;
+use crate::vlog;
+
+/// The speech-to-text service recordings are sent to.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum TranscriptionProvider {
+  /// A whisper.cpp server
+  #[default]
+  WhisperCpp,
+  /// The OpenAI audio transcription API
+  #[serde(rename = "openai-audio")]
+  OpenAiAudio,
+}
+
+impl fmt::Display for TranscriptionProvider {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      TranscriptionProvider::WhisperCpp => "whisper-cpp",
+      TranscriptionProvider::OpenAiAudio => "openai-audio",
+    });
+  }
+}
+
+/// Client for a speech-to-text service.
+#[derive(Debug, Clone)]
+pub struct AudioTranscriber {
+  http_client: HttpClient,
+  provider: TranscriptionProvider,
+  model: String,
+  language: String,
+  api_key: String,
+  splitter: Option<AudioSplitter>,
+  max_upload_size: u64,
+  concurrency: usize,
+}
+
+impl AudioTranscriber {
+  /// Creates a new AudioTranscriber for the given service.
+  ///
+  /// # Arguments
+  ///
+  /// * `http_client` - HTTP client for the service
+  /// * `provider` - The kind of service
+  ///
+  /// # Returns
+  ///
+  /// A new `AudioTranscriber` instance.
+  pub fn new(http_client: HttpClient, provider: TranscriptionProvider) -> Self {
+    return AudioTranscriber {
+      http_client,
+      provider,
+      model: String::new(),
+      language: String::new(),
+      api_key: String::new(),
+      splitter: None,
+      max_upload_size: 0,
+      concurrency: 1,
+    };
+  }
+
+  /// Sets the transcription model.
+  ///
+  /// # Arguments
+  ///
+  /// * `model` - The model name (e.g. "whisper-1"); ignored by whisper.cpp
+  ///
+  /// # Returns
+  ///
+  /// The updated `AudioTranscriber` instance.
+  pub fn with_model(mut self, model: String) -> Self {
+    self.model = model;
+    return self;
+  }
+
+  /// Sets the spoken language instead of detecting it.
+  ///
+  /// # Arguments
+  ///
+  /// * `language` - An ISO 639-1 code (e.g. "en"), or empty to detect it
+  ///
+  /// # Returns
+  ///
+  /// The updated `AudioTranscriber` instance.
+  pub fn with_language(mut self, language: String) -> Self {
+    self.language = language;
+    return self;
+  }
+
+  /// Sets the API key sent as a bearer token.
+  ///
+  /// # Arguments
+  ///
+  /// * `api_key` - The API key, or empty for unauthenticated services
+  ///
+  /// # Returns
+  ///
+  /// The updated `AudioTranscriber` instance.
+  pub fn with_api_key(mut self, api_key: String) -> Self {
+    self.api_key = api_key;
+    return self;
+  }
+
+  /// Splits recordings larger than the upload limit into chunks.
+  ///
+  /// # Arguments
+  ///
+  /// * `splitter` - Cuts recordings at pauses
+  /// * `max_upload_size` - Largest file sent in one request, in bytes (0
+  ///   never splits)
+  /// * `concurrency` - How many chunks are transcribed at once
+  ///
+  /// # Returns
+  ///
+  /// The updated `AudioTranscriber` instance.
+  pub fn with_splitting(
+    mut self,
+    splitter: AudioSplitter,
+    max_upload_size: u64,
+    concurrency: usize,
+  ) -> Self {
+    self.splitter = Some(splitter);
+    self.max_upload_size = max_upload_size;
+    self.concurrency = concurrency.max(1);
+    return self;
+  }
+
+  /// Transcribes a recording, in chunks if it is larger than the upload
+  /// limit.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - Path to the audio file
+  ///
+  /// # Returns
+  ///
+  /// An `AudioResult<WhisperTranscription>` containing the transcription or
+  /// an error.
+  pub async fn transcribe(
+    &self,
+    path: &Path,
+  ) -> AudioResult<WhisperTranscription> {
+    let size = tokio::fs::metadata(path)
+      .await
+      .map_err(|e| read_error(path, e))?
+      .len();
+
+    if let Some(splitter) = &self.splitter
+      && self.max_upload_size > 0
+      && size > self.max_upload_size
+    {
+      return self.transcribe_in_chunks(splitter, path).await;
+    }
+    return self.transcribe_file(path).await;
+  }
+
+  /// Cuts a recording at pauses and transcribes the chunks concurrently.
+  async fn transcribe_in_chunks(
+    &self,
+    splitter: &AudioSplitter,
+    path: &Path,
+  ) -> AudioResult<WhisperTranscription> {
+    let ranges = splitter.plan(path).await?;
+    vlog!(
+      "Transcribing {} in {} chunks, {} at a time",
+      path.display(),
+      ranges.len(),
+      self.concurrency
+    );
+
+    let semaphore = Arc::new(Semaphore::new(self.concurrency));
+    let mut tasks = JoinSet::new();
+    for (index, range) in ranges.into_iter().enumerate() {
+      let transcriber = self.clone();
+      let splitter = splitter.clone();
+      let path = PathBuf::from(path);
+      let semaphore = Arc::clone(&semaphore);
+      tasks.spawn(request_id::inherit(async move {
+        let _permit = semaphore
+          .acquire_owned()
+          .await
+          .map_err(|e| AudioError::Split(e.to_string()))?;
+        let chunk = splitter.extract(&path, range).await?;
+        let transcription = transcriber.transcribe_file(chunk.path()).await?;
+        return Ok::<_, AudioError>((index, range, transcription));
+      }));
+    }
+
+    let mut parts = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+      parts.push(result.map_err(|e| AudioError::Split(e.to_string()))??);
+    }
+    parts.sort_by_key(|(index, _, _)| *index);
+
+    return Ok(merge_transcriptions(
+      parts
+        .into_iter()
+        .map(|(_, range, transcription)| (range, transcription))
+        .collect(),
+    ));
+  }
+
+  /// Transcribes a recording in a single request.
+  async fn transcribe_file(
+    &self,
+    path: &Path,
+  ) -> AudioResult<WhisperTranscription> {
+    let size = tokio::fs::metadata(path)
+      .await
+      .map_err(|e| read_error(path, e))?
+      .len();
+    let file_name = path.file_name().map_or_else(
+      || String::from("audio"),
+      |name| name.to_string_lossy().to_string(),
+    );
+
+    vlog!(
+      "Transcribing {} ({} bytes) with {} at {}",
+      file_name,
+      size,
+      self.provider,
+      self.http_client.base_url()
+    );
+
+    let mut form = MultipartForm::new()
+      .file("file", path, &file_name)
+      .await
+      .map_err(|e| read_error(path, e))?
+      .text("response_format", "verbose_json");
+    if !self.language.is_empty() {
+      form = form.text("language", &self.language);
+    }
+
+    let transcription = match self.provider {
+      TranscriptionProvider::WhisperCpp => {
+        self
+          .http_client
+          .post_multipart::<WhisperTranscription>(
+            form,
+            "inference",
+            self.headers(),
+          )
+          .await
+      }
+      TranscriptionProvider::OpenAiAudio => {
+        let form = form
+          .text("model", &self.model)
+          .text("timestamp_granularities[]", "segment")
+          .text("timestamp_granularities[]", "word");
+        self
+          .http_client
+          .post_multipart::<VerboseTranscription>(
+            form,
+            "v1/audio/transcriptions",
+            self.headers(),
+          )
+          .await
+          .map(VerboseTranscription::into_transcription)
+      }
+    }
+    .map_err(|e| AudioError::Network(e.to_string()))?;
+
+    return Ok(transcription);
+  }
+
+  /// Builds the authorization header, if an API key is set.
+  fn headers(&self) -> Option<HashMap<String, String>> {
+    if self.api_key.is_empty() {
+      return None;
+    }
+    let mut headers = HashMap::new();
+    headers.insert(
+      String::from("Authorization"),
+      format!("Bearer {}", self.api_key),
+    );
+    return Some(headers);
+  }
+}
+
+/// Joins the transcriptions of consecutive chunks, shifting segment
+/// timestamps by the start of their chunk.
+///
+/// # Arguments
+///
+/// * `parts` - Each chunk with its transcription, in order
+///
+/// # Returns
+///
+/// The transcription of the whole recording. Segments are kept only if
+/// every chunk has them.
+fn merge_transcriptions(
+  parts: Vec<(AudioRange, WhisperTranscription)>,
+) -> WhisperTranscription {
+  let text = parts
+    .iter()
+    .map(|(_, transcription)| transcription.full_text().trim().to_string())
+    .filter(|text| !text.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ");
+  let language = parts
+    .iter()
+    .find_map(|(_, transcription)| transcription.language.clone());
+  let duration = parts.last().map(|(range, _)| range.end);
+
+  let mut segments = Some(Vec::new());
+  for (range, transcription) in parts {
+    match (&mut segments, transcription.segments) {
+      (Some(merged), Some(chunk_segments)) => {
+        merged.extend(chunk_segments.into_iter().map(|mut segment| {
+          segment.start = segment.start.map(|start| start + range.start);
+          segment.end = segment.end.map(|end| end + range.start);
+          segment
+        }));
+      }
+      _ => segments = None,
+    }
+  }
+
+  return WhisperTranscription {
+    text: Some(text),
+    language,
+    duration,
+    segments,
+  };
+}
+
+/// Wraps a failure to read an audio file.
+fn read_error(path: &Path, error: std::io::Error) -> AudioError {
+  return AudioError::Read {
+    path: path.display().to_string(),
+    error: error.to_string(),
+  };
+}
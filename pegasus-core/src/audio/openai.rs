@@ -0,0 +1,102 @@
+//! OpenAI `verbose_json` transcription responses.
+//!
+//! The OpenAI API reports timed words separately from segments and gives
+//! no per-word probability, only each segment's average token log
+//! probability. Words are assigned to the segment they start in and take
+//! the segment's probability, so low-confidence passages are still flagged
+//! during refinement.
+
+use serde::Deserialize;
+
+use crate::input::transcription::{
+  WhisperSegment, WhisperTranscription, WhisperWord,
+};
+
+/// Response of `/v1/audio/transcriptions` with `verbose_json`.
+#[derive(Debug, Deserialize)]
+pub struct VerboseTranscription {
+  text: String,
+  language: Option<String>,
+  duration: Option<f64>,
+  #[serde(default)]
+  segments: Vec<VerboseSegment>,
+  #[serde(default)]
+  words: Vec<VerboseWord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VerboseSegment {
+  start: f64,
+  end: f64,
+  text: String,
+  avg_logprob: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VerboseWord {
+  word: String,
+  start: f64,
+}
+
+impl VerboseTranscription {
+  /// Converts the response into a Whisper transcription.
+  ///
+  /// # Returns
+  ///
+  /// The transcription, with word probabilities taken from the average
+  /// log probability of their segment.
+  pub fn into_transcription(self) -> WhisperTranscription {
+    let segment_count = self.segments.len();
+    let mut words = self.words.into_iter().peekable();
+
+    let segments = self
+      .segments
+      .into_iter()
+      .enumerate()
+      .map(|(index, segment)| {
+        let probability = segment
+          .avg_logprob
+          .map_or(1.0, |logprob| logprob.exp().clamp(0.0, 1.0));
+        let is_last = index + 1 == segment_count;
+
+        let mut segment_words = Vec::new();
+        while let Some(word) =
+          words.next_if(|word| is_last || word.start < segment.end)
+        {
+          segment_words.push(WhisperWord {
+            word: format!(" {}", word.word.trim()),
+            probability,
+          });
+        }
+        if segment_words.is_empty() {
+          segment_words = segment
+            .text
+            .split_whitespace()
+            .map(|word| WhisperWord {
+              word: format!(" {}", word),
+              probability,
+            })
+            .collect();
+        }
+
+        WhisperSegment {
+          start: Some(segment.start),
+          end: Some(segment.end),
+          text: segment.text,
+          words: segment_words,
+        }
+      })
+      .collect::<Vec<_>>();
+
+    return WhisperTranscription {
+      text: Some(self.text),
+      language: self.language,
+      duration: self.duration,
+      segments: if segments.is_empty() {
+        None
+      } else {
+        Some(segments)
+      },
+    };
+  }
+}
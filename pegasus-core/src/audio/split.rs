@@ -0,0 +1,367 @@
+//! Splitting long recordings on silence.
+//!
+//! Transcription services limit the size of an upload (25 MB for OpenAI),
+//! so longer recordings are cut into chunks that are transcribed on their
+//! own. Cuts are placed in pauses, so no word is split between chunks.
+//!
+//! Pauses are found by the configured silence command, by default ffmpeg's
+//! `silencedetect` filter. Any command works, e.g. a Silero VAD script, as
+//! long as it prints `silence_start: <seconds>` and `silence_end:
+//! <seconds>` lines like ffmpeg does. The command runs without a shell
+//! (see [`crate::shell`]). The length of the recording comes from
+//! `ffprobe`, and the chunks are extracted with `ffmpeg` as 16 kHz mono WAV
+//! files.
+
+use std::path::Path;
+use std::process::Stdio;
+
+use crate::audio::errors::{AudioError, AudioResult};
+use crate::files::temporary::TemporaryFile;
+use crate::shell;
+use crate::{dlog, vlog};
+
+/// Placeholder for the input path in the silence command.
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+
+/// Silence command used unless one is configured.
+pub const DEFAULT_SILENCE_COMMAND: &str = "ffmpeg -nostdin -hide_banner \
+  -i {input} -af silencedetect=noise=-35dB:d=0.4 -f null -";
+
+/// A pause in a recording, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Silence {
+  pub start: f64,
+  pub end: f64,
+}
+
+/// A part of a recording, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioRange {
+  pub start: f64,
+  pub end: f64,
+}
+
+/// Cuts recordings into chunks at pauses.
+#[derive(Debug, Clone)]
+pub struct AudioSplitter {
+  silence_command: String,
+  chunk_seconds: f64,
+}
+
+impl AudioSplitter {
+  /// Creates a new AudioSplitter.
+  ///
+  /// # Arguments
+  ///
+  /// * `silence_command` - Command line that prints the pauses of
+  ///   `{input}`
+  /// * `chunk_seconds` - Longest chunk, in seconds
+  ///
+  /// # Returns
+  ///
+  /// A new `AudioSplitter` instance.
+  pub fn new(silence_command: String, chunk_seconds: f64) -> Self {
+    return AudioSplitter {
+      silence_command,
+      chunk_seconds,
+    };
+  }
+
+  /// Plans where to cut a recording.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - Path to the audio file
+  ///
+  /// # Returns
+  ///
+  /// An `AudioResult<Vec<AudioRange>>` containing the chunks in order or
+  /// an error.
+  pub async fn plan(&self, path: &Path) -> AudioResult<Vec<AudioRange>> {
+    let duration = probe_duration(path).await?;
+    let command = shell::split_with(
+      &self.silence_command,
+      INPUT_PLACEHOLDER,
+      &path.to_string_lossy(),
+    )
+    .ok_or_else(|| {
+      AudioError::Split(format!(
+        "invalid silence command '{}'",
+        self.silence_command
+      ))
+    })?;
+    let output = run(&command).await?;
+    let silences = parse_silences(&output);
+    dlog!(
+      "Found {} pauses in {:.1}s of audio",
+      silences.len(),
+      duration
+    );
+
+    return Ok(plan_ranges(duration, &silences, self.chunk_seconds));
+  }
+
+  /// Extracts a chunk of a recording into a temporary WAV file.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - Path to the audio file
+  /// * `range` - The part to extract
+  ///
+  /// # Returns
+  ///
+  /// An `AudioResult<TemporaryFile>` guarding the extracted chunk or an
+  /// error.
+  pub async fn extract(
+    &self,
+    path: &Path,
+    range: AudioRange,
+  ) -> AudioResult<TemporaryFile> {
+    let chunk = TemporaryFile::create_in_cache("audio-chunk", "wav")
+      .await
+      .map_err(|e| AudioError::Split(e.to_string()))?;
+    let command = [
+      String::from("ffmpeg"),
+      String::from("-nostdin"),
+      String::from("-v"),
+      String::from("error"),
+      String::from("-y"),
+      String::from("-ss"),
+      format!("{:.3}", range.start),
+      String::from("-t"),
+      format!("{:.3}", range.end - range.start),
+      String::from("-i"),
+      path.to_string_lossy().to_string(),
+      String::from("-vn"),
+      String::from("-ac"),
+      String::from("1"),
+      String::from("-ar"),
+      String::from("16000"),
+      chunk.path_string(),
+    ];
+    run(&command).await?;
+    return Ok(chunk);
+  }
+}
+
+/// Gets the length of a recording with `ffprobe`.
+async fn probe_duration(path: &Path) -> AudioResult<f64> {
+  let command = [
+    String::from("ffprobe"),
+    String::from("-v"),
+    String::from("error"),
+    String::from("-show_entries"),
+    String::from("format=duration"),
+    String::from("-of"),
+    String::from("default=noprint_wrappers=1:nokey=1"),
+    path.to_string_lossy().to_string(),
+  ];
+  let output = run(&command).await?;
+  return output.trim().parse::<f64>().map_err(|_| {
+    AudioError::Split(format!(
+      "ffprobe reported no duration for '{}'",
+      path.display()
+    ))
+  });
+}
+
+/// Runs a command and returns its standard output and error.
+///
+/// # Arguments
+///
+/// * `command` - The program followed by its arguments
+async fn run(command: &[String]) -> AudioResult<String> {
+  let display = command.join(" ");
+  vlog!("Running: {}", display);
+  let (program, arguments) = command
+    .split_first()
+    .ok_or_else(|| AudioError::Split(String::from("empty command")))?;
+  let output = tokio::process::Command::new(program)
+    .args(arguments)
+    .stdin(Stdio::null())
+    .output()
+    .await
+    .map_err(|e| AudioError::Split(format!("'{}': {}", display, e)))?;
+
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  if !output.status.success() {
+    return Err(AudioError::Split(format!(
+      "'{}' failed: {}",
+      display,
+      stderr.trim()
+    )));
+  }
+
+  let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+  text.push_str(&stderr);
+  return Ok(text);
+}
+
+/// Parses `silence_start:` and `silence_end:` lines.
+///
+/// # Arguments
+///
+/// * `output` - Output of the silence command
+///
+/// # Returns
+///
+/// The pauses in order. A pause that never ends is dropped.
+pub fn parse_silences(output: &str) -> Vec<Silence> {
+  let value_after = |line: &str, key: &str| {
+    let rest = &line[line.find(key)? + key.len()..];
+    return rest.split_whitespace().next()?.parse::<f64>().ok();
+  };
+
+  let mut silences = Vec::new();
+  let mut start = None;
+  for line in output.lines() {
+    if let Some(value) = value_after(line, "silence_start:") {
+      start = Some(value.max(0.0));
+    } else if let Some(end) = value_after(line, "silence_end:")
+      && let Some(start) = start.take()
+    {
+      silences.push(Silence { start, end });
+    }
+  }
+  return silences;
+}
+
+/// Chooses chunks no longer than `chunk_seconds`, cut in the middle of
+/// the latest pause that keeps each chunk at least half that long.
+///
+/// Where no such pause exists, the chunk is cut at its longest length.
+///
+/// # Arguments
+///
+/// * `duration` - Length of the recording in seconds
+/// * `silences` - The pauses in order
+/// * `chunk_seconds` - Longest chunk, in seconds
+///
+/// # Returns
+///
+/// The chunks in order, covering the whole recording.
+pub fn plan_ranges(
+  duration: f64,
+  silences: &[Silence],
+  chunk_seconds: f64,
+) -> Vec<AudioRange> {
+  let mut ranges = Vec::new();
+  let mut start = 0.0;
+  while chunk_seconds > 0.0 && duration - start > chunk_seconds {
+    let latest = start + chunk_seconds;
+    let earliest = start + chunk_seconds / 2.0;
+    let cut = silences
+      .iter()
+      .map(|silence| (silence.start + silence.end) / 2.0)
+      .rev()
+      .find(|middle| (earliest..=latest).contains(middle))
+      .unwrap_or(latest);
+    ranges.push(AudioRange { start, end: cut });
+    start = cut;
+  }
+  ranges.push(AudioRange {
+    start,
+    end: duration,
+  });
+  return ranges;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_ffmpeg_silencedetect_output() {
+    let output = "\
+[silencedetect @ 0x1] silence_start: -0.02
+[silencedetect @ 0x1] silence_end: 1.5 | silence_duration: 1.52
+size=N/A time=00:00:10.00 bitrate=N/A speed= 900x
+[silencedetect @ 0x1] silence_start: 8.25
+[silencedetect @ 0x1] silence_end: 9 | silence_duration: 0.75
+[silencedetect @ 0x1] silence_start: 9.8";
+
+    assert_eq!(
+      parse_silences(output),
+      vec![
+        Silence {
+          start: 0.0,
+          end: 1.5
+        },
+        Silence {
+          start: 8.25,
+          end: 9.0
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn ignores_an_end_without_a_start() {
+    assert!(parse_silences("silence_end: 2.0").is_empty());
+  }
+
+  #[test]
+  fn cuts_in_the_middle_of_the_latest_pause() {
+    let silences = [
+      Silence {
+        start: 30.0,
+        end: 32.0,
+      },
+      Silence {
+        start: 50.0,
+        end: 52.0,
+      },
+    ];
+
+    assert_eq!(
+      plan_ranges(100.0, &silences, 60.0),
+      vec![
+        AudioRange {
+          start: 0.0,
+          end: 51.0
+        },
+        AudioRange {
+          start: 51.0,
+          end: 100.0
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn cuts_at_the_longest_length_without_a_usable_pause() {
+    let silences = [Silence {
+      start: 5.0,
+      end: 6.0,
+    }];
+
+    assert_eq!(
+      plan_ranges(50.0, &silences, 20.0),
+      vec![
+        AudioRange {
+          start: 0.0,
+          end: 20.0
+        },
+        AudioRange {
+          start: 20.0,
+          end: 40.0
+        },
+        AudioRange {
+          start: 40.0,
+          end: 50.0
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn keeps_short_recordings_whole() {
+    assert_eq!(
+      plan_ranges(10.0, &[], 60.0),
+      vec![AudioRange {
+        start: 0.0,
+        end: 10.0
+      }]
+    );
+  }
+}
@@ -0,0 +1,39 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Managed backend errors.
+///
+/// Represents errors that can occur while starting the LLM server that
+/// Pegasus spawns on demand.
+#[derive(Error, Debug)]
+pub enum BackendError {
+  #[error(
+    "{}",
+    t!(
+      "error-backend-spawn",
+      command = .command.as_str(),
+      error = .error.as_str()
+    )
+  )]
+  Spawn { command: String, error: String },
+
+  #[error(
+    "{}",
+    t!(
+      "error-backend-exited",
+      command = .command.as_str(),
+      status = .status.as_str()
+    )
+  )]
+  Exited { command: String, status: String },
+
+  #[error(
+    "{}",
+    t!("error-backend-timeout", url = .url.as_str(), seconds = *.seconds)
+  )]
+  Timeout { url: String, seconds: u64 },
+}
+
+/// Result type for managed backend operations.
+pub type BackendResult<T> = Result<T, BackendError>;
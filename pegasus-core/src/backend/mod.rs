@@ -0,0 +1,290 @@
+//! LLM server spawned on demand.
+//!
+//! When `[backend] command` is set, e.g. `llama-server -m model.gguf --port
+//! 8080`, the server is started the first time a refiner is needed, unless
+//! something already answers at `[llm] url`. Pegasus waits until the server
+//! reports it is ready, reuses it for the rest of the process, and stops it
+//! when the [`ManagedBackend`] is dropped.
+//!
+//! The command runs through `sh -c` in its own process group, so stopping
+//! the group also stops the server the shell started. Like temporary files,
+//! running servers are tracked in a process-wide registry that [`stop_all`]
+//! clears on exit paths that skip destructors, including termination by a
+//! signal. Servers listening on a Unix domain socket (`unix://` URLs) are
+//! polled over the socket.
+
+pub mod errors;
+
+use std::collections::HashSet;
+use std::process::Stdio;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::process::{Child, Command};
+
+use crate::backend::errors::{BackendError, BackendResult};
+use crate::{dlog, vlog};
+
+/// How often readiness is polled while the server starts.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Timeout of a single readiness request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+const UNIX_SOCKET_SCHEME: &str = "unix://";
+const UNIX_SOCKET_HTTP_BASE: &str = "http://localhost";
+
+static RUNNING: LazyLock<Mutex<HashSet<u32>>> =
+  LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Stops every server that has not been stopped yet.
+///
+/// Call this before leaving the process without unwinding, e.g. before
+/// `std::process::exit` or when interrupted by a signal.
+pub fn stop_all() {
+  let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+  for pid in running.drain() {
+    terminate(pid);
+  }
+}
+
+fn register(pid: u32) {
+  let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+  running.insert(pid);
+}
+
+fn unregister(pid: u32) {
+  let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+  running.remove(&pid);
+}
+
+/// An LLM server started by Pegasus.
+#[derive(Debug)]
+pub struct ManagedBackend {
+  command: String,
+  url: String,
+  ready_timeout: Duration,
+  process: tokio::sync::Mutex<Option<Child>>,
+}
+
+impl ManagedBackend {
+  /// Creates a backend that is started on the first call to
+  /// [`ManagedBackend::ensure_running`].
+  ///
+  /// # Arguments
+  ///
+  /// * `command` - Shell command that runs the server
+  /// * `url` - URL the server answers at once started
+  /// * `ready_timeout` - How long to wait for the server to become ready
+  ///
+  /// # Returns
+  ///
+  /// A new `ManagedBackend` instance.
+  pub fn new(command: String, url: String, ready_timeout: Duration) -> Self {
+    return ManagedBackend {
+      command,
+      url,
+      ready_timeout,
+      process: tokio::sync::Mutex::new(None),
+    };
+  }
+
+  /// Gets the command that runs the server.
+  ///
+  /// # Returns
+  ///
+  /// The shell command.
+  pub fn command(&self) -> &str {
+    return &self.command;
+  }
+
+  /// Gets the URL the server answers at.
+  ///
+  /// # Returns
+  ///
+  /// The server URL.
+  pub fn url(&self) -> &str {
+    return &self.url;
+  }
+
+  /// Starts the server unless it is running, and waits until it is ready.
+  ///
+  /// Nothing is started if another server already answers at the URL. A
+  /// server that exited since it was started is started again.
+  ///
+  /// # Returns
+  ///
+  /// A `BackendResult<()>` indicating whether a server is ready.
+  pub async fn ensure_running(&self) -> BackendResult<()> {
+    let mut process = self.process.lock().await;
+    if let Some(child) = process.as_mut() {
+      if matches!(child.try_wait(), Ok(None)) {
+        return Ok(());
+      }
+      vlog!("Backend exited, starting it again: {}", self.command);
+      if let Some(pid) = child.id() {
+        unregister(pid);
+      }
+      *process = None;
+    }
+
+    let (client, probe_url) =
+      probe_client(&self.url).map_err(|e| self.spawn_error(e))?;
+    if is_ready(&client, &probe_url).await {
+      vlog!("A server already answers at {}, not starting one", self.url);
+      return Ok(());
+    }
+
+    vlog!("Starting backend: {}", self.command);
+    let mut command = Command::new("sh");
+    command
+      .arg("-c")
+      .arg(&self.command)
+      .stdin(Stdio::null())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null());
+    #[cfg(unix)]
+    command.process_group(0);
+    let mut child = command.spawn().map_err(|e| self.spawn_error(e))?;
+    let pid = child.id();
+    if let Some(pid) = pid {
+      register(pid);
+    }
+
+    let started = Instant::now();
+    loop {
+      if let Ok(Some(status)) = child.try_wait() {
+        if let Some(pid) = pid {
+          unregister(pid);
+        }
+        return Err(BackendError::Exited {
+          command: self.command.clone(),
+          status: status.to_string(),
+        });
+      }
+
+      if is_ready(&client, &probe_url).await {
+        vlog!("Backend is ready after {:.1?}", started.elapsed());
+        *process = Some(child);
+        return Ok(());
+      }
+
+      if started.elapsed() >= self.ready_timeout {
+        if let Some(pid) = pid {
+          unregister(pid);
+          terminate(pid);
+        }
+        return Err(BackendError::Timeout {
+          url: self.url.clone(),
+          seconds: self.ready_timeout.as_secs(),
+        });
+      }
+
+      tokio::time::sleep(POLL_INTERVAL).await;
+    }
+  }
+
+  fn spawn_error(&self, error: impl ToString) -> BackendError {
+    return BackendError::Spawn {
+      command: self.command.clone(),
+      error: error.to_string(),
+    };
+  }
+}
+
+impl Drop for ManagedBackend {
+  fn drop(&mut self) {
+    let Some(child) = self.process.get_mut().take() else {
+      return;
+    };
+    if let Some(pid) = child.id() {
+      vlog!("Stopping backend: {}", self.command);
+      unregister(pid);
+      terminate(pid);
+    }
+  }
+}
+
+/// Builds the client that polls the server for readiness.
+///
+/// A `unix:///path/to.sock` URL is polled over the socket.
+///
+/// # Arguments
+///
+/// * `url` - The URL the server answers at
+///
+/// # Returns
+///
+/// The client and the HTTP URL it sends requests to, or an error if the
+/// client cannot be built.
+fn probe_client(url: &str) -> Result<(reqwest::Client, String), String> {
+  let builder = reqwest::Client::builder()
+    .timeout(REQUEST_TIMEOUT)
+    .no_proxy();
+  let Some(socket_path) = url.strip_prefix(UNIX_SOCKET_SCHEME) else {
+    let client = builder.build().map_err(|e| e.to_string())?;
+    return Ok((client, url.to_string()));
+  };
+
+  #[cfg(unix)]
+  {
+    let client = builder
+      .unix_socket(socket_path)
+      .build()
+      .map_err(|e| e.to_string())?;
+    return Ok((client, String::from(UNIX_SOCKET_HTTP_BASE)));
+  }
+  #[cfg(not(unix))]
+  {
+    let _ = builder;
+    return Err(format!(
+      "Unix domain sockets are not supported on this platform: {}",
+      socket_path
+    ));
+  }
+}
+
+/// Checks whether the server at a URL is ready to answer requests.
+///
+/// Servers with a `/health` endpoint (llama.cpp, vLLM) are ready once it
+/// succeeds; llama.cpp answers 503 while the model loads. Other servers
+/// are ready once they answer at all.
+async fn is_ready(client: &reqwest::Client, url: &str) -> bool {
+  let health_url = format!("{}/health", url.trim_end_matches('/'));
+  return match client.get(&health_url).send().await {
+    Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => {
+      client
+        .get(url)
+        .send()
+        .await
+        .is_ok_and(|response| !response.status().is_server_error())
+    }
+    Ok(response) => response.status().is_success(),
+    Err(_) => false,
+  };
+}
+
+/// Asks a server and the processes it started to exit.
+///
+/// Uses blocking calls, so it works whether or not a Tokio runtime is
+/// still running.
+fn terminate(pid: u32) {
+  dlog!("Terminating backend process group {}", pid);
+  #[cfg(unix)]
+  let mut command = {
+    let mut command = std::process::Command::new("kill");
+    command.args(["-TERM", "--", &format!("-{}", pid)]);
+    command
+  };
+  #[cfg(not(unix))]
+  let mut command = {
+    let mut command = std::process::Command::new("taskkill");
+    command.args(["/T", "/F", "/PID", &pid.to_string()]);
+    command
+  };
+  let _ = command
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status();
+}
@@ -0,0 +1,39 @@
+use thiserror::Error;
+
+use crate::app::errors::ErrorKind;
+use crate::t;
+
+/// Benchmark errors.
+///
+/// Represents errors that prevent a benchmark from running.
+#[derive(Error, Debug)]
+pub enum BenchError {
+  #[error(
+    "{}",
+    t!("error-bench-read-directory", path = .0.as_str(), error = .1.as_str())
+  )]
+  ReadDirectory(String, String),
+
+  #[error("{}", t!("error-bench-no-cases", path = .0.as_str()))]
+  NoCases(String),
+
+  #[error(
+    "{}",
+    t!("error-bench-read-reference", path = .0.as_str(), error = .1.as_str())
+  )]
+  ReadReference(String, String),
+}
+
+impl BenchError {
+  /// Gets the category of the error.
+  ///
+  /// # Returns
+  ///
+  /// The `ErrorKind` deciding the exit status.
+  pub fn kind(&self) -> ErrorKind {
+    return ErrorKind::Input;
+  }
+}
+
+/// Result type for benchmark operations.
+pub type BenchResult<T> = Result<T, BenchError>;
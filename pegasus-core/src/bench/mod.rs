@@ -0,0 +1,288 @@
+//! Refinement quality benchmarks.
+//!
+//! `bench` refines every case in a directory and compares the results with
+//! references written by hand, so models, prompts and presets (selected
+//! with `--set`) can be compared by their error rates (see
+//! [`Accuracy`]). A case is a reference `<name>.expected.txt` and an input
+//! next to it: plain text `<name>.txt`, or a transcription `<name>.json`,
+//! `<name>.srt` or `<name>.vtt`.
+//!
+//! ## Main Components
+//!
+//! - [`find_cases`]: Collects the cases of a directory
+//! - [`run`]: Refines and scores the cases
+//! - [`BenchReport`]: Error rates per case and over all cases
+//! - [`BenchError`](errors::BenchError): Error types for benchmarks
+
+pub mod errors;
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::app::App;
+use crate::bench::errors::{BenchError, BenchResult};
+use crate::logging;
+use crate::output::accuracy::Accuracy;
+use crate::output::format::OutputFormat;
+use crate::{elog, vlog};
+
+/// Suffix of reference files.
+const REFERENCE_SUFFIX: &str = ".expected.txt";
+
+/// Extensions of inputs, in the order they are looked for.
+const INPUT_EXTENSIONS: [&str; 4] = ["txt", "json", "srt", "vtt"];
+
+/// An input and the refinement it is expected to produce.
+#[derive(Debug, Clone)]
+pub struct BenchCase {
+  /// Name of the case, the file name without extension
+  pub name: String,
+  /// Path of the input
+  pub input: PathBuf,
+  /// Path of the reference
+  pub reference: PathBuf,
+}
+
+/// Outcome of a case.
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+  /// Name of the case
+  pub name: String,
+  /// Errors of the refinement, or why the case failed
+  pub accuracy: Result<Accuracy, String>,
+  /// How long the refinement took in seconds
+  pub seconds: f64,
+}
+
+/// Outcome of a benchmark.
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+  /// Outcome of each case, by name
+  pub cases: Vec<CaseResult>,
+  /// Errors of all successful cases together
+  pub total: Accuracy,
+  /// How long all refinements took in seconds
+  pub seconds: f64,
+}
+
+impl BenchReport {
+  /// Gets the number of cases that could not be refined.
+  ///
+  /// # Returns
+  ///
+  /// The number of failed cases.
+  pub fn failed(&self) -> usize {
+    return self
+      .cases
+      .iter()
+      .filter(|case| case.accuracy.is_err())
+      .count();
+  }
+
+  /// Formats the report.
+  ///
+  /// Rates over all cases are computed from the summed edit counts, so
+  /// long cases weigh more than short ones.
+  ///
+  /// # Arguments
+  ///
+  /// * `format` - The desired output format
+  ///
+  /// # Returns
+  ///
+  /// A table of the cases and the total, or a JSON object.
+  pub fn format(&self, format: OutputFormat) -> String {
+    if format == OutputFormat::Json {
+      let cases: Vec<serde_json::Value> = self
+        .cases
+        .iter()
+        .map(|case| {
+          let mut value = match &case.accuracy {
+            Ok(accuracy) => accuracy.to_json(),
+            Err(e) => serde_json::json!({ "error": e }),
+          };
+          value["name"] = serde_json::json!(case.name);
+          value["seconds"] = serde_json::json!(case.seconds);
+          return value;
+        })
+        .collect();
+      let mut total = self.total.to_json();
+      total["seconds"] = serde_json::json!(self.seconds);
+      return serde_json::json!({ "cases": cases, "total": total }).to_string();
+    }
+
+    let width = self
+      .cases
+      .iter()
+      .map(|case| case.name.chars().count())
+      .chain(std::iter::once(5))
+      .max()
+      .unwrap_or(5);
+    let mut lines = vec![format!(
+      "{:<width$}  {:>6}  {:>6}  {:>6}  {:>5}  {:>5}  {:>5}  {:>7}",
+      "Case", "WER", "CER", "nWER", "Sub", "Del", "Ins", "Time"
+    )];
+    for case in &self.cases {
+      lines.push(match &case.accuracy {
+        Ok(accuracy) => accuracy_row(&case.name, accuracy, case.seconds, width),
+        Err(e) => format!("{:<width$}  failed: {}", case.name, e),
+      });
+    }
+    lines.push(accuracy_row("Total", &self.total, self.seconds, width));
+    return lines.join("\n");
+  }
+}
+
+/// Collects the cases of a benchmark directory.
+///
+/// References without an input are skipped with a warning.
+///
+/// # Arguments
+///
+/// * `directory` - The benchmark directory
+///
+/// # Returns
+///
+/// A `BenchResult<Vec<BenchCase>>` containing the cases sorted by name, or
+/// an error if the directory cannot be read or holds no cases.
+pub fn find_cases(directory: &Path) -> BenchResult<Vec<BenchCase>> {
+  let display = directory.display().to_string();
+  let entries = std::fs::read_dir(directory)
+    .map_err(|e| BenchError::ReadDirectory(display.clone(), e.to_string()))?;
+
+  let mut cases = Vec::new();
+  for entry in entries.flatten() {
+    let file_name = entry.file_name().to_string_lossy().to_string();
+    let Some(name) = file_name.strip_suffix(REFERENCE_SUFFIX) else {
+      continue;
+    };
+    let input = INPUT_EXTENSIONS
+      .iter()
+      .map(|extension| directory.join(format!("{}.{}", name, extension)))
+      .find(|path| path.is_file());
+    match input {
+      Some(input) => cases.push(BenchCase {
+        name: name.to_string(),
+        input,
+        reference: entry.path(),
+      }),
+      None => elog!(
+        logging::WARNING,
+        "Skipping {}, which has no input next to it",
+        file_name
+      ),
+    }
+  }
+
+  if cases.is_empty() {
+    return Err(BenchError::NoCases(display));
+  }
+  cases.sort_by(|first, second| first.name.cmp(&second.name));
+  return Ok(cases);
+}
+
+/// Refines the cases of a benchmark directory and scores the results.
+///
+/// Cases run one after another, so their durations are comparable. A case
+/// that fails to refine is recorded as failed and left out of the total.
+/// Once the application is cancelled, the report covers the cases
+/// finished before.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+/// * `directory` - The benchmark directory
+///
+/// # Returns
+///
+/// A `BenchResult<BenchReport>` with the scores, or an error if the cases
+/// cannot be collected or a reference cannot be read.
+pub async fn run(app: &App, directory: &Path) -> BenchResult<BenchReport> {
+  let cases = find_cases(directory)?;
+  let mut report = BenchReport {
+    cases: Vec::new(),
+    total: Accuracy::default(),
+    seconds: 0.0,
+  };
+
+  for case in cases {
+    if app.cancellation().is_cancelled() {
+      break;
+    }
+    let reference =
+      tokio::fs::read_to_string(&case.reference)
+        .await
+        .map_err(|e| {
+          BenchError::ReadReference(
+            case.reference.display().to_string(),
+            e.to_string(),
+          )
+        })?;
+
+    vlog!("Refining benchmark case {}", case.name);
+    let started = Instant::now();
+    let refined = refine_case(app, &case).await;
+    if app.cancellation().is_cancelled() {
+      break;
+    }
+    let seconds = started.elapsed().as_secs_f64();
+    report.seconds += seconds;
+
+    let accuracy = refined.map(|refined| {
+      return Accuracy::measure(&reference, &refined);
+    });
+    match &accuracy {
+      Ok(accuracy) => {
+        vlog!("{}: {}", case.name, accuracy);
+        report.total += *accuracy;
+      }
+      Err(e) => elog!(logging::WARNING, "{}: {}", case.name, e),
+    }
+    report.cases.push(CaseResult {
+      name: case.name,
+      accuracy,
+      seconds,
+    });
+  }
+  return Ok(report);
+}
+
+/// Refines the input of a case like `--file` or `whisper-transcribe`
+/// would, depending on its extension.
+///
+/// Returns the error message if refinement fails.
+async fn refine_case(app: &App, case: &BenchCase) -> Result<String, String> {
+  let input = Some(case.input.to_string_lossy().to_string());
+  let is_text = case
+    .input
+    .extension()
+    .is_some_and(|extension| extension == "txt");
+  let result = if is_text {
+    app.refine_text(None, input, OutputFormat::Text).await
+  } else {
+    app
+      .refine_whisper_transcription(None, input, OutputFormat::Text)
+      .await
+  };
+  return result.map_err(|e| e.to_string());
+}
+
+/// Formats the scores of a case as a table row.
+fn accuracy_row(
+  name: &str,
+  accuracy: &Accuracy,
+  seconds: f64,
+  width: usize,
+) -> String {
+  return format!(
+    "{:<width$}  {:>5.1}%  {:>5.1}%  {:>5.1}%  {:>5}  {:>5}  {:>5}  {:>6.1}s",
+    name,
+    accuracy.word_error_rate() * 100.0,
+    accuracy.character_error_rate() * 100.0,
+    accuracy.normalized_words.rate() * 100.0,
+    accuracy.words.substitutions,
+    accuracy.words.deletions,
+    accuracy.words.insertions,
+    seconds
+  );
+}
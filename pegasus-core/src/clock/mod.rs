@@ -0,0 +1,314 @@
+//! Injectable time and randomness.
+//!
+//! Retry backoff, cache expiry and the names of temporary files go
+//! through [`now`], [`sleep`] and [`random`] instead of the system clock
+//! and random number generator directly. By default these are the real
+//! ones; [`scope`] runs a future with others, like the request ID, so a
+//! test can run retries without waiting, move time past a cache's expiry
+//! and get the same file names on every run. Tasks spawned from the
+//! future use the system sources again.
+//!
+//! ## Main Components
+//!
+//! - [`Clock`] and [`Rng`]: Sources of time and randomness
+//! - [`SystemClock`] and [`SystemRng`]: The real sources
+//! - [`ManualClock`] and [`SeededRng`]: Deterministic sources for tests
+//! - [`scope`]: Runs a future with other sources
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+tokio::task_local! {
+  static SOURCES: Sources;
+}
+
+/// A future returned by [`Clock::sleep`].
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A source of the current time.
+pub trait Clock: Send + Sync {
+  /// Gets the current time.
+  ///
+  /// # Returns
+  ///
+  /// The time in UTC.
+  fn now(&self) -> DateTime<Utc>;
+
+  /// Waits for a duration.
+  ///
+  /// # Arguments
+  ///
+  /// * `duration` - How long to wait
+  ///
+  /// # Returns
+  ///
+  /// A future completing after the duration.
+  fn sleep(&self, duration: Duration) -> SleepFuture;
+}
+
+/// A source of random numbers.
+pub trait Rng: Send + Sync {
+  /// Gets the next random number.
+  ///
+  /// # Returns
+  ///
+  /// A number that is uniformly distributed over all `u64` values.
+  fn next_u64(&self) -> u64;
+}
+
+/// The system clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> DateTime<Utc> {
+    return Utc::now();
+  }
+
+  fn sleep(&self, duration: Duration) -> SleepFuture {
+    return Box::pin(tokio::time::sleep(duration));
+  }
+}
+
+/// The system random number generator.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemRng;
+
+impl Rng for SystemRng {
+  fn next_u64(&self) -> u64 {
+    return uuid::Uuid::new_v4().as_u64_pair().0;
+  }
+}
+
+/// A clock that only moves when told to.
+///
+/// Sleeping returns at once and moves the clock forward by the duration,
+/// so code that backs off between retries runs without waiting while the
+/// time it sees passes as usual.
+#[derive(Debug)]
+pub struct ManualClock {
+  now: Mutex<DateTime<Utc>>,
+  slept: Mutex<Vec<Duration>>,
+}
+
+impl ManualClock {
+  /// Creates a clock standing at the given time.
+  ///
+  /// # Arguments
+  ///
+  /// * `start` - The time the clock shows
+  ///
+  /// # Returns
+  ///
+  /// A new `ManualClock` instance.
+  pub fn new(start: DateTime<Utc>) -> Self {
+    return ManualClock {
+      now: Mutex::new(start),
+      slept: Mutex::new(Vec::new()),
+    };
+  }
+
+  /// Moves the clock forward.
+  ///
+  /// # Arguments
+  ///
+  /// * `duration` - How far to move it
+  pub fn advance(&self, duration: Duration) {
+    let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+    *now +=
+      chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX);
+  }
+
+  /// Gets the durations slept so far.
+  ///
+  /// # Returns
+  ///
+  /// The durations in the order they were slept, such as the delays
+  /// between retries.
+  pub fn slept(&self) -> Vec<Duration> {
+    return self.slept.lock().unwrap_or_else(|e| e.into_inner()).clone();
+  }
+}
+
+impl Clock for ManualClock {
+  fn now(&self) -> DateTime<Utc> {
+    return *self.now.lock().unwrap_or_else(|e| e.into_inner());
+  }
+
+  fn sleep(&self, duration: Duration) -> SleepFuture {
+    self
+      .slept
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .push(duration);
+    self.advance(duration);
+    return Box::pin(std::future::ready(()));
+  }
+}
+
+/// A random number generator giving the same numbers for the same seed.
+///
+/// Uses SplitMix64, which is fast and good enough for names and jitter;
+/// it must not be used where randomness is a security property.
+#[derive(Debug)]
+pub struct SeededRng {
+  state: AtomicU64,
+}
+
+impl SeededRng {
+  /// Creates a generator from a seed.
+  ///
+  /// # Arguments
+  ///
+  /// * `seed` - The seed
+  ///
+  /// # Returns
+  ///
+  /// A new `SeededRng` instance.
+  pub fn new(seed: u64) -> Self {
+    return SeededRng {
+      state: AtomicU64::new(seed),
+    };
+  }
+}
+
+impl Rng for SeededRng {
+  fn next_u64(&self) -> u64 {
+    let mut z = self
+      .state
+      .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+      .wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    return z ^ (z >> 31);
+  }
+}
+
+/// The clock and random number generator of a [`scope`].
+#[derive(Clone)]
+struct Sources {
+  clock: Arc<dyn Clock>,
+  rng: Arc<dyn Rng>,
+}
+
+/// Runs a future with the given clock and random number generator.
+///
+/// # Arguments
+///
+/// * `clock` - The clock for [`now`] and [`sleep`]
+/// * `rng` - The random number generator for [`random`]
+/// * `future` - The future to run
+///
+/// # Returns
+///
+/// The output of the future.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use pegasus_core::clock::{self, ManualClock, SeededRng};
+///
+/// # async fn run() {
+/// let start = chrono::DateTime::UNIX_EPOCH;
+/// let manual = Arc::new(ManualClock::new(start));
+/// let rng = Arc::new(SeededRng::new(7));
+/// clock::scope(manual.clone(), rng, async {
+///   clock::sleep(Duration::from_secs(60)).await;
+///   assert_eq!(clock::timestamp(), 60);
+/// })
+/// .await;
+/// assert_eq!(manual.slept(), vec![Duration::from_secs(60)]);
+/// # }
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(run());
+/// ```
+pub async fn scope<F: Future>(
+  clock: Arc<dyn Clock>,
+  rng: Arc<dyn Rng>,
+  future: F,
+) -> F::Output {
+  return SOURCES.scope(Sources { clock, rng }, future).await;
+}
+
+/// Gets the current time from the clock in scope.
+///
+/// # Returns
+///
+/// The time in UTC.
+pub fn now() -> DateTime<Utc> {
+  return SOURCES
+    .try_with(|sources| sources.clock.now())
+    .unwrap_or_else(|_| Utc::now());
+}
+
+/// Gets the current time from the clock in scope as a Unix timestamp.
+///
+/// # Returns
+///
+/// The seconds since the Unix epoch.
+pub fn timestamp() -> i64 {
+  return now().timestamp();
+}
+
+/// Waits for a duration on the clock in scope.
+///
+/// # Arguments
+///
+/// * `duration` - How long to wait
+pub async fn sleep(duration: Duration) {
+  let sleep = SOURCES
+    .try_with(|sources| sources.clock.sleep(duration))
+    .unwrap_or_else(|_| SystemClock.sleep(duration));
+  sleep.await;
+}
+
+/// Gets a random number from the generator in scope.
+///
+/// # Returns
+///
+/// A number that is uniformly distributed over all `u64` values.
+pub fn random() -> u64 {
+  return SOURCES
+    .try_with(|sources| sources.rng.next_u64())
+    .unwrap_or_else(|_| SystemRng.next_u64());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn repeats_numbers_for_a_seed() {
+    let first = SeededRng::new(42);
+    let second = SeededRng::new(42);
+    let numbers: Vec<u64> = (0..4).map(|_| first.next_u64()).collect();
+    assert_eq!(
+      numbers,
+      (0..4).map(|_| second.next_u64()).collect::<Vec<_>>()
+    );
+    assert_ne!(numbers[0], SeededRng::new(43).next_u64());
+  }
+
+  #[tokio::test]
+  async fn uses_the_sources_in_scope() {
+    let start = DateTime::UNIX_EPOCH;
+    let manual = Arc::new(ManualClock::new(start));
+    let expected = SeededRng::new(7).next_u64();
+    let number = scope(manual.clone(), Arc::new(SeededRng::new(7)), async {
+      sleep(Duration::from_secs(90)).await;
+      assert_eq!(timestamp(), 90);
+      return random();
+    })
+    .await;
+    assert_eq!(number, expected);
+    assert_eq!(manual.slept(), [Duration::from_secs(90)]);
+    assert_ne!(now(), start);
+  }
+}
@@ -0,0 +1,153 @@
+//! Per-project configuration discovery.
+//!
+//! Looks for a `.pegasus.toml` file in the current directory and its
+//! ancestors, similar to how formatters find their configuration. Values in
+//! the project file are merged over the user configuration, so a project can
+//! override only the settings it cares about (dictionary, model, ...).
+//!
+//! A project file comes with the repository it is found in, so it is not
+//! trusted: settings that run commands or choose where requests and API
+//! keys are sent are ignored in it, with a warning.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::errors::ConfigResult;
+use crate::config::resolver::read_table;
+use crate::{elog, logging, vlog};
+
+/// File name of the per-project configuration file.
+pub const PROJECT_CONFIG_NAME: &str = ".pegasus.toml";
+
+/// Keys holding file paths, which are resolved relative to the project file.
+const PATH_KEYS: &[(&str, &str)] = &[
+  ("dictionary", "path"),
+  ("llm", "api_key_file"),
+  ("transcription", "api_key_file"),
+];
+
+/// Keys that run commands or redirect requests, which only the system and
+/// user configuration, the environment and the command line may set.
+pub const UNTRUSTED_KEYS: &[(&str, &str)] = &[
+  ("llm", "url"),
+  ("llm", "fallback_url"),
+  ("llm", "api_key_command"),
+  ("network", "proxy"),
+  ("backend", "command"),
+  ("transcription", "url"),
+  ("transcription", "api_key_command"),
+  ("transcription", "silence_command"),
+  ("dictation", "record_command"),
+  ("server", "paste_command"),
+  ("output", "clipboard_command"),
+  ("output", "type_command"),
+  ("output", "notify_command"),
+  ("output", "pager"),
+  ("output", "webhook_url"),
+];
+
+/// Checks whether a `section.key=value` override sets one of the
+/// [`UNTRUSTED_KEYS`].
+///
+/// # Arguments
+///
+/// * `assignment` - The override assignment
+///
+/// # Returns
+///
+/// `true` if the override sets a key that runs commands or redirects
+/// requests.
+pub fn is_untrusted_override(assignment: &str) -> bool {
+  let path = assignment
+    .split_once('=')
+    .map_or(assignment, |(path, _)| path);
+  return path.trim().split_once('.').is_some_and(|(section, key)| {
+    return UNTRUSTED_KEYS.contains(&(section, key));
+  });
+}
+
+/// Finds the nearest project configuration file.
+///
+/// Walks from the current directory up to the filesystem root and returns
+/// the first `.pegasus.toml` found.
+///
+/// # Returns
+///
+/// The path to the project configuration file, or `None` if there is none.
+pub fn find_project_config() -> Option<PathBuf> {
+  let current_dir = std::env::current_dir().ok()?;
+  return current_dir
+    .ancestors()
+    .map(|dir| dir.join(PROJECT_CONFIG_NAME))
+    .find(|path| path.is_file());
+}
+
+/// Reads a project configuration file into a table.
+///
+/// Relative file paths inside the project file are resolved against the
+/// directory containing it, so a project dictionary can live next to it.
+/// Keys listed in [`UNTRUSTED_KEYS`] are dropped.
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the project configuration file
+///
+/// # Returns
+///
+/// A `ConfigResult<toml::Table>` containing the parsed table or an error.
+pub async fn read_project_table(
+  config_path: &Path,
+) -> ConfigResult<toml::Table> {
+  vlog!(
+    "Loading project configuration from: {}",
+    config_path.display()
+  );
+
+  let mut table = read_table(config_path).await?;
+  remove_untrusted_keys(&mut table, config_path);
+
+  let Some(project_dir) = config_path.parent() else {
+    return Ok(table);
+  };
+
+  for (section, key) in PATH_KEYS {
+    let Some(toml::Value::Table(section_table)) = table.get_mut(*section)
+    else {
+      continue;
+    };
+    let Some(toml::Value::String(path)) = section_table.get_mut(*key) else {
+      continue;
+    };
+    if !path.is_empty() && Path::new(path.as_str()).is_relative() {
+      *path = project_dir
+        .join(path.as_str())
+        .to_string_lossy()
+        .to_string();
+    }
+  }
+
+  return Ok(table);
+}
+
+/// Removes the keys a project file may not set, warning about each one.
+///
+/// # Arguments
+///
+/// * `table` - The project configuration table
+/// * `config_path` - Path to the project configuration file
+fn remove_untrusted_keys(table: &mut toml::Table, config_path: &Path) {
+  for (section, key) in UNTRUSTED_KEYS {
+    let Some(toml::Value::Table(section_table)) = table.get_mut(*section)
+    else {
+      continue;
+    };
+    if section_table.remove(*key).is_some() {
+      elog!(
+        logging::WARNING,
+        "Ignoring {}.{} in {}; set it in the user configuration instead",
+        section,
+        key,
+        config_path.display()
+      );
+    }
+  }
+}
@@ -0,0 +1,40 @@
+use thiserror::Error;
+
+use crate::app::errors::ErrorKind;
+use crate::t;
+
+/// Configuration-related errors.
+///
+/// Represents errors that can occur during configuration loading and parsing.
+#[derive(Error, Debug)]
+pub enum ConfigError {
+  #[error("{}", t!("error-config-file-read", path = .0.as_str()))]
+  FileRead(String),
+
+  #[error("{}", t!("error-config-file-write", path = .0.as_str()))]
+  FileWrite(String),
+
+  #[error("{}", t!("error-config-parse", error = .0.as_str()))]
+  Parse(String),
+
+  #[error("{}", t!("error-config-invalid", problems = .0.as_str()))]
+  Invalid(String),
+}
+
+impl ConfigError {
+  /// Gets the category of the error.
+  ///
+  /// # Returns
+  ///
+  /// `ErrorKind::Validation` for invalid values, `ErrorKind::Config`
+  /// otherwise.
+  pub fn kind(&self) -> ErrorKind {
+    return match self {
+      ConfigError::Invalid(_) => ErrorKind::Validation,
+      _ => ErrorKind::Config,
+    };
+  }
+}
+
+/// Result type for configuration operations.
+pub type ConfigResult<T> = Result<T, ConfigError>;
@@ -0,0 +1,137 @@
+//! Migration of configuration files written for older versions.
+//!
+//! Every configuration file carries a top-level `config_version`. Files
+//! without one predate versioning and are treated as version 1. When a file
+//! is older than [`CURRENT_CONFIG_VERSION`], renamed keys are moved to their
+//! new location in memory and a deprecation notice is produced for each, so
+//! old files keep working until they are rewritten with
+//! `pegasus config migrate`.
+
+use std::path::Path;
+
+use crate::config::errors::{ConfigError, ConfigResult};
+use crate::files::operations;
+
+/// The configuration layout version written by this release.
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+
+/// Version assumed for files without a `config_version` key.
+const UNVERSIONED_CONFIG_VERSION: u32 = 1;
+
+/// A key that moved to a new location in a given version.
+struct KeyRename {
+  version: u32,
+  from: (&'static str, &'static str),
+  to: (&'static str, &'static str),
+}
+
+/// All key renames, ordered by version.
+const KEY_RENAMES: &[KeyRename] = &[KeyRename {
+  version: 2,
+  from: ("general", "custom_dictionary_path"),
+  to: ("dictionary", "path"),
+}];
+
+/// Migrates a configuration table to the current layout in place.
+///
+/// # Arguments
+///
+/// * `table` - The configuration table read from a file
+///
+/// # Returns
+///
+/// A deprecation notice for every key that was moved.
+pub fn migrate(table: &mut toml::Table) -> Vec<String> {
+  let version = table
+    .get("config_version")
+    .and_then(|value| value.as_integer())
+    .map_or(UNVERSIONED_CONFIG_VERSION, |version| version as u32);
+
+  if version >= CURRENT_CONFIG_VERSION {
+    return Vec::new();
+  }
+
+  let mut notices = Vec::new();
+
+  for rename in KEY_RENAMES.iter().filter(|r| r.version > version) {
+    let (from_section, from_key) = rename.from;
+    let (to_section, to_key) = rename.to;
+
+    let Some(toml::Value::Table(section)) = table.get_mut(from_section) else {
+      continue;
+    };
+    let Some(value) = section.remove(from_key) else {
+      continue;
+    };
+    if section.is_empty() {
+      table.remove(from_section);
+    }
+
+    let target = table
+      .entry(to_section)
+      .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let toml::Value::Table(target) = target {
+      target.entry(to_key).or_insert(value);
+    }
+
+    notices.push(format!(
+      "'{}.{}' is deprecated, use '{}.{}' instead",
+      from_section, from_key, to_section, to_key
+    ));
+  }
+
+  table.insert(
+    "config_version".to_string(),
+    toml::Value::Integer(i64::from(CURRENT_CONFIG_VERSION)),
+  );
+
+  return notices;
+}
+
+/// Migrates a configuration file on disk to the current layout.
+///
+/// The original file is kept next to it with a `.bak` suffix. Comments are
+/// not preserved in the rewritten file. The file is rewritten atomically
+/// while holding an advisory lock.
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the configuration file
+///
+/// # Returns
+///
+/// A `ConfigResult<Vec<String>>` with the applied migration notices, empty
+/// if the file was already up to date.
+pub async fn migrate_file(config_path: &Path) -> ConfigResult<Vec<String>> {
+  let path = config_path.to_string_lossy();
+  let _lock = operations::lock_file(&path)
+    .await
+    .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+
+  let content = operations::read_to_string(&path)
+    .await
+    .map_err(|e| ConfigError::FileRead(e.to_string()))?;
+  let mut table: toml::Table =
+    toml::from_str(&content).map_err(|e| ConfigError::Parse(e.to_string()))?;
+
+  let version_before = table.get("config_version").cloned();
+  let notices = migrate(&mut table);
+  if notices.is_empty()
+    && version_before == table.get("config_version").cloned()
+  {
+    return Ok(notices);
+  }
+
+  let backup_path = format!("{}.bak", path);
+  operations::copy_file(&path, &backup_path)
+    .await
+    .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+
+  let migrated = toml::to_string_pretty(&table)
+    .map_err(|e| ConfigError::Parse(e.to_string()))?;
+  operations::write_string_atomic(&path, &migrated)
+    .await
+    .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+
+  return Ok(notices);
+}
@@ -0,0 +1,1910 @@
+//! Configuration management module with XDG compliance.
+//!
+//! This module handles loading, parsing, and accessing application configuration
+//! from TOML files stored in XDG-compliant directories. It provides default values
+//! for all settings and supports configuration reset operations.
+//!
+//! ## Configuration Sections
+//!
+//! - [`LLMConfig`]: LLM service settings
+//! - [`DictionaryConfig`]: Custom dictionary settings
+//! - [`WhisperTranscriptionConfig`]: Whisper transcription processing settings
+//! - [`NetworkConfig`]: Network resilience settings
+//! - [`InputConfig`]: Input reading settings
+//! - [`ServerConfig`]: Daemon settings
+//! - [`BackendConfig`]: LLM server spawned on demand
+//! - [`TranscriptionConfig`]: Speech-to-text service for audio input
+//! - [`DictationConfig`]: Recording and delivery for `pegasus dictate`
+//! - [`OutputConfig`]: Where results are written
+//! - [`UsageConfig`]: Usage statistics for `pegasus usage`
+//! - [`StyleConfig`]: Deterministic rewriting of refined text
+//! - [`ModelConfig`]: Per-model overrides of the LLM settings
+//!
+//! ## Configuration File Location
+//!
+//! Configuration is resolved in layers (see [`resolver`]), each overriding
+//! the previous one:
+//! - Built-in defaults
+//! - `$XDG_CONFIG_DIRS/pegasus/config.toml` (system)
+//! - `$XDG_CONFIG_HOME/pegasus/config.toml` (user)
+//! - `.pegasus.toml` in the current directory or its nearest ancestor
+//! - `PEGASUS_<SECTION>_<KEY>` environment variables
+//! - `--set section.key=value` command-line overrides
+//!
+//! Files written by Pegasus list every option commented out with its
+//! default value and a description (see [`template`]).
+//!
+//! A `[models."<name>"]` section holds settings for one model. When that
+//! model is selected with `[llm] model`, its settings replace the `[llm]`
+//! settings from configuration files, but not those from environment
+//! variables or command-line overrides.
+
+pub mod discovery;
+pub mod errors;
+pub mod migration;
+pub mod resolver;
+pub mod template;
+pub mod validation;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use xdg::BaseDirectories;
+
+use crate::audio::TranscriptionProvider;
+use crate::audio::split;
+use crate::config::errors::{ConfigError, ConfigResult};
+use crate::config::migration::CURRENT_CONFIG_VERSION;
+use crate::config::resolver::ConfigResolver;
+use crate::config::validation::Severity;
+use crate::dictation::{self, DictationTrigger};
+use crate::dictionary::MatchOptions;
+use crate::dictionary::glossary::GlossaryMode;
+use crate::files::operations;
+use crate::files::temporary::TemporaryFile;
+use crate::input::chunks::ChunkUnit;
+use crate::llm::client::LLMApi;
+use crate::llm::non_speech::NonSpeechPolicy;
+use crate::llm::output_limit::OverflowPolicy;
+use crate::llm::refusal::RefusalHandling;
+use crate::network::scheduler::BalanceStrategy;
+use crate::output::punctuation::UnicodePunctuation;
+use crate::output::sink;
+use crate::output::whitespace::{LineEnding, WhitespacePolicy};
+use crate::output::wrap::Wrap;
+use crate::secrets::ApiKeySource;
+use crate::{elog, logging};
+
+const DEFAULT_DIRECTORY: &str = "pegasus";
+const DEFAULT_CONFIG_NAME: &str = "config.toml";
+const DEFAULT_LLM_URL: &str = "http://127.0.0.1:8080";
+const DEFAULT_LLM_CONTEXT_PARAGRAPHS: usize = 0;
+const DEFAULT_LLM_CONTEXT_CHARACTERS: usize = 4000;
+const DEFAULT_LLM_TARGET_READING_LEVEL: f64 = 0.0;
+const DEFAULT_LLM_MAX_OUTPUT_CHARACTERS: usize = 100_000;
+const DEFAULT_LLM_PROBE_CAPABILITIES: bool = true;
+const DEFAULT_LLM_CONSTRAIN_OUTPUT: bool = false;
+const DEFAULT_LLM_KEEP_VERBATIM_TOKENS: bool = false;
+const DEFAULT_LLM_MIN_SIMILARITY: f64 = 0.0;
+const DEFAULT_LLM_RETRY_ANSWERS: bool = true;
+const DEFAULT_WHISPER_PROBABILITY_THRESHOLD: f64 = 0.7;
+const DEFAULT_WHISPER_DEDUPLICATE_SEGMENTS: bool = true;
+const DEFAULT_WHISPER_GAP_THRESHOLD_SECONDS: f64 = 5.0;
+const DEFAULT_WHISPER_LOGPROB_THRESHOLD: f64 = 0.0;
+const DEFAULT_INPUT_CHUNK_SIZE: usize = 8000;
+const DEFAULT_INPUT_CHUNK_OVERLAP_SENTENCES: usize = 2;
+const DEFAULT_INPUT_MAX_SIZE: u64 = 256 * 1024 * 1024;
+const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 3;
+const DEFAULT_CIRCUIT_BREAKER_WINDOW_SECONDS: u64 = 60;
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECONDS: u64 = 30;
+const DEFAULT_REQUEST_ID_HEADER: bool = false;
+const DEFAULT_NETWORK_RETRIES: u32 = 2;
+const DEFAULT_SERVER_DBUS: bool = true;
+const DEFAULT_SERVER_PASTE_COMMAND: &str = "wl-paste --no-newline";
+const DEFAULT_DICTATION_SINK: &str = "clipboard";
+const DEFAULT_OUTPUT_SINK: &str = "stdout";
+const DEFAULT_OUTPUT_WEBHOOK_RETRIES: u32 = 3;
+const DEFAULT_DICTIONARY_CASE_SENSITIVE: bool = false;
+const DEFAULT_DICTIONARY_WHOLE_WORD: bool = true;
+const DEFAULT_DICTIONARY_REGEX: bool = false;
+const DEFAULT_DICTIONARY_RECORD_CORRECTIONS: bool = true;
+const DEFAULT_OUTPUT_WRAP: usize = 0;
+const DEFAULT_OUTPUT_REFLOW: bool = true;
+const DEFAULT_OUTPUT_USE_PAGER: bool = true;
+const DEFAULT_BACKEND_READY_TIMEOUT_SECONDS: u64 = 120;
+const DEFAULT_TRANSCRIPTION_URL: &str = "http://127.0.0.1:8081";
+const DEFAULT_TRANSCRIPTION_MODEL: &str = "whisper-1";
+const DEFAULT_TRANSCRIPTION_MAX_UPLOAD_SIZE: u64 = 25 * 1024 * 1024;
+const DEFAULT_TRANSCRIPTION_CHUNK_SECONDS: f64 = 600.0;
+const DEFAULT_TRANSCRIPTION_CONCURRENCY: usize = 4;
+const DEFAULT_USAGE_ENABLED: bool = true;
+const DEFAULT_USAGE_COUNTERS: bool = false;
+const DEFAULT_USAGE_PROFILE: &str = "default";
+
+/// Main configuration structure for the Pegasus application.
+///
+/// This struct contains all configuration sections including LLM settings,
+/// dictionary settings, and Whisper transcription settings.
+///
+/// Every section and field is optional in the configuration file; missing
+/// values fall back to their defaults.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Config {
+  config_version: Option<u32>,
+  llm: LLMConfig,
+  whisper: WhisperTranscriptionConfig,
+  dictionary: DictionaryConfig,
+  input: InputConfig,
+  network: NetworkConfig,
+  server: ServerConfig,
+  backend: BackendConfig,
+  transcription: TranscriptionConfig,
+  dictation: DictationConfig,
+  output: OutputConfig,
+  usage: UsageConfig,
+  style: StyleConfig,
+  models: Option<BTreeMap<String, ModelConfig>>,
+}
+
+/// Configuration for the LLM service.
+///
+/// Contains settings for the LLM API endpoint, model, and API key, as well
+/// as where the API key is read from and how much of a session's earlier
+/// refinements is sent with each request.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct LLMConfig {
+  url: Option<String>,
+  model: Option<String>,
+  api_key: Option<String>,
+  api_key_source: Option<ApiKeySource>,
+  api_key_command: Option<String>,
+  api_key_file: Option<String>,
+  fallback_url: Option<String>,
+  context_paragraphs: Option<usize>,
+  context_characters: Option<usize>,
+  target_reading_level: Option<f64>,
+  max_output_characters: Option<usize>,
+  output_overflow: Option<OverflowPolicy>,
+  stop: Option<Vec<String>>,
+  tokenizer: Option<String>,
+  context_window: Option<usize>,
+  probe_capabilities: Option<bool>,
+  temperature: Option<f64>,
+  input_price: Option<f64>,
+  output_price: Option<f64>,
+  api: Option<LLMApi>,
+  grammar_file: Option<String>,
+  constrain_output: Option<bool>,
+  keep_verbatim_tokens: Option<bool>,
+  min_similarity: Option<f64>,
+  embeddings_model: Option<String>,
+  retry_answers: Option<bool>,
+  refusal: Option<RefusalHandling>,
+  endpoints: Option<Vec<String>>,
+  balance: Option<BalanceStrategy>,
+}
+
+/// Settings for a single model.
+///
+/// Configured as `[models."<name>"]` sections. Every key is also an `[llm]`
+/// key, which it replaces while the model is selected.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct ModelConfig {
+  temperature: Option<f64>,
+  context_window: Option<usize>,
+  tokenizer: Option<String>,
+  target_reading_level: Option<f64>,
+  max_output_characters: Option<usize>,
+  stop: Option<Vec<String>>,
+  input_price: Option<f64>,
+  output_price: Option<f64>,
+}
+
+/// Configuration for Whisper transcription processing.
+///
+/// Contains settings for processing Whisper JSON output to reduce
+/// hallucination using probability scores and timestamps.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct WhisperTranscriptionConfig {
+  probability_threshold: Option<f64>,
+  deduplicate_segments: Option<bool>,
+  gap_threshold_seconds: Option<f64>,
+  logprob_threshold: Option<f64>,
+}
+
+/// Configuration for reading input.
+///
+/// Contains settings for splitting long inputs into chunks that are refined
+/// one at a time.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct InputConfig {
+  chunk_size: Option<usize>,
+  chunk_overlap_sentences: Option<usize>,
+  chunk_unit: Option<ChunkUnit>,
+  max_size: Option<u64>,
+}
+
+/// Configuration for network resilience.
+///
+/// Contains proxy settings, circuit breaker settings that stop requests
+/// to an endpoint that keeps failing, and whether request IDs are sent.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct NetworkConfig {
+  proxy: Option<String>,
+  no_proxy: Option<String>,
+  circuit_breaker_threshold: Option<u32>,
+  circuit_breaker_window_seconds: Option<u64>,
+  circuit_breaker_cooldown_seconds: Option<u64>,
+  request_id_header: Option<bool>,
+  retries: Option<u32>,
+}
+
+/// Configuration for the daemon.
+///
+/// Contains settings that only apply when running `pegasus daemon`, and
+/// the token the CLI presents when forwarding requests to it.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct ServerConfig {
+  metrics_address: Option<String>,
+  tokens: Option<Vec<ServerToken>>,
+  client_token: Option<String>,
+  dbus: Option<bool>,
+  paste_command: Option<String>,
+}
+
+/// Configuration for an LLM server spawned on demand.
+///
+/// Contains the command that starts a local server answering at `[llm]
+/// url`, for machines where no server runs permanently.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct BackendConfig {
+  command: Option<String>,
+  ready_timeout_seconds: Option<u64>,
+}
+
+/// Configuration for transcribing audio.
+///
+/// Contains the speech-to-text service recordings are sent to before their
+/// transcription is refined, and how recordings too large for one upload
+/// are split.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct TranscriptionConfig {
+  provider: Option<TranscriptionProvider>,
+  url: Option<String>,
+  model: Option<String>,
+  language: Option<String>,
+  api_key: Option<String>,
+  api_key_source: Option<ApiKeySource>,
+  api_key_command: Option<String>,
+  api_key_file: Option<String>,
+  max_upload_size: Option<u64>,
+  chunk_seconds: Option<f64>,
+  silence_command: Option<String>,
+  concurrency: Option<usize>,
+}
+
+/// Configuration for dictation.
+///
+/// Contains what starts a recording in `pegasus dictate`, the command that
+/// records it, and the sinks the refined text is written to.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct DictationConfig {
+  trigger: Option<DictationTrigger>,
+  record_command: Option<String>,
+  sinks: Option<Vec<String>>,
+}
+
+/// Configuration for output.
+///
+/// Contains the sinks results are written to unless `--sink` is given, the
+/// commands behind the clipboard, typing and notification sinks, the
+/// webhook results are posted to, the pager for long results, and the
+/// line wrapping, newline and whitespace conventions of results.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct OutputConfig {
+  sinks: Option<Vec<String>>,
+  clipboard_command: Option<String>,
+  type_command: Option<String>,
+  notify_command: Option<String>,
+  webhook_url: Option<String>,
+  webhook_token: Option<String>,
+  webhook_retries: Option<u32>,
+  line_ending: Option<LineEnding>,
+  final_newline: Option<bool>,
+  collapse_blank_lines: Option<bool>,
+  trim_trailing_whitespace: Option<bool>,
+  wrap: Option<usize>,
+  reflow: Option<bool>,
+  use_pager: Option<bool>,
+  pager: Option<String>,
+}
+
+/// Configuration for usage statistics.
+///
+/// Contains whether runs are recorded for `pegasus usage`, and the profile
+/// they are reported under, so a project or `--set` can book its runs
+/// separately. Anonymous run counters are kept only when opted in.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct UsageConfig {
+  enabled: Option<bool>,
+  profile: Option<String>,
+  counters: Option<bool>,
+}
+
+/// Configuration for the style of refined text.
+///
+/// Contains how quotes, dashes and other punctuation are normalized after
+/// refinement, and what happens to emoji and non-speech markers.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct StyleConfig {
+  unicode_punctuation: Option<UnicodePunctuation>,
+  emoji: Option<NonSpeechPolicy>,
+  non_speech: Option<NonSpeechPolicy>,
+}
+
+/// A bearer token accepted by the daemon.
+///
+/// Configured as `[[server.tokens]]` entries. Requests made with the token
+/// are limited to `requests_per_minute` when it is set and non-zero.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ServerToken {
+  pub token: String,
+  pub requests_per_minute: Option<u32>,
+}
+
+/// Custom dictionary configuration.
+///
+/// Contains settings for the user's dictionary of domain terms.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct DictionaryConfig {
+  path: Option<String>,
+  case_sensitive: Option<bool>,
+  whole_word: Option<bool>,
+  regex: Option<bool>,
+  record_corrections: Option<bool>,
+  glossary: Option<GlossaryMode>,
+  spellcheck: Option<String>,
+}
+
+impl Config {
+  /// Loads configuration from all configuration layers.
+  ///
+  /// Missing files are skipped, so with no configuration at all the
+  /// default configuration is returned. The result is validated; warnings
+  /// are printed to stderr and errors are reported together.
+  ///
+  /// # Arguments
+  ///
+  /// * `overrides` - Command-line overrides in `section.key=value` form
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<Config>` containing the loaded configuration or an error.
+  pub async fn load(overrides: &[String]) -> ConfigResult<Config> {
+    let resolver = ConfigResolver::resolve(overrides).await?;
+    let config = resolver.build()?;
+
+    let mut errors = Vec::new();
+    for diagnostic in validation::validate(&resolver, &config) {
+      match diagnostic.severity {
+        Severity::Warning => {
+          elog!(logging::WARNING, "Warning: {}", diagnostic)
+        }
+        Severity::Error => errors.push(format!("  - {}", diagnostic)),
+      }
+    }
+
+    if !errors.is_empty() {
+      return Err(ConfigError::Invalid(errors.join("\n")));
+    }
+
+    return Ok(config);
+  }
+
+  /// Gets the LLM URL.
+  ///
+  /// Returns the configured URL or the default localhost URL if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the LLM URL.
+  pub fn get_llm_url(&self) -> String {
+    return self
+      .llm
+      .url
+      .clone()
+      .unwrap_or(String::from(DEFAULT_LLM_URL));
+  }
+
+  /// Gets the LLM model name.
+  ///
+  /// Returns the configured model name or None if not set.
+  ///
+  /// # Returns
+  ///
+  /// An `String` containing the model name.
+  pub fn get_llm_model(&self) -> String {
+    return self.llm.model.clone().unwrap_or_default();
+  }
+
+  /// Gets the LLM API key.
+  ///
+  /// Returns the configured API key or None if not set.
+  ///
+  /// # Returns
+  ///
+  /// An `String` containing the API key.
+  pub fn get_llm_api_key(&self) -> String {
+    return self.llm.api_key.clone().unwrap_or_default();
+  }
+
+  /// Gets the LLM API key source.
+  ///
+  /// Returns the configured source, or `Command` if only `api_key_command`
+  /// is set, `File` if only `api_key_file` is set, or `Config` otherwise.
+  ///
+  /// # Returns
+  ///
+  /// The `ApiKeySource` to read the API key from.
+  pub fn get_llm_api_key_source(&self) -> ApiKeySource {
+    if let Some(source) = self.llm.api_key_source {
+      return source;
+    }
+    if !self.get_llm_api_key_command().is_empty() {
+      return ApiKeySource::Command;
+    }
+    if !self.get_llm_api_key_file().is_empty() {
+      return ApiKeySource::File;
+    }
+    return ApiKeySource::Config;
+  }
+
+  /// Gets the LLM API key file path.
+  ///
+  /// Returns the configured path of a file containing the API key, or an
+  /// empty string if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the API key file path.
+  pub fn get_llm_api_key_file(&self) -> String {
+    return self.llm.api_key_file.clone().unwrap_or_default();
+  }
+
+  /// Gets the LLM API key command.
+  ///
+  /// Returns the configured command that prints the API key, or an empty
+  /// string if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the API key command.
+  pub fn get_llm_api_key_command(&self) -> String {
+    return self.llm.api_key_command.clone().unwrap_or_default();
+  }
+
+  /// Gets the fallback LLM URL.
+  ///
+  /// Returns the configured fallback URL or an empty string if not set.
+  /// The fallback endpoint is used when the primary endpoint fails.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the fallback LLM URL.
+  pub fn get_llm_fallback_url(&self) -> String {
+    return self.llm.fallback_url.clone().unwrap_or_default();
+  }
+
+  /// Gets the number of earlier paragraphs sent as context.
+  ///
+  /// Returns how many of the most recent refinements of an interactive
+  /// session or daemon connection are included with the next request.
+  /// Defaults to 0, which disables conversation context.
+  ///
+  /// # Returns
+  ///
+  /// A `usize` containing the number of paragraphs.
+  pub fn get_llm_context_paragraphs(&self) -> usize {
+    return self
+      .llm
+      .context_paragraphs
+      .unwrap_or(DEFAULT_LLM_CONTEXT_PARAGRAPHS);
+  }
+
+  /// Gets the character budget for conversation context.
+  ///
+  /// Returns the largest combined size in characters of the earlier
+  /// paragraphs and refinements sent with a request; older paragraphs that
+  /// do not fit are left out. Defaults to 4000 if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `usize` containing the budget in characters.
+  pub fn get_llm_context_characters(&self) -> usize {
+    return self
+      .llm
+      .context_characters
+      .unwrap_or(DEFAULT_LLM_CONTEXT_CHARACTERS);
+  }
+
+  /// Gets the reading level refined text should be written at.
+  ///
+  /// Returns the Flesch-Kincaid grade level the LLM is asked to target;
+  /// results that miss it by far are refined again. Defaults to 0, which
+  /// keeps the speaker's reading level.
+  ///
+  /// # Returns
+  ///
+  /// A `f64` containing the target grade level.
+  pub fn get_llm_target_reading_level(&self) -> f64 {
+    return self
+      .llm
+      .target_reading_level
+      .unwrap_or(DEFAULT_LLM_TARGET_READING_LEVEL);
+  }
+
+  /// Gets the largest answer accepted from the LLM.
+  ///
+  /// Returns the size in characters above which an answer is treated as
+  /// runaway generation. Defaults to 100000 if not set; 0 disables the
+  /// limit.
+  ///
+  /// # Returns
+  ///
+  /// A `usize` containing the limit in characters.
+  pub fn get_llm_max_output_characters(&self) -> usize {
+    return self
+      .llm
+      .max_output_characters
+      .unwrap_or(DEFAULT_LLM_MAX_OUTPUT_CHARACTERS);
+  }
+
+  /// Gets what to do with answers longer than the limit.
+  ///
+  /// Returns the configured policy, `Truncate` (cut at the last sentence
+  /// that fits) if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `OverflowPolicy` for long answers.
+  pub fn get_llm_output_overflow(&self) -> OverflowPolicy {
+    return self.llm.output_overflow.unwrap_or_default();
+  }
+
+  /// Gets the sequences at which the LLM stops generating.
+  ///
+  /// Returns the configured stop sequences, sent with every request, or an
+  /// empty list if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `Vec<String>` containing the stop sequences.
+  pub fn get_llm_stop(&self) -> Vec<String> {
+    return self.llm.stop.clone().unwrap_or_default();
+  }
+
+  /// Gets the tokenizer used to count tokens of the configured model.
+  ///
+  /// Returns the configured vocabulary name (e.g. `cl100k_base`), or
+  /// `estimate` for the character-based estimate. Defaults to an empty
+  /// string if not set, which picks the vocabulary of the model and falls
+  /// back to the estimate for unknown models.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the tokenizer name.
+  pub fn get_llm_tokenizer(&self) -> String {
+    return self.llm.tokenizer.clone().unwrap_or_default();
+  }
+
+  /// Gets the context window of the model.
+  ///
+  /// Returns the number of tokens the model accepts, which prompts are
+  /// checked against before sending. Defaults to 0 if not set, which uses
+  /// the context window detected from the endpoint, if any.
+  ///
+  /// # Returns
+  ///
+  /// A `usize` containing the context window in tokens.
+  pub fn get_llm_context_window(&self) -> usize {
+    return self.llm.context_window.unwrap_or_default();
+  }
+
+  /// Checks if the endpoint is probed for its context window and features.
+  ///
+  /// Queries llama.cpp `/props`, Ollama `/api/show` or the model metadata
+  /// of OpenAI-compatible servers before the first request, caching the
+  /// result for a day. Defaults to `true` if not set.
+  ///
+  /// # Returns
+  ///
+  /// `true` if the endpoint is probed, `false` otherwise.
+  pub fn get_llm_probe_capabilities(&self) -> bool {
+    return self
+      .llm
+      .probe_capabilities
+      .unwrap_or(DEFAULT_LLM_PROBE_CAPABILITIES);
+  }
+
+  /// Gets the sampling temperature.
+  ///
+  /// # Returns
+  ///
+  /// The temperature sent with every request, or `None` to use the
+  /// default of the service.
+  pub fn get_llm_temperature(&self) -> Option<f64> {
+    return self.llm.temperature;
+  }
+
+  /// Gets the API requests are sent to.
+  ///
+  /// Returns `chat` for the OpenAI-compatible `/v1/chat/completions`, or
+  /// `completion` for llama.cpp's native `/completion`. Defaults to `chat`
+  /// if not set.
+  ///
+  /// # Returns
+  ///
+  /// The configured `LLMApi`.
+  pub fn get_llm_api(&self) -> LLMApi {
+    return self.llm.api.unwrap_or_default();
+  }
+
+  /// Gets the path of the GBNF grammar constraining refined text.
+  ///
+  /// Returns the configured path, or an empty string to use the generated
+  /// grammar with `constrain_output`, or none.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the grammar file path.
+  pub fn get_llm_grammar_file(&self) -> String {
+    return self.llm.grammar_file.clone().unwrap_or_default();
+  }
+
+  /// Gets whether answers are constrained with generated grammars.
+  ///
+  /// Returns whether each request to llama.cpp carries a GBNF grammar
+  /// generated from the answer it asks for. Defaults to `false` if not
+  /// set.
+  ///
+  /// # Returns
+  ///
+  /// A `bool` indicating whether answers are constrained.
+  pub fn get_llm_constrain_output(&self) -> bool {
+    return self
+      .llm
+      .constrain_output
+      .unwrap_or(DEFAULT_LLM_CONSTRAIN_OUTPUT);
+  }
+
+  /// Gets whether timestamps, speaker tags and bracketed annotations are
+  /// kept verbatim.
+  ///
+  /// Returns whether these tokens are replaced with markers before plain
+  /// text is sent and spliced back into the refined text, so the model
+  /// cannot drop or reword them. Defaults to `false` if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `bool` indicating whether verbatim tokens are protected.
+  pub fn get_llm_keep_verbatim_tokens(&self) -> bool {
+    return self
+      .llm
+      .keep_verbatim_tokens
+      .unwrap_or(DEFAULT_LLM_KEEP_VERBATIM_TOKENS);
+  }
+
+  /// Gets the least semantic similarity refined text must keep to its
+  /// input.
+  ///
+  /// Returns the cosine similarity of their embeddings below which a
+  /// refinement is rejected, catching answers to the transcript instead of
+  /// refinements of it. Defaults to 0, which disables the check.
+  ///
+  /// # Returns
+  ///
+  /// A `f64` containing the least similarity, between 0 and 1.
+  pub fn get_llm_min_similarity(&self) -> f64 {
+    return self
+      .llm
+      .min_similarity
+      .unwrap_or(DEFAULT_LLM_MIN_SIMILARITY);
+  }
+
+  /// Gets whether refinements that look like answers to the transcript
+  /// are requested again.
+  ///
+  /// Defaults to `true` if not set.
+  ///
+  /// # Returns
+  ///
+  /// `true` if such refinements are retried with a reinforced prompt.
+  pub fn get_llm_retry_answers(&self) -> bool {
+    return self.llm.retry_answers.unwrap_or(DEFAULT_LLM_RETRY_ANSWERS);
+  }
+
+  /// Gets what happens when the model refuses to refine a text.
+  ///
+  /// Defaults to `retry` if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `RefusalHandling` of refinements.
+  pub fn get_llm_refusal(&self) -> RefusalHandling {
+    return self.llm.refusal.unwrap_or_default();
+  }
+
+  /// Gets the model embeddings are requested from.
+  ///
+  /// Returns the model sent to `/v1/embeddings` for the similarity check;
+  /// empty if not set, which uses `[llm] model`.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the embeddings model name.
+  pub fn get_llm_embeddings_model(&self) -> String {
+    return self
+      .llm
+      .embeddings_model
+      .clone()
+      .filter(|model| !model.is_empty())
+      .unwrap_or_else(|| self.get_llm_model());
+  }
+
+  /// Gets the further LLM endpoints sharing the load with the primary one.
+  ///
+  /// Returns the URLs of servers equivalent to `[llm] url`, over which
+  /// requests are distributed. Defaults to none if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `Vec<String>` containing the endpoint URLs.
+  pub fn get_llm_endpoints(&self) -> Vec<String> {
+    return self.llm.endpoints.clone().unwrap_or_default();
+  }
+
+  /// Gets how requests are distributed over the LLM endpoints.
+  ///
+  /// Returns the configured strategy or round-robin if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `BalanceStrategy` to use.
+  pub fn get_llm_balance(&self) -> BalanceStrategy {
+    return self.llm.balance.unwrap_or_default();
+  }
+
+  /// Gets the price of input tokens.
+  ///
+  /// Used to estimate the cost of a run. Defaults to 0 if not set.
+  ///
+  /// # Returns
+  ///
+  /// The price per million prompt tokens.
+  pub fn get_llm_input_price(&self) -> f64 {
+    return self.llm.input_price.unwrap_or_default();
+  }
+
+  /// Gets the price of output tokens.
+  ///
+  /// Used to estimate the cost of a run. Defaults to 0 if not set.
+  ///
+  /// # Returns
+  ///
+  /// The price per million completion tokens.
+  pub fn get_llm_output_price(&self) -> f64 {
+    return self.llm.output_price.unwrap_or_default();
+  }
+
+  /// Gets the input chunk size.
+  ///
+  /// Returns the target size of the chunks long inputs are split into, in
+  /// the configured chunk unit. Defaults to 8000 if not set. A value of 0
+  /// disables chunking.
+  ///
+  /// # Returns
+  ///
+  /// A `usize` containing the chunk size.
+  pub fn get_input_chunk_size(&self) -> usize {
+    return self.input.chunk_size.unwrap_or(DEFAULT_INPUT_CHUNK_SIZE);
+  }
+
+  /// Gets the unit chunk sizes are counted in.
+  ///
+  /// Returns `Characters`, or `Tokens` to count in tokens of the configured
+  /// model. Defaults to `Characters` if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `ChunkUnit` of the chunk size.
+  pub fn get_input_chunk_unit(&self) -> ChunkUnit {
+    return self.input.chunk_unit.unwrap_or_default();
+  }
+
+  /// Gets how many sentences of a chunk are carried over to the next.
+  ///
+  /// Returns how many sentences at the end of each refined chunk are sent
+  /// with the next chunk as context only, keeping pronoun references and
+  /// terminology consistent. Defaults to 2 if not set. A value of 0
+  /// disables the carryover.
+  ///
+  /// # Returns
+  ///
+  /// A `usize` containing the number of sentences.
+  pub fn get_input_chunk_overlap_sentences(&self) -> usize {
+    return self
+      .input
+      .chunk_overlap_sentences
+      .unwrap_or(DEFAULT_INPUT_CHUNK_OVERLAP_SENTENCES);
+  }
+
+  /// Gets the maximum input size.
+  ///
+  /// Returns the largest input in bytes that will be accepted. Defaults to
+  /// 256 MiB if not set. A value of 0 disables the limit.
+  ///
+  /// # Returns
+  ///
+  /// A `u64` containing the maximum input size in bytes.
+  pub fn get_input_max_size(&self) -> u64 {
+    return self.input.max_size.unwrap_or(DEFAULT_INPUT_MAX_SIZE);
+  }
+
+  /// Gets the proxy URL.
+  ///
+  /// Returns the configured proxy URL or an empty string if not set, in
+  /// which case the `HTTPS_PROXY` family of environment variables applies.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the proxy URL.
+  pub fn get_proxy(&self) -> String {
+    return self.network.proxy.clone().unwrap_or_default();
+  }
+
+  /// Gets the hosts that bypass the proxy.
+  ///
+  /// Returns the configured comma-separated host list or an empty string.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the no-proxy host list.
+  pub fn get_no_proxy(&self) -> String {
+    return self.network.no_proxy.clone().unwrap_or_default();
+  }
+
+  /// Gets the circuit breaker failure threshold.
+  ///
+  /// Returns the number of failures within the window that open the circuit.
+  /// Defaults to 3 if not set. A value of 0 disables the circuit breaker.
+  ///
+  /// # Returns
+  ///
+  /// A `u32` containing the failure threshold.
+  pub fn get_circuit_breaker_threshold(&self) -> u32 {
+    return self
+      .network
+      .circuit_breaker_threshold
+      .unwrap_or(DEFAULT_CIRCUIT_BREAKER_THRESHOLD);
+  }
+
+  /// Gets the circuit breaker failure window.
+  ///
+  /// Returns the time window in seconds in which failures are counted.
+  /// Defaults to 60 seconds if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `u64` containing the window in seconds.
+  pub fn get_circuit_breaker_window_seconds(&self) -> u64 {
+    return self
+      .network
+      .circuit_breaker_window_seconds
+      .unwrap_or(DEFAULT_CIRCUIT_BREAKER_WINDOW_SECONDS);
+  }
+
+  /// Gets the circuit breaker cooldown.
+  ///
+  /// Returns how long in seconds an open circuit fails fast before a trial
+  /// request is allowed. Defaults to 30 seconds if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `u64` containing the cooldown in seconds.
+  pub fn get_circuit_breaker_cooldown_seconds(&self) -> u64 {
+    return self
+      .network
+      .circuit_breaker_cooldown_seconds
+      .unwrap_or(DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECONDS);
+  }
+
+  /// Gets whether requests to services carry the request ID.
+  ///
+  /// Returns whether the `X-Request-Id` header is sent to the LLM,
+  /// transcription and webhook services. Defaults to `false` if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `bool` indicating whether the header is sent.
+  pub fn get_request_id_header(&self) -> bool {
+    return self
+      .network
+      .request_id_header
+      .unwrap_or(DEFAULT_REQUEST_ID_HEADER);
+  }
+
+  /// Gets how often a failed LLM request is retried.
+  ///
+  /// Returns how often a request failing with a connection error, rate
+  /// limiting (HTTP 429) or a server error (HTTP 5xx) is sent again, with
+  /// a delay that doubles each time. Defaults to 2 if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `u32` containing the number of retries.
+  pub fn get_network_retries(&self) -> u32 {
+    return self.network.retries.unwrap_or(DEFAULT_NETWORK_RETRIES);
+  }
+
+  /// Gets the address of the metrics endpoint.
+  ///
+  /// Returns the configured `host:port` address on which the daemon serves
+  /// Prometheus metrics, or an empty string if metrics are disabled.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the metrics address.
+  pub fn get_server_metrics_address(&self) -> String {
+    return self.server.metrics_address.clone().unwrap_or_default();
+  }
+
+  /// Gets the bearer tokens accepted by the daemon.
+  ///
+  /// Returns the configured tokens, or an empty list if the daemon accepts
+  /// requests without authentication.
+  ///
+  /// # Returns
+  ///
+  /// A `Vec<ServerToken>` containing the accepted tokens.
+  pub fn get_server_tokens(&self) -> Vec<ServerToken> {
+    return self.server.tokens.clone().unwrap_or_default();
+  }
+
+  /// Gets the token presented when forwarding requests to the daemon.
+  ///
+  /// Returns the configured token or an empty string if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the client token.
+  pub fn get_server_client_token(&self) -> String {
+    return self.server.client_token.clone().unwrap_or_default();
+  }
+
+  /// Gets whether the daemon offers its DBus service.
+  ///
+  /// Defaults to true if not set.
+  ///
+  /// # Returns
+  ///
+  /// `true` if `org.pegasus.Refiner` is registered on the session bus.
+  pub fn get_server_dbus(&self) -> bool {
+    return self.server.dbus.unwrap_or(DEFAULT_SERVER_DBUS);
+  }
+
+  /// Gets the command that prints the clipboard for `RefineClipboard`.
+  ///
+  /// The refined text is copied back with `[output] clipboard_command`.
+  /// Defaults to `wl-paste --no-newline` if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the shell command.
+  pub fn get_server_paste_command(&self) -> String {
+    return self
+      .server
+      .paste_command
+      .clone()
+      .unwrap_or_else(|| String::from(DEFAULT_SERVER_PASTE_COMMAND));
+  }
+
+  /// Gets the command that starts the LLM server.
+  ///
+  /// The server is started when it is first needed and stopped when
+  /// Pegasus exits. Defaults to an empty string if not set, which expects
+  /// a server to be running already.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the shell command.
+  pub fn get_backend_command(&self) -> String {
+    return self.backend.command.clone().unwrap_or_default();
+  }
+
+  /// Gets how long to wait for the started LLM server to become ready.
+  ///
+  /// Defaults to 120 seconds if not set, since large models take a while
+  /// to load.
+  ///
+  /// # Returns
+  ///
+  /// The timeout in seconds.
+  pub fn get_backend_ready_timeout_seconds(&self) -> u64 {
+    return self
+      .backend
+      .ready_timeout_seconds
+      .unwrap_or(DEFAULT_BACKEND_READY_TIMEOUT_SECONDS);
+  }
+
+  /// Gets the speech-to-text service audio is sent to.
+  ///
+  /// Defaults to a whisper.cpp server if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `TranscriptionProvider`.
+  pub fn get_transcription_provider(&self) -> TranscriptionProvider {
+    return self.transcription.provider.unwrap_or_default();
+  }
+
+  /// Gets the URL of the speech-to-text service.
+  ///
+  /// Returns the configured URL or a local whisper.cpp server on port 8081
+  /// if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the service URL.
+  pub fn get_transcription_url(&self) -> String {
+    return self
+      .transcription
+      .url
+      .clone()
+      .unwrap_or_else(|| String::from(DEFAULT_TRANSCRIPTION_URL));
+  }
+
+  /// Gets the speech-to-text model.
+  ///
+  /// Only used by `openai-audio`. Defaults to `whisper-1` if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the model name.
+  pub fn get_transcription_model(&self) -> String {
+    return self
+      .transcription
+      .model
+      .clone()
+      .unwrap_or_else(|| String::from(DEFAULT_TRANSCRIPTION_MODEL));
+  }
+
+  /// Gets the spoken language of recordings.
+  ///
+  /// Defaults to an empty string if not set, which lets the service detect
+  /// the language.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the ISO 639-1 language code.
+  pub fn get_transcription_language(&self) -> String {
+    return self.transcription.language.clone().unwrap_or_default();
+  }
+
+  /// Gets the API key of the speech-to-text service.
+  ///
+  /// Kept apart from the LLM API key, so recordings can be transcribed in
+  /// the cloud and refined locally. Defaults to an empty string if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the API key.
+  pub fn get_transcription_api_key(&self) -> String {
+    return self.transcription.api_key.clone().unwrap_or_default();
+  }
+
+  /// Gets the source of the speech-to-text API key.
+  ///
+  /// Returns the configured source, or `Command` if only `api_key_command`
+  /// is set, `File` if only `api_key_file` is set, or `Config` otherwise.
+  ///
+  /// # Returns
+  ///
+  /// The `ApiKeySource` to read the API key from.
+  pub fn get_transcription_api_key_source(&self) -> ApiKeySource {
+    if let Some(source) = self.transcription.api_key_source {
+      return source;
+    }
+    if !self.get_transcription_api_key_command().is_empty() {
+      return ApiKeySource::Command;
+    }
+    if !self.get_transcription_api_key_file().is_empty() {
+      return ApiKeySource::File;
+    }
+    return ApiKeySource::Config;
+  }
+
+  /// Gets the path of a file containing the speech-to-text API key.
+  ///
+  /// Defaults to an empty string if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the API key file path.
+  pub fn get_transcription_api_key_file(&self) -> String {
+    return self.transcription.api_key_file.clone().unwrap_or_default();
+  }
+
+  /// Gets the command that prints the speech-to-text API key.
+  ///
+  /// Defaults to an empty string if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the API key command.
+  pub fn get_transcription_api_key_command(&self) -> String {
+    return self
+      .transcription
+      .api_key_command
+      .clone()
+      .unwrap_or_default();
+  }
+
+  /// Gets the largest recording sent to the speech-to-text service in one
+  /// request.
+  ///
+  /// Larger recordings are split at pauses. Defaults to 25 MiB, the limit
+  /// of the OpenAI API, if not set; 0 never splits.
+  ///
+  /// # Returns
+  ///
+  /// The upload limit in bytes.
+  pub fn get_transcription_max_upload_size(&self) -> u64 {
+    return self
+      .transcription
+      .max_upload_size
+      .unwrap_or(DEFAULT_TRANSCRIPTION_MAX_UPLOAD_SIZE);
+  }
+
+  /// Gets the longest chunk a split recording is cut into.
+  ///
+  /// Defaults to 600 seconds if not set.
+  ///
+  /// # Returns
+  ///
+  /// The chunk length in seconds.
+  pub fn get_transcription_chunk_seconds(&self) -> f64 {
+    return self
+      .transcription
+      .chunk_seconds
+      .unwrap_or(DEFAULT_TRANSCRIPTION_CHUNK_SECONDS);
+  }
+
+  /// Gets the command that finds pauses in a recording.
+  ///
+  /// `{input}` is replaced with the path of the recording, and the command
+  /// runs without a shell. It must print ffmpeg `silencedetect` style
+  /// `silence_start:` and `silence_end:` lines. Defaults to ffmpeg's
+  /// `silencedetect` filter if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the command line.
+  pub fn get_transcription_silence_command(&self) -> String {
+    return self
+      .transcription
+      .silence_command
+      .clone()
+      .unwrap_or_else(|| String::from(split::DEFAULT_SILENCE_COMMAND));
+  }
+
+  /// Gets how many chunks of a split recording are transcribed at once.
+  ///
+  /// Defaults to 4 if not set.
+  ///
+  /// # Returns
+  ///
+  /// The number of concurrent requests.
+  pub fn get_transcription_concurrency(&self) -> usize {
+    return self
+      .transcription
+      .concurrency
+      .unwrap_or(DEFAULT_TRANSCRIPTION_CONCURRENCY);
+  }
+
+  /// Gets what starts and stops a dictation recording.
+  ///
+  /// Defaults to `voice` if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `DictationTrigger`.
+  pub fn get_dictation_trigger(&self) -> DictationTrigger {
+    return self.dictation.trigger.unwrap_or_default();
+  }
+
+  /// Gets the command that records one dictated utterance.
+  ///
+  /// `{output}` is replaced with the quoted path of the WAV file to write.
+  /// Defaults to sox's `rec` stopping after 1.5 seconds of silence if not
+  /// set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the shell command.
+  pub fn get_dictation_record_command(&self) -> String {
+    return self
+      .dictation
+      .record_command
+      .clone()
+      .unwrap_or_else(|| String::from(dictation::DEFAULT_RECORD_COMMAND));
+  }
+
+  /// Gets the sinks dictated text is written to.
+  ///
+  /// Defaults to the clipboard if not set. See [`crate::output::sink`] for
+  /// the accepted sinks.
+  ///
+  /// # Returns
+  ///
+  /// A `Vec<String>` containing the sink specs.
+  pub fn get_dictation_sinks(&self) -> Vec<String> {
+    return self
+      .dictation
+      .sinks
+      .clone()
+      .unwrap_or_else(|| vec![String::from(DEFAULT_DICTATION_SINK)]);
+  }
+
+  /// Gets whether runs are recorded for `pegasus usage`.
+  ///
+  /// Defaults to true if not set.
+  ///
+  /// # Returns
+  ///
+  /// `true` if the tokens, estimated cost, duration and word counts of each
+  /// run are recorded.
+  pub fn get_usage_enabled(&self) -> bool {
+    return self.usage.enabled.unwrap_or(DEFAULT_USAGE_ENABLED);
+  }
+
+  /// Gets whether runs, models and failure categories are counted for
+  /// `pegasus usage`.
+  ///
+  /// Defaults to false if not set.
+  ///
+  /// # Returns
+  ///
+  /// `true` if the anonymous run counters are kept.
+  pub fn get_usage_counters(&self) -> bool {
+    return self.usage.counters.unwrap_or(DEFAULT_USAGE_COUNTERS);
+  }
+
+  /// Gets the profile runs are recorded under.
+  ///
+  /// Defaults to `default` if not set or empty.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the profile name.
+  pub fn get_usage_profile(&self) -> String {
+    return self
+      .usage
+      .profile
+      .clone()
+      .filter(|profile| !profile.trim().is_empty())
+      .unwrap_or_else(|| String::from(DEFAULT_USAGE_PROFILE));
+  }
+
+  /// Gets how the punctuation of refined text is normalized.
+  ///
+  /// Defaults to `keep` if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `UnicodePunctuation` applied after refinement.
+  pub fn get_style_unicode_punctuation(&self) -> UnicodePunctuation {
+    return self.style.unicode_punctuation.unwrap_or_default();
+  }
+
+  /// Gets what happens to emoji in the text.
+  ///
+  /// Defaults to `model` if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `NonSpeechPolicy` for emoji.
+  pub fn get_style_emoji(&self) -> NonSpeechPolicy {
+    return self.style.emoji.unwrap_or_default();
+  }
+
+  /// Gets what happens to non-speech markers like `[laughter]` in the text.
+  ///
+  /// Defaults to `model` if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `NonSpeechPolicy` for non-speech markers.
+  pub fn get_style_non_speech(&self) -> NonSpeechPolicy {
+    return self.style.non_speech.unwrap_or_default();
+  }
+
+  /// Gets the sinks results are written to unless `--sink` is given.
+  ///
+  /// Defaults to standard output if not set. See [`crate::output::sink`]
+  /// for the accepted sinks.
+  ///
+  /// # Returns
+  ///
+  /// A `Vec<String>` containing the sink specs.
+  pub fn get_output_sinks(&self) -> Vec<String> {
+    return self
+      .output
+      .sinks
+      .clone()
+      .unwrap_or_else(|| vec![String::from(DEFAULT_OUTPUT_SINK)]);
+  }
+
+  /// Gets the command the clipboard sink pipes results into.
+  ///
+  /// Defaults to `wl-copy` if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the shell command.
+  pub fn get_output_clipboard_command(&self) -> String {
+    return self
+      .output
+      .clipboard_command
+      .clone()
+      .unwrap_or_else(|| String::from(sink::DEFAULT_CLIPBOARD_COMMAND));
+  }
+
+  /// Gets the command the typing sink pipes results into.
+  ///
+  /// Defaults to `wtype -` if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the shell command.
+  pub fn get_output_type_command(&self) -> String {
+    return self
+      .output
+      .type_command
+      .clone()
+      .unwrap_or_else(|| String::from(sink::DEFAULT_TYPE_COMMAND));
+  }
+
+  /// Gets the command the notification sink runs.
+  ///
+  /// `{text}` is replaced with the quoted result. Defaults to `notify-send
+  /// Pegasus {text}` if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the shell command.
+  pub fn get_output_notify_command(&self) -> String {
+    return self
+      .output
+      .notify_command
+      .clone()
+      .unwrap_or_else(|| String::from(sink::DEFAULT_NOTIFY_COMMAND));
+  }
+
+  /// Gets the URL the webhook sink posts results to.
+  ///
+  /// Defaults to an empty string if not set, which leaves the webhook sink
+  /// unusable.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the webhook URL.
+  pub fn get_output_webhook_url(&self) -> String {
+    return self.output.webhook_url.clone().unwrap_or_default();
+  }
+
+  /// Gets the bearer token sent to the webhook.
+  ///
+  /// Defaults to an empty string if not set, which sends no
+  /// `Authorization` header.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the token.
+  pub fn get_output_webhook_token(&self) -> String {
+    return self.output.webhook_token.clone().unwrap_or_default();
+  }
+
+  /// Gets how often a failed webhook request is retried.
+  ///
+  /// Defaults to 3 if not set.
+  ///
+  /// # Returns
+  ///
+  /// The number of retries.
+  pub fn get_output_webhook_retries(&self) -> u32 {
+    return self
+      .output
+      .webhook_retries
+      .unwrap_or(DEFAULT_OUTPUT_WEBHOOK_RETRIES);
+  }
+
+  /// Gets whether results too long for the terminal are paged.
+  ///
+  /// Defaults to true if not set.
+  ///
+  /// # Returns
+  ///
+  /// `true` if the stdout sink pipes long results into a pager.
+  pub fn get_output_use_pager(&self) -> bool {
+    return self.output.use_pager.unwrap_or(DEFAULT_OUTPUT_USE_PAGER);
+  }
+
+  /// Gets the pager for results too long for the terminal.
+  ///
+  /// Empty if not set, in which case `$PAGER` or `less` is used.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the pager's command line.
+  pub fn get_output_pager(&self) -> String {
+    return self.output.pager.clone().unwrap_or_default();
+  }
+
+  /// Gets how results are wrapped.
+  ///
+  /// The width defaults to 0, leaving lines alone, and paragraphs are
+  /// reflowed unless `reflow` is false.
+  ///
+  /// # Returns
+  ///
+  /// The `Wrap` applied to results.
+  pub fn get_output_wrap(&self) -> Wrap {
+    return Wrap {
+      width: self.output.wrap.unwrap_or(DEFAULT_OUTPUT_WRAP),
+      reflow: self.output.reflow.unwrap_or(DEFAULT_OUTPUT_REFLOW),
+    };
+  }
+
+  /// Gets the newline and whitespace conventions of results.
+  ///
+  /// Defaults to `lf` line endings with a final newline, leaving blank
+  /// lines and trailing whitespace alone, for the settings not set.
+  ///
+  /// # Returns
+  ///
+  /// The `WhitespacePolicy` applied to results.
+  pub fn get_output_whitespace(&self) -> WhitespacePolicy {
+    let defaults = WhitespacePolicy::default();
+    return WhitespacePolicy {
+      line_ending: self.output.line_ending.unwrap_or(defaults.line_ending),
+      final_newline: self
+        .output
+        .final_newline
+        .unwrap_or(defaults.final_newline),
+      collapse_blank_lines: self
+        .output
+        .collapse_blank_lines
+        .unwrap_or(defaults.collapse_blank_lines),
+      trim_trailing_whitespace: self
+        .output
+        .trim_trailing_whitespace
+        .unwrap_or(defaults.trim_trailing_whitespace),
+    };
+  }
+
+  /// Gets the Whisper probability threshold.
+  ///
+  /// Returns the configured probability threshold for flagging low-probability
+  /// words during transcription refinement. Defaults to 0.7 if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `f64` containing the probability threshold (0.0 to 1.0).
+  pub fn get_whisper_probability_threshold(&self) -> f64 {
+    return self
+      .whisper
+      .probability_threshold
+      .unwrap_or(DEFAULT_WHISPER_PROBABILITY_THRESHOLD);
+  }
+
+  /// Checks if repeated Whisper segments are collapsed before refinement.
+  ///
+  /// Whisper sometimes emits the same sentence many times in a row; such
+  /// runs of near-duplicate segments are reduced to their first segment.
+  /// Defaults to `true` if not set.
+  ///
+  /// # Returns
+  ///
+  /// `true` if repeated segments are collapsed, `false` otherwise.
+  pub fn get_whisper_deduplicate_segments(&self) -> bool {
+    return self
+      .whisper
+      .deduplicate_segments
+      .unwrap_or(DEFAULT_WHISPER_DEDUPLICATE_SEGMENTS);
+  }
+
+  /// Gets the shortest silent gap between Whisper segments to report.
+  ///
+  /// Returns how many seconds may pass between the end of a segment and
+  /// the start of the next before the gap is reported as a possible
+  /// recording problem. Defaults to 5.0 if not set; 0 disables gap reports.
+  ///
+  /// # Returns
+  ///
+  /// A `f64` containing the gap threshold in seconds.
+  pub fn get_whisper_gap_threshold_seconds(&self) -> f64 {
+    return self
+      .whisper
+      .gap_threshold_seconds
+      .unwrap_or(DEFAULT_WHISPER_GAP_THRESHOLD_SECONDS);
+  }
+
+  /// Gets the model confidence needed to accept a corrected Whisper word.
+  ///
+  /// When above 0, token log probabilities are requested from the LLM and
+  /// corrections of flagged words with a lower probability are reverted to
+  /// the original word. Defaults to 0.0 (disabled) if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `f64` containing the token probability threshold (0.0 to 1.0).
+  pub fn get_whisper_logprob_threshold(&self) -> f64 {
+    return self
+      .whisper
+      .logprob_threshold
+      .unwrap_or(DEFAULT_WHISPER_LOGPROB_THRESHOLD);
+  }
+
+  /// Gets the custom dictionary path.
+  ///
+  /// Returns the configured custom dictionary path or an empty string if not set.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the custom dictionary path.
+  pub fn get_custom_dictionary_path(&self) -> String {
+    return self.dictionary.path.clone().unwrap_or_default();
+  }
+
+  /// Gets how dictionary terms are matched.
+  ///
+  /// Defaults to case-insensitive matching of whole words, without regex
+  /// entries, for the settings not set.
+  ///
+  /// # Returns
+  ///
+  /// The `MatchOptions` of the dictionary.
+  pub fn get_dictionary_matching(&self) -> MatchOptions {
+    return MatchOptions {
+      case_sensitive: self
+        .dictionary
+        .case_sensitive
+        .unwrap_or(DEFAULT_DICTIONARY_CASE_SENSITIVE),
+      whole_word: self
+        .dictionary
+        .whole_word
+        .unwrap_or(DEFAULT_DICTIONARY_WHOLE_WORD),
+      regex: self.dictionary.regex.unwrap_or(DEFAULT_DICTIONARY_REGEX),
+    };
+  }
+
+  /// Gets whether words corrected during review are recorded for
+  /// `pegasus dictionary learn`.
+  ///
+  /// Defaults to true if not set.
+  ///
+  /// # Returns
+  ///
+  /// `true` if corrections are recorded.
+  pub fn get_dictionary_record_corrections(&self) -> bool {
+    return self
+      .dictionary
+      .record_corrections
+      .unwrap_or(DEFAULT_DICTIONARY_RECORD_CORRECTIONS);
+  }
+
+  /// Gets how abbreviations of glossary entries like `K8s = Kubernetes`
+  /// are written.
+  ///
+  /// Defaults to `keep` if not set.
+  ///
+  /// # Returns
+  ///
+  /// The `GlossaryMode` of the dictionary.
+  pub fn get_dictionary_glossary(&self) -> GlossaryMode {
+    return self.dictionary.glossary.unwrap_or_default();
+  }
+
+  /// Gets the path of the Hunspell dictionary refined text is
+  /// spellchecked against.
+  ///
+  /// Returns an empty string if not set, which disables spellchecking.
+  ///
+  /// # Returns
+  ///
+  /// A `String` containing the path of the `.dic` file.
+  pub fn get_dictionary_spellcheck(&self) -> String {
+    return self.dictionary.spellcheck.clone().unwrap_or_default();
+  }
+
+  /// Resets the configuration to default values and saves it.
+  ///
+  /// Writes a template listing every option commented out with its default
+  /// value and a description to the XDG config directory. An existing
+  /// configuration file is moved aside to `config.toml.bak`.
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<()>` indicating success or failure.
+  pub async fn reset_to_defaults() -> ConfigResult<()> {
+    let content = template::render(&Config::default())?;
+    let xdg_dirs = BaseDirectories::with_prefix(DEFAULT_DIRECTORY);
+    let config_path = xdg_dirs
+      .place_config_file(DEFAULT_CONFIG_NAME)
+      .map_err(|e| ConfigError::FileRead(e.to_string()))?;
+
+    if config_path.is_file() {
+      let path = config_path.to_string_lossy();
+      operations::move_file(&path, &format!("{}.bak", path))
+        .await
+        .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+    }
+
+    return Config::write_to_path(&content, config_path).await;
+  }
+
+  /// Migrates the user configuration file to the current layout.
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<Vec<String>>` with the applied migration notices, empty
+  /// if the file was already up to date.
+  pub async fn migrate_user_config() -> ConfigResult<Vec<String>> {
+    let xdg_dirs = BaseDirectories::with_prefix(DEFAULT_DIRECTORY);
+    let config_path = xdg_dirs
+      .find_config_file(DEFAULT_CONFIG_NAME)
+      .ok_or_else(|| {
+        ConfigError::FileRead(String::from("no user configuration file found"))
+      })?;
+    return migration::migrate_file(&config_path).await;
+  }
+
+  /// Opens the user configuration file in the user's editor.
+  ///
+  /// The file is edited as a temporary copy next to it, readable by the
+  /// user only, and only written back, atomically, if it still parses.
+  /// Invalid edits are kept in that copy so they are not lost.
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<bool>` that is `true` if the configuration changed.
+  pub async fn edit_user_config() -> ConfigResult<bool> {
+    let xdg_dirs = BaseDirectories::with_prefix(DEFAULT_DIRECTORY);
+    let config_path = xdg_dirs
+      .place_config_file(DEFAULT_CONFIG_NAME)
+      .map_err(|e| ConfigError::FileRead(e.to_string()))?;
+    let config_directory = config_path
+      .parent()
+      .map(Path::to_path_buf)
+      .unwrap_or_default();
+    let config_path = config_path.to_string_lossy().to_string();
+
+    let original = if Path::new(&config_path).is_file() {
+      operations::read_to_string(&config_path)
+        .await
+        .map_err(|e| ConfigError::FileRead(e.to_string()))?
+    } else {
+      template::render(&Config::default())?
+    };
+
+    let draft = TemporaryFile::create_in_with_content(
+      &config_directory,
+      ".config",
+      "toml",
+      &original,
+    )
+    .await
+    .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+
+    operations::edit_in_editor(draft.path())
+      .await
+      .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+
+    let edited = operations::read_to_string(&draft.path_string())
+      .await
+      .map_err(|e| ConfigError::FileRead(e.to_string()))?;
+    if edited == original {
+      return Ok(false);
+    }
+
+    if let Err(e) = toml::from_str::<Config>(&edited) {
+      let kept_path = draft.persist();
+      return Err(ConfigError::Parse(format!(
+        "{}\nYour edits were kept in {}",
+        e,
+        kept_path.display()
+      )));
+    }
+
+    let _lock = operations::lock_file(&config_path)
+      .await
+      .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+    operations::write_string_atomic(&config_path, &edited)
+      .await
+      .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+    return Ok(true);
+  }
+
+  /// Writes the content of a configuration file to a specific path.
+  ///
+  /// The file is written atomically while holding an advisory lock, so
+  /// concurrent Pegasus processes never see or produce a torn file.
+  ///
+  /// # Arguments
+  ///
+  /// * `config_content` - The TOML to write
+  /// * `config_path` - Path where the configuration should be saved
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<()>` indicating success or failure.
+  pub(crate) async fn write_to_path(
+    config_content: &str,
+    config_path: PathBuf,
+  ) -> ConfigResult<()> {
+    let config_path = config_path.to_string_lossy();
+    let _lock = operations::lock_file(&config_path)
+      .await
+      .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+    operations::write_string_atomic(&config_path, config_content)
+      .await
+      .map_err(|e| ConfigError::FileWrite(e.to_string()))?;
+    return Ok(());
+  }
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    return Config {
+      config_version: Some(CURRENT_CONFIG_VERSION),
+      llm: LLMConfig {
+        url: Some(String::from(DEFAULT_LLM_URL)),
+        model: Some(String::new()),
+        api_key: Some(String::new()),
+        api_key_source: None,
+        api_key_command: Some(String::new()),
+        api_key_file: Some(String::new()),
+        fallback_url: Some(String::new()),
+        context_paragraphs: Some(DEFAULT_LLM_CONTEXT_PARAGRAPHS),
+        context_characters: Some(DEFAULT_LLM_CONTEXT_CHARACTERS),
+        target_reading_level: Some(DEFAULT_LLM_TARGET_READING_LEVEL),
+        max_output_characters: Some(DEFAULT_LLM_MAX_OUTPUT_CHARACTERS),
+        output_overflow: Some(OverflowPolicy::default()),
+        stop: Some(Vec::new()),
+        tokenizer: Some(String::new()),
+        context_window: Some(0),
+        probe_capabilities: Some(DEFAULT_LLM_PROBE_CAPABILITIES),
+        temperature: None,
+        input_price: Some(0.0),
+        output_price: Some(0.0),
+        api: Some(LLMApi::default()),
+        grammar_file: Some(String::new()),
+        constrain_output: Some(DEFAULT_LLM_CONSTRAIN_OUTPUT),
+        keep_verbatim_tokens: Some(DEFAULT_LLM_KEEP_VERBATIM_TOKENS),
+        min_similarity: Some(DEFAULT_LLM_MIN_SIMILARITY),
+        embeddings_model: Some(String::new()),
+        retry_answers: Some(DEFAULT_LLM_RETRY_ANSWERS),
+        refusal: Some(RefusalHandling::default()),
+        endpoints: Some(Vec::new()),
+        balance: Some(BalanceStrategy::default()),
+      },
+      whisper: WhisperTranscriptionConfig {
+        probability_threshold: Some(DEFAULT_WHISPER_PROBABILITY_THRESHOLD),
+        deduplicate_segments: Some(DEFAULT_WHISPER_DEDUPLICATE_SEGMENTS),
+        gap_threshold_seconds: Some(DEFAULT_WHISPER_GAP_THRESHOLD_SECONDS),
+        logprob_threshold: Some(DEFAULT_WHISPER_LOGPROB_THRESHOLD),
+      },
+      dictionary: DictionaryConfig {
+        path: Some(String::new()),
+        case_sensitive: Some(DEFAULT_DICTIONARY_CASE_SENSITIVE),
+        whole_word: Some(DEFAULT_DICTIONARY_WHOLE_WORD),
+        regex: Some(DEFAULT_DICTIONARY_REGEX),
+        record_corrections: Some(DEFAULT_DICTIONARY_RECORD_CORRECTIONS),
+        glossary: Some(GlossaryMode::default()),
+        spellcheck: Some(String::new()),
+      },
+      input: InputConfig {
+        chunk_size: Some(DEFAULT_INPUT_CHUNK_SIZE),
+        chunk_overlap_sentences: Some(DEFAULT_INPUT_CHUNK_OVERLAP_SENTENCES),
+        chunk_unit: Some(ChunkUnit::default()),
+        max_size: Some(DEFAULT_INPUT_MAX_SIZE),
+      },
+      network: NetworkConfig {
+        proxy: Some(String::new()),
+        no_proxy: Some(String::new()),
+        circuit_breaker_threshold: Some(DEFAULT_CIRCUIT_BREAKER_THRESHOLD),
+        circuit_breaker_window_seconds: Some(
+          DEFAULT_CIRCUIT_BREAKER_WINDOW_SECONDS,
+        ),
+        circuit_breaker_cooldown_seconds: Some(
+          DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECONDS,
+        ),
+        request_id_header: Some(DEFAULT_REQUEST_ID_HEADER),
+        retries: Some(DEFAULT_NETWORK_RETRIES),
+      },
+      server: ServerConfig {
+        metrics_address: Some(String::new()),
+        tokens: Some(Vec::new()),
+        client_token: Some(String::new()),
+        dbus: Some(DEFAULT_SERVER_DBUS),
+        paste_command: Some(String::from(DEFAULT_SERVER_PASTE_COMMAND)),
+      },
+      backend: BackendConfig {
+        command: Some(String::new()),
+        ready_timeout_seconds: Some(DEFAULT_BACKEND_READY_TIMEOUT_SECONDS),
+      },
+      transcription: TranscriptionConfig {
+        provider: Some(TranscriptionProvider::default()),
+        url: Some(String::from(DEFAULT_TRANSCRIPTION_URL)),
+        model: Some(String::from(DEFAULT_TRANSCRIPTION_MODEL)),
+        language: Some(String::new()),
+        api_key: Some(String::new()),
+        api_key_source: None,
+        api_key_command: Some(String::new()),
+        api_key_file: Some(String::new()),
+        max_upload_size: Some(DEFAULT_TRANSCRIPTION_MAX_UPLOAD_SIZE),
+        chunk_seconds: Some(DEFAULT_TRANSCRIPTION_CHUNK_SECONDS),
+        silence_command: Some(String::from(split::DEFAULT_SILENCE_COMMAND)),
+        concurrency: Some(DEFAULT_TRANSCRIPTION_CONCURRENCY),
+      },
+      dictation: DictationConfig {
+        trigger: Some(DictationTrigger::default()),
+        record_command: Some(String::from(dictation::DEFAULT_RECORD_COMMAND)),
+        sinks: Some(vec![String::from(DEFAULT_DICTATION_SINK)]),
+      },
+      output: OutputConfig {
+        sinks: Some(vec![String::from(DEFAULT_OUTPUT_SINK)]),
+        clipboard_command: Some(String::from(sink::DEFAULT_CLIPBOARD_COMMAND)),
+        type_command: Some(String::from(sink::DEFAULT_TYPE_COMMAND)),
+        notify_command: Some(String::from(sink::DEFAULT_NOTIFY_COMMAND)),
+        webhook_url: Some(String::new()),
+        webhook_token: Some(String::new()),
+        webhook_retries: Some(DEFAULT_OUTPUT_WEBHOOK_RETRIES),
+        line_ending: Some(LineEnding::default()),
+        final_newline: Some(WhitespacePolicy::default().final_newline),
+        collapse_blank_lines: Some(false),
+        trim_trailing_whitespace: Some(false),
+        wrap: Some(DEFAULT_OUTPUT_WRAP),
+        reflow: Some(DEFAULT_OUTPUT_REFLOW),
+        use_pager: Some(DEFAULT_OUTPUT_USE_PAGER),
+        pager: Some(String::new()),
+      },
+      usage: UsageConfig {
+        enabled: Some(DEFAULT_USAGE_ENABLED),
+        profile: Some(String::from(DEFAULT_USAGE_PROFILE)),
+        counters: Some(DEFAULT_USAGE_COUNTERS),
+      },
+      style: StyleConfig {
+        unicode_punctuation: Some(UnicodePunctuation::default()),
+        emoji: Some(NonSpeechPolicy::default()),
+        non_speech: Some(NonSpeechPolicy::default()),
+      },
+      models: Some(BTreeMap::new()),
+    };
+  }
+}
@@ -0,0 +1,604 @@
+//! Layered configuration resolution with provenance.
+//!
+//! Configuration values are resolved from several layers, each overriding
+//! the previous one:
+//!
+//! 1. Built-in defaults
+//! 2. System configuration (`$XDG_CONFIG_DIRS/pegasus/config.toml`)
+//! 3. User configuration (`$XDG_CONFIG_HOME/pegasus/config.toml`)
+//! 4. Project configuration (nearest `.pegasus.toml`)
+//! 5. Environment variables (`PEGASUS_<SECTION>_<KEY>`)
+//! 6. Command-line overrides (`--set section.key=value`)
+//!
+//! The `[models."<name>"]` section of the selected model is then merged
+//! over the `[llm]` settings that did not come from layers 5 and 6.
+//!
+//! Layers are merged as TOML tables, so every layer may be partial. The
+//! resolver remembers which layer set each value, which powers
+//! `pegasus config show --origins`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use xdg::BaseDirectories;
+
+use crate::config::errors::{ConfigError, ConfigResult};
+use crate::config::{
+  Config, DEFAULT_CONFIG_NAME, DEFAULT_DIRECTORY, ModelConfig, discovery,
+  migration,
+};
+use crate::files::operations;
+use crate::{elog, logging, vlog};
+
+const ENV_PREFIX: &str = "PEGASUS_";
+
+/// Keys whose values are credentials and never printed.
+const SECRET_KEYS: [&str; 5] = [
+  "api_key",
+  "token",
+  "tokens",
+  "client_token",
+  "webhook_token",
+];
+
+/// Placeholder printed in place of secret values.
+const REDACTED: &str = "\"***\"";
+
+/// The layer a configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigOrigin {
+  /// Built-in default value
+  Default,
+  /// System-wide configuration file
+  System(PathBuf),
+  /// User configuration file
+  User(PathBuf),
+  /// Per-project configuration file
+  Project(PathBuf),
+  /// Environment variable
+  Environment(String),
+  /// Command-line override
+  CommandLine,
+  /// Section of the selected model
+  Model(String),
+}
+
+impl fmt::Display for ConfigOrigin {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return match self {
+      ConfigOrigin::Default => write!(f, "default"),
+      ConfigOrigin::System(path) => write!(f, "system: {}", path.display()),
+      ConfigOrigin::User(path) => write!(f, "user: {}", path.display()),
+      ConfigOrigin::Project(path) => write!(f, "project: {}", path.display()),
+      ConfigOrigin::Environment(name) => write!(f, "env: {}", name),
+      ConfigOrigin::CommandLine => write!(f, "command line"),
+      ConfigOrigin::Model(model) => write!(f, "model: {}", model),
+    };
+  }
+}
+
+/// Resolves configuration from all layers and tracks value origins.
+#[derive(Debug, Clone)]
+pub struct ConfigResolver {
+  table: toml::Table,
+  origins: BTreeMap<String, ConfigOrigin>,
+}
+
+impl ConfigResolver {
+  /// Creates a resolver holding only the built-in defaults.
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<ConfigResolver>` containing the resolver or an error.
+  pub fn new() -> ConfigResult<Self> {
+    let table = toml::Table::try_from(Config::default())
+      .map_err(|e| ConfigError::Parse(e.to_string()))?;
+
+    let mut resolver = ConfigResolver {
+      table: toml::Table::new(),
+      origins: BTreeMap::new(),
+    };
+    resolver.apply(table, ConfigOrigin::Default);
+
+    return Ok(resolver);
+  }
+
+  /// Resolves configuration from every layer.
+  ///
+  /// # Arguments
+  ///
+  /// * `overrides` - Command-line overrides in `section.key=value` form
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<ConfigResolver>` containing the resolver or an error.
+  pub async fn resolve(overrides: &[String]) -> ConfigResult<Self> {
+    let mut resolver = ConfigResolver::new()?;
+    let xdg_dirs = BaseDirectories::with_prefix(DEFAULT_DIRECTORY);
+
+    // System directories are listed from most to least preferred.
+    for config_dir in xdg_dirs.get_config_dirs().iter().rev() {
+      let config_path = config_dir.join(DEFAULT_CONFIG_NAME);
+      if config_path.is_file() {
+        vlog!(
+          "Loading system configuration from: {}",
+          config_path.display()
+        );
+        let table = read_table(&config_path).await?;
+        resolver.apply(table, ConfigOrigin::System(config_path));
+      }
+    }
+
+    if let Some(config_path) = xdg_dirs.find_config_file(DEFAULT_CONFIG_NAME) {
+      let table = read_table(&config_path).await?;
+      resolver.apply(table, ConfigOrigin::User(config_path));
+    }
+
+    if let Some(config_path) = discovery::find_project_config() {
+      let table = discovery::read_project_table(&config_path).await?;
+      resolver.apply(table, ConfigOrigin::Project(config_path));
+    }
+
+    for (name, value) in std::env::vars() {
+      if let Some(table) = resolver.parse_env_var(&name, &value) {
+        resolver.apply(table, ConfigOrigin::Environment(name));
+      }
+    }
+
+    for assignment in overrides {
+      let table = resolver.parse_override(assignment)?;
+      resolver.apply(table, ConfigOrigin::CommandLine);
+    }
+
+    resolver.apply_model_settings();
+    return Ok(resolver);
+  }
+
+  /// Merges the section of the selected model over the `[llm]` settings.
+  ///
+  /// Settings set by environment variables or command-line overrides are
+  /// kept, since they are more specific than the model's section.
+  fn apply_model_settings(&mut self) {
+    let Some(model) = self
+      .table
+      .get("llm")
+      .and_then(|llm| llm.get("model"))
+      .and_then(|model| model.as_str())
+      .map(String::from)
+    else {
+      return;
+    };
+    let Some(settings) = self
+      .table
+      .get("models")
+      .and_then(|models| models.get(&model))
+      .and_then(|settings| settings.as_table())
+      .cloned()
+    else {
+      return;
+    };
+
+    // Unknown keys are reported under `models` only.
+    let mut unknown_keys = Vec::new();
+    let _: Result<ModelConfig, toml::de::Error> = serde_ignored::deserialize(
+      toml::Value::Table(settings.clone()),
+      |path| unknown_keys.push(path.to_string()),
+    );
+
+    let mut llm = toml::Table::new();
+    for (key, value) in settings {
+      let path = join_key("llm", &key);
+      if unknown_keys.contains(&key)
+        || matches!(
+          self.origin_of(&path),
+          ConfigOrigin::Environment(_) | ConfigOrigin::CommandLine
+        )
+      {
+        continue;
+      }
+      self
+        .origins
+        .insert(path, ConfigOrigin::Model(model.clone()));
+      llm.insert(key, value);
+    }
+
+    vlog!("Applying the settings of model {}", model);
+    let mut table = toml::Table::new();
+    table.insert(String::from("llm"), toml::Value::Table(llm));
+    merge_tables(&mut self.table, table);
+  }
+
+  /// Merges a layer over the current values, recording its origin.
+  ///
+  /// # Arguments
+  ///
+  /// * `table` - The layer's values
+  /// * `origin` - Where the layer came from
+  pub fn apply(&mut self, table: toml::Table, origin: ConfigOrigin) {
+    let mut keys = Vec::new();
+    collect_keys(&table, "", &mut keys);
+    for key in keys {
+      self.origins.insert(key, origin.clone());
+    }
+    merge_tables(&mut self.table, table);
+  }
+
+  /// Builds the final configuration from the merged layers.
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<Config>` containing the configuration or an error.
+  pub fn build(&self) -> ConfigResult<Config> {
+    return self
+      .table
+      .clone()
+      .try_into()
+      .map_err(|e: toml::de::Error| ConfigError::Parse(e.to_string()));
+  }
+
+  /// Returns the dotted paths of keys that do not map to any setting.
+  ///
+  /// # Returns
+  ///
+  /// The unknown keys, or an empty vector if the configuration is invalid
+  /// for other reasons (reported by [`ConfigResolver::build`]).
+  pub fn unknown_keys(&self) -> Vec<String> {
+    let mut unknown_keys = Vec::new();
+    let _: Result<Config, toml::de::Error> = serde_ignored::deserialize(
+      toml::Value::Table(self.table.clone()),
+      |path| unknown_keys.push(path.to_string()),
+    );
+    // Optional tables show up as `?` segments in the paths.
+    return unknown_keys
+      .into_iter()
+      .map(|key| {
+        key
+          .split('.')
+          .filter(|segment| *segment != "?")
+          .collect::<Vec<_>>()
+          .join(".")
+      })
+      .collect();
+  }
+
+  /// Returns the origin of a value, searching parent tables for keys that
+  /// are tables themselves.
+  ///
+  /// # Arguments
+  ///
+  /// * `key` - The dotted key path
+  ///
+  /// # Returns
+  ///
+  /// The `ConfigOrigin` of the value.
+  pub fn origin_of(&self, key: &str) -> ConfigOrigin {
+    if let Some(origin) = self.origins.get(key) {
+      return origin.clone();
+    }
+    let prefix = format!("{}.", key);
+    return self
+      .origins
+      .iter()
+      .find(|(path, _)| path.starts_with(&prefix))
+      .map(|(_, origin)| origin.clone())
+      .unwrap_or(ConfigOrigin::Default);
+  }
+
+  /// Renders the effective configuration as `key = value` lines.
+  ///
+  /// Secret values such as API keys and bearer tokens are redacted.
+  ///
+  /// # Arguments
+  ///
+  /// * `with_origins` - Whether to annotate each value with its origin
+  ///
+  /// # Returns
+  ///
+  /// The rendered configuration.
+  pub fn describe(&self, with_origins: bool) -> String {
+    let mut values = Vec::new();
+    collect_values(&self.table, "", &mut values);
+
+    return values
+      .into_iter()
+      .map(|(key, value)| {
+        let value = if is_secret_key(&key) {
+          REDACTED.to_string()
+        } else {
+          value
+        };
+        if !with_origins {
+          return format!("{} = {}", key, value);
+        }
+        format!("{} = {}  # {}", key, value, self.origin_of(&key))
+      })
+      .collect::<Vec<_>>()
+      .join("\n");
+  }
+
+  /// Converts a `PEGASUS_<SECTION>_<KEY>` environment variable to a layer.
+  ///
+  /// Only known sections are considered, so unrelated `PEGASUS_*`
+  /// variables are ignored.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The environment variable name
+  /// * `value` - The environment variable value
+  ///
+  /// # Returns
+  ///
+  /// The layer table, or `None` if the variable does not map to a setting.
+  fn parse_env_var(&self, name: &str, value: &str) -> Option<toml::Table> {
+    let (section, key) = self.env_setting(name)?;
+    return Some(self.build_layer(&section, &key, value));
+  }
+
+  /// Gets the setting a `PEGASUS_<SECTION>_<KEY>` environment variable
+  /// maps to.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The environment variable name
+  ///
+  /// # Returns
+  ///
+  /// The section and key, or `None` if the variable does not map to a
+  /// setting.
+  fn env_setting(&self, name: &str) -> Option<(String, String)> {
+    let rest = name.strip_prefix(ENV_PREFIX)?.to_lowercase();
+    let (section, key) = rest.split_once('_')?;
+    if !matches!(self.table.get(section), Some(toml::Value::Table(_))) {
+      return None;
+    }
+    return Some((section.to_string(), key.to_string()));
+  }
+
+  /// Converts a `section.key=value` command-line override to a layer.
+  ///
+  /// # Arguments
+  ///
+  /// * `assignment` - The override assignment
+  ///
+  /// # Returns
+  ///
+  /// A `ConfigResult<toml::Table>` containing the layer or an error.
+  fn parse_override(&self, assignment: &str) -> ConfigResult<toml::Table> {
+    let invalid = || {
+      ConfigError::Parse(format!(
+        "invalid override '{}', expected section.key=value",
+        assignment
+      ))
+    };
+    let (path, value) = assignment.split_once('=').ok_or_else(invalid)?;
+    let (section, key) = path.trim().split_once('.').ok_or_else(invalid)?;
+    return Ok(self.build_layer(section, key, value.trim()));
+  }
+
+  /// Builds a single-value layer, typing the value like the current one.
+  ///
+  /// Values are parsed as TOML literals (numbers, booleans, arrays) unless
+  /// the setting currently holds a string, in which case the raw text is
+  /// used so e.g. `PEGASUS_LLM_MODEL=7` stays a string.
+  ///
+  /// # Arguments
+  ///
+  /// * `section` - The section name
+  /// * `key` - The key within the section
+  /// * `raw` - The raw value text
+  ///
+  /// # Returns
+  ///
+  /// The layer table.
+  fn build_layer(&self, section: &str, key: &str, raw: &str) -> toml::Table {
+    let current = self
+      .table
+      .get(section)
+      .and_then(|value| value.as_table())
+      .and_then(|table| table.get(key));
+
+    let value = match current {
+      Some(toml::Value::String(_)) => toml::Value::String(raw.to_string()),
+      _ => parse_literal(raw),
+    };
+
+    let mut section_table = toml::Table::new();
+    section_table.insert(key.to_string(), value);
+
+    let mut table = toml::Table::new();
+    table.insert(section.to_string(), toml::Value::Table(section_table));
+    return table;
+  }
+}
+
+/// Gets the settings of the `PEGASUS_<SECTION>_<KEY>` environment
+/// variables as overrides, e.g. to forward them to a daemon.
+///
+/// # Returns
+///
+/// A `ConfigResult<Vec<String>>` containing the `section.key=value`
+/// overrides, sorted.
+pub fn environment_overrides() -> ConfigResult<Vec<String>> {
+  let resolver = ConfigResolver::new()?;
+  let mut overrides: Vec<String> = std::env::vars()
+    .filter_map(|(name, value)| {
+      let (section, key) = resolver.env_setting(&name)?;
+      return Some(format!("{}.{}={}", section, key, value));
+    })
+    .collect();
+  overrides.sort();
+  return Ok(overrides);
+}
+
+/// Parses a raw value as a TOML literal, falling back to a string.
+///
+/// # Arguments
+///
+/// * `raw` - The raw value text
+///
+/// # Returns
+///
+/// The parsed `toml::Value`.
+fn parse_literal(raw: &str) -> toml::Value {
+  return format!("value = {}", raw)
+    .parse::<toml::Table>()
+    .ok()
+    .and_then(|mut table| table.remove("value"))
+    .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+}
+
+/// Lists every file that may contribute a configuration layer.
+///
+/// Includes files that do not exist yet, so a long-running process can
+/// notice when one is created.
+///
+/// # Returns
+///
+/// The system, user and project configuration file paths.
+pub fn source_paths() -> Vec<PathBuf> {
+  let xdg_dirs = BaseDirectories::with_prefix(DEFAULT_DIRECTORY);
+  let mut paths: Vec<PathBuf> = xdg_dirs
+    .get_config_dirs()
+    .iter()
+    .map(|config_dir| config_dir.join(DEFAULT_CONFIG_NAME))
+    .collect();
+
+  if let Some(config_path) = xdg_dirs.get_config_file(DEFAULT_CONFIG_NAME) {
+    paths.push(config_path);
+  }
+
+  let project_path = discovery::find_project_config().or_else(|| {
+    let current_dir = std::env::current_dir().ok()?;
+    return Some(current_dir.join(discovery::PROJECT_CONFIG_NAME));
+  });
+  paths.extend(project_path);
+
+  return paths;
+}
+
+/// Lists the keys whose values differ between two configurations.
+///
+/// # Arguments
+///
+/// * `old` - The previous configuration
+/// * `new` - The current configuration
+///
+/// # Returns
+///
+/// The dotted paths of the changed keys, in key order.
+pub fn changed_keys(old: &Config, new: &Config) -> Vec<String> {
+  let (Ok(old), Ok(new)) =
+    (toml::Table::try_from(old), toml::Table::try_from(new))
+  else {
+    return Vec::new();
+  };
+
+  let mut old_values = Vec::new();
+  collect_values(&old, "", &mut old_values);
+  let mut new_values = Vec::new();
+  collect_values(&new, "", &mut new_values);
+
+  let old_values: BTreeMap<String, String> = old_values.into_iter().collect();
+  let new_values: BTreeMap<String, String> = new_values.into_iter().collect();
+
+  let keys: BTreeSet<&String> = old_values
+    .keys()
+    .chain(new_values.keys())
+    .filter(|key| old_values.get(*key) != new_values.get(*key))
+    .collect();
+  return keys.into_iter().cloned().collect();
+}
+
+/// Reads a TOML configuration file into a table.
+///
+/// Files written for older versions are migrated to the current layout,
+/// with a warning for every deprecated key.
+///
+/// # Arguments
+///
+/// * `config_path` - Path to the configuration file
+///
+/// # Returns
+///
+/// A `ConfigResult<toml::Table>` containing the parsed table or an error.
+pub async fn read_table(config_path: &Path) -> ConfigResult<toml::Table> {
+  let content = operations::read_to_string(&config_path.to_string_lossy())
+    .await
+    .map_err(|e| ConfigError::FileRead(e.to_string()))?;
+  let mut table: toml::Table =
+    toml::from_str(&content).map_err(|e| ConfigError::Parse(e.to_string()))?;
+
+  for notice in migration::migrate(&mut table) {
+    elog!(
+      logging::WARNING,
+      "Warning: {} ({}); run `pegasus config migrate` to update the file",
+      notice,
+      config_path.display()
+    );
+  }
+
+  return Ok(table);
+}
+
+/// Deep-merges `overlay` into `base`.
+///
+/// Nested tables are merged key by key; any other value in `overlay`
+/// replaces the value in `base`.
+///
+/// # Arguments
+///
+/// * `base` - The table to merge into
+/// * `overlay` - The table whose values take precedence
+pub fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+  for (key, value) in overlay {
+    match (base.get_mut(&key), value) {
+      (Some(toml::Value::Table(base_table)), toml::Value::Table(table)) => {
+        merge_tables(base_table, table);
+      }
+      (_, value) => {
+        base.insert(key, value);
+      }
+    }
+  }
+}
+
+/// Collects the dotted paths of all leaf values in a table.
+fn collect_keys(table: &toml::Table, prefix: &str, keys: &mut Vec<String>) {
+  for (key, value) in table {
+    let path = join_key(prefix, key);
+    match value {
+      toml::Value::Table(nested) => collect_keys(nested, &path, keys),
+      _ => keys.push(path),
+    }
+  }
+}
+
+/// Collects the dotted paths and rendered values of all leaf values.
+fn collect_values(
+  table: &toml::Table,
+  prefix: &str,
+  values: &mut Vec<(String, String)>,
+) {
+  for (key, value) in table {
+    let path = join_key(prefix, key);
+    match value {
+      toml::Value::Table(nested) => collect_values(nested, &path, values),
+      value => values.push((path, value.to_string())),
+    }
+  }
+}
+
+/// Checks whether a dotted key path holds a credential.
+fn is_secret_key(path: &str) -> bool {
+  let key = path.rsplit('.').next().unwrap_or(path);
+  return SECRET_KEYS.contains(&key);
+}
+
+/// Joins a dotted key prefix and a key.
+fn join_key(prefix: &str, key: &str) -> String {
+  if prefix.is_empty() {
+    return key.to_string();
+  }
+  return format!("{}.{}", prefix, key);
+}
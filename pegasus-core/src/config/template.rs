@@ -0,0 +1,680 @@
+//! Commented template of the configuration file.
+//!
+//! `pegasus reset-config`, and `pegasus config edit` without a user
+//! configuration file, write every option commented out with its default
+//! value and a description, so the file documents what can be set and
+//! uncommenting a line is all it takes to change it.
+//!
+//! Values are taken from [`Config::default`], so the template cannot drift
+//! from the defaults. Options without a description here are still written,
+//! after the described options of their section.
+
+use crate::config::Config;
+use crate::config::errors::{ConfigError, ConfigResult};
+use crate::output::wrap::Wrap;
+
+/// Width descriptions are wrapped to, leaving room for the `# ` prefix.
+const DESCRIPTION_WIDTH: usize = 78;
+
+/// Text at the top of the template.
+const HEADER: &str = "Pegasus configuration. Every option is listed with \
+  its default value; uncomment a line to change it. Run `pegasus config show \
+  --origins` to see the effective configuration and where each value comes \
+  from.";
+
+/// A documented option.
+struct Key {
+  /// Name of the option
+  name: &'static str,
+  /// What the option does
+  description: &'static str,
+  /// Value shown for options without a default value
+  example: Option<&'static str>,
+}
+
+/// A documented section of the configuration file.
+struct Section {
+  /// Name of the section
+  name: &'static str,
+  /// What the section configures
+  description: &'static str,
+  /// The options of the section
+  keys: &'static [Key],
+  /// Commented example written instead of the keys, for sections that
+  /// hold tables named by the user
+  example: Option<&'static str>,
+}
+
+/// Describes an option with a default value.
+const fn key(name: &'static str, description: &'static str) -> Key {
+  return Key {
+    name,
+    description,
+    example: None,
+  };
+}
+
+/// Describes an option without a default value.
+const fn unset(
+  name: &'static str,
+  description: &'static str,
+  example: &'static str,
+) -> Key {
+  return Key {
+    name,
+    description,
+    example: Some(example),
+  };
+}
+
+/// All documented sections, in the order they are written.
+const SECTIONS: &[Section] = &[
+  Section {
+    name: "llm",
+    description: "LLM service the text is refined with.",
+    keys: &[
+      key("url", "URL of the OpenAI-compatible LLM service."),
+      key("model", "Model name sent with every request."),
+      key(
+        "api_key",
+        "API key of the LLM service, read when `api_key_source` is \
+         `config`. `PEGASUS_LLM_API_KEY` takes precedence.",
+      ),
+      unset(
+        "api_key_source",
+        "Where the API key is read from: `config`, `keyring` (managed with \
+         `pegasus auth`), `command` or `file`. Without a value, `command` \
+         or `file` is used when only `api_key_command` or `api_key_file` is \
+         set, and `config` otherwise.",
+        "\"keyring\"",
+      ),
+      key("api_key_command", "Shell command that prints the API key."),
+      key("api_key_file", "Path of a file containing the API key."),
+      key(
+        "fallback_url",
+        "URL of an LLM service used when the primary one fails; empty for \
+         none.",
+      ),
+      key(
+        "context_paragraphs",
+        "How many earlier refinements of an interactive session or daemon \
+         connection are sent with the next request; 0 disables conversation \
+         context.",
+      ),
+      key(
+        "context_characters",
+        "Largest combined size in characters of the earlier paragraphs sent \
+         as context; older paragraphs that do not fit are left out.",
+      ),
+      key(
+        "target_reading_level",
+        "Flesch-Kincaid grade level refined text is written at; results \
+         that miss it by far are refined again. 0 keeps the speaker's \
+         reading level.",
+      ),
+      key(
+        "max_output_characters",
+        "Size in characters above which an answer is treated as runaway \
+         generation; 0 disables the limit.",
+      ),
+      key(
+        "output_overflow",
+        "What happens to answers over the limit: `truncate` cuts them at \
+         the last sentence that fits, `fail` fails the request.",
+      ),
+      key(
+        "stop",
+        "Sequences at which the LLM stops generating, sent with every \
+         request.",
+      ),
+      key(
+        "tokenizer",
+        "Vocabulary tokens are counted with, like `cl100k_base`, or \
+         `estimate` for a character-based estimate. Empty picks the \
+         vocabulary of the model and falls back to the estimate.",
+      ),
+      key(
+        "context_window",
+        "Tokens the model accepts, which prompts are checked against before \
+         sending; 0 uses the context window detected from the endpoint.",
+      ),
+      key(
+        "probe_capabilities",
+        "Whether the endpoint is asked for its context window and features \
+         before the first request. The result is cached for a day.",
+      ),
+      unset(
+        "temperature",
+        "Sampling temperature sent with every request. Without a value, the \
+         default of the service is used.",
+        "0.2",
+      ),
+      key(
+        "input_price",
+        "Price per million prompt tokens, used to estimate the cost of a \
+         run.",
+      ),
+      key(
+        "output_price",
+        "Price per million completion tokens, used to estimate the cost of \
+         a run.",
+      ),
+      key(
+        "api",
+        "API requests are sent to: `chat` for the OpenAI-compatible \
+         `/v1/chat/completions`, or `completion` for llama.cpp's native \
+         `/completion`.",
+      ),
+      key(
+        "grammar_file",
+        "Path of a GBNF grammar constraining refined text; empty for none.",
+      ),
+      key(
+        "constrain_output",
+        "Whether each request to llama.cpp carries a GBNF grammar generated \
+         from the answer it asks for.",
+      ),
+      key(
+        "keep_verbatim_tokens",
+        "Whether timestamps, speaker tags and bracketed annotations are kept \
+         verbatim instead of being left to the model.",
+      ),
+      key(
+        "retry_answers",
+        "Whether refinements that answer the transcript instead of refining \
+         it, like an answer to its closing question, are requested again \
+         with a stricter prompt.",
+      ),
+      key(
+        "refusal",
+        "What happens when the model refuses to refine a text: \"retry\" \
+         requests it again framed as a transcript to correct, \"fail\" \
+         fails with a refusal error (exit status 8), \"off\" does not look \
+         for refusals.",
+      ),
+      key(
+        "min_similarity",
+        "Least cosine similarity between the embeddings of the input and \
+         the refined text, below which a refinement is rejected as an \
+         answer to the transcript; 0 disables the check.",
+      ),
+      key(
+        "embeddings_model",
+        "Model the `/v1/embeddings` endpoint computes embeddings with for \
+         `min_similarity`; empty uses `model`.",
+      ),
+      key(
+        "endpoints",
+        "URLs of further servers equivalent to `url`, over which requests \
+         are distributed.",
+      ),
+      key(
+        "balance",
+        "How requests are distributed over the endpoints: `round-robin` or \
+         `least-loaded`.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "whisper",
+    description: "Processing of Whisper JSON transcriptions.",
+    keys: &[
+      key(
+        "probability_threshold",
+        "Words transcribed with a lower probability are flagged for the LLM \
+         to correct (0.0 to 1.0).",
+      ),
+      key(
+        "deduplicate_segments",
+        "Whether runs of near-duplicate segments are reduced to their first \
+         segment before refinement.",
+      ),
+      key(
+        "gap_threshold_seconds",
+        "Silent gaps between segments at least this long are reported as \
+         possible recording problems; 0 disables gap reports.",
+      ),
+      key(
+        "logprob_threshold",
+        "Corrections of flagged words the LLM is less confident about are \
+         reverted to the original word (0.0 to 1.0); 0 disables the check.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "dictionary",
+    description: "Dictionary of domain terms the LLM is told about.",
+    keys: &[
+      key(
+        "path",
+        "Path of the dictionary file, with one term per line; empty for \
+         none. `Term!` enforces a term, `Term ^3` raises its weight and \
+         `ABBR = Term` adds a glossary entry. Terms after a `[de]` line are \
+         only used for transcripts in that language, up to the next section \
+         or `[*]`.",
+      ),
+      key(
+        "case_sensitive",
+        "Whether words only match a term in the same capitalization, so \
+         enforcement corrects misspellings but never casing.",
+      ),
+      key(
+        "whole_word",
+        "Whether terms only match whole words. When false, enforced terms \
+         are also corrected inside longer words like `kubernetes-native`.",
+      ),
+      key(
+        "regex",
+        "Whether `/pattern/ Term` lines are regex entries, replacing every \
+         match of the pattern with the term after refinement.",
+      ),
+      key(
+        "record_corrections",
+        "Whether words corrected in `pegasus tui` are recorded, so \
+         `pegasus dictionary learn` can propose the ones corrected \
+         repeatedly.",
+      ),
+      key(
+        "glossary",
+        "How abbreviations of `ABBR = Term` entries are written: `keep` as \
+         spoken, `expand` as the term, or `standardize` as `Term (ABBR)` at \
+         the first mention and the abbreviation afterwards.",
+      ),
+      key(
+        "spellcheck",
+        "Path of a Hunspell `.dic` file, with its `.aff` file next to it; \
+         refined words it does not know that are neither terms nor in the \
+         input are reported as possible hallucinations. Empty for none.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "input",
+    description: "Reading and splitting of input.",
+    keys: &[
+      key(
+        "chunk_size",
+        "Target size of the chunks long inputs are refined in, counted in \
+         `chunk_unit`; 0 disables chunking.",
+      ),
+      key(
+        "chunk_overlap_sentences",
+        "Sentences at the end of a chunk sent with the next chunk as \
+         context only; 0 disables the carryover.",
+      ),
+      key(
+        "chunk_unit",
+        "Unit chunk sizes are counted in: `characters` or `tokens` of the \
+         configured model.",
+      ),
+      key(
+        "max_size",
+        "Largest input in bytes that is accepted; 0 disables the limit.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "network",
+    description: "Proxies and resilience of requests to services.",
+    keys: &[
+      key(
+        "proxy",
+        "Proxy URL for all requests. Empty uses the `HTTPS_PROXY` family of \
+         environment variables.",
+      ),
+      key("no_proxy", "Comma-separated hosts that bypass the proxy."),
+      key(
+        "circuit_breaker_threshold",
+        "Failures of an endpoint within the window after which requests to \
+         it fail fast; 0 disables the circuit breaker.",
+      ),
+      key(
+        "circuit_breaker_window_seconds",
+        "Time window in seconds in which failures are counted.",
+      ),
+      key(
+        "circuit_breaker_cooldown_seconds",
+        "How long in seconds requests fail fast before a trial request is \
+         allowed.",
+      ),
+      key(
+        "request_id_header",
+        "Whether the `X-Request-Id` header is sent to the LLM, \
+         transcription and webhook services.",
+      ),
+      key(
+        "retries",
+        "How often an LLM request failing with a connection error, HTTP \
+         429 or a 5xx status is retried, waiting twice as long each time.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "server",
+    description: "The daemon started with `pegasus daemon`.",
+    keys: &[
+      key(
+        "metrics_address",
+        "`host:port` address Prometheus metrics are served on; empty \
+         disables metrics.",
+      ),
+      key(
+        "tokens",
+        "Bearer tokens the daemon accepts, as `{ token = \"...\", \
+         requests_per_minute = 60 }` entries; the rate limit is optional. \
+         Empty accepts requests without authentication.",
+      ),
+      key(
+        "client_token",
+        "Token presented when forwarding requests to the daemon.",
+      ),
+      key(
+        "dbus",
+        "Whether `org.pegasus.Refiner` is registered on the session bus.",
+      ),
+      key(
+        "paste_command",
+        "Shell command that prints the clipboard for `RefineClipboard`.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "backend",
+    description: "LLM server started when it is first needed.",
+    keys: &[
+      key(
+        "command",
+        "Shell command that starts a server answering at `[llm] url`; it is \
+         stopped when Pegasus exits. Empty expects a server to be running \
+         already.",
+      ),
+      key(
+        "ready_timeout_seconds",
+        "How long to wait for the started server to become ready.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "transcription",
+    description: "Speech-to-text service recordings are transcribed with.",
+    keys: &[
+      key(
+        "provider",
+        "Kind of service: `whisper-cpp` or `openai-audio`.",
+      ),
+      key("url", "URL of the speech-to-text service."),
+      key(
+        "model",
+        "Speech-to-text model, only used by `openai-audio`.",
+      ),
+      key(
+        "language",
+        "ISO 639-1 code of the spoken language; empty lets the service \
+         detect it.",
+      ),
+      key(
+        "api_key",
+        "API key of the speech-to-text service, kept apart from the LLM API \
+         key and read when `api_key_source` is `config`. \
+         `PEGASUS_TRANSCRIPTION_API_KEY` takes precedence.",
+      ),
+      unset(
+        "api_key_source",
+        "Where the API key is read from: `config`, `keyring` (managed with \
+         `pegasus auth --transcription`), `command` or `file`. Without a \
+         value, `command` or `file` is used when only `api_key_command` or \
+         `api_key_file` is set, and `config` otherwise.",
+        "\"keyring\"",
+      ),
+      key("api_key_command", "Shell command that prints the API key."),
+      key("api_key_file", "Path of a file containing the API key."),
+      key(
+        "max_upload_size",
+        "Largest recording in bytes sent in one request; larger recordings \
+         are split at pauses. 0 never splits.",
+      ),
+      key(
+        "chunk_seconds",
+        "Longest chunk in seconds a split recording is cut into.",
+      ),
+      key(
+        "silence_command",
+        "Command that finds pauses in a recording, printing ffmpeg \
+         `silencedetect` style lines. `{input}` is replaced with the path of \
+         the recording. Runs without a shell; use `sh -c '...'` for pipes.",
+      ),
+      key(
+        "concurrency",
+        "How many chunks of a split recording are transcribed at once.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "dictation",
+    description: "Recording and delivery for `pegasus dictate`.",
+    keys: &[
+      key(
+        "trigger",
+        "What starts and stops a recording: `voice` or `signal` \
+         (`SIGUSR1`).",
+      ),
+      key(
+        "record_command",
+        "Shell command that records one utterance. `{output}` is replaced \
+         with the path of the WAV file to write.",
+      ),
+      key("sinks", "Sinks dictated text is written to."),
+    ],
+    example: None,
+  },
+  Section {
+    name: "output",
+    description: "Where and how results are written.",
+    keys: &[
+      key(
+        "sinks",
+        "Sinks results are written to unless `--sink` is given: `stdout`, \
+         `file:<path>`, `clipboard`, `type`, `notify` or `webhook`.",
+      ),
+      key(
+        "clipboard_command",
+        "Shell command the clipboard sink pipes results into.",
+      ),
+      key(
+        "type_command",
+        "Shell command the typing sink pipes results into.",
+      ),
+      key(
+        "notify_command",
+        "Shell command the notification sink runs. `{text}` is replaced \
+         with the result.",
+      ),
+      key("webhook_url", "URL the webhook sink posts results to."),
+      key(
+        "webhook_token",
+        "Bearer token sent to the webhook; empty sends no `Authorization` \
+         header.",
+      ),
+      key(
+        "webhook_retries",
+        "How often a failed webhook request is retried.",
+      ),
+      key("line_ending", "Line ending of results: `lf` or `crlf`."),
+      key(
+        "final_newline",
+        "Whether the stdout and file sinks end results with a line ending.",
+      ),
+      key(
+        "collapse_blank_lines",
+        "Whether runs of blank lines become one blank line.",
+      ),
+      key(
+        "trim_trailing_whitespace",
+        "Whether spaces and tabs at the end of lines are removed.",
+      ),
+      key(
+        "wrap",
+        "Characters per line results are wrapped to; 0 leaves lines alone.",
+      ),
+      key(
+        "reflow",
+        "Whether the lines of a paragraph are joined before wrapping, \
+         instead of only breaking lines that are too long.",
+      ),
+      key(
+        "use_pager",
+        "Whether results too long for the terminal are piped into a pager.",
+      ),
+      key(
+        "pager",
+        "Command of the pager, run without a shell. Empty uses `$PAGER` \
+         or `less`; `cat` disables paging.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "usage",
+    description: "Usage statistics reported by `pegasus usage`.",
+    keys: &[
+      key(
+        "enabled",
+        "Whether the tokens, estimated cost, duration and word counts of \
+         each run are recorded.",
+      ),
+      key("profile", "Profile runs are recorded and reported under."),
+      key(
+        "counters",
+        "Whether runs, the models used and the categories of failures are \
+         counted locally, without any network access, for `pegasus usage`.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "style",
+    description: "Deterministic rewriting of refined text.",
+    keys: &[
+      key(
+        "unicode_punctuation",
+        "How quotes, dashes and ellipses are normalized: `keep`, `ascii` or \
+         `typographic`.",
+      ),
+      key(
+        "emoji",
+        "What happens to emoji: `model` leaves them to the model, `keep` \
+         protects them, `strip` removes them and `words` spells them out.",
+      ),
+      key(
+        "non_speech",
+        "What happens to non-speech markers like `[laughter]`: `model`, \
+         `keep`, `strip` or `words`, as for `emoji`.",
+      ),
+    ],
+    example: None,
+  },
+  Section {
+    name: "models",
+    description: "Settings of a single model, replacing the `[llm]` \
+      settings from configuration files while the model is selected with \
+      `[llm] model`. Accepted keys are temperature, context_window, \
+      tokenizer, target_reading_level, max_output_characters, stop, \
+      input_price and output_price.",
+    keys: &[],
+    example: Some(
+      "[models.\"llama-3.1-8b\"]\ntemperature = 0.2\ncontext_window = 8192",
+    ),
+  },
+];
+
+/// Renders a configuration as a commented template.
+///
+/// # Arguments
+///
+/// * `config` - The configuration whose values are written, normally the
+///   defaults
+///
+/// # Returns
+///
+/// A `ConfigResult<String>` containing the template, or an error if the
+/// configuration cannot be serialized.
+pub fn render(config: &Config) -> ConfigResult<String> {
+  let mut table = toml::Table::try_from(config)
+    .map_err(|e| ConfigError::Parse(e.to_string()))?;
+
+  let mut lines = comment(HEADER);
+  lines.push(String::new());
+  for (name, value) in table.iter() {
+    if !value.is_table() {
+      lines.push(format!("{} = {}", name, value));
+    }
+  }
+  table.retain(|_, value| value.is_table());
+
+  for section in SECTIONS {
+    let values = match table.remove(section.name) {
+      Some(toml::Value::Table(values)) => values,
+      _ => toml::Table::new(),
+    };
+    lines.push(String::new());
+    lines.extend(comment(section.description));
+    if let Some(example) = section.example {
+      lines.extend(example.lines().map(|line| format!("# {}", line)));
+      continue;
+    }
+    lines.push(format!("[{}]", section.name));
+    lines.extend(render_keys(section.keys, values));
+  }
+  for (name, values) in table {
+    let toml::Value::Table(values) = values else {
+      continue;
+    };
+    lines.push(String::new());
+    lines.push(format!("[{}]", name));
+    lines.extend(render_keys(&[], values));
+  }
+  return Ok(lines.join("\n") + "\n");
+}
+
+/// Renders the options of a section, described ones first.
+fn render_keys(keys: &[Key], mut values: toml::Table) -> Vec<String> {
+  let mut lines = Vec::new();
+  for key in keys {
+    let value = values
+      .remove(key.name)
+      .map(|value| value.to_string())
+      .or(key.example.map(String::from));
+    let Some(value) = value else {
+      continue;
+    };
+    lines.push(String::new());
+    lines.extend(comment(key.description));
+    lines.push(format!("# {} = {}", key.name, value));
+  }
+  for (name, value) in values {
+    lines.push(String::new());
+    lines.push(format!("# {} = {}", name, value));
+  }
+  return lines;
+}
+
+/// Wraps a description into comment lines.
+fn comment(text: &str) -> Vec<String> {
+  let wrap = Wrap {
+    width: DESCRIPTION_WIDTH,
+    reflow: true,
+  };
+  return wrap
+    .apply(text)
+    .lines()
+    .map(|line| format!("# {}", line))
+    .collect();
+}
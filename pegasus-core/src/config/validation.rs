@@ -0,0 +1,380 @@
+//! Configuration validation with actionable diagnostics.
+//!
+//! Runs after the configuration layers are merged and checks values that
+//! deserialize fine but cannot work at runtime: malformed URLs, thresholds
+//! outside 0.0–1.0, missing files and unknown keys. All problems are
+//! collected and reported at once, pointing at the file, line and column
+//! that set the offending value when it came from a configuration file.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::config::resolver::{ConfigOrigin, ConfigResolver};
+use crate::dictation;
+use crate::llm::tokenizer::Tokenizer;
+use crate::output::format::OutputFormat;
+use crate::output::sink;
+
+/// Most stop sequences the OpenAI chat completions API accepts.
+const MAX_STOP_SEQUENCES: usize = 4;
+
+/// Severity of a configuration diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  /// The configuration works, but is likely not what the user intended
+  Warning,
+  /// The configuration cannot be used
+  Error,
+}
+
+/// A single problem found in the configuration.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  key: String,
+  message: String,
+  origin: ConfigOrigin,
+  location: Option<(usize, usize)>,
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {} ({}", self.key, self.message, self.origin)?;
+    if let Some((line, column)) = self.location {
+      write!(f, ", line {}, column {}", line, column)?;
+    }
+    return write!(f, ")");
+  }
+}
+
+/// Validates the resolved configuration.
+///
+/// # Arguments
+///
+/// * `resolver` - The resolver the configuration was built from
+/// * `config` - The resolved configuration
+///
+/// # Returns
+///
+/// All diagnostics found, in key order.
+pub fn validate(resolver: &ConfigResolver, config: &Config) -> Vec<Diagnostic> {
+  let mut problems: Vec<(Severity, &str, String)> = Vec::new();
+
+  check_url(&mut problems, "llm.url", &config.get_llm_url());
+  check_url(
+    &mut problems,
+    "llm.fallback_url",
+    &config.get_llm_fallback_url(),
+  );
+  for endpoint in config.get_llm_endpoints() {
+    check_url(&mut problems, "llm.endpoints", &endpoint);
+  }
+  check_url(&mut problems, "network.proxy", &config.get_proxy());
+  check_url(
+    &mut problems,
+    "transcription.url",
+    &config.get_transcription_url(),
+  );
+  check_url(
+    &mut problems,
+    "output.webhook_url",
+    &config.get_output_webhook_url(),
+  );
+
+  let metrics_address = config.get_server_metrics_address();
+  if !metrics_address.is_empty()
+    && metrics_address.parse::<SocketAddr>().is_err()
+  {
+    problems.push((
+      Severity::Error,
+      "server.metrics_address",
+      format!(
+        "'{}' is not a valid address, expected e.g. '127.0.0.1:9464'",
+        metrics_address
+      ),
+    ));
+  }
+
+  if config
+    .get_server_tokens()
+    .iter()
+    .any(|token| token.token.is_empty())
+  {
+    problems.push((
+      Severity::Error,
+      "server.tokens",
+      "every token must have a non-empty 'token' value".to_string(),
+    ));
+  }
+
+  let stop = config.get_llm_stop();
+  if stop.iter().any(|sequence| sequence.is_empty()) {
+    problems.push((
+      Severity::Error,
+      "llm.stop",
+      "stop sequences must not be empty".to_string(),
+    ));
+  }
+  if stop.len() > MAX_STOP_SEQUENCES {
+    problems.push((
+      Severity::Warning,
+      "llm.stop",
+      format!(
+        "{} stop sequences set, but OpenAI-compatible services may accept \
+         at most {}",
+        stop.len(),
+        MAX_STOP_SEQUENCES
+      ),
+    ));
+  }
+
+  let reading_level = config.get_llm_target_reading_level();
+  if !(0.0..=20.0).contains(&reading_level) {
+    problems.push((
+      Severity::Error,
+      "llm.target_reading_level",
+      format!("must be between 0 and 20, got {}", reading_level),
+    ));
+  }
+
+  let min_similarity = config.get_llm_min_similarity();
+  if !(0.0..=1.0).contains(&min_similarity) {
+    problems.push((
+      Severity::Error,
+      "llm.min_similarity",
+      format!("must be between 0 and 1, got {}", min_similarity),
+    ));
+  }
+
+  if let Some(temperature) = config.get_llm_temperature()
+    && !(0.0..=2.0).contains(&temperature)
+  {
+    problems.push((
+      Severity::Error,
+      "llm.temperature",
+      format!("must be between 0.0 and 2.0, got {}", temperature),
+    ));
+  }
+
+  for (key, price) in [
+    ("llm.input_price", config.get_llm_input_price()),
+    ("llm.output_price", config.get_llm_output_price()),
+  ] {
+    if !(price >= 0.0 && price.is_finite()) {
+      problems.push((
+        Severity::Error,
+        key,
+        format!("must be 0 or a positive number, got {}", price),
+      ));
+    }
+  }
+
+  let chunk_seconds = config.get_transcription_chunk_seconds();
+  if !(chunk_seconds > 0.0 && chunk_seconds.is_finite()) {
+    problems.push((
+      Severity::Error,
+      "transcription.chunk_seconds",
+      format!("must be a positive number, got {}", chunk_seconds),
+    ));
+  }
+
+  if config.get_transcription_concurrency() == 0 {
+    problems.push((
+      Severity::Error,
+      "transcription.concurrency",
+      "must be at least 1".to_string(),
+    ));
+  }
+
+  for (key, specs) in [
+    ("output.sinks", config.get_output_sinks()),
+    ("dictation.sinks", config.get_dictation_sinks()),
+  ] {
+    if let Some(Err(e)) = specs
+      .iter()
+      .map(|spec| sink::create(spec, config, OutputFormat::Text))
+      .find(Result::is_err)
+    {
+      problems.push((Severity::Error, key, e.to_string()));
+    }
+  }
+
+  if !config
+    .get_dictation_record_command()
+    .contains(dictation::OUTPUT_PLACEHOLDER)
+  {
+    problems.push((
+      Severity::Error,
+      "dictation.record_command",
+      format!(
+        "must contain {} where the recording is written",
+        dictation::OUTPUT_PLACEHOLDER
+      ),
+    ));
+  }
+
+  let tokenizer = config.get_llm_tokenizer();
+  if !tokenizer.is_empty() && Tokenizer::named(&tokenizer).is_none() {
+    problems.push((
+      Severity::Error,
+      "llm.tokenizer",
+      format!(
+        "unknown tokenizer {:?}, expected one of {}",
+        tokenizer,
+        Tokenizer::names().join(", ")
+      ),
+    ));
+  }
+
+  let threshold = config.get_whisper_probability_threshold();
+  if !(0.0..=1.0).contains(&threshold) {
+    problems.push((
+      Severity::Error,
+      "whisper.probability_threshold",
+      format!("must be between 0.0 and 1.0, got {}", threshold),
+    ));
+  }
+
+  let logprob_threshold = config.get_whisper_logprob_threshold();
+  if !(0.0..=1.0).contains(&logprob_threshold) {
+    problems.push((
+      Severity::Error,
+      "whisper.logprob_threshold",
+      format!("must be between 0.0 and 1.0, got {}", logprob_threshold),
+    ));
+  }
+
+  let gap_threshold = config.get_whisper_gap_threshold_seconds();
+  if !(gap_threshold >= 0.0 && gap_threshold.is_finite()) {
+    problems.push((
+      Severity::Error,
+      "whisper.gap_threshold_seconds",
+      format!("must be 0 or a positive number, got {}", gap_threshold),
+    ));
+  }
+
+  check_file(
+    &mut problems,
+    "dictionary.path",
+    &config.get_custom_dictionary_path(),
+  );
+  check_file(
+    &mut problems,
+    "dictionary.spellcheck",
+    &config.get_dictionary_spellcheck(),
+  );
+  check_file(
+    &mut problems,
+    "llm.api_key_file",
+    &config.get_llm_api_key_file(),
+  );
+  check_file(
+    &mut problems,
+    "transcription.api_key_file",
+    &config.get_transcription_api_key_file(),
+  );
+  check_file(
+    &mut problems,
+    "llm.grammar_file",
+    &config.get_llm_grammar_file(),
+  );
+
+  let unknown_keys = resolver.unknown_keys();
+  for key in &unknown_keys {
+    problems.push((Severity::Warning, key.as_str(), "unknown key".to_string()));
+  }
+
+  let mut diagnostics: Vec<Diagnostic> = problems
+    .into_iter()
+    .map(|(severity, key, message)| {
+      let origin = resolver.origin_of(key);
+      let location = locate(&origin, key);
+      Diagnostic {
+        severity,
+        key: key.to_string(),
+        message,
+        origin,
+        location,
+      }
+    })
+    .collect();
+
+  diagnostics.sort_by(|a, b| a.key.cmp(&b.key));
+  return diagnostics;
+}
+
+/// Records an error if a non-empty URL setting cannot be parsed.
+fn check_url<'a>(
+  problems: &mut Vec<(Severity, &'a str, String)>,
+  key: &'a str,
+  url: &str,
+) {
+  if url.is_empty() {
+    return;
+  }
+  if let Err(e) = reqwest::Url::parse(url) {
+    problems.push((
+      Severity::Error,
+      key,
+      format!("'{}' is not a valid URL: {}", url, e),
+    ));
+  }
+}
+
+/// Records an error if a non-empty file setting points at a missing file.
+fn check_file<'a>(
+  problems: &mut Vec<(Severity, &'a str, String)>,
+  key: &'a str,
+  path: &str,
+) {
+  if path.is_empty() || Path::new(path).is_file() {
+    return;
+  }
+  problems.push((
+    Severity::Error,
+    key,
+    format!("file '{}' does not exist", path),
+  ));
+}
+
+/// Finds the 1-based line and column of a key's value in its source file.
+///
+/// # Arguments
+///
+/// * `origin` - The layer that set the value
+/// * `key` - The dotted key path
+///
+/// # Returns
+///
+/// The line and column, or `None` if the value did not come from a file.
+fn locate(origin: &ConfigOrigin, key: &str) -> Option<(usize, usize)> {
+  let path = match origin {
+    ConfigOrigin::System(path)
+    | ConfigOrigin::User(path)
+    | ConfigOrigin::Project(path) => path,
+    _ => return None,
+  };
+
+  let content = std::fs::read_to_string(path).ok()?;
+  let document = toml::de::DeTable::parse(&content).ok()?;
+
+  let mut table = document.get_ref();
+  let mut span = None;
+  for part in key.split('.') {
+    let (name, value) =
+      table.iter().find(|(name, _)| name.get_ref() == part)?;
+    span = Some(name.span());
+    match value.get_ref() {
+      toml::de::DeValue::Table(nested) => table = nested,
+      _ => break,
+    }
+  }
+
+  let offset = span?.start;
+  let before = &content[..offset];
+  let line = before.matches('\n').count() + 1;
+  let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+  return Some((line, column));
+}
@@ -0,0 +1,171 @@
+//! Control of a running dictation loop.
+//!
+//! While `pegasus dictate` runs, its PID is kept in
+//! `$XDG_RUNTIME_DIR/pegasus-dictate.pid` under a lock, so `pegasus dictate
+//! --toggle` and `--stop` signal that process only, and a second dictation
+//! loop refuses to start. A PID file left behind by a killed process is not
+//! locked and is ignored.
+//!
+//! `pegasus dictate --background` starts the loop as a detached process
+//! whose log is written to `$XDG_STATE_HOME/pegasus/dictate.log`.
+
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Mutex;
+
+use xdg::BaseDirectories;
+
+use crate::dictation::errors::{DictationError, DictationResult};
+use crate::files::errors::FileError;
+use crate::files::operations::{self, FileLock};
+
+const PID_FILE_NAME: &str = "pegasus-dictate.pid";
+const STATE_DIRECTORY: &str = "pegasus";
+const LOG_FILE_NAME: &str = "dictate.log";
+
+/// Flag that starts the loop in the background, removed from the arguments
+/// of the detached process.
+pub const BACKGROUND_FLAG: &str = "--background";
+
+/// Path of the PID file of this process, removed by [`remove_pid_file`].
+static PID_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// The PID file of a running dictation loop, removed when dropped.
+pub struct PidFile {
+  _lock: FileLock,
+}
+
+impl PidFile {
+  /// Records this process as the running dictation loop.
+  ///
+  /// # Returns
+  ///
+  /// A `DictationResult<PidFile>` holding the PID file, or
+  /// `DictationError::Running` if another loop holds it.
+  pub fn create() -> DictationResult<Self> {
+    let path = pid_path()?;
+    let path_name = path.to_string_lossy().to_string();
+    let lock = operations::try_lock_file(&path_name).map_err(|e| match e {
+      FileError::Locked(_) => DictationError::Running,
+      e => DictationError::Control(e.to_string()),
+    })?;
+    std::fs::write(&path, format!("{}\n", std::process::id()))
+      .map_err(|e| DictationError::Control(format!("{}: {}", path_name, e)))?;
+    *PID_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(path.clone());
+    return Ok(PidFile { _lock: lock });
+  }
+}
+
+impl Drop for PidFile {
+  fn drop(&mut self) {
+    remove_pid_file();
+  }
+}
+
+/// Removes the PID file of this process, if it wrote one.
+///
+/// Call this before leaving the process without unwinding, e.g. before
+/// `std::process::exit` or when interrupted by a signal.
+pub fn remove_pid_file() {
+  if let Some(path) = PID_FILE.lock().unwrap_or_else(|e| e.into_inner()).take()
+  {
+    let _ = std::fs::remove_file(path);
+  }
+}
+
+/// Sends a signal to the running dictation loop.
+///
+/// # Arguments
+///
+/// * `signal` - The signal name given to `kill`, such as `USR1`
+///
+/// # Returns
+///
+/// A `DictationResult<u32>` containing the PID that was signalled, or
+/// `DictationError::NotRunning` if no dictation loop is running.
+pub async fn send(signal: &str) -> DictationResult<u32> {
+  let path = pid_path()?;
+  let path_name = path.to_string_lossy().to_string();
+  // The lock is only free when no loop is running, whatever the PID file
+  // says.
+  match operations::try_lock_file(&path_name) {
+    Ok(_) => return Err(DictationError::NotRunning),
+    Err(FileError::Locked(_)) => {}
+    Err(e) => return Err(DictationError::Control(e.to_string())),
+  }
+  let pid = read_pid(&path).ok_or(DictationError::NotRunning)?;
+
+  let status = tokio::process::Command::new("kill")
+    .arg(format!("-{}", signal))
+    .arg(pid.to_string())
+    .status()
+    .await
+    .map_err(|e| DictationError::Control(format!("kill: {}", e)))?;
+  if !status.success() {
+    return Err(DictationError::Control(format!(
+      "failed to signal process {}: {}",
+      pid, status
+    )));
+  }
+  return Ok(pid);
+}
+
+/// Starts the dictation loop as a detached process.
+///
+/// The process is started from the current executable with the current
+/// arguments minus [`BACKGROUND_FLAG`], in its own process group so the
+/// terminal's Ctrl-C does not reach it, with its output appended to the
+/// dictation log.
+///
+/// # Returns
+///
+/// A `DictationResult<(u32, PathBuf)>` containing the PID of the process
+/// and the path of its log.
+pub fn spawn_background() -> DictationResult<(u32, PathBuf)> {
+  let control_error =
+    |e: std::io::Error| DictationError::Control(e.to_string());
+
+  let log_path = BaseDirectories::with_prefix(STATE_DIRECTORY)
+    .place_state_file(LOG_FILE_NAME)
+    .map_err(control_error)?;
+  let log = std::fs::OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(&log_path)
+    .map_err(control_error)?;
+  let executable = std::env::current_exe().map_err(control_error)?;
+  let args = std::env::args_os()
+    .skip(1)
+    .filter(|arg| arg != BACKGROUND_FLAG);
+
+  let mut command = std::process::Command::new(executable);
+  command
+    .args(args)
+    .stdin(Stdio::null())
+    .stdout(log.try_clone().map_err(control_error)?)
+    .stderr(log);
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+  }
+  let child = command.spawn().map_err(control_error)?;
+  return Ok((child.id(), log_path));
+}
+
+/// Gets the PID file path.
+fn pid_path() -> DictationResult<PathBuf> {
+  return BaseDirectories::new()
+    .get_runtime_directory()
+    .map(|runtime_dir| runtime_dir.join(PID_FILE_NAME))
+    .map_err(|_| {
+      DictationError::Control(String::from(
+        "no runtime directory available (is XDG_RUNTIME_DIR set?)",
+      ))
+    });
+}
+
+/// Reads the PID recorded in a PID file.
+fn read_pid(path: &std::path::Path) -> Option<u32> {
+  return std::fs::read_to_string(path).ok()?.trim().parse().ok();
+}
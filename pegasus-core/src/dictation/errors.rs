@@ -0,0 +1,37 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Dictation errors.
+///
+/// Represents errors that stop the dictation loop.
+#[derive(Error, Debug)]
+pub enum DictationError {
+  #[error(
+    "{}",
+    t!(
+      "error-dictation-record",
+      command = .command.as_str(),
+      error = .error.as_str()
+    )
+  )]
+  Record { command: String, error: String },
+
+  #[error("{0}")]
+  Sink(String),
+
+  #[error("{}", t!("error-dictation-signal", error = .0.as_str()))]
+  Signal(String),
+
+  #[error("{}", t!("error-dictation-running"))]
+  Running,
+
+  #[error("{}", t!("error-dictation-not-running"))]
+  NotRunning,
+
+  #[error("{}", t!("error-dictation-control", error = .0.as_str()))]
+  Control(String),
+}
+
+/// Result type for dictation operations.
+pub type DictationResult<T> = Result<T, DictationError>;
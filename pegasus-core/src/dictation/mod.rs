@@ -0,0 +1,320 @@
+//! Hands-free dictation.
+//!
+//! `pegasus dictate` runs until interrupted: it records an utterance,
+//! transcribes it with the `[transcription]` service, refines it like a
+//! Whisper JSON transcription and delivers the text, then records the next
+//! one.
+//!
+//! ## Triggers
+//!
+//! - `voice`: the record command waits for speech and stops after a pause.
+//!   The default command uses sox's `silence` effect as a simple voice
+//!   activity detector.
+//! - `signal`: recording starts on `SIGUSR1` and stops on the next one, or
+//!   when the record command exits by itself. Bind a desktop hotkey to
+//!   `pegasus dictate --toggle`, which signals the running loop only (see
+//!   [`control`]), for push-to-talk.
+//!
+//! `pegasus dictate --background` runs the loop as a detached process and
+//! `pegasus dictate --stop` ends it.
+//!
+//! The refined text of each utterance is written to `[dictation] sinks`,
+//! by default the clipboard (see [`crate::output::sink`]).
+//!
+//! Failures to transcribe, refine or deliver one utterance are logged and
+//! the loop goes on; failures to record stop it.
+
+pub mod control;
+pub mod errors;
+
+use std::fmt;
+use std::process::Stdio;
+use std::sync::Mutex;
+
+use tokio::process::Command;
+
+use crate::app::App;
+use crate::app::errors::RuntimeError;
+use crate::dictation::errors::{DictationError, DictationResult};
+use crate::files::temporary::TemporaryFile;
+use crate::logging::request_id;
+use crate::output::format::OutputFormat;
+use crate::output::sink::{self, OutputSink};
+use crate::shell;
+use crate::usage;
+use crate::{elog, logging, vlog};
+
+/// Placeholder for the recording path in the record command.
+pub const OUTPUT_PLACEHOLDER: &str = "{output}";
+
+/// Record command used unless one is configured.
+///
+/// Records 16 kHz mono audio with sox, starting once the level rises above
+/// 1% for 0.1 seconds and stopping after 1.5 seconds below it.
+pub const DEFAULT_RECORD_COMMAND: &str =
+  "rec -q -c 1 -r 16000 {output} silence 1 0.1 1% 1 1.5 1%";
+
+/// Recordings smaller than this hold no speech worth sending: a WAV header
+/// and a quarter of a second of 16 kHz 16-bit mono audio.
+const MIN_RECORDING_BYTES: u64 = 44 + 8000;
+
+/// Process group of the recorder running, stopped by [`stop_all`].
+static RECORDER: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Stops the recorder and removes the PID file of the dictation loop.
+///
+/// Call this before leaving the process without unwinding, e.g. before
+/// `std::process::exit` or when interrupted by a signal.
+pub fn stop_all() {
+  #[cfg(unix)]
+  if let Some(pid) = RECORDER.lock().unwrap_or_else(|e| e.into_inner()).take() {
+    let _ = std::process::Command::new("kill")
+      .args(["-TERM", "--", &format!("-{}", pid)])
+      .stdin(Stdio::null())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .status();
+  }
+  control::remove_pid_file();
+}
+
+/// What starts and stops a recording.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DictationTrigger {
+  /// The record command detects speech and pauses
+  #[default]
+  Voice,
+  /// `SIGUSR1` starts and stops recording
+  Signal,
+}
+
+impl fmt::Display for DictationTrigger {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      DictationTrigger::Voice => "voice",
+      DictationTrigger::Signal => "signal",
+    });
+  }
+}
+
+/// Listens for the push-to-talk signal.
+struct Trigger {
+  #[cfg(unix)]
+  signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Trigger {
+  fn new(trigger: DictationTrigger) -> DictationResult<Self> {
+    #[cfg(unix)]
+    {
+      use tokio::signal::unix::{SignalKind, signal};
+
+      let signal = match trigger {
+        DictationTrigger::Voice => None,
+        DictationTrigger::Signal => Some(
+          signal(SignalKind::user_defined1())
+            .map_err(|e| DictationError::Signal(e.to_string()))?,
+        ),
+      };
+      return Ok(Trigger { signal });
+    }
+    #[cfg(not(unix))]
+    {
+      if trigger == DictationTrigger::Signal {
+        return Err(DictationError::Signal(String::from(
+          "the signal trigger is only supported on Unix systems",
+        )));
+      }
+      return Ok(Trigger {});
+    }
+  }
+
+  /// Waits for the signal, or returns at once without a signal trigger.
+  async fn pressed(&mut self) {
+    #[cfg(unix)]
+    if let Some(signal) = self.signal.as_mut() {
+      signal.recv().await;
+    }
+  }
+
+  /// Waits for the signal, or forever without a signal trigger.
+  async fn released(&mut self) {
+    #[cfg(unix)]
+    if let Some(signal) = self.signal.as_mut() {
+      signal.recv().await;
+      return;
+    }
+    std::future::pending::<()>().await;
+  }
+
+  fn is_signal(&self) -> bool {
+    #[cfg(unix)]
+    return self.signal.is_some();
+    #[cfg(not(unix))]
+    return false;
+  }
+}
+
+/// Runs the dictation loop until the application is cancelled.
+///
+/// A recording in progress is finished and dropped; a recording being
+/// transcribed or refined is abandoned.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+///
+/// # Returns
+///
+/// A `DictationResult<()>` with the error that stopped the loop, if any.
+pub async fn run(app: &App) -> DictationResult<()> {
+  let config = app.config();
+  let record_command = config.get_dictation_record_command();
+  let sinks =
+    sink::create_all(&config.get_dictation_sinks(), config, OutputFormat::Text)
+      .map_err(|e| DictationError::Sink(e.to_string()))?;
+  let mut trigger = Trigger::new(config.get_dictation_trigger())?;
+  let _pid_file = control::PidFile::create()?;
+
+  elog!(
+    logging::INFO,
+    "Dictating with the {} trigger; press Ctrl-C to stop",
+    config.get_dictation_trigger()
+  );
+
+  loop {
+    if trigger.is_signal() {
+      vlog!("Waiting for SIGUSR1 to start recording");
+    }
+    tokio::select! {
+      _ = trigger.pressed() => {}
+      _ = app.cancellation().cancelled() => return Ok(()),
+    }
+
+    let recording = TemporaryFile::create_in_cache("dictation", "wav")
+      .await
+      .map_err(|e| DictationError::Record {
+        command: record_command.clone(),
+        error: e.to_string(),
+      })?;
+    // Ctrl-C interrupts the recorder too, which may then report a failure.
+    let recorded = record(&record_command, &recording, &mut trigger).await;
+    if app.cancellation().is_cancelled() {
+      return Ok(());
+    }
+    recorded?;
+
+    let size = tokio::fs::metadata(recording.path())
+      .await
+      .map_or(0, |metadata| metadata.len());
+    if size < MIN_RECORDING_BYTES {
+      vlog!("Skipping a recording of {} bytes", size);
+      continue;
+    }
+
+    let delivery = request_id::scope(
+      request_id::generate(),
+      deliver(app, &recording, &sinks),
+    );
+    usage::track(app.config(), "dictate", delivery).await;
+  }
+}
+
+/// Transcribes and refines one recording and writes it to the sinks,
+/// logging any failure.
+async fn deliver(
+  app: &App,
+  recording: &TemporaryFile,
+  sinks: &[Box<dyn OutputSink>],
+) {
+  let text = match app
+    .transcribe_audio(recording.path_string(), OutputFormat::Text)
+    .await
+  {
+    Ok(text) => text,
+    Err(RuntimeError::Cancelled(_)) => return,
+    Err(e) => {
+      elog!(logging::ERROR, "{}", e);
+      return;
+    }
+  };
+  let text = text.trim();
+  if text.is_empty() {
+    vlog!("Nothing was said");
+    return;
+  }
+
+  if let Err(e) = sink::write_all(sinks, text).await {
+    elog!(logging::ERROR, "{}", e);
+  }
+}
+
+/// Records one utterance, stopping early on the trigger signal.
+async fn record(
+  command: &str,
+  recording: &TemporaryFile,
+  trigger: &mut Trigger,
+) -> DictationResult<()> {
+  let command = command
+    .replace(OUTPUT_PLACEHOLDER, &shell::quote(&recording.path_string()));
+  let record_error = |error: String| DictationError::Record {
+    command: command.clone(),
+    error,
+  };
+
+  vlog!("Recording: {}", command);
+  let mut child = Command::new("sh");
+  child
+    .arg("-c")
+    .arg(format!("exec {}", command))
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .kill_on_drop(true);
+  // In its own process group, the recorder can be stopped with the
+  // processes it started when the loop is terminated.
+  #[cfg(unix)]
+  child.process_group(0);
+  let mut child = child.spawn().map_err(|e| record_error(e.to_string()))?;
+  *RECORDER.lock().unwrap_or_else(|e| e.into_inner()) = child.id();
+
+  let status = tokio::select! {
+    status = child.wait() => status,
+    _ = trigger.released() => {
+      interrupt(&mut child).await;
+      // A recorder stopped by the trigger may report the interruption as
+      // a failure, but the recording is still usable.
+      child.wait().await.map(|_| std::process::ExitStatus::default())
+    }
+  }
+  .map_err(|e| record_error(e.to_string()));
+  RECORDER.lock().unwrap_or_else(|e| e.into_inner()).take();
+  let status = status?;
+
+  if !status.success() {
+    return Err(record_error(status.to_string()));
+  }
+  return Ok(());
+}
+
+/// Asks a recorder to finish its file and exit.
+async fn interrupt(child: &mut tokio::process::Child) {
+  #[cfg(unix)]
+  if let Some(pid) = child.id() {
+    let _ = Command::new("kill")
+      .args(["-INT", &pid.to_string()])
+      .status()
+      .await;
+    return;
+  }
+  let _ = child.start_kill();
+}
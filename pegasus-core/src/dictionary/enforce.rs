@@ -0,0 +1,475 @@
+//! Deterministic enforcement of dictionary terms.
+//!
+//! Terms marked with `!` are not only emphasized in the prompt: after
+//! refinement, words that nearly match them are replaced with the term as
+//! written. A word nearly matches a term if it is:
+//! - the term in another casing (`kubernetes`)
+//! - a misspelling starting with the same letter, within one edit of terms
+//!   of 6 to 9 characters or two edits of longer terms (`Kubernets`).
+//!   Terms of 5 characters or fewer are only corrected in casing, since
+//!   short words are too easily confused with others.
+//! - the term split in two (`Postgre SQL`)
+//!
+//! Multi-word terms are matched word by word. Surrounding punctuation and
+//! a possessive `'s` are kept, and words that are themselves dictionary
+//! terms are never replaced with another term. Neither are inflected forms
+//! of the term (`Terraforms`, `Dockerized`) nor, with `[dictionary]
+//! spellcheck` set, words the spellchecker knows (`docket`).
+//!
+//! The [`MatchOptions`] of the dictionary change how words match:
+//! case-sensitive dictionaries only correct misspellings in the same
+//! capitalization, and without `whole_word` the term is also corrected in
+//! casing where it occurs inside a longer word. Matches of regex entries are
+//! replaced with their term last.
+
+use std::collections::HashSet;
+
+use regex::RegexBuilder;
+
+use crate::dictionary::spellcheck::Spellchecker;
+use crate::dictionary::{DictionaryEntry, MatchOptions};
+use crate::vlog;
+
+/// Characters removed from the start of a word before matching.
+const LEADING_PUNCTUATION: &[char] =
+  &['"', '\'', '(', '[', '{', '<', '\u{201c}', '\u{2018}'];
+
+/// Characters removed from the end of a word before matching.
+const TRAILING_PUNCTUATION: &[char] = &[
+  '.', ',', ';', ':', '!', '?', '"', '\'', ')', ']', '}', '>', '\u{201d}',
+  '\u{2019}',
+];
+
+/// Possessive suffixes kept after a matched word.
+const POSSESSIVE_SUFFIXES: [&str; 2] = ["'s", "\u{2019}s"];
+
+/// Longest term, in characters, only corrected in casing.
+const CASE_ONLY_LENGTH: usize = 5;
+
+/// Longest term, in characters, corrected within one edit.
+const ONE_EDIT_LENGTH: usize = 9;
+
+/// Endings of inflected and derived forms of a term, which are left alone.
+const INFLECTIONS: &[&str] = &[
+  "s", "es", "ed", "d", "ing", "er", "ers", "ized", "ised", "ize", "ise",
+];
+
+/// Words that are not dictionary terms but other words, and so are never
+/// corrected to a term.
+struct KnownWords<'a> {
+  /// All terms of the dictionary, case-folded
+  terms: HashSet<String>,
+  /// The spellchecker of `[dictionary] spellcheck`, if any
+  spellchecker: Option<&'a Spellchecker>,
+}
+
+impl KnownWords<'_> {
+  /// Checks whether a word is a term or spelled correctly.
+  fn contains(&self, word: &str, options: &MatchOptions) -> bool {
+    return self.terms.contains(&fold_case(word, options))
+      || self
+        .spellchecker
+        .is_some_and(|spellchecker| spellchecker.is_correct(word));
+  }
+}
+
+/// A word of the text, as a byte range.
+struct Word {
+  start: usize,
+  end: usize,
+  /// Whether punctuation follows the word, ending a phrase
+  punctuated: bool,
+}
+
+/// Corrects near matches of enforced terms to the terms as written.
+///
+/// # Arguments
+///
+/// * `text` - The refined text
+/// * `entries` - The dictionary entries
+/// * `options` - How terms are matched
+/// * `spellchecker` - Knows words never corrected to a term, if any
+///
+/// # Returns
+///
+/// The text with near matches of enforced terms corrected, or unchanged if
+/// no term is enforced.
+pub fn apply(
+  text: &str,
+  entries: &[DictionaryEntry],
+  options: &MatchOptions,
+  spellchecker: Option<&Spellchecker>,
+) -> String {
+  let (patterns, mut enforced): (Vec<&DictionaryEntry>, Vec<&DictionaryEntry>) =
+    entries
+      .iter()
+      .filter(|entry| entry.enforced)
+      .partition(|entry| entry.pattern.is_some());
+  if patterns.is_empty() && enforced.is_empty() {
+    return text.to_string();
+  }
+  // Longer terms first, so `Google Cloud` wins over `Google`.
+  enforced.sort_by_key(|entry| {
+    return std::cmp::Reverse(entry.term.split_whitespace().count());
+  });
+  let known = KnownWords {
+    terms: entries
+      .iter()
+      .flat_map(|entry| {
+        return std::iter::once(&entry.term).chain(&entry.abbreviation);
+      })
+      .map(|term| fold_case(term, options))
+      .collect(),
+    spellchecker,
+  };
+
+  let mut result = replace_words(text, &enforced, &known, options);
+  if !options.whole_word && !options.case_sensitive {
+    for entry in &enforced {
+      let pattern = regex::escape(&entry.term);
+      result = replace_matches(&result, &pattern, &entry.term, options);
+    }
+  }
+  for entry in patterns {
+    let Some(pattern) = &entry.pattern else {
+      continue;
+    };
+    let pattern = if options.whole_word {
+      format!(r"\b(?:{})\b", pattern)
+    } else {
+      pattern.clone()
+    };
+    result = replace_matches(&result, &pattern, &entry.term, options);
+  }
+  return result;
+}
+
+/// Corrects words nearly matching enforced terms.
+///
+/// # Arguments
+///
+/// * `text` - The refined text
+/// * `enforced` - The enforced entries, longest terms first
+/// * `known` - The words never corrected to a term
+/// * `options` - How terms are matched
+///
+/// # Returns
+///
+/// The text with the matched words replaced.
+fn replace_words(
+  text: &str,
+  enforced: &[&DictionaryEntry],
+  known: &KnownWords,
+  options: &MatchOptions,
+) -> String {
+  let words = split_words(text);
+  let mut claimed = vec![false; words.len()];
+  let mut replacements: Vec<(usize, usize, &str)> = Vec::new();
+  for entry in enforced.iter().copied() {
+    let term_words: Vec<&str> = entry.term.split_whitespace().collect();
+    let mut index = 0;
+    while index < words.len() {
+      let Some(length) =
+        match_at(text, &words, &claimed, index, &term_words, known, options)
+      else {
+        index += 1;
+        continue;
+      };
+      let (start, end) = (words[index].start, words[index + length - 1].end);
+      if text[start..end] != entry.term {
+        vlog!(
+          "Corrected '{}' to dictionary term '{}'",
+          &text[start..end],
+          entry.term
+        );
+        replacements.push((start, end, &entry.term));
+      }
+      claimed[index..index + length].fill(true);
+      index += length;
+    }
+  }
+
+  replacements.sort_by_key(|(start, _, _)| *start);
+  let mut result = String::with_capacity(text.len());
+  let mut position = 0;
+  for (start, end, term) in replacements {
+    result.push_str(&text[position..start]);
+    result.push_str(term);
+    position = end;
+  }
+  result.push_str(&text[position..]);
+  return result;
+}
+
+/// Replaces the matches of a pattern with a term, logging the corrections.
+///
+/// Matches inside an occurrence of the term as written are left alone, so
+/// a pattern like `kube` does not rewrite `Kubernetes` itself.
+///
+/// # Arguments
+///
+/// * `text` - The text
+/// * `pattern` - The regular expression
+/// * `term` - The term replacing its matches
+/// * `options` - How terms are matched
+///
+/// # Returns
+///
+/// The text with the matches replaced, or unchanged if the pattern is
+/// invalid.
+fn replace_matches(
+  text: &str,
+  pattern: &str,
+  term: &str,
+  options: &MatchOptions,
+) -> String {
+  let Ok(regex) = RegexBuilder::new(pattern)
+    .case_insensitive(!options.case_sensitive)
+    .build()
+  else {
+    return text.to_string();
+  };
+  let written: Vec<(usize, usize)> = text
+    .match_indices(term)
+    .map(|(start, found)| (start, start + found.len()))
+    .collect();
+
+  let mut result = String::with_capacity(text.len());
+  let mut position = 0;
+  for found in regex.find_iter(text) {
+    if found.is_empty()
+      || written.iter().any(|(start, end)| {
+        return found.start() >= *start && found.end() <= *end;
+      })
+    {
+      continue;
+    }
+    if found.as_str() != term {
+      vlog!(
+        "Corrected '{}' to dictionary term '{}'",
+        found.as_str(),
+        term
+      );
+    }
+    result.push_str(&text[position..found.start()]);
+    result.push_str(term);
+    position = found.end();
+  }
+  result.push_str(&text[position..]);
+  return result;
+}
+
+/// Matches a term against the words starting at an index.
+///
+/// # Returns
+///
+/// How many words match the term, or `None` if they do not.
+fn match_at(
+  text: &str,
+  words: &[Word],
+  claimed: &[bool],
+  index: usize,
+  term_words: &[&str],
+  known: &KnownWords,
+  options: &MatchOptions,
+) -> Option<usize> {
+  let available = |length: usize| {
+    let end = index + length;
+    return end <= words.len()
+      && !claimed[index..end].iter().any(|claimed| *claimed)
+      && !words[index..end - 1].iter().any(|word| word.punctuated);
+  };
+  let word = |offset: usize| {
+    let word = &words[index + offset];
+    return &text[word.start..word.end];
+  };
+
+  let length = term_words.len();
+  if available(length)
+    && term_words.iter().enumerate().all(|(offset, term_word)| {
+      return is_near_match(word(offset), term_word, known, options);
+    })
+  {
+    return Some(length);
+  }
+
+  let compact = fold_case(&term_words.concat(), options);
+  if available(length + 1) {
+    let joined: String = (0..=length).map(word).collect();
+    if fold_case(&joined, options) == compact {
+      return Some(length + 1);
+    }
+  }
+  return None;
+}
+
+/// Checks whether a word nearly matches a word of a term.
+fn is_near_match(
+  word: &str,
+  term_word: &str,
+  known: &KnownWords,
+  options: &MatchOptions,
+) -> bool {
+  let folded = fold_case(word, options);
+  let term_word = fold_case(term_word, options);
+  if folded == term_word {
+    return true;
+  }
+  let length = term_word.chars().count();
+  if length <= CASE_ONLY_LENGTH
+    || folded.chars().next() != term_word.chars().next()
+    || is_inflection(&folded, &term_word)
+    || known.contains(word, options)
+  {
+    return false;
+  }
+  let allowed = if length <= ONE_EDIT_LENGTH { 1 } else { 2 };
+  return edit_distance(&folded, &term_word) <= allowed;
+}
+
+/// Checks whether a word is the term with an inflectional ending, such as
+/// `terraforms` for `terraform`.
+fn is_inflection(word: &str, term_word: &str) -> bool {
+  return word.strip_prefix(term_word).is_some_and(|ending| {
+    return INFLECTIONS.contains(&ending.to_lowercase().as_str());
+  });
+}
+
+/// Lowercases a word unless terms are matched case-sensitively.
+fn fold_case(word: &str, options: &MatchOptions) -> String {
+  if options.case_sensitive {
+    return word.to_string();
+  }
+  return word.to_lowercase();
+}
+
+/// Splits text into words, without surrounding punctuation or possessive
+/// suffixes.
+fn split_words(text: &str) -> Vec<Word> {
+  let mut words = Vec::new();
+  let mut offset = 0;
+  for piece in text.split_inclusive(char::is_whitespace) {
+    let start = offset;
+    offset += piece.len();
+    let piece = piece.trim_end();
+    let leading =
+      piece.len() - piece.trim_start_matches(LEADING_PUNCTUATION).len();
+    let mut trimmed = piece[leading..].trim_end_matches(TRAILING_PUNCTUATION);
+    let punctuated = trimmed.len() < piece.len() - leading;
+    let lowercase = trimmed.to_lowercase();
+    if let Some(suffix) = POSSESSIVE_SUFFIXES
+      .iter()
+      .find(|suffix| lowercase.ends_with(*suffix))
+    {
+      trimmed = &trimmed[..trimmed.len() - suffix.len()];
+    }
+    if trimmed.is_empty() {
+      continue;
+    }
+    words.push(Word {
+      start: start + leading,
+      end: start + leading + trimmed.len(),
+      punctuated,
+    });
+  }
+  return words;
+}
+
+/// Counts the insertions, deletions, substitutions and transpositions
+/// turning one word into another.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let a: Vec<char> = a.chars().collect();
+  let b: Vec<char> = b.chars().collect();
+  let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+  for (i, row) in rows.iter_mut().enumerate() {
+    row[0] = i;
+  }
+  for (j, cell) in rows[0].iter_mut().enumerate() {
+    *cell = j;
+  }
+  for i in 1..=a.len() {
+    for j in 1..=b.len() {
+      let cost = usize::from(a[i - 1] != b[j - 1]);
+      let mut distance = (rows[i - 1][j] + 1)
+        .min(rows[i][j - 1] + 1)
+        .min(rows[i - 1][j - 1] + cost);
+      if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+        distance = distance.min(rows[i - 2][j - 2] + 1);
+      }
+      rows[i][j] = distance;
+    }
+  }
+  return rows[a.len()][b.len()];
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn enforce(text: &str, lines: &[&str], words: Option<&str>) -> String {
+    let entries: Vec<DictionaryEntry> = lines
+      .iter()
+      .map(|line| DictionaryEntry::parse(line))
+      .collect();
+    let spellchecker =
+      words.map(|words| Spellchecker::from_hunspell(words, ""));
+    return apply(
+      text,
+      &entries,
+      &MatchOptions::default(),
+      spellchecker.as_ref(),
+    );
+  }
+
+  #[test]
+  fn corrects_casing_misspellings_and_split_terms() {
+    assert_eq!(
+      enforce(
+        "Run kubernetes, Kubernets and Postgre SQL's backups.",
+        &["Kubernetes!", "PostgreSQL!"],
+        None,
+      ),
+      "Run Kubernetes, Kubernetes and PostgreSQL's backups."
+    );
+  }
+
+  #[test]
+  fn keeps_words_the_spellchecker_knows() {
+    assert_eq!(
+      enforce("Put the dokcer on the docket.", &["Docker!"], None),
+      "Put the Docker on the Docker."
+    );
+    assert_eq!(
+      enforce(
+        "Put the dokcer on the docket, Mr. Decker.",
+        &["Docker!"],
+        Some("2\ndocket\nDecker\n"),
+      ),
+      "Put the Docker on the docket, Mr. Decker."
+    );
+  }
+
+  #[test]
+  fn keeps_inflected_forms_of_terms() {
+    assert_eq!(
+      enforce(
+        "It terraforms what terraform planned and Terraformed.",
+        &["Terraform!"],
+        None,
+      ),
+      "It terraforms what Terraform planned and Terraformed."
+    );
+  }
+
+  #[test]
+  fn keeps_short_terms_and_other_terms() {
+    assert_eq!(
+      enforce(
+        "The rusty crate of Grafana and Grafane.",
+        &["Rust!", "Grafana!", "Grafane"],
+        None,
+      ),
+      "The rusty crate of Grafana and Grafane."
+    );
+  }
+}
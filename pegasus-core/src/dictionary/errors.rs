@@ -0,0 +1,53 @@
+use thiserror::Error;
+
+use crate::app::errors::ErrorKind;
+use crate::t;
+
+/// Dictionary errors.
+///
+/// Represents errors that occur while reading, importing or writing the
+/// custom dictionary.
+#[derive(Error, Debug)]
+pub enum DictionaryError {
+  #[error("{}", t!("error-dictionary-not-configured"))]
+  NotConfigured,
+
+  #[error(
+    "{}",
+    t!("error-dictionary-read", path = .0.as_str(), error = .1.as_str())
+  )]
+  Read(String, String),
+
+  #[error(
+    "{}",
+    t!("error-dictionary-parse", path = .0.as_str(), error = .1.as_str())
+  )]
+  Parse(String, String),
+
+  #[error("{0}")]
+  Write(String),
+
+  #[error("{}", t!("error-dictionary-invalid-column", column = *.0))]
+  InvalidColumn(usize),
+}
+
+impl DictionaryError {
+  /// Gets the category of the error.
+  ///
+  /// # Returns
+  ///
+  /// The `ErrorKind` deciding the exit status.
+  pub fn kind(&self) -> ErrorKind {
+    return match self {
+      DictionaryError::NotConfigured => ErrorKind::Config,
+      DictionaryError::Read(_, _) | DictionaryError::Parse(_, _) => {
+        ErrorKind::Input
+      }
+      DictionaryError::Write(_) => ErrorKind::Other,
+      DictionaryError::InvalidColumn(_) => ErrorKind::Validation,
+    };
+  }
+}
+
+/// Result type for dictionary operations.
+pub type DictionaryResult<T> = Result<T, DictionaryError>;
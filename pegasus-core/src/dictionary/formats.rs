@@ -0,0 +1,411 @@
+//! Import and export formats of the dictionary.
+//!
+//! - `text`: one term per line, like the dictionary file itself, with
+//!   its language sections
+//! - `csv`: one column of a CSV file, as exported by spreadsheets. Fields
+//!   may be quoted, with `""` for a quote inside a quoted field; the
+//!   delimiter is a comma, or a semicolon or tab if the first row has more
+//!   of those. Rows may have different numbers of fields. With a
+//!   header, a `language` column assigns terms to a
+//!   language section. Exported files have a `term` column with a header,
+//!   and a `language` column if the dictionary has sections.
+//! - `json`: an array of strings, or of objects with a `term` field and an
+//!   optional `language` field. Exported files hold objects if the
+//!   dictionary has sections.
+//!
+//! Every term may carry the markers of a dictionary file line, like
+//! `Kubernetes!`, which exports write back. Terms are trimmed, and empty
+//! terms are skipped.
+
+use std::fmt;
+use std::path::Path;
+
+use crate::dictionary::errors::{DictionaryError, DictionaryResult};
+use crate::dictionary::{self, DictionaryEntry, language};
+
+/// Header of the CSV column and name of the JSON field holding the term.
+const TERM_FIELD: &str = "term";
+
+/// Header of the CSV column and name of the JSON field holding the
+/// language of the term.
+const LANGUAGE_FIELD: &str = "language";
+
+/// Format of imported and exported terms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DictionaryFormat {
+  /// One term per line
+  #[default]
+  Text,
+  /// One column of a CSV file
+  Csv,
+  /// An array of strings
+  Json,
+}
+
+impl DictionaryFormat {
+  /// Parses a format from its name.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - `text`, `csv` or `json`
+  ///
+  /// # Returns
+  ///
+  /// The format, or `None` if the name is unknown.
+  pub fn from_name(name: &str) -> Option<Self> {
+    return match name {
+      "text" | "txt" => Some(DictionaryFormat::Text),
+      "csv" => Some(DictionaryFormat::Csv),
+      "json" => Some(DictionaryFormat::Json),
+      _ => None,
+    };
+  }
+
+  /// Guesses the format of a file from its extension.
+  ///
+  /// `.tsv` files are read as CSV; unknown extensions as text.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - Path of the file
+  ///
+  /// # Returns
+  ///
+  /// The format of the file.
+  pub fn from_path(path: &str) -> Self {
+    let extension = Path::new(path)
+      .extension()
+      .map(|extension| extension.to_string_lossy().to_lowercase())
+      .unwrap_or_default();
+    if extension == "tsv" {
+      return DictionaryFormat::Csv;
+    }
+    return DictionaryFormat::from_name(&extension).unwrap_or_default();
+  }
+}
+
+impl fmt::Display for DictionaryFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      DictionaryFormat::Text => "text",
+      DictionaryFormat::Csv => "csv",
+      DictionaryFormat::Json => "json",
+    });
+  }
+}
+
+/// Options for reading terms.
+#[derive(Debug, Clone, Copy)]
+pub struct ImportOptions {
+  /// Format of the content
+  pub format: DictionaryFormat,
+  /// Column of CSV files holding the terms, counted from 1
+  pub column: usize,
+  /// Whether the first row of CSV files is a header
+  pub header: bool,
+}
+
+/// Reads the terms of an imported file.
+///
+/// # Arguments
+///
+/// * `source` - Name of the file, for error messages
+/// * `content` - The content of the file
+/// * `options` - How the content is read
+///
+/// # Returns
+///
+/// A `DictionaryResult<Vec<DictionaryEntry>>` containing the entries in
+/// file order, or an error if the content cannot be parsed.
+pub fn import(
+  source: &str,
+  content: &str,
+  options: ImportOptions,
+) -> DictionaryResult<Vec<DictionaryEntry>> {
+  let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+  let terms: Vec<(String, Option<String>)> = match options.format {
+    DictionaryFormat::Text => {
+      return Ok(dictionary::parse(content, false));
+    }
+    DictionaryFormat::Csv => {
+      if options.column == 0 {
+        return Err(DictionaryError::InvalidColumn(options.column));
+      }
+      let rows = parse_csv(source, content)?;
+      if !rows.is_empty() && rows.iter().all(|row| row.len() < options.column) {
+        return Err(DictionaryError::InvalidColumn(options.column));
+      }
+      let language_column =
+        rows.first().filter(|_| options.header).and_then(|header| {
+          return header.iter().position(|field| {
+            return field.trim().eq_ignore_ascii_case(LANGUAGE_FIELD);
+          });
+        });
+      rows
+        .into_iter()
+        .skip(usize::from(options.header))
+        .filter_map(|row| {
+          let term = row.get(options.column - 1)?.clone();
+          let language =
+            language_column.and_then(|column| row.get(column)).cloned();
+          return Some((term, language));
+        })
+        .collect()
+    }
+    DictionaryFormat::Json => parse_json(source, content)?,
+  };
+  return Ok(
+    terms
+      .iter()
+      .map(|(term, section)| DictionaryEntry {
+        language: section
+          .as_deref()
+          .map(language::normalize)
+          .filter(|code| !code.is_empty()),
+        ..DictionaryEntry::parse(term)
+      })
+      .filter(|entry| !entry.term.is_empty())
+      .collect(),
+  );
+}
+
+/// Writes entries in a format.
+///
+/// # Arguments
+///
+/// * `entries` - The dictionary entries
+/// * `format` - The format to write
+///
+/// # Returns
+///
+/// A `DictionaryResult<String>` containing the entries in the format,
+/// ending with a newline. Text exports list the terms used for every
+/// language first, then each language section.
+pub fn export(
+  entries: &[DictionaryEntry],
+  format: DictionaryFormat,
+) -> DictionaryResult<String> {
+  let sectioned = entries.iter().any(|entry| entry.language.is_some());
+  return Ok(match format {
+    DictionaryFormat::Text => {
+      let mut sections: Vec<Option<&str>> = Vec::new();
+      for entry in entries {
+        if !sections.contains(&entry.language.as_deref()) {
+          sections.push(entry.language.as_deref());
+        }
+      }
+      sections.sort_by_key(|section| section.is_some());
+      let mut text = String::new();
+      for section in sections {
+        if section.is_some() {
+          text.push_str(&format!("{}\n", language::section_header(section)));
+        }
+        for entry in entries {
+          if entry.language.as_deref() == section {
+            text.push_str(&format!("{}\n", entry));
+          }
+        }
+      }
+      text
+    }
+    DictionaryFormat::Csv => write_csv(entries, sectioned)?,
+    DictionaryFormat::Json => {
+      let values: Vec<serde_json::Value> = entries
+        .iter()
+        .map(|entry| {
+          if !sectioned {
+            return serde_json::Value::from(entry.to_string());
+          }
+          return serde_json::json!({
+            TERM_FIELD: entry.to_string(),
+            LANGUAGE_FIELD: entry.language,
+          });
+        })
+        .collect();
+      let json = serde_json::to_string_pretty(&values).unwrap_or_default();
+      format!("{}\n", json)
+    }
+  });
+}
+
+/// Reads the strings of a JSON array, or the `term` and `language` fields
+/// of its objects.
+fn parse_json(
+  source: &str,
+  content: &str,
+) -> DictionaryResult<Vec<(String, Option<String>)>> {
+  let invalid = |reason: String| {
+    return DictionaryError::Parse(source.to_string(), reason);
+  };
+  let values: Vec<serde_json::Value> =
+    serde_json::from_str(content).map_err(|e| invalid(e.to_string()))?;
+  return values
+    .into_iter()
+    .map(|value| {
+      let (term, language) = match &value {
+        serde_json::Value::Object(object) => (
+          object.get(TERM_FIELD),
+          object
+            .get(LANGUAGE_FIELD)
+            .and_then(|language| language.as_str()),
+        ),
+        _ => (Some(&value), None),
+      };
+      let term = term.and_then(|term| term.as_str()).ok_or_else(|| {
+        return invalid(format!(
+          "expected a string or an object with a '{}' string, found {}",
+          TERM_FIELD, value
+        ));
+      })?;
+      return Ok((term.to_string(), language.map(String::from)));
+    })
+    .collect();
+}
+
+/// Splits CSV content into rows of fields, skipping blank rows.
+///
+/// Rows may have different numbers of fields.
+fn parse_csv(
+  source: &str,
+  content: &str,
+) -> DictionaryResult<Vec<Vec<String>>> {
+  let mut reader = csv::ReaderBuilder::new()
+    .has_headers(false)
+    .flexible(true)
+    .delimiter(detect_delimiter(content))
+    .from_reader(content.as_bytes());
+  let mut rows = Vec::new();
+  for record in reader.records() {
+    let record = record.map_err(|e| {
+      return DictionaryError::Parse(source.to_string(), e.to_string());
+    })?;
+    if record.iter().any(|field| !field.trim().is_empty()) {
+      rows.push(record.iter().map(String::from).collect());
+    }
+  }
+  return Ok(rows);
+}
+
+/// Picks the delimiter occurring most often in the first row.
+fn detect_delimiter(content: &str) -> u8 {
+  let first_row = content.lines().next().unwrap_or_default();
+  return [b',', b';', b'\t']
+    .into_iter()
+    .rev()
+    .max_by_key(|delimiter| first_row.matches(char::from(*delimiter)).count())
+    .unwrap_or(b',');
+}
+
+/// Writes entries as CSV with a header, and a language column if the
+/// dictionary has sections.
+fn write_csv(
+  entries: &[DictionaryEntry],
+  sectioned: bool,
+) -> DictionaryResult<String> {
+  let write_error = |e: csv::Error| DictionaryError::Write(e.to_string());
+  let mut writer = csv::Writer::from_writer(Vec::new());
+  if sectioned {
+    writer
+      .write_record([TERM_FIELD, LANGUAGE_FIELD])
+      .map_err(write_error)?;
+  } else {
+    writer.write_record([TERM_FIELD]).map_err(write_error)?;
+  }
+  for entry in entries {
+    let term = entry.to_string();
+    if sectioned {
+      let language = entry.language.as_deref().unwrap_or_default();
+      writer
+        .write_record([term.as_str(), language])
+        .map_err(write_error)?;
+    } else {
+      writer.write_record([term.as_str()]).map_err(write_error)?;
+    }
+  }
+  let csv = writer
+    .into_inner()
+    .map_err(|e| DictionaryError::Write(e.to_string()))?;
+  return Ok(String::from_utf8_lossy(&csv).to_string());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn options(format: DictionaryFormat) -> ImportOptions {
+    return ImportOptions {
+      format,
+      column: 1,
+      header: true,
+    };
+  }
+
+  fn entries() -> Vec<DictionaryEntry> {
+    return vec![
+      DictionaryEntry::parse("Kubernetes!"),
+      DictionaryEntry::parse("PostgreSQL ^3"),
+      DictionaryEntry::new("Smith, \"Doc\" Jr."),
+      DictionaryEntry {
+        language: Some(String::from("de")),
+        ..DictionaryEntry::new("Grüß Gott")
+      },
+    ];
+  }
+
+  #[test]
+  fn round_trips_every_format() {
+    for format in [
+      DictionaryFormat::Text,
+      DictionaryFormat::Csv,
+      DictionaryFormat::Json,
+    ] {
+      let exported = export(&entries(), format).unwrap();
+      let imported = import("terms", &exported, options(format)).unwrap();
+      assert_eq!(imported, entries(), "{}", format);
+    }
+  }
+
+  #[test]
+  fn exports_csv_with_quoted_fields() {
+    let csv = export(&entries(), DictionaryFormat::Csv).unwrap();
+    assert_eq!(
+      csv,
+      "term,language\nKubernetes!,\nPostgreSQL ^3,\n\
+       \"Smith, \"\"Doc\"\" Jr.\",\nGrüß Gott,de\n"
+    );
+  }
+
+  #[test]
+  fn imports_csv_with_a_sniffed_delimiter_and_ragged_rows() {
+    let content = "\u{feff}name;language;notes\n\
+                   Kubernetes;;\n\
+                   \"Grüß; Gott\";de\n\
+                   \n\
+                   Zürich\n";
+    let imported =
+      import("terms.csv", content, options(DictionaryFormat::Csv)).unwrap();
+    assert_eq!(
+      imported,
+      [
+        DictionaryEntry::new("Kubernetes"),
+        DictionaryEntry {
+          language: Some(String::from("de")),
+          ..DictionaryEntry::new("Grüß; Gott")
+        },
+        DictionaryEntry::new("Zürich"),
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_missing_csv_columns() {
+    let options = ImportOptions {
+      column: 3,
+      ..options(DictionaryFormat::Csv)
+    };
+    assert!(matches!(
+      import("terms.csv", "term,language\nKubernetes,\n", options),
+      Err(DictionaryError::InvalidColumn(3))
+    ));
+  }
+}
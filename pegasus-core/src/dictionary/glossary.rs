@@ -0,0 +1,178 @@
+//! Abbreviations of glossary entries.
+//!
+//! A dictionary line `K8s = Kubernetes` pairs an abbreviation with the term
+//! it stands for. How abbreviations are written is set with `[dictionary]
+//! glossary`:
+//! - `keep`: the prompt tells the model what the abbreviations stand for,
+//!   and both forms are left as spoken
+//! - `expand`: abbreviations are written out, which suits minutes read by
+//!   people outside the team
+//! - `standardize`: the first mention, in either form, is written as
+//!   `Kubernetes (K8s)` and later mentions as the abbreviation, as style
+//!   guides ask for
+//!
+//! Besides the prompt asking for it, [`Glossary`] brings the refined text in
+//! line afterwards, so mentions the model missed are fixed too. Across the
+//! chunks of a long text it remembers which abbreviations were introduced.
+//! Abbreviations match in the capitalization of the glossary only, since
+//! many are ordinary words in lowercase (`IT`); terms match in any.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::dictionary::DictionaryEntry;
+use crate::vlog;
+
+/// How abbreviations of glossary entries are written.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum GlossaryMode {
+  /// Left as spoken
+  #[default]
+  Keep,
+  /// Written out as the term
+  Expand,
+  /// Introduced as `Term (ABBR)`, then written as the abbreviation
+  Standardize,
+}
+
+impl fmt::Display for GlossaryMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      GlossaryMode::Keep => "keep",
+      GlossaryMode::Expand => "expand",
+      GlossaryMode::Standardize => "standardize",
+    });
+  }
+}
+
+/// A glossary entry with the pattern matching its mentions.
+struct GlossaryTerm {
+  abbreviation: String,
+  term: String,
+  /// Matches the term, optionally followed by the abbreviation in
+  /// parentheses, or the abbreviation alone
+  mentions: Regex,
+}
+
+/// Brings the abbreviations of refined text in line with the glossary.
+pub struct Glossary {
+  mode: GlossaryMode,
+  terms: Vec<GlossaryTerm>,
+  /// Abbreviations already introduced as `Term (ABBR)`
+  introduced: HashSet<String>,
+}
+
+impl Glossary {
+  /// Creates a glossary from the glossary entries of a dictionary.
+  ///
+  /// # Arguments
+  ///
+  /// * `entries` - The dictionary entries
+  /// * `mode` - How abbreviations are written
+  ///
+  /// # Returns
+  ///
+  /// A new `Glossary` with no abbreviation introduced yet.
+  pub fn new(entries: &[DictionaryEntry], mode: GlossaryMode) -> Self {
+    let mut terms: Vec<GlossaryTerm> = entries
+      .iter()
+      .filter_map(|entry| {
+        let abbreviation = entry.abbreviation.clone()?;
+        let pattern = format!(
+          "{}(?:\\s*\\(\\s*{}\\s*\\))?|{}",
+          bounded(&format!("(?i:{})", literal(&entry.term)), &entry.term),
+          literal(&abbreviation),
+          bounded(&literal(&abbreviation), &abbreviation)
+        );
+        return Some(GlossaryTerm {
+          mentions: Regex::new(&pattern).ok()?,
+          abbreviation,
+          term: entry.term.clone(),
+        });
+      })
+      .collect();
+    // Longer terms first, so `Google Cloud Platform` wins over `Google`.
+    terms.sort_by_key(|term| std::cmp::Reverse(term.term.len()));
+    return Glossary {
+      mode,
+      terms,
+      introduced: HashSet::new(),
+    };
+  }
+
+  /// Rewrites the mentions of glossary entries in refined text.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The refined text, or the next chunk of it
+  ///
+  /// # Returns
+  ///
+  /// The text with abbreviations written as the mode asks for, or
+  /// unchanged in `keep` mode.
+  pub fn apply(&mut self, text: &str) -> String {
+    if self.mode == GlossaryMode::Keep || self.terms.is_empty() {
+      return text.to_string();
+    }
+
+    let mut text = text.to_string();
+    for term in &self.terms {
+      let mut result = String::with_capacity(text.len());
+      let mut position = 0;
+      for mention in term.mentions.find_iter(&text) {
+        let replacement = match self.mode {
+          GlossaryMode::Standardize
+            if self.introduced.insert(term.abbreviation.clone()) =>
+          {
+            format!("{} ({})", term.term, term.abbreviation)
+          }
+          GlossaryMode::Standardize => term.abbreviation.clone(),
+          _ => term.term.clone(),
+        };
+        if mention.as_str() != replacement {
+          vlog!(
+            "Rewrote '{}' as '{}' per the glossary",
+            mention.as_str(),
+            replacement
+          );
+        }
+        result.push_str(&text[position..mention.start()]);
+        result.push_str(&replacement);
+        position = mention.end();
+      }
+      result.push_str(&text[position..]);
+      text = result;
+    }
+    return text;
+  }
+}
+
+/// Escapes text for a pattern, letting its spaces match any whitespace.
+fn literal(text: &str) -> String {
+  return text
+    .split_whitespace()
+    .map(regex::escape)
+    .collect::<Vec<_>>()
+    .join(r"\s+");
+}
+
+/// Adds word boundaries to a pattern where the text it matches starts or
+/// ends with a word character.
+fn bounded(pattern: &str, text: &str) -> String {
+  let is_word = |c: char| c.is_alphanumeric() || c == '_';
+  let start = if text.starts_with(is_word) { r"\b" } else { "" };
+  let end = if text.ends_with(is_word) { r"\b" } else { "" };
+  return format!("{}{}{}", start, pattern, end);
+}
@@ -0,0 +1,112 @@
+//! Language sections of the dictionary.
+//!
+//! A line like `[de]` starts a section whose terms are only used for
+//! transcripts in that language; `[*]` returns to terms used for every
+//! language, like those before the first section. Sections may be named
+//! with an ISO 639-1 code, a tag like `en-US`, or an English language name
+//! like `german`, which is what some services report as the detected
+//! language.
+
+/// Name of the section of terms used for every language.
+pub const SHARED_SECTION: &str = "*";
+
+/// English names of languages, with their ISO 639-1 codes.
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+  ("arabic", "ar"),
+  ("catalan", "ca"),
+  ("chinese", "zh"),
+  ("czech", "cs"),
+  ("danish", "da"),
+  ("dutch", "nl"),
+  ("english", "en"),
+  ("finnish", "fi"),
+  ("french", "fr"),
+  ("german", "de"),
+  ("greek", "el"),
+  ("hebrew", "he"),
+  ("hindi", "hi"),
+  ("hungarian", "hu"),
+  ("indonesian", "id"),
+  ("italian", "it"),
+  ("japanese", "ja"),
+  ("korean", "ko"),
+  ("norwegian", "no"),
+  ("persian", "fa"),
+  ("polish", "pl"),
+  ("portuguese", "pt"),
+  ("romanian", "ro"),
+  ("russian", "ru"),
+  ("spanish", "es"),
+  ("swedish", "sv"),
+  ("thai", "th"),
+  ("turkish", "tr"),
+  ("ukrainian", "uk"),
+  ("vietnamese", "vi"),
+];
+
+/// Normalizes a language to its ISO 639-1 code.
+///
+/// # Arguments
+///
+/// * `language` - A code (`de`), a tag (`de-AT`) or an English name
+///   (`German`)
+///
+/// # Returns
+///
+/// The lowercase code, or the lowercase name if it is not known.
+pub fn normalize(language: &str) -> String {
+  let language = language.trim().to_lowercase();
+  let primary = language
+    .split(['-', '_'])
+    .next()
+    .unwrap_or_default()
+    .to_string();
+  return LANGUAGE_NAMES
+    .iter()
+    .find(|(name, _)| *name == primary)
+    .map(|(_, code)| code.to_string())
+    .unwrap_or(primary);
+}
+
+/// Parses a section header line.
+///
+/// # Arguments
+///
+/// * `line` - A trimmed line of a dictionary file
+///
+/// # Returns
+///
+/// `Some(None)` for the shared section `[*]`, `Some(Some(code))` for a
+/// language section, or `None` if the line is not a section header. Names
+/// that are no language, like `[laughter]`, are terms.
+pub fn parse_section(line: &str) -> Option<Option<String>> {
+  let name = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+  if name == SHARED_SECTION {
+    return Some(None);
+  }
+  if !name
+    .chars()
+    .all(|c| c.is_ascii_alphabetic() || c == '-' || c == '_')
+  {
+    return None;
+  }
+  let code = normalize(name);
+  if !(2..=3).contains(&code.len()) {
+    return None;
+  }
+  return Some(Some(code));
+}
+
+/// Writes the header of a section.
+///
+/// # Arguments
+///
+/// * `language` - The language of the section, or `None` for the shared
+///   section
+///
+/// # Returns
+///
+/// The header line, like `[de]` or `[*]`.
+pub fn section_header(language: Option<&str>) -> String {
+  return format!("[{}]", language.unwrap_or(SHARED_SECTION));
+}
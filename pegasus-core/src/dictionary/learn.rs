@@ -0,0 +1,304 @@
+//! Learning dictionary terms from review corrections.
+//!
+//! When reviewed text is written in `pegasus tui`, every segment the user
+//! edited or rejected is compared word by word with the text they turned
+//! down: the suggestion for an edit or a rejection, or the original text
+//! for an edit without a suggestion. Words replaced there are appended to
+//! `$XDG_STATE_HOME/pegasus/corrections.jsonl`, unless `[dictionary]
+//! record_corrections` is false.
+//!
+//! `pegasus dictionary learn` counts how often each word was written by the
+//! user and proposes those corrected at least `--min-count` times that are
+//! not in the dictionary yet; `--add` appends them. A word written in place
+//! of several (`PostgreSQL` for `Postgre SQL`) counts as a correction too.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+
+use xdg::BaseDirectories;
+
+use crate::clock;
+use crate::dictionary::DictionaryEntry;
+use crate::dictionary::errors::{DictionaryError, DictionaryResult};
+use crate::files::operations;
+use crate::llm::alignment::longest_common_subsequence;
+use crate::{elog, logging, vlog};
+
+const STATE_DIRECTORY: &str = "pegasus";
+const CORRECTIONS_FILE: &str = "corrections.jsonl";
+
+/// Most words a replaced phrase may have to count as a correction.
+const MAX_PHRASE_WORDS: usize = 3;
+
+/// Largest alignment table, in cells, before a segment is skipped.
+const MAX_ALIGNMENT_CELLS: usize = 1_000_000;
+
+/// A word the user wrote in place of another.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Correction {
+  /// When the correction was recorded, as a Unix timestamp
+  pub timestamp: i64,
+  /// The word or phrase that was replaced
+  pub from: String,
+  /// The word the user wrote instead
+  pub to: String,
+}
+
+/// A term proposed for the dictionary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proposal {
+  /// The term as the user wrote it
+  pub term: String,
+  /// How often the user wrote it in place of another word
+  pub count: usize,
+  /// The words it replaced
+  pub variants: Vec<String>,
+}
+
+/// Finds the words the user replaced in a text.
+///
+/// Words are compared without surrounding punctuation, so only changed
+/// spelling or capitalization counts. Changes of capitalization alone are
+/// skipped where the word became lowercase or starts a sentence, since
+/// they are about grammar rather than vocabulary. Where a run of words was
+/// replaced with a different number of words, only a single word replacing
+/// up to three words is kept, for the same reason.
+///
+/// # Arguments
+///
+/// * `before` - The text the user turned down
+/// * `after` - The text the user kept
+///
+/// # Returns
+///
+/// The corrections, in text order.
+pub fn find_corrections(before: &str, after: &str) -> Vec<Correction> {
+  let before = split_words(before);
+  let after = split_words(after);
+  let sentence_starts = sentence_starts(&after);
+  let after: Vec<&str> = after.iter().map(|(word, _)| *word).collect();
+  let before: Vec<&str> = before.iter().map(|(word, _)| *word).collect();
+  if before.len().saturating_mul(after.len()) > MAX_ALIGNMENT_CELLS {
+    vlog!("Skipping corrections of a segment too long to align");
+    return Vec::new();
+  }
+
+  let timestamp = clock::timestamp();
+  let correction = |from: &[&str], to: &str| {
+    return Correction {
+      timestamp,
+      from: from.join(" "),
+      to: to.to_string(),
+    };
+  };
+  let mut corrections = Vec::new();
+  let mut previous = (0, 0);
+  let boundaries = longest_common_subsequence(&before, &after)
+    .into_iter()
+    .map(|(i, j)| (i, j, 1))
+    .chain(std::iter::once((before.len(), after.len(), 0)));
+  for (i, j, step) in boundaries {
+    let before_gap = &before[previous.0..i];
+    let after_gap = &after[previous.1..j];
+    if before_gap.len() == after_gap.len() {
+      for (offset, (from, to)) in before_gap.iter().zip(after_gap).enumerate() {
+        if !is_grammatical(from, to, sentence_starts[previous.1 + offset]) {
+          corrections.push(correction(&[from], to));
+        }
+      }
+    } else if let [to] = after_gap
+      && before_gap.len() <= MAX_PHRASE_WORDS
+      && !before_gap.is_empty()
+    {
+      corrections.push(correction(before_gap, to));
+    }
+    previous = (i + step, j + step);
+  }
+  return corrections;
+}
+
+/// Gets the path of the corrections file, creating its directory.
+///
+/// # Returns
+///
+/// A `DictionaryResult<PathBuf>` containing the path, or an error if the
+/// state directory cannot be created.
+pub fn path() -> DictionaryResult<PathBuf> {
+  return BaseDirectories::with_prefix(STATE_DIRECTORY)
+    .place_state_file(CORRECTIONS_FILE)
+    .map_err(|e| DictionaryError::Write(e.to_string()));
+}
+
+/// Appends corrections to the corrections file.
+///
+/// # Arguments
+///
+/// * `corrections` - The corrections to record
+///
+/// # Returns
+///
+/// A `DictionaryResult<()>` indicating success, or an error if the file
+/// cannot be written.
+pub async fn record(corrections: &[Correction]) -> DictionaryResult<()> {
+  if corrections.is_empty() {
+    return Ok(());
+  }
+  let path = path()?;
+  let mut lines = String::new();
+  for correction in corrections {
+    let line = serde_json::to_string(correction)
+      .map_err(|e| DictionaryError::Write(e.to_string()))?;
+    lines.push_str(&line);
+    lines.push('\n');
+  }
+  vlog!(
+    "Recording {} corrections in {}",
+    corrections.len(),
+    path.display()
+  );
+  return operations::append_string(&path.to_string_lossy(), &lines)
+    .await
+    .map_err(|e| DictionaryError::Write(e.to_string()));
+}
+
+/// Reads the recorded corrections.
+///
+/// Lines that cannot be parsed are skipped with a warning.
+///
+/// # Returns
+///
+/// A `DictionaryResult<Vec<Correction>>` containing the corrections in the
+/// order they were recorded, or an error if the file cannot be read.
+pub async fn load() -> DictionaryResult<Vec<Correction>> {
+  let path = path()?;
+  let content = match tokio::fs::read_to_string(&path).await {
+    Ok(content) => content,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+    Err(e) => {
+      return Err(DictionaryError::Read(
+        path.display().to_string(),
+        e.to_string(),
+      ));
+    }
+  };
+
+  let mut corrections = Vec::new();
+  for (number, line) in content.lines().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    match serde_json::from_str::<Correction>(line) {
+      Ok(correction) => corrections.push(correction),
+      Err(e) => elog!(
+        logging::WARNING,
+        "Skipping line {} of {}: {}",
+        number + 1,
+        path.display(),
+        e
+      ),
+    }
+  }
+  vlog!(
+    "Read {} corrections from {}",
+    corrections.len(),
+    path.display()
+  );
+  return Ok(corrections);
+}
+
+/// Proposes the words the user corrected repeatedly as dictionary terms.
+///
+/// Single characters and numbers are never proposed, nor words already in
+/// the dictionary, as terms or abbreviations, in any capitalization.
+///
+/// # Arguments
+///
+/// * `corrections` - The recorded corrections
+/// * `dictionary` - The entries of the dictionary
+/// * `min_count` - How often a word must have been written by the user
+///
+/// # Returns
+///
+/// The proposals, most frequent first.
+pub fn propose(
+  corrections: &[Correction],
+  dictionary: &[DictionaryEntry],
+  min_count: usize,
+) -> Vec<Proposal> {
+  let known: HashSet<String> = dictionary
+    .iter()
+    .flat_map(|entry| std::iter::once(&entry.term).chain(&entry.abbreviation))
+    .map(|term| term.to_lowercase())
+    .collect();
+
+  let mut counts: HashMap<&str, (usize, BTreeSet<&str>)> = HashMap::new();
+  for correction in corrections {
+    let term = correction.to.as_str();
+    if term.chars().count() < 2
+      || !term.chars().any(char::is_alphabetic)
+      || known.contains(&term.to_lowercase())
+    {
+      continue;
+    }
+    let (count, variants) = counts.entry(term).or_default();
+    *count += 1;
+    variants.insert(&correction.from);
+  }
+
+  let mut proposals: Vec<Proposal> = counts
+    .into_iter()
+    .filter(|(_, (count, _))| *count >= min_count.max(1))
+    .map(|(term, (count, variants))| {
+      return Proposal {
+        term: term.to_string(),
+        count,
+        variants: variants.into_iter().map(String::from).collect(),
+      };
+    })
+    .collect();
+  proposals.sort_by(|a, b| {
+    return b.count.cmp(&a.count).then_with(|| a.term.cmp(&b.term));
+  });
+  return proposals;
+}
+
+/// Checks whether a replacement only changes capitalization for grammar:
+/// lowercasing a word, or capitalizing the first word of a sentence.
+fn is_grammatical(from: &str, to: &str, sentence_start: bool) -> bool {
+  if from.to_lowercase() != to.to_lowercase() {
+    return false;
+  }
+  if !to.chars().any(char::is_uppercase) {
+    return true;
+  }
+  let mut rest = to.chars().skip(1);
+  return sentence_start && !rest.any(char::is_uppercase);
+}
+
+/// Splits text into words without surrounding punctuation, each with the
+/// punctuation that followed it.
+fn split_words(text: &str) -> Vec<(&str, &str)> {
+  return text
+    .split_whitespace()
+    .filter_map(|piece| {
+      let word = piece.trim_matches(|c: char| !c.is_alphanumeric());
+      if word.is_empty() {
+        return None;
+      }
+      let offset = word.as_ptr() as usize - piece.as_ptr() as usize;
+      return Some((word, &piece[offset + word.len()..]));
+    })
+    .collect();
+}
+
+/// Finds the words starting a sentence: the first word and words after
+/// sentence-ending punctuation.
+fn sentence_starts(words: &[(&str, &str)]) -> Vec<bool> {
+  let mut starts = Vec::with_capacity(words.len());
+  let mut start = true;
+  for (_, punctuation) in words {
+    starts.push(start);
+    start = punctuation.contains(['.', '!', '?']);
+  }
+  return starts;
+}
@@ -0,0 +1,471 @@
+//! Custom dictionary of domain terms.
+//!
+//! The dictionary configured with `[dictionary] path` is a text file with
+//! one term per line; blank lines and lines starting with `#` are ignored.
+//! Its terms are listed in the prompt so the LLM spells them correctly.
+//!
+//! A term may carry markers after it:
+//! - `!` (`Kubernetes!`): always enforce the term. The prompt asks for it
+//!   to be written exactly as spelled, and after refinement near matches
+//!   are corrected to it (see [`enforce`]). Write `\!` for a term that
+//!   ends with an exclamation mark.
+//! - `^<weight>` (`PostgreSQL ^3`): how important the term is. Terms are
+//!   listed in the prompt by descending weight, which defaults to 1.
+//!
+//! A glossary entry `K8s = Kubernetes` pairs an abbreviation with the term
+//! it stands for. `[dictionary] glossary` decides whether abbreviations are
+//! kept, written out or introduced once and used afterwards (see
+//! [`glossary`]).
+//!
+//! A shared dictionary can hold terms of several languages in sections
+//! like `[en]` and `[de]` (see [`language`]). Terms before the first
+//! section are used for every language; the terms of a section only for
+//! transcripts detected or declared (`[transcription] language`) to be in
+//! its language, and for transcripts of unknown language.
+//!
+//! How terms match is configured per dictionary (see [`MatchOptions`]):
+//! case-insensitively or not, as whole words or also inside longer words,
+//! and whether `/pattern/ Term` lines are regex entries. A regex entry is
+//! always enforced: every match of the pattern is replaced with the term,
+//! which is listed in the prompt like the other enforced terms.
+//!
+//! Glossaries maintained elsewhere, like a spreadsheet, are brought in with
+//! `pegasus dictionary import`, which reads one column of a CSV file or the
+//! strings of a JSON array (see [`formats`]) and appends the terms that are
+//! not in the dictionary yet. `pegasus dictionary export` writes the terms
+//! back out as text, CSV or JSON.
+//!
+//! Words the user repeatedly corrected while reviewing in `pegasus tui` are
+//! proposed as terms by `pegasus dictionary learn` (see [`learn`]).
+//!
+//! With a Hunspell dictionary set as `[dictionary] spellcheck`, refined
+//! words that are neither spelled correctly, nor terms, nor in the input
+//! are reported as possible hallucinations (see [`spellcheck`]).
+//!
+//! ## Main Components
+//!
+//! - [`DictionaryEntry`]: A term and its markers
+//! - [`MatchOptions`]: How terms are matched
+//! - [`GlossaryMode`](glossary::GlossaryMode): How abbreviations of
+//!   glossary entries are written
+//! - [`parse`]: Reads the entries of a dictionary file
+//! - [`parse_line`]: Reads the entry of a single line
+//! - [`select`]: Picks the entries for a transcript language
+//! - [`load`]: Loads a dictionary file
+//! - [`add_terms`]: Appends new terms to a dictionary file
+//! - [`DictionaryFormat`](formats::DictionaryFormat): Import and export
+//!   formats
+//! - [`Correction`](learn::Correction): A word corrected during review
+//! - [`Spellchecker`](spellcheck::Spellchecker): Flags refined words no
+//!   dictionary knows
+//! - [`DictionaryError`](errors::DictionaryError): Error types for the
+//!   dictionary
+
+pub mod enforce;
+pub mod errors;
+pub mod formats;
+pub mod glossary;
+pub mod language;
+pub mod learn;
+pub mod spellcheck;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::dictionary::errors::{DictionaryError, DictionaryResult};
+use crate::files::operations;
+use crate::vlog;
+
+/// Weight of terms without a `^<weight>` marker.
+pub const DEFAULT_WEIGHT: u32 = 1;
+
+/// Marker of enforced terms.
+const ENFORCE_MARKER: char = '!';
+
+/// Separator of the abbreviation and term of glossary entries.
+const GLOSSARY_SEPARATOR: char = '=';
+
+/// Prefix of the weight marker.
+const WEIGHT_MARKER: &str = "^";
+
+/// Delimiter of the pattern of regex entries.
+const PATTERN_DELIMITER: char = '/';
+
+/// How dictionary terms are matched, configured in `[dictionary]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchOptions {
+  /// Whether words only match terms in the same capitalization
+  pub case_sensitive: bool,
+  /// Whether terms only match whole words
+  pub whole_word: bool,
+  /// Whether `/pattern/ Term` lines are regex entries
+  pub regex: bool,
+}
+
+impl Default for MatchOptions {
+  fn default() -> Self {
+    return MatchOptions {
+      case_sensitive: false,
+      whole_word: true,
+      regex: false,
+    };
+  }
+}
+
+/// A dictionary term and its markers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictionaryEntry {
+  /// The term as it should be written
+  pub term: String,
+  /// How important the term is; higher weights are listed first
+  pub weight: u32,
+  /// Whether near matches are corrected to the term after refinement
+  pub enforced: bool,
+  /// Pattern of a regex entry, whose matches are replaced with the term
+  pub pattern: Option<String>,
+  /// Abbreviation of a glossary entry, standing for the term
+  pub abbreviation: Option<String>,
+  /// Language of the section the entry is in, or `None` for every language
+  pub language: Option<String>,
+}
+
+impl DictionaryEntry {
+  /// Creates an entry for a term without markers.
+  ///
+  /// # Arguments
+  ///
+  /// * `term` - The term as it should be written
+  ///
+  /// # Returns
+  ///
+  /// A new `DictionaryEntry` with the default weight.
+  pub fn new(term: impl Into<String>) -> Self {
+    return DictionaryEntry {
+      term: term.into(),
+      weight: DEFAULT_WEIGHT,
+      enforced: false,
+      pattern: None,
+      abbreviation: None,
+      language: None,
+    };
+  }
+
+  /// Parses a line of a dictionary file.
+  ///
+  /// # Arguments
+  ///
+  /// * `line` - The line, like `Kubernetes!`, `PostgreSQL ^3` or
+  ///   `K8s = Kubernetes`
+  ///
+  /// # Returns
+  ///
+  /// The entry, with the markers removed from the term.
+  pub fn parse(line: &str) -> Self {
+    let mut rest = line.trim();
+    let mut weight = DEFAULT_WEIGHT;
+    if let Some((term, marker)) = rest.rsplit_once(WEIGHT_MARKER)
+      && let Ok(value) = marker.trim().parse::<u32>()
+      && !term.trim().is_empty()
+    {
+      weight = value;
+      rest = term.trim_end();
+    }
+
+    let mut abbreviation = None;
+    if let Some((short, term)) = rest.split_once(GLOSSARY_SEPARATOR)
+      && !short.trim().is_empty()
+      && !term.trim().is_empty()
+    {
+      abbreviation = Some(short.trim().to_string());
+      rest = term.trim_start();
+    }
+
+    let (term, enforced) = if let Some(term) = rest.strip_suffix("\\!") {
+      (format!("{}{}", term, ENFORCE_MARKER), false)
+    } else if rest.len() > 1 && rest.ends_with(ENFORCE_MARKER) {
+      (rest[..rest.len() - 1].trim_end().to_string(), true)
+    } else {
+      (rest.to_string(), false)
+    };
+    return DictionaryEntry {
+      term,
+      weight,
+      enforced,
+      pattern: None,
+      abbreviation,
+      language: None,
+    };
+  }
+
+  /// Parses a regex entry line of a dictionary file.
+  ///
+  /// # Arguments
+  ///
+  /// * `line` - The line, like `/kube(rnetes)?|k8s/ Kubernetes ^2`
+  ///
+  /// # Returns
+  ///
+  /// The enforced entry with its pattern, or `None` if the line is not a
+  /// regex entry.
+  pub fn parse_regex(line: &str) -> Option<Self> {
+    let rest = line.trim().strip_prefix(PATTERN_DELIMITER)?;
+    let end = rest
+      .rmatch_indices(PATTERN_DELIMITER)
+      .map(|(index, _)| index)
+      .find(|index| {
+        return rest[index + 1..].starts_with(char::is_whitespace);
+      })?;
+    let pattern = &rest[..end];
+    let entry = DictionaryEntry::parse(&rest[end + 1..]);
+    if pattern.is_empty() || entry.term.is_empty() {
+      return None;
+    }
+    return Some(DictionaryEntry {
+      enforced: true,
+      pattern: Some(pattern.to_string()),
+      ..entry
+    });
+  }
+}
+
+impl From<String> for DictionaryEntry {
+  fn from(term: String) -> Self {
+    return DictionaryEntry::new(term);
+  }
+}
+
+impl From<&str> for DictionaryEntry {
+  fn from(term: &str) -> Self {
+    return DictionaryEntry::new(term);
+  }
+}
+
+impl fmt::Display for DictionaryEntry {
+  /// Writes the entry as a line of a dictionary file.
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(pattern) = &self.pattern {
+      let entry = DictionaryEntry {
+        enforced: false,
+        pattern: None,
+        ..self.clone()
+      };
+      return write!(f, "{0}{1}{0} {2}", PATTERN_DELIMITER, pattern, entry);
+    }
+    if let Some(abbreviation) = &self.abbreviation {
+      write!(f, "{} {} ", abbreviation, GLOSSARY_SEPARATOR)?;
+    }
+    match self.term.strip_suffix(ENFORCE_MARKER) {
+      Some(term) if !self.enforced => write!(f, "{}\\!", term)?,
+      _ => f.write_str(&self.term)?,
+    }
+    if self.enforced {
+      write!(f, "{}", ENFORCE_MARKER)?;
+    }
+    if self.weight != DEFAULT_WEIGHT {
+      write!(f, " {}{}", WEIGHT_MARKER, self.weight)?;
+    }
+    return Ok(());
+  }
+}
+
+/// Gets the path of the configured dictionary.
+///
+/// # Arguments
+///
+/// * `config` - Configuration with the `[dictionary]` settings
+///
+/// # Returns
+///
+/// A `DictionaryResult<String>` containing the path, or an error if no
+/// dictionary is configured.
+pub fn configured_path(config: &Config) -> DictionaryResult<String> {
+  let path = config.get_custom_dictionary_path();
+  if path.is_empty() {
+    return Err(DictionaryError::NotConfigured);
+  }
+  return Ok(path);
+}
+
+/// Reads the entries of a dictionary file.
+///
+/// # Arguments
+///
+/// * `content` - The content of the dictionary file
+/// * `regex` - Whether `/pattern/ Term` lines are read as regex entries
+///   instead of literal terms
+///
+/// # Returns
+///
+/// The entries, one per non-empty line that is neither a comment nor a
+/// section header, with the language of their section.
+pub fn parse(content: &str, regex: bool) -> Vec<DictionaryEntry> {
+  let mut section: Option<String> = None;
+  let mut entries = Vec::new();
+  for line in content.lines().map(|line| line.trim()) {
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    if let Some(language) = language::parse_section(line) {
+      section = language;
+      continue;
+    }
+    let entry = parse_line(line, regex);
+    if !entry.term.is_empty() {
+      entries.push(DictionaryEntry {
+        language: section.clone(),
+        ..entry
+      });
+    }
+  }
+  return entries;
+}
+
+/// Picks the entries used for a transcript.
+///
+/// # Arguments
+///
+/// * `entries` - The dictionary entries
+/// * `language` - The language of the transcript, as a code or name, or
+///   `None` if it is unknown
+///
+/// # Returns
+///
+/// The entries used for every language and those of the transcript's
+/// language, or all entries if the language is unknown.
+pub fn select(
+  entries: &[DictionaryEntry],
+  language: Option<&str>,
+) -> Vec<DictionaryEntry> {
+  let Some(language) = language.filter(|language| !language.is_empty()) else {
+    return entries.to_vec();
+  };
+  let code = language::normalize(language);
+  return entries
+    .iter()
+    .filter(|entry| {
+      return entry
+        .language
+        .as_ref()
+        .is_none_or(|section| *section == code);
+    })
+    .cloned()
+    .collect();
+}
+
+/// Reads the entry of a dictionary file line.
+///
+/// # Arguments
+///
+/// * `line` - The line
+/// * `regex` - Whether a `/pattern/ Term` line is read as a regex entry
+///   instead of a literal term
+///
+/// # Returns
+///
+/// The entry of the line.
+pub fn parse_line(line: &str, regex: bool) -> DictionaryEntry {
+  return regex
+    .then(|| DictionaryEntry::parse_regex(line))
+    .flatten()
+    .unwrap_or_else(|| DictionaryEntry::parse(line));
+}
+
+/// Loads a dictionary file.
+///
+/// # Arguments
+///
+/// * `path` - Path of the dictionary file
+/// * `regex` - Whether `/pattern/ Term` lines are read as regex entries
+///
+/// # Returns
+///
+/// A `DictionaryResult<Vec<DictionaryEntry>>` containing the entries, or
+/// an error if the file cannot be read or a pattern is invalid.
+pub async fn load(
+  path: &str,
+  regex: bool,
+) -> DictionaryResult<Vec<DictionaryEntry>> {
+  let content = tokio::fs::read_to_string(path)
+    .await
+    .map_err(|e| DictionaryError::Read(path.to_string(), e.to_string()))?;
+  let entries = parse(&content, regex);
+  for pattern in entries.iter().filter_map(|entry| entry.pattern.as_ref()) {
+    if let Err(e) = regex::Regex::new(pattern) {
+      return Err(DictionaryError::Parse(
+        path.to_string(),
+        format!("invalid pattern /{}/: {}", pattern, e),
+      ));
+    }
+  }
+  return Ok(entries);
+}
+
+/// Appends entries whose terms are not in a dictionary file yet.
+///
+/// The file is created if it does not exist. Existing lines, including
+/// comments, are kept as they are. Entries of another language than the
+/// section the file ends in are written after a header of their section.
+///
+/// # Arguments
+///
+/// * `path` - Path of the dictionary file
+/// * `entries` - The entries to add
+///
+/// # Returns
+///
+/// A `DictionaryResult<usize>` containing how many terms were added, or an
+/// error if the file cannot be read or written.
+pub async fn add_terms(
+  path: &str,
+  entries: &[DictionaryEntry],
+) -> DictionaryResult<usize> {
+  let _lock = operations::lock_file(path)
+    .await
+    .map_err(|e| DictionaryError::Write(e.to_string()))?;
+
+  let mut content = if Path::new(path).is_file() {
+    tokio::fs::read_to_string(path)
+      .await
+      .map_err(|e| DictionaryError::Read(path.to_string(), e.to_string()))?
+  } else {
+    String::new()
+  };
+
+  let mut known: HashSet<(String, Option<String>)> = parse(&content, false)
+    .into_iter()
+    .map(|entry| (entry.term, entry.language))
+    .collect();
+  let added: Vec<&DictionaryEntry> = entries
+    .iter()
+    .filter(|entry| known.insert((entry.term.clone(), entry.language.clone())))
+    .collect();
+  if added.is_empty() {
+    return Ok(0);
+  }
+
+  let mut section = content
+    .lines()
+    .rev()
+    .find_map(|line| language::parse_section(line.trim()))
+    .flatten();
+  if !content.is_empty() && !content.ends_with('\n') {
+    content.push('\n');
+  }
+  for entry in &added {
+    if entry.language != section {
+      section = entry.language.clone();
+      content.push_str(&format!(
+        "{}\n",
+        language::section_header(section.as_deref())
+      ));
+    }
+    content.push_str(&format!("{}\n", entry));
+  }
+  operations::write_string_atomic(path, &content)
+    .await
+    .map_err(|e| DictionaryError::Write(e.to_string()))?;
+  vlog!("Added {} terms to {}", added.len(), path);
+  return Ok(added.len());
+}
@@ -0,0 +1,542 @@
+//! Spellchecking of refined text against a Hunspell dictionary.
+//!
+//! With `[dictionary] spellcheck` set to a Hunspell `.dic` file, with its
+//! `.aff` file next to it, words of the refined text that are neither
+//! spelled correctly, nor dictionary terms, nor in the original input are
+//! flagged as possible hallucinations: the model wrote a word nobody said
+//! that no dictionary knows. They are logged as warnings, or listed under
+//! `possible_hallucinations` in JSON output.
+//!
+//! The word forms of the Hunspell dictionary are generated from its stems
+//! and prefix and suffix rules. Compounds are not formed, so languages
+//! compounding freely, like German, flag more words. Words with digits are
+//! never flagged.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::dictionary::DictionaryEntry;
+use crate::dictionary::errors::{DictionaryError, DictionaryResult};
+use crate::vlog;
+
+/// Extension of the affix file next to a Hunspell dictionary.
+const AFFIX_EXTENSION: &str = "aff";
+
+/// Affix file options marking stems that are no words on their own.
+const BARE_STEM_OPTIONS: [&str; 3] =
+  ["NEEDAFFIX", "ONLYINCOMPOUND", "FORBIDDENWORD"];
+
+/// Possessive suffixes ignored when checking a word.
+const POSSESSIVE_SUFFIXES: [&str; 2] = ["'s", "\u{2019}s"];
+
+/// A word of refined text found neither in a dictionary nor in the input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SuspectWord {
+  /// The word as written in the refined text
+  pub word: String,
+  /// How often it occurs
+  pub count: usize,
+}
+
+/// How the flags of a Hunspell dictionary are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum FlagType {
+  /// One character per flag
+  #[default]
+  Char,
+  /// Two characters per flag
+  Long,
+  /// Numbers separated by commas
+  Numeric,
+}
+
+/// A prefix or suffix rule of an affix file.
+struct AffixRule {
+  /// Characters removed from the stem
+  strip: String,
+  /// Characters added in their place
+  add: String,
+  /// Condition on the stem, or `None` for any stem
+  condition: Option<Regex>,
+}
+
+/// The rules of a prefix or suffix flag.
+struct Affix {
+  suffix: bool,
+  /// Whether the affix combines with affixes on the other side
+  cross_product: bool,
+  rules: Vec<AffixRule>,
+}
+
+impl Affix {
+  /// Applies the rules of the affix to a word.
+  fn apply(&self, word: &str) -> Vec<String> {
+    return self
+      .rules
+      .iter()
+      .filter(|rule| {
+        return rule
+          .condition
+          .as_ref()
+          .is_none_or(|condition| condition.is_match(word));
+      })
+      .filter_map(|rule| {
+        if self.suffix {
+          let stem = word.strip_suffix(rule.strip.as_str())?;
+          return Some(format!("{}{}", stem, rule.add));
+        }
+        let stem = word.strip_prefix(rule.strip.as_str())?;
+        return Some(format!("{}{}", rule.add, stem));
+      })
+      .collect();
+  }
+}
+
+/// Checks the spelling of words against the forms of a Hunspell
+/// dictionary.
+pub struct Spellchecker {
+  words: HashSet<String>,
+}
+
+impl std::fmt::Debug for Spellchecker {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    return f
+      .debug_struct("Spellchecker")
+      .field("words", &self.words.len())
+      .finish();
+  }
+}
+
+impl Spellchecker {
+  /// Loads a Hunspell dictionary and the affix file next to it.
+  ///
+  /// A missing affix file is not an error; only the stems are known then.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - Path of the `.dic` file
+  ///
+  /// # Returns
+  ///
+  /// A `DictionaryResult<Spellchecker>` containing the spellchecker, or an
+  /// error if a file cannot be read.
+  pub async fn load(path: &str) -> DictionaryResult<Self> {
+    let read_error = |path: &Path, e: std::io::Error| {
+      return DictionaryError::Read(path.display().to_string(), e.to_string());
+    };
+    let dictionary = tokio::fs::read(path)
+      .await
+      .map_err(|e| read_error(Path::new(path), e))?;
+    let affix_path = Path::new(path).with_extension(AFFIX_EXTENSION);
+    let affixes = match tokio::fs::read(&affix_path).await {
+      Ok(affixes) => affixes,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        vlog!("No affix file at {}", affix_path.display());
+        Vec::new()
+      }
+      Err(e) => return Err(read_error(&affix_path, e)),
+    };
+
+    let spellchecker =
+      Spellchecker::from_hunspell(&decode(dictionary), &decode(affixes));
+    vlog!(
+      "Loaded {} word forms for spellchecking from {}",
+      spellchecker.words.len(),
+      path
+    );
+    return Ok(spellchecker);
+  }
+
+  /// Creates a spellchecker from the content of Hunspell files.
+  ///
+  /// # Arguments
+  ///
+  /// * `dictionary` - The content of the `.dic` file
+  /// * `affixes` - The content of the `.aff` file
+  ///
+  /// # Returns
+  ///
+  /// A new `Spellchecker` knowing every word form of the dictionary.
+  pub fn from_hunspell(dictionary: &str, affixes: &str) -> Self {
+    let mut flag_type = FlagType::default();
+    let mut affix_rules: HashMap<String, Affix> = HashMap::new();
+    let mut bare_stem_flags: Vec<String> = Vec::new();
+    for line in affixes.lines() {
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      match fields.as_slice() {
+        ["FLAG", kind, ..] => {
+          flag_type = match *kind {
+            "long" => FlagType::Long,
+            "num" => FlagType::Numeric,
+            _ => FlagType::Char,
+          };
+        }
+        [option, flag, ..] if BARE_STEM_OPTIONS.contains(option) => {
+          bare_stem_flags.push(flag.to_string());
+        }
+        [kind @ ("PFX" | "SFX"), flag, cross_product, ..]
+          if !affix_rules.contains_key(*flag) =>
+        {
+          affix_rules.insert(
+            flag.to_string(),
+            Affix {
+              suffix: *kind == "SFX",
+              cross_product: *cross_product == "Y",
+              rules: Vec::new(),
+            },
+          );
+        }
+        ["PFX" | "SFX", flag, strip, add, rest @ ..] => {
+          let Some(affix) = affix_rules.get_mut(*flag) else {
+            continue;
+          };
+          let Some(condition) = parse_condition(rest.first(), affix.suffix)
+          else {
+            continue;
+          };
+          affix.rules.push(AffixRule {
+            strip: empty_if_zero(strip).to_string(),
+            add: empty_if_zero(add.split('/').next().unwrap_or_default())
+              .to_string(),
+            condition,
+          });
+        }
+        _ => {}
+      }
+    }
+
+    let mut words = HashSet::new();
+    let mut lines = dictionary.lines();
+    let first = lines.next().unwrap_or_default();
+    let entries = std::iter::once(first)
+      .filter(|line| line.trim().parse::<usize>().is_err())
+      .chain(lines);
+    for line in entries {
+      let Some(entry) = line.split_whitespace().next() else {
+        continue;
+      };
+      let (stem, flags) = split_entry(entry, flag_type);
+      if !flags.iter().any(|flag| bare_stem_flags.contains(flag)) {
+        words.insert(stem.clone());
+      }
+
+      let affixes: Vec<&Affix> = flags
+        .iter()
+        .filter_map(|flag| affix_rules.get(flag))
+        .collect();
+      let mut suffixed = Vec::new();
+      for affix in affixes.iter().filter(|affix| affix.suffix) {
+        for form in affix.apply(&stem) {
+          if affix.cross_product {
+            suffixed.push(form.clone());
+          }
+          words.insert(form);
+        }
+      }
+      for affix in affixes.iter().filter(|affix| !affix.suffix) {
+        words.extend(affix.apply(&stem));
+        if affix.cross_product {
+          for form in &suffixed {
+            words.extend(affix.apply(form));
+          }
+        }
+      }
+    }
+    return Spellchecker { words };
+  }
+
+  /// Checks whether a word is spelled correctly.
+  ///
+  /// Words capitalized or written in capitals are correct if their
+  /// lowercase or capitalized form is.
+  ///
+  /// # Arguments
+  ///
+  /// * `word` - The word, without surrounding punctuation
+  ///
+  /// # Returns
+  ///
+  /// `true` if the dictionary knows the word.
+  pub fn is_correct(&self, word: &str) -> bool {
+    let word = word.replace('\u{2019}', "'");
+    let lowercase = word.to_lowercase();
+    let mut chars = lowercase.chars();
+    let capitalized: String = chars
+      .next()
+      .map(|first| first.to_uppercase().chain(chars).collect())
+      .unwrap_or_default();
+    return [word, lowercase, capitalized]
+      .iter()
+      .any(|form| self.words.contains(form));
+  }
+
+  /// Finds the words of refined text that no dictionary knows and that
+  /// are not in the original input.
+  ///
+  /// # Arguments
+  ///
+  /// * `original` - The input text
+  /// * `refined` - The refined text
+  /// * `dictionary` - The entries of the custom dictionary
+  ///
+  /// # Returns
+  ///
+  /// The suspect words, in order of their first occurrence.
+  pub fn check(
+    &self,
+    original: &str,
+    refined: &str,
+    dictionary: &[DictionaryEntry],
+  ) -> Vec<SuspectWord> {
+    let heard: HashSet<String> = split_words(original).map(fold).collect();
+    let known: HashSet<String> = dictionary
+      .iter()
+      .flat_map(|entry| {
+        return entry
+          .term
+          .split_whitespace()
+          .chain(entry.abbreviation.as_deref());
+      })
+      .map(fold)
+      .collect();
+    let is_known = |word: &str| {
+      let folded = fold(word);
+      return heard.contains(&folded)
+        || known.contains(&folded)
+        || self.is_correct(word);
+    };
+
+    let mut suspects: Vec<SuspectWord> = Vec::new();
+    for word in split_words(refined) {
+      if !word.chars().any(char::is_alphabetic)
+        || word.chars().any(|c| c.is_ascii_digit())
+        || is_known(word)
+      {
+        continue;
+      }
+      let stem = POSSESSIVE_SUFFIXES
+        .iter()
+        .find_map(|suffix| word.strip_suffix(suffix));
+      if stem.is_some_and(is_known) {
+        continue;
+      }
+      match suspects.iter_mut().find(|suspect| suspect.word == word) {
+        Some(suspect) => suspect.count += 1,
+        None => suspects.push(SuspectWord {
+          word: word.to_string(),
+          count: 1,
+        }),
+      }
+    }
+    return suspects;
+  }
+}
+
+/// Decodes a Hunspell file, reading it as Latin-1 if it is not UTF-8.
+fn decode(bytes: Vec<u8>) -> String {
+  return String::from_utf8(bytes).unwrap_or_else(|e| {
+    return e.into_bytes().into_iter().map(char::from).collect();
+  });
+}
+
+/// Reads the `0` of an affix rule as nothing.
+fn empty_if_zero(field: &str) -> &str {
+  if field == "0" {
+    return "";
+  }
+  return field;
+}
+
+/// Turns the condition of an affix rule into a pattern matching the start
+/// of prefixed or the end of suffixed stems.
+///
+/// # Returns
+///
+/// `Some(None)` for rules applying to any stem, `Some(pattern)` for
+/// others, or `None` if the condition is invalid.
+fn parse_condition(
+  condition: Option<&&str>,
+  suffix: bool,
+) -> Option<Option<Regex>> {
+  let Some(condition) = condition.filter(|condition| **condition != ".") else {
+    return Some(None);
+  };
+  let mut pattern = String::new();
+  for c in condition.chars() {
+    if "\\*+?(){}|$".contains(c) {
+      pattern.push('\\');
+    }
+    pattern.push(c);
+  }
+  let pattern = if suffix {
+    format!("(?:{})$", pattern)
+  } else {
+    format!("^(?:{})", pattern)
+  };
+  return Regex::new(&pattern).ok().map(Some);
+}
+
+/// Splits a dictionary entry like `walk/SDG` into its stem and flags.
+fn split_entry(entry: &str, flag_type: FlagType) -> (String, Vec<String>) {
+  let separator = entry
+    .char_indices()
+    .find(|(index, c)| *c == '/' && !entry[..*index].ends_with('\\'))
+    .map(|(index, _)| index);
+  let (stem, flags) = match separator {
+    Some(index) => (&entry[..index], &entry[index + 1..]),
+    None => (entry, ""),
+  };
+  let flags = match flag_type {
+    FlagType::Char => flags.chars().map(String::from).collect(),
+    FlagType::Long => flags
+      .chars()
+      .collect::<Vec<_>>()
+      .chunks(2)
+      .map(|flag| flag.iter().collect())
+      .collect(),
+    FlagType::Numeric => flags
+      .split(',')
+      .filter(|flag| !flag.is_empty())
+      .map(String::from)
+      .collect(),
+  };
+  return (stem.replace("\\/", "/"), flags);
+}
+
+/// Splits text into words without surrounding punctuation; hyphenated and
+/// slashed words are split into their parts.
+fn split_words(text: &str) -> impl Iterator<Item = &str> {
+  return text
+    .split(|c: char| {
+      return c.is_whitespace()
+        || matches!(c, '-' | '/' | '\u{2013}' | '\u{2014}');
+    })
+    .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+    .filter(|word| !word.is_empty());
+}
+
+/// Lowercases a word and straightens its apostrophes for comparison.
+fn fold(word: &str) -> String {
+  return word.to_lowercase().replace('\u{2019}', "'");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const AFFIXES: &str = "\
+SET UTF-8
+NEEDAFFIX X
+PFX U Y 1
+PFX U 0 un .
+SFX S Y 2
+SFX S 0 s [^y]
+SFX S y ies [^aeiou]y
+SFX D N 1
+SFX D 0 ed .
+";
+
+  const DICTIONARY: &str = "\
+5
+walk/SD
+carry/SU
+happy/U
+pseudo/XS
+Kate
+";
+
+  fn spellchecker() -> Spellchecker {
+    return Spellchecker::from_hunspell(DICTIONARY, AFFIXES);
+  }
+
+  #[test]
+  fn generates_the_forms_of_the_stems() {
+    let spellchecker = spellchecker();
+    for word in ["walk", "walks", "walked", "carries", "unhappy", "Kate"] {
+      assert!(spellchecker.is_correct(word), "{}", word);
+    }
+    for word in ["carrys", "unwalk", "walkeds", "pseudo"] {
+      assert!(!spellchecker.is_correct(word), "{}", word);
+    }
+    assert!(spellchecker.is_correct("pseudos"));
+  }
+
+  #[test]
+  fn combines_cross_product_affixes_only() {
+    let spellchecker = spellchecker();
+    assert!(spellchecker.is_correct("uncarries"));
+    let affixes = AFFIXES.replace("SFX S Y 2", "SFX S N 2");
+    let spellchecker = Spellchecker::from_hunspell(DICTIONARY, &affixes);
+    assert!(!spellchecker.is_correct("uncarries"));
+    assert!(spellchecker.is_correct("carries"));
+  }
+
+  #[test]
+  fn accepts_capitalized_forms_of_known_words() {
+    let spellchecker = spellchecker();
+    assert!(spellchecker.is_correct("Walks"));
+    assert!(spellchecker.is_correct("WALKED"));
+    assert!(spellchecker.is_correct("KATE"));
+  }
+
+  #[test]
+  fn splits_entries_by_flag_type() {
+    assert_eq!(
+      split_entry("walk/SD", FlagType::Char),
+      (
+        String::from("walk"),
+        vec![String::from("S"), String::from("D")]
+      )
+    );
+    assert_eq!(
+      split_entry("walk/AaBb", FlagType::Long),
+      (
+        String::from("walk"),
+        vec![String::from("Aa"), String::from("Bb")]
+      )
+    );
+    assert_eq!(
+      split_entry("walk/12,3", FlagType::Numeric),
+      (
+        String::from("walk"),
+        vec![String::from("12"), String::from("3")]
+      )
+    );
+    assert_eq!(
+      split_entry("and\\/or", FlagType::Char),
+      (String::from("and/or"), Vec::new())
+    );
+  }
+
+  #[test]
+  fn reads_latin_1_files() {
+    assert_eq!(decode(vec![b'c', 0xe9]), "c\u{e9}");
+    assert_eq!(decode("café".as_bytes().to_vec()), "café");
+  }
+
+  #[test]
+  fn flags_words_neither_known_nor_heard() {
+    let dictionary = [DictionaryEntry::new("Kubernetes cluster")];
+    let suspects = spellchecker().check(
+      "kate walked to the zorble",
+      "Kate's zorble walks to the Kubernetes blorp-walk, blorp, 3rd plinth.",
+      &dictionary,
+    );
+    assert_eq!(
+      suspects,
+      [
+        SuspectWord {
+          word: String::from("blorp"),
+          count: 2,
+        },
+        SuspectWord {
+          word: String::from("plinth"),
+          count: 1,
+        },
+      ]
+    );
+  }
+}
@@ -0,0 +1,70 @@
+//! Optional JSON records in the XDG cache directory.
+//!
+//! The capability probes and the queue's output record are kept in
+//! `$XDG_CACHE_HOME/pegasus`. A cache only saves work: a record that is
+//! missing or cannot be read is empty, and one that cannot be written is
+//! skipped with a debug message. Records are written atomically, since
+//! parallel runs may write them at once.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use xdg::BaseDirectories;
+
+use crate::dlog;
+use crate::files::operations;
+
+/// Directory of the cache under `$XDG_CACHE_HOME`.
+const CACHE_DIRECTORY: &str = "pegasus";
+
+/// Reads a record from the cache.
+///
+/// # Arguments
+///
+/// * `file_name` - File name of the record in the cache directory
+///
+/// # Returns
+///
+/// The record, or the default value if it is missing or unreadable.
+pub async fn read<T>(file_name: &str) -> T
+where
+  T: DeserializeOwned + Default,
+{
+  let Some(path) =
+    BaseDirectories::with_prefix(CACHE_DIRECTORY).find_cache_file(file_name)
+  else {
+    return T::default();
+  };
+  return match tokio::fs::read_to_string(&path).await {
+    Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+    Err(_) => T::default(),
+  };
+}
+
+/// Writes a record to the cache, logging failures.
+///
+/// # Arguments
+///
+/// * `file_name` - File name of the record in the cache directory
+/// * `record` - The record
+pub async fn write<T: Serialize>(file_name: &str, record: &T) {
+  let path = match BaseDirectories::with_prefix(CACHE_DIRECTORY)
+    .place_cache_file(file_name)
+  {
+    Ok(path) => path,
+    Err(e) => {
+      dlog!("Could not create the cache directory: {}", e);
+      return;
+    }
+  };
+  let content = match serde_json::to_string_pretty(record) {
+    Ok(content) => content,
+    Err(e) => {
+      dlog!("Could not encode {}: {}", file_name, e);
+      return;
+    }
+  };
+  let path = path.to_string_lossy();
+  if let Err(e) = operations::write_string_atomic(&path, &content).await {
+    dlog!("Could not write {}: {}", path, e);
+  }
+}
@@ -0,0 +1,64 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// File operation errors.
+///
+/// Represents errors that can occur during file and directory operations.
+#[derive(Error, Debug)]
+pub enum FileError {
+  #[error("{}", t!("error-files-read", path = .0.as_str()))]
+  FileRead(String),
+
+  #[error(
+    "{}",
+    t!("error-files-write", path = .0.as_str(), error = .1.as_str())
+  )]
+  FileWrite(String, String),
+
+  #[error(
+    "{}",
+    t!("error-files-create", path = .0.as_str(), error = .1.as_str())
+  )]
+  Create(String, String),
+
+  #[error(
+    "{}",
+    t!(
+      "error-files-copy",
+      source = .0.as_str(),
+      destination = .1.as_str(),
+      error = .2.as_str()
+    )
+  )]
+  Copy(String, String, String),
+
+  #[error(
+    "{}",
+    t!(
+      "error-files-move",
+      source = .0.as_str(),
+      destination = .1.as_str(),
+      error = .2.as_str()
+    )
+  )]
+  Move(String, String, String),
+
+  #[error(
+    "{}",
+    t!("error-files-lock", path = .0.as_str(), error = .1.as_str())
+  )]
+  Lock(String, String),
+
+  #[error("{}", t!("error-files-locked", path = .0.as_str()))]
+  Locked(String),
+
+  #[error(
+    "{}",
+    t!("error-files-editor", editor = .0.as_str(), error = .1.as_str())
+  )]
+  Editor(String, String),
+}
+
+/// Result type for file operations.
+pub type FileResult<T> = Result<T, FileError>;
@@ -5,14 +5,21 @@
 //!
 //! ## Submodules
 //!
-//! - [`operations`]: Core file system operations (read, write, delete, etc.)
+//! - [`operations`]: Core file system operations (read, atomic write, copy,
+//!   move, append, locking)
+//! - [`cache`]: Optional JSON records in the XDG cache directory
+//! - [`temporary`]: Temporary files in the XDG cache directory, removed on
+//!   drop
 //! - [`errors`]: Error types for file operations
 //!
 //! ## Features
 //!
 //! - Async file operations using Tokio
+//! - Atomic writes and advisory file locks for concurrent processes
 //! - XDG directory compliance helpers
 //! - Comprehensive error handling with context
 
+pub mod cache;
 pub mod errors;
 pub mod operations;
+pub mod temporary;
@@ -0,0 +1,337 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::clock;
+use crate::files::errors::{FileError, FileResult};
+
+const DEFAULT_EDITOR: &str = "vi";
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Reads the entire contents of a file into a string.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file to read
+///
+/// # Returns
+///
+/// A `FileResult<String>` containing the file contents or an error.
+pub async fn read_to_string(file_path: &str) -> FileResult<String> {
+  return tokio::fs::read_to_string(file_path)
+    .await
+    .map_err(|e| FileError::FileRead(e.to_string()));
+}
+
+/// Returns the size of a file in bytes.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file
+///
+/// # Returns
+///
+/// A `FileResult<u64>` containing the file size or an error.
+pub async fn file_size(file_path: &str) -> FileResult<u64> {
+  return tokio::fs::metadata(file_path)
+    .await
+    .map(|metadata| metadata.len())
+    .map_err(|e| FileError::FileRead(e.to_string()));
+}
+
+/// Writes a string to a file atomically.
+///
+/// The content is written to a temporary file in the same directory, synced
+/// to disk and then renamed over the destination, so readers never observe
+/// a partially written file. The permissions of an existing destination are
+/// kept, so a private file stays private.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file to write
+/// * `content` - The content to write
+///
+/// # Returns
+///
+/// A `FileResult<()>` indicating success or failure.
+pub async fn write_string_atomic(
+  file_path: &str,
+  content: &str,
+) -> FileResult<()> {
+  let path = Path::new(file_path);
+  let temp_path = temp_sibling_path(path);
+
+  let write_result = async {
+    let mut file = tokio::fs::File::create(&temp_path).await?;
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+      file.set_permissions(metadata.permissions()).await?;
+    }
+    tokio::io::AsyncWriteExt::write_all(&mut file, content.as_bytes()).await?;
+    file.sync_all().await?;
+    return tokio::fs::rename(&temp_path, path).await;
+  }
+  .await;
+
+  if let Err(e) = write_result {
+    let _ = tokio::fs::remove_file(&temp_path).await;
+    return Err(FileError::FileWrite(file_path.to_string(), e.to_string()));
+  }
+
+  return Ok(());
+}
+
+/// Copies a file, overwriting the destination.
+///
+/// The destination is written atomically, so an interrupted copy never
+/// leaves a truncated file behind. It gets the permissions of the source,
+/// so a backup of a private file stays private.
+///
+/// # Arguments
+///
+/// * `source_path` - The path to the file to copy
+/// * `destination_path` - The path to copy the file to
+///
+/// # Returns
+///
+/// A `FileResult<()>` indicating success or failure.
+pub async fn copy_file(
+  source_path: &str,
+  destination_path: &str,
+) -> FileResult<()> {
+  let content = tokio::fs::read(source_path).await.map_err(|e| {
+    FileError::Copy(
+      source_path.to_string(),
+      destination_path.to_string(),
+      e.to_string(),
+    )
+  })?;
+
+  let temp_path = temp_sibling_path(Path::new(destination_path));
+
+  let copy_result = async {
+    let mut file = tokio::fs::File::create(&temp_path).await?;
+    let permissions = tokio::fs::metadata(source_path).await?.permissions();
+    file.set_permissions(permissions).await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, &content).await?;
+    file.sync_all().await?;
+    return tokio::fs::rename(&temp_path, destination_path).await;
+  }
+  .await;
+
+  if let Err(e) = copy_result {
+    let _ = tokio::fs::remove_file(&temp_path).await;
+    return Err(FileError::Copy(
+      source_path.to_string(),
+      destination_path.to_string(),
+      e.to_string(),
+    ));
+  }
+
+  return Ok(());
+}
+
+/// Moves a file, overwriting the destination.
+///
+/// Uses a rename when possible and falls back to copy-and-delete when the
+/// destination is on a different filesystem.
+///
+/// # Arguments
+///
+/// * `source_path` - The path to the file to move
+/// * `destination_path` - The path to move the file to
+///
+/// # Returns
+///
+/// A `FileResult<()>` indicating success or failure.
+pub async fn move_file(
+  source_path: &str,
+  destination_path: &str,
+) -> FileResult<()> {
+  if tokio::fs::rename(source_path, destination_path)
+    .await
+    .is_ok()
+  {
+    return Ok(());
+  }
+
+  copy_file(source_path, destination_path).await?;
+
+  return tokio::fs::remove_file(source_path).await.map_err(|e| {
+    FileError::Move(
+      source_path.to_string(),
+      destination_path.to_string(),
+      e.to_string(),
+    )
+  });
+}
+
+/// Appends a string to a file, creating it if necessary.
+///
+/// The content is written with a single `write` call on a file opened in
+/// append mode, so lines appended by concurrent processes do not interleave.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file to append to
+/// * `content` - The content to append
+///
+/// # Returns
+///
+/// A `FileResult<()>` indicating success or failure.
+pub async fn append_string(file_path: &str, content: &str) -> FileResult<()> {
+  let append_result = async {
+    let mut file = tokio::fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(file_path)
+      .await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, content.as_bytes()).await?;
+    return file.sync_data().await;
+  }
+  .await;
+
+  return append_result
+    .map_err(|e| FileError::FileWrite(file_path.to_string(), e.to_string()));
+}
+
+/// An advisory lock on a file, released when dropped.
+///
+/// The lock is taken on a separate `<file>.lock` file so the locked file
+/// itself can be replaced atomically while the lock is held.
+#[derive(Debug)]
+pub struct FileLock {
+  _file: std::fs::File,
+}
+
+/// Takes an exclusive advisory lock for the given file.
+///
+/// Waits until other processes holding the lock release it.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file to lock
+///
+/// # Returns
+///
+/// A `FileResult<FileLock>` containing the lock guard or an error.
+pub async fn lock_file(file_path: &str) -> FileResult<FileLock> {
+  let lock_path = sibling_path(Path::new(file_path), "lock");
+  let error_path = file_path.to_string();
+
+  return tokio::task::spawn_blocking(move || {
+    let file = std::fs::OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .open(&lock_path)
+      .map_err(|e| FileError::Lock(error_path.clone(), e.to_string()))?;
+    file
+      .lock()
+      .map_err(|e| FileError::Lock(error_path.clone(), e.to_string()))?;
+    return Ok(FileLock { _file: file });
+  })
+  .await
+  .map_err(|e| FileError::Lock(file_path.to_string(), e.to_string()))?;
+}
+
+/// Takes an exclusive advisory lock for the given file without waiting.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file to lock
+///
+/// # Returns
+///
+/// A `FileResult<FileLock>` containing the lock guard, or
+/// `FileError::Locked` if another process holds the lock.
+pub fn try_lock_file(file_path: &str) -> FileResult<FileLock> {
+  let lock_path = sibling_path(Path::new(file_path), "lock");
+  let file = std::fs::OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .write(true)
+    .open(&lock_path)
+    .map_err(|e| FileError::Lock(file_path.to_string(), e.to_string()))?;
+
+  return match file.try_lock() {
+    Ok(()) => Ok(FileLock { _file: file }),
+    Err(std::fs::TryLockError::WouldBlock) => {
+      Err(FileError::Locked(file_path.to_string()))
+    }
+    Err(std::fs::TryLockError::Error(e)) => {
+      Err(FileError::Lock(file_path.to_string(), e.to_string()))
+    }
+  };
+}
+
+/// Opens a file in the user's editor and waits for it to exit.
+///
+/// The editor is taken from `$VISUAL`, then `$EDITOR`, falling back to
+/// `vi`. It is run through the shell so it may include arguments.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the file to edit
+///
+/// # Returns
+///
+/// A `FileResult<()>` indicating whether the editor exited successfully.
+pub async fn edit_in_editor(file_path: &Path) -> FileResult<()> {
+  let editor = std::env::var("VISUAL")
+    .or_else(|_| std::env::var("EDITOR"))
+    .unwrap_or_else(|_| String::from(DEFAULT_EDITOR));
+  let status = tokio::process::Command::new("sh")
+    .arg("-c")
+    .arg(format!("{} \"$1\"", editor))
+    .arg("sh")
+    .arg(file_path)
+    .status()
+    .await
+    .map_err(|e| FileError::Editor(editor.clone(), e.to_string()))?;
+
+  if !status.success() {
+    return Err(FileError::Editor(editor, format!("exited with {}", status)));
+  }
+  return Ok(());
+}
+
+/// Builds a hidden sibling path such as `.config.toml.lock`.
+///
+/// # Arguments
+///
+/// * `path` - The original file path
+/// * `suffix` - The suffix to append
+///
+/// # Returns
+///
+/// The sibling path in the same directory.
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+  let file_name = path
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+  return path.with_file_name(format!(".{}.{}", file_name, suffix));
+}
+
+/// Builds a hidden sibling path for a temporary file such as
+/// `.config.toml.tmp.1234-0-9f3a2c71`, unique to this process and call.
+///
+/// # Arguments
+///
+/// * `path` - The file the temporary file will replace
+///
+/// # Returns
+///
+/// The temporary path in the same directory.
+fn temp_sibling_path(path: &Path) -> PathBuf {
+  let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+  return sibling_path(
+    path,
+    &format!(
+      "tmp.{}-{}-{:x}",
+      std::process::id(),
+      counter,
+      clock::random() as u32
+    ),
+  );
+}
@@ -0,0 +1,246 @@
+//! Temporary files with automatic cleanup.
+//!
+//! [`TemporaryFile`] is an RAII guard: the file it points to is removed when
+//! the guard is dropped, unless it has been persisted.
+//!
+//! Removal is a plain blocking `std::fs` call, so it works whether or not a
+//! Tokio runtime is still running. Since `std::process::exit` and signals
+//! skip destructors, every live temporary file is also tracked in a
+//! process-wide registry that [`remove_all`] clears on those exit paths.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use xdg::BaseDirectories;
+
+use crate::clock;
+use crate::files::errors::{FileError, FileResult};
+
+const CACHE_DIRECTORY: &str = "pegasus";
+const MAX_CREATE_ATTEMPTS: u32 = 16;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+static LIVE_FILES: LazyLock<Mutex<HashSet<PathBuf>>> =
+  LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Removes every temporary file that has not been dropped or persisted yet.
+///
+/// Call this before leaving the process without unwinding, e.g. before
+/// `std::process::exit` or when interrupted by a signal.
+pub fn remove_all() {
+  let mut live_files = LIVE_FILES.lock().unwrap_or_else(|e| e.into_inner());
+  for path in live_files.drain() {
+    let _ = std::fs::remove_file(path);
+  }
+}
+
+fn register(path: &Path) {
+  let mut live_files = LIVE_FILES.lock().unwrap_or_else(|e| e.into_inner());
+  live_files.insert(path.to_path_buf());
+}
+
+fn unregister(path: &Path) {
+  let mut live_files = LIVE_FILES.lock().unwrap_or_else(|e| e.into_inner());
+  live_files.remove(path);
+}
+
+/// A file that is removed when the guard is dropped.
+#[derive(Debug)]
+pub struct TemporaryFile {
+  path: PathBuf,
+  keep: bool,
+}
+
+impl TemporaryFile {
+  /// Wraps an existing path in a cleanup guard.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The path to remove on drop
+  ///
+  /// # Returns
+  ///
+  /// A new `TemporaryFile` instance.
+  pub fn new(path: PathBuf) -> Self {
+    register(&path);
+    return TemporaryFile { path, keep: false };
+  }
+
+  /// Creates a new empty file with a unique name in the XDG cache
+  /// directory (e.g. `~/.cache/pegasus/capture-1234-0-5f3a.wav`).
+  ///
+  /// # Arguments
+  ///
+  /// * `prefix` - File name prefix
+  /// * `extension` - File extension without the leading dot
+  ///
+  /// # Returns
+  ///
+  /// A `FileResult<TemporaryFile>` guarding the created file.
+  pub async fn create_in_cache(
+    prefix: &str,
+    extension: &str,
+  ) -> FileResult<Self> {
+    let xdg_dirs = BaseDirectories::with_prefix(CACHE_DIRECTORY);
+    let cache_dir = xdg_dirs
+      .create_cache_directory("")
+      .map_err(|e| FileError::Create(prefix.to_string(), e.to_string()))?;
+    return TemporaryFile::create_in(&cache_dir, prefix, extension).await;
+  }
+
+  /// Creates a new file in the XDG cache directory holding `content`.
+  ///
+  /// # Arguments
+  ///
+  /// * `prefix` - File name prefix
+  /// * `extension` - File extension without the leading dot
+  /// * `content` - Initial file content
+  ///
+  /// # Returns
+  ///
+  /// A `FileResult<TemporaryFile>` guarding the created file.
+  pub async fn create_in_cache_with_content(
+    prefix: &str,
+    extension: &str,
+    content: &str,
+  ) -> FileResult<Self> {
+    let temporary_file =
+      TemporaryFile::create_in_cache(prefix, extension).await?;
+    return temporary_file.write(content).await;
+  }
+
+  /// Creates a new file in `directory` holding `content`.
+  ///
+  /// # Arguments
+  ///
+  /// * `directory` - The directory to create the file in
+  /// * `prefix` - File name prefix
+  /// * `extension` - File extension without the leading dot
+  /// * `content` - Initial file content
+  ///
+  /// # Returns
+  ///
+  /// A `FileResult<TemporaryFile>` guarding the created file.
+  pub async fn create_in_with_content(
+    directory: &Path,
+    prefix: &str,
+    extension: &str,
+    content: &str,
+  ) -> FileResult<Self> {
+    let temporary_file =
+      TemporaryFile::create_in(directory, prefix, extension).await?;
+    return temporary_file.write(content).await;
+  }
+
+  /// Creates a new empty file with a unique name in `directory`.
+  ///
+  /// On Unix the file is readable and writable by the user only, since
+  /// drafts may hold API keys or transcripts.
+  ///
+  /// # Arguments
+  ///
+  /// * `directory` - The directory to create the file in
+  /// * `prefix` - File name prefix
+  /// * `extension` - File extension without the leading dot
+  ///
+  /// # Returns
+  ///
+  /// A `FileResult<TemporaryFile>` guarding the created file.
+  pub async fn create_in(
+    directory: &Path,
+    prefix: &str,
+    extension: &str,
+  ) -> FileResult<Self> {
+    let mut last_error = None;
+
+    for _ in 0..MAX_CREATE_ATTEMPTS {
+      let path = directory.join(unique_name(prefix, extension));
+      let mut options = tokio::fs::OpenOptions::new();
+      options.write(true).create_new(true);
+      #[cfg(unix)]
+      options.mode(0o600);
+      let create_result = options.open(&path).await;
+
+      match create_result {
+        Ok(_) => return Ok(TemporaryFile::new(path)),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+          last_error = Some(e);
+        }
+        Err(e) => {
+          return Err(FileError::Create(
+            path.to_string_lossy().to_string(),
+            e.to_string(),
+          ));
+        }
+      }
+    }
+
+    return Err(FileError::Create(
+      directory.join(prefix).to_string_lossy().to_string(),
+      last_error.map(|e| e.to_string()).unwrap_or_default(),
+    ));
+  }
+
+  /// Writes the initial content of the file.
+  async fn write(self, content: &str) -> FileResult<Self> {
+    tokio::fs::write(&self.path, content)
+      .await
+      .map_err(|e| FileError::FileWrite(self.path_string(), e.to_string()))?;
+    return Ok(self);
+  }
+
+  /// Gets the path of the temporary file.
+  ///
+  /// # Returns
+  ///
+  /// The file path.
+  pub fn path(&self) -> &Path {
+    return &self.path;
+  }
+
+  /// Gets the path of the temporary file as a string.
+  ///
+  /// # Returns
+  ///
+  /// The file path as a `String`.
+  pub fn path_string(&self) -> String {
+    return self.path.to_string_lossy().to_string();
+  }
+
+  /// Keeps the file on disk instead of removing it on drop.
+  ///
+  /// # Returns
+  ///
+  /// The path of the kept file.
+  pub fn persist(mut self) -> PathBuf {
+    self.keep = true;
+    unregister(&self.path);
+    return self.path.clone();
+  }
+}
+
+impl Drop for TemporaryFile {
+  fn drop(&mut self) {
+    if self.keep {
+      return;
+    }
+    unregister(&self.path);
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
+/// Builds a file name unique to this process and call.
+fn unique_name(prefix: &str, extension: &str) -> String {
+  let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
+  return format!(
+    "{}-{}-{}-{:x}.{}",
+    prefix,
+    std::process::id(),
+    counter,
+    clock::random() as u32,
+    extension
+  );
+}
@@ -0,0 +1,268 @@
+//! Localized user-facing messages.
+//!
+//! Messages are looked up by ID in [Fluent](https://projectfluent.org)
+//! catalogs. The English catalog is built in and is the fallback for
+//! every message; translations are plugged in without rebuilding by
+//! placing a catalog at `$XDG_DATA_HOME/pegasus/locales/<language>/
+//! pegasus.ftl` (or in one of `$XDG_DATA_DIRS`). The language is taken
+//! from `PEGASUS_LANG`, then `LC_ALL`, `LC_MESSAGES` and `LANG`, so
+//! `LANG=de_DE.UTF-8` looks for `de-DE` first and then `de`.
+//!
+//! Translations may also cover the command-line help: a message
+//! `help-<command>` replaces the description of a (sub)command, such as
+//! `help-queue-add`, and `help-<command>--<argument>` the help of one of
+//! its arguments, such as `help-queue-add--force`. The English help stays
+//! in the command-line definitions.
+//!
+//! The errors of the library format their messages from the catalog too,
+//! as `error-<module>-<error>` such as `error-network-response`.
+//!
+//! ## Main Components
+//!
+//! - [`t!`]: Formats a message in the user's language
+//! - [`text`]: Formats a message from its ID and arguments
+//! - [`translation`]: Looks up a message in the translation only
+//! - [`language`]: The language messages are shown in
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+use xdg::BaseDirectories;
+
+use crate::elog;
+use crate::logging;
+
+#[doc(hidden)]
+pub use fluent_bundle;
+
+/// Directory of the catalogs under the XDG data directories.
+const DATA_DIRECTORY: &str = "pegasus";
+
+/// File name of a catalog in its language directory.
+const CATALOG_FILE: &str = "pegasus.ftl";
+
+/// The built-in English catalog.
+const ENGLISH: &str = include_str!("../../locales/en/pegasus.ftl");
+
+/// Environment variables naming the language, by precedence.
+const LANGUAGE_VARIABLES: [&str; 4] =
+  ["PEGASUS_LANG", "LC_ALL", "LC_MESSAGES", "LANG"];
+
+static CATALOGS: OnceLock<Catalogs> = OnceLock::new();
+
+/// The English catalog and the translation of the user's language.
+struct Catalogs {
+  language: LanguageIdentifier,
+  translation: Option<FluentBundle<FluentResource>>,
+  english: FluentBundle<FluentResource>,
+}
+
+/// Formats a message in the user's language.
+///
+/// Takes the message ID and its arguments as `name = value` pairs, where
+/// values are strings or numbers. Messages missing from the translation
+/// are formatted in English.
+///
+/// # Examples
+///
+/// ```rust
+/// use pegasus_core::t;
+///
+/// let message = t!("queue-refined", done = 3, unchanged = 1);
+/// assert_eq!(message, "Refined 3 files (1 unchanged)");
+/// ```
+#[macro_export]
+macro_rules! t {
+    ($id:expr $(, $name:ident = $value:expr)* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut args = $crate::i18n::fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)*
+        $crate::i18n::text($id, &args)
+    }};
+}
+
+/// Formats a message in the user's language.
+///
+/// # Arguments
+///
+/// * `id` - The message ID
+/// * `args` - The arguments of the message
+///
+/// # Returns
+///
+/// The message from the translation, or else from the English catalog,
+/// or else the ID itself.
+pub fn text(id: &str, args: &FluentArgs) -> String {
+  let catalogs = catalogs();
+  return catalogs
+    .translation
+    .as_ref()
+    .and_then(|bundle| format(bundle, id, args))
+    .or_else(|| format(&catalogs.english, id, args))
+    .unwrap_or_else(|| id.to_string());
+}
+
+/// Looks up a message in the translation of the user's language only.
+///
+/// # Arguments
+///
+/// * `id` - The message ID
+///
+/// # Returns
+///
+/// The message without arguments, or `None` if no translation is plugged
+/// in or it lacks the message.
+pub fn translation(id: &str) -> Option<String> {
+  let bundle = catalogs().translation.as_ref()?;
+  return format(bundle, id, &FluentArgs::new());
+}
+
+/// Gets the language messages are shown in.
+///
+/// # Returns
+///
+/// The language from the environment, or `en` if none is set.
+pub fn language() -> &'static LanguageIdentifier {
+  return &catalogs().language;
+}
+
+/// Loads the catalogs on first use.
+fn catalogs() -> &'static Catalogs {
+  return CATALOGS.get_or_init(|| {
+    let english_id: LanguageIdentifier = "en".parse().unwrap_or_default();
+    let language = requested_language().unwrap_or(english_id.clone());
+    let english = bundle(&english_id, ENGLISH.to_string(), "built-in");
+    let translation = find_catalog(&language).map(|(path, content)| {
+      return bundle(&language, content, &path.display().to_string());
+    });
+    return Catalogs {
+      language,
+      translation,
+      english,
+    };
+  });
+}
+
+/// Reads the language from the environment.
+///
+/// `C` and `POSIX` select English; encodings and modifiers like
+/// `.UTF-8` and `@euro` are ignored.
+fn requested_language() -> Option<LanguageIdentifier> {
+  let value = LANGUAGE_VARIABLES.iter().find_map(|name| {
+    return std::env::var(name).ok().filter(|value| !value.is_empty());
+  })?;
+  let tag = value
+    .split(['.', '@'])
+    .next()
+    .unwrap_or_default()
+    .replace('_', "-");
+  if tag == "C" || tag == "POSIX" {
+    return None;
+  }
+  return tag.parse().ok();
+}
+
+/// Finds the catalog of a language in the XDG data directories, trying
+/// the full language tag first and then the language alone.
+fn find_catalog(language: &LanguageIdentifier) -> Option<(PathBuf, String)> {
+  if language.language.as_str() == "en" && language.region.is_none() {
+    return None;
+  }
+  let xdg_dirs = BaseDirectories::with_prefix(DATA_DIRECTORY);
+  let tags = [language.to_string(), language.language.to_string()];
+  return tags.iter().find_map(|tag| {
+    let path =
+      xdg_dirs.find_data_file(format!("locales/{}/{}", tag, CATALOG_FILE))?;
+    return match std::fs::read_to_string(&path) {
+      Ok(content) => Some((path, content)),
+      Err(e) => {
+        elog!(logging::WARNING, "Cannot read {}: {}", path.display(), e);
+        None
+      }
+    };
+  });
+}
+
+/// Builds the bundle of a catalog, logging syntax errors.
+///
+/// Messages that parse are kept even if others in the catalog do not.
+fn bundle(
+  language: &LanguageIdentifier,
+  content: String,
+  source: &str,
+) -> FluentBundle<FluentResource> {
+  let resource =
+    FluentResource::try_new(content).unwrap_or_else(|(resource, errors)| {
+      elog!(
+        logging::WARNING,
+        "Skipping {} invalid messages in the {} catalog",
+        errors.len(),
+        source
+      );
+      return resource;
+    });
+  let mut bundle = FluentBundle::new_concurrent(vec![language.clone()]);
+  // Unicode isolation marks around arguments show up in terminals.
+  bundle.set_use_isolating(false);
+  if let Err(errors) = bundle.add_resource(resource) {
+    elog!(
+      logging::WARNING,
+      "Skipping {} duplicate messages in the {} catalog",
+      errors.len(),
+      source
+    );
+  }
+  return bundle;
+}
+
+/// Formats a message of a bundle, if the bundle has it.
+fn format(
+  bundle: &FluentBundle<FluentResource>,
+  id: &str,
+  args: &FluentArgs,
+) -> Option<String> {
+  let pattern = bundle.get_message(id)?.value()?;
+  let mut errors = Vec::new();
+  let text = bundle.format_pattern(pattern, Some(args), &mut errors);
+  return Some(text.into_owned());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::input::errors::InputError;
+  use crate::llm::errors::{LLMError, OverflowAdvice};
+
+  #[test]
+  fn parses_the_english_catalog() {
+    assert!(FluentResource::try_new(ENGLISH.to_string()).is_ok());
+  }
+
+  #[test]
+  fn formats_errors_from_the_catalog() {
+    let error = LLMError::ContextOverflow {
+      needed: 9000,
+      answer: 512,
+      window: 8192,
+      advice: OverflowAdvice::LowerChunkSize,
+    };
+    assert_eq!(
+      error.to_string(),
+      "Prompt of about 9000 tokens and an answer of about 512 tokens exceed \
+       the context window of 8192 tokens; lower `[input] chunk_size` to \
+       split the input into smaller chunks"
+    );
+    let error = InputError::InvalidTranscript(vec![
+      String::from("cue 1 overlaps cue 2"),
+      String::from("cue 3 is empty"),
+    ]);
+    assert_eq!(
+      error.to_string(),
+      "Transcription failed strict validation:\n  - cue 1 overlaps cue 2\n  \
+       - cue 3 is empty"
+    );
+  }
+}
@@ -0,0 +1,529 @@
+//! Streaming chunked input reader.
+//!
+//! Reads input line by line and groups lines into chunks of bounded size, so
+//! very large transcripts can be refined piece by piece without loading the
+//! whole file into memory. Chunks close at the first paragraph boundary
+//! (blank line) after reaching the target size; paragraphs longer than twice
+//! the target are split between lines, and lines longer than twice the
+//! target between sentences, or between words if they have no sentence
+//! boundary. A line is never buffered whole: once it reaches a few times
+//! the target size it is cut at a space, so input without line breaks is
+//! streamed too. Sizes are counted in characters, or in tokens of the model
+//! when a tokenizer is set.
+
+use std::io::Cursor;
+
+use encoding_rs::{Encoding, UTF_8};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::input::compression::BoxedReader;
+use crate::input::encoding::DecodingReader;
+use crate::input::errors::{InputError, InputResult};
+use crate::input::sentences;
+use crate::llm::tokenizer::Tokenizer;
+use crate::timing::{self, Phase};
+
+/// Bytes of a line buffered per unit of the target size before the line is
+/// cut, enough for four-byte characters at twice the target.
+const MAX_LINE_BYTES_PER_UNIT: usize = 8;
+
+/// What chunk sizes are counted in.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkUnit {
+  /// Unicode characters
+  #[default]
+  Characters,
+  /// Tokens of the configured model
+  Tokens,
+}
+
+/// A chunk of input text.
+#[derive(Debug, Clone)]
+pub struct InputChunk {
+  /// The chunk text without trailing blank lines
+  pub text: String,
+  /// Whether the chunk ended at a paragraph boundary
+  pub ends_paragraph: bool,
+  /// Whether the chunk ended at a line break, rather than inside a line
+  /// split between sentences
+  pub ends_line: bool,
+}
+
+/// Reads input as a stream of bounded chunks.
+pub struct ChunkReader {
+  reader: BufReader<BoxedReader>,
+  source_name: String,
+  target_chars: usize,
+  tokenizer: Option<Tokenizer>,
+  buffer: Vec<u8>,
+  bytes_read: usize,
+}
+
+/// A line of input, or the start of a line that was too long to buffer.
+struct Line {
+  text: String,
+  complete: bool,
+}
+
+impl ChunkReader {
+  /// Creates a chunk reader over in-memory text.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The input text
+  /// * `target_chars` - Target chunk size in characters (0 for one chunk)
+  ///
+  /// # Returns
+  ///
+  /// A new `ChunkReader` instance.
+  pub fn from_text(text: String, target_chars: usize) -> Self {
+    let reader: BoxedReader = Box::new(Cursor::new(text.into_bytes()));
+    return ChunkReader::new(reader, String::from("input"), target_chars);
+  }
+
+  /// Creates a chunk reader streaming from a file.
+  ///
+  /// Files in encodings other than UTF-8 are transcoded while reading.
+  ///
+  /// # Arguments
+  ///
+  /// * `reader` - The opened (and decompressed) file stream
+  /// * `file_path` - Path to the input file, used in error messages
+  /// * `target_chars` - Target chunk size in characters (0 for one chunk)
+  /// * `encoding` - The encoding of the file
+  ///
+  /// # Returns
+  ///
+  /// A new `ChunkReader` instance.
+  pub fn from_file(
+    reader: BoxedReader,
+    file_path: &str,
+    target_chars: usize,
+    encoding: &'static Encoding,
+  ) -> Self {
+    let reader: BoxedReader = if encoding == UTF_8 {
+      reader
+    } else {
+      Box::new(DecodingReader::new(reader, encoding))
+    };
+    return ChunkReader::new(reader, file_path.to_string(), target_chars);
+  }
+
+  fn new(
+    reader: BoxedReader,
+    source_name: String,
+    target_chars: usize,
+  ) -> Self {
+    return ChunkReader {
+      reader: BufReader::new(reader),
+      source_name,
+      target_chars,
+      tokenizer: None,
+      buffer: Vec::new(),
+      bytes_read: 0,
+    };
+  }
+
+  /// Counts chunk sizes in tokens instead of characters.
+  ///
+  /// # Arguments
+  ///
+  /// * `tokenizer` - The tokenizer of the model (none for characters)
+  ///
+  /// # Returns
+  ///
+  /// The updated `ChunkReader` instance.
+  pub fn with_tokenizer(mut self, tokenizer: Option<Tokenizer>) -> Self {
+    self.tokenizer = tokenizer;
+    return self;
+  }
+
+  /// Measures text in the unit of the target size.
+  fn measure(&self, text: &str) -> usize {
+    return match &self.tokenizer {
+      Some(tokenizer) => tokenizer.count(text),
+      None => text.chars().count(),
+    };
+  }
+
+  /// Reads the next line, or the start of a line too long to buffer.
+  ///
+  /// Lines are cut at the last space before the limit, or at a character
+  /// boundary if there is none. The rest of the line stays buffered.
+  async fn next_line(&mut self) -> InputResult<Option<Line>> {
+    let limit = self.target_chars * MAX_LINE_BYTES_PER_UNIT;
+    let (end, complete) = loop {
+      if let Some(newline) = self.buffer.iter().position(|b| *b == b'\n') {
+        break (newline, true);
+      }
+      if limit > 0 && self.buffer.len() >= limit {
+        break (cut_point(&self.buffer[..limit]), false);
+      }
+      let read = self.reader.fill_buf().await.map_err(|e| {
+        if e.kind() == std::io::ErrorKind::InvalidData {
+          return InputError::InvalidUtf8 {
+            path: self.source_name.clone(),
+            offset: self.bytes_read,
+          };
+        }
+        return InputError::FileReadError {
+          path: self.source_name.clone(),
+          error: e.to_string(),
+        };
+      })?;
+      if read.is_empty() {
+        if self.buffer.is_empty() {
+          return Ok(None);
+        }
+        break (self.buffer.len(), true);
+      }
+      self.buffer.extend_from_slice(read);
+      let read = read.len();
+      self.reader.consume(read);
+    };
+
+    let consumed = if complete && end < self.buffer.len() {
+      end + 1
+    } else {
+      end
+    };
+    let mut bytes: Vec<u8> = self.buffer.drain(..consumed).collect();
+    bytes.truncate(end);
+    if complete && bytes.last() == Some(&b'\r') {
+      bytes.pop();
+    }
+    let mut text =
+      String::from_utf8(bytes).map_err(|e| InputError::InvalidUtf8 {
+        path: self.source_name.clone(),
+        offset: self.bytes_read + e.utf8_error().valid_up_to(),
+      })?;
+    if self.bytes_read == 0 && text.starts_with('\u{feff}') {
+      text.remove(0);
+    }
+    self.bytes_read += consumed;
+    return Ok(Some(Line { text, complete }));
+  }
+
+  /// Puts text back in front of the input, to be read again.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The text to read again
+  /// * `complete` - Whether the text ends its line
+  fn unread(&mut self, text: &str, complete: bool) {
+    let mut bytes = text.as_bytes().to_vec();
+    if complete {
+      bytes.push(b'\n');
+    }
+    self.bytes_read = self.bytes_read.saturating_sub(bytes.len());
+    self.buffer.splice(0..0, bytes);
+  }
+
+  /// Reads the next chunk.
+  ///
+  /// # Returns
+  ///
+  /// An `InputResult<Option<InputChunk>>` with the next chunk, or `None`
+  /// at the end of input.
+  pub async fn next_chunk(&mut self) -> InputResult<Option<InputChunk>> {
+    let _timer = timing::start(Phase::Input);
+    let mut text = String::new();
+    let mut char_count = 0;
+
+    while let Some(Line {
+      text: line,
+      complete,
+    }) = self.next_line().await?
+    {
+      let line_chars = self.measure(&line);
+
+      if !complete {
+        if line.trim().is_empty() {
+          continue;
+        }
+        if char_count > 0 {
+          self.unread(&line, false);
+          return Ok(Some(InputChunk {
+            text: text.trim_end().to_string(),
+            ends_paragraph: false,
+            ends_line: true,
+          }));
+        }
+        let measure = |text: &str| self.measure(text);
+        let split = sentence_split(&line, self.target_chars, measure)
+          .or_else(|| word_split(&line, self.target_chars, measure))
+          .unwrap_or_else(|| char_split(&line, self.target_chars));
+        self.unread(&line[split..], false);
+        return Ok(Some(InputChunk {
+          text: line[..split].trim_end().to_string(),
+          ends_paragraph: false,
+          ends_line: false,
+        }));
+      }
+
+      if line.trim().is_empty() {
+        if char_count == 0 {
+          continue;
+        }
+        if self.target_chars > 0 && char_count >= self.target_chars {
+          return Ok(Some(InputChunk {
+            text: text.trim_end().to_string(),
+            ends_paragraph: true,
+            ends_line: true,
+          }));
+        }
+      } else if self.target_chars > 0
+        && char_count == 0
+        && line_chars > self.target_chars * 2
+        && let Some(split) =
+          sentence_split(&line, self.target_chars, |text| self.measure(text))
+            .or_else(|| {
+              word_split(&line, self.target_chars, |text| self.measure(text))
+            })
+      {
+        self.unread(&line[split..], true);
+        return Ok(Some(InputChunk {
+          text: line[..split].trim_end().to_string(),
+          ends_paragraph: false,
+          ends_line: false,
+        }));
+      } else if self.target_chars > 0
+        && char_count > 0
+        && char_count + line_chars > self.target_chars * 2
+      {
+        self.unread(&line, true);
+        return Ok(Some(InputChunk {
+          text: text.trim_end().to_string(),
+          ends_paragraph: false,
+          ends_line: true,
+        }));
+      }
+
+      text.push_str(&line);
+      text.push('\n');
+      char_count += line_chars + 1;
+    }
+
+    if text.trim().is_empty() {
+      return Ok(None);
+    }
+
+    return Ok(Some(InputChunk {
+      text: text.trim_end().to_string(),
+      ends_paragraph: true,
+      ends_line: true,
+    }));
+  }
+}
+
+/// Finds where to cut a line that is too long to buffer.
+///
+/// # Arguments
+///
+/// * `bytes` - The start of the line, as long as may be buffered
+///
+/// # Returns
+///
+/// The byte offset after the last space, or of the last character boundary
+/// if there is no space.
+fn cut_point(bytes: &[u8]) -> usize {
+  if let Some(space) = bytes
+    .iter()
+    .rposition(|b| b.is_ascii_whitespace())
+    .filter(|space| *space > 0)
+  {
+    return space + 1;
+  }
+  return (1..bytes.len())
+    .rev()
+    .find(|index| bytes[*index] & 0xC0 != 0x80)
+    .unwrap_or(bytes.len());
+}
+
+/// Finds where to split a line that is too long for one chunk.
+///
+/// # Arguments
+///
+/// * `line` - The line to split
+/// * `target_chars` - Target chunk size
+/// * `measure` - Measures text in the unit of the target size
+///
+/// # Returns
+///
+/// The byte offset of the last sentence start within the target size, or
+/// of the first sentence start if the first sentence is longer; `None` if
+/// the line is a single sentence.
+fn sentence_split(
+  line: &str,
+  target_chars: usize,
+  measure: impl Fn(&str) -> usize,
+) -> Option<usize> {
+  return split_at_starts(
+    line,
+    &sentences::sentence_starts(line),
+    target_chars,
+    measure,
+  );
+}
+
+/// Finds where to split a line without sentence boundaries between words.
+///
+/// # Arguments
+///
+/// * `line` - The line to split
+/// * `target_chars` - Target chunk size
+/// * `measure` - Measures text in the unit of the target size
+///
+/// # Returns
+///
+/// The byte offset of the last word start within the target size, or of
+/// the first word start if the first word is longer; `None` if the line is
+/// a single word.
+fn word_split(
+  line: &str,
+  target_chars: usize,
+  measure: impl Fn(&str) -> usize,
+) -> Option<usize> {
+  let mut starts = vec![0];
+  let mut previous = None;
+  for (index, character) in line.char_indices() {
+    if !character.is_whitespace() && previous.is_some_and(char::is_whitespace) {
+      starts.push(index);
+    }
+    previous = Some(character);
+  }
+  return split_at_starts(line, &starts, target_chars, measure);
+}
+
+/// Finds where to split a line without spaces.
+///
+/// # Arguments
+///
+/// * `line` - The line to split
+/// * `target_chars` - Target chunk size in characters
+///
+/// # Returns
+///
+/// The byte offset after `target_chars` characters, or the length of the
+/// line if it is shorter.
+fn char_split(line: &str, target_chars: usize) -> usize {
+  return line
+    .char_indices()
+    .nth(target_chars.max(1))
+    .map_or(line.len(), |(index, _)| index);
+}
+
+/// Chooses the last of the given starts that keeps the first part within
+/// the target size.
+///
+/// # Arguments
+///
+/// * `line` - The line to split
+/// * `starts` - Byte offsets where the line may be split, beginning with 0
+/// * `target_chars` - Target chunk size
+/// * `measure` - Measures text in the unit of the target size
+///
+/// # Returns
+///
+/// The byte offset to split at, the first start after 0 if even the first
+/// part is longer than the target, or `None` if there is no start after 0.
+fn split_at_starts(
+  line: &str,
+  starts: &[usize],
+  target_chars: usize,
+  measure: impl Fn(&str) -> usize,
+) -> Option<usize> {
+  let mut split = None;
+  let mut chars = 0;
+  let mut previous = 0;
+  for &start in starts.iter().skip(1) {
+    chars += measure(&line[previous..start]);
+    previous = start;
+    if split.is_some() && chars > target_chars {
+      break;
+    }
+    split = Some(start);
+  }
+  return split;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  async fn read_all(text: &str, target_chars: usize) -> Vec<InputChunk> {
+    let mut reader = ChunkReader::from_text(text.to_string(), target_chars);
+    let mut chunks = Vec::new();
+    while let Some(chunk) = reader.next_chunk().await.unwrap() {
+      chunks.push(chunk);
+    }
+    return chunks;
+  }
+
+  #[tokio::test]
+  async fn streams_a_single_line_without_buffering_it_whole() {
+    let text = "word ".repeat(2000);
+    let mut reader = ChunkReader::from_text(text.clone(), 50);
+
+    let mut words = Vec::new();
+    while let Some(chunk) = reader.next_chunk().await.unwrap() {
+      assert!(reader.buffer.len() <= 50 * MAX_LINE_BYTES_PER_UNIT + 8192);
+      assert!(chunk.text.chars().count() <= 50 * 2, "{}", chunk.text);
+      words.extend(chunk.text.split_whitespace().map(String::from));
+    }
+    assert_eq!(words.len(), 2000);
+  }
+
+  #[tokio::test]
+  async fn cuts_unbroken_text_at_character_boundaries() {
+    let text = "ä".repeat(1000);
+    let chunks = read_all(&text, 10).await;
+
+    assert!(chunks.len() > 1);
+    assert_eq!(
+      chunks
+        .iter()
+        .map(|chunk| chunk.text.as_str())
+        .collect::<String>(),
+      text
+    );
+    assert!(
+      chunks[..chunks.len() - 1]
+        .iter()
+        .all(|chunk| !chunk.ends_line)
+    );
+  }
+
+  #[tokio::test]
+  async fn closes_chunks_at_paragraph_boundaries() {
+    let chunks = read_all("one two\r\nthree\r\n\r\nfour five\n", 10).await;
+
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].text, "one two\nthree");
+    assert!(chunks[0].ends_paragraph);
+    assert_eq!(chunks[1].text, "four five");
+  }
+
+  #[tokio::test]
+  async fn removes_a_byte_order_mark() {
+    let chunks = read_all("\u{feff}hello", 0).await;
+
+    assert_eq!(chunks[0].text, "hello");
+  }
+
+  #[test]
+  fn cuts_after_the_last_space() {
+    assert_eq!(cut_point(b"one two thr"), 8);
+    assert_eq!(cut_point("abcä".as_bytes()), 3);
+  }
+}
@@ -0,0 +1,137 @@
+//! Transparent decompression of input files.
+//!
+//! Archived transcription dumps are usually compressed. Gzip and zstd files
+//! are recognized by their magic bytes and decompressed on the fly, so they
+//! stream through the chunked reader like any other file.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, BufReader, ReadBuf};
+
+use crate::vlog;
+
+/// A boxed asynchronous byte stream.
+pub type BoxedReader = Box<dyn AsyncRead + Unpin + Send>;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression format of an input file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+  /// Plain, uncompressed content
+  None,
+  /// Gzip (`.gz`)
+  Gzip,
+  /// Zstandard (`.zst`)
+  Zstd,
+}
+
+impl Compression {
+  /// Detects the compression format from the leading bytes of a file.
+  ///
+  /// # Arguments
+  ///
+  /// * `magic` - The leading bytes of the file
+  ///
+  /// # Returns
+  ///
+  /// The detected `Compression`.
+  pub fn detect(magic: &[u8]) -> Self {
+    if magic.starts_with(&GZIP_MAGIC) {
+      return Compression::Gzip;
+    }
+    if magic.starts_with(&ZSTD_MAGIC) {
+      return Compression::Zstd;
+    }
+    return Compression::None;
+  }
+}
+
+/// Opens a file for reading, decompressing it if it is compressed.
+///
+/// Decompressed content is capped at `max_size` bytes, so a small archive
+/// cannot expand past the input size limit.
+///
+/// # Arguments
+///
+/// * `file_path` - Path to the file
+/// * `max_size` - Maximum decompressed size in bytes (0 for no limit)
+///
+/// # Returns
+///
+/// An `io::Result<BoxedReader>` yielding the decompressed content.
+pub async fn open_file(
+  file_path: &str,
+  max_size: u64,
+) -> io::Result<BoxedReader> {
+  let mut file = tokio::fs::File::open(file_path).await?;
+
+  let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+  (&mut file)
+    .take(ZSTD_MAGIC.len() as u64)
+    .read_to_end(&mut magic)
+    .await?;
+  file.rewind().await?;
+
+  let compression = Compression::detect(&magic);
+  let reader: BoxedReader = match compression {
+    Compression::None => return Ok(Box::new(file)),
+    Compression::Gzip => {
+      let mut decoder = GzipDecoder::new(BufReader::new(file));
+      decoder.multiple_members(true);
+      Box::new(decoder)
+    }
+    Compression::Zstd => Box::new(ZstdDecoder::new(BufReader::new(file))),
+  };
+
+  vlog!("Decompressing '{}' ({:?})", file_path, compression);
+  return Ok(Box::new(LimitedReader::new(reader, max_size)));
+}
+
+/// Fails the stream once more than the limit has been read.
+struct LimitedReader {
+  inner: BoxedReader,
+  limit: u64,
+  read: u64,
+}
+
+impl LimitedReader {
+  fn new(inner: BoxedReader, limit: u64) -> Self {
+    return LimitedReader {
+      inner,
+      limit,
+      read: 0,
+    };
+  }
+}
+
+impl AsyncRead for LimitedReader {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+
+    // The error is raised on the read after the limit was crossed, since a
+    // read that fails must not have filled the buffer.
+    if this.limit > 0 && this.read > this.limit {
+      return Poll::Ready(Err(io::Error::new(
+        io::ErrorKind::FileTooLarge,
+        format!(
+          "decompressed content exceeds the limit of {} bytes (see [input] max_size)",
+          this.limit
+        ),
+      )));
+    }
+
+    let before = buf.filled().len();
+    ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+    this.read += (buf.filled().len() - before) as u64;
+    return Poll::Ready(Ok(()));
+  }
+}
@@ -0,0 +1,181 @@
+//! Character encoding detection and transcoding.
+//!
+//! Transcripts produced by Windows tooling are often Windows-1252 or UTF-16
+//! rather than UTF-8. The encoding is detected from the leading bytes of a
+//! file (byte order mark, UTF-16 zero-byte pattern, UTF-8 validity, then a
+//! statistical guess) unless overridden, and the content is transcoded to
+//! UTF-8 before it is refined.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Decoder, Encoding, UTF_8, UTF_16BE, UTF_16LE};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::input::errors::{InputError, InputResult};
+
+const READ_BUFFER_SIZE: usize = 8192;
+
+/// Looks up an encoding by its WHATWG label (e.g. "utf-16le", "latin1").
+///
+/// # Arguments
+///
+/// * `label` - The encoding label
+///
+/// # Returns
+///
+/// An `InputResult<&'static Encoding>` containing the encoding or an error.
+pub fn from_label(label: &str) -> InputResult<&'static Encoding> {
+  return Encoding::for_label(label.trim().as_bytes())
+    .ok_or_else(|| InputError::UnknownEncoding(label.to_string()));
+}
+
+/// Detects the encoding of a file from its leading bytes.
+///
+/// # Arguments
+///
+/// * `prefix` - The leading bytes of the file
+///
+/// # Returns
+///
+/// The detected `&'static Encoding`.
+pub fn detect(prefix: &[u8]) -> &'static Encoding {
+  if let Some((encoding, _)) = Encoding::for_bom(prefix) {
+    return encoding;
+  }
+
+  if let Some(encoding) = detect_utf16(prefix) {
+    return encoding;
+  }
+
+  // A multi-byte character cut off at the end of the sample is still UTF-8.
+  match std::str::from_utf8(prefix) {
+    Ok(_) => return UTF_8,
+    Err(e) if e.error_len().is_none() => return UTF_8,
+    Err(_) => {}
+  }
+
+  let mut detector = EncodingDetector::new();
+  detector.feed(prefix, false);
+  return detector.guess(None, true);
+}
+
+/// Detects BOM-less UTF-16 from the position of zero bytes.
+///
+/// Mostly-ASCII UTF-16 text has a zero in every other byte, in the high byte
+/// of each code unit.
+fn detect_utf16(prefix: &[u8]) -> Option<&'static Encoding> {
+  let units = prefix.len() / 2;
+  if units < 2 {
+    return None;
+  }
+
+  let even_zeros = prefix.iter().step_by(2).filter(|b| **b == 0).count();
+  let odd_zeros = prefix
+    .iter()
+    .skip(1)
+    .step_by(2)
+    .filter(|b| **b == 0)
+    .count();
+
+  if odd_zeros * 2 > units && even_zeros * 10 < units {
+    return Some(UTF_16LE);
+  }
+  if even_zeros * 2 > units && odd_zeros * 10 < units {
+    return Some(UTF_16BE);
+  }
+  return None;
+}
+
+/// Decodes bytes to a UTF-8 string, removing any byte order mark.
+///
+/// Malformed sequences are replaced with U+FFFD.
+///
+/// # Arguments
+///
+/// * `bytes` - The raw bytes
+/// * `encoding` - The encoding of the bytes
+///
+/// # Returns
+///
+/// The decoded text.
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> String {
+  let (text, _) = encoding.decode_with_bom_removal(bytes);
+  return text.into_owned();
+}
+
+/// An `AsyncRead` adapter transcoding a byte stream to UTF-8.
+pub struct DecodingReader<R> {
+  inner: R,
+  decoder: Decoder,
+  output: Vec<u8>,
+  output_position: usize,
+  finished: bool,
+}
+
+impl<R: AsyncRead + Unpin> DecodingReader<R> {
+  /// Creates a reader decoding `inner` from the given encoding.
+  ///
+  /// # Arguments
+  ///
+  /// * `inner` - The underlying byte stream
+  /// * `encoding` - The encoding of the byte stream
+  ///
+  /// # Returns
+  ///
+  /// A new `DecodingReader` instance.
+  pub fn new(inner: R, encoding: &'static Encoding) -> Self {
+    return DecodingReader {
+      inner,
+      decoder: encoding.new_decoder_with_bom_removal(),
+      output: Vec::new(),
+      output_position: 0,
+      finished: false,
+    };
+  }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DecodingReader<R> {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<io::Result<()>> {
+    let this = self.get_mut();
+
+    loop {
+      if this.output_position < this.output.len() {
+        let pending = &this.output[this.output_position..];
+        let length = pending.len().min(buf.remaining());
+        buf.put_slice(&pending[..length]);
+        this.output_position += length;
+        return Poll::Ready(Ok(()));
+      }
+
+      if this.finished {
+        return Poll::Ready(Ok(()));
+      }
+
+      let mut raw = [0u8; READ_BUFFER_SIZE];
+      let mut raw_buf = ReadBuf::new(&mut raw);
+      ready!(Pin::new(&mut this.inner).poll_read(cx, &mut raw_buf))?;
+      let input = raw_buf.filled();
+      let last = input.is_empty();
+
+      let capacity = this
+        .decoder
+        .max_utf8_buffer_length(input.len())
+        .unwrap_or(input.len() * 3 + 16);
+      this.output.clear();
+      this.output.resize(capacity, 0);
+      this.output_position = 0;
+
+      let (_, _, written, _) =
+        this.decoder.decode_to_utf8(input, &mut this.output, last);
+      this.output.truncate(written);
+      this.finished = last;
+    }
+  }
+}
@@ -0,0 +1,82 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Input reading errors.
+///
+/// Represents errors that can occur when reading input from various sources.
+#[derive(Error, Debug)]
+pub enum InputError {
+  #[error(
+    "{}",
+    t!("error-input-file-read", path = .path.as_str(), error = .error.as_str())
+  )]
+  FileReadError { path: String, error: String },
+
+  #[error("{}", t!("error-input-stdin-read", error = .0.as_str()))]
+  StdinRead(String),
+
+  #[error("{}", t!("error-input-empty"))]
+  EmptyInput,
+
+  #[error("{}", t!("error-input-no-input"))]
+  NoInputProvided,
+
+  #[error(
+    "{}",
+    t!(
+      "error-input-too-large",
+      path = .path.as_str(),
+      size = *.size,
+      limit = *.limit
+    )
+  )]
+  TooLarge { path: String, size: u64, limit: u64 },
+
+  #[error("{}", t!("error-input-binary", path = .0.as_str()))]
+  BinaryInput(String),
+
+  #[error(
+    "{}",
+    t!("error-input-invalid-utf8", path = .path.as_str(), offset = *.offset)
+  )]
+  InvalidUtf8 { path: String, offset: usize },
+
+  #[error(
+    "{}",
+    t!(
+      "error-input-malformed-transcript",
+      format = .format.as_str(),
+      error = .error.as_str()
+    )
+  )]
+  MalformedTranscript { format: String, error: String },
+
+  #[error(
+    "{}",
+    t!("error-input-invalid-transcript", problems = .0.join("\n  - "))
+  )]
+  InvalidTranscript(Vec<String>),
+
+  #[error("{}", t!("error-input-unknown-encoding", encoding = .0.as_str()))]
+  UnknownEncoding(String),
+
+  #[error(
+    "{}",
+    t!("error-input-invalid-range", range = .0.as_str(), error = .1.as_str())
+  )]
+  InvalidRange(String, String),
+
+  #[error(
+    "{}",
+    t!(
+      "error-input-range-not-in-input",
+      range = .0.as_str(),
+      error = .1.as_str()
+    )
+  )]
+  RangeNotInInput(String, String),
+}
+
+/// Result type for input reading operations.
+pub type InputResult<T> = Result<T, InputError>;
@@ -0,0 +1,286 @@
+//! Input reading module for reading input from various sources.
+//!
+//! This module provides utilities for reading input from various sources
+//! including input and files, either whole or as a stream of chunks, and
+//! splits live streams into units refined one at a time (see [`stream`]).
+//! Transcriptions are parsed from Whisper JSON, whisper.cpp JSON, SRT,
+//! WebVTT or plain text, detected from their content (see
+//! [`transcript_format`]). A slice of the input can be selected for
+//! refinement and spliced back afterwards (see [`range`]).
+
+pub mod chunks;
+pub mod compression;
+pub mod encoding;
+pub mod errors;
+pub mod range;
+pub mod sentences;
+pub mod stream;
+pub mod transcript_format;
+pub mod transcription;
+pub mod validation;
+
+use encoding_rs::{Encoding, UTF_8};
+use tokio::io::AsyncReadExt;
+
+use crate::files::operations;
+use crate::input::chunks::ChunkReader;
+use crate::input::compression::BoxedReader;
+use crate::input::errors::{InputError, InputResult};
+use crate::timing::{self, Phase};
+use crate::vlog;
+
+/// Options controlling how input is read.
+#[derive(Debug, Clone, Default)]
+pub struct InputOptions {
+  /// Maximum input size in bytes (0 for no limit)
+  pub max_size: u64,
+  /// Encoding label of input files, detected when not set
+  pub encoding: Option<String>,
+}
+
+/// Input source enumeration.
+#[derive(Debug, Clone)]
+enum InputSource {
+  /// Inline text input.
+  Input(String),
+  /// Input from a file.
+  File(String),
+}
+
+impl InputSource {
+  /// Resolves the input source based on the provided input and file path.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input
+  /// * `file_path` - The file path for input text
+  ///
+  /// # Returns
+  ///
+  /// Returns the input source, or an error if no input is provided.
+  fn resolve_input_source(
+    input: Option<String>,
+    file_path: Option<String>,
+  ) -> InputResult<InputSource> {
+    if let Some(input) = input {
+      return Ok(InputSource::Input(input));
+    }
+
+    if let Some(file_path) = file_path {
+      return Ok(InputSource::File(file_path));
+    }
+
+    return Err(InputError::NoInputProvided);
+  }
+
+  /// Checks the size and content type of the input source and determines
+  /// its encoding.
+  ///
+  /// For files only the metadata and the leading bytes are read, so oversized
+  /// or binary files are rejected before any further work is done. Inline
+  /// input is always UTF-8.
+  ///
+  /// # Arguments
+  ///
+  /// * `options` - The input reading options
+  ///
+  /// # Returns
+  ///
+  /// Returns the encoding of the input, or an error if it is not acceptable.
+  async fn validate(
+    &self,
+    options: &InputOptions,
+  ) -> InputResult<&'static Encoding> {
+    match self {
+      InputSource::Input(input) => {
+        validation::check_size("input", input.len() as u64, options.max_size)?;
+        validation::check_not_binary("input", input.as_bytes())?;
+        return Ok(UTF_8);
+      }
+      InputSource::File(file) => {
+        let size = operations::file_size(file).await.map_err(|e| {
+          InputError::FileReadError {
+            path: file.to_string(),
+            error: e.to_string(),
+          }
+        })?;
+        validation::check_size(file, size, options.max_size)?;
+
+        let mut prefix = Vec::with_capacity(validation::SNIFF_LENGTH);
+        InputSource::open_file(file, options)
+          .await?
+          .take(validation::SNIFF_LENGTH as u64)
+          .read_to_end(&mut prefix)
+          .await
+          .map_err(|e| InputSource::read_error(file, e))?;
+
+        let encoding = match &options.encoding {
+          Some(label) => encoding::from_label(label)?,
+          None => encoding::detect(&prefix),
+        };
+        vlog!("Reading '{}' as {}", file, encoding.name());
+
+        // UTF-16 text legitimately contains zero bytes.
+        if encoding.is_ascii_compatible() {
+          validation::check_not_binary(file, &prefix)?;
+        }
+        if encoding == UTF_8 {
+          validation::check_utf8_prefix(file, &prefix)?;
+        }
+        return Ok(encoding);
+      }
+    };
+  }
+
+  /// Reads input from the resolved input source.
+  ///
+  /// # Arguments
+  ///
+  /// * `options` - The input reading options
+  ///
+  /// # Returns
+  ///
+  /// Returns the input text, or an error if input reading fails.
+  pub async fn read_from_input_source(
+    &self,
+    options: &InputOptions,
+  ) -> InputResult<String> {
+    let encoding = self.validate(options).await?;
+    match self {
+      InputSource::Input(input) => {
+        if input.trim().is_empty() {
+          return Err(InputError::EmptyInput);
+        }
+        return Ok(input.clone());
+      }
+      InputSource::File(file) => {
+        let mut bytes = Vec::new();
+        InputSource::open_file(file, options)
+          .await?
+          .read_to_end(&mut bytes)
+          .await
+          .map_err(|e| InputSource::read_error(file, e))?;
+        let content = if encoding == UTF_8 {
+          let content = validation::decode_utf8(file, bytes)?;
+          content
+            .strip_prefix('\u{feff}')
+            .map(str::to_string)
+            .unwrap_or(content)
+        } else {
+          encoding::decode(&bytes, encoding)
+        };
+        if content.trim().is_empty() {
+          return Err(InputError::EmptyInput);
+        }
+        return Ok(content);
+      }
+    };
+  }
+}
+
+impl InputSource {
+  /// Opens the resolved input source as a stream of chunks.
+  ///
+  /// # Arguments
+  ///
+  /// * `target_chars` - Target chunk size in characters (0 for one chunk)
+  /// * `options` - The input reading options
+  ///
+  /// # Returns
+  ///
+  /// Returns the chunk reader, or an error if the source cannot be opened.
+  async fn open_chunks(
+    &self,
+    target_chars: usize,
+    options: &InputOptions,
+  ) -> InputResult<ChunkReader> {
+    let encoding = self.validate(options).await?;
+    return match self {
+      InputSource::Input(input) => {
+        Ok(ChunkReader::from_text(input.clone(), target_chars))
+      }
+      InputSource::File(file) => {
+        let reader = InputSource::open_file(file, options).await?;
+        Ok(ChunkReader::from_file(reader, file, target_chars, encoding))
+      }
+    };
+  }
+
+  /// Opens an input file, decompressing gzip and zstd files on the fly.
+  ///
+  /// # Arguments
+  ///
+  /// * `file` - Path to the input file
+  /// * `options` - The input reading options
+  ///
+  /// # Returns
+  ///
+  /// Returns the file stream, or an error if the file cannot be opened.
+  async fn open_file(
+    file: &str,
+    options: &InputOptions,
+  ) -> InputResult<BoxedReader> {
+    return compression::open_file(file, options.max_size)
+      .await
+      .map_err(|e| InputSource::read_error(file, e));
+  }
+
+  fn read_error(file: &str, error: std::io::Error) -> InputError {
+    return InputError::FileReadError {
+      path: file.to_string(),
+      error: error.to_string(),
+    };
+  }
+}
+
+pub struct InputReader {}
+
+impl InputReader {
+  /// Reads input from the provided input and file path.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input
+  /// * `file_path` - The file path for input text
+  /// * `options` - The input reading options
+  ///
+  /// # Returns
+  ///
+  /// Returns the input text, or an error if input reading fails.
+  pub async fn read_input(
+    input: Option<String>,
+    file_path: Option<String>,
+    options: &InputOptions,
+  ) -> InputResult<String> {
+    let _timer = timing::start(Phase::Input);
+    let input_source = InputSource::resolve_input_source(input, file_path)?;
+    let input_text = input_source.read_from_input_source(options).await?;
+    return Ok(input_text);
+  }
+
+  /// Opens the provided input or file path as a stream of chunks.
+  ///
+  /// Files are read incrementally, so memory use stays bounded by the
+  /// chunk size regardless of the file size.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The inline text input
+  /// * `file_path` - The file path for input text
+  /// * `target_chars` - Target chunk size in characters (0 for one chunk)
+  /// * `options` - The input reading options
+  ///
+  /// # Returns
+  ///
+  /// Returns the chunk reader, or an error if the input cannot be opened.
+  pub async fn open_chunks(
+    input: Option<String>,
+    file_path: Option<String>,
+    target_chars: usize,
+    options: &InputOptions,
+  ) -> InputResult<ChunkReader> {
+    let _timer = timing::start(Phase::Input);
+    let input_source = InputSource::resolve_input_source(input, file_path)?;
+    return input_source.open_chunks(target_chars, options).await;
+  }
+}
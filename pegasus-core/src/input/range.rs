@@ -0,0 +1,232 @@
+//! Selected ranges of an input, for `--lines`, `--bytes` and
+//! `--regex-range`.
+//!
+//! Only the selected slice of a long document is refined; the text around
+//! it is kept byte for byte and the refined slice spliced back in between
+//! (see [`Splice`]), so one problematic section can be iterated on without
+//! paying for, or disturbing, the rest.
+
+use std::fmt;
+
+use regex::Regex;
+
+use crate::input::errors::{InputError, InputResult};
+
+/// A part of the input selected for refinement.
+#[derive(Debug, Clone)]
+pub enum InputRange {
+  /// Lines `start` to `end`, counted from 1 and inclusive; `end` defaults
+  /// to the last line
+  Lines { start: usize, end: Option<usize> },
+  /// Bytes `start` up to `end`, counted from 0 and exclusive; `end`
+  /// defaults to the end of the input
+  Bytes { start: usize, end: Option<usize> },
+  /// The first match of a regular expression
+  Regex(Regex),
+}
+
+impl InputRange {
+  /// Parses a line range like `40:120`, `40:`, `:120` or `40`.
+  ///
+  /// # Arguments
+  ///
+  /// * `spec` - The range, with lines counted from 1
+  ///
+  /// # Returns
+  ///
+  /// The range, or an error if it is malformed or empty.
+  pub fn lines(spec: &str) -> InputResult<Self> {
+    let (start, end) = parse_bounds(spec)?;
+    let start = start.unwrap_or(1);
+    if start == 0 {
+      return Err(InputError::InvalidRange(
+        spec.to_string(),
+        String::from("lines are counted from 1"),
+      ));
+    }
+    return Ok(InputRange::Lines { start, end });
+  }
+
+  /// Parses a byte range like `100:2000`, `100:` or `:2000`.
+  ///
+  /// # Arguments
+  ///
+  /// * `spec` - The range, with bytes counted from 0 and the end excluded
+  ///
+  /// # Returns
+  ///
+  /// The range, or an error if it is malformed or empty.
+  pub fn bytes(spec: &str) -> InputResult<Self> {
+    let (start, end) = parse_bounds(spec)?;
+    return Ok(InputRange::Bytes {
+      start: start.unwrap_or(0),
+      end,
+    });
+  }
+
+  /// Compiles a regular expression whose first match is the range.
+  ///
+  /// # Arguments
+  ///
+  /// * `pattern` - The pattern; `(?s)` lets `.` match line breaks
+  ///
+  /// # Returns
+  ///
+  /// The range, or an error if the pattern is invalid.
+  pub fn regex(pattern: &str) -> InputResult<Self> {
+    return Regex::new(pattern).map(InputRange::Regex).map_err(|e| {
+      InputError::InvalidRange(pattern.to_string(), e.to_string())
+    });
+  }
+
+  /// Splits a text around the range.
+  ///
+  /// A line range ending past the last line ends with the text.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The whole input
+  ///
+  /// # Returns
+  ///
+  /// The text before, in and after the range, or an error if the range is
+  /// not in the text.
+  pub fn split<'a>(&self, text: &'a str) -> InputResult<Splice<'a>> {
+    let (start, end) = match self {
+      InputRange::Lines { start, end } => {
+        let line_starts: Vec<usize> = std::iter::once(0)
+          .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+          .filter(|offset| *offset < text.len())
+          .collect();
+        let Some(&from) = line_starts.get(start - 1) else {
+          return Err(self.not_in_input(format!(
+            "the input has {} lines",
+            line_starts.len()
+          )));
+        };
+        let to = end
+          .and_then(|end| line_starts.get(end))
+          .copied()
+          .unwrap_or(text.len());
+        (from, to)
+      }
+      InputRange::Bytes { start, end } => {
+        let end = end.unwrap_or(text.len());
+        if *start > end || end > text.len() {
+          return Err(
+            self.not_in_input(format!("the input has {} bytes", text.len())),
+          );
+        }
+        if !text.is_char_boundary(*start) || !text.is_char_boundary(end) {
+          return Err(
+            self.not_in_input(String::from("it splits a UTF-8 character")),
+          );
+        }
+        (*start, end)
+      }
+      InputRange::Regex(regex) => match regex.find(text) {
+        Some(found) => (found.start(), found.end()),
+        None => {
+          return Err(self.not_in_input(String::from("it does not match")));
+        }
+      },
+    };
+    return Ok(Splice {
+      before: &text[..start],
+      slice: &text[start..end],
+      after: &text[end..],
+    });
+  }
+
+  /// Creates the error for a range that is not in the input.
+  fn not_in_input(&self, reason: String) -> InputError {
+    return InputError::RangeNotInInput(self.to_string(), reason);
+  }
+}
+
+impl fmt::Display for InputRange {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (unit, start, end) = match self {
+      InputRange::Lines { start, end } => ("lines", start, end),
+      InputRange::Bytes { start, end } => ("bytes", start, end),
+      InputRange::Regex(regex) => return write!(f, "/{}/", regex.as_str()),
+    };
+    return match end {
+      Some(end) => write!(f, "{} {}:{}", unit, start, end),
+      None => write!(f, "{} {}:", unit, start),
+    };
+  }
+}
+
+/// An input split around the range selected for refinement.
+#[derive(Debug, Clone, Copy)]
+pub struct Splice<'a> {
+  /// The text before the range
+  pub before: &'a str,
+  /// The text of the range
+  pub slice: &'a str,
+  /// The text after the range
+  pub after: &'a str,
+}
+
+impl Splice<'_> {
+  /// Puts the refined slice back between the text around it.
+  ///
+  /// The whitespace the slice starts and ends with is kept, since the
+  /// refinement of a slice has none, so the slice stays separated from its
+  /// surroundings as before. The final line ending of the input is left
+  /// out, since the output sinks end results with their own.
+  ///
+  /// # Arguments
+  ///
+  /// * `refined` - The refined slice
+  ///
+  /// # Returns
+  ///
+  /// The whole text with the slice replaced.
+  pub fn join(&self, refined: &str) -> String {
+    let content = self.slice.trim_start();
+    let leading = &self.slice[..self.slice.len() - content.len()];
+    let trailing = &content[content.trim_end().len()..];
+    let joined = format!(
+      "{}{}{}{}{}",
+      self.before,
+      leading,
+      refined.trim(),
+      trailing,
+      self.after
+    );
+    let without_line_ending = joined
+      .strip_suffix('\n')
+      .map(|text| text.strip_suffix('\r').unwrap_or(text));
+    return without_line_ending.unwrap_or(&joined).to_string();
+  }
+}
+
+/// Parses the bounds of a `start:end` range, either of which may be left
+/// out.
+fn parse_bounds(spec: &str) -> InputResult<(Option<usize>, Option<usize>)> {
+  let invalid = |reason: &str| {
+    return InputError::InvalidRange(spec.to_string(), reason.to_string());
+  };
+  let (start, end) = match spec.split_once(':') {
+    Some((start, end)) => (start.trim(), end.trim()),
+    None => (spec.trim(), spec.trim()),
+  };
+  let parse = |bound: &str| -> InputResult<Option<usize>> {
+    if bound.is_empty() {
+      return Ok(None);
+    }
+    return bound
+      .parse()
+      .map(Some)
+      .map_err(|_| invalid("use START:END with whole numbers"));
+  };
+  let (start, end) = (parse(start)?, parse(end)?);
+  if let (Some(start), Some(end)) = (start, end)
+    && start > end
+  {
+    return Err(invalid("the start is after the end"));
+  }
+  return Ok((start, end));
+}
@@ -0,0 +1,185 @@
+//! Sentence segmentation.
+//!
+//! Splits text into sentences for chunking, context carryover, readability
+//! metrics and review. A sentence ends at a run of terminal punctuation
+//! (`.`, `!`, `?`, `…` and their counterparts in other scripts), optionally
+//! followed by closing quotes or brackets, and then whitespace. Periods
+//! after common abbreviations and initials, and periods followed by a
+//! lowercase word, do not end a sentence. Full-width terminators used in
+//! Chinese and Japanese end a sentence without following whitespace.
+
+/// Abbreviations whose period does not end a sentence, lowercase and
+/// without the final period.
+const ABBREVIATIONS: &[&str] = &[
+  "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "mt", "ave", "vs", "etc",
+  "eg", "ie", "cf", "al", "inc", "ltd", "co", "corp", "no", "nos", "vol",
+  "fig", "approx", "dept", "est", "jan", "feb", "mar", "apr", "jun", "jul",
+  "aug", "sep", "sept", "oct", "nov", "dec",
+];
+
+/// Splits text into sentences.
+///
+/// # Arguments
+///
+/// * `text` - The text to split
+///
+/// # Returns
+///
+/// The sentences in order, without surrounding whitespace.
+pub fn split(text: &str) -> Vec<&str> {
+  let mut starts = sentence_starts(text);
+  starts.push(text.len());
+  return starts
+    .windows(2)
+    .map(|range| text[range[0]..range[1]].trim())
+    .filter(|sentence| !sentence.is_empty())
+    .collect();
+}
+
+/// Finds where the sentences of a text start.
+///
+/// # Arguments
+///
+/// * `text` - The text to split
+///
+/// # Returns
+///
+/// The byte offsets of the sentence starts in order, always beginning
+/// with 0.
+pub fn sentence_starts(text: &str) -> Vec<usize> {
+  let mut starts = vec![0];
+  let chars: Vec<(usize, char)> = text.char_indices().collect();
+  let mut index = 0;
+
+  while index < chars.len() {
+    let (_, c) = chars[index];
+    if !is_terminator(c) {
+      index += 1;
+      continue;
+    }
+
+    let terminator = index;
+    while index < chars.len() && is_terminator(chars[index].1) {
+      index += 1;
+    }
+    let single_period = c == '.' && index - terminator == 1;
+    while index < chars.len() && is_closing(chars[index].1) {
+      index += 1;
+    }
+
+    let Some(&(next_offset, next)) = chars.get(index) else {
+      break;
+    };
+    if !next.is_whitespace() {
+      if is_full_width(c) {
+        starts.push(next_offset);
+      }
+      continue;
+    }
+
+    while index < chars.len() && chars[index].1.is_whitespace() {
+      index += 1;
+    }
+    let Some(&(start, first)) = chars.get(index) else {
+      break;
+    };
+    if single_period
+      && (first.is_lowercase() || is_abbreviation(&text[..chars[terminator].0]))
+    {
+      continue;
+    }
+    starts.push(start);
+  }
+  return starts;
+}
+
+/// Checks whether the word before a period is an abbreviation or initial.
+fn is_abbreviation(before: &str) -> bool {
+  let word = before
+    .rsplit(|c: char| c.is_whitespace() || "([{\"'“‘«".contains(c))
+    .next()
+    .unwrap_or_default();
+  let mut letters = word.chars().filter(|c| c.is_alphabetic());
+  let is_initial = matches!(
+    (letters.next(), letters.next()),
+    (Some(letter), None) if letter.is_uppercase()
+  );
+  if is_initial || word.contains('.') {
+    return true;
+  }
+
+  let word = word.to_lowercase();
+  return ABBREVIATIONS.contains(&word.as_str());
+}
+
+/// Checks whether a character ends a sentence.
+fn is_terminator(c: char) -> bool {
+  return matches!(
+    c,
+    '.' | '!' | '?' | '…' | '。' | '！' | '？' | '｡' | '؟' | '۔' | '।' | '॥'
+  );
+}
+
+/// Checks whether a character is a terminator that needs no whitespace
+/// after it.
+fn is_full_width(c: char) -> bool {
+  return matches!(c, '。' | '！' | '？' | '｡');
+}
+
+/// Checks whether a character closes a quotation or bracket.
+fn is_closing(c: char) -> bool {
+  return matches!(
+    c,
+    '"' | '\'' | ')' | ']' | '}' | '”' | '’' | '»' | '」' | '』' | '）'
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn splits_after_terminal_punctuation() {
+    assert_eq!(
+      split("Is it done? Yes!  It is... Finally."),
+      ["Is it done?", "Yes!", "It is...", "Finally."]
+    );
+    assert_eq!(sentence_starts("One. Two."), [0, 5]);
+    assert!(split("  ").is_empty());
+  }
+
+  #[test]
+  fn keeps_abbreviations_initials_and_lowercase_continuations() {
+    assert_eq!(
+      split("Dr. Smith met J. R. Tolkien at 5 p.m. on Jan. 3. They talked."),
+      [
+        "Dr. Smith met J. R. Tolkien at 5 p.m. on Jan. 3.",
+        "They talked."
+      ]
+    );
+    assert_eq!(
+      split("It cost approx. ten. e.g. this."),
+      ["It cost approx. ten. e.g. this."]
+    );
+  }
+
+  #[test]
+  fn includes_closing_quotes_and_brackets() {
+    assert_eq!(
+      split("He said \"stop.\" Then he left (quickly.) Done."),
+      ["He said \"stop.\"", "Then he left (quickly.)", "Done."]
+    );
+  }
+
+  #[test]
+  fn needs_whitespace_except_after_full_width_terminators() {
+    assert_eq!(
+      split("Version 1.2 is out.Really"),
+      ["Version 1.2 is out.Really"]
+    );
+    assert_eq!(
+      split("今日は晴れ。明日は雨？はい"),
+      ["今日は晴れ。", "明日は雨？", "はい"]
+    );
+  }
+}
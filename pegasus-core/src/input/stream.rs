@@ -0,0 +1,110 @@
+//! Splitting a live input stream into refinement units.
+//!
+//! `--line-mode` refines each unit on its own as soon as it has been read,
+//! so Pegasus can sit behind a streaming transcriber in a pipeline. A unit
+//! is a single line, or a paragraph ending at a blank line.
+
+use std::fmt;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, Lines};
+
+use crate::input::errors::{InputError, InputResult};
+
+/// What makes up one refinement unit of a stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StreamUnit {
+  /// Every non-empty line
+  #[default]
+  Line,
+  /// Lines up to a blank line
+  Paragraph,
+}
+
+impl StreamUnit {
+  /// Parses a unit name.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - "line" or "paragraph"
+  ///
+  /// # Returns
+  ///
+  /// The `StreamUnit`, or `None` for an unknown name.
+  pub fn from_name(name: &str) -> Option<Self> {
+    return match name {
+      "line" => Some(StreamUnit::Line),
+      "paragraph" => Some(StreamUnit::Paragraph),
+      _ => None,
+    };
+  }
+}
+
+impl fmt::Display for StreamUnit {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      StreamUnit::Line => "line",
+      StreamUnit::Paragraph => "paragraph",
+    });
+  }
+}
+
+/// Reads refinement units from a stream as they arrive.
+pub struct UnitReader<R> {
+  lines: Lines<R>,
+  unit: StreamUnit,
+}
+
+impl<R: AsyncBufRead + Unpin> UnitReader<R> {
+  /// Creates a reader splitting a stream into units.
+  ///
+  /// # Arguments
+  ///
+  /// * `reader` - The buffered stream
+  /// * `unit` - What makes up one unit
+  ///
+  /// # Returns
+  ///
+  /// A new `UnitReader` instance.
+  pub fn new(reader: R, unit: StreamUnit) -> Self {
+    return UnitReader {
+      lines: reader.lines(),
+      unit,
+    };
+  }
+
+  /// Waits for the next unit.
+  ///
+  /// Blank lines between units are skipped. A paragraph cut off by the end
+  /// of the stream is still returned.
+  ///
+  /// # Returns
+  ///
+  /// An `InputResult<Option<String>>` containing the unit, `None` at the
+  /// end of the stream, or an error.
+  pub async fn next_unit(&mut self) -> InputResult<Option<String>> {
+    let mut paragraph: Vec<String> = Vec::new();
+    while let Some(line) = self
+      .lines
+      .next_line()
+      .await
+      .map_err(|e| InputError::StdinRead(e.to_string()))?
+    {
+      let line = line.trim_end_matches('\r');
+      if line.trim().is_empty() {
+        if paragraph.is_empty() {
+          continue;
+        }
+        break;
+      }
+      if self.unit == StreamUnit::Line {
+        return Ok(Some(line.to_string()));
+      }
+      paragraph.push(line.to_string());
+    }
+
+    if paragraph.is_empty() {
+      return Ok(None);
+    }
+    return Ok(Some(paragraph.join("\n")));
+  }
+}
@@ -0,0 +1,418 @@
+//! Detection and parsing of transcription formats.
+//!
+//! Speech-to-text toolchains write transcriptions in different shapes:
+//! OpenAI Whisper's verbose JSON, whisper.cpp's JSON (`-oj`, or `-ojf` with
+//! token probabilities), SRT and WebVTT subtitles, or plain text. Each is
+//! converted into a [`WhisperTranscription`], keeping whatever timestamps
+//! and word probabilities the format carries, so the rest of the pipeline
+//! does not need to know which one it was given.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::input::errors::{InputError, InputResult};
+use crate::input::transcription::{
+  WhisperSegment, WhisperTranscription, WhisperWord,
+};
+
+/// A transcription format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+  /// OpenAI Whisper JSON, with optional segments and word probabilities
+  WhisperJson,
+  /// whisper.cpp JSON, with optional token probabilities
+  WhisperCppJson,
+  /// SubRip subtitles
+  Srt,
+  /// WebVTT subtitles
+  Vtt,
+  /// Plain text without timing
+  Text,
+}
+
+impl TranscriptFormat {
+  /// Every format, for trying all parsers on the same content.
+  pub const ALL: [TranscriptFormat; 5] = [
+    TranscriptFormat::WhisperJson,
+    TranscriptFormat::WhisperCppJson,
+    TranscriptFormat::Srt,
+    TranscriptFormat::Vtt,
+    TranscriptFormat::Text,
+  ];
+
+  /// Parses a format from its name.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - `whisper`, `whisper-cpp`, `srt`, `vtt` or `text`
+  ///
+  /// # Returns
+  ///
+  /// The format, or `None` if the name is unknown.
+  pub fn from_name(name: &str) -> Option<Self> {
+    return match name {
+      "whisper" => Some(TranscriptFormat::WhisperJson),
+      "whisper-cpp" => Some(TranscriptFormat::WhisperCppJson),
+      "srt" => Some(TranscriptFormat::Srt),
+      "vtt" => Some(TranscriptFormat::Vtt),
+      "text" => Some(TranscriptFormat::Text),
+      _ => None,
+    };
+  }
+
+  /// Detects the format of a transcription from its content.
+  ///
+  /// JSON objects are whisper.cpp output if they hold a `transcription`
+  /// array and Whisper JSON otherwise; subtitles are recognized by their
+  /// `WEBVTT` header or SRT timing line (`start --> end`, both valid
+  /// timestamps). Anything else, including text that only looks like JSON,
+  /// is plain text.
+  ///
+  /// # Arguments
+  ///
+  /// * `content` - The transcription
+  ///
+  /// # Returns
+  ///
+  /// The detected format.
+  pub fn detect(content: &str) -> Self {
+    let content = content.trim_start_matches('\u{feff}').trim_start();
+    if content.starts_with('{')
+      && let Ok(value) = serde_json::from_str::<serde_json::Value>(content)
+    {
+      return if value["transcription"].is_array() {
+        TranscriptFormat::WhisperCppJson
+      } else {
+        TranscriptFormat::WhisperJson
+      };
+    }
+    if content.starts_with("WEBVTT") {
+      return TranscriptFormat::Vtt;
+    }
+    if content.lines().take(2).any(is_timing_line) {
+      return TranscriptFormat::Srt;
+    }
+    return TranscriptFormat::Text;
+  }
+
+  /// Parses a transcription in this format.
+  ///
+  /// Never panics, whatever the content: hostile or truncated files are
+  /// rejected with an error, which is what the fuzz targets in `fuzz/`
+  /// check.
+  ///
+  /// # Arguments
+  ///
+  /// * `content` - The transcription
+  ///
+  /// # Returns
+  ///
+  /// An `InputResult<WhisperTranscription>` containing the transcription,
+  /// or an error if it is malformed.
+  pub fn parse(self, content: &str) -> InputResult<WhisperTranscription> {
+    let content = content.trim_start_matches('\u{feff}');
+    return match self {
+      TranscriptFormat::WhisperJson => {
+        serde_json::from_str(content).map_err(|e| self.error(e.to_string()))
+      }
+      TranscriptFormat::WhisperCppJson => {
+        let output: WhisperCppOutput = serde_json::from_str(content)
+          .map_err(|e| self.error(e.to_string()))?;
+        Ok(output.into_transcription())
+      }
+      TranscriptFormat::Srt | TranscriptFormat::Vtt => {
+        let segments = parse_cues(content).map_err(|e| self.error(e))?;
+        Ok(WhisperTranscription {
+          text: None,
+          language: None,
+          duration: segments.last().and_then(|segment| segment.end),
+          segments: Some(segments),
+        })
+      }
+      TranscriptFormat::Text => Ok(WhisperTranscription {
+        text: Some(content.trim().to_string()),
+        language: None,
+        duration: None,
+        segments: None,
+      }),
+    };
+  }
+
+  /// Creates the error of a malformed transcription in this format.
+  fn error(self, message: String) -> InputError {
+    return InputError::MalformedTranscript {
+      format: self.to_string(),
+      error: message,
+    };
+  }
+}
+
+impl fmt::Display for TranscriptFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      TranscriptFormat::WhisperJson => "Whisper JSON",
+      TranscriptFormat::WhisperCppJson => "whisper.cpp JSON",
+      TranscriptFormat::Srt => "SRT",
+      TranscriptFormat::Vtt => "WebVTT",
+      TranscriptFormat::Text => "plain text",
+    });
+  }
+}
+
+/// whisper.cpp JSON output.
+#[derive(Deserialize)]
+struct WhisperCppOutput {
+  #[serde(default)]
+  result: Option<WhisperCppResult>,
+  transcription: Vec<WhisperCppSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppResult {
+  language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppSegment {
+  offsets: Option<WhisperCppOffsets>,
+  text: String,
+  #[serde(default)]
+  tokens: Vec<WhisperCppToken>,
+}
+
+/// Start and end of a segment in milliseconds.
+#[derive(Deserialize)]
+struct WhisperCppOffsets {
+  from: u64,
+  to: u64,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppToken {
+  text: String,
+  p: Option<f64>,
+}
+
+impl WhisperCppOutput {
+  fn into_transcription(self) -> WhisperTranscription {
+    let segments: Vec<WhisperSegment> = self
+      .transcription
+      .into_iter()
+      .map(|segment| WhisperSegment {
+        start: segment.offsets.as_ref().map(|o| o.from as f64 / 1000.0),
+        end: segment.offsets.as_ref().map(|o| o.to as f64 / 1000.0),
+        text: segment.text.trim().to_string(),
+        words: token_words(&segment.tokens).unwrap_or_default(),
+      })
+      .collect();
+    return WhisperTranscription {
+      text: None,
+      language: self.result.and_then(|result| result.language),
+      duration: segments.last().and_then(|segment| segment.end),
+      segments: Some(segments),
+    };
+  }
+}
+
+/// Joins whisper.cpp tokens into words, skipping special tokens like
+/// `[_BEG_]`. A token starting with whitespace starts a new word, and a
+/// word is as probable as its least probable token.
+///
+/// # Returns
+///
+/// The words, or `None` if the tokens carry no probabilities.
+fn token_words(tokens: &[WhisperCppToken]) -> Option<Vec<WhisperWord>> {
+  let mut words: Vec<WhisperWord> = Vec::new();
+  for token in tokens {
+    if token.text.starts_with("[_") || token.text.starts_with("<|") {
+      continue;
+    }
+    let probability = token.p?;
+    match words.last_mut() {
+      Some(word) if !token.text.starts_with(char::is_whitespace) => {
+        word.word.push_str(&token.text);
+        word.probability = word.probability.min(probability);
+      }
+      _ => words.push(WhisperWord {
+        word: token.text.clone(),
+        probability,
+      }),
+    }
+  }
+  return Some(words);
+}
+
+/// Parses the cues of SRT or WebVTT subtitles into segments.
+///
+/// Blocks without a timing line (the WebVTT header, `NOTE` and `STYLE`
+/// blocks) are skipped, as are cues without text.
+///
+/// # Returns
+///
+/// The segments, or a message naming the malformed timing line.
+fn parse_cues(content: &str) -> Result<Vec<WhisperSegment>, String> {
+  let content = content.replace("\r\n", "\n");
+  let mut segments = Vec::new();
+  for block in content.split("\n\n") {
+    let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+    let Some(timing) = lines.next() else {
+      continue;
+    };
+    let (start, end) = timing.split_once("-->").unwrap_or_default();
+    let end = end.split_whitespace().next().unwrap_or_default();
+    let (Some(start), Some(end)) =
+      (parse_timestamp(start), parse_timestamp(end))
+    else {
+      return Err(format!("invalid timing line '{}'", timing.trim()));
+    };
+
+    let text = lines
+      .map(|line| strip_markup(line.trim()))
+      .filter(|line| !line.is_empty())
+      .collect::<Vec<_>>()
+      .join(" ");
+    if text.is_empty() {
+      continue;
+    }
+    segments.push(WhisperSegment {
+      start: Some(start),
+      end: Some(end),
+      text,
+      words: Vec::new(),
+    });
+  }
+  return Ok(segments);
+}
+
+/// Checks whether a line is the timing line of a cue, such as
+/// `00:00:01,000 --> 00:00:02,500`, optionally followed by cue settings.
+fn is_timing_line(line: &str) -> bool {
+  let Some((start, end)) = line.split_once("-->") else {
+    return false;
+  };
+  let end = end.split_whitespace().next().unwrap_or_default();
+  return parse_timestamp(start).is_some() && parse_timestamp(end).is_some();
+}
+
+/// Parses an `HH:MM:SS,mmm` (SRT) or `[HH:]MM:SS.mmm` (WebVTT) timestamp.
+///
+/// Only digits and the decimal separator are accepted, so signs,
+/// exponents, `NaN` and `inf`, which `f64` parsing would take, are
+/// malformed.
+///
+/// # Returns
+///
+/// The timestamp in seconds, or `None` if it is malformed.
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+  let timestamp = timestamp.trim().replace(',', ".");
+  let mut seconds = 0.0;
+  let parts: Vec<&str> = timestamp.split(':').collect();
+  if !(2..=3).contains(&parts.len()) {
+    return None;
+  }
+  for part in parts {
+    if !part.starts_with(|c: char| c.is_ascii_digit())
+      || !part.chars().all(|c| c.is_ascii_digit() || c == '.')
+    {
+      return None;
+    }
+    seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+  }
+  return seconds.is_finite().then_some(seconds);
+}
+
+/// Removes formatting from a cue line: `<...>` tags (voices, classes and
+/// karaoke timestamps), `{\...}` overrides and HTML entities.
+///
+/// Only brackets that open a tag are markup, so text such as `x < y` or
+/// `I <3 it` is kept.
+fn strip_markup(line: &str) -> String {
+  let mut text = String::with_capacity(line.len());
+  let mut rest = line;
+  while let Some(start) = rest.find(['<', '{']) {
+    text.push_str(&rest[..start]);
+    let closing = if rest[start..].starts_with('<') {
+      '>'
+    } else {
+      '}'
+    };
+    let markup = rest[start + 1..]
+      .find(closing)
+      .map(|length| &rest[start + 1..start + 1 + length])
+      .filter(|inner| is_markup(closing, inner));
+    match markup {
+      Some(inner) => rest = &rest[start + inner.len() + 2..],
+      None => {
+        text.push_str(&rest[start..start + 1]);
+        rest = &rest[start + 1..];
+      }
+    }
+  }
+  text.push_str(rest);
+  return text
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&nbsp;", " ")
+    .replace("&amp;", "&")
+    .trim()
+    .to_string();
+}
+
+/// Checks whether the content between brackets is a tag: a cue tag like
+/// `i`, `/i` or `v Speaker`, a karaoke timestamp, or an override like
+/// `\an8`.
+fn is_markup(closing: char, inner: &str) -> bool {
+  if closing == '}' {
+    return inner.starts_with('\\');
+  }
+  return inner.starts_with(|c: char| c.is_ascii_lowercase() || c == '/')
+    || parse_timestamp(inner).is_some();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn strips_tags_but_not_angle_brackets_in_text() {
+    assert_eq!(
+      strip_markup("<v Ana><i>Hello</i></v> {\\an8}world"),
+      "Hello world"
+    );
+    assert_eq!(strip_markup("<00:00:01.500>karaoke"), "karaoke");
+    assert_eq!(
+      strip_markup("x < y and I <3 it {sic}"),
+      "x < y and I <3 it {sic}"
+    );
+    assert_eq!(strip_markup("a <B> tag"), "a <B> tag");
+  }
+
+  #[test]
+  fn detects_subtitles_by_their_timing_line() {
+    assert_eq!(
+      TranscriptFormat::detect("1\n00:00:01,000 --> 00:00:02,000\nHi\n"),
+      TranscriptFormat::Srt
+    );
+    assert_eq!(
+      TranscriptFormat::detect("Then --> means implies.\nNext line."),
+      TranscriptFormat::Text
+    );
+  }
+
+  #[test]
+  fn detects_text_that_only_looks_like_json() {
+    assert_eq!(
+      TranscriptFormat::detect("{laughs} Welcome back."),
+      TranscriptFormat::Text
+    );
+    assert_eq!(
+      TranscriptFormat::detect(r#"{"transcription": []}"#),
+      TranscriptFormat::WhisperCppJson
+    );
+    assert_eq!(
+      TranscriptFormat::detect(r#"{"text": "Hi"}"#),
+      TranscriptFormat::WhisperJson
+    );
+  }
+}
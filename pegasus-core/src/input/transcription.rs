@@ -0,0 +1,506 @@
+//! Whisper transcription data structures for structured refinement.
+//!
+//! This module provides types for parsing and working with Whisper JSON
+//! transcription output, including word-level confidence scores and timestamps
+//! to reduce hallucination during text refinement.
+//!
+//! ## Components
+//!
+//! - [`WhisperWord`]: Individual word with confidence and timing
+//! - [`WhisperSegment`]: Segment of transcription with words
+//! - [`WhisperTranscription`]: Complete transcription data
+//! - [`ConfidenceSummary`]: Confidence statistics for deciding whether a
+//!   transcription needs human review
+//! - [`TimelineIssue`]: Silent gap or overlap between segments, which
+//!   usually points to a recording problem
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Similarity from which consecutive segments count as repetitions.
+const REPEATED_SEGMENT_SIMILARITY: f64 = 0.8;
+
+/// Words from which a segment said twice counts as a repetition; shorter
+/// ones like "Yes." or "No, no." are often really said twice.
+const REPEATED_SEGMENT_MIN_WORDS: usize = 4;
+
+/// Segments from which a run of short repetitions counts as a loop.
+const REPEATED_SEGMENT_MIN_RUN: usize = 3;
+
+/// Overlap in seconds tolerated between segments, for rounded timestamps.
+const OVERLAP_TOLERANCE: f64 = 0.05;
+
+/// Represents a single word in a Whisper transcription with timing and probability.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WhisperWord {
+  /// The word text (may include leading space)
+  pub word: String,
+  /// Probability score (0.0 to 1.0)
+  pub probability: f64,
+}
+
+/// Represents a segment of transcribed speech.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WhisperSegment {
+  /// Start time in seconds (optional)
+  pub start: Option<f64>,
+  /// End time in seconds (optional)
+  pub end: Option<f64>,
+  /// Segment text
+  pub text: String,
+  /// Individual words in this segment (none without word-level data)
+  #[serde(default)]
+  pub words: Vec<WhisperWord>,
+}
+
+/// Complete Whisper transcription data from JSON output.
+///
+/// Supports both full Whisper JSON (with word-level data) and simple
+/// text-only formats. Optional fields default to None for simple formats.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WhisperTranscription {
+  /// Full text content
+  pub text: Option<String>,
+  /// Detected or specified language (optional for simple formats)
+  pub language: Option<String>,
+  /// Total duration in seconds (optional for simple formats)
+  pub duration: Option<f64>,
+  /// Segments of transcription with word-level data (optional)
+  pub segments: Option<Vec<WhisperSegment>>,
+}
+
+/// Confidence statistics of a group of words.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfidenceStats {
+  /// Number of words
+  pub words: usize,
+  /// Mean word probability, `None` without words
+  pub mean: Option<f64>,
+  /// Lowest word probability, `None` without words
+  pub min: Option<f64>,
+  /// Number of words with a probability below the threshold
+  pub below_threshold: usize,
+}
+
+impl ConfidenceStats {
+  /// Computes the statistics of a group of words.
+  ///
+  /// # Arguments
+  ///
+  /// * `words` - The words
+  /// * `threshold` - The probability threshold (0.0 to 1.0)
+  ///
+  /// # Returns
+  ///
+  /// The confidence statistics of the words.
+  fn from_words<'a>(
+    words: impl IntoIterator<Item = &'a WhisperWord>,
+    threshold: f64,
+  ) -> Self {
+    let mut count = 0;
+    let mut sum = 0.0;
+    let mut min = f64::INFINITY;
+    let mut below_threshold = 0;
+
+    for word in words {
+      count += 1;
+      sum += word.probability;
+      min = min.min(word.probability);
+      if word.probability < threshold {
+        below_threshold += 1;
+      }
+    }
+
+    return ConfidenceStats {
+      words: count,
+      mean: (count > 0).then(|| sum / count as f64),
+      min: (count > 0).then_some(min),
+      below_threshold,
+    };
+  }
+}
+
+/// Confidence statistics of a transcription, overall and per segment.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfidenceSummary {
+  /// The probability threshold the words were compared against
+  pub threshold: f64,
+  /// Statistics of all words
+  pub overall: ConfidenceStats,
+  /// Statistics of each segment, in order
+  pub segments: Vec<ConfidenceStats>,
+}
+
+/// The kind of a [`TimelineIssue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineIssueKind {
+  /// No speech between two segments for longer than the gap threshold
+  Gap,
+  /// A segment starts before the previous one ends
+  Overlap,
+}
+
+/// A silent gap or an overlap between consecutive segments.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineIssue {
+  /// Whether the segments are apart or overlap
+  pub kind: TimelineIssueKind,
+  /// Index of the later segment
+  pub segment: usize,
+  /// Start of the gap or overlap in seconds
+  pub start: f64,
+  /// End of the gap or overlap in seconds
+  pub end: f64,
+}
+
+impl fmt::Display for TimelineIssue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let kind = match self.kind {
+      TimelineIssueKind::Gap => "Silent gap",
+      TimelineIssueKind::Overlap => "Overlap",
+    };
+    return write!(
+      f,
+      "{} of {:.1}s before segment {} ({:.1}s to {:.1}s)",
+      kind,
+      self.end - self.start,
+      self.segment + 1,
+      self.start,
+      self.end
+    );
+  }
+}
+
+impl WhisperTranscription {
+  /// Finds silent gaps and overlaps between consecutive segments.
+  ///
+  /// Segments without timestamps are skipped.
+  ///
+  /// # Arguments
+  ///
+  /// * `gap_threshold` - Shortest gap in seconds to report (0 reports no
+  ///   gaps)
+  ///
+  /// # Returns
+  ///
+  /// The issues in timeline order.
+  pub fn timeline_issues(&self, gap_threshold: f64) -> Vec<TimelineIssue> {
+    let mut issues = Vec::new();
+    let mut previous_end: Option<f64> = None;
+
+    for (index, segment) in self.segments.iter().flatten().enumerate() {
+      let (Some(start), Some(end)) = (segment.start, segment.end) else {
+        continue;
+      };
+
+      if let Some(previous_end) = previous_end {
+        if gap_threshold > 0.0 && start - previous_end >= gap_threshold {
+          issues.push(TimelineIssue {
+            kind: TimelineIssueKind::Gap,
+            segment: index,
+            start: previous_end,
+            end: start,
+          });
+        } else if previous_end - start > OVERLAP_TOLERANCE {
+          issues.push(TimelineIssue {
+            kind: TimelineIssueKind::Overlap,
+            segment: index,
+            start,
+            end: previous_end.min(end),
+          });
+        }
+      }
+      previous_end = Some(previous_end.map_or(end, |last| last.max(end)));
+    }
+    return issues;
+  }
+
+  /// Summarizes the word confidence of the transcription.
+  ///
+  /// Simple formats without segments have no words, so their statistics
+  /// are empty.
+  ///
+  /// # Arguments
+  ///
+  /// * `threshold` - The probability threshold (0.0 to 1.0)
+  ///
+  /// # Returns
+  ///
+  /// The overall and per-segment confidence statistics.
+  pub fn confidence_summary(&self, threshold: f64) -> ConfidenceSummary {
+    let segments = self.segments.as_deref().unwrap_or_default();
+    return ConfidenceSummary {
+      threshold,
+      overall: ConfidenceStats::from_words(
+        segments.iter().flat_map(|segment| &segment.words),
+        threshold,
+      ),
+      segments: segments
+        .iter()
+        .map(|segment| ConfidenceStats::from_words(&segment.words, threshold))
+        .collect(),
+    };
+  }
+
+  /// Returns all words with probability below the given threshold.
+  ///
+  /// Returns empty vector if no segments are present (simple format).
+  ///
+  /// # Arguments
+  ///
+  /// * `threshold` - The probability threshold (0.0 to 1.0)
+  ///
+  /// # Returns
+  ///
+  /// A vector of references to low-probability words.
+  pub fn get_low_probability_words(&self, threshold: f64) -> Vec<&WhisperWord> {
+    match &self.segments {
+      None => return Vec::new(),
+      Some(segments) => {
+        return segments
+          .iter()
+          .flat_map(|segment| &segment.words)
+          .filter(|word| word.probability < threshold)
+          .collect();
+      }
+    }
+  }
+
+  /// Collapses runs of near-duplicate consecutive segments.
+  ///
+  /// Whisper sometimes gets stuck repeating a sentence. A run of segments
+  /// whose words (ignoring case and punctuation) nearly match those of its
+  /// first segment is collapsed to that segment if it looks like such a
+  /// loop: the sentence has at least [`REPEATED_SEGMENT_MIN_WORDS`] words,
+  /// the run has at least [`REPEATED_SEGMENT_MIN_RUN`] segments, or a
+  /// repetition starts at the same time as the first segment or has no
+  /// duration. Short replies said twice, like "Yes. Yes.", are kept.
+  ///
+  /// # Returns
+  ///
+  /// The number of segments removed.
+  pub fn collapse_repeated_segments(&mut self) -> usize {
+    let Some(segments) = &mut self.segments else {
+      return 0;
+    };
+
+    let mut keep = vec![true; segments.len()];
+    let mut start = 0;
+    while start < segments.len() {
+      let words = normalized_words(&segments[start].text);
+      let mut end = start + 1;
+      while end < segments.len()
+        && similarity(&words, &normalized_words(&segments[end].text))
+          >= REPEATED_SEGMENT_SIMILARITY
+      {
+        end += 1;
+      }
+      if is_repetition(&segments[start..end], words.len()) {
+        keep[start + 1..end].fill(false);
+      }
+      start = end;
+    }
+
+    let mut flags = keep.iter();
+    segments.retain(|_| *flags.next().unwrap_or(&true));
+    return keep.iter().filter(|kept| !**kept).count();
+  }
+
+  /// Returns the number of words in the transcription.
+  ///
+  /// Returns 0 if no segments are present (simple format).
+  ///
+  /// # Returns
+  ///
+  /// The total word count.
+  pub fn word_count(&self) -> usize {
+    match &self.segments {
+      None => return 0,
+      Some(segments) => {
+        return segments.iter().map(|segment| segment.words.len()).sum();
+      }
+    }
+  }
+
+  /// Returns the full text of the transcription.
+  ///
+  /// For simple formats, returns the text field directly.
+  /// For full formats with segments, concatenates segment text.
+  ///
+  /// # Returns
+  ///
+  /// The transcription text, or empty string if none available.
+  pub fn full_text(&self) -> String {
+    // If we have a direct text field, use it
+    if let Some(text) = &self.text {
+      return text.clone();
+    }
+
+    // Otherwise, concatenate from segments
+    match &self.segments {
+      None => return String::new(),
+      Some(segments) => {
+        return segments
+          .iter()
+          .map(|s| s.text.as_str())
+          .collect::<Vec<_>>()
+          .join("\n");
+      }
+    }
+  }
+
+  /// Returns the language, or "unknown" if not specified.
+  ///
+  /// # Returns
+  ///
+  /// The detected language or "unknown".
+  pub fn language_or_default(&self) -> String {
+    return self
+      .language
+      .clone()
+      .unwrap_or_else(|| "unknown".to_string());
+  }
+
+  /// Returns the duration, or 0.0 if not specified.
+  ///
+  /// # Returns
+  ///
+  /// The duration in seconds, or 0.0.
+  pub fn duration_or_default(&self) -> f64 {
+    return self.duration.unwrap_or(0.0);
+  }
+}
+
+/// Checks whether a run of similar segments is a repetition loop.
+///
+/// # Arguments
+///
+/// * `run` - The segments, starting with the first occurrence
+/// * `words` - The number of words of the first segment
+///
+/// # Returns
+///
+/// `true` if the segments after the first should be dropped.
+fn is_repetition(run: &[WhisperSegment], words: usize) -> bool {
+  let Some((first, repetitions)) = run.split_first() else {
+    return false;
+  };
+  if repetitions.is_empty() {
+    return false;
+  }
+  let same_time = repetitions.iter().any(|segment| {
+    let restarts = segment.start.is_some() && segment.start == first.start;
+    let empty = matches!(
+      (segment.start, segment.end),
+      (Some(start), Some(end)) if end <= start
+    );
+    return restarts || empty;
+  });
+  return words >= REPEATED_SEGMENT_MIN_WORDS
+    || run.len() >= REPEATED_SEGMENT_MIN_RUN
+    || same_time;
+}
+
+/// Splits text into lowercase words, ignoring punctuation.
+fn normalized_words(text: &str) -> Vec<String> {
+  return text
+    .split(|c: char| !c.is_alphanumeric() && c != '\'')
+    .filter(|word| !word.is_empty())
+    .map(|word| word.to_lowercase())
+    .collect();
+}
+
+/// Computes how similar two word sequences are.
+///
+/// # Returns
+///
+/// One minus the word-level edit distance divided by the length of the
+/// longer sequence: 1.0 for identical sequences, 0.0 for unrelated ones.
+fn similarity(first: &[String], second: &[String]) -> f64 {
+  let longest = first.len().max(second.len());
+  if longest == 0 {
+    return 1.0;
+  }
+
+  // Levenshtein distance, keeping a single row of the table.
+  let mut row: Vec<usize> = (0..=second.len()).collect();
+  for (i, first_word) in first.iter().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, second_word) in second.iter().enumerate() {
+      let substitution = diagonal + usize::from(first_word != second_word);
+      diagonal = row[j + 1];
+      row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+    }
+  }
+  return 1.0 - row[second.len()] as f64 / longest as f64;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Builds a transcription of segments with their start, end and text.
+  fn transcription(segments: &[(f64, f64, &str)]) -> WhisperTranscription {
+    let segments: Vec<serde_json::Value> = segments
+      .iter()
+      .map(|(start, end, text)| {
+        return serde_json::json!({ "start": start, "end": end, "text": text });
+      })
+      .collect();
+    return serde_json::from_value(serde_json::json!({ "segments": segments }))
+      .unwrap();
+  }
+
+  /// Gets the texts of the segments.
+  fn texts(transcription: &WhisperTranscription) -> Vec<&str> {
+    return transcription
+      .segments
+      .iter()
+      .flatten()
+      .map(|segment| segment.text.as_str())
+      .collect();
+  }
+
+  #[test]
+  fn collapses_repeated_sentences() {
+    let mut transcription = transcription(&[
+      (0.0, 2.0, "Thanks for watching the video."),
+      (2.0, 4.0, "Thanks for watching the video!"),
+      (4.0, 5.0, "Bye."),
+    ]);
+    assert_eq!(transcription.collapse_repeated_segments(), 1);
+    assert_eq!(
+      texts(&transcription),
+      ["Thanks for watching the video.", "Bye."]
+    );
+  }
+
+  #[test]
+  fn keeps_short_replies_said_twice() {
+    let mut transcription = transcription(&[
+      (0.0, 0.5, "Yes."),
+      (0.6, 1.0, "Yes."),
+      (1.0, 2.0, "Ok"),
+    ]);
+    assert_eq!(transcription.collapse_repeated_segments(), 0);
+    assert_eq!(texts(&transcription), ["Yes.", "Yes.", "Ok"]);
+  }
+
+  #[test]
+  fn collapses_loops_of_short_segments() {
+    let mut looped = transcription(&[
+      (0.0, 0.5, "Yes."),
+      (0.6, 1.0, "Yes."),
+      (1.1, 1.5, "Yes."),
+    ]);
+    assert_eq!(looped.collapse_repeated_segments(), 2);
+
+    let mut restarted =
+      transcription(&[(3.0, 3.5, "Thank you."), (3.0, 3.5, "Thank you.")]);
+    assert_eq!(restarted.collapse_repeated_segments(), 1);
+    assert_eq!(texts(&restarted), ["Thank you."]);
+  }
+}
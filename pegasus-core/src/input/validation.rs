@@ -0,0 +1,220 @@
+//! Early validation of raw input.
+//!
+//! Rejects inputs that exceed the configured size limit and inputs that are
+//! clearly not text (binary files, invalid UTF-8), so garbage is never sent
+//! to the LLM. With `--strict`, transcriptions are also checked for
+//! structural problems (see [`check_transcription`]).
+
+use crate::input::errors::{InputError, InputResult};
+use crate::input::transcription::WhisperTranscription;
+
+/// Number of leading bytes inspected for binary content.
+pub const SNIFF_LENGTH: usize = 8192;
+
+/// Fails if the input size exceeds the limit.
+///
+/// # Arguments
+///
+/// * `source` - Name of the input (file path or "input")
+/// * `size` - Input size in bytes
+/// * `max_size` - Maximum size in bytes (0 for no limit)
+///
+/// # Returns
+///
+/// An `InputResult<()>` indicating whether the size is acceptable.
+pub fn check_size(source: &str, size: u64, max_size: u64) -> InputResult<()> {
+  if max_size > 0 && size > max_size {
+    return Err(InputError::TooLarge {
+      path: source.to_string(),
+      size,
+      limit: max_size,
+    });
+  }
+  return Ok(());
+}
+
+/// Fails if the leading bytes look like binary data.
+///
+/// Text transcripts never contain NUL bytes, and only rarely control
+/// characters other than whitespace, so either is a strong binary signal.
+///
+/// # Arguments
+///
+/// * `source` - Name of the input (file path or "input")
+/// * `bytes` - The leading bytes of the input
+///
+/// # Returns
+///
+/// An `InputResult<()>` indicating whether the content looks like text.
+pub fn check_not_binary(source: &str, bytes: &[u8]) -> InputResult<()> {
+  let sample = &bytes[..bytes.len().min(SNIFF_LENGTH)];
+  if sample.is_empty() {
+    return Ok(());
+  }
+
+  let control_count = sample
+    .iter()
+    .filter(|byte| byte.is_ascii_control() && !byte.is_ascii_whitespace())
+    .count();
+
+  if sample.contains(&0) || control_count * 10 > sample.len() {
+    return Err(InputError::BinaryInput(source.to_string()));
+  }
+
+  return Ok(());
+}
+
+/// Fails if the leading bytes contain invalid UTF-8.
+///
+/// A multi-byte character cut off at the end of the sample is not an error.
+///
+/// # Arguments
+///
+/// * `source` - Name of the input (file path or "input")
+/// * `bytes` - The leading bytes of the input
+///
+/// # Returns
+///
+/// An `InputResult<()>` indicating whether the bytes are valid so far.
+pub fn check_utf8_prefix(source: &str, bytes: &[u8]) -> InputResult<()> {
+  if let Err(e) = std::str::from_utf8(bytes)
+    && e.error_len().is_some()
+  {
+    return Err(InputError::InvalidUtf8 {
+      path: source.to_string(),
+      offset: e.valid_up_to(),
+    });
+  }
+  return Ok(());
+}
+
+/// Decodes bytes as UTF-8, reporting the offset of the first invalid byte.
+///
+/// # Arguments
+///
+/// * `source` - Name of the input (file path or "input")
+/// * `bytes` - The raw input bytes
+///
+/// # Returns
+///
+/// An `InputResult<String>` containing the decoded text or an error.
+pub fn decode_utf8(source: &str, bytes: Vec<u8>) -> InputResult<String> {
+  return String::from_utf8(bytes).map_err(|e| InputError::InvalidUtf8 {
+    path: source.to_string(),
+    offset: e.utf8_error().valid_up_to(),
+  });
+}
+
+/// Fails if a transcription has structural problems.
+///
+/// Checks that there is text, that no segment or word is empty, that
+/// timestamps are present, finite and in order (each segment ends after it
+/// starts, starts no earlier than the previous one and ends within the
+/// duration) and that probabilities are between 0.0 and 1.0. All problems
+/// are reported at once.
+///
+/// # Arguments
+///
+/// * `transcription` - The parsed transcription
+///
+/// # Returns
+///
+/// An `InputResult<()>` listing every problem found.
+pub fn check_transcription(
+  transcription: &WhisperTranscription,
+) -> InputResult<()> {
+  let mut problems = Vec::new();
+
+  if let Some(duration) = transcription.duration
+    && !(duration.is_finite() && duration >= 0.0)
+  {
+    problems.push(format!("duration {} is not a valid length", duration));
+  }
+
+  match &transcription.segments {
+    None if transcription.text.as_deref().is_none_or(is_blank) => {
+      problems.push(String::from("there is neither text nor segments"));
+    }
+    None => {}
+    Some(segments) if segments.is_empty() => {
+      problems.push(String::from("the segment list is empty"));
+    }
+    Some(segments) => {
+      let mut previous_start: Option<f64> = None;
+      for (index, segment) in segments.iter().enumerate() {
+        let number = index + 1;
+        if is_blank(&segment.text) {
+          problems.push(format!("segment {} has no text", number));
+        }
+
+        for (name, time) in [("start", segment.start), ("end", segment.end)] {
+          match time {
+            None => problems
+              .push(format!("segment {} has no {} timestamp", number, name)),
+            Some(time) if !(time.is_finite() && time >= 0.0) => {
+              problems.push(format!(
+                "segment {} has an invalid {} timestamp {}",
+                number, name, time
+              ));
+            }
+            Some(_) => {}
+          }
+        }
+
+        if let (Some(start), Some(end)) = (segment.start, segment.end) {
+          if end < start {
+            problems.push(format!(
+              "segment {} ends at {}s, before it starts at {}s",
+              number, end, start
+            ));
+          }
+          if let Some(previous) = previous_start
+            && start < previous
+          {
+            problems.push(format!(
+              "segment {} starts at {}s, before segment {} at {}s",
+              number,
+              start,
+              number - 1,
+              previous
+            ));
+          }
+          if let Some(duration) = transcription.duration
+            && end > duration
+          {
+            problems.push(format!(
+              "segment {} ends at {}s, after the duration of {}s",
+              number, end, duration
+            ));
+          }
+        }
+        previous_start = segment.start.or(previous_start);
+
+        for word in &segment.words {
+          if is_blank(&word.word) {
+            problems.push(format!("segment {} has an empty word", number));
+          }
+          if !(0.0..=1.0).contains(&word.probability) {
+            problems.push(format!(
+              "word '{}' in segment {} has probability {}, outside 0.0 to \
+               1.0",
+              word.word.trim(),
+              number,
+              word.probability
+            ));
+          }
+        }
+      }
+    }
+  }
+
+  if !problems.is_empty() {
+    return Err(InputError::InvalidTranscript(problems));
+  }
+  return Ok(());
+}
+
+/// Checks whether a text is empty or only whitespace.
+fn is_blank(text: &str) -> bool {
+  return text.trim().is_empty();
+}
@@ -0,0 +1,103 @@
+//! Bearer-token authentication and per-token rate limits for the daemon.
+//!
+//! When `[[server.tokens]]` are configured, a connection must send an
+//! `authenticate` request with one of them before it may refine. Each
+//! token's requests are counted over a sliding one-minute window shared by
+//! all connections using that token; the windows restart when the
+//! configuration is reloaded.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::ServerToken;
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// The tokens accepted by the daemon and their recent requests.
+pub struct TokenAuth {
+  limits: HashMap<String, u32>,
+  requests: Mutex<HashMap<String, Vec<Instant>>>,
+}
+
+impl TokenAuth {
+  /// Creates an authenticator for the configured tokens.
+  ///
+  /// # Arguments
+  ///
+  /// * `tokens` - The accepted tokens
+  ///
+  /// # Returns
+  ///
+  /// A new `TokenAuth`, or `None` if no tokens are configured.
+  pub fn new(tokens: Vec<ServerToken>) -> Option<Self> {
+    if tokens.is_empty() {
+      return None;
+    }
+
+    let limits = tokens
+      .into_iter()
+      .map(|token| (token.token, token.requests_per_minute.unwrap_or(0)))
+      .collect();
+    return Some(TokenAuth {
+      limits,
+      requests: Mutex::new(HashMap::new()),
+    });
+  }
+
+  /// Looks up a presented token.
+  ///
+  /// # Arguments
+  ///
+  /// * `presented` - The token sent by the client
+  ///
+  /// # Returns
+  ///
+  /// The matching configured token, or `None` if it is not accepted.
+  pub fn authenticate(&self, presented: &str) -> Option<String> {
+    // Compare against every token so the time taken does not reveal
+    // which one, if any, matched.
+    let mut matched = None;
+    for token in self.limits.keys() {
+      if constant_time_eq(token.as_bytes(), presented.as_bytes()) {
+        matched = Some(token.clone());
+      }
+    }
+    return matched;
+  }
+
+  /// Counts a request against a token's rate limit.
+  ///
+  /// # Arguments
+  ///
+  /// * `token` - An authenticated token
+  ///
+  /// # Returns
+  ///
+  /// `Ok(())` if the request may proceed, or the limit that was exceeded.
+  pub fn check_rate(&self, token: &str) -> Result<(), u32> {
+    let limit = self.limits.get(token).copied().unwrap_or(0);
+    if limit == 0 {
+      return Ok(());
+    }
+
+    let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+    let recent = requests.entry(token.to_string()).or_default();
+    let now = Instant::now();
+    recent.retain(|at| now.duration_since(*at) < RATE_LIMIT_WINDOW);
+
+    if recent.len() >= limit as usize {
+      return Err(limit);
+    }
+    recent.push(now);
+    return Ok(());
+  }
+}
+
+/// Compares two byte strings in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  if a.len() != b.len() {
+    return false;
+  }
+  return a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0;
+}
@@ -0,0 +1,230 @@
+//! Client for a running Pegasus daemon.
+
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::UnixStream;
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+
+use crate::ipc::daemon;
+use crate::ipc::errors::{IpcError, IpcResult};
+use crate::logging::request_id;
+use crate::vlog;
+
+/// How long connecting and authenticating may take before the request is
+/// refined locally instead.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the daemon may stay silent while working on a request before
+/// the request is given up. Each streamed chunk restarts the wait.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A connection to the daemon.
+pub struct DaemonClient {
+  lines: Lines<BufReader<OwnedReadHalf>>,
+  writer: OwnedWriteHalf,
+  next_id: u64,
+  auth_error: Option<IpcError>,
+  cancellation: CancellationToken,
+  overrides: Vec<String>,
+}
+
+impl DaemonClient {
+  /// Connects to the daemon if one is running.
+  ///
+  /// # Arguments
+  ///
+  /// * `token` - The bearer token to authenticate with (empty for none)
+  ///
+  /// # Returns
+  ///
+  /// The connected client, or `None` if no daemon is listening or it does
+  /// not answer within [`CONNECT_TIMEOUT`].
+  pub async fn connect(token: &str) -> Option<Self> {
+    return timeout(CONNECT_TIMEOUT, Self::open(token))
+      .await
+      .ok()
+      .flatten();
+  }
+
+  /// Opens the connection and authenticates it.
+  async fn open(token: &str) -> Option<Self> {
+    let socket_path = daemon::socket_path()?;
+    let stream = UnixStream::connect(&socket_path).await.ok()?;
+    vlog!("Forwarding request to daemon at {}", socket_path.display());
+
+    let (reader, writer) = stream.into_split();
+    let mut client = DaemonClient {
+      lines: BufReader::new(reader).lines(),
+      writer,
+      next_id: 1,
+      auth_error: None,
+      cancellation: CancellationToken::new(),
+      overrides: Vec::new(),
+    };
+
+    // A rejected token is reported by the first request instead of
+    // silently falling back to refining locally.
+    if !token.is_empty() {
+      client.auth_error = client
+        .call("authenticate", json!({ "token": token }))
+        .await
+        .err();
+    }
+    return Some(client);
+  }
+
+  /// Sets the token that cancels the client's requests.
+  ///
+  /// A request cancelled while the daemon works on it is cancelled on the
+  /// daemon too, and fails with `IpcError::Cancelled`.
+  ///
+  /// # Arguments
+  ///
+  /// * `cancellation` - The token, usually the application's
+  ///
+  /// # Returns
+  ///
+  /// The updated `DaemonClient` instance.
+  pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+    self.cancellation = cancellation;
+    return self;
+  }
+
+  /// Sets the configuration overrides sent with every request.
+  ///
+  /// # Arguments
+  ///
+  /// * `overrides` - The overrides in `section.key=value` form, usually
+  ///   the `PEGASUS_*` environment variables of the client
+  ///
+  /// # Returns
+  ///
+  /// The updated `DaemonClient` instance.
+  pub fn with_overrides(mut self, overrides: Vec<String>) -> Self {
+    self.overrides = overrides;
+    return self;
+  }
+
+  /// Refines text on the daemon.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The text to refine
+  ///
+  /// # Returns
+  ///
+  /// An `IpcResult<String>` containing the refined text or an error.
+  pub async fn refine(&mut self, text: String) -> IpcResult<String> {
+    let params = json!({
+      "text": text,
+      "requestId": request_id::current(),
+      "overrides": self.overrides,
+    });
+    let result = self.call("refine", params).await?;
+    return result_text(result);
+  }
+
+  /// Refines a Whisper JSON transcription on the daemon.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The Whisper JSON transcription
+  ///
+  /// # Returns
+  ///
+  /// An `IpcResult<String>` containing the refined text or an error.
+  pub async fn refine_whisper(
+    &mut self,
+    transcription: Value,
+  ) -> IpcResult<String> {
+    let result = self
+      .call(
+        "refineWhisper",
+        json!({
+          "transcription": transcription,
+          "requestId": request_id::current(),
+          "overrides": self.overrides,
+        }),
+      )
+      .await?;
+    return result_text(result);
+  }
+
+  /// Sends a request and waits for its result, skipping notifications.
+  async fn call(&mut self, method: &str, params: Value) -> IpcResult<Value> {
+    if let Some(error) = self.auth_error.take() {
+      return Err(error);
+    }
+
+    let id = self.next_id;
+    self.next_id += 1;
+
+    let request =
+      json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+    self.send(&request).await?;
+
+    let cancel = json!({
+      "jsonrpc": "2.0",
+      "method": "cancel",
+      "params": { "id": id },
+    });
+    loop {
+      let line = tokio::select! {
+        line = timeout(RESPONSE_TIMEOUT, self.lines.next_line()) => {
+          let Ok(line) = line else {
+            self.send(&cancel).await?;
+            return Err(IpcError::Io(format!(
+              "the daemon did not answer within {} seconds",
+              RESPONSE_TIMEOUT.as_secs()
+            )));
+          };
+          line.map_err(|e| IpcError::Io(e.to_string()))?
+        }
+        _ = self.cancellation.cancelled() => {
+          self.send(&cancel).await?;
+          return Err(IpcError::Cancelled);
+        }
+      };
+      let Some(line) = line else {
+        break;
+      };
+      let message: Value = serde_json::from_str(&line)
+        .map_err(|e| IpcError::Io(format!("invalid daemon response: {}", e)))?;
+      if message.get("id") != Some(&json!(id)) {
+        continue;
+      }
+
+      if let Some(error) = message.get("error") {
+        let message = error["message"].as_str().unwrap_or("unknown error");
+        return Err(IpcError::Remote {
+          code: error["code"].as_i64().unwrap_or_default(),
+          message: message.to_string(),
+        });
+      }
+      return Ok(message["result"].clone());
+    }
+
+    return Err(IpcError::Io(String::from("daemon closed the connection")));
+  }
+
+  /// Sends a message to the daemon.
+  async fn send(&mut self, message: &Value) -> IpcResult<()> {
+    return self
+      .writer
+      .write_all(format!("{}\n", message).as_bytes())
+      .await
+      .map_err(|e| IpcError::Io(e.to_string()));
+  }
+}
+
+/// Extracts the refined text from a refinement result.
+fn result_text(result: Value) -> IpcResult<String> {
+  return result["text"]
+    .as_str()
+    .map(str::to_string)
+    .ok_or_else(|| IpcError::Io(String::from("daemon response has no text")));
+}
@@ -0,0 +1,146 @@
+//! Background daemon serving JSON-RPC on a Unix domain socket.
+//!
+//! The daemon keeps the configuration, API key and HTTP connections warm,
+//! so CLI invocations that forward their requests to it skip that setup.
+//! A lock file next to the socket ensures only one daemon runs per user,
+//! and `[[server.tokens]]` restrict which clients may use it.
+//! With `[server] metrics_address` set, Prometheus metrics are served on
+//! `http://<metrics_address>/metrics`. Under systemd, readiness and watchdog
+//! pings are reported with `sd_notify` (see [`crate::systemd`]). With
+//! `[server] dbus` enabled, the daemon also answers on the session bus (see
+//! [`dbus`]).
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::net::UnixListener;
+use xdg::BaseDirectories;
+
+use crate::app::App;
+use crate::files::operations;
+use crate::ipc::errors::{IpcError, IpcResult};
+use crate::ipc::{Server, dbus, reload, serve_connection};
+use crate::metrics::exporter;
+use crate::systemd;
+use crate::{dlog, elog, logging, vlog};
+
+const SOCKET_NAME: &str = "pegasus.sock";
+
+/// Gets the daemon socket path, `$XDG_RUNTIME_DIR/pegasus.sock`.
+///
+/// # Returns
+///
+/// The socket path, or `None` if no runtime directory is available.
+pub fn socket_path() -> Option<PathBuf> {
+  let xdg_dirs = BaseDirectories::new();
+  return xdg_dirs
+    .get_runtime_directory()
+    .ok()
+    .map(|runtime_dir| runtime_dir.join(SOCKET_NAME));
+}
+
+/// Runs the daemon until the process is terminated or the application is
+/// cancelled.
+///
+/// Once cancelled, the daemon stops accepting connections, cancels the
+/// running requests and removes its socket.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+/// * `overrides` - The `--set` overrides, reapplied on every reload
+///
+/// # Returns
+///
+/// An `IpcResult<()>` indicating whether the daemon stopped cleanly.
+pub async fn serve(app: App, overrides: Vec<String>) -> IpcResult<()> {
+  let socket_path = socket_path().ok_or_else(|| {
+    IpcError::Startup(String::from(
+      "no runtime directory available (is XDG_RUNTIME_DIR set?)",
+    ))
+  })?;
+  let socket_name = socket_path.to_string_lossy().to_string();
+
+  let _lock = operations::try_lock_file(&socket_name)
+    .map_err(|e| IpcError::Startup(e.to_string()))?;
+
+  // Holding the lock means no other daemon is running, so an existing
+  // socket file is stale.
+  if socket_path.exists() {
+    std::fs::remove_file(&socket_path)
+      .map_err(|e| IpcError::Startup(e.to_string()))?;
+  }
+
+  let cancellation = app.cancellation().clone();
+  let metrics_address = app.config().get_server_metrics_address();
+  if app.config().get_server_tokens().is_empty() {
+    vlog!("No [[server.tokens]] configured, accepting all connections");
+  }
+  let server = Arc::new(Server::new(app, true, overrides).await?);
+  reload::spawn(Arc::clone(&server));
+
+  if !metrics_address.is_empty() {
+    let metrics_listener = exporter::bind(&metrics_address)
+      .await
+      .map_err(|e| IpcError::Startup(e.to_string()))?;
+    elog!(
+      logging::INFO,
+      "Serving metrics on http://{}/metrics",
+      metrics_address
+    );
+    tokio::spawn(async move {
+      if let Err(e) = exporter::serve(metrics_listener).await {
+        elog!(logging::ERROR, "{}", e);
+      }
+    });
+  }
+
+  // A missing session bus, e.g. on a headless server, only disables the
+  // DBus service.
+  let _dbus = if server.config().get_server_dbus() {
+    match dbus::serve(Arc::clone(&server)).await {
+      Ok(connection) => {
+        elog!(
+          logging::INFO,
+          "Serving {} on the session bus",
+          dbus::BUS_NAME
+        );
+        Some(connection)
+      }
+      Err(e) => {
+        elog!(logging::WARNING, "{}; continuing without DBus", e);
+        None
+      }
+    }
+  } else {
+    None
+  };
+
+  let listener = UnixListener::bind(&socket_path)
+    .map_err(|e| IpcError::Startup(format!("{}: {}", socket_name, e)))?;
+  elog!(logging::INFO, "Pegasus daemon listening on {}", socket_name);
+  systemd::notify(&format!("READY=1\nSTATUS=Listening on {}", socket_name));
+  systemd::spawn_watchdog();
+
+  loop {
+    let accepted = tokio::select! {
+      accepted = listener.accept() => accepted,
+      _ = cancellation.cancelled() => break,
+    };
+    let (stream, _) = accepted.map_err(|e| IpcError::Io(e.to_string()))?;
+    dlog!("Accepted daemon connection");
+
+    let server = Arc::clone(&server);
+    tokio::spawn(async move {
+      let (reader, writer) = stream.into_split();
+      if let Err(e) = serve_connection(server, reader, writer).await {
+        vlog!("Daemon connection failed: {}", e);
+      }
+    });
+  }
+
+  elog!(logging::INFO, "Pegasus daemon stopping");
+  systemd::notify("STOPPING=1");
+  return std::fs::remove_file(&socket_path)
+    .map_err(|e| IpcError::Io(e.to_string()));
+}
@@ -0,0 +1,144 @@
+//! DBus service of the daemon.
+//!
+//! `pegasus daemon` registers `org.pegasus.Refiner` on the session bus, so
+//! desktop shortcuts and scripts refine text without starting a process:
+//!
+//! ```text
+//! busctl --user call org.pegasus.Refiner /org/pegasus/Refiner \
+//!   org.pegasus.Refiner Refine s "so um hello"
+//! ```
+//!
+//! ## Methods
+//!
+//! - `Refine(s text) → s`: refines the text
+//! - `RefineClipboard() → s`: refines the clipboard, read with `[server]
+//!   paste_command`, copies the result back with `[output]
+//!   clipboard_command` and returns it
+//!
+//! The session bus only accepts connections from the user running the
+//! daemon, so `[[server.tokens]]` are not checked here.
+
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Instant;
+
+use tokio::process::Command;
+use zbus::connection::{Builder, Connection};
+use zbus::fdo;
+
+use crate::ipc::Server;
+use crate::ipc::errors::{IpcError, IpcResult};
+use crate::ipc::protocol::REFINEMENT_FAILED;
+use crate::logging::request_id;
+use crate::metrics::{self, RequestStatus};
+use crate::output::format::OutputFormat;
+use crate::output::sink;
+use crate::vlog;
+
+/// Well-known bus name of the service.
+pub const BUS_NAME: &str = "org.pegasus.Refiner";
+
+/// Object path of the service.
+pub const OBJECT_PATH: &str = "/org/pegasus/Refiner";
+
+/// The `org.pegasus.Refiner` interface.
+struct RefinerInterface {
+  server: Arc<Server>,
+}
+
+#[zbus::interface(name = "org.pegasus.Refiner")]
+impl RefinerInterface {
+  /// Refines text.
+  async fn refine(&self, text: String) -> fdo::Result<String> {
+    let started = Instant::now();
+    let result = request_id::scope(request_id::generate(), async {
+      return self.server.refine(text).await.map_err(|e| e.to_string());
+    })
+    .await;
+    return record("dbusRefine", started, result);
+  }
+
+  /// Refines the clipboard and copies the result back.
+  async fn refine_clipboard(&self) -> fdo::Result<String> {
+    let started = Instant::now();
+    let result =
+      request_id::scope(request_id::generate(), self.refine_clipboard_text())
+        .await;
+    return record("dbusRefineClipboard", started, result);
+  }
+}
+
+impl RefinerInterface {
+  async fn refine_clipboard_text(&self) -> Result<String, String> {
+    let config = self.server.config();
+    let text = paste(&config.get_server_paste_command()).await?;
+    if text.trim().is_empty() {
+      return Err(String::from("The clipboard is empty"));
+    }
+
+    let refined = self.server.refine(text).await.map_err(|e| e.to_string())?;
+    let clipboard = sink::create("clipboard", &config, OutputFormat::Text)
+      .map_err(|e| e.to_string())?;
+    clipboard.write(&refined).await.map_err(|e| e.to_string())?;
+    return Ok(refined);
+  }
+}
+
+/// Registers the service on the session bus.
+///
+/// The service stops when the returned connection is dropped.
+///
+/// # Arguments
+///
+/// * `server` - The shared server state
+///
+/// # Returns
+///
+/// An `IpcResult<Connection>` containing the bus connection or an error.
+pub async fn serve(server: Arc<Server>) -> IpcResult<Connection> {
+  let startup_error =
+    |e: zbus::Error| IpcError::Startup(format!("DBus: {}", e));
+  return Builder::session()
+    .map_err(startup_error)?
+    .name(BUS_NAME)
+    .map_err(startup_error)?
+    .serve_at(OBJECT_PATH, RefinerInterface { server })
+    .map_err(startup_error)?
+    .build()
+    .await
+    .map_err(startup_error);
+}
+
+/// Records a DBus call in the metrics and converts its error.
+fn record(
+  method: &'static str,
+  started: Instant,
+  result: Result<String, String>,
+) -> fdo::Result<String> {
+  let status = match &result {
+    Ok(_) => RequestStatus::Ok,
+    Err(_) => RequestStatus::Error(REFINEMENT_FAILED),
+  };
+  metrics::record_request(method, status, started.elapsed());
+  return result.map_err(fdo::Error::Failed);
+}
+
+/// Reads the clipboard with a shell command.
+async fn paste(command: &str) -> Result<String, String> {
+  vlog!("Reading the clipboard: {}", command);
+  let output = Command::new("sh")
+    .arg("-c")
+    .arg(command)
+    .stdin(Stdio::null())
+    .output()
+    .await
+    .map_err(|e| format!("'{}': {}", command, e))?;
+  if !output.status.success() {
+    return Err(format!(
+      "'{}' failed: {}",
+      command,
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+  return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+}
@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// IPC server errors.
+///
+/// Represents errors that stop the IPC server itself. Errors of individual
+/// requests are reported to the client as JSON-RPC error responses instead.
+#[derive(Error, Debug)]
+pub enum IpcError {
+  #[error("{}", t!("error-ipc-startup", error = .0.as_str()))]
+  Startup(String),
+
+  #[error("{}", t!("error-ipc-io", error = .0.as_str()))]
+  Io(String),
+
+  /// An error response of the daemon.
+  #[error("{message}")]
+  Remote { code: i64, message: String },
+
+  /// The request was cancelled before the daemon answered it.
+  #[error("{}", t!("error-ipc-cancelled"))]
+  Cancelled,
+}
+
+/// Result type for IPC operations.
+pub type IpcResult<T> = Result<T, IpcError>;
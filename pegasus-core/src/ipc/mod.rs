@@ -0,0 +1,700 @@
+//! JSON-RPC over stdio and Unix sockets.
+//!
+//! Editor plugins keep one long-lived `pegasus --stdio` process, and
+//! `pegasus daemon` serves the same protocol on a Unix socket, so the
+//! configuration is loaded once and HTTP connections are pooled between
+//! requests.
+//!
+//! ## Protocol
+//!
+//! JSON-RPC 2.0 messages, one JSON object per line on stdin and stdout.
+//!
+//! - `refine` `{ "text": "..." }` → `{ "text": "..." }`
+//! - `refineWhisper` `{ "transcription": { ...Whisper JSON... } }` →
+//!   `{ "text": "..." }`
+//!
+//! Both take an optional `"requestId"` that is used in logs and upstream
+//! requests instead of a generated one (see [`crate::logging::request_id`]),
+//! and their results carry the `"requestId"` used. An optional
+//! `"overrides": ["section.key=value", ...]` is applied over the server's
+//! configuration for that request; the client forwards its `PEGASUS_*`
+//! environment variables this way. Keys that run commands or redirect
+//! requests cannot be overridden.
+//!
+//! - `cancel` `{ "id": <request id> }`: cancels a running request, which
+//!   stops at its next chunk, drops its upstream request and is answered
+//!   with error code -32800
+//! - `shutdown`: waits for running requests, then closes the connection
+//! - `authenticate` `{ "token": "..." }`: required by a daemon with
+//!   `[[server.tokens]]` configured before `refine` and `refineWhisper`
+//!   are accepted (see [`auth`])
+//!
+//! Configuration and dictionary changes are picked up without restarting
+//! (see [`reload`]). On Unix the daemon also offers `Refine` and
+//! `RefineClipboard` on the session bus (see [`dbus`]).
+//!
+//! While a `refine` request runs, every refined chunk is sent as a
+//! `partial` notification `{ "id": <request id>, "index": n, "text": "..." }`.
+//! Only a few messages are queued for a client that does not read them:
+//! requests then wait before refining their next chunk, and reading stops
+//! too. A client that reads nothing for [`STALL_TIMEOUT`] is given up; its
+//! requests are cancelled and the connection closed.
+//!
+//! With `[llm] context_paragraphs` set, each connection remembers its most
+//! recent `refine` requests and sends them as context with the next one.
+//!
+//! When the application is cancelled (see [`App::with_cancellation`]), the
+//! running requests are cancelled the same way and connections stop
+//! reading new ones.
+
+pub mod auth;
+#[cfg(unix)]
+pub mod client;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(unix)]
+pub mod dbus;
+pub mod errors;
+pub mod protocol;
+pub mod reload;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tokio::io::{
+  AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::sync::mpsc::{self, Sender};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use crate::app::App;
+use crate::app::errors::RuntimeError;
+use crate::app::errors::RuntimeResult;
+use crate::app::refiner::Refiner;
+use crate::clock;
+use crate::config::{Config, discovery};
+use crate::ipc::auth::TokenAuth;
+use crate::ipc::errors::{IpcError, IpcResult};
+use crate::ipc::protocol::{
+  AuthenticateParams, CancelParams, INVALID_PARAMS, METHOD_NOT_FOUND,
+  Notification, PARSE_ERROR, RATE_LIMITED, REQUEST_CANCELLED, RefineParams,
+  RefineWhisperParams, Request, Response, UNAUTHORIZED,
+};
+use crate::llm::context::ConversationContext;
+use crate::logging::request_id;
+use crate::metrics::{self, RequestStatus};
+use crate::usage;
+use crate::{elog, logging};
+
+/// Messages queued for a client before senders wait for it to read them.
+const QUEUED_MESSAGES: usize = 16;
+
+/// How long a sender waits for a client that reads nothing before the
+/// connection is given up.
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tokens cancelling the running requests, by request id.
+type InFlight = Arc<Mutex<HashMap<String, CancellationToken>>>;
+
+/// State shared by every connection.
+pub struct Server {
+  runtime: RwLock<Arc<Runtime>>,
+  /// The runtime of the most recent request overrides, dropped on reload
+  overridden: Mutex<Option<(Vec<String>, Arc<Runtime>)>>,
+  authenticate: bool,
+  overrides: Vec<String>,
+}
+
+/// The configuration and everything built from it, replaced as a whole when
+/// the configuration is reloaded. Running requests keep the runtime they
+/// started with.
+struct Runtime {
+  app: App,
+  refiner: Refiner,
+  auth: Option<TokenAuth>,
+}
+
+impl Server {
+  /// Creates a server for the configured application.
+  ///
+  /// # Arguments
+  ///
+  /// * `app` - The configured application
+  /// * `authenticate` - Whether connections must authenticate with one of
+  ///   the configured `[[server.tokens]]`
+  /// * `overrides` - The `--set` overrides the server was started with
+  ///
+  /// # Returns
+  ///
+  /// An `IpcResult<Server>` containing the server or an error.
+  pub async fn new(
+    app: App,
+    authenticate: bool,
+    overrides: Vec<String>,
+  ) -> IpcResult<Self> {
+    let runtime = Runtime::new(app, authenticate)
+      .await
+      .map_err(|e| IpcError::Startup(e.to_string()))?;
+    return Ok(Server {
+      runtime: RwLock::new(Arc::new(runtime)),
+      overridden: Mutex::new(None),
+      authenticate,
+      overrides,
+    });
+  }
+
+  /// Gets the `--set` overrides the server was started with.
+  ///
+  /// # Returns
+  ///
+  /// The overrides, reapplied on every reload.
+  pub fn overrides(&self) -> &[String] {
+    return &self.overrides;
+  }
+
+  /// Gets the configuration currently in use.
+  ///
+  /// # Returns
+  ///
+  /// A copy of the current `Config`.
+  pub fn config(&self) -> Config {
+    return self.runtime().app.config().clone();
+  }
+
+  /// Replaces the configuration, reloading the dictionary and rebuilding
+  /// the refiner.
+  ///
+  /// The previous configuration stays in use if the new one cannot be
+  /// applied.
+  ///
+  /// # Arguments
+  ///
+  /// * `config` - The new configuration
+  ///
+  /// # Returns
+  ///
+  /// A `RuntimeResult<()>` indicating whether the configuration was applied.
+  pub async fn reload(&self, config: Config) -> RuntimeResult<()> {
+    let app = self.runtime().app.with_config(config);
+    let runtime = Runtime::new(app, self.authenticate).await?;
+    *self.runtime.write().unwrap_or_else(|e| e.into_inner()) =
+      Arc::new(runtime);
+    *self.overridden.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    return Ok(());
+  }
+
+  /// Refines plain text without conversation context.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The text to refine
+  ///
+  /// # Returns
+  ///
+  /// The refined text, or an error if refinement fails.
+  pub async fn refine(&self, text: String) -> RuntimeResult<String> {
+    let runtime = self.runtime();
+    let mut chunks = runtime.app.open_chunks(Some(text), None).await?;
+    return runtime.refiner.refine_chunks(&mut chunks).await;
+  }
+
+  /// Gets the current runtime.
+  fn runtime(&self) -> Arc<Runtime> {
+    return Arc::clone(&self.runtime.read().unwrap_or_else(|e| e.into_inner()));
+  }
+
+  /// Gets the runtime of a request with configuration overrides.
+  ///
+  /// The overrides are applied over the server's own, and the runtime
+  /// built for them is kept for the next request with the same overrides.
+  ///
+  /// # Arguments
+  ///
+  /// * `overrides` - The overrides of the request
+  ///
+  /// # Returns
+  ///
+  /// The runtime, or an error if an override is not accepted or the
+  /// configuration is invalid.
+  async fn runtime_with(
+    &self,
+    overrides: &[String],
+  ) -> RuntimeResult<Arc<Runtime>> {
+    if overrides.is_empty() {
+      return Ok(self.runtime());
+    }
+    if let Some(assignment) = overrides
+      .iter()
+      .find(|assignment| discovery::is_untrusted_override(assignment))
+    {
+      let key = assignment.split_once('=').map_or("", |(key, _)| key);
+      return Err(RuntimeError::Validation(format!(
+        "the daemon does not accept an override of {}",
+        key
+      )));
+    }
+    if let Some((cached, runtime)) =
+      &*self.overridden.lock().unwrap_or_else(|e| e.into_inner())
+      && cached == overrides
+    {
+      return Ok(Arc::clone(runtime));
+    }
+
+    let all = [self.overrides.as_slice(), overrides].concat();
+    let config = Config::load(&all)
+      .await
+      .map_err(|e| RuntimeError::Validation(e.to_string()))?;
+    let app = self.runtime().app.with_config(config);
+    let runtime = Arc::new(Runtime::new(app, false).await?);
+    *self.overridden.lock().unwrap_or_else(|e| e.into_inner()) =
+      Some((overrides.to_vec(), Arc::clone(&runtime)));
+    return Ok(runtime);
+  }
+}
+
+impl Runtime {
+  /// Builds the refiner and token list for an application.
+  async fn new(app: App, authenticate: bool) -> RuntimeResult<Self> {
+    let refiner = app.create_refiner().await?;
+    let auth = if authenticate {
+      TokenAuth::new(app.config().get_server_tokens())
+    } else {
+      None
+    };
+    return Ok(Runtime { app, refiner, auth });
+  }
+}
+
+/// A single client connection.
+struct Connection {
+  server: Arc<Server>,
+  sender: Sender<String>,
+  context: Mutex<ConversationContext>,
+  /// Cancels the requests of the connection
+  cancellation: CancellationToken,
+  /// Whether the client stopped reading
+  stalled: AtomicBool,
+}
+
+/// Serves JSON-RPC requests on stdin and stdout until `shutdown` or the end
+/// of stdin.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+/// * `overrides` - The `--set` overrides, reapplied on every reload
+///
+/// # Returns
+///
+/// An `IpcResult<()>` indicating whether the server shut down cleanly.
+pub async fn serve_stdio(app: App, overrides: Vec<String>) -> IpcResult<()> {
+  let server = Arc::new(Server::new(app, false, overrides).await?);
+  reload::spawn(Arc::clone(&server));
+  return serve_connection(server, tokio::io::stdin(), tokio::io::stdout())
+    .await;
+}
+
+/// Serves JSON-RPC requests on a connection until `shutdown` or the end of
+/// its input.
+///
+/// # Arguments
+///
+/// * `server` - The shared server state
+/// * `reader` - The incoming half of the connection
+/// * `writer` - The outgoing half of the connection
+///
+/// # Returns
+///
+/// An `IpcResult<()>` indicating whether the connection closed cleanly.
+pub async fn serve_connection(
+  server: Arc<Server>,
+  reader: impl AsyncRead + Unpin,
+  mut writer: impl AsyncWrite + Unpin + Send + 'static,
+) -> IpcResult<()> {
+  let (sender, mut receiver) = mpsc::channel::<String>(QUEUED_MESSAGES);
+  let writer_task = tokio::spawn(async move {
+    while let Some(message) = receiver.recv().await {
+      writer.write_all(message.as_bytes()).await?;
+      writer.write_all(b"\n").await?;
+      writer.flush().await?;
+    }
+    return Ok::<(), std::io::Error>(());
+  });
+
+  let context = Mutex::new(server.runtime().app.create_context());
+  let cancellation = server.runtime().app.cancellation().child_token();
+  let connection = Arc::new(Connection {
+    server,
+    sender,
+    context,
+    cancellation: cancellation.clone(),
+    stalled: AtomicBool::new(false),
+  });
+  let in_flight: InFlight = Arc::new(Mutex::new(HashMap::new()));
+  let mut tasks = JoinSet::new();
+  let mut lines = BufReader::new(reader).lines();
+  let mut shutdown_id = None;
+  let mut token = None;
+
+  loop {
+    let line = tokio::select! {
+      line = lines.next_line() => line,
+      _ = cancellation.cancelled() => break,
+    };
+    let Some(line) = line.map_err(|e| IpcError::Io(e.to_string()))? else {
+      break;
+    };
+
+    // Reap finished requests so a long-lived session does not accumulate
+    // completed tasks.
+    while tasks.try_join_next().is_some() {}
+
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let request = match serde_json::from_str::<Request>(&line) {
+      Ok(request) => request,
+      Err(e) => {
+        connection
+          .send(&Response::error(Value::Null, PARSE_ERROR, e.to_string()))
+          .await;
+        continue;
+      }
+    };
+
+    match request.method.as_str() {
+      "shutdown" => {
+        shutdown_id = request.id;
+        break;
+      }
+      "cancel" => connection.cancel(request, &in_flight).await,
+      "authenticate" => connection.authenticate(request, &mut token).await,
+      _ => {
+        if let Err((code, message)) = connection.admit(&token) {
+          metrics::record_request(
+            metrics_method(&request.method),
+            RequestStatus::Error(code),
+            Duration::ZERO,
+          );
+          if let Some(id) = request.id {
+            connection.send(&Response::error(id, code, message)).await;
+          }
+          continue;
+        }
+
+        // Hold the lock while spawning so the task cannot finish and
+        // unregister itself before it has been registered.
+        let mut running = in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        let key = request.id.as_ref().map(Value::to_string);
+        let request_cancellation = cancellation.child_token();
+        tasks.spawn(Arc::clone(&connection).handle(
+          request,
+          request_cancellation.clone(),
+          Arc::clone(&in_flight),
+        ));
+        if let Some(key) = key {
+          running.insert(key, request_cancellation);
+        }
+      }
+    }
+  }
+
+  while tasks.join_next().await.is_some() {}
+  if let Some(id) = shutdown_id {
+    connection.send(&Response::success(id, Value::Null)).await;
+  }
+
+  if connection.stalled.load(Ordering::Relaxed) {
+    // The writer is stuck on a client that does not read.
+    writer_task.abort();
+    return Err(IpcError::Io(String::from("client stopped reading")));
+  }
+  drop(connection);
+  return writer_task
+    .await
+    .map_err(|e| IpcError::Io(e.to_string()))?
+    .map_err(|e| IpcError::Io(e.to_string()));
+}
+
+impl Connection {
+  /// Queues a message for the client, waiting while the queue is full.
+  ///
+  /// A client that reads nothing for [`STALL_TIMEOUT`] is given up: its
+  /// requests are cancelled and the messages for it dropped.
+  async fn send(&self, message: &impl Serialize) {
+    if self.stalled.load(Ordering::Relaxed) {
+      return;
+    }
+    let Ok(line) = serde_json::to_string(message) else {
+      return;
+    };
+    tokio::select! {
+      _ = self.sender.send(line) => {}
+      _ = clock::sleep(STALL_TIMEOUT) => {
+        if !self.stalled.swap(true, Ordering::Relaxed) {
+          elog!(
+            logging::WARNING,
+            "Client read nothing for {}s; cancelling its requests",
+            STALL_TIMEOUT.as_secs()
+          );
+        }
+        self.cancellation.cancel();
+      }
+    }
+  }
+
+  /// Handles a request and sends its response.
+  async fn handle(
+    self: Arc<Self>,
+    request: Request,
+    cancellation: CancellationToken,
+    in_flight: InFlight,
+  ) {
+    let started = Instant::now();
+    let id = request
+      .params
+      .get("requestId")
+      .and_then(Value::as_str)
+      .map_or_else(request_id::generate, String::from);
+    let config = self.server.config();
+    let run = request_id::scope(id.clone(), async {
+      return match request.method.as_str() {
+        "refine" => self.refine(&request, &cancellation).await,
+        "refineWhisper" => self.refine_whisper(&request, &cancellation).await,
+        method => {
+          Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method)))
+        }
+      };
+    });
+    let result =
+      usage::track(&config, &request.method, run)
+        .await
+        .map(|mut result| {
+          result["requestId"] = Value::String(id);
+          return result;
+        });
+
+    let status = match &result {
+      Ok(_) => RequestStatus::Ok,
+      Err((REQUEST_CANCELLED, _)) => RequestStatus::Cancelled,
+      Err((code, _)) => RequestStatus::Error(*code),
+    };
+    metrics::record_request(
+      metrics_method(&request.method),
+      status,
+      started.elapsed(),
+    );
+
+    let Some(id) = request.id else {
+      return;
+    };
+    in_flight
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .remove(&id.to_string());
+
+    match result {
+      Ok(result) => self.send(&Response::success(id, result)).await,
+      Err((code, message)) => {
+        self.send(&Response::error(id, code, message)).await
+      }
+    }
+  }
+
+  /// Refines plain text, streaming each refined chunk as a notification.
+  async fn refine(
+    &self,
+    request: &Request,
+    cancellation: &CancellationToken,
+  ) -> Result<Value, (i64, String)> {
+    let params: RefineParams = parse_params(&request.params)?;
+    let id = request.id.clone().unwrap_or(Value::Null);
+
+    let runtime = self
+      .server
+      .runtime_with(&params.overrides)
+      .await
+      .map_err(refinement_error)?;
+    let mut chunks = runtime
+      .app
+      .open_chunks(Some(params.text.clone()), None)
+      .await
+      .map_err(refinement_error)?;
+
+    // Requests running concurrently on one connection each see the
+    // context as it was when they started.
+    let context = self
+      .context
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .clone();
+    let mut index = 0;
+    let text = runtime
+      .refiner
+      .refine_chunks_in_context(
+        &mut chunks,
+        &context,
+        cancellation,
+        |partial| {
+          let notification = Notification::new(
+            "partial",
+            json!({ "id": id, "index": index, "text": partial }),
+          );
+          index += 1;
+          return async move { self.send(&notification).await };
+        },
+      )
+      .await
+      .map_err(refinement_error)?;
+
+    self
+      .context
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .push(params.text, text.clone());
+    return Ok(json!({ "text": text }));
+  }
+
+  /// Refines a Whisper transcription.
+  async fn refine_whisper(
+    &self,
+    request: &Request,
+    cancellation: &CancellationToken,
+  ) -> Result<Value, (i64, String)> {
+    let params: RefineWhisperParams = parse_params(&request.params)?;
+    let runtime = self
+      .server
+      .runtime_with(&params.overrides)
+      .await
+      .map_err(refinement_error)?;
+    let text = cancellation
+      .run_until_cancelled(
+        runtime.refiner.refine_whisper(&params.transcription),
+      )
+      .await
+      .ok_or_else(|| refinement_error(RuntimeError::Cancelled(String::new())))?
+      .map_err(refinement_error)?;
+    return Ok(json!({ "text": text }));
+  }
+
+  /// Authenticates the connection with a bearer token.
+  async fn authenticate(&self, request: Request, token: &mut Option<String>) {
+    let result =
+      parse_params::<AuthenticateParams>(&request.params).and_then(|params| {
+        match &self.server.runtime().auth {
+          None => Ok(()),
+          Some(auth) => match auth.authenticate(&params.token) {
+            Some(accepted) => {
+              *token = Some(accepted);
+              Ok(())
+            }
+            None => Err((UNAUTHORIZED, String::from("Invalid token"))),
+          },
+        }
+      });
+
+    let Some(id) = request.id else {
+      return;
+    };
+    match result {
+      Ok(()) => {
+        let result = json!({ "authenticated": true });
+        self.send(&Response::success(id, result)).await
+      }
+      Err((code, message)) => {
+        self.send(&Response::error(id, code, message)).await
+      }
+    }
+  }
+
+  /// Checks that the connection may start a request.
+  fn admit(&self, token: &Option<String>) -> Result<(), (i64, String)> {
+    let runtime = self.server.runtime();
+    let Some(auth) = &runtime.auth else {
+      return Ok(());
+    };
+    let Some(token) = token else {
+      return Err((
+        UNAUTHORIZED,
+        String::from("Authentication required; set [server] client_token"),
+      ));
+    };
+    return auth.check_rate(token).map_err(|limit| {
+      (
+        RATE_LIMITED,
+        format!("Rate limit of {} requests per minute exceeded", limit),
+      )
+    });
+  }
+
+  /// Cancels a running request, which then answers itself as cancelled.
+  async fn cancel(&self, request: Request, in_flight: &InFlight) {
+    let params = match parse_params::<CancelParams>(&request.params) {
+      Ok(params) => params,
+      Err((code, message)) => {
+        if let Some(id) = request.id {
+          self.send(&Response::error(id, code, message)).await;
+        }
+        return;
+      }
+    };
+
+    let running = in_flight
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .remove(&params.id.to_string());
+    let cancelled = running.is_some();
+    if let Some(cancellation) = running {
+      cancellation.cancel();
+    }
+
+    if let Some(id) = request.id {
+      let result = json!({ "cancelled": cancelled });
+      self.send(&Response::success(id, result)).await;
+    }
+  }
+}
+
+/// Deserializes method parameters.
+fn parse_params<T: DeserializeOwned>(
+  params: &Value,
+) -> Result<T, (i64, String)> {
+  return serde_json::from_value(params.clone())
+    .map_err(|e| (INVALID_PARAMS, format!("Invalid params: {}", e)));
+}
+
+/// Gets the method label recorded in the metrics.
+///
+/// Unknown methods share one label so clients cannot create unbounded
+/// metric series.
+fn metrics_method(method: &str) -> &'static str {
+  return match method {
+    "refine" => "refine",
+    "refineWhisper" => "refineWhisper",
+    _ => "unknown",
+  };
+}
+
+/// Maps a refinement error to a JSON-RPC error.
+///
+/// The code tells the category of the error, so the message is sent
+/// without its prefix.
+fn refinement_error(error: RuntimeError) -> (i64, String) {
+  let code = protocol::error_code(error.kind());
+  return match error {
+    RuntimeError::Cancelled(_) => (code, String::from("Request cancelled")),
+    RuntimeError::Input(message)
+    | RuntimeError::Network(message)
+    | RuntimeError::Refinement(message)
+    | RuntimeError::Validation(message)
+    | RuntimeError::Refusal(message)
+    | RuntimeError::Daemon(message) => (code, message),
+  };
+}
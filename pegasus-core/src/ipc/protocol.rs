@@ -0,0 +1,208 @@
+//! JSON-RPC 2.0 message types.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::app::errors::ErrorKind;
+use crate::input::transcription::WhisperTranscription;
+
+const JSONRPC_VERSION: &str = "2.0";
+
+/// Invalid JSON was received.
+pub const PARSE_ERROR: i64 = -32700;
+/// The method does not exist.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters.
+pub const INVALID_PARAMS: i64 = -32602;
+/// The request failed while refining.
+pub const REFINEMENT_FAILED: i64 = -32000;
+/// The input could not be refined as given.
+pub const INVALID_INPUT: i64 = -32003;
+/// The LLM service could not be reached.
+pub const SERVICE_UNAVAILABLE: i64 = -32004;
+/// A configured value of the daemon, such as its API key, is invalid.
+pub const INVALID_CONFIGURATION: i64 = -32005;
+/// The model refused to refine the text.
+pub const REFUSED: i64 = -32006;
+/// The connection has not authenticated with a valid token.
+pub const UNAUTHORIZED: i64 = -32001;
+/// The token's rate limit has been exceeded.
+pub const RATE_LIMITED: i64 = -32002;
+/// The request was cancelled by the client.
+pub const REQUEST_CANCELLED: i64 = -32800;
+
+/// Gets the error code of a failed refinement.
+///
+/// # Arguments
+///
+/// * `kind` - The category of the failure
+///
+/// # Returns
+///
+/// The JSON-RPC error code, `REFINEMENT_FAILED` for categories without a
+/// code of their own.
+pub fn error_code(kind: ErrorKind) -> i64 {
+  return match kind {
+    ErrorKind::Input => INVALID_INPUT,
+    ErrorKind::Network => SERVICE_UNAVAILABLE,
+    ErrorKind::Validation => INVALID_CONFIGURATION,
+    ErrorKind::Refusal => REFUSED,
+    ErrorKind::Cancelled => REQUEST_CANCELLED,
+    _ => REFINEMENT_FAILED,
+  };
+}
+
+/// Gets the category of a failed refinement from its error code.
+///
+/// # Arguments
+///
+/// * `code` - The JSON-RPC error code
+///
+/// # Returns
+///
+/// The category of the failure, or `None` if the code is no refinement
+/// failure.
+pub fn error_kind(code: i64) -> Option<ErrorKind> {
+  return match code {
+    REFINEMENT_FAILED => Some(ErrorKind::Llm),
+    INVALID_INPUT => Some(ErrorKind::Input),
+    SERVICE_UNAVAILABLE => Some(ErrorKind::Network),
+    INVALID_CONFIGURATION => Some(ErrorKind::Validation),
+    REFUSED => Some(ErrorKind::Refusal),
+    REQUEST_CANCELLED => Some(ErrorKind::Cancelled),
+    _ => None,
+  };
+}
+
+/// An incoming request or notification (a request without an `id`).
+#[derive(Debug, Deserialize)]
+pub struct Request {
+  /// Request ID, echoed in the response
+  pub id: Option<Value>,
+  /// Method name
+  pub method: String,
+  /// Method parameters
+  #[serde(default)]
+  pub params: Value,
+}
+
+/// A response to a request.
+#[derive(Debug, Serialize)]
+pub struct Response {
+  jsonrpc: &'static str,
+  id: Value,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  result: Option<Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  error: Option<RpcError>,
+}
+
+impl Response {
+  /// Creates a successful response.
+  ///
+  /// # Arguments
+  ///
+  /// * `id` - The request ID
+  /// * `result` - The result value
+  ///
+  /// # Returns
+  ///
+  /// A new `Response` instance.
+  pub fn success(id: Value, result: Value) -> Self {
+    return Response {
+      jsonrpc: JSONRPC_VERSION,
+      id,
+      result: Some(result),
+      error: None,
+    };
+  }
+
+  /// Creates an error response.
+  ///
+  /// # Arguments
+  ///
+  /// * `id` - The request ID (`null` if it could not be read)
+  /// * `code` - The JSON-RPC error code
+  /// * `message` - A human-readable error message
+  ///
+  /// # Returns
+  ///
+  /// A new `Response` instance.
+  pub fn error(id: Value, code: i64, message: String) -> Self {
+    return Response {
+      jsonrpc: JSONRPC_VERSION,
+      id,
+      result: None,
+      error: Some(RpcError { code, message }),
+    };
+  }
+}
+
+/// A JSON-RPC error object.
+#[derive(Debug, Serialize)]
+pub struct RpcError {
+  code: i64,
+  message: String,
+}
+
+/// A notification sent to the client.
+#[derive(Debug, Serialize)]
+pub struct Notification {
+  jsonrpc: &'static str,
+  method: &'static str,
+  params: Value,
+}
+
+impl Notification {
+  /// Creates a notification.
+  ///
+  /// # Arguments
+  ///
+  /// * `method` - The notification method name
+  /// * `params` - The notification parameters
+  ///
+  /// # Returns
+  ///
+  /// A new `Notification` instance.
+  pub fn new(method: &'static str, params: Value) -> Self {
+    return Notification {
+      jsonrpc: JSONRPC_VERSION,
+      method,
+      params,
+    };
+  }
+}
+
+/// Parameters of the `refine` method.
+#[derive(Debug, Deserialize)]
+pub struct RefineParams {
+  /// The text to refine
+  pub text: String,
+  /// Configuration overrides in `section.key=value` form
+  #[serde(default)]
+  pub overrides: Vec<String>,
+}
+
+/// Parameters of the `refineWhisper` method.
+#[derive(Debug, Deserialize)]
+pub struct RefineWhisperParams {
+  /// The Whisper JSON transcription to refine
+  pub transcription: WhisperTranscription,
+  /// Configuration overrides in `section.key=value` form
+  #[serde(default)]
+  pub overrides: Vec<String>,
+}
+
+/// Parameters of the `cancel` notification.
+#[derive(Debug, Deserialize)]
+pub struct CancelParams {
+  /// ID of the request to cancel
+  pub id: Value,
+}
+
+/// Parameters of the `authenticate` method.
+#[derive(Debug, Deserialize)]
+pub struct AuthenticateParams {
+  /// The bearer token
+  pub token: String,
+}
@@ -0,0 +1,116 @@
+//! Configuration hot reload for long-running servers.
+//!
+//! Polls the configuration files and the dictionary for changes and, when
+//! one changes, reloads the configuration with the original `--set`
+//! overrides and rebuilds the refiner. Requests already running finish with
+//! the previous configuration. A configuration that fails to load or
+//! validate is reported and the previous one stays in use.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::config::{Config, resolver};
+use crate::ipc::Server;
+use crate::{elog, logging};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Modification time and size of each watched file, `None` if missing.
+type Snapshot = BTreeMap<PathBuf, Option<(SystemTime, u64)>>;
+
+/// Starts watching the server's configuration in the background.
+///
+/// # Arguments
+///
+/// * `server` - The server to reload
+pub fn spawn(server: Arc<Server>) {
+  tokio::spawn(watch(server));
+}
+
+/// Reloads the server whenever a watched file changes.
+async fn watch(server: Arc<Server>) {
+  let mut previous = snapshot(&server.config()).await;
+
+  loop {
+    tokio::time::sleep(POLL_INTERVAL).await;
+
+    let current = snapshot(&server.config()).await;
+    let changed_files: Vec<String> = current
+      .iter()
+      .filter(|(path, state)| previous.get(*path) != Some(*state))
+      .map(|(path, _)| path.display().to_string())
+      .collect();
+    previous = current;
+
+    if changed_files.is_empty() {
+      continue;
+    }
+    reload(&server, &changed_files).await;
+  }
+}
+
+/// Reloads the configuration after files changed.
+async fn reload(server: &Server, changed: &[String]) {
+  let config = match Config::load(server.overrides()).await {
+    Ok(config) => config,
+    Err(e) => {
+      elog!(
+        logging::ERROR,
+        "Not reloading after changes to {}: {}",
+        changed.join(", "),
+        e
+      );
+      return;
+    }
+  };
+
+  let changed_keys = resolver::changed_keys(&server.config(), &config);
+  if let Err(e) = server.reload(config).await {
+    elog!(
+      logging::ERROR,
+      "Not reloading after changes to {}: {}",
+      changed.join(", "),
+      e
+    );
+    return;
+  }
+
+  if changed_keys.is_empty() {
+    elog!(
+      logging::INFO,
+      "Reloaded after changes to {}",
+      changed.join(", ")
+    );
+  } else {
+    elog!(
+      logging::INFO,
+      "Reloaded after changes to {} (changed: {})",
+      changed.join(", "),
+      changed_keys.join(", ")
+    );
+  }
+}
+
+/// Records the state of every file the configuration depends on.
+async fn snapshot(config: &Config) -> Snapshot {
+  let mut paths = resolver::source_paths();
+  let dictionary_path = config.get_custom_dictionary_path();
+  if !dictionary_path.is_empty() {
+    paths.push(PathBuf::from(dictionary_path));
+  }
+
+  let mut snapshot = Snapshot::new();
+  for path in paths {
+    let state = match tokio::fs::metadata(&path).await {
+      Ok(metadata) => metadata
+        .modified()
+        .ok()
+        .map(|modified| (modified, metadata.len())),
+      Err(_) => None,
+    };
+    snapshot.insert(path, state);
+  }
+  return snapshot;
+}
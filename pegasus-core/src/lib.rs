@@ -0,0 +1,105 @@
+//! Pegasus core library.
+//!
+//! Refines speech-to-text transcripts with an LLM: fixes punctuation,
+//! capitalization and misrecognized words while keeping the speaker's
+//! wording. The `pegasus` command-line tool is a thin wrapper around this
+//! crate; other applications (GUI frontends, bots) can embed refinement
+//! directly instead of shelling out.
+//!
+//! ## Modules
+//!
+//! - [`app`]: Refinement orchestration ([`app::App`], [`Refiner`])
+//! - [`audio`]: Transcribing recordings with a speech-to-text service
+//! - [`bench`]: Scoring refinements against references
+//! - [`clock`]: Injectable time and randomness for retries, expiry and names
+//! - [`backend`]: LLM server spawned on demand
+//! - [`config`]: Layered configuration loading and validation
+//! - [`dictation`]: Hands-free dictation with a voice or hotkey trigger
+//! - [`dictionary`]: Custom dictionary of domain terms
+//! - [`i18n`]: Localized user-facing messages
+//! - [`input`]: Reading, decoding and chunking input text
+//! - [`ipc`]: JSON-RPC over stdio and the Unix socket daemon
+//! - [`llm`]: LLM client and prompts
+//! - [`metrics`]: Prometheus metrics for the daemon
+//! - [`network`]: HTTP client with proxy and circuit breaker support
+//! - [`files`]: Atomic file operations, locks and temporary files
+//! - [`queue`]: Persistent job queue for batch refinement
+//! - [`repl`]: Interactive refinement sessions
+//! - [`secrets`]: API key sources (keyring, command, file)
+//! - [`shell`]: Splitting configured command lines into arguments
+//! - [`systemd`]: `sd_notify` support and user unit installation
+//! - [`timing`]: Per-phase durations for `--timing`
+//! - [`tui`]: Interactive review of Whisper transcriptions
+//! - [`update`]: Self-update from GitHub releases
+//! - [`usage`]: Usage statistics of refinement runs
+//! - [`output`]: Output formats
+//! - [`logging`]: Verbose logging
+//!
+//! ## Examples
+//!
+//! Refining text without any configuration file:
+//!
+//! ```no_run
+//! use pegasus_core::Refiner;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let refined = Refiner::builder()
+//!   .url("http://127.0.0.1:8080")
+//!   .model("qwen2.5")
+//!   .dictionary(vec![String::from("Pegasus")])
+//!   .build()?
+//!   .refine("so um pegasus is a tool that fixes transcripts")
+//!   .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Refining with the user's layered configuration, like the CLI does:
+//!
+//! ```no_run
+//! use pegasus_core::app::App;
+//! use pegasus_core::config::Config;
+//! use pegasus_core::output::format::OutputFormat;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let config = Config::load(&[]).await?;
+//! let app = App::new(config);
+//! let refined = app
+//!   .refine_text(Some(String::from("hello world")), None, OutputFormat::Text)
+//!   .await?;
+//! println!("{}", refined);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod app;
+pub mod audio;
+pub mod backend;
+pub mod bench;
+pub mod clock;
+pub mod config;
+pub mod dictation;
+pub mod dictionary;
+pub mod files;
+pub mod i18n;
+pub mod input;
+pub mod ipc;
+pub mod llm;
+pub mod logging;
+pub mod metrics;
+pub mod network;
+pub mod output;
+pub mod queue;
+pub mod repl;
+pub mod secrets;
+pub mod shell;
+#[cfg(unix)]
+pub mod systemd;
+pub mod timing;
+#[cfg(unix)]
+pub mod tui;
+pub mod update;
+pub mod usage;
+
+pub use app::refiner::{Refiner, RefinerBuilder};
+pub use tokio_util::sync::CancellationToken;
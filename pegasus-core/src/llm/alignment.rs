@@ -0,0 +1,546 @@
+//! Cross-checking corrections of low-confidence Whisper words.
+//!
+//! The refined text is aligned word by word with the original transcription.
+//! Where the model replaced a word Whisper was unsure about, the model's
+//! own token probabilities (from `logprobs`) tell how sure it was of the
+//! replacement. Replacements the model was unsure about too are reverted to
+//! the original word, since a guess by the model is no better than a guess
+//! by Whisper and may be a hallucination.
+//!
+//! The edit scripts between word or character sequences behind the error
+//! rates of `bench`, `wer` and reports are computed here too, see
+//! [`count_edits`] and [`align`].
+
+use serde::Serialize;
+
+use crate::input::transcription::WhisperWord;
+
+/// Largest alignment table, in cells, before the check is skipped.
+const MAX_ALIGNMENT_CELLS: usize = 4_000_000;
+
+/// A token of the refined text with the model's probability for it.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenSpan {
+  /// Byte offset of the token in the refined text
+  pub start: usize,
+  /// Byte offset after the token
+  pub end: usize,
+  /// Probability of the token (0.0 to 1.0)
+  pub probability: f64,
+}
+
+/// A word of the refined text.
+struct RefinedWord {
+  /// Byte range of the word without surrounding punctuation
+  start: usize,
+  end: usize,
+  normalized: String,
+}
+
+/// Reverts corrections of low-confidence words the model was unsure about.
+///
+/// Only one-to-one replacements are checked; where the model merged, split
+/// or dropped words, the refined text is kept as is.
+///
+/// # Arguments
+///
+/// * `refined` - The refined text
+/// * `tokens` - The tokens of the refined text with their probabilities
+/// * `original` - The words of the transcription, in order
+/// * `word_threshold` - Whisper probability below which a word is flagged
+/// * `token_threshold` - Model probability below which a replacement of a
+///   flagged word is reverted
+///
+/// # Returns
+///
+/// The refined text with unconfident corrections reverted, and the number
+/// of reverted words, or `None` if the texts are too long to align.
+pub fn revert_unconfident_corrections(
+  refined: &str,
+  tokens: &[TokenSpan],
+  original: &[WhisperWord],
+  word_threshold: f64,
+  token_threshold: f64,
+) -> Option<(String, usize)> {
+  let original: Vec<(&WhisperWord, String)> = original
+    .iter()
+    .map(|word| (word, normalize(&word.word)))
+    .filter(|(_, normalized)| !normalized.is_empty())
+    .collect();
+  let refined_words = split_words(refined);
+
+  if original.len().saturating_mul(refined_words.len()) > MAX_ALIGNMENT_CELLS {
+    return None;
+  }
+
+  let original_normalized: Vec<&str> = original
+    .iter()
+    .map(|(_, normalized)| normalized.as_str())
+    .collect();
+  let refined_normalized: Vec<&str> = refined_words
+    .iter()
+    .map(|word| word.normalized.as_str())
+    .collect();
+  let matches =
+    longest_common_subsequence(&original_normalized, &refined_normalized);
+
+  // Pair up the words between consecutive matches when both sides have the
+  // same number of them, which is what a replacement looks like.
+  let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+  let mut previous = (0, 0);
+  let boundaries = matches
+    .iter()
+    .map(|&(i, j)| (i, j, 1))
+    .chain(std::iter::once((original.len(), refined_words.len(), 0)));
+  for (i, j, step) in boundaries {
+    let original_gap = &original[previous.0..i];
+    let refined_gap = &refined_words[previous.1..j];
+    if original_gap.len() == refined_gap.len() {
+      for ((word, _), refined_word) in original_gap.iter().zip(refined_gap) {
+        if word.probability >= word_threshold {
+          continue;
+        }
+        let confidence = token_confidence(tokens, refined_word);
+        if confidence.is_some_and(|confidence| confidence < token_threshold) {
+          let current = &refined[refined_word.start..refined_word.end];
+          replacements.push((
+            refined_word.start,
+            refined_word.end,
+            match_case(trim_punctuation(word.word.trim()), current),
+          ));
+        }
+      }
+    }
+    previous = (i + step, j + step);
+  }
+
+  let mut text = refined.to_string();
+  for (start, end, replacement) in replacements.iter().rev() {
+    text.replace_range(*start..*end, replacement);
+  }
+  return Some((text, replacements.len()));
+}
+
+/// Splits text into words, recording their byte ranges without the
+/// surrounding punctuation.
+fn split_words(text: &str) -> Vec<RefinedWord> {
+  let mut words = Vec::new();
+  let mut offset = 0;
+  for part in text.split_inclusive(char::is_whitespace) {
+    let word = part.trim_end();
+    let core = trim_punctuation(word);
+    if !core.is_empty() {
+      let start = offset + (core.as_ptr() as usize - word.as_ptr() as usize);
+      words.push(RefinedWord {
+        start,
+        end: start + core.len(),
+        normalized: normalize(core),
+      });
+    }
+    offset += part.len();
+  }
+  return words;
+}
+
+/// Strips punctuation from both ends of a word.
+fn trim_punctuation(word: &str) -> &str {
+  return word.trim_matches(|c: char| !c.is_alphanumeric());
+}
+
+/// Lowercases a word and drops everything but letters and digits.
+///
+/// # Arguments
+///
+/// * `word` - The word
+///
+/// # Returns
+///
+/// The normalized word, empty if it has no letters or digits.
+pub(crate) fn normalize(word: &str) -> String {
+  return word
+    .chars()
+    .filter(|c| c.is_alphanumeric())
+    .flat_map(char::to_lowercase)
+    .collect();
+}
+
+/// Capitalizes a replacement like the word it replaces.
+fn match_case(replacement: &str, current: &str) -> String {
+  let capitalized = current.chars().next().is_some_and(char::is_uppercase);
+  let mut chars = replacement.chars();
+  return match chars.next() {
+    Some(first) if capitalized => first.to_uppercase().chain(chars).collect(),
+    _ => replacement.to_string(),
+  };
+}
+
+/// Gets the lowest probability of the tokens overlapping a word.
+fn token_confidence(tokens: &[TokenSpan], word: &RefinedWord) -> Option<f64> {
+  return tokens
+    .iter()
+    .filter(|token| token.start < word.end && token.end > word.start)
+    .map(|token| token.probability)
+    .reduce(f64::min);
+}
+
+/// Finds the longest common subsequence of two word sequences.
+///
+/// # Returns
+///
+/// The index pairs of the matching words, in order.
+pub(crate) fn longest_common_subsequence(
+  first: &[&str],
+  second: &[&str],
+) -> Vec<(usize, usize)> {
+  let width = second.len() + 1;
+  let mut table = vec![0u32; (first.len() + 1) * width];
+  for i in (0..first.len()).rev() {
+    for j in (0..second.len()).rev() {
+      table[i * width + j] = if first[i] == second[j] {
+        table[(i + 1) * width + j + 1] + 1
+      } else {
+        table[(i + 1) * width + j].max(table[i * width + j + 1])
+      };
+    }
+  }
+
+  let mut matches = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < first.len() && j < second.len() {
+    if first[i] == second[j] {
+      matches.push((i, j));
+      i += 1;
+      j += 1;
+    } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+      i += 1;
+    } else {
+      j += 1;
+    }
+  }
+  return matches;
+}
+
+/// Numbers of each edit of an edit script.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EditCounts {
+  /// Units of the reference replaced by another
+  pub substitutions: usize,
+  /// Units of the reference missing from the hypothesis
+  pub deletions: usize,
+  /// Units of the hypothesis missing from the reference
+  pub insertions: usize,
+}
+
+impl EditCounts {
+  /// Gets the total number of edits.
+  fn total(&self) -> usize {
+    return self.substitutions + self.deletions + self.insertions;
+  }
+}
+
+/// Counts the edits turning a hypothesis into its reference.
+///
+/// # Arguments
+///
+/// * `reference` - The expected units
+/// * `hypothesis` - The actual units
+///
+/// # Returns
+///
+/// The counts of a minimal edit script; among scripts of the same length,
+/// the one with the most substitutions.
+pub fn count_edits<T: PartialEq>(
+  reference: &[T],
+  hypothesis: &[T],
+) -> EditCounts {
+  // Each cell holds the counts of the cheapest script turning the first
+  // `i` reference units into the first `j` hypothesis units. Only the
+  // previous row is kept, so long texts need little memory.
+  let mut previous: Vec<EditCounts> = (0..=hypothesis.len())
+    .map(|j| EditCounts {
+      insertions: j,
+      ..EditCounts::default()
+    })
+    .collect();
+  let mut current = previous.clone();
+
+  for (i, expected) in reference.iter().enumerate() {
+    current[0] = EditCounts {
+      deletions: i + 1,
+      ..EditCounts::default()
+    };
+    for (j, actual) in hypothesis.iter().enumerate() {
+      let mut diagonal = previous[j];
+      if expected != actual {
+        diagonal.substitutions += 1;
+      }
+      let mut deletion = previous[j + 1];
+      deletion.deletions += 1;
+      let mut insertion = current[j];
+      insertion.insertions += 1;
+
+      current[j + 1] = [diagonal, deletion, insertion]
+        .into_iter()
+        .min_by_key(|counts| counts.total())
+        .unwrap_or(diagonal);
+    }
+    std::mem::swap(&mut previous, &mut current);
+  }
+  return previous[hypothesis.len()];
+}
+
+/// How a unit of the reference relates to the hypothesis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edit {
+  /// Both texts have the same unit
+  Match,
+  /// The hypothesis has another unit in its place
+  Substitution,
+  /// The hypothesis lacks the reference unit
+  Deletion,
+  /// The hypothesis has a unit the reference lacks
+  Insertion,
+}
+
+/// A step of an alignment: a unit of either text or both.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Aligned<T> {
+  /// How the units relate
+  pub edit: Edit,
+  /// The unit of the reference, missing for insertions
+  pub reference: Option<T>,
+  /// The unit of the hypothesis, missing for deletions
+  pub hypothesis: Option<T>,
+}
+
+/// Aligns a hypothesis with its reference.
+///
+/// The alignment follows the same edit script as [`count_edits`], so
+/// counting its edits gives the same numbers. Unlike counting, it keeps
+/// the whole table in memory.
+///
+/// # Arguments
+///
+/// * `reference` - The expected units
+/// * `hypothesis` - The actual units
+/// * `max_cells` - Largest alignment table, in cells, to build
+///
+/// # Returns
+///
+/// The steps of the alignment in order, or `None` if the sequences are too
+/// long to align.
+pub fn align<T: PartialEq + Clone>(
+  reference: &[T],
+  hypothesis: &[T],
+  max_cells: usize,
+) -> Option<Vec<Aligned<T>>> {
+  let width = hypothesis.len() + 1;
+  let cells = (reference.len() + 1).saturating_mul(width);
+  if cells > max_cells {
+    return None;
+  }
+
+  // Costs are kept for two rows like in `count_edits`; the whole
+  // table only holds the step that reached each cell.
+  let mut steps = vec![Edit::Insertion; cells];
+  let mut previous: Vec<usize> = (0..width).collect();
+  let mut current = previous.clone();
+  for (i, expected) in reference.iter().enumerate() {
+    current[0] = i + 1;
+    steps[(i + 1) * width] = Edit::Deletion;
+    for (j, actual) in hypothesis.iter().enumerate() {
+      let (diagonal, matched) = if expected == actual {
+        (previous[j], Edit::Match)
+      } else {
+        (previous[j] + 1, Edit::Substitution)
+      };
+      let candidates = [
+        (diagonal, matched),
+        (previous[j + 1] + 1, Edit::Deletion),
+        (current[j] + 1, Edit::Insertion),
+      ];
+      let (cost, step) = candidates
+        .into_iter()
+        .min_by_key(|(cost, _)| *cost)
+        .unwrap_or(candidates[0]);
+      current[j + 1] = cost;
+      steps[(i + 1) * width + j + 1] = step;
+    }
+    std::mem::swap(&mut previous, &mut current);
+  }
+
+  let mut alignment = Vec::new();
+  let (mut i, mut j) = (reference.len(), hypothesis.len());
+  while i > 0 || j > 0 {
+    let edit = steps[i * width + j];
+    let (expected, actual) = match edit {
+      Edit::Match | Edit::Substitution => {
+        i -= 1;
+        j -= 1;
+        (Some(reference[i].clone()), Some(hypothesis[j].clone()))
+      }
+      Edit::Deletion => {
+        i -= 1;
+        (Some(reference[i].clone()), None)
+      }
+      Edit::Insertion => {
+        j -= 1;
+        (None, Some(hypothesis[j].clone()))
+      }
+    };
+    alignment.push(Aligned {
+      edit,
+      reference: expected,
+      hypothesis: actual,
+    });
+  }
+  alignment.reverse();
+  return Some(alignment);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn words(words: &[(&str, f64)]) -> Vec<WhisperWord> {
+    return words
+      .iter()
+      .map(|(word, probability)| WhisperWord {
+        word: word.to_string(),
+        probability: *probability,
+      })
+      .collect();
+  }
+
+  fn token(refined: &str, word: &str, probability: f64) -> TokenSpan {
+    let start = refined.find(word).unwrap();
+    return TokenSpan {
+      start,
+      end: start + word.len(),
+      probability,
+    };
+  }
+
+  #[test]
+  fn reverts_unsure_replacements_of_flagged_words() {
+    let original = words(&[
+      (" I", 0.9),
+      (" saw", 0.9),
+      (" the", 0.9),
+      (" see", 0.3),
+      (" today.", 0.9),
+    ]);
+    let refined = "I saw the sea today.";
+
+    let unsure = [token(refined, "sea", 0.2)];
+    assert_eq!(
+      revert_unconfident_corrections(refined, &unsure, &original, 0.5, 0.5),
+      Some((String::from("I saw the see today."), 1))
+    );
+    let sure = [token(refined, "sea", 0.9)];
+    assert_eq!(
+      revert_unconfident_corrections(refined, &sure, &original, 0.5, 0.5),
+      Some((refined.to_string(), 0))
+    );
+  }
+
+  #[test]
+  fn keeps_the_case_and_punctuation_of_the_refined_word() {
+    let original = words(&[(" teh", 0.2), (" end", 0.9)]);
+    let refined = "\"The end.\"";
+    let tokens = [token(refined, "The", 0.1)];
+    assert_eq!(
+      revert_unconfident_corrections(refined, &tokens, &original, 0.5, 0.5),
+      Some((String::from("\"Teh end.\""), 1))
+    );
+  }
+
+  #[test]
+  fn keeps_confident_words_and_merged_words() {
+    let original = words(&[(" hello", 0.9), (" world", 0.9)]);
+    let refined = "Hello there.";
+    let tokens = [token(refined, "there", 0.1)];
+    assert_eq!(
+      revert_unconfident_corrections(refined, &tokens, &original, 0.5, 0.5),
+      Some((refined.to_string(), 0))
+    );
+
+    let original = words(&[(" ice", 0.2), (" cream", 0.2)]);
+    let refined = "Icecream.";
+    let tokens = [token(refined, "Icecream", 0.1)];
+    assert_eq!(
+      revert_unconfident_corrections(refined, &tokens, &original, 0.5, 0.5),
+      Some((refined.to_string(), 0))
+    );
+  }
+
+  #[test]
+  fn skips_texts_too_long_to_align() {
+    let original = words(&[(" word", 0.1); 2_001]);
+    let refined = "word ".repeat(2_001);
+    assert_eq!(
+      revert_unconfident_corrections(&refined, &[], &original, 0.5, 0.5),
+      None
+    );
+  }
+
+  #[test]
+  fn finds_the_longest_common_subsequence() {
+    assert_eq!(
+      longest_common_subsequence(&["a", "b", "c", "d"], &["b", "x", "d"]),
+      [(1, 0), (3, 2)]
+    );
+    assert!(longest_common_subsequence(&["a"], &[]).is_empty());
+  }
+
+  #[test]
+  fn counts_each_kind_of_edit() {
+    let counts = count_edits(&["a", "b", "c", "d"], &["a", "x", "c", "e", "f"]);
+    assert_eq!(
+      counts,
+      EditCounts {
+        substitutions: 2,
+        deletions: 0,
+        insertions: 1,
+      }
+    );
+    let counts = count_edits(&["a", "b", "c"], &["a", "c"]);
+    assert_eq!(
+      counts,
+      EditCounts {
+        substitutions: 0,
+        deletions: 1,
+        insertions: 0,
+      }
+    );
+  }
+
+  #[test]
+  fn aligns_along_the_counted_script() {
+    let reference = ["the", "cat", "sat", "down"];
+    let hypothesis = ["the", "bat", "sat", "right", "down", "now"];
+    let alignment = align(&reference, &hypothesis, 100).unwrap();
+    let edits: Vec<Edit> = alignment.iter().map(|step| step.edit).collect();
+    assert_eq!(
+      edits,
+      [
+        Edit::Match,
+        Edit::Substitution,
+        Edit::Match,
+        Edit::Insertion,
+        Edit::Match,
+        Edit::Insertion,
+      ]
+    );
+    let counts = count_edits(&reference, &hypothesis);
+    assert_eq!((counts.substitutions, counts.insertions), (1, 2));
+    assert_eq!(alignment[3].reference, None);
+    assert_eq!(alignment[3].hypothesis, Some("right"));
+  }
+
+  #[test]
+  fn refuses_tables_over_the_limit() {
+    assert!(align(&["a"; 10], &["b"; 10], 100).is_none());
+  }
+}
@@ -0,0 +1,309 @@
+//! Context window and feature detection of the LLM endpoint.
+//!
+//! Different servers expose their limits in different places: llama.cpp
+//! at `/props`, Ollama at `/api/show`, and OpenAI-compatible servers such
+//! as vLLM in their model metadata at `/v1/models/{model}`. The endpoint
+//! is probed in that order and the result is cached for a day in
+//! `$XDG_CACHE_HOME/pegasus/capabilities.json`, keyed by URL and model.
+//! Endpoints that could not be probed are cached for ten minutes, so a
+//! server without any of these endpoints is not probed on every run.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock;
+use crate::files::cache;
+use crate::metrics;
+use crate::network::HttpClient;
+use crate::{dlog, vlog};
+
+/// File name of the probe cache.
+const CACHE_FILE: &str = "capabilities.json";
+
+/// How long a probe result is reused, in seconds.
+const CACHE_TTL_SECONDS: i64 = 24 * 60 * 60;
+
+/// How long a failed probe is reused, in seconds.
+const NEGATIVE_CACHE_TTL_SECONDS: i64 = 10 * 60;
+
+/// Context size Ollama runs models with unless `num_ctx` is set.
+const OLLAMA_DEFAULT_NUM_CTX: usize = 4096;
+
+/// The kind of server behind the endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+  /// llama.cpp `llama-server`
+  LlamaCpp,
+  /// Ollama
+  Ollama,
+  /// Another OpenAI-compatible server
+  OpenAi,
+}
+
+impl fmt::Display for Backend {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      Backend::LlamaCpp => "llama.cpp",
+      Backend::Ollama => "Ollama",
+      Backend::OpenAi => "OpenAI-compatible",
+    });
+  }
+}
+
+/// What the endpoint supports, where known.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capabilities {
+  /// The kind of server
+  pub backend: Backend,
+  /// Context window in tokens
+  pub context_window: Option<usize>,
+  /// Whether token log probabilities are returned
+  pub logprobs: Option<bool>,
+}
+
+impl fmt::Display for Capabilities {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let feature = |supported: Option<bool>| match supported {
+      Some(true) => "yes",
+      Some(false) => "no",
+      None => "unknown",
+    };
+    let context_window = self
+      .context_window
+      .map_or_else(|| String::from("unknown"), |tokens| tokens.to_string());
+    return write!(
+      f,
+      "{:<16} {}\n{:<16} {}\n{:<16} {}",
+      "backend",
+      self.backend,
+      "context_window",
+      context_window,
+      "logprobs",
+      feature(self.logprobs)
+    );
+  }
+}
+
+/// A cached probe result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+  /// When the endpoint was probed, as a Unix timestamp
+  probed_at: i64,
+  /// `None` if the endpoint could not be probed
+  capabilities: Option<Capabilities>,
+}
+
+impl CacheEntry {
+  /// Checks whether the result is recent enough to be reused.
+  fn is_fresh(&self) -> bool {
+    let ttl = match self.capabilities {
+      Some(_) => CACHE_TTL_SECONDS,
+      None => NEGATIVE_CACHE_TTL_SECONDS,
+    };
+    return clock::timestamp() - self.probed_at < ttl;
+  }
+}
+
+/// Response of llama.cpp `/props`.
+#[derive(Debug, Deserialize)]
+struct LlamaProps {
+  default_generation_settings: LlamaGenerationSettings,
+}
+
+#[derive(Debug, Deserialize)]
+struct LlamaGenerationSettings {
+  n_ctx: Option<usize>,
+}
+
+/// Response of Ollama `/api/show`.
+#[derive(Debug, Deserialize)]
+struct OllamaShow {
+  #[serde(default)]
+  parameters: String,
+  model_info: HashMap<String, serde_json::Value>,
+}
+
+/// Response of `/v1/models/{model}`.
+#[derive(Debug, Deserialize)]
+struct ModelMetadata {
+  /// `model` for model objects
+  object: String,
+  /// Reported by vLLM
+  max_model_len: Option<usize>,
+  /// Reported by OpenRouter and others
+  context_length: Option<usize>,
+}
+
+/// Gets the capabilities of an endpoint, from the cache if fresh.
+///
+/// Failed probes are cached too, for a shorter time.
+///
+/// # Arguments
+///
+/// * `http_client` - HTTP client for the endpoint
+/// * `model` - The configured model name
+/// * `headers` - Authentication headers for the endpoint
+/// * `refresh` - Whether to probe even if a cached result is fresh
+///
+/// # Returns
+///
+/// The capabilities, or `None` if the endpoint could not be probed.
+pub async fn detect(
+  http_client: &HttpClient,
+  model: &str,
+  headers: Option<HashMap<String, String>>,
+  refresh: bool,
+) -> Option<Capabilities> {
+  let key = format!("{} {}", http_client.base_url(), model);
+  let mut entries: HashMap<String, CacheEntry> = cache::read(CACHE_FILE).await;
+
+  if !refresh {
+    let fresh = entries.get(&key).filter(|entry| entry.is_fresh());
+    metrics::record_cache_lookup("capabilities", fresh.is_some());
+    if let Some(entry) = fresh {
+      dlog!("Using cached capabilities of {}", key);
+      return entry.capabilities.clone();
+    }
+  }
+
+  let capabilities = probe(http_client, model, headers).await;
+  if let Some(capabilities) = &capabilities {
+    vlog!(
+      "Detected {} endpoint with a context window of {}",
+      capabilities.backend,
+      capabilities
+        .context_window
+        .map_or_else(|| String::from("unknown size"), |n| n.to_string())
+    );
+  }
+  entries.insert(
+    key,
+    CacheEntry {
+      probed_at: clock::timestamp(),
+      capabilities: capabilities.clone(),
+    },
+  );
+  cache::write(CACHE_FILE, &entries).await;
+  return capabilities;
+}
+
+/// Probes the endpoint for each supported kind of server in turn.
+async fn probe(
+  http_client: &HttpClient,
+  model: &str,
+  headers: Option<HashMap<String, String>>,
+) -> Option<Capabilities> {
+  if let Ok(props) = http_client
+    .fetch_json::<LlamaProps>("props", None, headers.clone())
+    .await
+  {
+    return Some(Capabilities {
+      backend: Backend::LlamaCpp,
+      context_window: props.default_generation_settings.n_ctx,
+      logprobs: Some(true),
+    });
+  }
+
+  let body = serde_json::json!({ "model": model });
+  if let Ok(show) = http_client
+    .fetch_json::<OllamaShow>("api/show", Some(&body), headers.clone())
+    .await
+  {
+    return Some(Capabilities {
+      backend: Backend::Ollama,
+      context_window: Some(ollama_context_window(&show)),
+      logprobs: None,
+    });
+  }
+
+  if let Ok(metadata) = http_client
+    .fetch_json::<ModelMetadata>(&format!("v1/models/{}", model), None, headers)
+    .await
+    && metadata.object == "model"
+  {
+    return Some(Capabilities {
+      backend: Backend::OpenAi,
+      context_window: metadata.max_model_len.or(metadata.context_length),
+      logprobs: None,
+    });
+  }
+
+  dlog!("Could not detect the capabilities of the endpoint");
+  return None;
+}
+
+/// Gets the context Ollama runs a model with.
+///
+/// That is `num_ctx` if the model sets it, otherwise Ollama's default,
+/// capped at the model's trained context length.
+fn ollama_context_window(show: &OllamaShow) -> usize {
+  let num_ctx = show.parameters.lines().find_map(|line| {
+    let mut parts = line.split_whitespace();
+    if parts.next() != Some("num_ctx") {
+      return None;
+    }
+    return parts.next()?.parse::<usize>().ok();
+  });
+  if let Some(num_ctx) = num_ctx {
+    return num_ctx;
+  }
+
+  let trained = show
+    .model_info
+    .iter()
+    .find(|(key, _)| key.ends_with(".context_length"))
+    .and_then(|(_, value)| value.as_u64())
+    .map_or(usize::MAX, |length| length as usize);
+  return OLLAMA_DEFAULT_NUM_CTX.min(trained);
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  use super::*;
+  use crate::clock::{ManualClock, SeededRng};
+
+  #[tokio::test]
+  async fn expires_cached_probes_after_a_day() {
+    let manual = Arc::new(ManualClock::new(chrono::DateTime::UNIX_EPOCH));
+    let rng = Arc::new(SeededRng::new(0));
+    clock::scope(manual.clone(), rng, async {
+      let entry = CacheEntry {
+        probed_at: clock::timestamp(),
+        capabilities: Some(Capabilities {
+          backend: Backend::LlamaCpp,
+          context_window: Some(4096),
+          logprobs: None,
+        }),
+      };
+      manual.advance(Duration::from_secs(CACHE_TTL_SECONDS as u64 - 1));
+      assert!(entry.is_fresh());
+      manual.advance(Duration::from_secs(1));
+      assert!(!entry.is_fresh());
+    })
+    .await;
+  }
+
+  #[tokio::test]
+  async fn expires_failed_probes_sooner() {
+    let manual = Arc::new(ManualClock::new(chrono::DateTime::UNIX_EPOCH));
+    let rng = Arc::new(SeededRng::new(0));
+    clock::scope(manual.clone(), rng, async {
+      let entry = CacheEntry {
+        probed_at: clock::timestamp(),
+        capabilities: None,
+      };
+      manual
+        .advance(Duration::from_secs(NEGATIVE_CACHE_TTL_SECONDS as u64 - 1));
+      assert!(entry.is_fresh());
+      manual.advance(Duration::from_secs(1));
+      assert!(!entry.is_fresh());
+    })
+    .await;
+  }
+}
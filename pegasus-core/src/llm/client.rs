@@ -0,0 +1,1718 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use tokio::sync::OnceCell;
+
+use crate::clock;
+use crate::dictionary::enforce;
+use crate::dictionary::glossary::GlossaryMode;
+use crate::dictionary::spellcheck::Spellchecker;
+use crate::dictionary::{DictionaryEntry, MatchOptions};
+use crate::input::transcription::{WhisperTranscription, WhisperWord};
+use crate::llm::alignment::{self, TokenSpan};
+use crate::llm::capabilities::{self, Backend, Capabilities};
+use crate::llm::context::{Turn, strip_carryover};
+use crate::llm::errors::{LLMError, LLMResult, OverflowAdvice};
+use crate::llm::grammar::OutputMode;
+use crate::llm::leakage;
+use crate::llm::non_speech::{NonSpeechFilter, NonSpeechPolicy};
+use crate::llm::output_limit::OutputLimit;
+use crate::llm::prompts::{
+  build_answer_retry_instruction, build_carryover_user_prompt,
+  build_chapters_system_prompt, build_chapters_user_prompt,
+  build_dictionary_section, build_reading_level_instruction,
+  build_reading_level_retry_prompt, build_refusal_retry_instruction,
+  build_summary_system_prompt, build_summary_user_prompt, build_system_prompt,
+  build_user_prompt, build_whisper_system_prompt, build_whisper_user_prompt,
+};
+use crate::llm::refusal::{self, RefusalHandling};
+use crate::llm::request::{
+  ApplyTemplateRequest, ChatCompletionRequest, ChatMessage, CompletionRequest,
+  EmbeddingsRequest,
+};
+use crate::llm::response::{
+  ApplyTemplateResponse, ChatCompletionResponse, CompletionResponse,
+  EmbeddingsResponse, TokenLogprob,
+};
+use crate::llm::tokenizer::Tokenizer;
+use crate::llm::verbatim::{self, VerbatimKinds, VerbatimTokens};
+use crate::metrics;
+use crate::network::HttpClient;
+use crate::network::errors::{NetworkError, NetworkResult};
+use crate::network::scheduler::{BalanceStrategy, Scheduler};
+use crate::output::chapters::ChapterBreak;
+use crate::output::readability::Readability;
+use crate::output::summary::Summary;
+use crate::timing::{self, Phase};
+use crate::usage;
+use crate::{dlog, elog, logging, slog, vlog};
+
+/// Delay before the first retry of a failed request, doubled for each
+/// further retry.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Grade levels a refinement may miss its target by before it is retried.
+const READING_LEVEL_TOLERANCE: f64 = 2.0;
+
+/// Fewest words for a reliable grade level; shorter texts are not retried.
+const READING_LEVEL_MIN_WORDS: usize = 50;
+
+/// Tokens reserved for the JSON answers of chapter lists and summaries.
+const STRUCTURED_ANSWER_TOKENS: usize = 512;
+
+/// Tokens a chat message takes beyond its content, for its role and
+/// delimiters.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// The API requests are sent to.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LLMApi {
+  /// OpenAI-compatible `/v1/chat/completions`
+  #[default]
+  Chat,
+  /// llama.cpp's native `/completion`, with the prompt formatted by the
+  /// model's chat template at `/apply-template`
+  Completion,
+}
+
+impl fmt::Display for LLMApi {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      LLMApi::Chat => "chat",
+      LLMApi::Completion => "completion",
+    });
+  }
+}
+
+/// An answer of the LLM.
+struct Answer {
+  /// The trimmed answer text
+  text: String,
+  /// The tokens of the text, if log probabilities were returned
+  tokens: Option<Vec<TokenSpan>>,
+}
+
+/// LLM client for text refinement using OpenAI-compatible APIs.
+///
+/// Provides methods to refine transcribed text using local or remote
+/// LLM services that support the OpenAI chat completions API format, or
+/// llama.cpp's native completion API.
+#[derive(Debug, Clone)]
+pub struct LLMClient {
+  http_client: HttpClient,
+  scheduler: Scheduler,
+  fallback_client: Option<HttpClient>,
+  model: String,
+  api_key: String,
+  api: LLMApi,
+  grammar: String,
+  constrain_output: bool,
+  target_reading_level: f64,
+  output_limit: OutputLimit,
+  stop_sequences: Vec<String>,
+  temperature: Option<f64>,
+  logprob_threshold: f64,
+  keep_verbatim_tokens: bool,
+  non_speech: NonSpeechFilter,
+  min_similarity: f64,
+  embeddings_model: String,
+  retry_answers: bool,
+  retries: u32,
+  refusal: RefusalHandling,
+  dictionary_matching: MatchOptions,
+  spellchecker: Option<Arc<Spellchecker>>,
+  glossary: GlossaryMode,
+  context_window: usize,
+  tokenizer: Tokenizer,
+  probe_capabilities: bool,
+  capabilities: OnceCell<Option<Capabilities>>,
+}
+
+impl LLMClient {
+  /// Creates a new LLMClient with the given configuration.
+  ///
+  /// # Arguments
+  ///
+  /// * `http_client` - HTTP client for the primary LLM API endpoint
+  /// * `model` - Model name to use
+  /// * `api_key` - Optional API key for authenticated endpoints
+  ///
+  /// # Returns
+  ///
+  /// A new `LLMClient` instance.
+  pub fn new(http_client: HttpClient, model: String, api_key: String) -> Self {
+    return LLMClient {
+      scheduler: Scheduler::new(
+        vec![http_client.clone()],
+        BalanceStrategy::default(),
+      ),
+      http_client,
+      fallback_client: None,
+      model,
+      api_key,
+      api: LLMApi::default(),
+      grammar: String::new(),
+      constrain_output: false,
+      target_reading_level: 0.0,
+      output_limit: OutputLimit::default(),
+      stop_sequences: Vec::new(),
+      temperature: None,
+      logprob_threshold: 0.0,
+      keep_verbatim_tokens: false,
+      non_speech: NonSpeechFilter::default(),
+      min_similarity: 0.0,
+      embeddings_model: String::new(),
+      retry_answers: false,
+      retries: 0,
+      refusal: RefusalHandling::Off,
+      dictionary_matching: MatchOptions::default(),
+      spellchecker: None,
+      glossary: GlossaryMode::default(),
+      context_window: 0,
+      tokenizer: Tokenizer::estimator(),
+      probe_capabilities: false,
+      capabilities: OnceCell::new(),
+    };
+  }
+
+  /// Sets a fallback endpoint used when the primary endpoint fails.
+  ///
+  /// # Arguments
+  ///
+  /// * `fallback_client` - HTTP client for the fallback LLM API endpoint
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_fallback(mut self, fallback_client: HttpClient) -> Self {
+    self.fallback_client = Some(fallback_client);
+    return self;
+  }
+
+  /// Sets further endpoints that share the load with the primary endpoint.
+  ///
+  /// # Arguments
+  ///
+  /// * `endpoints` - HTTP clients for equivalent LLM API endpoints
+  /// * `strategy` - How requests are distributed over the endpoints
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_endpoints(
+    mut self,
+    endpoints: Vec<HttpClient>,
+    strategy: BalanceStrategy,
+  ) -> Self {
+    let mut clients = vec![self.http_client.clone()];
+    clients.extend(endpoints);
+    self.scheduler = Scheduler::new(clients, strategy);
+    return self;
+  }
+
+  /// Sets the reading level refinements should be written at.
+  ///
+  /// # Arguments
+  ///
+  /// * `grade_level` - The target Flesch-Kincaid grade level (0 keeps the
+  ///   speaker's level)
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_target_reading_level(mut self, grade_level: f64) -> Self {
+    self.target_reading_level = grade_level;
+    return self;
+  }
+
+  /// Sets the largest answer accepted from the LLM.
+  ///
+  /// # Arguments
+  ///
+  /// * `output_limit` - The size limit and what to do with longer answers
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_output_limit(mut self, output_limit: OutputLimit) -> Self {
+    self.output_limit = output_limit;
+    return self;
+  }
+
+  /// Sets sequences at which the LLM stops generating.
+  ///
+  /// Cuts off trailing commentary such as "Note:" that chatty models add
+  /// after the refined text.
+  ///
+  /// # Arguments
+  ///
+  /// * `stop_sequences` - The stop sequences (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+    self.stop_sequences = stop_sequences;
+    return self;
+  }
+
+  /// Sets the sampling temperature sent with every request.
+  ///
+  /// # Arguments
+  ///
+  /// * `temperature` - The temperature, or `None` for the service default
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_temperature(mut self, temperature: Option<f64>) -> Self {
+    self.temperature = temperature;
+    return self;
+  }
+
+  /// Sets the model confidence needed to accept a correction of a flagged
+  /// Whisper word.
+  ///
+  /// When set, log probabilities are requested and corrections the model
+  /// gave a lower probability are reverted to the word Whisper heard.
+  ///
+  /// # Arguments
+  ///
+  /// * `threshold` - The token probability (0 disables the check)
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_logprob_threshold(mut self, threshold: f64) -> Self {
+    self.logprob_threshold = threshold;
+    return self;
+  }
+
+  /// Sets whether timestamps, speaker tags and bracketed annotations are
+  /// kept verbatim.
+  ///
+  /// The tokens are replaced with markers before plain text is sent and
+  /// spliced back into the answer (see [`VerbatimTokens`]).
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to protect verbatim tokens
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_keep_verbatim_tokens(mut self, enabled: bool) -> Self {
+    self.keep_verbatim_tokens = enabled;
+    return self;
+  }
+
+  /// Sets the least semantic similarity refinements must keep to their
+  /// input.
+  ///
+  /// The embeddings of the input and the refined text are requested from
+  /// `/v1/embeddings`; refinements whose cosine similarity falls below the
+  /// threshold are rejected, since the model likely answered a question in
+  /// the transcript instead of refining it.
+  ///
+  /// # Arguments
+  ///
+  /// * `min_similarity` - The least similarity, or 0 to disable the check
+  /// * `embeddings_model` - Model the embeddings are computed with
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_similarity_check(
+    mut self,
+    min_similarity: f64,
+    embeddings_model: String,
+  ) -> Self {
+    self.min_similarity = min_similarity;
+    self.embeddings_model = embeddings_model;
+    return self;
+  }
+
+  /// Sets whether refinements that look like answers to the transcript are
+  /// requested again (see [`leakage`]).
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to retry such refinements
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_answer_retry(mut self, enabled: bool) -> Self {
+    self.retry_answers = enabled;
+    return self;
+  }
+
+  /// Sets how often a request failing with a transient error is retried.
+  ///
+  /// # Arguments
+  ///
+  /// * `retries` - How often a request failing with a connection error,
+  ///   HTTP 429 or a 5xx status is sent again
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_retries(mut self, retries: u32) -> Self {
+    self.retries = retries;
+    return self;
+  }
+
+  /// Sets what happens when the model refuses to refine a text (see
+  /// [`refusal`]).
+  ///
+  /// # Arguments
+  ///
+  /// * `handling` - Whether refusals are retried, failed or not looked for
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_refusal_handling(mut self, handling: RefusalHandling) -> Self {
+    self.refusal = handling;
+    return self;
+  }
+
+  /// Sets what happens to emoji and non-speech markers.
+  ///
+  /// # Arguments
+  ///
+  /// * `non_speech` - The policies for emoji and non-speech markers
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_non_speech(mut self, non_speech: NonSpeechFilter) -> Self {
+    self.non_speech = non_speech;
+    return self;
+  }
+
+  /// Sets how dictionary terms are presented and enforced.
+  ///
+  /// # Arguments
+  ///
+  /// * `options` - The matching options of the dictionary
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_dictionary_matching(mut self, options: MatchOptions) -> Self {
+    self.dictionary_matching = options;
+    return self;
+  }
+
+  /// Sets the spellchecker whose words enforced dictionary terms never
+  /// replace.
+  ///
+  /// # Arguments
+  ///
+  /// * `spellchecker` - The spellchecker, or `None` for none
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_spellchecker(
+    mut self,
+    spellchecker: Option<Arc<Spellchecker>>,
+  ) -> Self {
+    self.spellchecker = spellchecker;
+    return self;
+  }
+
+  /// Sets how the prompt asks for abbreviations of glossary entries to be
+  /// written.
+  ///
+  /// # Arguments
+  ///
+  /// * `mode` - How abbreviations are written
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_glossary_mode(mut self, mode: GlossaryMode) -> Self {
+    self.glossary = mode;
+    return self;
+  }
+
+  /// Sets the context window prompts are checked against before sending.
+  ///
+  /// # Arguments
+  ///
+  /// * `context_window` - The context window in tokens (0 disables the
+  ///   check)
+  /// * `tokenizer` - The tokenizer used to count prompt tokens
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_context_window(
+    mut self,
+    context_window: usize,
+    tokenizer: Tokenizer,
+  ) -> Self {
+    self.context_window = context_window;
+    self.tokenizer = tokenizer;
+    return self;
+  }
+
+  /// Sets the API requests are sent to.
+  ///
+  /// # Arguments
+  ///
+  /// * `api` - The chat or completion API
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_api(mut self, api: LLMApi) -> Self {
+    self.api = api;
+    return self;
+  }
+
+  /// Sets a GBNF grammar constraining refined text, in place of the
+  /// generated one.
+  ///
+  /// # Arguments
+  ///
+  /// * `grammar` - The grammar (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_grammar(mut self, grammar: String) -> Self {
+    self.grammar = grammar;
+    return self;
+  }
+
+  /// Enables constraining answers with grammars generated from what each
+  /// request asks for.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to send generated grammars
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_output_constraint(mut self, enabled: bool) -> Self {
+    self.constrain_output = enabled;
+    return self;
+  }
+
+  /// Gets the grammar constraining an answer.
+  ///
+  /// # Arguments
+  ///
+  /// * `mode` - What the answer is expected to contain
+  /// * `input` - The text to refine, empty if the answer is no refinement
+  ///
+  /// # Returns
+  ///
+  /// The configured grammar for refined text, else the generated one if
+  /// enabled, else an empty string.
+  fn grammar(&self, mode: OutputMode, input: &str) -> String {
+    if mode == OutputMode::Prose && !self.grammar.is_empty() {
+      return self.grammar.clone();
+    }
+    if self.constrain_output {
+      return mode.grammar(input);
+    }
+    return String::new();
+  }
+
+  /// Builds the authentication headers of requests.
+  ///
+  /// # Returns
+  ///
+  /// The `Authorization` header if an API key is set, otherwise `None`.
+  fn headers(&self) -> Option<HashMap<String, String>> {
+    if self.api_key.is_empty() {
+      return None;
+    }
+    dlog!("Using API key authentication");
+    return Some(HashMap::from([(
+      "Authorization".to_string(),
+      format!("Bearer {}", self.api_key),
+    )]));
+  }
+
+  /// Enables detecting the context window and features of the endpoint.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to probe the endpoint before the first request
+  ///
+  /// # Returns
+  ///
+  /// The updated `LLMClient` instance.
+  pub fn with_capability_probe(mut self, enabled: bool) -> Self {
+    self.probe_capabilities = enabled;
+    return self;
+  }
+
+  /// Gets the capabilities of the endpoint, probing it on first use.
+  ///
+  /// # Returns
+  ///
+  /// The capabilities, or `None` if probing is disabled or failed.
+  async fn capabilities(&self) -> Option<&Capabilities> {
+    if !self.probe_capabilities {
+      return None;
+    }
+    return self
+      .capabilities
+      .get_or_init(|| async {
+        return capabilities::detect(
+          &self.http_client,
+          &self.model,
+          self.headers(),
+          false,
+        )
+        .await;
+      })
+      .await
+      .as_ref();
+  }
+
+  /// Probes the endpoint for its capabilities, ignoring cached results.
+  ///
+  /// # Returns
+  ///
+  /// The capabilities, or `None` if the endpoint could not be probed.
+  pub async fn probe_capabilities(&self) -> Option<Capabilities> {
+    return capabilities::detect(
+      &self.http_client,
+      &self.model,
+      self.headers(),
+      true,
+    )
+    .await;
+  }
+
+  /// Counts the tokens the dictionary adds to the system prompt.
+  fn dictionary_tokens(&self, dictionary: &[DictionaryEntry]) -> usize {
+    return self.tokenizer.count(&build_dictionary_section(
+      dictionary,
+      &self.dictionary_matching,
+      self.glossary,
+    ));
+  }
+
+  /// Estimates the tokens of an answer.
+  ///
+  /// A refinement is about as long as the text it refines; structured
+  /// answers are short whatever their input.
+  fn expected_answer_tokens(&self, mode: OutputMode, input: &str) -> usize {
+    let estimate = match mode {
+      OutputMode::Prose => self.tokenizer.count(input),
+      OutputMode::Chapters | OutputMode::Summary => STRUCTURED_ANSWER_TOKENS,
+    };
+    // No token is shorter than a character.
+    return match self.output_limit.max_characters {
+      0 => estimate,
+      max_characters => estimate.min(max_characters),
+    };
+  }
+
+  /// Checks that a prompt and its answer fit the context window.
+  ///
+  /// Servers truncate prompts that do not fit, which silently drops part
+  /// of the transcript, and stop answers that reach the end of the
+  /// window, so requests that cannot fit are rejected before sending.
+  /// Without a configured context window, the detected one is used.
+  ///
+  /// # Arguments
+  ///
+  /// * `messages` - The chat messages of the request
+  /// * `answer` - The tokens expected in the answer
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<()>` that is a `ContextOverflow` error if the request
+  /// does not fit.
+  async fn check_context_window(
+    &self,
+    messages: &[ChatMessage],
+    answer: usize,
+  ) -> LLMResult<()> {
+    let context_window = if self.context_window > 0 {
+      Some(self.context_window)
+    } else {
+      match self.capabilities().await {
+        Some(Capabilities {
+          context_window: Some(context_window),
+          ..
+        }) => Some(*context_window),
+        _ => None,
+      }
+    };
+    if context_window.is_none() && !logging::is_verbose() {
+      return Ok(());
+    }
+
+    let needed: usize = messages
+      .iter()
+      .map(|message| {
+        self.tokenizer.count(message.content()) + MESSAGE_OVERHEAD_TOKENS
+      })
+      .sum();
+    slog!(
+      "prompt_size",
+      messages = messages.len(),
+      tokens = needed,
+      answer_tokens = answer,
+      context_window = context_window
+        .map_or_else(|| String::from("unknown"), |tokens| tokens.to_string())
+    );
+    let Some(context_window) = context_window else {
+      return Ok(());
+    };
+    if needed + answer > context_window {
+      slog!(
+        "truncation",
+        decision = "reject",
+        reason = "context_overflow",
+        tokens = needed,
+        answer_tokens = answer,
+        context_window = context_window
+      );
+      return Err(LLMError::ContextOverflow {
+        needed,
+        answer,
+        window: context_window,
+        advice: OverflowAdvice::ShortenInput,
+      });
+    }
+    return Ok(());
+  }
+
+  /// Executes a refinement, asking for a rewrite if it misses the target
+  /// reading level by more than the tolerance.
+  ///
+  /// # Arguments
+  ///
+  /// * `system_prompt` - The system prompt for the LLM
+  /// * `context` - Earlier refinements, sent as previous chat turns
+  /// * `user_prompt` - The user prompt containing text to refine
+  /// * `logprobs` - Whether to request token log probabilities
+  /// * `input` - The text to refine
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<Answer>` containing the refined text or an error.
+  async fn execute_at_reading_level(
+    &self,
+    system_prompt: String,
+    context: &[Turn],
+    user_prompt: String,
+    logprobs: bool,
+    input: &str,
+  ) -> LLMResult<Answer> {
+    if self.target_reading_level <= 0.0 {
+      let messages = build_messages(system_prompt, context, user_prompt);
+      return self
+        .execute_request(messages, logprobs, OutputMode::Prose, input)
+        .await;
+    }
+
+    let system_prompt = format!(
+      "{}{}",
+      system_prompt,
+      build_reading_level_instruction(self.target_reading_level)
+    );
+    let mut messages = build_messages(system_prompt, context, user_prompt);
+    let answer = self
+      .execute_request(messages.clone(), logprobs, OutputMode::Prose, input)
+      .await?;
+
+    let readability = Readability::measure(&answer.text);
+    let missed_by =
+      (readability.flesch_kincaid_grade - self.target_reading_level).abs();
+    if readability.words < READING_LEVEL_MIN_WORDS
+      || missed_by <= READING_LEVEL_TOLERANCE
+    {
+      return Ok(answer);
+    }
+
+    vlog!(
+      "Refined text reads at grade {:.1} instead of {:.1}, retrying",
+      readability.flesch_kincaid_grade,
+      self.target_reading_level
+    );
+    messages.push(ChatMessage::new("assistant".to_string(), answer.text));
+    messages.push(ChatMessage::new(
+      "user".to_string(),
+      build_reading_level_retry_prompt(
+        self.target_reading_level,
+        readability.flesch_kincaid_grade,
+      ),
+    ));
+    return self
+      .execute_request(messages, logprobs, OutputMode::Prose, input)
+      .await;
+  }
+
+  /// Executes a refinement, requesting it again with a reinforced system
+  /// prompt if it looks like an answer to the transcript.
+  ///
+  /// # Arguments
+  ///
+  /// * `system_prompt` - The system prompt for the LLM
+  /// * `context` - Earlier refinements, sent as previous chat turns
+  /// * `user_prompt` - The user prompt containing text to refine
+  /// * `logprobs` - Whether to request token log probabilities
+  /// * `input` - The text to refine, to compare the answer with
+  /// * `carryover` - The end of the previous chunk the answer may repeat
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<Answer>` containing the refined text or an error.
+  async fn execute_unanswered(
+    &self,
+    system_prompt: String,
+    context: &[Turn],
+    user_prompt: String,
+    logprobs: bool,
+    input: &str,
+    carryover: &str,
+  ) -> LLMResult<Answer> {
+    let answer = self
+      .execute_unrefused(
+        system_prompt.clone(),
+        context,
+        user_prompt.clone(),
+        logprobs,
+        input,
+        carryover,
+      )
+      .await?;
+    if !self.retry_answers {
+      return Ok(answer);
+    }
+    let Some(leakage) =
+      leakage::detect(input, &strip_carryover(&answer.text, carryover))
+    else {
+      return Ok(answer);
+    };
+
+    vlog!(
+      "Refinement looks like an answer to the transcript ({}), retrying",
+      leakage
+    );
+    let system_prompt =
+      format!("{}{}", system_prompt, build_answer_retry_instruction());
+    let answer = self
+      .execute_unrefused(
+        system_prompt,
+        context,
+        user_prompt,
+        logprobs,
+        input,
+        carryover,
+      )
+      .await?;
+    if let Some(leakage) =
+      leakage::detect(input, &strip_carryover(&answer.text, carryover))
+    {
+      elog!(
+        logging::WARNING,
+        "Refinement still looks like an answer to the transcript: {}",
+        leakage
+      );
+    }
+    return Ok(answer);
+  }
+
+  /// Executes a refinement, handling a refusal of the model as
+  /// `[llm] refusal` asks for.
+  ///
+  /// # Arguments
+  ///
+  /// * `system_prompt` - The system prompt for the LLM
+  /// * `context` - Earlier refinements, sent as previous chat turns
+  /// * `user_prompt` - The user prompt containing text to refine
+  /// * `logprobs` - Whether to request token log probabilities
+  /// * `input` - The text to refine, to compare the answer with
+  /// * `carryover` - The end of the previous chunk the answer may repeat
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<Answer>` containing the refined text, or
+  /// `LLMError::Refusal` if the model refused for good.
+  async fn execute_unrefused(
+    &self,
+    system_prompt: String,
+    context: &[Turn],
+    user_prompt: String,
+    logprobs: bool,
+    input: &str,
+    carryover: &str,
+  ) -> LLMResult<Answer> {
+    let answer = self
+      .execute_at_reading_level(
+        system_prompt.clone(),
+        context,
+        user_prompt.clone(),
+        logprobs,
+        input,
+      )
+      .await?;
+    if self.refusal == RefusalHandling::Off {
+      return Ok(answer);
+    }
+    let Some(kind) =
+      refusal::detect(input, &strip_carryover(&answer.text, carryover))
+    else {
+      return Ok(answer);
+    };
+    if self.refusal == RefusalHandling::Fail {
+      return Err(LLMError::Refusal(kind));
+    }
+
+    vlog!(
+      "The model refused to refine the text ({} refusal), retrying",
+      kind
+    );
+    let system_prompt =
+      format!("{}{}", system_prompt, build_refusal_retry_instruction());
+    let answer = self
+      .execute_at_reading_level(
+        system_prompt,
+        context,
+        user_prompt,
+        logprobs,
+        input,
+      )
+      .await?;
+    if let Some(kind) =
+      refusal::detect(input, &strip_carryover(&answer.text, carryover))
+    {
+      return Err(LLMError::Refusal(kind));
+    }
+    return Ok(answer);
+  }
+
+  /// Executes the LLM refinement request with given prompts.
+  ///
+  /// # Arguments
+  ///
+  /// * `system_prompt` - The system prompt for the LLM
+  /// * `context` - Earlier refinements, sent as previous chat turns
+  /// * `user_prompt` - The user prompt containing text to refine
+  /// * `mode` - What the answer is expected to contain
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<String>` containing the refined text or an error.
+  async fn execute_refinement(
+    &self,
+    system_prompt: String,
+    context: &[Turn],
+    user_prompt: String,
+    mode: OutputMode,
+  ) -> LLMResult<String> {
+    let messages = build_messages(system_prompt, context, user_prompt);
+    return self.execute_messages(messages, mode).await;
+  }
+
+  /// Executes a chat completion request with the given messages.
+  ///
+  /// # Arguments
+  ///
+  /// * `messages` - The chat messages, starting with the system prompt
+  /// * `mode` - What the answer is expected to contain
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<String>` containing the answer or an error.
+  async fn execute_messages(
+    &self,
+    messages: Vec<ChatMessage>,
+    mode: OutputMode,
+  ) -> LLMResult<String> {
+    return Ok(self.execute_request(messages, false, mode, "").await?.text);
+  }
+
+  /// Executes a request with the given messages, optionally with token
+  /// log probabilities.
+  ///
+  /// # Arguments
+  ///
+  /// * `messages` - The chat messages, starting with the system prompt
+  /// * `logprobs` - Whether to request token log probabilities
+  /// * `mode` - What the answer is expected to contain
+  /// * `input` - The text to refine, empty if the answer is no refinement
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<Answer>` containing the answer, with its tokens if
+  /// requested and returned by the service, or an error.
+  async fn execute_request(
+    &self,
+    messages: Vec<ChatMessage>,
+    logprobs: bool,
+    mode: OutputMode,
+    input: &str,
+  ) -> LLMResult<Answer> {
+    let answer = self.expected_answer_tokens(mode, input);
+    self.check_context_window(&messages, answer).await?;
+
+    let grammar = self.grammar(mode, input);
+    let (content, tokens) = match self.api {
+      LLMApi::Chat => self.complete_chat(messages, logprobs, grammar).await?,
+      LLMApi::Completion => {
+        if logprobs {
+          vlog!("The completion API does not return log probabilities");
+        }
+        (self.complete_prompt(&messages, grammar).await?, None)
+      }
+    };
+
+    let _timer = timing::start(Phase::PostProcessing);
+    let refined_text = content.trim().to_string();
+    if refined_text.is_empty() {
+      return Err(LLMError::RefinementFailed(
+        "LLM returned empty content".to_string(),
+      ));
+    }
+
+    return Ok(Answer {
+      text: self.output_limit.apply(refined_text)?,
+      tokens,
+    });
+  }
+
+  /// Sends messages to the chat completions API.
+  ///
+  /// # Arguments
+  ///
+  /// * `messages` - The chat messages, starting with the system prompt
+  /// * `logprobs` - Whether to request token log probabilities
+  /// * `grammar` - GBNF grammar of the answer (none if empty), only sent
+  ///   to llama.cpp
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult` containing the untrimmed answer and its tokens, if
+  /// returned, or an error.
+  async fn complete_chat(
+    &self,
+    messages: Vec<ChatMessage>,
+    logprobs: bool,
+    grammar: String,
+  ) -> LLMResult<(String, Option<Vec<TokenSpan>>)> {
+    let grammar = match self.capabilities().await {
+      _ if grammar.is_empty() => grammar,
+      Some(Capabilities {
+        backend: Backend::LlamaCpp,
+        ..
+      }) => grammar,
+      _ => {
+        dlog!("Endpoint is not known to be llama.cpp; sending no grammar");
+        String::new()
+      }
+    };
+
+    let logprobs = logprobs
+      && match self.capabilities().await {
+        Some(Capabilities {
+          logprobs: Some(false),
+          ..
+        }) => {
+          vlog!("Endpoint does not return log probabilities; not requesting");
+          false
+        }
+        _ => true,
+      };
+
+    let prompt_timer = timing::start(Phase::Prompt);
+    let request = ChatCompletionRequest::new(self.model.clone(), messages)
+      .with_stop(self.stop_sequences.clone())
+      .with_temperature(self.temperature)
+      .with_logprobs(logprobs)
+      .with_grammar(grammar);
+    drop(prompt_timer);
+
+    let network_timer = timing::start(Phase::Network);
+    let completion: ChatCompletionResponse =
+      self.post(&request, "v1/chat/completions").await?;
+    drop(network_timer);
+
+    if let Some(usage) = &completion.usage {
+      metrics::record_tokens(usage.prompt_tokens, usage.completion_tokens);
+    }
+    let (prompt_tokens, completion_tokens) =
+      completion.usage.as_ref().map_or((0, 0), |usage| {
+        (usage.prompt_tokens, usage.completion_tokens)
+      });
+    usage::record_request(prompt_tokens, completion_tokens);
+
+    let choice = completion.choices.into_iter().next().ok_or_else(|| {
+      LLMError::InvalidResponse("No choices in response".to_string())
+    })?;
+    let content = choice.message.content;
+    let tokens = choice
+      .logprobs
+      .and_then(|logprobs| logprobs.content)
+      .and_then(|tokens| token_spans(&tokens, &content));
+    return Ok((content, tokens));
+  }
+
+  /// Sends messages to llama.cpp's completion API, formatted as one prompt
+  /// by the model's chat template.
+  ///
+  /// # Arguments
+  ///
+  /// * `messages` - The chat messages, starting with the system prompt
+  /// * `grammar` - GBNF grammar of the answer (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<String>` containing the untrimmed answer or an error.
+  async fn complete_prompt(
+    &self,
+    messages: &[ChatMessage],
+    grammar: String,
+  ) -> LLMResult<String> {
+    let network_timer = timing::start(Phase::Network);
+    let template: ApplyTemplateResponse = self
+      .post(&ApplyTemplateRequest { messages }, "apply-template")
+      .await?;
+    drop(network_timer);
+
+    let prompt_timer = timing::start(Phase::Prompt);
+    // No token is shorter than a character, so the output limit also
+    // bounds the number of tokens worth generating.
+    let n_predict = match self.output_limit.max_characters {
+      0 => -1,
+      max_characters => i64::try_from(max_characters).unwrap_or(-1),
+    };
+    let request = CompletionRequest::new(template.prompt, n_predict)
+      .with_stop(self.stop_sequences.clone())
+      .with_temperature(self.temperature)
+      .with_grammar(grammar);
+    drop(prompt_timer);
+
+    let _timer = timing::start(Phase::Network);
+    let completion: CompletionResponse =
+      self.post(&request, "completion").await?;
+    metrics::record_tokens(
+      completion.tokens_evaluated,
+      completion.tokens_predicted,
+    );
+    usage::record_request(
+      completion.tokens_evaluated,
+      completion.tokens_predicted,
+    );
+    return Ok(completion.content);
+  }
+
+  /// Posts a request, retrying it after transient failures.
+  ///
+  /// The delay before a retry starts at [`RETRY_DELAY`] and doubles each
+  /// time.
+  ///
+  /// # Arguments
+  ///
+  /// * `body` - The JSON request body
+  /// * `endpoint` - Endpoint path to append to the base URL
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<T>` containing the decoded response or an error.
+  async fn post<T, B>(&self, body: &B, endpoint: &str) -> LLMResult<T>
+  where
+    T: serde::de::DeserializeOwned,
+    B: serde::Serialize,
+  {
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+      match self.post_once(body, endpoint).await {
+        Err(e) if e.is_transient() && attempt < self.retries => {
+          attempt += 1;
+          elog!(
+            logging::WARNING,
+            "LLM request failed ({}), retrying in {}ms ({}/{})",
+            e,
+            delay.as_millis(),
+            attempt,
+            self.retries
+          );
+          clock::sleep(delay).await;
+          delay *= 2;
+        }
+        result => return result.map_err(request_error),
+      }
+    }
+  }
+
+  /// Posts a request to the endpoint picked by the scheduler, trying the
+  /// other endpoints and then the fallback endpoint if it fails.
+  ///
+  /// # Arguments
+  ///
+  /// * `body` - The JSON request body
+  /// * `endpoint` - Endpoint path to append to the base URL
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<T>` containing the decoded response or an error.
+  async fn post_once<T, B>(&self, body: &B, endpoint: &str) -> NetworkResult<T>
+  where
+    T: serde::de::DeserializeOwned,
+    B: serde::Serialize,
+  {
+    let headers = self.headers();
+    let mut tried = Vec::new();
+    let mut last_error = None;
+    while let Some(lease) = self.scheduler.acquire(&tried) {
+      tried.push(lease.index());
+      if self.scheduler.len() > 1 {
+        dlog!("Sending request to {}", lease.client().base_url());
+      }
+      let result = lease
+        .client()
+        .post_with_json::<T, _>(body, endpoint, headers.clone())
+        .await;
+      metrics::record_llm_request(result.is_ok());
+      match result {
+        Ok(response) => {
+          lease.record(true);
+          return Ok(response);
+        }
+        Err(e @ NetworkError::DecodeError) => {
+          lease.record(true);
+          return Err(e);
+        }
+        Err(e) => {
+          lease.record(false);
+          last_error = Some(e);
+        }
+      }
+    }
+
+    let e = last_error.unwrap_or(NetworkError::RequestFailed);
+    let Some(fallback) = &self.fallback_client else {
+      return Err(e);
+    };
+    vlog!(
+      "Primary endpoint failed ({}), switching to fallback: {}",
+      e,
+      fallback.base_url()
+    );
+    let fallback_result = fallback
+      .post_with_json::<T, _>(body, endpoint, headers)
+      .await;
+    metrics::record_llm_request(fallback_result.is_ok());
+    return fallback_result;
+  }
+
+  /// Refines the input text using the LLM.
+  ///
+  /// Sends the text to the LLM with appropriate system and user prompts,
+  /// including dictionary words to reduce hallucination. Near matches of
+  /// enforced dictionary terms in the answer are corrected afterwards.
+  ///
+  /// # Arguments
+  ///
+  /// * `input_text` - The transcription text to refine
+  /// * `dictionary` - Entries of the user's custom dictionary
+  /// * `context` - Earlier refinements to keep terminology consistent with
+  /// * `carryover` - The end of the previous chunk, sent as context only
+  ///   (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<String>` containing the refined text or an error.
+  pub async fn refine_text(
+    &self,
+    input_text: &str,
+    dictionary: &[DictionaryEntry],
+    context: &[Turn],
+    carryover: &str,
+  ) -> LLMResult<String> {
+    dlog!("Preparing LLM request for text refinement");
+    if !context.is_empty() {
+      vlog!("Including {} earlier paragraphs as context", context.len());
+    }
+
+    let timer = timing::start(Phase::Prompt);
+    let filtered_text = self.non_speech.apply(input_text);
+    let kinds = VerbatimKinds {
+      transcript: self.keep_verbatim_tokens,
+      emoji: self.non_speech.emoji == NonSpeechPolicy::Keep,
+      markers: self.non_speech.markers == NonSpeechPolicy::Keep,
+    };
+    let (protected_text, verbatim) = if kinds.any() {
+      VerbatimTokens::protect(&filtered_text, kinds)
+    } else {
+      (filtered_text, VerbatimTokens::default())
+    };
+    let mut system_prompt =
+      build_system_prompt(dictionary, &self.dictionary_matching, self.glossary);
+    if !verbatim.is_empty() {
+      system_prompt.push_str(&verbatim::build_instruction());
+    }
+    let user_prompt = if carryover.is_empty() {
+      build_user_prompt(&protected_text)
+    } else {
+      build_carryover_user_prompt(&protected_text, carryover)
+    };
+    drop(timer);
+    slog!(
+      "prompt",
+      kind = "text",
+      system_tokens = self.tokenizer.count(&system_prompt),
+      user_tokens = self.tokenizer.count(&user_prompt),
+      dictionary_entries = dictionary.len(),
+      dictionary_tokens = self.dictionary_tokens(dictionary),
+      context_turns = context.len(),
+      carryover_tokens = self.tokenizer.count(carryover),
+      verbatim = !verbatim.is_empty()
+    );
+
+    // Callers split long texts into chunks of `[input] chunk_size`.
+    let answer = self
+      .execute_unanswered(
+        system_prompt,
+        context,
+        user_prompt,
+        false,
+        input_text,
+        carryover,
+      )
+      .await
+      .map_err(|e| match e {
+        LLMError::ContextOverflow {
+          needed,
+          answer,
+          window,
+          ..
+        } => LLMError::ContextOverflow {
+          needed,
+          answer,
+          window,
+          advice: OverflowAdvice::LowerChunkSize,
+        },
+        e => e,
+      })?;
+
+    vlog!("Text refinement completed successfully");
+
+    let mut refined = strip_carryover(&answer.text, carryover);
+    if !verbatim.is_empty() {
+      refined = verbatim.restore(&refined);
+    }
+    let refined = self.non_speech.apply(&refined);
+    let refined = enforce::apply(
+      &refined,
+      dictionary,
+      &self.dictionary_matching,
+      self.spellchecker.as_deref(),
+    );
+    self.check_similarity(input_text, &refined).await?;
+    usage::record_words(input_text, &refined);
+    return Ok(refined);
+  }
+
+  /// Refines Whisper transcription using confidence scores to reduce hallucination.
+  ///
+  /// Sends the transcription to the LLM with low-confidence words flagged,
+  /// allowing the LLM to make better decisions about ambiguous words.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The Whisper transcription data with confidence scores
+  /// * `dictionary` - Entries of the user's custom dictionary
+  /// * `probability_threshold` - Words below this threshold will be flagged
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<String>` containing the refined text or an error.
+  pub async fn refine_whisper_transcription(
+    &self,
+    transcription: &WhisperTranscription,
+    dictionary: &[DictionaryEntry],
+    probability_threshold: f64,
+  ) -> LLMResult<String> {
+    dlog!("Preparing LLM request for Whisper transcription refinement");
+
+    let timer = timing::start(Phase::Prompt);
+    let system_prompt = build_whisper_system_prompt(
+      dictionary,
+      &self.dictionary_matching,
+      self.glossary,
+    );
+    let user_prompt =
+      build_whisper_user_prompt(transcription, probability_threshold);
+    drop(timer);
+    slog!(
+      "prompt",
+      kind = "whisper",
+      system_tokens = self.tokenizer.count(&system_prompt),
+      user_tokens = self.tokenizer.count(&user_prompt),
+      dictionary_entries = dictionary.len(),
+      dictionary_tokens = self.dictionary_tokens(dictionary),
+      threshold = probability_threshold,
+      flagged_words = transcription
+        .get_low_probability_words(probability_threshold)
+        .len()
+    );
+
+    let logprobs = self.logprob_threshold > 0.0;
+    let answer = self
+      .execute_unanswered(
+        system_prompt,
+        &[],
+        user_prompt,
+        logprobs,
+        &transcription.full_text(),
+        "",
+      )
+      .await?;
+
+    vlog!("Whisper transcription refinement completed successfully");
+
+    let refined = if logprobs {
+      self.check_corrections(answer, transcription, probability_threshold)
+    } else {
+      answer.text
+    };
+    let refined = self.non_speech.apply(&refined);
+    let refined = enforce::apply(
+      &refined,
+      dictionary,
+      &self.dictionary_matching,
+      self.spellchecker.as_deref(),
+    );
+    let original = transcription.full_text();
+    self.check_similarity(&original, &refined).await?;
+    usage::record_words(&original, &refined);
+    return Ok(refined);
+  }
+
+  /// Rejects a refinement whose meaning drifted from its input.
+  ///
+  /// If the embeddings cannot be computed, the refinement is kept with a
+  /// warning, so an unavailable endpoint does not fail the run.
+  ///
+  /// # Arguments
+  ///
+  /// * `original` - The text sent for refinement
+  /// * `refined` - The refined text
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<()>` indicating the refinement is kept, or
+  /// `LLMError::LowSimilarity` if the check is enabled and it fails.
+  async fn check_similarity(
+    &self,
+    original: &str,
+    refined: &str,
+  ) -> LLMResult<()> {
+    if self.min_similarity <= 0.0 || original.trim().is_empty() {
+      return Ok(());
+    }
+
+    let request = EmbeddingsRequest {
+      model: &self.embeddings_model,
+      input: [original, refined],
+    };
+    let network_timer = timing::start(Phase::Network);
+    let result: LLMResult<EmbeddingsResponse> =
+      self.post(&request, "v1/embeddings").await;
+    drop(network_timer);
+    let mut data = match result {
+      Ok(response) => response.data,
+      Err(e) => {
+        elog!(
+          logging::WARNING,
+          "Skipping the similarity check, embeddings failed: {}",
+          e
+        );
+        return Ok(());
+      }
+    };
+    data.sort_by_key(|embedding| embedding.index);
+    let similarity = match data.as_slice() {
+      [original, refined, ..] => {
+        cosine_similarity(&original.embedding, &refined.embedding)
+      }
+      _ => None,
+    };
+    let Some(similarity) = similarity else {
+      elog!(
+        logging::WARNING,
+        "Skipping the similarity check, the embeddings endpoint returned no \
+         usable vectors"
+      );
+      return Ok(());
+    };
+
+    vlog!(
+      "Refined text has a similarity of {:.3} to its input",
+      similarity
+    );
+    if similarity < self.min_similarity {
+      return Err(LLMError::LowSimilarity {
+        similarity,
+        threshold: self.min_similarity,
+      });
+    }
+    return Ok(());
+  }
+
+  /// Reverts corrections of flagged words the model was unsure about.
+  ///
+  /// # Arguments
+  ///
+  /// * `answer` - The refinement, with its tokens if returned
+  /// * `transcription` - The refined Whisper transcription
+  /// * `probability_threshold` - Words below this threshold were flagged
+  ///
+  /// # Returns
+  ///
+  /// The refined text with unconfident corrections reverted, or unchanged
+  /// if the service returned no log probabilities.
+  fn check_corrections(
+    &self,
+    answer: Answer,
+    transcription: &WhisperTranscription,
+    probability_threshold: f64,
+  ) -> String {
+    let Some(tokens) = answer.tokens else {
+      vlog!("LLM returned no log probabilities; keeping all corrections");
+      return answer.text;
+    };
+
+    let words: Vec<WhisperWord> = transcription
+      .segments
+      .iter()
+      .flatten()
+      .flat_map(|segment| segment.words.iter().cloned())
+      .collect();
+    return match alignment::revert_unconfident_corrections(
+      &answer.text,
+      &tokens,
+      &words,
+      probability_threshold,
+      self.logprob_threshold,
+    ) {
+      Some((text, reverted)) => {
+        vlog!(
+          "Reverted {} unconfident corrections of flagged words",
+          reverted
+        );
+        text
+      }
+      None => {
+        vlog!("Transcription too long to cross-check corrections");
+        answer.text
+      }
+    };
+  }
+
+  /// Asks the LLM where the chapters of a transcription start.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The Whisper transcription with segments
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<Vec<ChapterBreak>>` containing the proposed chapter
+  /// starts as returned by the LLM, or an error.
+  pub async fn find_chapters(
+    &self,
+    transcription: &WhisperTranscription,
+  ) -> LLMResult<Vec<ChapterBreak>> {
+    dlog!("Preparing LLM request for chapters");
+
+    let timer = timing::start(Phase::Prompt);
+    let system_prompt = build_chapters_system_prompt();
+    let user_prompt = build_chapters_user_prompt(transcription);
+    drop(timer);
+
+    let content = self
+      .execute_refinement(system_prompt, &[], user_prompt, OutputMode::Chapters)
+      .await?;
+
+    let breaks: Vec<ChapterBreak> =
+      parse_json_answer(&content, ('[', ']'), "chapter list")?;
+
+    vlog!("LLM proposed {} chapters", breaks.len());
+
+    return Ok(breaks);
+  }
+
+  /// Generates a title and short summary of a refined text.
+  ///
+  /// # Arguments
+  ///
+  /// * `refined_text` - The refined transcript
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<Summary>` containing the title and summary, or an error.
+  pub async fn summarize(&self, refined_text: &str) -> LLMResult<Summary> {
+    dlog!("Preparing LLM request for title and summary");
+
+    let timer = timing::start(Phase::Prompt);
+    let system_prompt = build_summary_system_prompt();
+    let user_prompt = build_summary_user_prompt(refined_text);
+    drop(timer);
+
+    let content = self
+      .execute_refinement(system_prompt, &[], user_prompt, OutputMode::Summary)
+      .await?;
+
+    let summary: Summary = parse_json_answer(&content, ('{', '}'), "summary")?;
+
+    vlog!("Generated title: {}", summary.title);
+
+    return Ok(summary);
+  }
+}
+
+/// Parses the JSON value of a structured answer.
+///
+/// Models often wrap the value in a code fence or a sentence, so the text
+/// from the first opening to the last closing delimiter is parsed.
+///
+/// # Arguments
+///
+/// * `content` - The answer
+/// * `delimiters` - The delimiters of the value, `[` and `]` or `{` and `}`
+/// * `what` - What the value is, for the error message
+///
+/// # Returns
+///
+/// A `LLMResult<T>` containing the value, or an error if the answer holds
+/// no valid value.
+fn parse_json_answer<T: DeserializeOwned>(
+  content: &str,
+  (open, close): (char, char),
+  what: &str,
+) -> LLMResult<T> {
+  let json = match (content.find(open), content.rfind(close)) {
+    (Some(start), Some(end)) if start < end => &content[start..=end],
+    _ => content,
+  };
+  return serde_json::from_str(json).map_err(|e| {
+    LLMError::InvalidResponse(format!("Invalid {}: {}", what, e))
+  });
+}
+
+/// Locates the tokens of an answer in its trimmed text.
+///
+/// # Arguments
+///
+/// * `tokens` - The tokens with their log probabilities
+/// * `content` - The untrimmed answer
+///
+/// # Returns
+///
+/// The byte ranges and probabilities of the tokens in the trimmed answer,
+/// or `None` if the tokens do not spell out the answer.
+fn token_spans(
+  tokens: &[TokenLogprob],
+  content: &str,
+) -> Option<Vec<TokenSpan>> {
+  let leading = content.len() - content.trim_start().len();
+  let mut spans = Vec::with_capacity(tokens.len());
+  let mut offset = 0;
+  for token in tokens {
+    let end = offset + token.token.len();
+    if content.get(offset..end) != Some(token.token.as_str()) {
+      return None;
+    }
+    spans.push(TokenSpan {
+      start: offset.saturating_sub(leading),
+      end: end.saturating_sub(leading),
+      probability: token.logprob.exp(),
+    });
+    offset = end;
+  }
+  return Some(spans);
+}
+
+/// Computes the cosine similarity of two vectors.
+///
+/// # Returns
+///
+/// The similarity between -1 and 1, or `None` if the vectors differ in
+/// length or one of them is zero.
+fn cosine_similarity(a: &[f64], b: &[f64]) -> Option<f64> {
+  if a.len() != b.len() || a.is_empty() {
+    return None;
+  }
+  let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+  let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+  let norm_b = b.iter().map(|y| y * y).sum::<f64>().sqrt();
+  if norm_a == 0.0 || norm_b == 0.0 {
+    return None;
+  }
+  return Some(dot / (norm_a * norm_b));
+}
+
+/// Builds the chat messages of a refinement request.
+///
+/// # Arguments
+///
+/// * `system_prompt` - The system prompt for the LLM
+/// * `context` - Earlier refinements, sent as previous chat turns
+/// * `user_prompt` - The user prompt containing text to refine
+///
+/// # Returns
+///
+/// The system prompt, a user and assistant message per earlier
+/// refinement, then the user prompt.
+fn build_messages(
+  system_prompt: String,
+  context: &[Turn],
+  user_prompt: String,
+) -> Vec<ChatMessage> {
+  let mut messages =
+    vec![ChatMessage::new("system".to_string(), system_prompt)];
+  for turn in context {
+    messages.push(ChatMessage::new(
+      "user".to_string(),
+      build_user_prompt(&turn.original),
+    ));
+    messages.push(ChatMessage::new(
+      "assistant".to_string(),
+      turn.refined.clone(),
+    ));
+  }
+  messages.push(ChatMessage::new("user".to_string(), user_prompt));
+  return messages;
+}
+
+/// Maps a failed request to an LLM error.
+///
+/// A response that cannot be decoded came from the service, so it is
+/// reported as an invalid response rather than a failed request.
+fn request_error(error: NetworkError) -> LLMError {
+  return match error {
+    NetworkError::DecodeError => LLMError::InvalidResponse(error.to_string()),
+    error => LLMError::ApiRequestFailed(error),
+  };
+}
@@ -0,0 +1,183 @@
+//! Conversation context carried across consecutive refinements.
+//!
+//! In long dictation sessions each paragraph is refined with its own
+//! request, so the model cannot see how earlier paragraphs spelled names or
+//! which style they settled on. [`ConversationContext`] remembers the most
+//! recent paragraphs and their refinements, which are sent as earlier chat
+//! turns with the next request.
+//!
+//! Within a single long input, each chunk additionally carries the last
+//! sentences of the previous chunk as context-only text, so pronouns and
+//! terminology stay consistent across chunk boundaries.
+
+use std::collections::VecDeque;
+
+use crate::input::sentences;
+use crate::llm::alignment::normalize;
+use crate::slog;
+
+/// A previously refined paragraph.
+#[derive(Debug, Clone)]
+pub struct Turn {
+  pub original: String,
+  pub refined: String,
+}
+
+/// The most recent refinements of a session.
+///
+/// At most `max_turns` paragraphs are remembered, and only the newest of
+/// them that fit in `max_characters` are sent, so the context cannot crowd
+/// the text being refined out of the model's context window.
+#[derive(Debug, Clone, Default)]
+pub struct ConversationContext {
+  turns: VecDeque<Turn>,
+  max_turns: usize,
+  max_characters: usize,
+}
+
+impl ConversationContext {
+  /// Creates an empty context.
+  ///
+  /// # Arguments
+  ///
+  /// * `max_turns` - How many paragraphs to remember (0 disables context)
+  /// * `max_characters` - Budget for the paragraphs sent with a request
+  ///
+  /// # Returns
+  ///
+  /// A new `ConversationContext` instance.
+  pub fn new(max_turns: usize, max_characters: usize) -> Self {
+    return ConversationContext {
+      turns: VecDeque::new(),
+      max_turns,
+      max_characters,
+    };
+  }
+
+  /// Remembers a refined paragraph, forgetting the oldest one if full.
+  ///
+  /// # Arguments
+  ///
+  /// * `original` - The text that was refined
+  /// * `refined` - The refined text
+  pub fn push(&mut self, original: String, refined: String) {
+    if self.max_turns == 0 {
+      return;
+    }
+    self.turns.push_back(Turn { original, refined });
+    while self.turns.len() > self.max_turns {
+      self.turns.pop_front();
+    }
+  }
+
+  /// Forgets the most recently refined paragraph.
+  pub fn pop(&mut self) {
+    self.turns.pop_back();
+  }
+
+  /// Gets the paragraphs to send with the next request.
+  ///
+  /// # Returns
+  ///
+  /// The newest paragraphs whose combined length fits the character
+  /// budget, oldest first.
+  pub fn turns(&self) -> Vec<Turn> {
+    let mut characters = 0;
+    let mut turns: Vec<Turn> = self
+      .turns
+      .iter()
+      .rev()
+      .take_while(|turn| {
+        characters +=
+          turn.original.chars().count() + turn.refined.chars().count();
+        return characters <= self.max_characters;
+      })
+      .cloned()
+      .collect();
+    turns.reverse();
+    if turns.len() < self.turns.len() {
+      slog!(
+        "truncation",
+        decision = "drop_context",
+        reason = "context_budget",
+        remembered = self.turns.len(),
+        sent = turns.len(),
+        budget_characters = self.max_characters
+      );
+    }
+    return turns;
+  }
+}
+
+/// Gets the last sentences of a refined chunk, to carry over as context
+/// for the next chunk.
+///
+/// # Arguments
+///
+/// * `text` - The refined chunk
+/// * `sentences` - How many sentences to carry over (0 for none)
+///
+/// # Returns
+///
+/// The last `sentences` sentences of the text, or all of it if it has
+/// fewer.
+pub fn carryover(text: &str, sentences: usize) -> String {
+  if sentences == 0 {
+    return String::new();
+  }
+
+  let starts = sentences::sentence_starts(text);
+  let start = starts[starts.len().saturating_sub(sentences)];
+  return text[start..].trim().to_string();
+}
+
+/// Removes carried-over context the model repeated at the start of its
+/// answer.
+///
+/// The context is found with or without the `<context>` tags it was sent
+/// in; words are compared without case and punctuation, so it is found
+/// even if the model touched it up.
+///
+/// # Arguments
+///
+/// * `refined` - The refined chunk
+/// * `carryover` - The context sent with the chunk
+///
+/// # Returns
+///
+/// The refined chunk without the repeated context.
+pub fn strip_carryover(refined: &str, carryover: &str) -> String {
+  let lowercase = refined.to_ascii_lowercase();
+  if lowercase.trim_start().starts_with("<context>")
+    && let Some(end) = lowercase.find("</context>")
+  {
+    return refined[end + "</context>".len()..].trim_start().to_string();
+  }
+
+  let expected: Vec<String> = carryover
+    .split_whitespace()
+    .map(normalize)
+    .filter(|word| !word.is_empty())
+    .collect();
+  if expected.is_empty() {
+    return refined.to_string();
+  }
+
+  let mut matched = 0;
+  let mut offset = 0;
+  for part in refined.split_inclusive(char::is_whitespace) {
+    offset += part.len();
+    let word = normalize(part);
+    if word.is_empty() {
+      continue;
+    }
+    if word != expected[matched] {
+      return refined.to_string();
+    }
+    matched += 1;
+    if matched == expected.len() {
+      return refined[offset..].trim_start().to_string();
+    }
+  }
+  return refined.to_string();
+}
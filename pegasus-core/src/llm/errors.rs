@@ -0,0 +1,76 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::llm::refusal::RefusalKind;
+use crate::network::errors::NetworkError;
+use crate::t;
+
+/// LLM-related errors.
+///
+/// Represents errors that can occur during LLM API communication and text refinement.
+#[derive(Error, Debug)]
+pub enum LLMError {
+  #[error("{}", t!("error-llm-api-request", error = .0.to_string()))]
+  ApiRequestFailed(NetworkError),
+
+  #[error("{}", t!("error-llm-invalid-response", error = .0.as_str()))]
+  InvalidResponse(String),
+
+  #[error("{}", t!("error-llm-refinement", error = .0.as_str()))]
+  RefinementFailed(String),
+
+  #[error("{}", t!("error-llm-output-too-long", error = .0.as_str()))]
+  OutputTooLong(String),
+
+  #[error(
+    "{}",
+    t!(
+      "error-llm-context-overflow",
+      needed = *.needed,
+      answer = *.answer,
+      window = *.window,
+      advice = .advice.to_string()
+    )
+  )]
+  ContextOverflow {
+    needed: usize,
+    answer: usize,
+    window: usize,
+    advice: OverflowAdvice,
+  },
+
+  #[error(
+    "{}",
+    t!(
+      "error-llm-low-similarity",
+      similarity = format!("{:.2}", .similarity),
+      threshold = format!("{:.2}", .threshold)
+    )
+  )]
+  LowSimilarity { similarity: f64, threshold: f64 },
+
+  #[error("{}", t!("error-llm-refusal", kind = .0.to_string()))]
+  Refusal(RefusalKind),
+}
+
+/// What to do about a request that does not fit the context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowAdvice {
+  /// The input is sent whole
+  ShortenInput,
+  /// The input is split into chunks of `[input] chunk_size`
+  LowerChunkSize,
+}
+
+impl fmt::Display for OverflowAdvice {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(&match self {
+      OverflowAdvice::ShortenInput => t!("error-llm-shorten-input"),
+      OverflowAdvice::LowerChunkSize => t!("error-llm-lower-chunk-size"),
+    });
+  }
+}
+
+/// Result type for LLM operations.
+pub type LLMResult<T> = Result<T, LLMError>;
@@ -0,0 +1,303 @@
+//! GBNF grammars constraining answers to their expected shape.
+//!
+//! llama.cpp can restrict sampling to a grammar, so an answer cannot start
+//! with "Here is the refined text:", wrap the text in a Markdown fence, or
+//! return chapters that are not valid JSON. With `[llm] constrain_output`
+//! the grammar of each request is generated from what it asks for (see
+//! [`OutputMode`]); it is sent with the completion API, and with the chat
+//! API when the endpoint is detected as llama.cpp.
+//!
+//! The openings forbidden are the assistant openings of
+//! [`crate::llm::phrases`], in any casing and with or without their
+//! contractions. An opening is only forbidden as a whole word and when the
+//! input does not start with it, so `Surely` and a transcript starting with
+//! `Sure` can still be written. Inline code in backticks is allowed; only
+//! fences of three backticks are not.
+
+use crate::llm::phrases::{self, ASSISTANT_OPENINGS};
+
+/// How many characters of the input are compared with the openings.
+const OPENING_LENGTH: usize = 40;
+
+/// A JSON string.
+const JSON_STRING_RULE: &str = "string ::= \"\\\"\" ([^\"\\\\\\n] | \"\\\\\" \
+                                ([\"\\\\/bfnrt] | \"u\" [0-9a-fA-F]{4}))* \
+                                \"\\\"\"";
+
+/// Optional JSON whitespace.
+const JSON_WHITESPACE_RULE: &str = "ws ::= [ \\t\\n]*";
+
+/// Text without a run of three backticks.
+const PROSE_TEXT_RULE: &str =
+  "text ::= ([^`] | \"`\" [^`] | \"``\" [^`])* (\"`\" | \"``\")?";
+
+/// Characters continuing a word, which make an opening another word.
+const WORD_CHARACTERS: &str = "a-zA-Z0-9";
+
+/// What an answer is expected to contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+  /// Refined text without preamble or Markdown fences
+  Prose,
+  /// A JSON array of `{ "segment": n, "title": "..." }` objects
+  Chapters,
+  /// A JSON object with `title` and `summary`
+  Summary,
+}
+
+impl OutputMode {
+  /// Generates the grammar of answers in this mode.
+  ///
+  /// # Arguments
+  ///
+  /// * `input` - The text to refine, whose own opening prose may repeat
+  ///
+  /// # Returns
+  ///
+  /// The GBNF grammar, with `root` as its start rule.
+  pub fn grammar(self, input: &str) -> String {
+    return match self {
+      OutputMode::Prose => prose_grammar(input),
+      OutputMode::Chapters => [
+        "root ::= \"[\" ws chapter (ws \",\" ws chapter)* ws \"]\"",
+        "chapter ::= \"{\" ws \"\\\"segment\\\"\" ws \":\" ws [0-9]+ ws \",\" \
+         ws \"\\\"title\\\"\" ws \":\" ws string ws \"}\"",
+        JSON_STRING_RULE,
+        JSON_WHITESPACE_RULE,
+      ]
+      .join("\n"),
+      OutputMode::Summary => [
+        "root ::= \"{\" ws \"\\\"title\\\"\" ws \":\" ws string ws \",\" ws \
+         \"\\\"summary\\\"\" ws \":\" ws string ws \"}\"",
+        JSON_STRING_RULE,
+        JSON_WHITESPACE_RULE,
+      ]
+      .join("\n"),
+    };
+  }
+}
+
+/// A node of the trie of forbidden openings.
+#[derive(Default)]
+struct Node {
+  children: Vec<(char, Node)>,
+  /// Whether a forbidden opening ends here
+  forbidden: bool,
+}
+
+impl Node {
+  fn insert(&mut self, opening: &str) {
+    let mut node = self;
+    for c in opening.chars() {
+      let index = match node.children.iter().position(|(child, _)| *child == c)
+      {
+        Some(index) => index,
+        None => {
+          node.children.push((c, Node::default()));
+          node.children.len() - 1
+        }
+      };
+      node = &mut node.children[index].1;
+    }
+    node.forbidden = true;
+  }
+}
+
+/// Generates the grammar of prose: text without Markdown fences that
+/// neither starts with whitespace, a backtick or a Markdown heading nor
+/// with a chatty opening the input does not start with.
+///
+/// Each node of the trie of openings becomes a rule that either follows a
+/// child towards an opening, leaves the trie with any other character and
+/// continues freely, or ends the text. At the end of an opening, only a
+/// character continuing the word, as in `Surely`, may follow.
+fn prose_grammar(input: &str) -> String {
+  let mut trie = Node::default();
+  for opening in forbidden_openings(input) {
+    trie.insert(&opening);
+  }
+
+  let mut rules = Vec::new();
+  add_rule(&trie, "root", " \\t\\n#", &mut rules);
+  rules.push(String::from(PROSE_TEXT_RULE));
+  return rules.join("\n");
+}
+
+/// Gets the openings prose may not start with, in lowercase: the written
+/// forms of those the input does not start with itself.
+fn forbidden_openings(input: &str) -> Vec<String> {
+  let input = phrases::opening(input, OPENING_LENGTH);
+  return ASSISTANT_OPENINGS
+    .iter()
+    .filter(|opening| !phrases::starts_with_phrase(&input, opening))
+    .flat_map(|opening| phrases::written_forms(opening))
+    .collect();
+}
+
+/// Adds the rule of a trie node and of its descendants.
+///
+/// # Arguments
+///
+/// * `node` - The trie node
+/// * `name` - Name of the rule
+/// * `excluded` - Escaped characters the text may not continue with here,
+///   besides the children and backticks
+/// * `rules` - The rules generated so far
+fn add_rule(node: &Node, name: &str, excluded: &str, rules: &mut Vec<String>) {
+  let index = rules.len();
+  rules.push(String::new());
+
+  let mut alternatives = Vec::new();
+  let mut children = String::new();
+  for (c, child) in &node.children {
+    let variants: String = variants(*c).into_iter().map(escape).collect();
+    children.push_str(&variants);
+    let child_name = format!("opening{}", rules.len());
+    alternatives.push(format!("[{}] {}", variants, child_name));
+    if child.forbidden {
+      rules.push(format!("{} ::= [{}] text", child_name, WORD_CHARACTERS));
+      continue;
+    }
+    add_rule(child, &child_name, "", rules);
+  }
+  alternatives.push(format!("[^`{}{}] text", excluded, children));
+
+  rules[index] = format!("{} ::= ({})?", name, alternatives.join(" | "));
+}
+
+/// Gets the characters an opening may be written with in place of one of
+/// its lowercase characters: the character in either case, and a curly
+/// apostrophe for a straight one.
+fn variants(c: char) -> Vec<char> {
+  let mut variants = vec![c];
+  variants.extend(c.to_uppercase().filter(|upper| *upper != c));
+  if c == '\'' {
+    variants.push('\u{2019}');
+  }
+  return variants;
+}
+
+/// Escapes a character for a GBNF string or character class.
+fn escape(c: char) -> String {
+  return match c {
+    '"' | '\\' | ']' | '^' | '-' => format!("\\{}", c),
+    '\n' => String::from("\\n"),
+    '\t' => String::from("\\t"),
+    c => c.to_string(),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use regex::Regex;
+
+  use super::*;
+
+  /// Translates a grammar without recursive rules into a regex matching
+  /// the same texts.
+  fn to_regex(grammar: &str) -> Regex {
+    let rules: HashMap<&str, &str> = grammar
+      .lines()
+      .filter_map(|line| line.split_once(" ::= "))
+      .collect();
+    return Regex::new(&format!("^(?s:{})$", expand(&rules, rules["root"])))
+      .unwrap();
+  }
+
+  fn expand(rules: &HashMap<&str, &str>, rule: &str) -> String {
+    let mut pattern = String::new();
+    let mut chars = rule.chars().peekable();
+    while let Some(c) = chars.next() {
+      match c {
+        '"' => {
+          let mut literal = String::new();
+          while let Some(c) = chars.next() {
+            match c {
+              '"' => break,
+              '\\' => literal.push(match chars.next().unwrap() {
+                'n' => '\n',
+                't' => '\t',
+                c => c,
+              }),
+              c => literal.push(c),
+            }
+          }
+          pattern.push_str(&format!("(?:{})", regex::escape(&literal)));
+        }
+        '[' => {
+          pattern.push('[');
+          while let Some(c) = chars.next() {
+            pattern.push(c);
+            if c == '\\' {
+              pattern.push(chars.next().unwrap());
+            } else if c == ']' {
+              break;
+            }
+          }
+        }
+        '{' => {
+          pattern.push('{');
+          for c in chars.by_ref() {
+            pattern.push(c);
+            if c == '}' {
+              break;
+            }
+          }
+        }
+        '(' => pattern.push_str("(?:"),
+        ')' | '|' | '?' | '*' | '+' => pattern.push(c),
+        c if c.is_alphanumeric() => {
+          let mut name = c.to_string();
+          while let Some(c) = chars.next_if(|c| c.is_alphanumeric()) {
+            name.push(c);
+          }
+          pattern.push_str(&format!("(?:{})", expand(rules, rules[&*name])));
+        }
+        _ => {}
+      }
+    }
+    return pattern;
+  }
+
+  #[test]
+  fn forbids_chatty_openings_as_whole_words() {
+    let prose = to_regex(&OutputMode::Prose.grammar("we met at noon"));
+    assert!(prose.is_match("We met at noon."));
+    assert!(prose.is_match("Surely we met at noon."));
+    assert!(prose.is_match("Heres the thing."));
+    assert!(!prose.is_match("Sure, we met at noon."));
+    assert!(!prose.is_match("Here is the refined text: we met."));
+    assert!(!prose.is_match("Here\u{2019}s the refined text."));
+    assert!(!prose.is_match("SURE thing."));
+    assert!(!prose.is_match("I'm sorry, we met at noon."));
+    assert!(!prose.is_match(" We met."));
+    assert!(!prose.is_match("# Notes"));
+  }
+
+  #[test]
+  fn allows_openings_the_input_starts_with() {
+    let prose = to_regex(&OutputMode::Prose.grammar("sure, I can do that"));
+    assert!(prose.is_match("Sure, I can do that."));
+    let prose = to_regex(&OutputMode::Prose.grammar("Here is my point"));
+    assert!(prose.is_match("Here's my point."));
+    assert!(!prose.is_match("Certainly, I can do that."));
+  }
+
+  #[test]
+  fn allows_inline_code_but_no_fences() {
+    let prose = to_regex(&OutputMode::Prose.grammar(""));
+    assert!(prose.is_match("Run `ls` and ``cat`` now `"));
+    assert!(!prose.is_match("Run:\n```\nls\n```"));
+  }
+
+  #[test]
+  fn constrains_chapters_and_summaries_to_json() {
+    let chapters = to_regex(&OutputMode::Chapters.grammar(""));
+    assert!(chapters.is_match(r#"[{"segment": 0, "title": "Intro"}]"#));
+    assert!(!chapters.is_match(r#"[{"title": "Intro"}]"#));
+    let summary = to_regex(&OutputMode::Summary.grammar(""));
+    assert!(summary.is_match(r#"{"title": "A", "summary": "B \"c\""}"#));
+  }
+}
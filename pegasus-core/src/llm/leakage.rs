@@ -0,0 +1,95 @@
+//! Detection of answers to the transcript.
+//!
+//! Instruction-tuned models sometimes respond to what a transcript says
+//! instead of refining it: a transcript ending with a question comes back
+//! answered, or a request in it is carried out. [`detect`] compares a
+//! refinement with its input for the typical signs:
+//! - the refinement opens like an assistant reply (`Sure`, `Here is`,
+//!   `As an AI`) while the input does not
+//! - the input ends with a question, but no question is left
+//! - the refinement is more than twice as long as an input of some length
+//!
+//! With `[llm] retry_answers`, such refinements are requested again with a
+//! system prompt stressing that the transcript is text to refine, not a
+//! message to the assistant.
+
+use std::fmt;
+
+use crate::llm::phrases::{self, ASSISTANT_OPENINGS};
+
+/// How many characters of a text are compared with the openings.
+const OPENING_LENGTH: usize = 40;
+
+/// Question marks ending a question, in any script.
+const QUESTION_MARKS: [char; 3] = ['?', '\u{ff1f}', '\u{061f}'];
+
+/// Fewest words of an input for its refinement's length to be judged.
+const MIN_EXPANSION_WORDS: usize = 20;
+
+/// Most times longer than its input a refinement may be.
+const MAX_EXPANSION_RATIO: f64 = 2.0;
+
+/// Why a refinement looks like an answer to the transcript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Leakage {
+  /// The refinement opens like an assistant reply
+  AssistantOpening(String),
+  /// The input ends with a question that is no longer there
+  QuestionAnswered,
+  /// The refinement is far longer than the input
+  Expanded,
+}
+
+impl fmt::Display for Leakage {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return match self {
+      Leakage::AssistantOpening(opening) => {
+        write!(f, "it opens with '{}'", opening)
+      }
+      Leakage::QuestionAnswered => {
+        f.write_str("the closing question of the input was answered")
+      }
+      Leakage::Expanded => {
+        f.write_str("it is more than twice as long as the input")
+      }
+    };
+  }
+}
+
+/// Checks whether a refinement responds to its input instead of refining
+/// it.
+///
+/// # Arguments
+///
+/// * `input` - The text sent for refinement
+/// * `refined` - The refinement
+///
+/// # Returns
+///
+/// The first sign of an answer found, or `None` if the refinement looks
+/// like a refinement.
+pub fn detect(input: &str, refined: &str) -> Option<Leakage> {
+  let input_opening = phrases::opening(input, OPENING_LENGTH);
+  let refined_opening = phrases::opening(refined, OPENING_LENGTH);
+  if let Some(found) = ASSISTANT_OPENINGS.iter().find(|phrase| {
+    return phrases::starts_with_phrase(&refined_opening, phrase)
+      && !phrases::starts_with_phrase(&input_opening, phrase);
+  }) {
+    return Some(Leakage::AssistantOpening(found.to_string()));
+  }
+
+  if input.trim_end().ends_with(QUESTION_MARKS)
+    && !refined.contains(QUESTION_MARKS)
+  {
+    return Some(Leakage::QuestionAnswered);
+  }
+
+  let input_words = input.split_whitespace().count();
+  let refined_words = refined.split_whitespace().count();
+  if input_words >= MIN_EXPANSION_WORDS
+    && refined_words as f64 > input_words as f64 * MAX_EXPANSION_RATIO
+  {
+    return Some(Leakage::Expanded);
+  }
+  return None;
+}
@@ -0,0 +1,38 @@
+//! LLM module for text refinement.
+//!
+//! This module provides integration with LLM services using OpenAI-compatible
+//! APIs for refining transcribed text.
+//!
+//! ## Main Components
+//!
+//! - [`LLMClient`]: HTTP client for LLM API communication
+//! - [`Capabilities`]: Context window and features detected from the endpoint
+//! - [`ConversationContext`]: Recent refinements sent with the next request
+//! - [`OutputMode`]: What an answer contains, and its GBNF grammar
+//! - [`OutputLimit`]: Cap on the size of answers, against runaway generation
+//! - [`NonSpeechFilter`]: Emoji and non-speech markers kept, stripped or
+//!   replaced with words
+//! - [`Tokenizer`]: Token counts for the configured model
+//! - [`VerbatimTokens`]: Timestamps, speaker tags and annotations kept out
+//!   of the model's reach
+//! - [`Leakage`]: Signs that a refinement answered the transcript instead
+//! - [`RefusalKind`]: Why the model refused to refine a text
+//! - [`LLMError`]: Error types for LLM operations
+//! - [`LLMResult<T>`]: Result type alias for LLM operations
+
+pub(crate) mod alignment;
+pub mod capabilities;
+pub mod client;
+pub mod context;
+pub mod errors;
+pub mod grammar;
+pub mod leakage;
+pub mod non_speech;
+pub mod output_limit;
+pub(crate) mod phrases;
+pub mod prompts;
+pub mod refusal;
+mod request;
+mod response;
+pub mod tokenizer;
+pub mod verbatim;
@@ -0,0 +1,423 @@
+//! Handling of emoji and non-speech markers.
+//!
+//! Transcripts and dictated text can carry emoji (`👍`) and markers of
+//! sounds that are not speech (`[laughter]`, `(applause)`, `♪`). Left to
+//! the model, they are kept one time and dropped the next. `[style] emoji`
+//! and `[style] non_speech` make the outcome deterministic:
+//! - `model`: leave them to the model
+//! - `keep`: protect them from the model like verbatim tokens (see
+//!   [`crate::llm::verbatim`])
+//! - `strip`: remove them before and after refinement
+//! - `words`: replace them with words before and after refinement, such
+//!   as `thumbs up` for `👍` or `laughter` for `[laughter]`; emoji without
+//!   a known name are removed
+
+use std::fmt;
+
+use crate::dlog;
+
+/// Sounds recognized as markers in brackets or parentheses, like
+/// `[laughter]` or `(laughs)`, compared in lowercase with underscores read
+/// as spaces.
+const SOUNDS: [&str; 26] = [
+  "applause",
+  "background noise",
+  "beep",
+  "blank audio",
+  "cheering",
+  "clapping",
+  "coughing",
+  "coughs",
+  "crosstalk",
+  "inaudible",
+  "laughing",
+  "laughs",
+  "laughter",
+  "music",
+  "music playing",
+  "no audio",
+  "noise",
+  "pause",
+  "sighs",
+  "silence",
+  "sneezes",
+  "static",
+  "typing",
+  "unintelligible",
+  "upbeat music",
+  "whispers",
+];
+
+/// Names of common emoji, by their first code point.
+const EMOJI_NAMES: [(char, &str); 42] = [
+  ('\u{2705}', "check mark"),
+  ('\u{274C}', "cross mark"),
+  ('\u{26A0}', "warning"),
+  ('\u{2728}', "sparkles"),
+  ('\u{2764}', "red heart"),
+  ('\u{2B50}', "star"),
+  ('\u{1F389}', "party popper"),
+  ('\u{1F440}', "eyes"),
+  ('\u{1F44B}', "waving hand"),
+  ('\u{1F44C}', "OK hand"),
+  ('\u{1F44D}', "thumbs up"),
+  ('\u{1F44E}', "thumbs down"),
+  ('\u{1F44F}', "clapping hands"),
+  ('\u{1F494}', "broken heart"),
+  ('\u{1F4A1}', "light bulb"),
+  ('\u{1F4AA}', "flexed biceps"),
+  ('\u{1F4AF}', "hundred points"),
+  ('\u{1F525}', "fire"),
+  ('\u{1F600}', "grinning face"),
+  ('\u{1F602}', "face with tears of joy"),
+  ('\u{1F605}', "grinning face with sweat"),
+  ('\u{1F609}', "winking face"),
+  ('\u{1F60A}', "smiling face"),
+  ('\u{1F60D}', "smiling face with heart eyes"),
+  ('\u{1F60E}', "smiling face with sunglasses"),
+  ('\u{1F621}', "angry face"),
+  ('\u{1F622}', "crying face"),
+  ('\u{1F62C}', "grimacing face"),
+  ('\u{1F62D}', "loudly crying face"),
+  ('\u{1F62E}', "surprised face"),
+  ('\u{1F634}', "sleeping face"),
+  ('\u{1F641}', "slightly frowning face"),
+  ('\u{1F642}', "slightly smiling face"),
+  ('\u{1F64C}', "raised hands"),
+  ('\u{1F64F}', "folded hands"),
+  ('\u{1F680}', "rocket"),
+  ('\u{1F914}', "thinking face"),
+  ('\u{1F923}', "rolling on the floor laughing"),
+  ('\u{1F926}', "person facepalming"),
+  ('\u{1F937}', "person shrugging"),
+  ('\u{1F973}', "partying face"),
+  ('\u{1F97A}', "pleading face"),
+];
+
+/// Zero width joiner between the emoji of a sequence.
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// What happens to emoji or non-speech markers.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum NonSpeechPolicy {
+  /// Left to the model
+  #[default]
+  Model,
+  /// Protected from the model and kept as written
+  Keep,
+  /// Removed
+  Strip,
+  /// Replaced with words
+  Words,
+}
+
+impl fmt::Display for NonSpeechPolicy {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      NonSpeechPolicy::Model => "model",
+      NonSpeechPolicy::Keep => "keep",
+      NonSpeechPolicy::Strip => "strip",
+      NonSpeechPolicy::Words => "words",
+    });
+  }
+}
+
+/// A kind of non-speech token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+  Emoji,
+  Marker,
+}
+
+/// Policies for emoji and non-speech markers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NonSpeechFilter {
+  /// What happens to emoji
+  pub emoji: NonSpeechPolicy,
+  /// What happens to non-speech markers
+  pub markers: NonSpeechPolicy,
+}
+
+impl NonSpeechFilter {
+  /// Creates a filter.
+  ///
+  /// # Arguments
+  ///
+  /// * `emoji` - What happens to emoji
+  /// * `markers` - What happens to non-speech markers
+  ///
+  /// # Returns
+  ///
+  /// A new `NonSpeechFilter`.
+  pub fn new(emoji: NonSpeechPolicy, markers: NonSpeechPolicy) -> Self {
+    return NonSpeechFilter { emoji, markers };
+  }
+
+  /// Strips or replaces emoji and markers with words, as configured.
+  ///
+  /// Tokens under the `model` and `keep` policies are left alone. Spacing
+  /// around removed tokens is tidied up, so no double spaces are left.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The text before or after refinement
+  ///
+  /// # Returns
+  ///
+  /// The filtered text.
+  pub fn apply(&self, text: &str) -> String {
+    let rewrites = |policy| {
+      return matches!(policy, NonSpeechPolicy::Strip | NonSpeechPolicy::Words);
+    };
+    if !rewrites(self.emoji) && !rewrites(self.markers) {
+      return text.to_string();
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut count = 0;
+    while let Some((start, end, kind)) = next_token(rest) {
+      result.push_str(&rest[..start]);
+      let token = &rest[start..end];
+      rest = &rest[end..];
+      let policy = match kind {
+        TokenKind::Emoji => self.emoji,
+        TokenKind::Marker => self.markers,
+      };
+      match policy {
+        NonSpeechPolicy::Strip => {
+          count += 1;
+          remove(&mut result, &mut rest);
+        }
+        NonSpeechPolicy::Words => {
+          count += 1;
+          match to_words(token, kind) {
+            Some(words) => insert_words(&mut result, rest, &words),
+            None => remove(&mut result, &mut rest),
+          }
+        }
+        NonSpeechPolicy::Model | NonSpeechPolicy::Keep => {
+          result.push_str(token);
+        }
+      }
+    }
+    result.push_str(rest);
+
+    if count > 0 {
+      dlog!("Rewrote {} emoji and non-speech markers", count);
+    }
+    return result;
+  }
+}
+
+/// Gets the length of an emoji at the start of a string, including skin
+/// tone modifiers, variation selectors and joined emoji.
+///
+/// # Returns
+///
+/// The length in bytes, or `None` if the string does not start with one.
+pub fn emoji_length(text: &str) -> Option<usize> {
+  let mut length = 0;
+  let mut position = 0;
+  let mut previous = None;
+  let mut expects_emoji = true;
+  for c in text.chars() {
+    if expects_emoji {
+      if !is_pictograph(c) {
+        break;
+      }
+      expects_emoji = false;
+    } else if c == ZERO_WIDTH_JOINER {
+      expects_emoji = true;
+    } else {
+      let flag =
+        is_regional_indicator(c) && previous.is_some_and(is_regional_indicator);
+      if !is_modifier(c) && !flag {
+        break;
+      }
+    }
+    position += c.len_utf8();
+    if !expects_emoji {
+      length = position;
+    }
+    previous = Some(c);
+  }
+  return (length > 0).then_some(length);
+}
+
+/// Gets the length of a non-speech marker at the start of a string: a
+/// known sound in brackets or parentheses like `[laughter]` or
+/// `(applause)`, or a run of music notes. Other bracketed text, such as
+/// `[sic]`, `[1]` or an editor's `[the committee]`, is no marker.
+///
+/// # Returns
+///
+/// The length in bytes, or `None` if the string does not start with one.
+pub fn marker_length(text: &str) -> Option<usize> {
+  let first = text.chars().next()?;
+  if is_music_note(first) {
+    return Some(
+      text
+        .chars()
+        .take_while(|c| is_music_note(*c))
+        .map(char::len_utf8)
+        .sum(),
+    );
+  }
+
+  let close = match first {
+    '[' => ']',
+    '(' => ')',
+    _ => return None,
+  };
+  let rest = &text[1..];
+  let end = rest.find([close, first, '\n'])?;
+  if !rest[end..].starts_with(close) {
+    return None;
+  }
+  let sound = rest[..end].trim().to_lowercase().replace('_', " ");
+  return SOUNDS.contains(&sound.as_str()).then_some(end + 2);
+}
+
+/// Finds the next emoji or non-speech marker in a text.
+///
+/// # Returns
+///
+/// The byte range and kind of the token, or `None` if there is none.
+fn next_token(text: &str) -> Option<(usize, usize, TokenKind)> {
+  for (start, _) in text.char_indices() {
+    let rest = &text[start..];
+    if let Some(length) = marker_length(rest) {
+      return Some((start, start + length, TokenKind::Marker));
+    }
+    if let Some(length) = emoji_length(rest) {
+      return Some((start, start + length, TokenKind::Emoji));
+    }
+  }
+  return None;
+}
+
+/// Gets the words a token is replaced with.
+///
+/// Returns `None` for an emoji without a known name.
+fn to_words(token: &str, kind: TokenKind) -> Option<String> {
+  let first = token.chars().next()?;
+  return match kind {
+    TokenKind::Emoji => EMOJI_NAMES
+      .iter()
+      .find(|(emoji, _)| *emoji == first)
+      .map(|(_, name)| name.to_string()),
+    TokenKind::Marker if is_music_note(first) => Some(String::from("music")),
+    TokenKind::Marker => {
+      let content = &token[1..token.len() - 1];
+      Some(content.trim().to_string())
+    }
+  };
+}
+
+/// Drops a removed token's surrounding space, so no double space, space
+/// before punctuation or space at the start of a line is left.
+fn remove(result: &mut String, rest: &mut &str) {
+  if result.is_empty() || result.ends_with('\n') {
+    *rest = rest.trim_start_matches([' ', '\t']);
+    return;
+  }
+  let next = rest.chars().next();
+  let closes = next.is_none_or(|c| {
+    return matches!(c, ' ' | '\t' | '\n' | '.' | ',' | '!' | '?' | ';')
+      || matches!(c, ':' | ')' | ']');
+  });
+  if closes {
+    result.truncate(result.trim_end_matches([' ', '\t']).len());
+  }
+}
+
+/// Adds the words of a token, separated from adjacent words by spaces.
+fn insert_words(result: &mut String, rest: &str, words: &str) {
+  if result
+    .chars()
+    .next_back()
+    .is_some_and(char::is_alphanumeric)
+  {
+    result.push(' ');
+  }
+  result.push_str(words);
+  if rest.chars().next().is_some_and(char::is_alphanumeric) {
+    result.push(' ');
+  }
+}
+
+/// Checks whether a character is an emoji pictograph.
+fn is_pictograph(c: char) -> bool {
+  return !is_music_note(c)
+    && matches!(
+      c as u32,
+      0x1F000..=0x1FAFF
+        | 0x2600..=0x27BF
+        | 0x2B05..=0x2B55
+        | 0x231A..=0x231B
+        | 0x23E9..=0x23FA
+        | 0x3030
+        | 0x303D
+        | 0x3297
+        | 0x3299
+    );
+}
+
+/// Checks whether a character modifies the emoji before it.
+fn is_modifier(c: char) -> bool {
+  return matches!(
+    c as u32,
+    0xFE0E..=0xFE0F | 0x1F3FB..=0x1F3FF | 0x20E3 | 0xE0020..=0xE007F
+  );
+}
+
+/// Checks whether a character is half of a flag.
+fn is_regional_indicator(c: char) -> bool {
+  return matches!(c as u32, 0x1F1E6..=0x1F1FF);
+}
+
+/// Checks whether a character is a music note.
+fn is_music_note(c: char) -> bool {
+  return matches!(c, '\u{2669}' | '\u{266A}' | '\u{266B}' | '\u{266C}');
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn recognizes_known_sounds_in_brackets_and_parentheses() {
+    assert_eq!(marker_length("[laughter] ok"), Some(10));
+    assert_eq!(marker_length("[BLANK_AUDIO]"), Some(13));
+    assert_eq!(marker_length("( Applause )"), Some(12));
+    assert_eq!(marker_length("\u{266A}\u{266B} la"), Some(6));
+  }
+
+  #[test]
+  fn leaves_other_bracketed_text_alone() {
+    for text in ["[sic]", "[1]", "[the committee]", "(see above)", "[music"] {
+      assert_eq!(marker_length(text), None, "{}", text);
+    }
+  }
+
+  #[test]
+  fn strips_only_markers() {
+    let filter =
+      NonSpeechFilter::new(NonSpeechPolicy::Model, NonSpeechPolicy::Strip);
+    assert_eq!(
+      filter.apply("They [inaudible] said [sic] it (laughs) twice."),
+      "They said [sic] it twice."
+    );
+  }
+}
@@ -0,0 +1,103 @@
+//! Guard against runaway generation.
+//!
+//! Local models sometimes get stuck repeating themselves until they hit
+//! their token limit. [`OutputLimit`] caps the size of each answer, either
+//! cutting it at the last sentence that fits or failing the request.
+
+use crate::llm::errors::{LLMError, LLMResult};
+use crate::{elog, logging, slog};
+
+/// What to do with an answer longer than the limit.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowPolicy {
+  /// Cut the answer at the last sentence boundary within the limit
+  #[default]
+  Truncate,
+  /// Fail the request with an error
+  Fail,
+}
+
+/// The largest answer accepted from the LLM.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputLimit {
+  /// Largest answer in characters (0 for no limit)
+  pub max_characters: usize,
+  /// What to do with longer answers
+  pub policy: OverflowPolicy,
+}
+
+impl OutputLimit {
+  /// Applies the limit to an answer.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The answer of the LLM
+  ///
+  /// # Returns
+  ///
+  /// A `LLMResult<String>` containing the answer, truncated if needed, or
+  /// an `OutputTooLong` error under the `Fail` policy.
+  pub fn apply(&self, text: String) -> LLMResult<String> {
+    let length = text.chars().count();
+    if self.max_characters == 0 || length <= self.max_characters {
+      return Ok(text);
+    }
+
+    if self.policy == OverflowPolicy::Fail {
+      return Err(LLMError::OutputTooLong(format!(
+        "{} characters, the limit is {}",
+        length, self.max_characters
+      )));
+    }
+
+    elog!(
+      logging::WARNING,
+      "LLM output of {} characters exceeds the limit of {}; truncating",
+      length,
+      self.max_characters
+    );
+    let end = text
+      .char_indices()
+      .nth(self.max_characters)
+      .map_or(text.len(), |(index, _)| index);
+    let truncated = truncate_at_sentence(&text[..end]).to_string();
+    slog!(
+      "truncation",
+      decision = "truncate",
+      reason = "output_limit",
+      characters = length,
+      limit = self.max_characters,
+      kept = truncated.chars().count()
+    );
+    return Ok(truncated);
+  }
+}
+
+/// Cuts text after its last complete sentence.
+///
+/// Falls back to the last word boundary if no sentence ends in the second
+/// half of the text, so a single huge sentence is not cut to nothing.
+fn truncate_at_sentence(text: &str) -> &str {
+  let sentence_end = text
+    .rfind(['.', '!', '?', '\n'])
+    .map(|index| index + 1)
+    .filter(|end| *end >= text.len() / 2);
+  if let Some(end) = sentence_end {
+    return text[..end].trim_end();
+  }
+
+  return match text.rfind(char::is_whitespace) {
+    Some(end) if end > 0 => text[..end].trim_end(),
+    _ => text,
+  };
+}
@@ -0,0 +1,221 @@
+//! Phrases of assistant replies and refusals.
+//!
+//! The checks of answers look for the same phrases: the grammar of refined
+//! prose forbids assistant openings (see [`crate::llm::grammar`]), the
+//! leakage check finds them after the fact (see [`crate::llm::leakage`]),
+//! and the refusal check finds refusals (see [`crate::llm::refusal`]).
+//!
+//! Phrases are written normalized: [`normalize`] lowercases text,
+//! straightens apostrophes, expands contractions and collapses whitespace,
+//! so `I can’t  help` and `I cannot help` match the same phrase.
+
+/// Openings of assistant replies, normalized.
+pub const ASSISTANT_OPENINGS: &[&str] = &[
+  "sure",
+  "certainly",
+  "of course",
+  "absolutely",
+  "here is",
+  "here are",
+  "refined text",
+  "as an ai",
+  "i am sorry",
+  "great question",
+  "good question",
+  "the answer is",
+  "happy to help",
+  "i would be happy",
+];
+
+/// Phrases opening a refusal, normalized.
+pub const REFUSAL_PHRASES: &[&str] = &[
+  "i cannot help with",
+  "i cannot assist with",
+  "i cannot do that",
+  "i cannot comply",
+  "i cannot fulfill",
+  "i cannot provide",
+  "i am unable to",
+  "i am not able to",
+  "i will not be able to",
+  "i must decline",
+  "i have to decline",
+  "i am not comfortable",
+];
+
+/// Contractions and their expansions, lowercase.
+const CONTRACTIONS: &[(&str, &str)] = &[
+  ("can't", "cannot"),
+  ("can not", "cannot"),
+  ("won't", "will not"),
+  ("i'm", "i am"),
+  ("i'd", "i would"),
+  ("i'll", "i will"),
+  ("i've", "i have"),
+  ("here's", "here is"),
+  ("it's", "it is"),
+  ("that's", "that is"),
+  ("don't", "do not"),
+  ("doesn't", "does not"),
+  ("isn't", "is not"),
+  ("aren't", "are not"),
+  ("couldn't", "could not"),
+  ("shouldn't", "should not"),
+  ("wouldn't", "would not"),
+];
+
+/// Apostrophes straightened before matching.
+const APOSTROPHES: [char; 3] = ['\u{2019}', '\u{2018}', '\u{02bc}'];
+
+/// Normalizes a text for matching phrases.
+///
+/// # Arguments
+///
+/// * `text` - The text
+///
+/// # Returns
+///
+/// The text in lowercase, with straight apostrophes, contractions expanded
+/// and runs of whitespace replaced with a single space.
+pub fn normalize(text: &str) -> String {
+  let mut text = text
+    .to_lowercase()
+    .replace(APOSTROPHES, "'")
+    .split_whitespace()
+    .collect::<Vec<&str>>()
+    .join(" ");
+  for (contraction, expansion) in CONTRACTIONS {
+    text = replace_phrase(&text, contraction, expansion);
+  }
+  return text;
+}
+
+/// Gets the ways a normalized phrase can be written, with and without its
+/// contractions.
+///
+/// # Arguments
+///
+/// * `phrase` - The normalized phrase
+///
+/// # Returns
+///
+/// The phrase, followed by its contracted forms, in lowercase.
+pub fn written_forms(phrase: &str) -> Vec<String> {
+  let mut forms = vec![phrase.to_string()];
+  for (contraction, expansion) in CONTRACTIONS {
+    let contracted = replace_phrase(phrase, expansion, contraction);
+    if !forms.contains(&contracted) {
+      forms.push(contracted);
+    }
+  }
+  return forms;
+}
+
+/// Checks whether a normalized text starts with a phrase followed by a
+/// word boundary.
+///
+/// # Arguments
+///
+/// * `text` - The normalized text, without leading punctuation
+/// * `phrase` - The normalized phrase
+///
+/// # Returns
+///
+/// `true` if the text opens with the phrase as whole words.
+pub fn starts_with_phrase(text: &str, phrase: &str) -> bool {
+  return text.strip_prefix(phrase).is_some_and(|rest| {
+    return !rest.starts_with(char::is_alphanumeric);
+  });
+}
+
+/// Checks whether a normalized text contains a phrase as whole words.
+///
+/// # Arguments
+///
+/// * `text` - The normalized text
+/// * `phrase` - The normalized phrase
+///
+/// # Returns
+///
+/// `true` if the phrase occurs between word boundaries.
+pub fn contains_phrase(text: &str, phrase: &str) -> bool {
+  return find_phrase(text, phrase).next().is_some();
+}
+
+/// Gets the opening of a text, normalized and without leading punctuation.
+///
+/// # Arguments
+///
+/// * `text` - The text
+/// * `length` - How many characters of the text to look at
+///
+/// # Returns
+///
+/// The normalized opening.
+pub fn opening(text: &str, length: usize) -> String {
+  let start = text.trim_start_matches(|c: char| !c.is_alphanumeric());
+  return normalize(&start.chars().take(length).collect::<String>());
+}
+
+/// Finds the byte offsets of a phrase between word boundaries.
+fn find_phrase<'a>(
+  text: &'a str,
+  phrase: &'a str,
+) -> impl Iterator<Item = usize> + 'a {
+  return text.match_indices(phrase).map(|(start, _)| start).filter(
+    move |start| {
+      let before = text[..*start].chars().next_back();
+      let after = text[start + phrase.len()..].chars().next();
+      return !before.is_some_and(char::is_alphanumeric)
+        && !after.is_some_and(char::is_alphanumeric);
+    },
+  );
+}
+
+/// Replaces a phrase between word boundaries.
+fn replace_phrase(text: &str, phrase: &str, replacement: &str) -> String {
+  let mut result = String::with_capacity(text.len());
+  let mut position = 0;
+  for start in find_phrase(text, phrase).collect::<Vec<usize>>() {
+    if start < position {
+      continue;
+    }
+    result.push_str(&text[position..start]);
+    result.push_str(replacement);
+    position = start + phrase.len();
+  }
+  result.push_str(&text[position..]);
+  return result;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn normalizes_case_apostrophes_contractions_and_whitespace() {
+    assert_eq!(
+      normalize("I Can\u{2019}t  help\nwith that, I'm sorry."),
+      "i cannot help with that, i am sorry."
+    );
+    assert_eq!(normalize("Scan't"), "scan't");
+  }
+
+  #[test]
+  fn lists_contracted_forms() {
+    assert_eq!(written_forms("here is"), ["here is", "here's"]);
+    assert_eq!(written_forms("i am sorry"), ["i am sorry", "i'm sorry"]);
+    assert_eq!(written_forms("sure"), ["sure"]);
+  }
+
+  #[test]
+  fn matches_whole_words() {
+    assert!(starts_with_phrase("sure, we can", "sure"));
+    assert!(!starts_with_phrase("surely we can", "sure"));
+    assert!(contains_phrase(
+      "well, i cannot do that",
+      "i cannot do that"
+    ));
+    assert!(!contains_phrase("the harmless one", "harm"));
+  }
+}
@@ -0,0 +1,189 @@
+//! Detection of refusals.
+//!
+//! Safety-tuned models sometimes decline to refine a transcript, most often
+//! one about medicine, crime or violence, and send a refusal like `I can't
+//! help with that` in place of the text. [`detect`] finds such refusals in
+//! the opening of an answer, unless the input says the same, and tells
+//! whether the model cited its policy, objected to the content or gave no
+//! reason.
+//!
+//! What happens next is set with `[llm] refusal`:
+//! - `retry`: the text is requested again with a system prompt framing it
+//!   as a transcript to correct, not content to endorse, and the run fails
+//!   with `LLMError::Refusal` if the model still refuses
+//! - `fail`: the run fails with `LLMError::Refusal` right away
+//! - `off`: refusals are not looked for, and end up as refined text
+//!
+//! Answers are compared as normalized by [`phrases::normalize`], so
+//! curly apostrophes, contractions and line breaks do not hide a refusal.
+
+use std::fmt;
+
+use crate::llm::phrases::{self, REFUSAL_PHRASES};
+
+/// Words of a refusal citing the model's rules, in lowercase.
+const POLICY_MARKERS: &[&str] = &[
+  "policy",
+  "policies",
+  "guidelines",
+  "terms of use",
+  "usage rules",
+  "as an ai",
+  "programming",
+];
+
+/// Words of a refusal objecting to the content, in lowercase.
+const CONTENT_MARKERS: &[&str] = &[
+  "harmful",
+  "harm",
+  "explicit",
+  "offensive",
+  "illegal",
+  "inappropriate",
+  "violent",
+  "violence",
+  "dangerous",
+  "sensitive",
+];
+
+/// How many characters of an answer are searched for a refusal.
+const OPENING_LENGTH: usize = 200;
+
+/// What happens when the model refuses to refine a text.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum RefusalHandling {
+  /// Requested again with a neutral framing, failing if refused again
+  #[default]
+  Retry,
+  /// Failed with `LLMError::Refusal`
+  Fail,
+  /// Not looked for
+  Off,
+}
+
+impl fmt::Display for RefusalHandling {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      RefusalHandling::Retry => "retry",
+      RefusalHandling::Fail => "fail",
+      RefusalHandling::Off => "off",
+    });
+  }
+}
+
+/// Why the model refused, as far as its answer tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefusalKind {
+  /// The model cited its policy or guidelines
+  Policy,
+  /// The model objected to what the text is about
+  Content,
+  /// The model gave no reason
+  General,
+}
+
+impl fmt::Display for RefusalKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      RefusalKind::Policy => "policy",
+      RefusalKind::Content => "content",
+      RefusalKind::General => "general",
+    });
+  }
+}
+
+/// Checks whether an answer refuses to refine its input.
+///
+/// # Arguments
+///
+/// * `input` - The text sent for refinement
+/// * `answer` - The answer of the model
+///
+/// # Returns
+///
+/// The kind of refusal, or `None` if the answer does not open with a
+/// refusal the input lacks.
+pub fn detect(input: &str, answer: &str) -> Option<RefusalKind> {
+  let opening = phrases::normalize(
+    &answer.chars().take(OPENING_LENGTH).collect::<String>(),
+  );
+  let input = phrases::normalize(input);
+  let refuses = REFUSAL_PHRASES.iter().any(|phrase| {
+    return phrases::contains_phrase(&opening, phrase)
+      && !phrases::contains_phrase(&input, phrase);
+  });
+  if !refuses {
+    return None;
+  }
+
+  let answer = phrases::normalize(answer);
+  let mentions = |markers: &[&str]| {
+    return markers
+      .iter()
+      .any(|marker| phrases::contains_phrase(&answer, marker));
+  };
+  if mentions(POLICY_MARKERS) {
+    return Some(RefusalKind::Policy);
+  }
+  if mentions(CONTENT_MARKERS) {
+    return Some(RefusalKind::Content);
+  }
+  return Some(RefusalKind::General);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_refusals_however_they_are_written() {
+    for answer in [
+      "I can't help with that.",
+      "I can\u{2019}t help with that.",
+      "I cannot\nhelp with that.",
+      "Sorry, but I CAN NOT help with that.",
+      "I won't be able to do this.",
+    ] {
+      assert_eq!(
+        detect("The patient took the pills.", answer),
+        Some(RefusalKind::General),
+        "{}",
+        answer
+      );
+    }
+  }
+
+  #[test]
+  fn tells_why_the_model_refused() {
+    assert_eq!(
+      detect("text", "I'm unable to do that; it violates my guidelines."),
+      Some(RefusalKind::Policy)
+    );
+    assert_eq!(
+      detect("text", "I must decline, as the content is violent."),
+      Some(RefusalKind::Content)
+    );
+  }
+
+  #[test]
+  fn ignores_refusals_the_input_contains() {
+    assert_eq!(
+      detect(
+        "He said: I can\u{2019}t help with that.",
+        "He said: I can't help with that."
+      ),
+      None
+    );
+    assert_eq!(detect("text", "The refined text."), None);
+  }
+}
@@ -0,0 +1,217 @@
+use serde::Serialize;
+
+/// OpenAI-compatible chat completion request.
+#[derive(Debug, Serialize)]
+pub struct ChatCompletionRequest {
+  model: String,
+  messages: Vec<ChatMessage>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  stop: Vec<String>,
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  logprobs: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  temperature: Option<f64>,
+  #[serde(skip_serializing_if = "String::is_empty")]
+  grammar: String,
+}
+
+impl ChatCompletionRequest {
+  /// Creates a new `ChatCompletionRequest` with the specified model and messages.
+  ///
+  /// # Arguments
+  ///
+  /// * `model` - Model name to use (e.g., "llama3.2", "gpt-4")
+  /// * `messages` - List of messages to send to the LLM
+  ///
+  /// # Returns
+  ///
+  /// A new `ChatCompletionRequest` instance.
+  pub fn new(model: String, messages: Vec<ChatMessage>) -> Self {
+    return ChatCompletionRequest {
+      model,
+      messages,
+      stop: Vec::new(),
+      logprobs: false,
+      temperature: None,
+      grammar: String::new(),
+    };
+  }
+
+  /// Sets sequences at which the LLM stops generating.
+  ///
+  /// # Arguments
+  ///
+  /// * `stop` - The stop sequences (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// The updated `ChatCompletionRequest` instance.
+  pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+    self.stop = stop;
+    return self;
+  }
+
+  /// Requests the log probability of each generated token.
+  ///
+  /// # Arguments
+  ///
+  /// * `logprobs` - Whether to request log probabilities
+  ///
+  /// # Returns
+  ///
+  /// The updated `ChatCompletionRequest` instance.
+  pub fn with_logprobs(mut self, logprobs: bool) -> Self {
+    self.logprobs = logprobs;
+    return self;
+  }
+
+  /// Sets the sampling temperature.
+  ///
+  /// # Arguments
+  ///
+  /// * `temperature` - The temperature, or `None` for the service default
+  ///
+  /// # Returns
+  ///
+  /// The updated `ChatCompletionRequest` instance.
+  pub fn with_temperature(mut self, temperature: Option<f64>) -> Self {
+    self.temperature = temperature;
+    return self;
+  }
+
+  /// Constrains the answer with a GBNF grammar, a llama.cpp extension.
+  ///
+  /// # Arguments
+  ///
+  /// * `grammar` - The grammar (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// The updated `ChatCompletionRequest` instance.
+  pub fn with_grammar(mut self, grammar: String) -> Self {
+    self.grammar = grammar;
+    return self;
+  }
+}
+
+/// OpenAI-compatible chat message structure.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessage {
+  role: String,
+  content: String,
+}
+
+impl ChatMessage {
+  /// Creates a new `ChatMessage` with the specified role and content.
+  ///
+  /// # Arguments
+  ///
+  /// * `role` - Role of the message (e.g., "system", "user")
+  /// * `content` - Content of the message
+  ///
+  /// # Returns
+  ///
+  /// A new `ChatMessage` instance.
+  pub fn new(role: String, content: String) -> Self {
+    return ChatMessage { role, content };
+  }
+
+  /// Gets the content of the message.
+  ///
+  /// # Returns
+  ///
+  /// The message text.
+  pub fn content(&self) -> &str {
+    return &self.content;
+  }
+}
+
+/// llama.cpp native completion request.
+#[derive(Debug, Serialize)]
+pub struct CompletionRequest {
+  prompt: String,
+  n_predict: i64,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  stop: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  temperature: Option<f64>,
+  #[serde(skip_serializing_if = "String::is_empty")]
+  grammar: String,
+}
+
+impl CompletionRequest {
+  /// Creates a new `CompletionRequest` for a prompt.
+  ///
+  /// # Arguments
+  ///
+  /// * `prompt` - The prompt, already formatted with the chat template
+  /// * `n_predict` - Most tokens to generate (-1 for no limit)
+  ///
+  /// # Returns
+  ///
+  /// A new `CompletionRequest` instance.
+  pub fn new(prompt: String, n_predict: i64) -> Self {
+    return CompletionRequest {
+      prompt,
+      n_predict,
+      stop: Vec::new(),
+      temperature: None,
+      grammar: String::new(),
+    };
+  }
+
+  /// Sets sequences at which the LLM stops generating.
+  ///
+  /// # Arguments
+  ///
+  /// * `stop` - The stop sequences (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// The updated `CompletionRequest` instance.
+  pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+    self.stop = stop;
+    return self;
+  }
+
+  /// Sets the sampling temperature.
+  ///
+  /// # Arguments
+  ///
+  /// * `temperature` - The temperature, or `None` for the service default
+  ///
+  /// # Returns
+  ///
+  /// The updated `CompletionRequest` instance.
+  pub fn with_temperature(mut self, temperature: Option<f64>) -> Self {
+    self.temperature = temperature;
+    return self;
+  }
+
+  /// Constrains the answer with a GBNF grammar.
+  ///
+  /// # Arguments
+  ///
+  /// * `grammar` - The grammar (none if empty)
+  ///
+  /// # Returns
+  ///
+  /// The updated `CompletionRequest` instance.
+  pub fn with_grammar(mut self, grammar: String) -> Self {
+    self.grammar = grammar;
+    return self;
+  }
+}
+
+/// OpenAI-compatible embeddings request.
+#[derive(Debug, Serialize)]
+pub struct EmbeddingsRequest<'a> {
+  pub model: &'a str,
+  pub input: [&'a str; 2],
+}
+
+/// llama.cpp request formatting chat messages with the model's template.
+#[derive(Debug, Serialize)]
+pub struct ApplyTemplateRequest<'a> {
+  pub messages: &'a [ChatMessage],
+}
@@ -0,0 +1,73 @@
+use serde::Deserialize;
+
+/// OpenAI-compatible chat completion response.
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionResponse {
+  pub choices: Vec<Choice>,
+  pub usage: Option<Usage>,
+}
+
+/// Token usage reported with the chat completion response.
+#[derive(Debug, Deserialize)]
+pub struct Usage {
+  #[serde(default)]
+  pub prompt_tokens: u64,
+  #[serde(default)]
+  pub completion_tokens: u64,
+}
+
+/// A choice in the chat completion response.
+#[derive(Debug, Deserialize)]
+pub struct Choice {
+  pub message: ResponseMessage,
+  pub logprobs: Option<ChoiceLogprobs>,
+}
+
+/// Token log probabilities of a choice, if requested and supported.
+#[derive(Debug, Deserialize)]
+pub struct ChoiceLogprobs {
+  pub content: Option<Vec<TokenLogprob>>,
+}
+
+/// A generated token and its log probability.
+#[derive(Debug, Deserialize)]
+pub struct TokenLogprob {
+  pub token: String,
+  pub logprob: f64,
+}
+
+/// Message structure in the response.
+#[derive(Debug, Deserialize)]
+pub struct ResponseMessage {
+  pub content: String,
+}
+
+/// llama.cpp native completion response.
+#[derive(Debug, Deserialize)]
+pub struct CompletionResponse {
+  pub content: String,
+  #[serde(default)]
+  pub tokens_evaluated: u64,
+  #[serde(default)]
+  pub tokens_predicted: u64,
+}
+
+/// OpenAI-compatible embeddings response.
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingsResponse {
+  pub data: Vec<Embedding>,
+}
+
+/// The embedding of one input.
+#[derive(Debug, Deserialize)]
+pub struct Embedding {
+  pub embedding: Vec<f64>,
+  #[serde(default)]
+  pub index: usize,
+}
+
+/// Prompt produced by llama.cpp's chat template.
+#[derive(Debug, Deserialize)]
+pub struct ApplyTemplateResponse {
+  pub prompt: String,
+}
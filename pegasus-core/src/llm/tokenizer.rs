@@ -0,0 +1,159 @@
+//! Token counting for the configured model.
+//!
+//! OpenAI models are counted exactly with their BPE vocabulary. Other
+//! models, such as most local ones, fall back to an estimate of one token
+//! per four ASCII characters and one per other character, unless a
+//! vocabulary is chosen with `[llm] tokenizer`.
+
+use std::fmt;
+
+use tiktoken_rs::CoreBPE;
+use tiktoken_rs::tokenizer::{self, Tokenizer as Vocabulary};
+
+/// Name of the fallback estimator in `[llm] tokenizer`.
+const ESTIMATE: &str = "estimate";
+
+/// Vocabularies that can be chosen by name.
+const VOCABULARIES: &[(&str, Vocabulary)] = &[
+  ("o200k_base", Vocabulary::O200kBase),
+  ("cl100k_base", Vocabulary::Cl100kBase),
+  ("p50k_base", Vocabulary::P50kBase),
+  ("r50k_base", Vocabulary::R50kBase),
+];
+
+/// Counts tokens of text.
+#[derive(Clone, Copy)]
+pub struct Tokenizer {
+  name: &'static str,
+  bpe: Option<&'static CoreBPE>,
+}
+
+impl Tokenizer {
+  /// Gets the tokenizer selected by a setting.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - A vocabulary name, `estimate`, or empty to pick the
+  ///   vocabulary of the model
+  /// * `model` - The configured model name
+  ///
+  /// # Returns
+  ///
+  /// The named tokenizer, the model's tokenizer, or the estimator if the
+  /// model is unknown.
+  pub fn resolve(name: &str, model: &str) -> Self {
+    if let Some(tokenizer) = Tokenizer::named(name) {
+      return tokenizer;
+    }
+    return match tokenizer::get_tokenizer(model) {
+      Some(vocabulary) => Tokenizer::from_vocabulary(vocabulary),
+      None => Tokenizer::estimator(),
+    };
+  }
+
+  /// Gets a tokenizer by name.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - A vocabulary name such as `cl100k_base`, or `estimate`
+  ///
+  /// # Returns
+  ///
+  /// The tokenizer, or `None` if the name is unknown.
+  pub fn named(name: &str) -> Option<Self> {
+    if name == ESTIMATE {
+      return Some(Tokenizer::estimator());
+    }
+    return VOCABULARIES
+      .iter()
+      .find(|(vocabulary_name, _)| *vocabulary_name == name)
+      .map(|(_, vocabulary)| Tokenizer::from_vocabulary(*vocabulary));
+  }
+
+  /// Gets the names accepted by [`Tokenizer::named`].
+  ///
+  /// # Returns
+  ///
+  /// The vocabulary names followed by `estimate`.
+  pub fn names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> =
+      VOCABULARIES.iter().map(|(name, _)| *name).collect();
+    names.push(ESTIMATE);
+    return names;
+  }
+
+  /// Gets the estimator used for unknown models.
+  ///
+  /// # Returns
+  ///
+  /// A tokenizer that estimates counts from characters.
+  pub fn estimator() -> Self {
+    return Tokenizer {
+      name: ESTIMATE,
+      bpe: None,
+    };
+  }
+
+  fn from_vocabulary(vocabulary: Vocabulary) -> Self {
+    let (name, bpe) = match vocabulary {
+      Vocabulary::O200kBase => {
+        ("o200k_base", tiktoken_rs::o200k_base_singleton())
+      }
+      Vocabulary::Cl100kBase => {
+        ("cl100k_base", tiktoken_rs::cl100k_base_singleton())
+      }
+      Vocabulary::P50kBase => ("p50k_base", tiktoken_rs::p50k_base_singleton()),
+      Vocabulary::P50kEdit => ("p50k_edit", tiktoken_rs::p50k_edit_singleton()),
+      Vocabulary::R50kBase | Vocabulary::Gpt2 => {
+        ("r50k_base", tiktoken_rs::r50k_base_singleton())
+      }
+    };
+    return Tokenizer {
+      name,
+      bpe: Some(bpe),
+    };
+  }
+
+  /// Gets the name of the tokenizer.
+  ///
+  /// # Returns
+  ///
+  /// The vocabulary name, or `estimate`.
+  pub fn name(&self) -> &'static str {
+    return self.name;
+  }
+
+  /// Checks whether counts are exact rather than estimated.
+  ///
+  /// # Returns
+  ///
+  /// `true` if a vocabulary is used, `false` for the estimator.
+  pub fn is_exact(&self) -> bool {
+    return self.bpe.is_some();
+  }
+
+  /// Counts the tokens of a text.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The text to count
+  ///
+  /// # Returns
+  ///
+  /// The number of tokens, exact or estimated.
+  pub fn count(&self, text: &str) -> usize {
+    if let Some(bpe) = self.bpe {
+      return bpe.encode_ordinary(text).len();
+    }
+
+    let ascii = text.chars().filter(char::is_ascii).count();
+    let other = text.chars().count() - ascii;
+    return ascii.div_ceil(4) + other;
+  }
+}
+
+impl fmt::Debug for Tokenizer {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.debug_tuple("Tokenizer").field(&self.name).finish();
+  }
+}
@@ -0,0 +1,297 @@
+//! Protection of tokens that must survive refinement unchanged.
+//!
+//! Transcripts often carry inline timestamps (`00:01:23`), speaker tags at
+//! the start of a line (`SPEAKER_01:`, `Jane Doe:`) and bracketed
+//! annotations (`[laughter]`, `[inaudible]`) that a model tends to drop or
+//! reword. With `[llm] keep_verbatim_tokens`, each of them is replaced with
+//! a numbered marker before the text is sent, and spliced back into the
+//! answer afterwards. A marker the model dropped has its token reinserted
+//! after the previous one, so no token is lost even when the model ignores
+//! the instruction to keep the markers.
+//!
+//! Emoji and non-speech markers (see [`crate::llm::non_speech`]) are
+//! protected the same way when their policy is `keep`.
+
+use crate::llm::non_speech;
+use crate::logging;
+use crate::{elog, vlog};
+
+/// Opening and closing characters of markers, rare in transcripts.
+const MARKER_OPEN: char = '⟦';
+const MARKER_CLOSE: char = '⟧';
+
+/// Longest bracketed annotation, in characters, protected as a token.
+const MAX_ANNOTATION_CHARS: usize = 40;
+
+/// Longest speaker name, in characters, protected as a token.
+const MAX_SPEAKER_CHARS: usize = 32;
+
+/// Kinds of tokens protected from the model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VerbatimKinds {
+  /// Timestamps, speaker tags and bracketed annotations
+  pub transcript: bool,
+  /// Emoji
+  pub emoji: bool,
+  /// Non-speech markers like `[laughter]` or `(applause)`
+  pub markers: bool,
+}
+
+impl VerbatimKinds {
+  /// Checks whether any kind of token is protected.
+  ///
+  /// # Returns
+  ///
+  /// `true` if at least one kind is enabled.
+  pub fn any(&self) -> bool {
+    return self.transcript || self.emoji || self.markers;
+  }
+}
+
+/// Tokens replaced with markers in a text.
+#[derive(Debug, Default, Clone)]
+pub struct VerbatimTokens {
+  tokens: Vec<String>,
+}
+
+impl VerbatimTokens {
+  /// Replaces the verbatim tokens of a text with markers.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The text to refine
+  /// * `kinds` - The kinds of tokens to protect
+  ///
+  /// # Returns
+  ///
+  /// The text with markers, and the tokens to restore in the answer.
+  pub fn protect(text: &str, kinds: VerbatimKinds) -> (String, Self) {
+    let mut protected = String::with_capacity(text.len());
+    let mut tokens = Vec::new();
+    for line in text.split_inclusive('\n') {
+      let mut rest = line;
+      if let Some(length) =
+        speaker_tag_length(rest).filter(|_| kinds.transcript)
+      {
+        protected.push_str(&marker(tokens.len()));
+        tokens.push(rest[..length].to_string());
+        rest = &rest[length..];
+      }
+
+      while let Some((start, end)) = next_token(rest, kinds) {
+        protected.push_str(&rest[..start]);
+        protected.push_str(&marker(tokens.len()));
+        tokens.push(rest[start..end].to_string());
+        rest = &rest[end..];
+      }
+      protected.push_str(rest);
+    }
+
+    if !tokens.is_empty() {
+      vlog!("Protecting {} verbatim tokens", tokens.len());
+    }
+    return (protected, VerbatimTokens { tokens });
+  }
+
+  /// Checks whether any tokens were protected.
+  ///
+  /// # Returns
+  ///
+  /// `true` if the text had no verbatim tokens.
+  pub fn is_empty(&self) -> bool {
+    return self.tokens.is_empty();
+  }
+
+  /// Splices the tokens back into the answer.
+  ///
+  /// Each marker is replaced with its token. A token whose marker is
+  /// missing is inserted after the previous token (or at the start), and
+  /// markers the model repeated or made up are removed.
+  ///
+  /// # Arguments
+  ///
+  /// * `answer` - The refined text with markers
+  ///
+  /// # Returns
+  ///
+  /// The refined text with the original tokens.
+  pub fn restore(&self, answer: &str) -> String {
+    let mut text = answer.to_string();
+    let mut position = 0;
+    let mut reinserted = 0;
+    for (index, token) in self.tokens.iter().enumerate() {
+      let marker = marker(index);
+      let found = text[position..]
+        .find(&marker)
+        .map(|offset| position + offset)
+        .or_else(|| text.find(&marker));
+      match found {
+        Some(start) => {
+          text.replace_range(start..start + marker.len(), token);
+          position = start + token.len();
+        }
+        None => {
+          reinserted += 1;
+          let insertion = if position == 0 {
+            format!("{} ", token)
+          } else {
+            format!(" {}", token)
+          };
+          text.insert_str(position, &insertion);
+          position += insertion.len();
+        }
+      }
+    }
+
+    if reinserted > 0 {
+      elog!(
+        logging::WARNING,
+        "The model dropped {} of {} verbatim tokens; reinserted them",
+        reinserted,
+        self.tokens.len()
+      );
+    }
+    return remove_stray_markers(&text);
+  }
+}
+
+/// Builds the instruction to keep the markers.
+///
+/// # Returns
+///
+/// A paragraph to append to the system prompt.
+pub fn build_instruction() -> String {
+  return format!(
+    "\n\nThe text contains markers like {}0{}. Keep every marker exactly \
+     as written, in the same place relative to the surrounding words.",
+    MARKER_OPEN, MARKER_CLOSE
+  );
+}
+
+/// Formats the marker of a token.
+fn marker(index: usize) -> String {
+  return format!("{}{}{}", MARKER_OPEN, index, MARKER_CLOSE);
+}
+
+/// Gets the length of a speaker tag at the start of a line, such as
+/// `SPEAKER_01:` or `Jane Doe:`, up to and including the colon.
+///
+/// Names are up to three words, each starting with an uppercase letter or
+/// a digit.
+fn speaker_tag_length(line: &str) -> Option<usize> {
+  let indent = line.len() - line.trim_start().len();
+  let colon = line.find(':')?;
+  let name = &line[indent..colon];
+  let words: Vec<&str> = name.split(' ').collect();
+  let is_name = !name.is_empty()
+    && name.chars().count() <= MAX_SPEAKER_CHARS
+    && words.len() <= 3
+    && words.iter().all(|word| {
+      return word.chars().next().is_some_and(|first| {
+        return first.is_uppercase() || first.is_ascii_digit();
+      }) && word
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    });
+  let after = &line[colon + 1..];
+  if !is_name || !after.starts_with([' ', '\t']) {
+    return None;
+  }
+  return Some(colon + 1);
+}
+
+/// Finds the next token of the given kinds in a line.
+///
+/// # Returns
+///
+/// The byte range of the token, or `None` if there is none.
+fn next_token(line: &str, kinds: VerbatimKinds) -> Option<(usize, usize)> {
+  let mut previous = None;
+  for (start, c) in line.char_indices() {
+    let rest = &line[start..];
+    let marker = kinds.markers.then(|| non_speech::marker_length(rest));
+    let emoji = kinds.emoji.then(|| non_speech::emoji_length(rest));
+    if let Some(length) = marker.flatten().or(emoji.flatten()) {
+      return Some((start, start + length));
+    }
+    if !kinds.transcript {
+      continue;
+    }
+    if c == '[' {
+      let rest = &line[start + 1..];
+      let end = rest.find([']', '[', '\n']);
+      if let Some(end) = end
+        && rest[end..].starts_with(']')
+        && (1..=MAX_ANNOTATION_CHARS).contains(&rest[..end].chars().count())
+      {
+        return Some((start, start + end + 2));
+      }
+    }
+    let at_word_start = !previous.is_some_and(char::is_alphanumeric);
+    if c.is_ascii_digit()
+      && at_word_start
+      && let Some(length) = timestamp_length(&line[start..])
+    {
+      return Some((start, start + length));
+    }
+    previous = Some(c);
+  }
+  return None;
+}
+
+/// Gets the length of a timestamp like `1:23`, `01:02:03` or
+/// `00:01:02.500` at the start of a string.
+fn timestamp_length(text: &str) -> Option<usize> {
+  let bytes = text.as_bytes();
+  let digits = |from: usize| {
+    return bytes[from..]
+      .iter()
+      .take_while(|b| b.is_ascii_digit())
+      .count();
+  };
+
+  let mut length = digits(0);
+  if !(1..=2).contains(&length) {
+    return None;
+  }
+  let mut groups = 0;
+  while groups < 2
+    && bytes.get(length) == Some(&b':')
+    && digits(length + 1) == 2
+  {
+    length += 3;
+    groups += 1;
+  }
+  if groups == 0 {
+    return None;
+  }
+  if matches!(bytes.get(length), Some(b'.' | b',')) {
+    let fraction = digits(length + 1);
+    if (1..=3).contains(&fraction) {
+      length += 1 + fraction;
+    }
+  }
+  if bytes.get(length).is_some_and(u8::is_ascii_alphanumeric) {
+    return None;
+  }
+  return Some(length);
+}
+
+/// Removes markers left in a text after the tokens were restored.
+fn remove_stray_markers(text: &str) -> String {
+  let mut result = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find(MARKER_OPEN) {
+    let after = &rest[start + MARKER_OPEN.len_utf8()..];
+    let digits = after.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && after[digits..].starts_with(MARKER_CLOSE) {
+      result.push_str(rest[..start].trim_end_matches(' '));
+      rest = &after[digits + MARKER_CLOSE.len_utf8()..];
+    } else {
+      result.push_str(&rest[..start + MARKER_OPEN.len_utf8()]);
+      rest = after;
+    }
+  }
+  result.push_str(rest);
+  return result;
+}
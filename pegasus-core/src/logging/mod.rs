@@ -0,0 +1,340 @@
+//! Global logging module with verbosity levels.
+//!
+//! Provides a global verbosity level and macros for conditional logging
+//! with timestamps. All modules can use the [`vlog!`] and [`dlog!`] macros
+//! to print diagnostics without passing verbosity through function
+//! signatures. Every diagnostic goes to stderr, so stdout only ever carries
+//! the refined text.
+//!
+//! ## Components
+//!
+//! - [`Verbosity`]: The levels selected with `-q` and repeated `-v`
+//! - [`set_verbosity`]: Set the global verbosity at application startup
+//! - [`is_verbose`]: Check if verbose messages are printed
+//! - [`vlog!`]: Macro for printing timestamped verbose messages (`-v`)
+//! - [`dlog!`]: Macro for printing timestamped debug messages (`-vv`)
+//! - [`slog!`]: Macro for printing verbose events with `key=value` fields,
+//!   such as prompt sizes and truncation decisions, that can be filtered
+//!   and parsed from logs alone
+//! - [`set_journald`]: Format messages for the systemd journal
+//! - [`elog!`]: Macro for printing warnings, errors and server messages
+//! - [`request_id`]: The correlation ID of the running refinement, which
+//!   prefixes verbose and debug messages (and warnings and errors with
+//!   `-v` or in journald mode)
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use pegasus_core::logging::{Verbosity, set_verbosity};
+//! use pegasus_core::vlog;
+//!
+//! // At startup, set the verbosity from CLI args:
+//! set_verbosity(Verbosity::from_flags(false, 1));
+//!
+//! // Anywhere in the codebase:
+//! let user = "world";
+//! vlog!("Hello world...");
+//! vlog!("Hello {}", user);
+//! ```
+
+pub mod request_id;
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+#[doc(hidden)]
+pub use chrono;
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+static JOURNALD: AtomicBool = AtomicBool::new(false);
+
+/// Syslog priority of error messages.
+pub const ERROR: u8 = 3;
+/// Syslog priority of warnings.
+pub const WARNING: u8 = 4;
+/// Syslog priority of informational messages.
+pub const INFO: u8 = 6;
+/// Syslog priority of verbose messages.
+pub const DEBUG: u8 = 7;
+
+/// How much is logged to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+  /// Errors only (`-q`)
+  Quiet,
+  /// Errors, warnings and server messages
+  Normal,
+  /// Progress messages as well (`-v`)
+  Verbose,
+  /// Request-level details as well (`-vv`)
+  Debug,
+}
+
+impl Verbosity {
+  /// Creates a verbosity from CLI flags.
+  ///
+  /// # Arguments
+  ///
+  /// * `quiet` - Whether `-q` was passed
+  /// * `verbose` - How many times `-v` was passed
+  ///
+  /// # Returns
+  ///
+  /// The appropriate `Verbosity` variant.
+  pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+    if quiet {
+      return Self::Quiet;
+    }
+    return match verbose {
+      0 => Self::Normal,
+      1 => Self::Verbose,
+      _ => Self::Debug,
+    };
+  }
+
+  /// Gets the least important syslog priority that is printed.
+  ///
+  /// # Returns
+  ///
+  /// The syslog priority.
+  pub fn max_priority(self) -> u8 {
+    return match self {
+      Self::Quiet => ERROR,
+      Self::Normal | Self::Verbose => INFO,
+      Self::Debug => DEBUG,
+    };
+  }
+}
+
+/// Sets the global verbosity.
+///
+/// This should be called once at application startup, typically from
+/// main.rs after parsing CLI arguments.
+///
+/// # Arguments
+///
+/// * `verbosity` - How much to log
+pub fn set_verbosity(verbosity: Verbosity) {
+  VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// Gets the global verbosity.
+///
+/// # Returns
+///
+/// The current `Verbosity`.
+pub fn verbosity() -> Verbosity {
+  return match VERBOSITY.load(Ordering::Relaxed) {
+    0 => Verbosity::Quiet,
+    1 => Verbosity::Normal,
+    2 => Verbosity::Verbose,
+    _ => Verbosity::Debug,
+  };
+}
+
+/// Checks if verbose messages are printed.
+///
+/// # Returns
+///
+/// `true` if the verbosity is at least [`Verbosity::Verbose`].
+pub fn is_verbose() -> bool {
+  return verbosity() >= Verbosity::Verbose;
+}
+
+/// Sets whether messages are formatted for the systemd journal.
+///
+/// In journald mode messages carry a `<priority>` prefix instead of a
+/// timestamp, since the journal records its own.
+///
+/// # Arguments
+///
+/// * `value` - Whether to format messages for the journal
+pub fn set_journald(value: bool) {
+  JOURNALD.store(value, Ordering::Relaxed);
+}
+
+/// Checks if messages are formatted for the systemd journal.
+///
+/// # Returns
+///
+/// `true` if journald mode is enabled, `false` otherwise.
+pub fn is_journald() -> bool {
+  return JOURNALD.load(Ordering::Relaxed);
+}
+
+/// Formats an event and its fields in logfmt, as `event key=value ...`.
+///
+/// Values that are empty or contain whitespace, quotes or `=` are quoted.
+///
+/// # Arguments
+///
+/// * `event` - The name of the event
+/// * `fields` - The names and values of its fields
+///
+/// # Returns
+///
+/// The formatted line.
+pub fn fields(event: &str, fields: &[(&str, &dyn Display)]) -> String {
+  let mut line = event.to_string();
+  for (key, value) in fields {
+    let value = value.to_string();
+    let plain = !value.is_empty()
+      && !value
+        .chars()
+        .any(|c| c.is_whitespace() || c == '"' || c == '=');
+    if plain {
+      line.push_str(&format!(" {}={}", key, value));
+    } else {
+      line.push_str(&format!(" {}={:?}", key, value));
+    }
+  }
+  return line;
+}
+
+/// Prints a verbose message with timestamp to stderr if `-v` was given.
+///
+/// Messages are prefixed with the current time in HH:MM:SS format, or with
+/// the info priority in journald mode, followed by the request ID.
+/// Below [`Verbosity::Verbose`], this macro does nothing.
+///
+/// # Examples
+///
+/// ```rust
+/// use pegasus_core::vlog;
+///
+/// let user = "world";
+/// vlog!("Hello world...");
+/// vlog!("Hello {}", user);
+/// ```
+#[macro_export]
+macro_rules! vlog {
+    ($($arg:tt)*) => {
+        if $crate::logging::is_verbose() {
+            let prefix = $crate::logging::request_id::log_prefix();
+            if $crate::logging::is_journald() {
+                eprintln!(
+                    "<{}>{}{}",
+                    $crate::logging::INFO,
+                    prefix,
+                    format!($($arg)*)
+                );
+            } else {
+                let now = $crate::logging::chrono::Local::now();
+                eprintln!(
+                    "[{}] {}{}",
+                    now.format("%H:%M:%S"),
+                    prefix,
+                    format!($($arg)*)
+                );
+            }
+        }
+    };
+}
+
+/// Prints a debug message with timestamp to stderr if `-vv` was given.
+///
+/// Messages are prefixed with the current time in HH:MM:SS format, or with
+/// the debug priority in journald mode, followed by the request ID.
+/// Below [`Verbosity::Debug`], this macro does nothing.
+///
+/// # Examples
+///
+/// ```rust
+/// use pegasus_core::dlog;
+///
+/// dlog!("Sending POST request to: {}", "http://127.0.0.1:8080");
+/// ```
+#[macro_export]
+macro_rules! dlog {
+    ($($arg:tt)*) => {
+        if $crate::logging::verbosity() >= $crate::logging::Verbosity::Debug {
+            let prefix = $crate::logging::request_id::log_prefix();
+            if $crate::logging::is_journald() {
+                eprintln!(
+                    "<{}>{}{}",
+                    $crate::logging::DEBUG,
+                    prefix,
+                    format!($($arg)*)
+                );
+            } else {
+                let now = $crate::logging::chrono::Local::now();
+                eprintln!(
+                    "[{}] {}{}",
+                    now.format("%H:%M:%S"),
+                    prefix,
+                    format!($($arg)*)
+                );
+            }
+        }
+    };
+}
+
+/// Prints a verbose event with `key=value` fields to stderr if `-v` was
+/// given.
+///
+/// The event is printed like [`vlog!`] messages, formatted by [`fields`].
+/// The values are only evaluated when the event is printed, so costly
+/// measurements like token counts can be passed directly.
+///
+/// # Examples
+///
+/// ```rust
+/// use pegasus_core::slog;
+///
+/// let tokens = 1200;
+/// slog!("prompt", kind = "text", user_tokens = tokens);
+/// ```
+#[macro_export]
+macro_rules! slog {
+    ($event:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::logging::is_verbose() {
+            $crate::vlog!(
+                "{}",
+                $crate::logging::fields(
+                    $event,
+                    &[$((
+                        stringify!($key),
+                        &$value as &dyn ::std::fmt::Display
+                    )),*]
+                )
+            );
+        }
+    };
+}
+
+/// Prints a warning, an error or a message from a long-running server to
+/// stderr.
+///
+/// Messages less important than the verbosity allows are dropped, so `-q`
+/// leaves only errors. In journald mode the message is prefixed with its
+/// syslog priority so the journal records it at the right level. There, or
+/// with `-v`, messages logged during a request carry its request ID.
+///
+/// # Examples
+///
+/// ```rust
+/// use pegasus_core::elog;
+/// use pegasus_core::logging;
+///
+/// elog!(logging::INFO, "Listening on {}", "/run/user/1000/pegasus.sock");
+/// ```
+#[macro_export]
+macro_rules! elog {
+    ($priority:expr, $($arg:tt)*) => {{
+        let priority: u8 = $priority;
+        if priority <= $crate::logging::verbosity().max_priority() {
+            let prefix = if $crate::logging::is_journald()
+                || $crate::logging::is_verbose()
+            {
+                $crate::logging::request_id::log_prefix()
+            } else {
+                String::new()
+            };
+            if $crate::logging::is_journald() {
+                eprintln!("<{}>{}{}", priority, prefix, format!($($arg)*));
+            } else {
+                eprintln!("{}{}", prefix, format!($($arg)*));
+            }
+        }
+    }};
+}
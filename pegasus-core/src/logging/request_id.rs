@@ -0,0 +1,83 @@
+//! Correlation IDs of refinements.
+//!
+//! Every refinement runs with a request ID: the CLI generates one per
+//! invocation, and long-running modes (the daemon, `--line-mode`,
+//! dictation, the REPL) generate one per request or unit. The ID is
+//! prefixed to log messages, added to JSON output and webhook payloads,
+//! forwarded to the daemon and, with `[network] request_id_header`, sent
+//! to services as `X-Request-Id`, so a failure can be traced from the
+//! client through the daemon to the server logs.
+//!
+//! The ID is held in a task-local, so tasks spawned with `tokio::spawn`
+//! only see it when wrapped in [`inherit`].
+
+use std::future::Future;
+
+tokio::task_local! {
+  static REQUEST_ID: String;
+}
+
+/// HTTP header carrying the request ID.
+pub const HEADER: &str = "X-Request-Id";
+
+/// Generates a new request ID.
+///
+/// # Returns
+///
+/// A random UUID in its hyphenated form.
+pub fn generate() -> String {
+  return uuid::Uuid::new_v4().to_string();
+}
+
+/// Runs a future with the given request ID.
+///
+/// # Arguments
+///
+/// * `id` - The request ID
+/// * `future` - The future to run
+///
+/// # Returns
+///
+/// The output of the future.
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+  return REQUEST_ID.scope(id, future).await;
+}
+
+/// Wraps a future so that it runs with the current request ID, for
+/// futures that are spawned onto other tasks.
+///
+/// # Arguments
+///
+/// * `future` - The future to wrap
+///
+/// # Returns
+///
+/// A future running with the request ID of the caller, if any.
+pub fn inherit<F: Future>(future: F) -> impl Future<Output = F::Output> {
+  let id = current();
+  return async move {
+    return match id {
+      Some(id) => REQUEST_ID.scope(id, future).await,
+      None => future.await,
+    };
+  };
+}
+
+/// Gets the request ID of the running task.
+///
+/// # Returns
+///
+/// The request ID, or `None` outside of a request.
+pub fn current() -> Option<String> {
+  return REQUEST_ID.try_with(Clone::clone).ok();
+}
+
+/// Gets the prefix of log messages of the running task.
+///
+/// # Returns
+///
+/// `"[<id>] "`, or an empty string outside of a request.
+#[doc(hidden)]
+pub fn log_prefix() -> String {
+  return current().map_or_else(String::new, |id| format!("[{}] ", id));
+}
@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Metrics exporter errors.
+///
+/// Represents errors that can occur while serving the `/metrics` endpoint.
+#[derive(Error, Debug)]
+pub enum MetricsError {
+  #[error(
+    "{}",
+    t!("error-metrics-bind", address = .0.as_str(), error = .1.as_str())
+  )]
+  Bind(String, String),
+
+  #[error("{}", t!("error-metrics-io", error = .0.as_str()))]
+  Io(String),
+}
+
+/// Result type for metrics operations.
+pub type MetricsResult<T> = Result<T, MetricsError>;
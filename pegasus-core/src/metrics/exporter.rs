@@ -0,0 +1,135 @@
+//! Minimal HTTP endpoint serving the metrics to Prometheus.
+
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::metrics;
+use crate::metrics::errors::{MetricsError, MetricsResult};
+use crate::vlog;
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Longest accepted request line or header, in bytes.
+const MAX_LINE_LENGTH: u64 = 8192;
+
+/// Most headers read before the request is rejected.
+const MAX_HEADERS: usize = 100;
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Binds the metrics endpoint.
+///
+/// # Arguments
+///
+/// * `address` - The `host:port` address to listen on
+///
+/// # Returns
+///
+/// A `MetricsResult<TcpListener>` containing the bound listener or an error.
+pub async fn bind(address: &str) -> MetricsResult<TcpListener> {
+  return TcpListener::bind(address)
+    .await
+    .map_err(|e| MetricsError::Bind(address.to_string(), e.to_string()));
+}
+
+/// Serves `GET /metrics` on a bound listener until the process exits.
+///
+/// # Arguments
+///
+/// * `listener` - The listener returned by [`bind`]
+///
+/// # Returns
+///
+/// A `MetricsResult<()>` that is only returned on failure.
+pub async fn serve(listener: TcpListener) -> MetricsResult<()> {
+  loop {
+    let (stream, _) = listener
+      .accept()
+      .await
+      .map_err(|e| MetricsError::Io(e.to_string()))?;
+
+    tokio::spawn(async move {
+      if let Err(e) = respond(stream).await {
+        vlog!("Metrics request failed: {}", e);
+      }
+    });
+  }
+}
+
+/// Answers a single HTTP request and closes the connection.
+async fn respond(stream: TcpStream) -> std::io::Result<()> {
+  let mut reader = BufReader::new(stream);
+
+  let request_line = tokio::time::timeout(READ_TIMEOUT, read_head(&mut reader))
+    .await
+    .map_err(|_| {
+      std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out")
+    })??;
+
+  let mut parts = request_line.split_whitespace();
+  let method = parts.next().unwrap_or_default();
+  let path = parts.next().unwrap_or_default();
+
+  let (status, body) = match (method, path) {
+    ("GET", "/metrics") => ("200 OK", metrics::render()),
+    (_, "/metrics") => ("405 Method Not Allowed", String::new()),
+    _ => ("404 Not Found", String::new()),
+  };
+
+  let response = format!(
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    status,
+    CONTENT_TYPE,
+    body.len(),
+    body
+  );
+
+  let mut stream = reader.into_inner();
+  stream.write_all(response.as_bytes()).await?;
+  return stream.shutdown().await;
+}
+
+/// Reads the request line and drains the headers; the request body, if any,
+/// is ignored.
+///
+/// Lines longer than [`MAX_LINE_LENGTH`] and more than [`MAX_HEADERS`]
+/// headers are rejected, so a client cannot grow memory without limit.
+async fn read_head(
+  reader: &mut BufReader<TcpStream>,
+) -> std::io::Result<String> {
+  let request_line = read_bounded_line(reader).await?;
+
+  let mut headers = 0;
+  while read_bounded_line(reader).await?.len() > 2 {
+    headers += 1;
+    if headers > MAX_HEADERS {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "too many headers",
+      ));
+    }
+  }
+
+  return Ok(request_line);
+}
+
+/// Reads a single line of at most [`MAX_LINE_LENGTH`] bytes.
+async fn read_bounded_line(
+  reader: &mut BufReader<TcpStream>,
+) -> std::io::Result<String> {
+  let mut line = String::new();
+  (&mut *reader)
+    .take(MAX_LINE_LENGTH)
+    .read_line(&mut line)
+    .await?;
+  if !line.ends_with('\n') && line.len() as u64 >= MAX_LINE_LENGTH {
+    return Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      "line too long",
+    ));
+  }
+  return Ok(line);
+}
@@ -0,0 +1,259 @@
+//! Prometheus metrics for long-running servers.
+//!
+//! Request, LLM and token counters are recorded in a process-wide registry
+//! as requests are served. When `[server] metrics_address` is set, the
+//! daemon serves them in the Prometheus text format on `/metrics`.
+//!
+//! ## Metrics
+//!
+//! - `pegasus_requests_total{method, status}`: JSON-RPC requests by outcome
+//!   (`ok`, `error` or `cancelled`)
+//! - `pegasus_request_duration_seconds{method}`: request latency histogram
+//! - `pegasus_errors_total{code}`: failed requests by JSON-RPC error code
+//! - `pegasus_llm_requests_total{status}`: chat completion requests
+//! - `pegasus_llm_tokens_total{kind}`: prompt and completion tokens reported
+//!   by the LLM service
+//! - `pegasus_cache_lookups_total{cache, result}`: cache lookups by outcome
+//!   (`hit` or `miss`), from which the hit rate is derived
+
+pub mod errors;
+pub mod exporter;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Upper bounds in seconds of the request latency histogram buckets.
+const DURATION_BUCKETS: [f64; 10] =
+  [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+static REGISTRY: LazyLock<Mutex<Registry>> =
+  LazyLock::new(|| Mutex::new(Registry::default()));
+
+/// Outcome of a served request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestStatus {
+  /// The request succeeded
+  Ok,
+  /// The request failed with the given JSON-RPC error code
+  Error(i64),
+  /// The request was cancelled by the client
+  Cancelled,
+}
+
+/// A cumulative latency histogram.
+#[derive(Debug, Default)]
+struct Histogram {
+  buckets: [u64; DURATION_BUCKETS.len()],
+  count: u64,
+  sum: f64,
+}
+
+/// All recorded metrics.
+#[derive(Debug, Default)]
+struct Registry {
+  requests: BTreeMap<(String, &'static str), u64>,
+  durations: BTreeMap<String, Histogram>,
+  errors: BTreeMap<i64, u64>,
+  llm_requests: BTreeMap<&'static str, u64>,
+  prompt_tokens: u64,
+  completion_tokens: u64,
+  cache_lookups: BTreeMap<(&'static str, &'static str), u64>,
+}
+
+/// Records a served request.
+///
+/// # Arguments
+///
+/// * `method` - The JSON-RPC method name
+/// * `status` - The outcome of the request
+/// * `duration` - How long the request took to complete
+pub fn record_request(method: &str, status: RequestStatus, duration: Duration) {
+  let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+
+  let label = match status {
+    RequestStatus::Ok => "ok",
+    RequestStatus::Error(code) => {
+      *registry.errors.entry(code).or_default() += 1;
+      "error"
+    }
+    RequestStatus::Cancelled => "cancelled",
+  };
+  *registry
+    .requests
+    .entry((method.to_string(), label))
+    .or_default() += 1;
+
+  // Cancelled requests never complete, so their duration is meaningless.
+  if status == RequestStatus::Cancelled {
+    return;
+  }
+
+  let seconds = duration.as_secs_f64();
+  let histogram = registry.durations.entry(method.to_string()).or_default();
+  for (bucket, bound) in histogram.buckets.iter_mut().zip(DURATION_BUCKETS) {
+    if seconds <= bound {
+      *bucket += 1;
+    }
+  }
+  histogram.count += 1;
+  histogram.sum += seconds;
+}
+
+/// Records a chat completion request to the LLM service.
+///
+/// # Arguments
+///
+/// * `success` - Whether the request returned a completion
+pub fn record_llm_request(success: bool) {
+  let status = if success { "ok" } else { "error" };
+  let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+  *registry.llm_requests.entry(status).or_default() += 1;
+}
+
+/// Records the token usage reported by the LLM service.
+///
+/// # Arguments
+///
+/// * `prompt_tokens` - Tokens in the prompt
+/// * `completion_tokens` - Tokens in the completion
+pub fn record_tokens(prompt_tokens: u64, completion_tokens: u64) {
+  let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+  registry.prompt_tokens += prompt_tokens;
+  registry.completion_tokens += completion_tokens;
+}
+
+/// Records a cache lookup.
+///
+/// # Arguments
+///
+/// * `cache` - The name of the cache, e.g. `capabilities`
+/// * `hit` - Whether the lookup found a usable entry
+pub fn record_cache_lookup(cache: &'static str, hit: bool) {
+  let result = if hit { "hit" } else { "miss" };
+  let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+  *registry.cache_lookups.entry((cache, result)).or_default() += 1;
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+///
+/// # Returns
+///
+/// The metrics as a `String`.
+pub fn render() -> String {
+  let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+  let mut output = String::new();
+
+  write_header(
+    &mut output,
+    "pegasus_requests_total",
+    "Requests served, by method and outcome.",
+    "counter",
+  );
+  for ((method, status), count) in &registry.requests {
+    let _ = writeln!(
+      output,
+      "pegasus_requests_total{{method=\"{}\",status=\"{}\"}} {}",
+      method, status, count
+    );
+  }
+
+  write_header(
+    &mut output,
+    "pegasus_request_duration_seconds",
+    "Time taken to complete requests.",
+    "histogram",
+  );
+  for (method, histogram) in &registry.durations {
+    for (count, bound) in histogram.buckets.iter().zip(DURATION_BUCKETS) {
+      let _ = writeln!(
+        output,
+        "pegasus_request_duration_seconds_bucket{{method=\"{}\",le=\"{}\"}} {}",
+        method, bound, count
+      );
+    }
+    let _ = writeln!(
+      output,
+      "pegasus_request_duration_seconds_bucket{{method=\"{}\",le=\"+Inf\"}} {}",
+      method, histogram.count
+    );
+    let _ = writeln!(
+      output,
+      "pegasus_request_duration_seconds_sum{{method=\"{}\"}} {}",
+      method, histogram.sum
+    );
+    let _ = writeln!(
+      output,
+      "pegasus_request_duration_seconds_count{{method=\"{}\"}} {}",
+      method, histogram.count
+    );
+  }
+
+  write_header(
+    &mut output,
+    "pegasus_errors_total",
+    "Failed requests, by JSON-RPC error code.",
+    "counter",
+  );
+  for (code, count) in &registry.errors {
+    let _ = writeln!(
+      output,
+      "pegasus_errors_total{{code=\"{}\"}} {}",
+      code, count
+    );
+  }
+
+  write_header(
+    &mut output,
+    "pegasus_llm_requests_total",
+    "Chat completion requests sent to the LLM service.",
+    "counter",
+  );
+  for (status, count) in &registry.llm_requests {
+    let _ = writeln!(
+      output,
+      "pegasus_llm_requests_total{{status=\"{}\"}} {}",
+      status, count
+    );
+  }
+
+  write_header(
+    &mut output,
+    "pegasus_llm_tokens_total",
+    "Tokens reported by the LLM service.",
+    "counter",
+  );
+  let _ = writeln!(
+    output,
+    "pegasus_llm_tokens_total{{kind=\"prompt\"}} {}",
+    registry.prompt_tokens
+  );
+  let _ = writeln!(
+    output,
+    "pegasus_llm_tokens_total{{kind=\"completion\"}} {}",
+    registry.completion_tokens
+  );
+
+  write_header(
+    &mut output,
+    "pegasus_cache_lookups_total",
+    "Cache lookups, by cache and outcome.",
+    "counter",
+  );
+  for ((cache, result), count) in &registry.cache_lookups {
+    let _ = writeln!(
+      output,
+      "pegasus_cache_lookups_total{{cache=\"{}\",result=\"{}\"}} {}",
+      cache, result, count
+    );
+  }
+
+  return output;
+}
+
+/// Writes the `HELP` and `TYPE` lines of a metric.
+fn write_header(output: &mut String, name: &str, help: &str, kind: &str) {
+  let _ = writeln!(output, "# HELP {} {}", name, help);
+  let _ = writeln!(output, "# TYPE {} {}", name, kind);
+}
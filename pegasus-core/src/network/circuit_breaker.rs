@@ -0,0 +1,166 @@
+//! Circuit breaker for failing service endpoints.
+//!
+//! Tracks recent failures per endpoint and, once too many failures occur
+//! within the configured window, "opens" the circuit so subsequent requests
+//! fail fast instead of hammering a dead server. After the cooldown elapses
+//! a single trial request is let through (half-open); a success closes the
+//! circuit again, a failure re-opens it.
+//!
+//! State is shared process-wide so every `HttpClient` talking to the same
+//! endpoint observes the same breaker.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::network::errors::{NetworkError, NetworkResult};
+use crate::vlog;
+
+static CIRCUITS: LazyLock<Mutex<HashMap<String, CircuitState>>> =
+  LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Failure bookkeeping for a single endpoint.
+#[derive(Debug, Default)]
+struct CircuitState {
+  failures: Vec<Instant>,
+  opened_at: Option<Instant>,
+}
+
+/// Circuit breaker settings applied to requests for an endpoint.
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreaker {
+  failure_threshold: u32,
+  window: Duration,
+  cooldown: Duration,
+}
+
+impl CircuitBreaker {
+  /// Creates a new CircuitBreaker.
+  ///
+  /// A `failure_threshold` of 0 disables the breaker.
+  ///
+  /// # Arguments
+  ///
+  /// * `failure_threshold` - Failures within the window that open the circuit
+  /// * `window` - Time window in which failures are counted
+  /// * `cooldown` - How long the circuit stays open before a trial request
+  ///
+  /// # Returns
+  ///
+  /// A new `CircuitBreaker` instance.
+  pub fn new(
+    failure_threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+  ) -> Self {
+    return CircuitBreaker {
+      failure_threshold,
+      window,
+      cooldown,
+    };
+  }
+
+  /// Returns a breaker that never opens.
+  ///
+  /// # Returns
+  ///
+  /// A disabled `CircuitBreaker` instance.
+  pub fn disabled() -> Self {
+    return CircuitBreaker::new(0, Duration::ZERO, Duration::ZERO);
+  }
+
+  fn is_enabled(&self) -> bool {
+    return self.failure_threshold > 0;
+  }
+
+  /// Checks whether a request to the endpoint may proceed.
+  ///
+  /// # Arguments
+  ///
+  /// * `endpoint` - The endpoint identifier (usually the base URL)
+  ///
+  /// # Returns
+  ///
+  /// `Ok(())` if the request may proceed, or `NetworkError::CircuitOpen`.
+  pub fn check(&self, endpoint: &str) -> NetworkResult<()> {
+    if !self.is_enabled() {
+      return Ok(());
+    }
+
+    let mut circuits = CIRCUITS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(state) = circuits.get_mut(endpoint) else {
+      return Ok(());
+    };
+
+    if let Some(opened_at) = state.opened_at {
+      let elapsed = opened_at.elapsed();
+      if elapsed < self.cooldown {
+        let remaining = (self.cooldown - elapsed).as_secs().max(1);
+        vlog!(
+          "Circuit open for {}, failing fast ({}s remaining)",
+          endpoint,
+          remaining
+        );
+        return Err(NetworkError::CircuitOpen(endpoint.to_string(), remaining));
+      }
+
+      // Half-open: allow one trial request and restart the cooldown so
+      // concurrent callers keep failing fast until it resolves.
+      vlog!("Circuit half-open for {}, allowing trial request", endpoint);
+      state.opened_at = Some(Instant::now());
+    }
+
+    return Ok(());
+  }
+
+  /// Records a successful request, closing the circuit.
+  ///
+  /// # Arguments
+  ///
+  /// * `endpoint` - The endpoint identifier (usually the base URL)
+  pub fn record_success(&self, endpoint: &str) {
+    if !self.is_enabled() {
+      return;
+    }
+
+    let mut circuits = CIRCUITS.lock().unwrap_or_else(|e| e.into_inner());
+    if circuits.remove(endpoint).is_some() {
+      vlog!("Circuit closed for {}", endpoint);
+    }
+  }
+
+  /// Records a failed request, opening the circuit if the threshold is hit.
+  ///
+  /// # Arguments
+  ///
+  /// * `endpoint` - The endpoint identifier (usually the base URL)
+  pub fn record_failure(&self, endpoint: &str) {
+    if !self.is_enabled() {
+      return;
+    }
+
+    let mut circuits = CIRCUITS.lock().unwrap_or_else(|e| e.into_inner());
+    let state = circuits.entry(endpoint.to_string()).or_default();
+    let now = Instant::now();
+
+    state
+      .failures
+      .retain(|failure| now.duration_since(*failure) <= self.window);
+    state.failures.push(now);
+
+    if state.opened_at.is_some() {
+      // A failed trial request re-opens the circuit for another cooldown.
+      state.opened_at = Some(now);
+      return;
+    }
+
+    if state.failures.len() >= self.failure_threshold as usize {
+      vlog!(
+        "Circuit opened for {} after {} failures",
+        endpoint,
+        state.failures.len()
+      );
+      state.opened_at = Some(now);
+    }
+  }
+}
@@ -0,0 +1,55 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Network-related errors.
+///
+/// Represents errors that can occur during HTTP requests and network communication.
+#[derive(Error, Debug)]
+pub enum NetworkError {
+  #[error("{}", t!("error-network-invalid-url", url = .0.as_str()))]
+  InvalidURL(String),
+
+  #[error("{}", t!("error-network-invalid-proxy", url = .0.as_str()))]
+  InvalidProxy(String),
+
+  #[error("{}", t!("error-network-unsupported-socket", url = .0.as_str()))]
+  UnsupportedSocket(String),
+
+  #[error("{}", t!("error-network-client", error = .0.as_str()))]
+  ClientFailed(String),
+
+  #[error("{}", t!("error-network-request"))]
+  RequestFailed,
+
+  #[error("{}", t!("error-network-response", status = *.0))]
+  ResponseError(u16),
+
+  #[error("{}", t!("error-network-decode"))]
+  DecodeError,
+
+  #[error(
+    "{}",
+    t!("error-network-circuit-open", url = .0.as_str(), seconds = *.1)
+  )]
+  CircuitOpen(String, u64),
+}
+
+impl NetworkError {
+  /// Checks whether the request may succeed when sent again.
+  ///
+  /// # Returns
+  ///
+  /// `true` for connection failures, rate limiting (HTTP 429) and server
+  /// errors (HTTP 5xx).
+  pub fn is_transient(&self) -> bool {
+    return match self {
+      NetworkError::RequestFailed => true,
+      NetworkError::ResponseError(status) => *status == 429 || *status >= 500,
+      _ => false,
+    };
+  }
+}
+
+/// Result type for network operations.
+pub type NetworkResult<T> = Result<T, NetworkError>;
@@ -0,0 +1,550 @@
+//! HTTP client module for network requests to external services.
+//!
+//! This module provides a simple HTTP client for communicating with remote
+//! services. It supports JSON POST requests, and JSON response parsing.
+//!
+//! ## Main Components
+//!
+//! - [`HttpClient`]: HTTP client for making requests to external services
+//! - [`CircuitBreaker`]: Fail-fast protection for repeatedly failing endpoints
+//! - [`Scheduler`]: Load balancing across equivalent endpoints
+//! - [`MultipartForm`]: `multipart/form-data` bodies for file uploads
+//! - [`NetworkError`]: Error types for network operations
+//! - [`NetworkResult<T>`]: Result type alias for network operations
+//!
+//! ## Features
+//!
+//! - POST requests with JSON body and optional headers
+//! - POST requests with `multipart/form-data` body for file uploads
+//! - POST requests whose response is ignored, for webhooks
+//! - GET requests for probing the service
+//! - GET requests for raw bytes, for downloads
+//! - JSON response deserialization
+//! - URL validation before requests
+//! - Circuit breaking for endpoints that keep failing
+//! - Round-robin or least-loaded distribution over several endpoints
+//! - `X-Request-Id` headers carrying the request ID
+//! - HTTP, HTTPS and SOCKS5 proxies (configured or via `HTTPS_PROXY`)
+//! - Unix domain socket endpoints via `unix:///path/to.sock` URLs
+
+pub mod circuit_breaker;
+pub mod errors;
+pub mod multipart;
+pub mod scheduler;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::logging::request_id;
+use crate::network::circuit_breaker::CircuitBreaker;
+use crate::network::errors::{NetworkError, NetworkResult};
+use crate::network::multipart::MultipartForm;
+use crate::{dlog, vlog};
+
+const UNIX_SOCKET_SCHEME: &str = "unix://";
+const UNIX_SOCKET_HTTP_BASE: &str = "http://localhost";
+
+/// HTTP client for network requests to external services.
+///
+/// Provides generic POST functionality with multipart form support.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+  base_url: String,
+  unix_socket: Option<PathBuf>,
+  client: reqwest::Client,
+  circuit_breaker: CircuitBreaker,
+  request_id_header: bool,
+}
+
+impl HttpClient {
+  /// Creates a new HttpClient with base URL.
+  ///
+  /// A base URL of the form `unix:///run/llama.sock` connects to the
+  /// service over the given Unix domain socket instead of TCP.
+  ///
+  /// # Arguments
+  ///
+  /// * `base_url` - Base URL for all HTTP requests
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<HttpClient>` with the new client, or an error if the
+  /// client cannot be set up.
+  pub fn new(base_url: String) -> NetworkResult<Self> {
+    let unix_socket =
+      base_url.strip_prefix(UNIX_SOCKET_SCHEME).map(PathBuf::from);
+
+    let client = match &unix_socket {
+      Some(socket_path) => {
+        HttpClient::build_unix_socket_client(&base_url, socket_path)?
+      }
+      None => reqwest::Client::new(),
+    };
+
+    return Ok(HttpClient {
+      base_url,
+      unix_socket,
+      client,
+      circuit_breaker: CircuitBreaker::disabled(),
+      request_id_header: false,
+    });
+  }
+
+  /// Builds a reqwest client that sends every request over a Unix socket.
+  ///
+  /// # Arguments
+  ///
+  /// * `base_url` - The `unix://` URL of the service
+  /// * `socket_path` - Path to the Unix domain socket
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<reqwest::Client>` with the client bound to the
+  /// socket, or an error.
+  #[cfg(unix)]
+  fn build_unix_socket_client(
+    _base_url: &str,
+    socket_path: &std::path::Path,
+  ) -> NetworkResult<reqwest::Client> {
+    vlog!("Using Unix domain socket: {}", socket_path.display());
+    return reqwest::Client::builder()
+      .unix_socket(socket_path)
+      .build()
+      .map_err(|e| NetworkError::ClientFailed(e.to_string()));
+  }
+
+  #[cfg(not(unix))]
+  fn build_unix_socket_client(
+    base_url: &str,
+    _socket_path: &std::path::Path,
+  ) -> NetworkResult<reqwest::Client> {
+    return Err(NetworkError::UnsupportedSocket(base_url.to_string()));
+  }
+
+  /// Returns the HTTP URL requests are addressed to.
+  ///
+  /// For Unix socket endpoints this is a placeholder host, since the
+  /// connection itself goes to the socket.
+  ///
+  /// # Returns
+  ///
+  /// The HTTP base URL as a string slice.
+  fn http_base_url(&self) -> &str {
+    if self.unix_socket.is_some() {
+      return UNIX_SOCKET_HTTP_BASE;
+    }
+    return &self.base_url;
+  }
+
+  /// Routes all requests of this client through the given proxy.
+  ///
+  /// Supports `http://`, `https://`, `socks5://` and `socks5h://` proxy URLs.
+  /// When no proxy is configured, the standard `HTTP_PROXY`, `HTTPS_PROXY`
+  /// and `NO_PROXY` environment variables are honored instead.
+  ///
+  /// # Arguments
+  ///
+  /// * `proxy_url` - The proxy URL
+  /// * `no_proxy` - Comma-separated hosts that bypass the proxy
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<HttpClient>` with the proxied client or an error.
+  pub fn with_proxy(
+    mut self,
+    proxy_url: &str,
+    no_proxy: &str,
+  ) -> NetworkResult<Self> {
+    if self.unix_socket.is_some() {
+      vlog!("Ignoring proxy for Unix domain socket endpoint");
+      return Ok(self);
+    }
+
+    let proxy = reqwest::Proxy::all(proxy_url)
+      .map_err(|_| NetworkError::InvalidProxy(proxy_url.to_string()))?
+      .no_proxy(reqwest::NoProxy::from_string(no_proxy));
+
+    self.client = reqwest::Client::builder()
+      .proxy(proxy)
+      .build()
+      .map_err(|_| NetworkError::InvalidProxy(proxy_url.to_string()))?;
+
+    vlog!("Using proxy: {}", proxy_url);
+
+    return Ok(self);
+  }
+
+  /// Sets the circuit breaker used to guard requests to this endpoint.
+  ///
+  /// # Arguments
+  ///
+  /// * `circuit_breaker` - The circuit breaker settings to apply
+  ///
+  /// # Returns
+  ///
+  /// The updated `HttpClient` instance.
+  pub fn with_circuit_breaker(
+    mut self,
+    circuit_breaker: CircuitBreaker,
+  ) -> Self {
+    self.circuit_breaker = circuit_breaker;
+    return self;
+  }
+
+  /// Sets whether requests carry the request ID in an `X-Request-Id`
+  /// header.
+  ///
+  /// # Arguments
+  ///
+  /// * `enabled` - Whether to send the header
+  ///
+  /// # Returns
+  ///
+  /// The updated `HttpClient` instance.
+  pub fn with_request_id_header(mut self, enabled: bool) -> Self {
+    self.request_id_header = enabled;
+    return self;
+  }
+
+  /// Returns the base URL of this client.
+  ///
+  /// # Returns
+  ///
+  /// The base URL as a string slice.
+  pub fn base_url(&self) -> &str {
+    return &self.base_url;
+  }
+
+  /// Sends a POST request with JSON body to the given endpoint.
+  ///
+  /// Validates the service URL, sends the request with JSON body and optional
+  /// headers, and deserializes the JSON response into the specified type.
+  ///
+  /// # Type Parameters
+  ///
+  /// * `T` - Type to deserialize the JSON response into
+  /// * `B` - Type of the request body (must implement Serialize)
+  ///
+  /// # Arguments
+  ///
+  /// * `body` - JSON-serializable body to send in the request
+  /// * `endpoint` - Endpoint path to append to the base URL
+  /// * `headers` - Optional map of header names to values
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<T>` containing the deserialized response or an error.
+  pub async fn post_with_json<T, B>(
+    &self,
+    body: &B,
+    endpoint: &str,
+    headers: Option<HashMap<String, String>>,
+  ) -> NetworkResult<T>
+  where
+    T: serde::de::DeserializeOwned,
+    B: Serialize,
+  {
+    self.circuit_breaker.check(&self.base_url)?;
+
+    let result = self.send_post_with_json(body, endpoint, headers).await;
+
+    match &result {
+      Ok(_) => self.circuit_breaker.record_success(&self.base_url),
+      Err(NetworkError::DecodeError) => {}
+      Err(_) => self.circuit_breaker.record_failure(&self.base_url),
+    }
+
+    return result;
+  }
+
+  /// Performs the POST request without circuit breaker bookkeeping.
+  async fn send_post_with_json<T, B>(
+    &self,
+    body: &B,
+    endpoint: &str,
+    headers: Option<HashMap<String, String>>,
+  ) -> NetworkResult<T>
+  where
+    T: serde::de::DeserializeOwned,
+    B: Serialize,
+  {
+    self.check_url().await?;
+
+    let full_url = self.endpoint_url(endpoint);
+
+    dlog!("Sending POST request to: {}", full_url);
+
+    let request_builder = self.client.post(&full_url).json(body);
+    return self.send(request_builder, headers).await;
+  }
+
+  /// Sends a POST request with a `multipart/form-data` body to the given
+  /// endpoint.
+  ///
+  /// Unlike [`HttpClient::post_with_json`], the URL is not checked first,
+  /// since hosted transcription services may not answer at their base URL.
+  ///
+  /// # Type Parameters
+  ///
+  /// * `T` - Type to deserialize the JSON response into
+  ///
+  /// # Arguments
+  ///
+  /// * `form` - The form to send
+  /// * `endpoint` - Endpoint path to append to the base URL
+  /// * `headers` - Optional map of header names to values
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<T>` containing the deserialized response or an error.
+  pub async fn post_multipart<T>(
+    &self,
+    form: MultipartForm,
+    endpoint: &str,
+    headers: Option<HashMap<String, String>>,
+  ) -> NetworkResult<T>
+  where
+    T: serde::de::DeserializeOwned,
+  {
+    self.circuit_breaker.check(&self.base_url)?;
+
+    let full_url = self.endpoint_url(endpoint);
+    dlog!("Sending multipart POST request to: {}", full_url);
+
+    let request_builder =
+      self.client.post(&full_url).multipart(form.into_form());
+    let result = self.send(request_builder, headers).await;
+
+    match &result {
+      Ok(_) => self.circuit_breaker.record_success(&self.base_url),
+      Err(NetworkError::DecodeError) => {}
+      Err(_) => self.circuit_breaker.record_failure(&self.base_url),
+    }
+
+    return result;
+  }
+
+  /// Sends a GET request, or a POST request when a body is given, and
+  /// decodes the JSON response.
+  ///
+  /// Meant for optional probes of the service: the URL is not checked
+  /// first and failures are not counted by the circuit breaker, since an
+  /// unsupported endpoint says nothing about the health of the service.
+  ///
+  /// # Type Parameters
+  ///
+  /// * `T` - Type to deserialize the JSON response into
+  ///
+  /// # Arguments
+  ///
+  /// * `endpoint` - Endpoint path to append to the base URL
+  /// * `body` - JSON body to POST, or `None` to GET
+  /// * `headers` - Optional map of header names to values
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<T>` containing the deserialized response or an error.
+  pub async fn fetch_json<T>(
+    &self,
+    endpoint: &str,
+    body: Option<&serde_json::Value>,
+    headers: Option<HashMap<String, String>>,
+  ) -> NetworkResult<T>
+  where
+    T: serde::de::DeserializeOwned,
+  {
+    let full_url = self.endpoint_url(endpoint);
+    let request_builder = match body {
+      Some(body) => {
+        dlog!("Sending POST request to: {}", full_url);
+        self.client.post(&full_url).json(body)
+      }
+      None => {
+        dlog!("Sending GET request to: {}", full_url);
+        self.client.get(&full_url)
+      }
+    };
+    return self.send(request_builder, headers).await;
+  }
+
+  /// Sends a GET request to the given endpoint and returns the body.
+  ///
+  /// # Arguments
+  ///
+  /// * `endpoint` - Endpoint path to append to the base URL, or an empty
+  ///   string for the base URL itself
+  /// * `headers` - Optional map of header names to values
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<Vec<u8>>` containing the response body or an error.
+  pub async fn fetch_bytes(
+    &self,
+    endpoint: &str,
+    headers: Option<HashMap<String, String>>,
+  ) -> NetworkResult<Vec<u8>> {
+    let full_url = self.endpoint_url(endpoint);
+    dlog!("Sending GET request to: {}", full_url);
+    let response = self
+      .send_for_status(self.client.get(&full_url), headers)
+      .await?;
+    let body = response
+      .bytes()
+      .await
+      .map_err(|_| NetworkError::DecodeError)?;
+    return Ok(body.to_vec());
+  }
+
+  /// Sends a POST request with a JSON body to the base URL, accepting any
+  /// successful response without decoding it.
+  ///
+  /// Meant for notifications like webhooks, whose responses carry no data.
+  /// The URL is not checked first.
+  ///
+  /// # Type Parameters
+  ///
+  /// * `B` - Type of the request body (must implement Serialize)
+  ///
+  /// # Arguments
+  ///
+  /// * `body` - JSON-serializable body to send in the request
+  /// * `headers` - Optional map of header names to values
+  ///
+  /// # Returns
+  ///
+  /// A `NetworkResult<()>` indicating whether the request succeeded.
+  pub async fn notify_with_json<B>(
+    &self,
+    body: &B,
+    headers: Option<HashMap<String, String>>,
+  ) -> NetworkResult<()>
+  where
+    B: Serialize,
+  {
+    self.circuit_breaker.check(&self.base_url)?;
+
+    let full_url = self.endpoint_url("");
+    dlog!("Sending POST request to: {}", full_url);
+
+    let request_builder = self.client.post(&full_url).json(body);
+    let result = self
+      .send_for_status(request_builder, headers)
+      .await
+      .map(|_| ());
+
+    match &result {
+      Ok(_) => self.circuit_breaker.record_success(&self.base_url),
+      Err(_) => self.circuit_breaker.record_failure(&self.base_url),
+    }
+
+    return result;
+  }
+
+  /// Builds the full URL of an endpoint, or the base URL for an empty
+  /// endpoint.
+  fn endpoint_url(&self, endpoint: &str) -> String {
+    let base_url = self.http_base_url();
+    if endpoint.is_empty() {
+      return base_url.to_string();
+    }
+    if base_url.ends_with("/") {
+      return format!("{}{}", base_url, endpoint);
+    }
+    return format!("{}/{}", base_url, endpoint);
+  }
+
+  /// Sends a request with optional headers and decodes the JSON response.
+  async fn send<T>(
+    &self,
+    request_builder: reqwest::RequestBuilder,
+    headers: Option<HashMap<String, String>>,
+  ) -> NetworkResult<T>
+  where
+    T: serde::de::DeserializeOwned,
+  {
+    let response = self.send_for_status(request_builder, headers).await?;
+
+    let parsed_response = response
+      .json::<T>()
+      .await
+      .map_err(|_| NetworkError::DecodeError)?;
+
+    return Ok(parsed_response);
+  }
+
+  /// Sends a request with optional headers and fails unless the response
+  /// status is successful.
+  async fn send_for_status(
+    &self,
+    mut request_builder: reqwest::RequestBuilder,
+    headers: Option<HashMap<String, String>>,
+  ) -> NetworkResult<reqwest::Response> {
+    if let Some(hdrs) = headers {
+      for (key, value) in hdrs {
+        request_builder = request_builder.header(key, value);
+      }
+    }
+    if self.request_id_header
+      && let Some(id) = request_id::current()
+    {
+      request_builder = request_builder.header(request_id::HEADER, id);
+    }
+
+    let response = request_builder
+      .send()
+      .await
+      .map_err(|_| NetworkError::RequestFailed)?;
+
+    dlog!(
+      "Received response from service. Status: {}",
+      response.status()
+    );
+
+    if !response.status().is_success() {
+      return Err(NetworkError::ResponseError(response.status().as_u16()));
+    }
+
+    return Ok(response);
+  }
+
+  async fn check_url(&self) -> NetworkResult<()> {
+    vlog!("Checking if service URL is reachable...");
+
+    if let Some(socket_path) = &self.unix_socket
+      && !socket_path.exists()
+    {
+      vlog!("Unix socket does not exist: {}", socket_path.display());
+      return Err(NetworkError::InvalidURL(self.base_url.clone()));
+    }
+
+    let _url = reqwest::Url::parse(&self.base_url).map_err(|e| {
+      vlog!("Invalid URL format: {}", e);
+      NetworkError::InvalidURL(self.base_url.clone())
+    })?;
+
+    let response =
+      self
+        .client
+        .get(self.http_base_url())
+        .send()
+        .await
+        .map_err(|e| {
+          vlog!("Failed to connect to URL: {}", e);
+          NetworkError::RequestFailed
+        })?;
+
+    let status = response.status();
+    if status != reqwest::StatusCode::OK
+      && status != reqwest::StatusCode::NOT_FOUND
+    {
+      vlog!("URL returned unexpected status: {}", status);
+      return Err(NetworkError::InvalidURL(self.base_url.clone()));
+    }
+
+    vlog!("Service URL is reachable with status: {}", status);
+
+    return Ok(());
+  }
+}
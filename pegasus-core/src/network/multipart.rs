@@ -0,0 +1,88 @@
+//! `multipart/form-data` request bodies.
+//!
+//! Audio transcription endpoints take the recording as a file upload.
+//! Files are streamed from disk rather than read into memory first, with
+//! their content type guessed from the file extension.
+
+use std::path::Path;
+
+use reqwest::Body;
+use reqwest::multipart::{Form, Part};
+
+/// A `multipart/form-data` body.
+#[derive(Debug)]
+pub struct MultipartForm {
+  form: Form,
+}
+
+impl MultipartForm {
+  /// Creates an empty form with a unique boundary.
+  ///
+  /// # Returns
+  ///
+  /// A new `MultipartForm` instance.
+  pub fn new() -> Self {
+    return MultipartForm { form: Form::new() };
+  }
+
+  /// Adds a text field.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The field name
+  /// * `value` - The field value
+  ///
+  /// # Returns
+  ///
+  /// The updated `MultipartForm` instance.
+  pub fn text(self, name: &str, value: &str) -> Self {
+    return MultipartForm {
+      form: self.form.text(name.to_string(), value.to_string()),
+    };
+  }
+
+  /// Adds a file field, streamed from disk when the request is sent.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The field name
+  /// * `path` - Path to the file
+  /// * `file_name` - The file name reported to the server
+  ///
+  /// # Returns
+  ///
+  /// A `std::io::Result<MultipartForm>` containing the updated form, or an
+  /// error if the file cannot be opened.
+  pub async fn file(
+    self,
+    name: &str,
+    path: &Path,
+    file_name: &str,
+  ) -> std::io::Result<Self> {
+    let file = tokio::fs::File::open(path).await?;
+    let length = file.metadata().await?.len();
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let part = Part::stream_with_length(Body::from(file), length)
+      .file_name(file_name.to_string())
+      .mime_str(mime.as_ref())
+      .map_err(std::io::Error::other)?;
+    return Ok(MultipartForm {
+      form: self.form.part(name.to_string(), part),
+    });
+  }
+
+  /// Finishes the form.
+  ///
+  /// # Returns
+  ///
+  /// The reqwest form, which sets its own `Content-Type` header.
+  pub fn into_form(self) -> Form {
+    return self.form;
+  }
+}
+
+impl Default for MultipartForm {
+  fn default() -> Self {
+    return MultipartForm::new();
+  }
+}
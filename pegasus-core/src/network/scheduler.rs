@@ -0,0 +1,226 @@
+//! Load balancing across equivalent endpoints.
+//!
+//! With `[llm] endpoints`, requests are spread over `[llm] url` and the
+//! listed servers, which must serve the same model. The [`Scheduler`]
+//! picks an endpoint per request, either in turn or the one with the
+//! fewest requests in flight, and skips endpoints that failed recently
+//! until their health cooldown has passed. A request that fails on one
+//! endpoint is tried on the others before giving up.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::network::HttpClient;
+use crate::vlog;
+
+/// How long an endpoint is skipped after a failed request.
+const HEALTH_COOLDOWN: Duration = Duration::from_secs(15);
+
+/// How requests are distributed over the endpoints.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum BalanceStrategy {
+  /// Each endpoint in turn
+  #[default]
+  RoundRobin,
+  /// The endpoint with the fewest requests in flight
+  LeastLoaded,
+}
+
+impl fmt::Display for BalanceStrategy {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      BalanceStrategy::RoundRobin => "round-robin",
+      BalanceStrategy::LeastLoaded => "least-loaded",
+    });
+  }
+}
+
+/// An endpoint and its load and health.
+#[derive(Debug)]
+struct Endpoint {
+  client: HttpClient,
+  in_flight: AtomicUsize,
+  unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+  fn is_healthy(&self) -> bool {
+    let unhealthy_until = self
+      .unhealthy_until
+      .lock()
+      .unwrap_or_else(|e| e.into_inner());
+    return unhealthy_until.is_none_or(|until| Instant::now() >= until);
+  }
+}
+
+/// Distributes requests over equivalent endpoints.
+///
+/// Clones share the load and health of the endpoints.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+  endpoints: Arc<Vec<Endpoint>>,
+  strategy: BalanceStrategy,
+  next: Arc<AtomicUsize>,
+}
+
+impl Scheduler {
+  /// Creates a scheduler over the given endpoints.
+  ///
+  /// # Arguments
+  ///
+  /// * `clients` - HTTP clients of the endpoints, at least one
+  /// * `strategy` - How requests are distributed
+  ///
+  /// # Returns
+  ///
+  /// A new `Scheduler` instance.
+  pub fn new(clients: Vec<HttpClient>, strategy: BalanceStrategy) -> Self {
+    let endpoints = clients
+      .into_iter()
+      .map(|client| Endpoint {
+        client,
+        in_flight: AtomicUsize::new(0),
+        unhealthy_until: Mutex::new(None),
+      })
+      .collect();
+    return Scheduler {
+      endpoints: Arc::new(endpoints),
+      strategy,
+      next: Arc::new(AtomicUsize::new(0)),
+    };
+  }
+
+  /// Gets the number of endpoints.
+  ///
+  /// # Returns
+  ///
+  /// How many endpoints requests are distributed over.
+  pub fn len(&self) -> usize {
+    return self.endpoints.len();
+  }
+
+  /// Checks whether the scheduler has no endpoints.
+  ///
+  /// # Returns
+  ///
+  /// `true` if there are no endpoints.
+  pub fn is_empty(&self) -> bool {
+    return self.endpoints.is_empty();
+  }
+
+  /// Picks the endpoint for the next request.
+  ///
+  /// Healthy endpoints are preferred; when every remaining endpoint failed
+  /// recently, one of them is tried anyway.
+  ///
+  /// # Arguments
+  ///
+  /// * `tried` - Indices of endpoints the request already failed on
+  ///
+  /// # Returns
+  ///
+  /// A lease on the endpoint, or `None` if every endpoint was tried.
+  pub fn acquire(&self, tried: &[usize]) -> Option<Lease<'_>> {
+    let untried: Vec<usize> = (0..self.endpoints.len())
+      .filter(|index| !tried.contains(index))
+      .collect();
+    let healthy: Vec<usize> = untried
+      .iter()
+      .copied()
+      .filter(|index| self.endpoints[*index].is_healthy())
+      .collect();
+    let candidates = if healthy.is_empty() { untried } else { healthy };
+    if candidates.is_empty() {
+      return None;
+    }
+
+    // Least-loaded takes turns among the endpoints with the fewest
+    // requests in flight, so idle endpoints share sequential requests.
+    let candidates = match self.strategy {
+      BalanceStrategy::RoundRobin => candidates,
+      BalanceStrategy::LeastLoaded => {
+        let load = |index: &usize| {
+          self.endpoints[*index].in_flight.load(Ordering::Relaxed)
+        };
+        let least = candidates.iter().map(load).min().unwrap_or(0);
+        candidates
+          .into_iter()
+          .filter(|index| load(index) == least)
+          .collect()
+      }
+    };
+    let turn = self.next.fetch_add(1, Ordering::Relaxed);
+    let index = candidates[turn % candidates.len()];
+
+    let endpoint = &self.endpoints[index];
+    endpoint.in_flight.fetch_add(1, Ordering::Relaxed);
+    return Some(Lease { index, endpoint });
+  }
+}
+
+/// An endpoint picked for one request, counted as in flight until dropped.
+pub struct Lease<'a> {
+  index: usize,
+  endpoint: &'a Endpoint,
+}
+
+impl Lease<'_> {
+  /// Gets the index of the endpoint, for [`Scheduler::acquire`].
+  ///
+  /// # Returns
+  ///
+  /// The index of the endpoint.
+  pub fn index(&self) -> usize {
+    return self.index;
+  }
+
+  /// Gets the HTTP client of the endpoint.
+  ///
+  /// # Returns
+  ///
+  /// The HTTP client.
+  pub fn client(&self) -> &HttpClient {
+    return &self.endpoint.client;
+  }
+
+  /// Records the outcome of the request in the endpoint's health.
+  ///
+  /// # Arguments
+  ///
+  /// * `success` - Whether the endpoint answered
+  pub fn record(&self, success: bool) {
+    let mut unhealthy_until = self
+      .endpoint
+      .unhealthy_until
+      .lock()
+      .unwrap_or_else(|e| e.into_inner());
+    if success {
+      *unhealthy_until = None;
+      return;
+    }
+    vlog!(
+      "Endpoint {} failed, skipping it for {}s",
+      self.endpoint.client.base_url(),
+      HEALTH_COOLDOWN.as_secs()
+    );
+    *unhealthy_until = Some(Instant::now() + HEALTH_COOLDOWN);
+  }
+}
+
+impl Drop for Lease<'_> {
+  fn drop(&mut self) {
+    self.endpoint.in_flight.fetch_sub(1, Ordering::Relaxed);
+  }
+}
@@ -0,0 +1,418 @@
+//! Accuracy of a text against a reference, for `bench` and `wer`.
+//!
+//! Word and character error rates are the edit distance between the
+//! hypothesis and the reference (substitutions, deletions and insertions
+//! needed to turn one into the other) divided by the length of the
+//! reference. Words are compared as written, so punctuation and casing
+//! errors count, which is what refinement fixes; the normalized word error
+//! rate ignores both to measure word choice alone.
+//!
+//! Both are computed by [`crate::llm::alignment`], which also aligns
+//! refined words with a transcription. [`align`] pairs up the units of
+//! both texts along a minimal edit script, so the errors behind a rate can
+//! be printed with [`format_alignment`].
+
+use std::fmt;
+use std::ops::AddAssign;
+
+use serde::Serialize;
+
+use crate::llm::alignment;
+pub use crate::llm::alignment::{Aligned, Edit};
+use crate::output::format::OutputFormat;
+
+/// Largest alignment table, in cells, before alignment is refused.
+const MAX_ALIGNMENT_CELLS: usize = 50_000_000;
+
+/// Edits turning a hypothesis into its reference.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ErrorCounts {
+  /// Number of units (words or characters) in the reference
+  pub reference_length: usize,
+  /// Units of the reference replaced by another
+  pub substitutions: usize,
+  /// Units of the reference missing from the hypothesis
+  pub deletions: usize,
+  /// Units of the hypothesis missing from the reference
+  pub insertions: usize,
+}
+
+impl ErrorCounts {
+  /// Counts the edits between two sequences.
+  ///
+  /// # Arguments
+  ///
+  /// * `reference` - The expected units
+  /// * `hypothesis` - The actual units
+  ///
+  /// # Returns
+  ///
+  /// The counts of a minimal edit script; among scripts of the same length,
+  /// the one with the most substitutions.
+  pub fn between<T: PartialEq>(reference: &[T], hypothesis: &[T]) -> Self {
+    let edits = alignment::count_edits(reference, hypothesis);
+    return ErrorCounts {
+      reference_length: reference.len(),
+      substitutions: edits.substitutions,
+      deletions: edits.deletions,
+      insertions: edits.insertions,
+    };
+  }
+
+  /// Gets the total number of edits.
+  ///
+  /// # Returns
+  ///
+  /// Substitutions, deletions and insertions together.
+  pub fn errors(&self) -> usize {
+    return self.substitutions + self.deletions + self.insertions;
+  }
+
+  /// Gets the error rate.
+  ///
+  /// # Returns
+  ///
+  /// The edits per reference unit; 0.0 for two empty sequences and 1.0 for
+  /// a non-empty hypothesis of an empty reference. Can exceed 1.0.
+  pub fn rate(&self) -> f64 {
+    if self.reference_length == 0 {
+      return if self.errors() == 0 { 0.0 } else { 1.0 };
+    }
+    return self.errors() as f64 / self.reference_length as f64;
+  }
+}
+
+impl AddAssign for ErrorCounts {
+  fn add_assign(&mut self, other: Self) {
+    self.reference_length += other.reference_length;
+    self.substitutions += other.substitutions;
+    self.deletions += other.deletions;
+    self.insertions += other.insertions;
+  }
+}
+
+/// Word, character and normalized word errors of a text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Accuracy {
+  /// Errors between the words as written
+  pub words: ErrorCounts,
+  /// Errors between the characters, with whitespace runs as one space
+  pub characters: ErrorCounts,
+  /// Errors between the words, lowercased and without punctuation
+  pub normalized_words: ErrorCounts,
+}
+
+impl Accuracy {
+  /// Measures a text against its reference.
+  ///
+  /// # Arguments
+  ///
+  /// * `reference` - The expected text
+  /// * `hypothesis` - The actual text
+  ///
+  /// # Returns
+  ///
+  /// The error counts of the text.
+  pub fn measure(reference: &str, hypothesis: &str) -> Self {
+    return Accuracy {
+      words: ErrorCounts::between(&words(reference), &words(hypothesis)),
+      characters: ErrorCounts::between(
+        &characters(reference),
+        &characters(hypothesis),
+      ),
+      normalized_words: ErrorCounts::between(
+        &normalized_words(reference),
+        &normalized_words(hypothesis),
+      ),
+    };
+  }
+
+  /// Gets the word error rate.
+  ///
+  /// # Returns
+  ///
+  /// The word error rate (0.0 is a perfect match).
+  pub fn word_error_rate(&self) -> f64 {
+    return self.words.rate();
+  }
+
+  /// Gets the character error rate.
+  ///
+  /// # Returns
+  ///
+  /// The character error rate (0.0 is a perfect match).
+  pub fn character_error_rate(&self) -> f64 {
+    return self.characters.rate();
+  }
+
+  /// Converts the errors to JSON.
+  ///
+  /// # Returns
+  ///
+  /// An object with the `wer`, `cer` and `normalized_wer` rates and the
+  /// counts behind each.
+  pub fn to_json(&self) -> serde_json::Value {
+    return serde_json::json!({
+      "wer": self.word_error_rate(),
+      "cer": self.character_error_rate(),
+      "normalized_wer": self.normalized_words.rate(),
+      "words": self.words,
+      "characters": self.characters,
+      "normalized_words": self.normalized_words,
+    });
+  }
+}
+
+impl AddAssign for Accuracy {
+  fn add_assign(&mut self, other: Self) {
+    self.words += other.words;
+    self.characters += other.characters;
+    self.normalized_words += other.normalized_words;
+  }
+}
+
+impl fmt::Display for Accuracy {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return write!(
+      f,
+      "WER {:.1}%, CER {:.1}%, normalized WER {:.1}%",
+      self.word_error_rate() * 100.0,
+      self.character_error_rate() * 100.0,
+      self.normalized_words.rate() * 100.0
+    );
+  }
+}
+
+/// Aligns a hypothesis with its reference.
+///
+/// The alignment follows the same edit script as
+/// [`ErrorCounts::between`], so counting its edits gives the same errors.
+///
+/// # Arguments
+///
+/// * `reference` - The expected units
+/// * `hypothesis` - The actual units
+///
+/// # Returns
+///
+/// The steps of the alignment in order, or `None` if the sequences are too
+/// long to align.
+pub fn align<T: PartialEq + Clone>(
+  reference: &[T],
+  hypothesis: &[T],
+) -> Option<Vec<Aligned<T>>> {
+  return alignment::align(reference, hypothesis, MAX_ALIGNMENT_CELLS);
+}
+
+/// Formats a word alignment as `REF:` and `HYP:` rows with the edits
+/// marked below, wrapped to the given width.
+///
+/// Missing words are shown as asterisks, and edits are marked `S`, `D` and
+/// `I`.
+///
+/// # Arguments
+///
+/// * `alignment` - The alignment of the words
+/// * `width` - The maximum line width in characters
+///
+/// # Returns
+///
+/// Blocks of three rows, separated by blank lines.
+pub fn format_alignment(alignment: &[Aligned<&str>], width: usize) -> String {
+  const PREFIX: &str = "REF: ";
+  let mut blocks: Vec<[String; 3]> = Vec::new();
+  let mut rows = [String::new(), String::new(), String::new()];
+  for step in alignment {
+    let column = [step.reference, step.hypothesis]
+      .iter()
+      .map(|word| word.map_or(1, |word| word.chars().count()))
+      .max()
+      .unwrap_or(1);
+    let used = rows[0].chars().count();
+    if used > 0 && PREFIX.len() + used + 1 + column > width {
+      blocks.push(std::mem::take(&mut rows));
+    }
+
+    let missing = "*".repeat(column);
+    let marker = match step.edit {
+      Edit::Match => " ",
+      Edit::Substitution => "S",
+      Edit::Deletion => "D",
+      Edit::Insertion => "I",
+    };
+    let cells = [
+      step.reference.unwrap_or(&missing),
+      step.hypothesis.unwrap_or(&missing),
+      marker,
+    ];
+    for (row, cell) in rows.iter_mut().zip(cells) {
+      if !row.is_empty() {
+        row.push(' ');
+      }
+      row.push_str(&format!("{:<column$}", cell));
+    }
+  }
+  if !rows[0].is_empty() {
+    blocks.push(rows);
+  }
+
+  return blocks
+    .iter()
+    .map(|[reference, hypothesis, markers]| {
+      return format!(
+        "REF: {}\nHYP: {}\n     {}",
+        reference.trim_end(),
+        hypothesis.trim_end(),
+        markers.trim_end()
+      )
+      .trim_end()
+      .to_string();
+    })
+    .collect::<Vec<_>>()
+    .join("\n\n");
+}
+
+/// Formats the errors of a text, with the alignment of its words.
+///
+/// # Arguments
+///
+/// * `accuracy` - The errors of the text
+/// * `alignment` - The word alignment, or `None` to leave it out
+/// * `format` - The desired output format
+///
+/// # Returns
+///
+/// The alignment followed by the rates and their counts, or a JSON object
+/// with an `alignment` array.
+pub fn format_report(
+  accuracy: &Accuracy,
+  alignment: Option<&[Aligned<&str>]>,
+  format: OutputFormat,
+) -> String {
+  if format == OutputFormat::Json {
+    let mut value = accuracy.to_json();
+    value["alignment"] = serde_json::json!(alignment);
+    return value.to_string();
+  }
+
+  let rates = [
+    ("WER", &accuracy.words),
+    ("CER", &accuracy.characters),
+    ("Normalized WER", &accuracy.normalized_words),
+  ]
+  .iter()
+  .map(|(name, counts)| {
+    return format!(
+      "{}: {:.2}% (S {}, D {}, I {}, N {})",
+      name,
+      counts.rate() * 100.0,
+      counts.substitutions,
+      counts.deletions,
+      counts.insertions,
+      counts.reference_length
+    );
+  })
+  .collect::<Vec<_>>()
+  .join("\n");
+  return match alignment {
+    Some(alignment) if !alignment.is_empty() => {
+      format!("{}\n\n{}", format_alignment(alignment, 80), rates)
+    }
+    _ => rates,
+  };
+}
+
+/// Splits a text into words as written.
+///
+/// # Arguments
+///
+/// * `text` - The text to split
+///
+/// # Returns
+///
+/// The whitespace-separated words.
+pub fn words(text: &str) -> Vec<&str> {
+  return text.split_whitespace().collect();
+}
+
+/// Splits a text into lowercased words without punctuation.
+///
+/// # Arguments
+///
+/// * `text` - The text to split
+///
+/// # Returns
+///
+/// The normalized words; words of only punctuation are dropped.
+pub fn normalized_words(text: &str) -> Vec<String> {
+  return text
+    .split_whitespace()
+    .map(|word| {
+      word
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+    })
+    .filter(|word| !word.is_empty())
+    .collect();
+}
+
+/// Splits a text into characters, with each whitespace run as one space.
+fn characters(text: &str) -> Vec<char> {
+  return words(text).join(" ").chars().collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn counts(
+    reference_length: usize,
+    substitutions: usize,
+    deletions: usize,
+    insertions: usize,
+  ) -> ErrorCounts {
+    return ErrorCounts {
+      reference_length,
+      substitutions,
+      deletions,
+      insertions,
+    };
+  }
+
+  #[test]
+  fn counts_substitutions() {
+    let accuracy = Accuracy::measure("the cat sat", "the bat sat");
+    assert_eq!(accuracy.words, counts(3, 1, 0, 0));
+    assert_eq!(accuracy.characters, counts(11, 1, 0, 0));
+    assert!((accuracy.word_error_rate() - 1.0 / 3.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn counts_insertions() {
+    let accuracy = Accuracy::measure("the cat sat", "the fat cat sat");
+    assert_eq!(accuracy.words, counts(3, 0, 0, 1));
+    assert_eq!(accuracy.characters, counts(11, 0, 0, 4));
+  }
+
+  #[test]
+  fn counts_deletions() {
+    let accuracy = Accuracy::measure("the cat sat down", "the cat down");
+    assert_eq!(accuracy.words, counts(4, 0, 1, 0));
+    assert_eq!(accuracy.characters, counts(16, 0, 4, 0));
+  }
+
+  #[test]
+  fn normalizes_casing_and_punctuation() {
+    let accuracy = Accuracy::measure("The cat, sat.", "the cat sat");
+    assert_eq!(accuracy.words, counts(3, 3, 0, 0));
+    assert_eq!(accuracy.normalized_words, counts(3, 0, 0, 0));
+  }
+
+  #[test]
+  fn rates_empty_references() {
+    assert_eq!(ErrorCounts::between::<&str>(&[], &[]).rate(), 0.0);
+    assert_eq!(ErrorCounts::between(&[], &["word"]).rate(), 1.0);
+  }
+}
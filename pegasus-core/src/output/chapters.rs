@@ -0,0 +1,108 @@
+//! Chapters of a long transcript.
+//!
+//! `pegasus chapters` asks the LLM where the topics of a Whisper
+//! transcription change and what to call each section, then refines each
+//! section on its own. The result is rendered as Markdown with a linked
+//! table of contents, or listed as JSON.
+
+use serde::{Deserialize, Serialize};
+
+/// Where a chapter starts, as proposed by the LLM.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChapterBreak {
+  /// Index of the first segment of the chapter
+  pub segment: usize,
+  /// The chapter title
+  pub title: String,
+}
+
+/// A titled section of a refined transcript.
+#[derive(Debug, Clone, Serialize)]
+pub struct Chapter {
+  /// The chapter title
+  pub title: String,
+  /// Start time in seconds, if the transcription has timestamps
+  pub start: Option<f64>,
+  /// The refined text of the chapter
+  pub text: String,
+}
+
+/// Renders chapters as Markdown.
+///
+/// Each chapter becomes a second-level heading with an explicit anchor,
+/// and a table of contents linking to them comes first.
+///
+/// # Arguments
+///
+/// * `chapters` - The chapters in order
+///
+/// # Returns
+///
+/// The Markdown document.
+pub fn to_markdown(chapters: &[Chapter]) -> String {
+  let anchors: Vec<String> = chapters
+    .iter()
+    .enumerate()
+    .map(|(index, chapter)| anchor(index, &chapter.title))
+    .collect();
+
+  let mut markdown = String::new();
+  for (chapter, anchor) in chapters.iter().zip(&anchors) {
+    markdown.push_str(&format!(
+      "- [{}{}](#{})\n",
+      timestamp_prefix(chapter.start),
+      chapter.title,
+      anchor
+    ));
+  }
+
+  for (chapter, anchor) in chapters.iter().zip(&anchors) {
+    markdown.push_str(&format!(
+      "\n<a id=\"{}\"></a>\n## {}{}\n\n{}\n",
+      anchor,
+      timestamp_prefix(chapter.start),
+      chapter.title,
+      chapter.text
+    ));
+  }
+  return markdown.trim_end().to_string();
+}
+
+/// Formats a time for chapter headings.
+///
+/// # Arguments
+///
+/// * `seconds` - Time since the start of the recording
+///
+/// # Returns
+///
+/// `M:SS`, or `H:MM:SS` from the first hour on.
+pub fn format_timestamp(seconds: f64) -> String {
+  let total = seconds.max(0.0) as u64;
+  let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+  if hours > 0 {
+    return format!("{}:{:02}:{:02}", hours, minutes, seconds);
+  }
+  return format!("{}:{:02}", minutes, seconds);
+}
+
+/// Gets the `M:SS ` prefix of a title, empty without a start time.
+fn timestamp_prefix(start: Option<f64>) -> String {
+  return start
+    .map(|start| format!("{} ", format_timestamp(start)))
+    .unwrap_or_default();
+}
+
+/// Builds a unique anchor from the chapter number and title.
+fn anchor(index: usize, title: &str) -> String {
+  let slug = title
+    .to_lowercase()
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .collect::<Vec<_>>()
+    .join("-");
+  if slug.is_empty() {
+    return format!("chapter-{}", index + 1);
+  }
+  return format!("chapter-{}-{}", index + 1, slug);
+}
@@ -0,0 +1,31 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Output errors.
+///
+/// Represents errors that occur when delivering a result to an output
+/// sink.
+#[derive(Error, Debug)]
+pub enum OutputError {
+  #[error(
+    "{}",
+    t!("error-output-unknown-sink", sink = .0.as_str(), expected = .1.as_str())
+  )]
+  UnknownSink(String, String),
+
+  #[error(
+    "{}",
+    t!("error-output-invalid", sink = .sink.as_str(), error = .error.as_str())
+  )]
+  Invalid { sink: String, error: String },
+
+  #[error(
+    "{}",
+    t!("error-output-write", sink = .sink.as_str(), error = .error.as_str())
+  )]
+  Write { sink: String, error: String },
+}
+
+/// Result type for output operations.
+pub type OutputResult<T> = Result<T, OutputError>;
@@ -0,0 +1,238 @@
+//! Side-by-side HTML comparison of a refinement, for `--side-by-side`.
+//!
+//! The page shows the input and the refined text in two columns, one row
+//! per paragraph of the input (or segment of a transcription), so people
+//! without a diff tool can review a refinement in a browser. Removed and
+//! replaced words are struck through on the left, added and replacing
+//! words highlighted on the right, and words the transcriber was unsure
+//! of are marked in both columns with their probability as a tooltip.
+
+use std::fmt::Write;
+
+use crate::input::transcription::WhisperTranscription;
+use crate::output::accuracy::{self, Edit};
+
+/// A word of the input, with its probability if it was transcribed.
+#[derive(Debug, Clone)]
+struct SourceWord {
+  text: String,
+  probability: Option<f64>,
+}
+
+/// The input and refined text of a refinement, aligned word by word.
+#[derive(Debug, Clone)]
+pub struct SideBySide {
+  rows: Vec<(String, String)>,
+}
+
+impl SideBySide {
+  /// Compares a plain text input with its refinement.
+  ///
+  /// # Arguments
+  ///
+  /// * `original` - The input text
+  /// * `refined` - The refined text
+  ///
+  /// # Returns
+  ///
+  /// The comparison, with a row per paragraph of the input.
+  pub fn from_text(original: &str, refined: &str) -> Self {
+    let mut paragraphs: Vec<Vec<SourceWord>> = vec![Vec::new()];
+    for line in original.lines() {
+      if line.trim().is_empty() {
+        if paragraphs.last().is_some_and(|words| !words.is_empty()) {
+          paragraphs.push(Vec::new());
+        }
+        continue;
+      }
+      if let Some(paragraph) = paragraphs.last_mut() {
+        paragraph.extend(line.split_whitespace().map(|word| SourceWord {
+          text: word.to_string(),
+          probability: None,
+        }));
+      }
+    }
+    return SideBySide::compare(paragraphs, refined, 0.0);
+  }
+
+  /// Compares a transcription with its refinement.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The refined transcription
+  /// * `refined` - The refined text
+  /// * `threshold` - The probability below which words are marked
+  ///
+  /// # Returns
+  ///
+  /// The comparison, with a row per segment of the transcription, or per
+  /// paragraph of its text if it has no segments.
+  pub fn from_transcription(
+    transcription: &WhisperTranscription,
+    refined: &str,
+    threshold: f64,
+  ) -> Self {
+    let Some(segments) = &transcription.segments else {
+      return SideBySide::from_text(&transcription.full_text(), refined);
+    };
+    let rows = segments
+      .iter()
+      .map(|segment| {
+        if segment.words.is_empty() {
+          return segment
+            .text
+            .split_whitespace()
+            .map(|word| SourceWord {
+              text: word.to_string(),
+              probability: None,
+            })
+            .collect();
+        }
+        return segment
+          .words
+          .iter()
+          .filter(|word| !word.word.trim().is_empty())
+          .map(|word| SourceWord {
+            text: word.word.trim().to_string(),
+            probability: Some(word.probability),
+          })
+          .collect();
+      })
+      .collect();
+    return SideBySide::compare(rows, refined, threshold);
+  }
+
+  /// Aligns the words of the input rows with the refined text and renders
+  /// both columns of each row.
+  ///
+  /// Added words go to the row of the input word before them. Texts too
+  /// long to align are shown without highlighting, in a single row.
+  fn compare(
+    rows: Vec<Vec<SourceWord>>,
+    refined: &str,
+    threshold: f64,
+  ) -> Self {
+    let rows: Vec<Vec<SourceWord>> =
+      rows.into_iter().filter(|row| !row.is_empty()).collect();
+    let source: Vec<&SourceWord> = rows.iter().flatten().collect();
+    let source_words: Vec<&str> =
+      source.iter().map(|word| word.text.as_str()).collect();
+    let refined_words = accuracy::words(refined);
+
+    let Some(alignment) = accuracy::align(&source_words, &refined_words) else {
+      return SideBySide {
+        rows: vec![(escape(&source_words.join(" ")), escape(refined))],
+      };
+    };
+
+    // The row each input word belongs to, by its position in `source`.
+    let row_of: Vec<usize> = rows
+      .iter()
+      .enumerate()
+      .flat_map(|(i, row)| std::iter::repeat_n(i, row.len()))
+      .collect();
+    let mut columns = vec![(Vec::new(), Vec::new()); rows.len().max(1)];
+    let mut next: usize = 0;
+    for step in alignment {
+      let index = match step.reference {
+        Some(_) => next,
+        None => next.saturating_sub(1),
+      };
+      let row = row_of.get(index).copied().unwrap_or(0);
+      let (left, right) = &mut columns[row];
+      let word = step.reference.map(|_| source[next]);
+      let low = word
+        .and_then(|word| word.probability)
+        .filter(|probability| *probability < threshold);
+      if let Some(word) = word {
+        left.push(mark(step.edit, "del", &word.text, low));
+        next += 1;
+      }
+      if let Some(refined_word) = step.hypothesis {
+        right.push(mark(step.edit, "ins", refined_word, low));
+      }
+    }
+
+    return SideBySide {
+      rows: columns
+        .into_iter()
+        .map(|(left, right)| (left.join(" "), right.join(" ")))
+        .collect(),
+    };
+  }
+
+  /// Renders the comparison as a standalone HTML page.
+  ///
+  /// # Returns
+  ///
+  /// The page with a two-column table and a legend.
+  pub fn to_html(&self) -> String {
+    let mut body = String::from(
+      "<h1>Pegasus refinement</h1>\n<p class=\"legend\"><del>removed or \
+       replaced</del> <ins>added or replacing</ins> <mark>low \
+       confidence</mark></p>\n<table>\n<tr><th>Original</th><th>Refined\
+       </th></tr>\n",
+    );
+    for (left, right) in &self.rows {
+      let _ = writeln!(body, "<tr><td>{}</td><td>{}</td></tr>", left, right);
+    }
+    body.push_str("</table>\n");
+    return format!(
+      "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+       <title>Pegasus refinement</title>\n<style>\n{}</style>\n</head>\n\
+       <body>\n{}</body>\n</html>\n",
+      STYLE, body
+    );
+  }
+}
+
+/// Style sheet of side-by-side pages.
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2rem; }
+table { border-collapse: collapse; width: 100%; table-layout: fixed; }
+th, td { border: 1px solid #ddd; padding: 0.5rem; text-align: left; \
+vertical-align: top; line-height: 1.5; }
+del { background: #fdd; }
+ins { background: #dfd; text-decoration: none; }
+mark { background: none; border-bottom: 2px dotted #d80; }
+";
+
+/// Renders a word of one column, highlighted by its edit and marked if its
+/// input word had a low probability.
+///
+/// # Arguments
+///
+/// * `edit` - How the word relates to the other column
+/// * `tag` - The element highlighting edits in this column
+/// * `word` - The word
+/// * `low` - The probability of the input word, if below the threshold
+fn mark(edit: Edit, tag: &str, word: &str, low: Option<f64>) -> String {
+  let mut html = escape(word);
+  if edit != Edit::Match {
+    html = format!("<{}>{}</{}>", tag, html, tag);
+  }
+  if let Some(probability) = low {
+    html = format!(
+      "<mark title=\"probability {:.2}\">{}</mark>",
+      probability, html
+    );
+  }
+  return html;
+}
+
+/// Escapes text for HTML.
+///
+/// # Arguments
+///
+/// * `text` - The text
+///
+/// # Returns
+///
+/// The text with `&`, `<`, `>` and `"` replaced by entities.
+pub(crate) fn escape(text: &str) -> String {
+  return text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;");
+}
@@ -0,0 +1,27 @@
+//! Output format handling for refined text results.
+//!
+//! ## Components
+//! - [`OutputFormat`]: Enum for text/JSON output formats
+//! - [`Accuracy`]: Word and character error rates against a reference
+//! - [`Chapter`]: Titled section of a refined transcript
+//! - [`SideBySide`]: HTML page comparing the input with the refined text
+//! - [`Summary`]: Generated title and summary of a refined text
+//! - [`Readability`]: Flesch reading ease and grade level of a text
+//! - [`RunReport`]: Report of a refinement run for reviewers
+//! - [`UnicodePunctuation`]: Normalization of quotes and dashes
+//! - [`OutputSink`]: Destination of a result (stdout, file, clipboard, ...)
+//! - [`WhitespacePolicy`]: Line endings and whitespace of a result
+//! - [`Wrap`]: Paragraph reflow and wrapping to a column width
+
+pub mod accuracy;
+pub mod chapters;
+pub mod errors;
+pub mod format;
+pub mod html;
+pub mod punctuation;
+pub mod readability;
+pub mod report;
+pub mod sink;
+pub mod summary;
+pub mod whitespace;
+pub mod wrap;
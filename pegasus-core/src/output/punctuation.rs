@@ -0,0 +1,142 @@
+//! Normalization of quotes, dashes and other punctuation.
+//!
+//! Models mix straight and curly quotes freely, while downstream systems
+//! often expect one or the other. `[style] unicode_punctuation` rewrites
+//! the refined text after refinement, without asking the model:
+//! - `keep`: leave the punctuation as the model wrote it
+//! - `ascii`: curly quotes, dashes, ellipses and non-breaking spaces become
+//!   their ASCII equivalents
+//! - `typographic`: straight quotes become curly quotes, `...` an ellipsis
+//!   and `--` between words an em dash
+
+use std::fmt;
+
+/// How punctuation of refined text is normalized.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodePunctuation {
+  /// Punctuation is left as the model wrote it
+  #[default]
+  Keep,
+  /// Punctuation is replaced with ASCII characters
+  Ascii,
+  /// Straight quotes, `...` and `--` are replaced with typographic ones
+  Typographic,
+}
+
+impl UnicodePunctuation {
+  /// Normalizes the punctuation of a text.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The refined text
+  ///
+  /// # Returns
+  ///
+  /// The text with its punctuation normalized.
+  pub fn apply(self, text: &str) -> String {
+    return match self {
+      UnicodePunctuation::Keep => text.to_string(),
+      UnicodePunctuation::Ascii => to_ascii(text),
+      UnicodePunctuation::Typographic => to_typographic(text),
+    };
+  }
+}
+
+impl fmt::Display for UnicodePunctuation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      UnicodePunctuation::Keep => "keep",
+      UnicodePunctuation::Ascii => "ascii",
+      UnicodePunctuation::Typographic => "typographic",
+    });
+  }
+}
+
+/// Replaces typographic punctuation with ASCII characters.
+fn to_ascii(text: &str) -> String {
+  let mut result = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}'
+      | '\u{2039}' | '\u{203A}' => result.push('\''),
+      '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}'
+      | '\u{00AB}' | '\u{00BB}' => result.push('"'),
+      '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' => {
+        result.push('-')
+      }
+      '\u{2014}' | '\u{2015}' => result.push_str("--"),
+      '\u{2026}' => result.push_str("..."),
+      '\u{00A0}' | '\u{2007}' | '\u{2009}' | '\u{202F}' => result.push(' '),
+      _ => result.push(c),
+    }
+  }
+  return result;
+}
+
+/// Replaces straight quotes, `...` and `--` with typographic punctuation.
+///
+/// A quote opens at the start of the text or after whitespace, an opening
+/// bracket or a dash, and closes everywhere else. An apostrophe after
+/// whitespace and before a digit, as in `'90s`, is kept as an apostrophe.
+/// Runs of three or more dashes are left alone, so separators and
+/// subtitle arrows (`-->`) keep their shape.
+fn to_typographic(text: &str) -> String {
+  let chars: Vec<char> = text.chars().collect();
+  let mut result = String::with_capacity(text.len());
+  let mut index = 0;
+  while index < chars.len() {
+    let c = chars[index];
+    let previous = result.chars().next_back();
+    let next = chars.get(index + 1).copied();
+    let opens = previous.is_none_or(|p| {
+      return p.is_whitespace()
+        || matches!(p, '(' | '[' | '{' | '-' | '\u{2014}' | '\u{2013}')
+        || p == '\u{201C}'
+        || p == '\u{2018}';
+    });
+    match c {
+      '"' => result.push(if opens { '\u{201C}' } else { '\u{201D}' }),
+      '\'' => {
+        let year = next.is_some_and(|n| n.is_ascii_digit());
+        result.push(if opens && !year {
+          '\u{2018}'
+        } else {
+          '\u{2019}'
+        });
+      }
+      '.'
+        if chars[index..].starts_with(&['.', '.', '.'])
+          && previous != Some('.')
+          && chars.get(index + 3) != Some(&'.') =>
+      {
+        result.push('\u{2026}');
+        index += 3;
+        continue;
+      }
+      '-'
+        if next == Some('-')
+          && previous.is_some_and(|p| p.is_alphanumeric() || p == ' ')
+          && chars
+            .get(index + 2)
+            .is_some_and(|n| n.is_alphanumeric() || *n == ' ') =>
+      {
+        result.push('\u{2014}');
+        index += 2;
+        continue;
+      }
+      _ => result.push(c),
+    }
+    index += 1;
+  }
+  return result;
+}
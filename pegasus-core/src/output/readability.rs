@@ -0,0 +1,126 @@
+//! Readability metrics of refined text, for `--readability`.
+//!
+//! Computes the Flesch reading ease and Flesch-Kincaid grade level
+//! locally. Syllables are estimated from vowel groups, which is accurate
+//! enough for English text; other languages get rough figures.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::input::sentences;
+
+/// Readability metrics of a text.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Readability {
+  /// Number of words
+  pub words: usize,
+  /// Number of sentences
+  pub sentences: usize,
+  /// Estimated number of syllables
+  pub syllables: usize,
+  /// Flesch reading ease (higher is easier, 60 to 70 is plain English)
+  pub flesch_reading_ease: f64,
+  /// Flesch-Kincaid grade level (US school grade)
+  pub flesch_kincaid_grade: f64,
+}
+
+impl Readability {
+  /// Measures the readability of a text.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The text to measure
+  ///
+  /// # Returns
+  ///
+  /// The readability metrics; all zero for text without words.
+  pub fn measure(text: &str) -> Self {
+    let words: Vec<&str> = text
+      .split_whitespace()
+      .filter(|word| word.chars().any(char::is_alphanumeric))
+      .collect();
+    let syllables: usize = words.iter().map(|word| count_syllables(word)).sum();
+    let sentences = sentences::split(text)
+      .into_iter()
+      .filter(|sentence| sentence.chars().any(char::is_alphanumeric))
+      .count()
+      .max(1);
+
+    if words.is_empty() {
+      return Readability {
+        words: 0,
+        sentences: 0,
+        syllables: 0,
+        flesch_reading_ease: 0.0,
+        flesch_kincaid_grade: 0.0,
+      };
+    }
+
+    let words_per_sentence = words.len() as f64 / sentences as f64;
+    let syllables_per_word = syllables as f64 / words.len() as f64;
+    return Readability {
+      words: words.len(),
+      sentences,
+      syllables,
+      flesch_reading_ease: 206.835
+        - 1.015 * words_per_sentence
+        - 84.6 * syllables_per_word,
+      flesch_kincaid_grade: 0.39 * words_per_sentence
+        + 11.8 * syllables_per_word
+        - 15.59,
+    };
+  }
+}
+
+impl fmt::Display for Readability {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return write!(
+      f,
+      "{:<16} {:>10.1}\n{:<16} {:>10.1}\n{:<16} {:>10}\n{:<16} {:>10}",
+      "reading_ease",
+      self.flesch_reading_ease,
+      "grade_level",
+      self.flesch_kincaid_grade,
+      "words",
+      self.words,
+      "sentences",
+      self.sentences
+    );
+  }
+}
+
+/// Estimates the syllables of a word from its vowel groups.
+fn count_syllables(word: &str) -> usize {
+  let letters: Vec<char> = word
+    .chars()
+    .filter(|c| c.is_alphabetic())
+    .flat_map(char::to_lowercase)
+    .collect();
+  if letters.is_empty() {
+    return 0;
+  }
+
+  let is_vowel = |c: char| "aeiouy".contains(c);
+  let mut syllables = 0;
+  let mut previous_vowel = false;
+  for &letter in &letters {
+    let vowel = is_vowel(letter);
+    if vowel && !previous_vowel {
+      syllables += 1;
+    }
+    previous_vowel = vowel;
+  }
+
+  // A final silent "e" ("make") is not a syllable, but "-le" ("table") is.
+  let length = letters.len();
+  if syllables > 1
+    && letters[length - 1] == 'e'
+    && !(length > 2
+      && letters[length - 2] == 'l'
+      && !is_vowel(letters[length - 3]))
+  {
+    syllables -= 1;
+  }
+  return syllables.max(1);
+}
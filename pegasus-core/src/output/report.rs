@@ -0,0 +1,649 @@
+//! Run report of a refinement, for `--report`.
+//!
+//! A report gathers what a reviewer needs to judge one run in a single
+//! file: statistics of the input, the settings it was refined with, how
+//! the word confidences of a transcription are distributed, how much the
+//! refinement changed and which words it replaced, the phase durations,
+//! and the tokens and estimated cost. It is written as HTML, Markdown or
+//! JSON, chosen by the extension of its path (see [`ReportFormat`]).
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::input::transcription::WhisperTranscription;
+use crate::output::accuracy::{self, Edit, ErrorCounts};
+use crate::output::html;
+use crate::usage::RunCost;
+
+/// Number of bars of the confidence histogram, each a tenth wide.
+const HISTOGRAM_BUCKETS: usize = 10;
+
+/// Most corrections listed in a report.
+const MAX_CORRECTIONS: usize = 50;
+
+/// Width of the longest bar of the Markdown histogram, in characters.
+const BAR_WIDTH: usize = 40;
+
+/// The file format of a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+  /// A standalone HTML page
+  Html,
+  /// A Markdown document
+  Markdown,
+  /// A JSON object
+  Json,
+}
+
+impl ReportFormat {
+  /// Determines the format of a report from the extension of its path.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The path of the report
+  ///
+  /// # Returns
+  ///
+  /// The format for `.html`/`.htm`, `.md`/`.markdown` and `.json`, or
+  /// `None` for any other extension.
+  pub fn from_path(path: &str) -> Option<Self> {
+    let extension = Path::new(path)
+      .extension()?
+      .to_string_lossy()
+      .to_lowercase();
+    return match extension.as_str() {
+      "html" | "htm" => Some(ReportFormat::Html),
+      "md" | "markdown" => Some(ReportFormat::Markdown),
+      "json" => Some(ReportFormat::Json),
+      _ => None,
+    };
+  }
+}
+
+/// Size of the input of a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputStats {
+  /// Unicode characters
+  pub characters: usize,
+  /// Whitespace-separated words
+  pub words: usize,
+  /// Lines
+  pub lines: usize,
+  /// Segments of a transcription
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub segments: Option<usize>,
+  /// Duration of a transcription in seconds
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub duration: Option<f64>,
+  /// Language of a transcription
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub language: Option<String>,
+}
+
+/// A setting the run was refined with.
+#[derive(Debug, Clone, Serialize)]
+pub struct Setting {
+  /// The configuration key, like `llm.model`
+  pub key: String,
+  /// The effective value
+  pub value: String,
+}
+
+/// Distribution of the word probabilities of a transcription.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfidenceHistogram {
+  /// The probability below which words are flagged
+  pub threshold: f64,
+  /// Words per tenth of the probability range, from `0.0..0.1` up to
+  /// `0.9..=1.0`
+  pub buckets: Vec<usize>,
+}
+
+/// How much the refinement changed the words of the input.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffSummary {
+  /// Words of the input
+  pub input_words: usize,
+  /// Words of the refined text
+  pub output_words: usize,
+  /// Input words replaced by another word, including changes to
+  /// punctuation and casing
+  pub replaced: usize,
+  /// Input words the refinement removed
+  pub removed: usize,
+  /// Words the refinement added
+  pub added: usize,
+  /// Edits per input word
+  pub change_rate: f64,
+}
+
+/// A replacement the refinement made, with how often it made it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Correction {
+  /// The input word, empty for added words
+  pub original: String,
+  /// The refined word, empty for removed words
+  pub refined: String,
+  /// How often the word was replaced this way
+  pub count: usize,
+}
+
+/// Time spent in a phase of the run.
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+  /// The phase name, or `total`
+  pub phase: String,
+  /// The milliseconds spent in it
+  pub milliseconds: f64,
+}
+
+/// Report of a refinement run.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+  /// When the report was generated, in RFC 3339
+  pub generated_at: String,
+  /// Size of the input
+  pub input: InputStats,
+  /// Settings the run was refined with
+  pub settings: Vec<Setting>,
+  /// Word confidences, for transcriptions with word probabilities
+  pub confidence: Option<ConfidenceHistogram>,
+  /// Changes of the refinement
+  pub diff: DiffSummary,
+  /// The most frequent replacements, most frequent first
+  pub corrections: Vec<Correction>,
+  /// Distinct replacements, of which `corrections` lists at most 50
+  pub distinct_corrections: usize,
+  /// Phase durations
+  pub timings: Vec<PhaseTiming>,
+  /// Tokens and estimated cost, if requests were counted
+  pub cost: Option<RunCost>,
+}
+
+impl RunReport {
+  /// Compares the input of a run with its refined text.
+  ///
+  /// Texts too long to align are only counted, so the report lists no
+  /// corrections for them.
+  ///
+  /// # Arguments
+  ///
+  /// * `original` - The input text
+  /// * `refined` - The refined text
+  ///
+  /// # Returns
+  ///
+  /// A report with the input statistics, the diff summary and the
+  /// corrections, and no settings, confidences, timings or cost yet.
+  pub fn new(original: &str, refined: &str) -> Self {
+    let input_words = accuracy::words(original);
+    let output_words = accuracy::words(refined);
+
+    let mut counts: HashMap<(&str, &str), usize> = HashMap::new();
+    let errors = match accuracy::align(&input_words, &output_words) {
+      Some(alignment) => {
+        let mut errors = ErrorCounts {
+          reference_length: input_words.len(),
+          ..ErrorCounts::default()
+        };
+        for step in alignment {
+          match step.edit {
+            Edit::Match => continue,
+            Edit::Substitution => errors.substitutions += 1,
+            Edit::Deletion => errors.deletions += 1,
+            Edit::Insertion => errors.insertions += 1,
+          }
+          let key = (
+            step.reference.unwrap_or_default(),
+            step.hypothesis.unwrap_or_default(),
+          );
+          *counts.entry(key).or_default() += 1;
+        }
+        errors
+      }
+      None => ErrorCounts::between(&input_words, &output_words),
+    };
+
+    let distinct_corrections = counts.len();
+    let mut corrections: Vec<Correction> = counts
+      .into_iter()
+      .map(|((original, refined), count)| Correction {
+        original: original.to_string(),
+        refined: refined.to_string(),
+        count,
+      })
+      .collect();
+    corrections.sort_by(|a, b| {
+      b.count
+        .cmp(&a.count)
+        .then_with(|| a.original.cmp(&b.original))
+        .then_with(|| a.refined.cmp(&b.refined))
+    });
+    corrections.truncate(MAX_CORRECTIONS);
+
+    return RunReport {
+      generated_at: chrono::Local::now().to_rfc3339(),
+      input: InputStats {
+        characters: original.chars().count(),
+        words: input_words.len(),
+        lines: original.lines().count(),
+        segments: None,
+        duration: None,
+        language: None,
+      },
+      settings: Vec::new(),
+      confidence: None,
+      diff: DiffSummary {
+        input_words: input_words.len(),
+        output_words: output_words.len(),
+        replaced: errors.substitutions,
+        removed: errors.deletions,
+        added: errors.insertions,
+        change_rate: errors.rate(),
+      },
+      corrections,
+      distinct_corrections,
+      timings: Vec::new(),
+      cost: None,
+    };
+  }
+
+  /// Adds the segments, duration, language and word confidences of the
+  /// transcription the input was taken from.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The refined transcription
+  /// * `threshold` - The probability below which words are flagged
+  ///
+  /// # Returns
+  ///
+  /// The report with the transcription details; without word
+  /// probabilities, it has no confidence histogram.
+  pub fn with_transcription(
+    mut self,
+    transcription: &WhisperTranscription,
+    threshold: f64,
+  ) -> Self {
+    let segments = transcription.segments.as_deref().unwrap_or_default();
+    self.input.segments = Some(segments.len());
+    self.input.duration = transcription.duration;
+    self.input.language = transcription.language.clone();
+
+    let mut buckets = vec![0; HISTOGRAM_BUCKETS];
+    let mut words = 0;
+    for word in segments.iter().flat_map(|segment| &segment.words) {
+      let bucket =
+        (word.probability.clamp(0.0, 1.0) * HISTOGRAM_BUCKETS as f64) as usize;
+      buckets[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+      words += 1;
+    }
+    if words > 0 {
+      self.confidence = Some(ConfidenceHistogram { threshold, buckets });
+    }
+    return self;
+  }
+
+  /// Sets the settings the run was refined with.
+  ///
+  /// # Arguments
+  ///
+  /// * `settings` - Pairs of configuration keys and values
+  ///
+  /// # Returns
+  ///
+  /// The report with the settings.
+  pub fn with_settings(mut self, settings: Vec<(&str, String)>) -> Self {
+    self.settings = settings
+      .into_iter()
+      .map(|(key, value)| Setting {
+        key: key.to_string(),
+        value,
+      })
+      .collect();
+    return self;
+  }
+
+  /// Sets the phase durations of the run.
+  ///
+  /// # Arguments
+  ///
+  /// * `timings` - Phase names with their milliseconds (see
+  ///   [`timing::phases`](crate::timing::phases))
+  ///
+  /// # Returns
+  ///
+  /// The report with the timings.
+  pub fn with_timings(mut self, timings: Vec<(&str, f64)>) -> Self {
+    self.timings = timings
+      .into_iter()
+      .map(|(phase, milliseconds)| PhaseTiming {
+        phase: phase.to_string(),
+        milliseconds,
+      })
+      .collect();
+    return self;
+  }
+
+  /// Sets the tokens and estimated cost of the run.
+  ///
+  /// # Arguments
+  ///
+  /// * `cost` - The counts of the run, if it was tracked
+  ///
+  /// # Returns
+  ///
+  /// The report with the cost.
+  pub fn with_cost(mut self, cost: Option<RunCost>) -> Self {
+    self.cost = cost;
+    return self;
+  }
+
+  /// Renders the report.
+  ///
+  /// # Arguments
+  ///
+  /// * `format` - The file format
+  ///
+  /// # Returns
+  ///
+  /// The report as an HTML page, a Markdown document or pretty-printed
+  /// JSON.
+  pub fn render(&self, format: ReportFormat) -> String {
+    return match format {
+      ReportFormat::Html => self.to_html(),
+      ReportFormat::Markdown => self.to_markdown(),
+      ReportFormat::Json => {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+      }
+    };
+  }
+
+  /// Gets the sections of the report as rows of label and value cells.
+  ///
+  /// The histogram and corrections have their own layout and are left
+  /// out.
+  fn tables(&self) -> Vec<(&'static str, Vec<[String; 2]>)> {
+    let mut input = vec![
+      [
+        String::from("Characters"),
+        self.input.characters.to_string(),
+      ],
+      [String::from("Words"), self.input.words.to_string()],
+      [String::from("Lines"), self.input.lines.to_string()],
+    ];
+    if let Some(segments) = self.input.segments {
+      input.push([String::from("Segments"), segments.to_string()]);
+    }
+    if let Some(duration) = self.input.duration {
+      input.push([String::from("Duration"), format!("{:.1} s", duration)]);
+    }
+    if let Some(language) = &self.input.language {
+      input.push([String::from("Language"), language.clone()]);
+    }
+
+    let settings = self
+      .settings
+      .iter()
+      .map(|setting| [setting.key.clone(), setting.value.clone()])
+      .collect();
+
+    let diff = vec![
+      [
+        String::from("Words in / out"),
+        format!("{} / {}", self.diff.input_words, self.diff.output_words),
+      ],
+      [String::from("Replaced"), self.diff.replaced.to_string()],
+      [String::from("Removed"), self.diff.removed.to_string()],
+      [String::from("Added"), self.diff.added.to_string()],
+      [
+        String::from("Change rate"),
+        format!("{:.1}%", self.diff.change_rate * 100.0),
+      ],
+    ];
+
+    let timings = self
+      .timings
+      .iter()
+      .map(|timing| {
+        [
+          timing.phase.clone(),
+          format!("{:.1} ms", timing.milliseconds),
+        ]
+      })
+      .collect();
+
+    let cost = match &self.cost {
+      Some(cost) => vec![
+        [String::from("Requests"), cost.requests.to_string()],
+        [
+          String::from("Prompt tokens"),
+          cost.prompt_tokens.to_string(),
+        ],
+        [
+          String::from("Completion tokens"),
+          cost.completion_tokens.to_string(),
+        ],
+        [String::from("Estimated cost"), format!("{:.4}", cost.cost)],
+      ],
+      None => Vec::new(),
+    };
+
+    return vec![
+      ("Input", input),
+      ("Settings", settings),
+      ("Changes", diff),
+      ("Timings", timings),
+      ("Cost", cost),
+    ];
+  }
+
+  /// Gets the rows of the confidence histogram: the probability range,
+  /// the word count and its share of the longest bar.
+  fn histogram_rows(
+    histogram: &ConfidenceHistogram,
+  ) -> Vec<(String, usize, f64)> {
+    let largest = histogram.buckets.iter().copied().max().unwrap_or(0).max(1);
+    return histogram
+      .buckets
+      .iter()
+      .enumerate()
+      .map(|(i, count)| {
+        let low = i as f64 / HISTOGRAM_BUCKETS as f64;
+        let high = (i + 1) as f64 / HISTOGRAM_BUCKETS as f64;
+        (
+          format!("{:.1}–{:.1}", low, high),
+          *count,
+          *count as f64 / largest as f64,
+        )
+      })
+      .collect();
+  }
+
+  /// Gets the note under the corrections table, if it is cut short.
+  fn corrections_note(&self) -> Option<String> {
+    return (self.distinct_corrections > self.corrections.len()).then(|| {
+      format!(
+        "The {} most frequent of {} distinct corrections.",
+        self.corrections.len(),
+        self.distinct_corrections
+      )
+    });
+  }
+
+  /// Renders the report as a Markdown document.
+  fn to_markdown(&self) -> String {
+    let mut markdown =
+      format!("# Pegasus run report\n\nGenerated {}\n", self.generated_at);
+    let mut tables = self.tables().into_iter();
+    for (title, rows) in tables.by_ref().take(2) {
+      markdown_table(&mut markdown, title, &rows);
+    }
+
+    if let Some(histogram) = &self.confidence {
+      let _ = write!(
+        markdown,
+        "\n## Confidence\n\nFlagged below {:.2}.\n\n| Probability | Words | \
+         |\n| --- | ---: | --- |\n",
+        histogram.threshold
+      );
+      for (range, count, share) in Self::histogram_rows(histogram) {
+        let bar = "█".repeat((share * BAR_WIDTH as f64).round() as usize);
+        let _ = writeln!(markdown, "| {} | {} | {} |", range, count, bar);
+      }
+    }
+
+    if let Some((title, rows)) = tables.next() {
+      markdown_table(&mut markdown, title, &rows);
+    }
+
+    markdown.push_str("\n## Corrections\n\n");
+    if self.corrections.is_empty() {
+      markdown.push_str("No words were changed.\n");
+    } else {
+      markdown
+        .push_str("| Original | Refined | Count |\n| --- | --- | ---: |\n");
+      for correction in &self.corrections {
+        let _ = writeln!(
+          markdown,
+          "| {} | {} | {} |",
+          markdown_cell(&correction.original),
+          markdown_cell(&correction.refined),
+          correction.count
+        );
+      }
+      if let Some(note) = self.corrections_note() {
+        let _ = write!(markdown, "\n{}\n", note);
+      }
+    }
+
+    for (title, rows) in tables {
+      markdown_table(&mut markdown, title, &rows);
+    }
+    return markdown;
+  }
+
+  /// Renders the report as a standalone HTML page.
+  fn to_html(&self) -> String {
+    let mut body = format!(
+      "<h1>Pegasus run report</h1>\n<p>Generated {}</p>\n",
+      html::escape(&self.generated_at)
+    );
+    let mut tables = self.tables().into_iter();
+    for (title, rows) in tables.by_ref().take(2) {
+      html_table(&mut body, title, &rows);
+    }
+
+    if let Some(histogram) = &self.confidence {
+      let _ = write!(
+        body,
+        "<h2>Confidence</h2>\n<p>Flagged below {:.2}.</p>\n<table>\n\
+         <tr><th>Probability</th><th>Words</th><th></th></tr>\n",
+        histogram.threshold
+      );
+      for (range, count, share) in Self::histogram_rows(histogram) {
+        let _ = writeln!(
+          body,
+          "<tr><td>{}</td><td class=\"number\">{}</td><td class=\"bar\">\
+           <div style=\"width: {:.1}%\"></div></td></tr>",
+          range,
+          count,
+          share * 100.0
+        );
+      }
+      body.push_str("</table>\n");
+    }
+
+    if let Some((title, rows)) = tables.next() {
+      html_table(&mut body, title, &rows);
+    }
+
+    body.push_str("<h2>Corrections</h2>\n");
+    if self.corrections.is_empty() {
+      body.push_str("<p>No words were changed.</p>\n");
+    } else {
+      body.push_str(
+        "<table>\n<tr><th>Original</th><th>Refined</th><th>Count</th></tr>\n",
+      );
+      for correction in &self.corrections {
+        let _ = writeln!(
+          body,
+          "<tr><td><del>{}</del></td><td><ins>{}</ins></td>\
+           <td class=\"number\">{}</td></tr>",
+          html::escape(&correction.original),
+          html::escape(&correction.refined),
+          correction.count
+        );
+      }
+      body.push_str("</table>\n");
+      if let Some(note) = self.corrections_note() {
+        let _ = writeln!(body, "<p>{}</p>", note);
+      }
+    }
+
+    for (title, rows) in tables {
+      html_table(&mut body, title, &rows);
+    }
+    return format!(
+      "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+       <title>Pegasus run report</title>\n<style>\n{}</style>\n</head>\n\
+       <body>\n{}</body>\n</html>\n",
+      HTML_STYLE, body
+    );
+  }
+}
+
+/// Style sheet of HTML reports.
+const HTML_STYLE: &str = "\
+body { font-family: sans-serif; max-width: 48rem; margin: 2rem auto; }
+table { border-collapse: collapse; margin-bottom: 1rem; }
+th, td { border: 1px solid #ddd; padding: 0.25rem 0.5rem; text-align: left; }
+td.number { text-align: right; }
+td.bar { width: 20rem; }
+td.bar div { background: #4a7fc1; height: 0.8rem; }
+del { background: #fdd; text-decoration: none; }
+ins { background: #dfd; text-decoration: none; }
+";
+
+/// Appends a section of label and value rows as a Markdown table, unless
+/// it has no rows.
+fn markdown_table(markdown: &mut String, title: &str, rows: &[[String; 2]]) {
+  if rows.is_empty() {
+    return;
+  }
+  let _ = write!(markdown, "\n## {}\n\n| | |\n| --- | --- |\n", title);
+  for [label, value] in rows {
+    let _ = writeln!(
+      markdown,
+      "| {} | {} |",
+      markdown_cell(label),
+      markdown_cell(value)
+    );
+  }
+}
+
+/// Appends a section of label and value rows as an HTML table, unless it
+/// has no rows.
+fn html_table(body: &mut String, title: &str, rows: &[[String; 2]]) {
+  if rows.is_empty() {
+    return;
+  }
+  let _ = write!(body, "<h2>{}</h2>\n<table>\n", title);
+  for [label, value] in rows {
+    let _ = writeln!(
+      body,
+      "<tr><th>{}</th><td>{}</td></tr>",
+      html::escape(label),
+      html::escape(value)
+    );
+  }
+  body.push_str("</table>\n");
+}
+
+/// Escapes a Markdown table cell, so pipes do not end it.
+fn markdown_cell(text: &str) -> String {
+  return text.replace('|', "\\|");
+}
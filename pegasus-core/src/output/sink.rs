@@ -0,0 +1,664 @@
+//! Destinations for refined output.
+//!
+//! A result is written to every configured sink in turn, e.g. printed and
+//! copied to the clipboard at once. Sinks are named by a short spec, given
+//! with `--sink` or in `[output] sinks`:
+//!
+//! - `stdout`: print the result, through a pager if it does not fit on
+//!   the terminal
+//! - `file:<path>`: write the result to a file, replacing it
+//! - `clipboard`: pipe the result into `[output] clipboard_command`
+//! - `type`: pipe the result into `[output] type_command`, which types it
+//!   into the focused window
+//! - `notify`: run `[output] notify_command`
+//! - `webhook`: POST the result as JSON to `[output] webhook_url`
+//!
+//! The stdout and file sinks end the result with a line ending unless
+//! `[output] final_newline` is false (see [`crate::output::whitespace`]).
+//! Like git, the stdout sink pipes results longer than the terminal into
+//! `[output] pager`, `$PAGER` or `less`, unless `[output] use_pager` is
+//! false or `--no-pager` is given; `less` is run with `LESS=FRX` unless
+//! `$LESS` is set. The pager runs without a shell.
+//! Command sinks receive the result on standard input; a `{text}`
+//! placeholder in the command is also replaced with the quoted result, for
+//! tools like `notify-send` that take it as an argument.
+//!
+//! The webhook receives `{ "event": "refined", "output": ..., "format":
+//! "text" | "json", "request_id": ..., "created_at": ..., "version": ... }`,
+//! where `output` is the text, or the JSON object with `--output-json`.
+//! Requests that cannot connect or are answered with a server error or
+//! HTTP 429 are retried `[output] webhook_retries` times with growing
+//! delays; other failures are reported at once.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::clock;
+use crate::config::Config;
+use crate::files::operations;
+use crate::logging::request_id;
+use crate::network::HttpClient;
+use crate::output::errors::{OutputError, OutputResult};
+use crate::output::format::OutputFormat;
+use crate::shell;
+use crate::{elog, logging, vlog};
+
+/// Placeholder for the result in command sinks.
+pub const TEXT_PLACEHOLDER: &str = "{text}";
+
+/// Clipboard command used unless one is configured.
+pub const DEFAULT_CLIPBOARD_COMMAND: &str = "wl-copy";
+
+/// Typing command used unless one is configured.
+pub const DEFAULT_TYPE_COMMAND: &str = "wtype -";
+
+/// Pager used unless one is configured or `$PAGER` is set.
+pub const DEFAULT_PAGER: &str = "less";
+
+/// Notification command used unless one is configured.
+pub const DEFAULT_NOTIFY_COMMAND: &str = "notify-send Pegasus {text}";
+
+const FILE_PREFIX: &str = "file:";
+
+/// Sink names accepted in a spec.
+const SINK_NAMES: &[&str] = &[
+  "stdout",
+  "file:<path>",
+  "clipboard",
+  "type",
+  "notify",
+  "webhook",
+];
+
+/// Delay before the first webhook retry, doubled for each further one.
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The future returned by [`OutputSink::write`].
+pub type SinkFuture<'a> =
+  Pin<Box<dyn Future<Output = OutputResult<()>> + Send + 'a>>;
+
+/// A destination for refined output.
+pub trait OutputSink: Send + Sync {
+  /// Gets the spec the sink was created from, for messages.
+  ///
+  /// # Returns
+  ///
+  /// The sink spec (e.g. "clipboard").
+  fn name(&self) -> &str;
+
+  /// Writes a result to the sink.
+  ///
+  /// # Arguments
+  ///
+  /// * `output` - The formatted result
+  ///
+  /// # Returns
+  ///
+  /// A future resolving to whether the result was written.
+  fn write<'a>(&'a self, output: &'a str) -> SinkFuture<'a>;
+}
+
+/// Prints results to standard output.
+pub struct StdoutSink {
+  terminator: &'static str,
+  pager: Option<Vec<String>>,
+}
+
+impl StdoutSink {
+  /// Creates a sink printing results.
+  ///
+  /// # Arguments
+  ///
+  /// * `terminator` - What is printed after each result
+  ///
+  /// # Returns
+  ///
+  /// A new `StdoutSink` instance.
+  pub fn new(terminator: &'static str) -> Self {
+    return StdoutSink {
+      terminator,
+      pager: None,
+    };
+  }
+
+  /// Sets the pager for results that do not fit on the terminal.
+  ///
+  /// # Arguments
+  ///
+  /// * `pager` - The pager program and its arguments, or `None` to never
+  ///   page
+  ///
+  /// # Returns
+  ///
+  /// The updated `StdoutSink` instance.
+  pub fn with_pager(mut self, pager: Option<Vec<String>>) -> Self {
+    self.pager = pager;
+    return self;
+  }
+}
+
+impl OutputSink for StdoutSink {
+  fn name(&self) -> &str {
+    return "stdout";
+  }
+
+  fn write<'a>(&'a self, output: &'a str) -> SinkFuture<'a> {
+    return Box::pin(async move {
+      let text = format!("{}{}", output, self.terminator);
+      if let Some(pager) = &self.pager
+        && exceeds_screen(&text)
+      {
+        match page(pager, &text).await {
+          Ok(()) => return Ok(()),
+          Err(e) => {
+            vlog!("Could not run pager '{}': {}", pager.join(" "), e)
+          }
+        }
+      }
+      print!("{}", text);
+      let _ = std::io::Write::flush(&mut std::io::stdout());
+      return Ok(());
+    });
+  }
+}
+
+/// Writes results to a file, replacing its content.
+pub struct FileSink {
+  name: String,
+  path: String,
+  terminator: &'static str,
+}
+
+impl FileSink {
+  /// Creates a sink writing to a file.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The file to write
+  /// * `terminator` - What is written after the result
+  ///
+  /// # Returns
+  ///
+  /// A new `FileSink` instance.
+  pub fn new(path: String, terminator: &'static str) -> Self {
+    return FileSink {
+      name: format!("{}{}", FILE_PREFIX, path),
+      path,
+      terminator,
+    };
+  }
+}
+
+impl OutputSink for FileSink {
+  fn name(&self) -> &str {
+    return &self.name;
+  }
+
+  fn write<'a>(&'a self, output: &'a str) -> SinkFuture<'a> {
+    return Box::pin(async move {
+      vlog!("Writing output to {}", self.path);
+      return operations::write_string_atomic(
+        &self.path,
+        &format!("{}{}", output, self.terminator),
+      )
+      .await
+      .map_err(|e| write_error(self, e));
+    });
+  }
+}
+
+/// Pipes results into a shell command.
+pub struct CommandSink {
+  name: String,
+  command: String,
+}
+
+impl CommandSink {
+  /// Creates a sink running a command for each result.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The sink spec, for messages
+  /// * `command` - Shell command receiving the result on standard input
+  ///
+  /// # Returns
+  ///
+  /// A new `CommandSink` instance.
+  pub fn new(name: impl Into<String>, command: String) -> Self {
+    return CommandSink {
+      name: name.into(),
+      command,
+    };
+  }
+}
+
+impl OutputSink for CommandSink {
+  fn name(&self) -> &str {
+    return &self.name;
+  }
+
+  fn write<'a>(&'a self, output: &'a str) -> SinkFuture<'a> {
+    return Box::pin(async move {
+      let command = self
+        .command
+        .replace(TEXT_PLACEHOLDER, &shell::quote(output));
+      vlog!("Sending output to {}: {}", self.name, self.command);
+
+      let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| write_error(self, e))?;
+      if let Some(mut stdin) = child.stdin.take() {
+        // Commands that take the text as an argument may exit without
+        // reading it.
+        let _ = stdin.write_all(output.as_bytes()).await;
+      }
+
+      let result = child
+        .wait_with_output()
+        .await
+        .map_err(|e| write_error(self, e))?;
+      if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let mut message =
+          format!("'{}' failed, {}", self.command, result.status);
+        if !stderr.trim().is_empty() {
+          message = format!("{}: {}", message, stderr.trim());
+        }
+        return Err(write_error(self, message));
+      }
+      return Ok(());
+    });
+  }
+}
+
+/// Posts results as JSON to a webhook.
+pub struct WebhookSink {
+  http_client: HttpClient,
+  token: String,
+  retries: u32,
+  format: OutputFormat,
+}
+
+/// The JSON body sent to a webhook.
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+  event: &'static str,
+  output: Value,
+  format: &'static str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  request_id: Option<String>,
+  created_at: String,
+  version: &'a str,
+}
+
+impl WebhookSink {
+  /// Creates a sink posting to a webhook.
+  ///
+  /// # Arguments
+  ///
+  /// * `http_client` - HTTP client for the webhook URL
+  /// * `token` - Bearer token sent with each request, or empty for none
+  /// * `retries` - How often a failed request is retried
+  ///
+  /// # Returns
+  ///
+  /// A new `WebhookSink` instance.
+  pub fn new(http_client: HttpClient, token: String, retries: u32) -> Self {
+    return WebhookSink {
+      http_client,
+      token,
+      retries,
+      format: OutputFormat::Text,
+    };
+  }
+
+  /// Sets the format of the results posted.
+  ///
+  /// # Arguments
+  ///
+  /// * `format` - The output format; JSON results are embedded as objects
+  ///
+  /// # Returns
+  ///
+  /// The updated `WebhookSink` instance.
+  pub fn with_format(mut self, format: OutputFormat) -> Self {
+    self.format = format;
+    return self;
+  }
+}
+
+impl OutputSink for WebhookSink {
+  fn name(&self) -> &str {
+    return "webhook";
+  }
+
+  fn write<'a>(&'a self, output: &'a str) -> SinkFuture<'a> {
+    return Box::pin(async move {
+      // JSON output is embedded as an object rather than a string.
+      let (format, output) = match self.format {
+        OutputFormat::Json => (
+          "json",
+          serde_json::from_str::<Value>(output)
+            .unwrap_or_else(|_| Value::String(output.to_string())),
+        ),
+        OutputFormat::Text => ("text", Value::String(output.to_string())),
+      };
+      let payload = WebhookPayload {
+        event: "refined",
+        format,
+        output,
+        request_id: request_id::current(),
+        created_at: clock::now().to_rfc3339(),
+        version: env!("CARGO_PKG_VERSION"),
+      };
+      let headers = (!self.token.is_empty()).then(|| {
+        HashMap::from([(
+          String::from("Authorization"),
+          format!("Bearer {}", self.token),
+        )])
+      });
+
+      let mut delay = WEBHOOK_RETRY_DELAY;
+      let mut attempt = 0;
+      loop {
+        vlog!("Posting output to {}", self.http_client.base_url());
+        match self
+          .http_client
+          .notify_with_json(&payload, headers.clone())
+          .await
+        {
+          Ok(()) => return Ok(()),
+          Err(e) if attempt >= self.retries || !e.is_transient() => {
+            return Err(write_error(self, e));
+          }
+          Err(e) => {
+            attempt += 1;
+            elog!(
+              logging::WARNING,
+              "Webhook failed ({}), retrying in {}s ({}/{})",
+              e,
+              delay.as_secs(),
+              attempt,
+              self.retries
+            );
+            clock::sleep(delay).await;
+            delay *= 2;
+          }
+        }
+      }
+    });
+  }
+}
+
+/// Creates a sink from its spec.
+///
+/// # Arguments
+///
+/// * `spec` - The sink spec (e.g. "clipboard" or "file:out.txt")
+/// * `config` - Configuration with the sink commands
+/// * `format` - The format of the results written
+///
+/// # Returns
+///
+/// An `OutputResult<Box<dyn OutputSink>>` containing the sink or an error
+/// for an unknown spec.
+pub fn create(
+  spec: &str,
+  config: &Config,
+  format: OutputFormat,
+) -> OutputResult<Box<dyn OutputSink>> {
+  let terminator = config.get_output_whitespace().terminator();
+  if let Some(path) = spec.strip_prefix(FILE_PREFIX)
+    && !path.is_empty()
+  {
+    return Ok(Box::new(FileSink::new(path.to_string(), terminator)));
+  }
+
+  return match spec {
+    "stdout" => Ok(Box::new(
+      StdoutSink::new(terminator).with_pager(resolve_pager(config)),
+    )),
+    "clipboard" => Ok(Box::new(CommandSink::new(
+      spec,
+      config.get_output_clipboard_command(),
+    ))),
+    "type" => Ok(Box::new(CommandSink::new(
+      spec,
+      config.get_output_type_command(),
+    ))),
+    "notify" => Ok(Box::new(CommandSink::new(
+      spec,
+      config.get_output_notify_command(),
+    ))),
+    "webhook" => {
+      create_webhook(config).map(|sink| Box::new(sink.with_format(format)) as _)
+    }
+    _ => Err(OutputError::UnknownSink(
+      spec.to_string(),
+      SINK_NAMES.join(", "),
+    )),
+  };
+}
+
+/// Checks that a spec names a known sink, without creating it.
+///
+/// # Arguments
+///
+/// * `spec` - The sink spec
+///
+/// # Returns
+///
+/// An `OutputResult<()>` with an error for an unknown spec.
+pub fn check_spec(spec: &str) -> OutputResult<()> {
+  let known = spec
+    .strip_prefix(FILE_PREFIX)
+    .map_or_else(|| SINK_NAMES.contains(&spec), |path| !path.is_empty());
+  if known {
+    return Ok(());
+  }
+  return Err(OutputError::UnknownSink(
+    spec.to_string(),
+    SINK_NAMES.join(", "),
+  ));
+}
+
+/// Gets the pager command from `[output]` and `$PAGER`, split into words.
+///
+/// Like `$PAGER`, the command runs without a shell. Returns `None` if
+/// paging is disabled, the pager is `cat` or its quoting is invalid.
+fn resolve_pager(config: &Config) -> Option<Vec<String>> {
+  if !config.get_output_use_pager() {
+    return None;
+  }
+  let configured = config.get_output_pager();
+  let pager = if configured.trim().is_empty() {
+    std::env::var("PAGER")
+      .ok()
+      .filter(|pager| !pager.trim().is_empty())
+      .unwrap_or_else(|| String::from(DEFAULT_PAGER))
+  } else {
+    configured
+  };
+  let Some(words) = shell::split(&pager) else {
+    vlog!("Not paging output: invalid pager command '{}'", pager);
+    return None;
+  };
+  if words[0] == "cat" {
+    return None;
+  }
+  return Some(words);
+}
+
+/// Checks whether a result is printed to a terminal it does not fit on.
+///
+/// Lines longer than the terminal count once per row they take up.
+fn exceeds_screen(text: &str) -> bool {
+  #[cfg(unix)]
+  {
+    use std::io::IsTerminal;
+
+    if !std::io::stdout().is_terminal() {
+      return false;
+    }
+    let (rows, columns) = crate::tui::terminal::size();
+    let lines: usize = text
+      .lines()
+      .map(|line| line.chars().count().div_ceil(columns).max(1))
+      .sum();
+    return lines >= rows;
+  }
+  #[cfg(not(unix))]
+  {
+    let _ = text;
+    return false;
+  }
+}
+
+/// Pipes a result into a pager and waits for it to quit.
+async fn page(pager: &[String], text: &str) -> std::io::Result<()> {
+  vlog!("Paging output with {}", pager.join(" "));
+  let mut command = Command::new(&pager[0]);
+  command.args(&pager[1..]).stdin(Stdio::piped());
+  if std::env::var_os("LESS").is_none() {
+    command.env("LESS", "FRX");
+  }
+  let mut child = command.spawn()?;
+  if let Some(mut stdin) = child.stdin.take() {
+    // The pager may be quit before it read everything.
+    let _ = stdin.write_all(text.as_bytes()).await;
+  }
+  child.wait().await?;
+  return Ok(());
+}
+
+/// Creates the webhook sink from `[output]` and the proxy settings.
+fn create_webhook(config: &Config) -> OutputResult<WebhookSink> {
+  let invalid = |error: String| OutputError::Invalid {
+    sink: String::from("webhook"),
+    error,
+  };
+  let url = config.get_output_webhook_url();
+  if url.is_empty() {
+    return Err(invalid(String::from("[output] webhook_url is not set")));
+  }
+
+  let mut http_client = HttpClient::new(url)
+    .map_err(|e| invalid(e.to_string()))?
+    .with_request_id_header(config.get_request_id_header());
+  let proxy = config.get_proxy();
+  if !proxy.is_empty() {
+    http_client = http_client
+      .with_proxy(&proxy, &config.get_no_proxy())
+      .map_err(|e| invalid(e.to_string()))?;
+  }
+  return Ok(WebhookSink::new(
+    http_client,
+    config.get_output_webhook_token(),
+    config.get_output_webhook_retries(),
+  ));
+}
+
+/// Creates the sinks for a list of specs.
+///
+/// # Arguments
+///
+/// * `specs` - The sink specs, in the order results are written
+/// * `config` - Configuration with the sink commands
+/// * `format` - The format of the results written
+///
+/// # Returns
+///
+/// An `OutputResult<Vec<Box<dyn OutputSink>>>` containing the sinks or
+/// the error for the first unknown spec.
+pub fn create_all(
+  specs: &[String],
+  config: &Config,
+  format: OutputFormat,
+) -> OutputResult<Vec<Box<dyn OutputSink>>> {
+  return specs
+    .iter()
+    .map(|spec| create(spec, config, format))
+    .collect();
+}
+
+/// Writes a result to every sink.
+///
+/// A failing sink does not keep the result from the others. Failures
+/// after the first are logged.
+///
+/// # Arguments
+///
+/// * `sinks` - The sinks to write to
+/// * `output` - The formatted result
+///
+/// # Returns
+///
+/// An `OutputResult<()>` with the first failure, if any.
+pub async fn write_all(
+  sinks: &[Box<dyn OutputSink>],
+  output: &str,
+) -> OutputResult<()> {
+  let mut first_error = None;
+  for sink in sinks {
+    match sink.write(output).await {
+      Ok(()) => {}
+      Err(e) if first_error.is_none() => first_error = Some(e),
+      Err(e) => elog!(logging::ERROR, "{}", e),
+    }
+  }
+  return first_error.map_or(Ok(()), Err);
+}
+
+/// Wraps a failure to write to a sink.
+fn write_error(sink: &dyn OutputSink, error: impl ToString) -> OutputError {
+  return OutputError::Write {
+    sink: sink.name().to_string(),
+    error: error.to_string(),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::clock::{ManualClock, SeededRng};
+
+  #[tokio::test]
+  async fn backs_off_between_webhook_retries() {
+    // Nothing listens on the port once the listener is dropped.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let sink =
+      WebhookSink::new(HttpClient::new(url).unwrap(), String::new(), 3);
+
+    let manual = Arc::new(ManualClock::new(chrono::DateTime::UNIX_EPOCH));
+    let rng = Arc::new(SeededRng::new(0));
+    let result = clock::scope(manual.clone(), rng, sink.write("text")).await;
+
+    assert!(result.is_err());
+    assert_eq!(
+      manual.slept(),
+      [
+        WEBHOOK_RETRY_DELAY,
+        WEBHOOK_RETRY_DELAY * 2,
+        WEBHOOK_RETRY_DELAY * 4
+      ]
+    );
+  }
+}
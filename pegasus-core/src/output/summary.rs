@@ -0,0 +1,33 @@
+//! Title and summary of a refined text, for `--with-summary`.
+
+use serde::{Deserialize, Serialize};
+
+/// A generated title and short summary.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Summary {
+  /// One-line title
+  pub title: String,
+  /// Summary of about three sentences
+  pub summary: String,
+}
+
+impl Summary {
+  /// Renders the summary as a Markdown header above the text.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The refined text
+  ///
+  /// # Returns
+  ///
+  /// The title as a heading, the summary as a quote, then the text.
+  pub fn to_markdown(&self, text: &str) -> String {
+    let quote = self
+      .summary
+      .lines()
+      .map(|line| format!("> {}", line).trim_end().to_string())
+      .collect::<Vec<_>>()
+      .join("\n");
+    return format!("# {}\n\n{}\n\n{}", self.title.trim(), quote, text);
+  }
+}
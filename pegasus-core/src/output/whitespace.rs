@@ -0,0 +1,125 @@
+//! Newline and whitespace conventions of the output.
+//!
+//! The destination of a result may expect Windows line endings, no
+//! trailing whitespace or at most one blank line between paragraphs.
+//! `[output]` settings rewrite the formatted result to match:
+//! - `line_ending`: `lf` or `crlf`
+//! - `final_newline`: whether the stdout and file sinks end the result
+//!   with a line ending
+//! - `collapse_blank_lines`: runs of blank lines become one blank line
+//! - `trim_trailing_whitespace`: spaces and tabs at the end of lines are
+//!   removed
+
+use std::fmt;
+
+/// Line ending of the output.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+  /// `\n`, as on Unix
+  #[default]
+  Lf,
+  /// `\r\n`, as on Windows
+  Crlf,
+}
+
+impl LineEnding {
+  /// Gets the characters of the line ending.
+  ///
+  /// # Returns
+  ///
+  /// `"\n"` or `"\r\n"`.
+  pub fn as_str(self) -> &'static str {
+    return match self {
+      LineEnding::Lf => "\n",
+      LineEnding::Crlf => "\r\n",
+    };
+  }
+}
+
+impl fmt::Display for LineEnding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      LineEnding::Lf => "lf",
+      LineEnding::Crlf => "crlf",
+    });
+  }
+}
+
+/// How newlines and whitespace of the output are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhitespacePolicy {
+  /// Line ending of every line
+  pub line_ending: LineEnding,
+  /// Whether the result ends with a line ending
+  pub final_newline: bool,
+  /// Whether runs of blank lines become one blank line
+  pub collapse_blank_lines: bool,
+  /// Whether whitespace at the end of lines is removed
+  pub trim_trailing_whitespace: bool,
+}
+
+impl Default for WhitespacePolicy {
+  fn default() -> Self {
+    return WhitespacePolicy {
+      line_ending: LineEnding::Lf,
+      final_newline: true,
+      collapse_blank_lines: false,
+      trim_trailing_whitespace: false,
+    };
+  }
+}
+
+impl WhitespacePolicy {
+  /// Rewrites the lines of a result.
+  ///
+  /// Line endings in the text are replaced with the configured one, so
+  /// applying the policy twice gives the same result.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The formatted result
+  ///
+  /// # Returns
+  ///
+  /// The result with its lines rewritten.
+  pub fn apply(&self, text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.split('\n') {
+      let line = line.strip_suffix('\r').unwrap_or(line);
+      let line = if self.trim_trailing_whitespace {
+        line.trim_end_matches([' ', '\t'])
+      } else {
+        line
+      };
+      let blank = line.trim().is_empty();
+      let after_blank = lines.last().is_some_and(|last| last.trim().is_empty());
+      if self.collapse_blank_lines && blank && after_blank {
+        continue;
+      }
+      lines.push(line);
+    }
+    return lines.join(self.line_ending.as_str());
+  }
+
+  /// Gets what the stdout and file sinks write after a result.
+  ///
+  /// # Returns
+  ///
+  /// The line ending, or nothing without a final newline.
+  pub fn terminator(&self) -> &'static str {
+    if !self.final_newline {
+      return "";
+    }
+    return self.line_ending.as_str();
+  }
+}
@@ -0,0 +1,169 @@
+//! Paragraph reflow and wrapping of the output.
+//!
+//! Models break lines wherever they like. With `[output] wrap` (or
+//! `--wrap`) set to a width, paragraphs are rewrapped to that many
+//! characters per line: the lines of a paragraph are joined and broken
+//! again at the last space that fits. With `[output] reflow = false` (or
+//! `--no-reflow`), the model's line breaks are kept and only lines longer
+//! than the width are broken.
+//!
+//! Lines that are not prose keep their shape: Markdown headings, tables
+//! and fenced or indented code, and subtitle timing lines (`-->`) are
+//! neither joined nor broken. List items and quotes are wrapped on their
+//! own, with continuation lines indented under their text. Words longer
+//! than the width are never split.
+
+/// Fence opening and closing a Markdown code block.
+const CODE_FENCE: &str = "```";
+
+/// Leading spaces that make a line an indented code block.
+const CODE_INDENT: usize = 4;
+
+/// How a line of the output is treated.
+enum LineKind {
+  /// An empty or whitespace-only line, separating paragraphs
+  Blank,
+  /// A line kept exactly as written
+  Verbatim,
+  /// A list item or quote, with the width of its marker
+  Item(usize),
+  /// A line of a paragraph
+  Text,
+}
+
+/// Wrapping settings of the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wrap {
+  /// Characters per line, or 0 to leave lines alone
+  pub width: usize,
+  /// Whether the lines of a paragraph are joined before wrapping
+  pub reflow: bool,
+}
+
+impl Wrap {
+  /// Wraps the lines of a result.
+  ///
+  /// # Arguments
+  ///
+  /// * `text` - The refined text
+  ///
+  /// # Returns
+  ///
+  /// The text wrapped to the width, or unchanged if the width is 0.
+  pub fn apply(&self, text: &str) -> String {
+    if self.width == 0 {
+      return text.to_string();
+    }
+
+    let mut output: Vec<String> = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut indent = "";
+    let mut in_code = false;
+    for line in text.split('\n') {
+      let kind = if in_code || line.trim_start().starts_with(CODE_FENCE) {
+        if line.trim_start().starts_with(CODE_FENCE) {
+          in_code = !in_code;
+        }
+        LineKind::Verbatim
+      } else {
+        classify(line)
+      };
+
+      let joins = self.reflow && matches!(kind, LineKind::Text);
+      if !joins || paragraph.is_empty() {
+        output.extend(self.fill(indent, indent, &paragraph));
+        paragraph.clear();
+        indent = leading_whitespace(line);
+      }
+      match kind {
+        LineKind::Blank | LineKind::Verbatim => output.push(line.to_string()),
+        LineKind::Item(marker) => {
+          let (first, rest) = line.split_at(indent.len() + marker);
+          let hanging = " ".repeat(first.chars().count());
+          let words: Vec<&str> = rest.split_whitespace().collect();
+          let lines = self.fill(first.trim_end(), &hanging, &words);
+          output.extend(lines);
+        }
+        LineKind::Text if joins => paragraph.extend(line.split_whitespace()),
+        LineKind::Text => {
+          let words: Vec<&str> = line.split_whitespace().collect();
+          output.extend(self.fill(indent, indent, &words));
+        }
+      }
+    }
+    output.extend(self.fill(indent, indent, &paragraph));
+    return output.join("\n");
+  }
+
+  /// Breaks words into lines of at most the width.
+  ///
+  /// # Arguments
+  ///
+  /// * `first` - Prefix of the first line, such as a list marker
+  /// * `indent` - Prefix of the following lines
+  /// * `words` - The words to lay out
+  ///
+  /// # Returns
+  ///
+  /// The lines, or none if there are no words.
+  fn fill(&self, first: &str, indent: &str, words: &[&str]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::from(first);
+    let mut length = first.chars().count();
+    let mut empty = true;
+    for word in words {
+      let word_length = word.chars().count();
+      if !empty && length + 1 + word_length > self.width {
+        lines.push(std::mem::replace(&mut line, String::from(indent)));
+        length = indent.chars().count();
+        empty = true;
+      }
+      if !empty || (!line.is_empty() && !line.ends_with(char::is_whitespace)) {
+        line.push(' ');
+        length += 1;
+      }
+      line.push_str(word);
+      length += word_length;
+      empty = false;
+    }
+    if !empty {
+      lines.push(line);
+    }
+    return lines;
+  }
+}
+
+/// Determines how a line outside of code fences is treated.
+fn classify(line: &str) -> LineKind {
+  let trimmed = line.trim_start();
+  if trimmed.is_empty() {
+    return LineKind::Blank;
+  }
+  let indent = line.len() - trimmed.len();
+  if line.starts_with('\t')
+    || indent >= CODE_INDENT
+    || trimmed.starts_with(['#', '|'])
+    || trimmed.contains("-->")
+  {
+    return LineKind::Verbatim;
+  }
+  if let Some(marker) = ["- ", "* ", "+ ", "> "]
+    .iter()
+    .find(|marker| trimmed.starts_with(*marker))
+  {
+    return LineKind::Item(marker.len());
+  }
+  let digits = trimmed.bytes().take_while(u8::is_ascii_digit).count();
+  if digits > 0
+    && matches!(trimmed.as_bytes().get(digits), Some(b'.' | b')'))
+    && trimmed.as_bytes().get(digits + 1) == Some(&b' ')
+  {
+    return LineKind::Item(digits + 2);
+  }
+  return LineKind::Text;
+}
+
+/// Gets the whitespace a line starts with.
+fn leading_whitespace(line: &str) -> &str {
+  return &line[..line.len() - line.trim_start().len()];
+}
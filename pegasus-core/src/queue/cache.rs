@@ -0,0 +1,158 @@
+//! Record of the inputs and settings each queue result was refined from.
+//!
+//! When a job is done, a digest of its input file, its kind and the
+//! configuration it was refined with is stored in
+//! `$XDG_CACHE_HOME/pegasus/outputs.json`, keyed by the path of the result.
+//! A later run over the same corpus finds the digest unchanged and skips
+//! the job instead of paying for the same refinement again; changing the
+//! input, any setting, the files the settings point to (the dictionary,
+//! the Hunspell dictionary and the grammar) or the Pegasus release and
+//! its built-in prompts changes the digest. Like the capability cache, the
+//! record is kept with [`crate::files::cache`] and is optional: when it
+//! cannot be read or written, jobs are refined as if it were empty.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::dictionary;
+use crate::files::cache;
+use crate::llm::prompts;
+use crate::queue::Job;
+
+/// File name of the output record.
+const CACHE_FILE: &str = "outputs.json";
+
+/// Digests of the inputs and settings of the results written so far.
+#[derive(Debug, Default)]
+pub struct OutputCache {
+  digests: HashMap<String, String>,
+}
+
+impl OutputCache {
+  /// Reads the output record; a missing or unreadable record is empty.
+  ///
+  /// # Returns
+  ///
+  /// The record.
+  pub async fn load() -> Self {
+    return OutputCache {
+      digests: cache::read(CACHE_FILE).await,
+    };
+  }
+
+  /// Checks whether the result of a job exists and was refined from the
+  /// same input and settings.
+  ///
+  /// # Arguments
+  ///
+  /// * `job` - The job
+  /// * `digest` - The digest of its input and settings, see [`digest`]
+  ///
+  /// # Returns
+  ///
+  /// `true` if refining the job again would repeat the last refinement.
+  pub fn is_unchanged(&self, job: &Job, digest: &str) -> bool {
+    return Path::new(&job.output).is_file()
+      && self.digests.get(&job.output).is_some_and(|d| d == digest);
+  }
+
+  /// Records the digest a result was refined from and writes the record,
+  /// logging failures since the record is optional.
+  ///
+  /// # Arguments
+  ///
+  /// * `job` - The job whose result was written
+  /// * `digest` - The digest of its input and settings
+  pub async fn record(&mut self, job: &Job, digest: String) {
+    self.digests.insert(job.output.clone(), digest);
+    cache::write(CACHE_FILE, &self.digests).await;
+  }
+}
+
+/// Computes the digest of the input of a job and the settings it is
+/// refined with, including the content of the files they point to, the
+/// Pegasus version and the built-in prompts.
+///
+/// # Arguments
+///
+/// * `job` - The job
+/// * `config` - The configuration of the run
+///
+/// # Returns
+///
+/// The SHA-256 digest in hex, or `None` if the input cannot be read.
+pub async fn digest(job: &Job, config: &Config) -> Option<String> {
+  let input = tokio::fs::read(&job.input).await.ok()?;
+  let settings = serde_json::to_vec(config).ok()?;
+  let mut hasher = Sha256::new();
+  hasher.update(job.kind.to_string().as_bytes());
+  hasher.update([0]);
+  hasher.update(&settings);
+  hasher.update([0]);
+  hasher.update(&input);
+  hasher.update([0]);
+  hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+  hasher.update([0]);
+  hasher.update(prompts::builtin_prompts().as_bytes());
+  let files = [
+    dictionary::configured_path(config).unwrap_or_default(),
+    config.get_dictionary_spellcheck(),
+    config.get_llm_grammar_file(),
+  ];
+  for path in files {
+    hasher.update([0]);
+    if !path.is_empty() {
+      hasher.update(tokio::fs::read(path).await.unwrap_or_default());
+    }
+  }
+  return Some(
+    hasher
+      .finalize()
+      .iter()
+      .map(|byte| format!("{:02x}", byte))
+      .collect(),
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::queue::{JobKind, JobStatus};
+
+  #[tokio::test]
+  async fn changes_when_the_grammar_file_changes() {
+    let directory = std::env::temp_dir()
+      .join(format!("pegasus-digest-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let input = directory.join("input.txt");
+    let grammar = directory.join("prose.gbnf");
+    std::fs::write(&input, "hello world").unwrap();
+    std::fs::write(&grammar, "root ::= [a-z ]+").unwrap();
+    let config: Config = toml::from_str(&format!(
+      "[llm]\ngrammar_file = {:?}",
+      grammar.to_string_lossy()
+    ))
+    .unwrap();
+    let job = Job {
+      id: 1,
+      kind: JobKind::Text,
+      input: input.to_string_lossy().to_string(),
+      output: String::new(),
+      status: JobStatus::Pending,
+      attempts: 0,
+      error: None,
+    };
+
+    let before = digest(&job, &config).await;
+    assert_eq!(digest(&job, &config).await, before);
+    std::fs::write(&grammar, "root ::= [A-Za-z ]+").unwrap();
+    let after = digest(&job, &config).await;
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert!(before.is_some());
+    assert_ne!(after, before);
+  }
+}
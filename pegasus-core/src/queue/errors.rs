@@ -0,0 +1,72 @@
+use thiserror::Error;
+
+use crate::app::errors::ErrorKind;
+use crate::t;
+
+/// Job queue errors.
+///
+/// Represents errors that occur while reading, updating or running the
+/// on-disk job queue.
+#[derive(Error, Debug)]
+pub enum QueueError {
+  #[error("{}", t!("error-queue-location", error = .0.as_str()))]
+  Location(String),
+
+  #[error(
+    "{}",
+    t!("error-queue-read", path = .0.as_str(), error = .1.as_str())
+  )]
+  Read(String, String),
+
+  #[error(
+    "{}",
+    t!("error-queue-corrupt", path = .0.as_str(), error = .1.as_str())
+  )]
+  Corrupt(String, String),
+
+  #[error("{0}")]
+  Write(String),
+
+  #[error("{}", t!("error-queue-missing-input", path = .0.as_str()))]
+  MissingInput(String),
+
+  #[error("{}", t!("error-queue-directory", path = .0.as_str()))]
+  Directory(String),
+
+  #[error(
+    "{}",
+    t!(
+      "error-queue-output-collision",
+      first = .first.as_str(),
+      second = .second.as_str(),
+      output = .output.as_str()
+    )
+  )]
+  OutputCollision {
+    output: String,
+    first: String,
+    second: String,
+  },
+
+  #[error("{}", t!("error-queue-busy"))]
+  Busy,
+}
+
+impl QueueError {
+  /// Gets the category of the error.
+  ///
+  /// # Returns
+  ///
+  /// The `ErrorKind` deciding the exit status.
+  pub fn kind(&self) -> ErrorKind {
+    return match self {
+      QueueError::MissingInput(_)
+      | QueueError::Directory(_)
+      | QueueError::OutputCollision { .. } => ErrorKind::Input,
+      _ => ErrorKind::Other,
+    };
+  }
+}
+
+/// Result type for job queue operations.
+pub type QueueResult<T> = Result<T, QueueError>;
@@ -0,0 +1,806 @@
+//! Persistent job queue for batch refinement.
+//!
+//! `queue add` records files to refine in `$XDG_STATE_HOME/pegasus/
+//! queue.json`, and `queue run` works through them, possibly over several
+//! sessions. The queue file is rewritten atomically under a lock, once per
+//! job, and each result is written before its job is marked done, so an
+//! interrupted run loses at most the job in progress: jobs left running by
+//! a crash are queued again by the next run. Jobs the model refused to
+//! refine are told apart from those that failed otherwise, and jobs done in
+//! an earlier run are removed when the next run starts.
+//!
+//! Adding files fails if two of them, or a file and a job still to run,
+//! would write the same result, such as `talk.txt` and `talk.json`, which
+//! are both refined to `talk.refined.txt`.
+//!
+//! Directories can be added with `--recursive`, optionally only their
+//! files with given extensions; with an output directory, the results
+//! mirror the layout of the input directory. Files whose result is newer
+//! than the file itself are up to date and not added again.
+//!
+//! When running, jobs whose result was already refined from the same input
+//! and settings are skipped (see [`cache`]), so repeated batch runs over a
+//! corpus only pay for what changed; `force` refines them anyway.
+//!
+//! ## Main Components
+//!
+//! - [`Queue`]: The on-disk queue
+//! - [`Job`]: A file to refine and where to write the result
+//! - [`AddOptions`]: What to add and where the results go
+//! - [`run`]: Refines the pending jobs
+//! - [`OutputCache`](cache::OutputCache): What each result was refined from
+//! - [`QueueError`](errors::QueueError): Error types for queue operations
+
+pub mod cache;
+pub mod errors;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use xdg::BaseDirectories;
+
+use crate::app::App;
+use crate::app::errors::ErrorKind;
+use crate::files::errors::FileError;
+use crate::files::operations::{self, FileLock};
+use crate::logging::{self, request_id};
+use crate::metrics;
+use crate::output::format::OutputFormat;
+use crate::queue::cache::OutputCache;
+use crate::queue::errors::{QueueError, QueueResult};
+use crate::usage;
+use crate::{elog, vlog};
+
+const STATE_DIRECTORY: &str = "pegasus";
+const QUEUE_FILE: &str = "queue.json";
+
+/// What kind of file a job refines.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+  /// A plain text transcript
+  #[default]
+  Text,
+  /// A Whisper JSON transcription
+  Whisper,
+  /// A recording, transcribed with the configured service first
+  Audio,
+}
+
+impl JobKind {
+  /// Parses a job kind from its name.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - `text`, `whisper` or `audio`
+  ///
+  /// # Returns
+  ///
+  /// The kind, or `None` if the name is unknown.
+  pub fn from_name(name: &str) -> Option<Self> {
+    return match name {
+      "text" => Some(JobKind::Text),
+      "whisper" => Some(JobKind::Whisper),
+      "audio" => Some(JobKind::Audio),
+      _ => None,
+    };
+  }
+}
+
+impl fmt::Display for JobKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    return f.write_str(match self {
+      JobKind::Text => "text",
+      JobKind::Whisper => "whisper",
+      JobKind::Audio => "audio",
+    });
+  }
+}
+
+/// Progress of a job.
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+  /// Waiting to be refined
+  Pending,
+  /// Being refined, or interrupted while it was
+  Running,
+  /// Refined and written
+  Done,
+  /// Refinement failed; see the job's error
+  Failed,
+  /// The model refused to refine the input; see the job's error
+  Refused,
+}
+
+/// A file to refine and where to write the result.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Job {
+  /// Number of the job, unique within the queue
+  pub id: u64,
+  /// What kind of file the input is
+  pub kind: JobKind,
+  /// Absolute path of the file to refine
+  pub input: String,
+  /// Absolute path the refined text is written to
+  pub output: String,
+  /// Progress of the job
+  pub status: JobStatus,
+  /// How many times the job was started
+  pub attempts: u32,
+  /// Why the last attempt failed
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+/// Contents of the queue file.
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+struct QueueState {
+  next_id: u64,
+  jobs: Vec<Job>,
+}
+
+/// What [`Queue::add`] adds and where the results are written.
+#[derive(Debug, Clone, Default)]
+pub struct AddOptions {
+  /// What kind of files the inputs are
+  pub kind: JobKind,
+  /// Directory to write the results to; defaults to the directory of each
+  /// input
+  pub output_dir: Option<PathBuf>,
+  /// Whether directories are searched for files, including their
+  /// subdirectories
+  pub recursive: bool,
+  /// Extensions of the files to add from directories, without the dot;
+  /// empty for all files
+  pub extensions: Vec<String>,
+  /// Whether files are added even if their result is up to date
+  pub force: bool,
+}
+
+/// Outcome of [`Queue::add`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AddSummary {
+  /// Files given or found in directories
+  pub found: usize,
+  /// Jobs added
+  pub added: usize,
+  /// Files skipped because their result is newer than the file
+  pub up_to_date: usize,
+}
+
+/// Outcome of a [`run`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RunSummary {
+  /// Jobs refined and written
+  pub done: usize,
+  /// Jobs that failed
+  pub failed: usize,
+  /// Jobs the model refused to refine
+  pub refused: usize,
+  /// Jobs skipped because their result was refined from the same input
+  /// and settings
+  pub unchanged: usize,
+}
+
+impl RunSummary {
+  /// Gets the number of jobs the run has worked on.
+  ///
+  /// # Returns
+  ///
+  /// The number of jobs done, failed or refused.
+  pub fn attempted(&self) -> usize {
+    return self.done + self.failed + self.refused;
+  }
+}
+
+/// The on-disk job queue.
+#[derive(Debug, Clone)]
+pub struct Queue {
+  path: PathBuf,
+}
+
+impl Queue {
+  /// Opens the queue in the XDG state directory, creating the directory.
+  ///
+  /// # Returns
+  ///
+  /// A `QueueResult<Queue>` containing the queue, or an error if the state
+  /// directory cannot be created.
+  pub fn open() -> QueueResult<Self> {
+    let path = BaseDirectories::with_prefix(STATE_DIRECTORY)
+      .place_state_file(QUEUE_FILE)
+      .map_err(|e| QueueError::Location(e.to_string()))?;
+    return Ok(Queue { path });
+  }
+
+  /// Gets the path of the queue file.
+  ///
+  /// # Returns
+  ///
+  /// The path of the queue file.
+  pub fn path(&self) -> &Path {
+    return &self.path;
+  }
+
+  /// Adds files to the queue.
+  ///
+  /// Files already waiting in the queue with the same output are skipped,
+  /// and so are files whose output is newer than the file unless `force`
+  /// is set.
+  ///
+  /// # Arguments
+  ///
+  /// * `inputs` - Paths of the files, or with `recursive` directories, to
+  ///   refine
+  /// * `options` - What kind of files they are and where to write the
+  ///   results
+  ///
+  /// # Returns
+  ///
+  /// A `QueueResult<AddSummary>` with the number of files found and the
+  /// number of jobs added, or an error if an input does not exist or is a
+  /// directory without `recursive`.
+  pub async fn add(
+    &self,
+    inputs: &[String],
+    options: &AddOptions,
+  ) -> QueueResult<AddSummary> {
+    let output_dir = match &options.output_dir {
+      Some(dir) => Some(
+        std::path::absolute(dir)
+          .map_err(|e| QueueError::Write(e.to_string()))?,
+      ),
+      None => None,
+    };
+    let mut summary = AddSummary::default();
+    let mut jobs = Vec::new();
+    for input in inputs {
+      for (input_path, relative_dir) in
+        find_inputs(input, options, output_dir.as_deref())?
+      {
+        summary.found += 1;
+        let output_path = output_path(
+          &input_path,
+          output_dir.as_deref().map(|dir| dir.join(&relative_dir)),
+        );
+        if !options.force && is_up_to_date(&input_path, &output_path) {
+          vlog!("Skipping {}, which is up to date", input_path.display());
+          summary.up_to_date += 1;
+          continue;
+        }
+        jobs.push((
+          input_path.to_string_lossy().to_string(),
+          output_path.to_string_lossy().to_string(),
+        ));
+      }
+    }
+
+    check_collisions(&jobs, &[])?;
+
+    let kind = options.kind;
+    summary.added = self
+      .update(|state| {
+        check_collisions(&jobs, &state.jobs)?;
+        let mut added = 0;
+        for (input, output) in jobs {
+          let queued = state.jobs.iter().any(|job| {
+            job.input == input
+              && job.output == output
+              && matches!(job.status, JobStatus::Pending | JobStatus::Running)
+          });
+          if queued {
+            vlog!("Skipping {}, which is already queued", input);
+            continue;
+          }
+          state.next_id += 1;
+          state.jobs.push(Job {
+            id: state.next_id,
+            kind,
+            input,
+            output,
+            status: JobStatus::Pending,
+            attempts: 0,
+            error: None,
+          });
+          added += 1;
+        }
+        return Ok(added);
+      })
+      .await??;
+    return Ok(summary);
+  }
+
+  /// Gets all jobs in the queue.
+  ///
+  /// # Returns
+  ///
+  /// A `QueueResult<Vec<Job>>` containing the jobs in the order they were
+  /// added.
+  pub async fn jobs(&self) -> QueueResult<Vec<Job>> {
+    return Ok(self.load().await?.jobs);
+  }
+
+  /// Queues failed and refused jobs again.
+  ///
+  /// # Returns
+  ///
+  /// A `QueueResult<usize>` containing the number of jobs queued again.
+  pub async fn retry_failed(&self) -> QueueResult<usize> {
+    return self
+      .update(|state| {
+        return requeue(state, JobStatus::Failed)
+          + requeue(state, JobStatus::Refused);
+      })
+      .await;
+  }
+
+  /// Reads and parses the queue file; a missing file is an empty queue.
+  async fn load(&self) -> QueueResult<QueueState> {
+    let path = self.path.to_string_lossy().to_string();
+    let content = match tokio::fs::read_to_string(&self.path).await {
+      Ok(content) => content,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+        return Ok(QueueState::default());
+      }
+      Err(e) => return Err(QueueError::Read(path, e.to_string())),
+    };
+    return serde_json::from_str(&content)
+      .map_err(|e| QueueError::Corrupt(path, e.to_string()));
+  }
+
+  /// Applies a change to the queue file while holding its lock.
+  async fn update<T>(
+    &self,
+    change: impl FnOnce(&mut QueueState) -> T,
+  ) -> QueueResult<T> {
+    let path = self.path.to_string_lossy().to_string();
+    let _lock = operations::lock_file(&path)
+      .await
+      .map_err(|e| QueueError::Write(e.to_string()))?;
+
+    let mut state = self.load().await?;
+    let result = change(&mut state);
+    let content = serde_json::to_string_pretty(&state)
+      .map_err(|e| QueueError::Write(e.to_string()))?;
+    operations::write_string_atomic(&path, &content)
+      .await
+      .map_err(|e| QueueError::Write(e.to_string()))?;
+    return Ok(result);
+  }
+
+  /// Takes the lock that allows only one process to run the queue.
+  fn lock_runner(&self) -> QueueResult<FileLock> {
+    let runner_path = self.path.with_extension("run");
+    return operations::try_lock_file(&runner_path.to_string_lossy()).map_err(
+      |e| match e {
+        FileError::Locked(_) => QueueError::Busy,
+        e => QueueError::Write(e.to_string()),
+      },
+    );
+  }
+
+  /// Records the outcome of a job, if any, and marks the next pending job
+  /// as running, rewriting the queue file once.
+  async fn advance(
+    &self,
+    finished: Option<Outcome>,
+  ) -> QueueResult<Option<Job>> {
+    return self
+      .update(|state| {
+        if let Some(outcome) = finished {
+          outcome.apply(state);
+        }
+        let job = state
+          .jobs
+          .iter_mut()
+          .find(|job| job.status == JobStatus::Pending)?;
+        job.status = JobStatus::Running;
+        job.attempts += 1;
+        return Some(job.clone());
+      })
+      .await;
+  }
+
+  /// Records the outcome of a job.
+  async fn finish(&self, outcome: Outcome) -> QueueResult<()> {
+    return self.update(|state| outcome.apply(state)).await;
+  }
+}
+
+/// The outcome of a job, recorded with the claim of the next one.
+struct Outcome {
+  id: u64,
+  status: JobStatus,
+  error: Option<String>,
+}
+
+impl Outcome {
+  fn new(id: u64, status: JobStatus, error: Option<String>) -> Self {
+    return Outcome { id, status, error };
+  }
+
+  fn apply(self, state: &mut QueueState) {
+    if let Some(job) = state.jobs.iter_mut().find(|job| job.id == self.id) {
+      job.status = self.status;
+      job.error = self.error;
+    }
+  }
+}
+
+/// Refines the pending jobs of the queue in the order they were added.
+///
+/// Jobs left running by an interrupted run are queued again first. A job
+/// that fails is logged and marked failed, or refused if the model refused
+/// to refine it, and the run goes on with the next one. A job whose result
+/// was refined from the same input and settings before is marked done
+/// without refining it again, unless `force` is set.
+///
+/// Once the application is cancelled, the job being refined is queued
+/// again and the run stops with the summary of the jobs before it.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+/// * `queue` - The queue to run
+/// * `limit` - Maximum number of jobs to refine, or `None` for all
+/// * `force` - Whether to refine jobs whose result is unchanged
+///
+/// # Returns
+///
+/// A `QueueResult<RunSummary>` with the number of jobs done, failed,
+/// refused and skipped as unchanged, or an error if the queue cannot be
+/// read or written.
+pub async fn run(
+  app: &App,
+  queue: &Queue,
+  limit: Option<usize>,
+  force: bool,
+) -> QueueResult<RunSummary> {
+  let _runner = queue.lock_runner()?;
+
+  let (interrupted, pruned) = queue
+    .update(|state| {
+      let pruned = prune_done(state);
+      return (requeue(state, JobStatus::Running), pruned);
+    })
+    .await?;
+  if interrupted > 0 {
+    vlog!("Queued {} interrupted jobs again", interrupted);
+  }
+  if pruned > 0 {
+    vlog!("Removed {} jobs done in earlier runs", pruned);
+  }
+
+  let mut cache = OutputCache::load().await;
+  let mut summary = RunSummary::default();
+  // The outcome of each job is recorded when the next one is claimed, so
+  // the queue file is rewritten once per job.
+  let mut finished = None;
+  while limit.is_none_or(|limit| summary.attempted() < limit)
+    && !app.cancellation().is_cancelled()
+  {
+    let Some(job) = queue.advance(finished.take()).await? else {
+      break;
+    };
+
+    let digest = cache::digest(&job, app.config()).await;
+    let unchanged = match &digest {
+      Some(digest) if !force => {
+        let unchanged = cache.is_unchanged(&job, digest);
+        metrics::record_cache_lookup("output", unchanged);
+        unchanged
+      }
+      _ => false,
+    };
+    if unchanged {
+      vlog!(
+        "Skipping {}, which is unchanged since its last run",
+        job.input
+      );
+      summary.unchanged += 1;
+      finished = Some(Outcome::new(job.id, JobStatus::Done, None));
+      continue;
+    }
+
+    let refinement =
+      request_id::scope(request_id::generate(), refine_job(app, &job));
+    let result = usage::track(app.config(), "queue", refinement).await;
+    match result {
+      Ok(()) => {
+        elog!(logging::INFO, "Refined {} -> {}", job.input, job.output);
+        summary.done += 1;
+        if let Some(digest) = digest {
+          cache.record(&job, digest).await;
+        }
+        finished = Some(Outcome::new(job.id, JobStatus::Done, None));
+      }
+      Err((JobStatus::Pending, _)) => {
+        vlog!("Queued {} again after the run was cancelled", job.input);
+        finished = Some(Outcome::new(job.id, JobStatus::Pending, None));
+        break;
+      }
+      Err((status, e)) => {
+        elog!(logging::WARNING, "Failed to refine {}: {}", job.input, e);
+        if status == JobStatus::Refused {
+          summary.refused += 1;
+        } else {
+          summary.failed += 1;
+        }
+        finished = Some(Outcome::new(job.id, status, Some(e)));
+      }
+    }
+  }
+  if let Some(outcome) = finished {
+    queue.finish(outcome).await?;
+  }
+
+  return Ok(summary);
+}
+
+/// Formats an overview of the queue.
+///
+/// # Arguments
+///
+/// * `jobs` - The jobs in the queue
+/// * `format` - The desired output format
+///
+/// # Returns
+///
+/// The number of jobs in each state and the failed jobs with their errors,
+/// as text or a JSON object.
+pub fn format_status(jobs: &[Job], format: OutputFormat) -> String {
+  let count = |status: JobStatus| {
+    return jobs.iter().filter(|job| job.status == status).count();
+  };
+  let with_status = |status: JobStatus| {
+    return jobs
+      .iter()
+      .filter(|job| job.status == status)
+      .collect::<Vec<&Job>>();
+  };
+  let failed = with_status(JobStatus::Failed);
+  let refused = with_status(JobStatus::Refused);
+
+  if format == OutputFormat::Json {
+    return serde_json::json!({
+      "pending": count(JobStatus::Pending),
+      "running": count(JobStatus::Running),
+      "done": count(JobStatus::Done),
+      "failed": failed,
+      "refused": refused,
+    })
+    .to_string();
+  }
+
+  let mut lines = vec![
+    format!("Pending: {}", count(JobStatus::Pending)),
+    format!("Running: {}", count(JobStatus::Running)),
+    format!("Done: {}", count(JobStatus::Done)),
+    format!("Failed: {}", failed.len()),
+  ];
+  lines.extend(failed.iter().map(|job| failed_line(job)));
+  lines.push(format!("Refused: {}", refused.len()));
+  lines.extend(refused.iter().map(|job| failed_line(job)));
+  return lines.join("\n");
+}
+
+/// Formats a failed or refused job with its error.
+fn failed_line(job: &Job) -> String {
+  return format!(
+    "  #{} {}: {}",
+    job.id,
+    job.input,
+    job.error.as_deref().unwrap_or_default()
+  );
+}
+
+/// Refines the input of a job and writes the result.
+///
+/// Returns the status to mark the job with and the error message if either
+/// fails; a cancelled job is pending again.
+async fn refine_job(app: &App, job: &Job) -> Result<(), (JobStatus, String)> {
+  let input = Some(job.input.clone());
+  let refined = match job.kind {
+    JobKind::Text => app.refine_text(None, input, OutputFormat::Text).await,
+    JobKind::Whisper => {
+      app
+        .refine_whisper_transcription(None, input, OutputFormat::Text)
+        .await
+    }
+    JobKind::Audio => {
+      app
+        .transcribe_audio(job.input.clone(), OutputFormat::Text)
+        .await
+    }
+  }
+  .map_err(|e| {
+    let status = match e.kind() {
+      ErrorKind::Refusal => JobStatus::Refused,
+      ErrorKind::Cancelled => JobStatus::Pending,
+      _ => JobStatus::Failed,
+    };
+    return (status, e.to_string());
+  })?;
+  if let Some(parent) = Path::new(&job.output).parent() {
+    tokio::fs::create_dir_all(parent).await.map_err(|e| {
+      let error = format!("Cannot create '{}': {}", parent.display(), e);
+      return (JobStatus::Failed, error);
+    })?;
+  }
+  return operations::write_string_atomic(&job.output, &(refined + "\n"))
+    .await
+    .map_err(|e| (JobStatus::Failed, e.to_string()));
+}
+
+/// Removes the jobs that are done.
+fn prune_done(state: &mut QueueState) -> usize {
+  let count = state.jobs.len();
+  state.jobs.retain(|job| job.status != JobStatus::Done);
+  return count - state.jobs.len();
+}
+
+/// Checks that no two inputs would write the same output.
+///
+/// # Arguments
+///
+/// * `jobs` - The inputs and outputs to add
+/// * `queued` - The jobs already in the queue; only those still to run
+///   count
+///
+/// # Returns
+///
+/// A `QueueResult<()>` that is `QueueError::OutputCollision` if an output
+/// is used by two different inputs.
+fn check_collisions(
+  jobs: &[(String, String)],
+  queued: &[Job],
+) -> QueueResult<()> {
+  let mut inputs = std::collections::HashMap::new();
+  let waiting = queued
+    .iter()
+    .filter(|job| matches!(job.status, JobStatus::Pending | JobStatus::Running))
+    .map(|job| (&job.input, &job.output));
+  for (input, output) in waiting.chain(jobs.iter().map(|(i, o)| (i, o))) {
+    if let Some(other) = inputs.insert(output, input)
+      && other != input
+    {
+      return Err(QueueError::OutputCollision {
+        output: output.clone(),
+        first: other.clone(),
+        second: input.clone(),
+      });
+    }
+  }
+  return Ok(());
+}
+
+/// Queues the jobs in the given state again.
+fn requeue(state: &mut QueueState, status: JobStatus) -> usize {
+  let mut requeued = 0;
+  for job in state.jobs.iter_mut().filter(|job| job.status == status) {
+    job.status = JobStatus::Pending;
+    job.error = None;
+    requeued += 1;
+  }
+  return requeued;
+}
+
+/// Gets the output path of an input, `<name>.refined.txt` in the output
+/// directory or next to the input.
+fn output_path(input: &Path, output_dir: Option<PathBuf>) -> PathBuf {
+  let stem = input
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_else(|| String::from("transcript"));
+  let name = format!("{}.refined.txt", stem);
+  return match output_dir {
+    Some(dir) => dir.join(name),
+    None => input.with_file_name(name),
+  };
+}
+
+/// Finds the files to add for an input path.
+///
+/// # Arguments
+///
+/// * `input` - A file, or with `--recursive` a directory, whose links to
+///   directories are skipped
+/// * `options` - Whether to search directories and for which extensions
+/// * `output_dir` - The absolute output directory, which is not searched
+///
+/// # Returns
+///
+/// The absolute path of each file, with the directory it is in relative
+/// to the input directory (empty for files given directly), or an error
+/// if the input does not exist or is a directory without `recursive`.
+fn find_inputs(
+  input: &str,
+  options: &AddOptions,
+  output_dir: Option<&Path>,
+) -> QueueResult<Vec<(PathBuf, PathBuf)>> {
+  let path = std::path::absolute(input)
+    .map_err(|_| QueueError::MissingInput(input.to_string()))?;
+  if path.is_file() {
+    return Ok(vec![(path, PathBuf::new())]);
+  }
+  if !path.is_dir() {
+    return Err(QueueError::MissingInput(input.to_string()));
+  }
+  if !options.recursive {
+    return Err(QueueError::Directory(input.to_string()));
+  }
+
+  let mut files = Vec::new();
+  let mut directories = vec![path.clone()];
+  while let Some(directory) = directories.pop() {
+    let entries = std::fs::read_dir(&directory).map_err(|e| {
+      QueueError::Read(directory.display().to_string(), e.to_string())
+    })?;
+    for entry in entries.flatten() {
+      let entry_path = entry.path();
+      // Results written into the input directory are not inputs.
+      if output_dir.is_some_and(|dir| entry_path == dir) {
+        continue;
+      }
+      // Symbolic links to directories are not followed, as a link to a
+      // parent directory would be searched forever.
+      let Ok(file_type) = entry.file_type() else {
+        continue;
+      };
+      if file_type.is_dir() {
+        directories.push(entry_path);
+      } else if file_type.is_symlink() && entry_path.is_dir() {
+        vlog!("Skipping {}, a link to a directory", entry_path.display());
+      } else if entry_path.is_file() && has_extension(&entry_path, options) {
+        let relative_dir = directory
+          .strip_prefix(&path)
+          .unwrap_or(Path::new(""))
+          .to_path_buf();
+        files.push((entry_path, relative_dir));
+      }
+    }
+  }
+  files.sort();
+  return Ok(files);
+}
+
+/// Checks whether a file found in a directory has one of the extensions
+/// to add, and is not a result itself.
+fn has_extension(path: &Path, options: &AddOptions) -> bool {
+  if path.to_string_lossy().ends_with(".refined.txt") {
+    return false;
+  }
+  if options.extensions.is_empty() {
+    return true;
+  }
+  let extension = path
+    .extension()
+    .map(|extension| extension.to_string_lossy().to_lowercase())
+    .unwrap_or_default();
+  return options
+    .extensions
+    .iter()
+    .any(|wanted| wanted.trim_start_matches('.').to_lowercase() == extension);
+}
+
+/// Checks whether the output of an input was written after the input was
+/// last modified.
+fn is_up_to_date(input: &Path, output: &Path) -> bool {
+  let modified = |path: &Path| std::fs::metadata(path)?.modified();
+  return match (modified(input), modified(output)) {
+    (Ok(input), Ok(output)) => output >= input,
+    _ => false,
+  };
+}
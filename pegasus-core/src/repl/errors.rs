@@ -0,0 +1,18 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Interactive session errors.
+///
+/// Represents errors that stop the refinement session.
+#[derive(Error, Debug)]
+pub enum ReplError {
+  #[error("{}", t!("error-repl-editor", error = .0.as_str()))]
+  Editor(String),
+
+  #[error("{0}")]
+  Load(String),
+}
+
+/// Result type for interactive session operations.
+pub type ReplResult<T> = Result<T, ReplError>;
@@ -0,0 +1,298 @@
+//! Interactive refinement session.
+//!
+//! `pegasus repl` refines each line as soon as it is entered, reusing one
+//! LLM client for the whole session. A line ending in `\` continues the
+//! paragraph on the next line. Input history is kept in
+//! `$XDG_STATE_HOME/pegasus/repl_history`.
+//!
+//! With `[llm] context_paragraphs` set, the most recent paragraphs and
+//! their refinements are sent along with each request, so names and style
+//! stay consistent through the session.
+//!
+//! ## Commands
+//!
+//! - `:dict`: list the dictionary words
+//! - `:dict add <word>`, `:dict remove <word>`: change the dictionary for
+//!   the rest of the session
+//! - `:undo`: drop the last refined paragraph
+//! - `:show`: print the refined text of the session
+//! - `:write <path>`: write the refined text of the session to a file
+//! - `:help`: list the commands
+//! - `:quit`: end the session (also Ctrl-D)
+
+pub mod errors;
+
+use std::path::PathBuf;
+
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use xdg::BaseDirectories;
+
+use crate::app::App;
+use crate::app::refiner::Refiner;
+use crate::config::Config;
+use crate::files::operations;
+use crate::llm::context::ConversationContext;
+use crate::logging::request_id;
+use crate::repl::errors::{ReplError, ReplResult};
+use crate::usage;
+use crate::{elog, logging, t};
+
+const STATE_DIRECTORY: &str = "pegasus";
+const HISTORY_FILE: &str = "repl_history";
+
+/// The state of an interactive session.
+struct Session {
+  config: Config,
+  refiner: Refiner,
+  editor: Option<DefaultEditor>,
+  context: ConversationContext,
+  paragraphs: Vec<String>,
+}
+
+/// Runs an interactive session until the user quits.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+///
+/// # Returns
+///
+/// A `ReplResult<()>` indicating success or failure.
+pub async fn run(app: &App) -> ReplResult<()> {
+  let refiner = app
+    .create_refiner()
+    .await
+    .map_err(|e| ReplError::Load(e.to_string()))?;
+  let mut editor =
+    DefaultEditor::new().map_err(|e| ReplError::Editor(e.to_string()))?;
+
+  let history_path = history_path();
+  if let Some(history_path) = &history_path {
+    // The history file does not exist before the first session.
+    let _ = editor.load_history(history_path);
+  }
+
+  let mut session = Session {
+    config: app.config().clone(),
+    refiner,
+    editor: Some(editor),
+    context: app.create_context(),
+    paragraphs: Vec::new(),
+  };
+  println!("{}", t!("repl-welcome"));
+
+  while let Some(paragraph) = session.read_paragraph().await? {
+    let paragraph = paragraph.trim();
+    if paragraph.is_empty() {
+      continue;
+    }
+
+    match paragraph.strip_prefix(':') {
+      Some(command) => {
+        if !session.run_command(command).await {
+          break;
+        }
+      }
+      None => session.refine(paragraph).await,
+    }
+  }
+
+  if let (Some(editor), Some(history_path)) =
+    (&mut session.editor, history_path)
+    && let Err(e) = editor.save_history(&history_path)
+  {
+    elog!(
+      logging::WARNING,
+      "Failed to save history to {}: {}",
+      history_path.display(),
+      e
+    );
+  }
+  return Ok(());
+}
+
+impl Session {
+  /// Reads a paragraph, joining lines that end in `\`.
+  ///
+  /// # Returns
+  ///
+  /// A `ReplResult<Option<String>>` containing the paragraph, an empty
+  /// string if the input was interrupted, or `None` at the end of input.
+  async fn read_paragraph(&mut self) -> ReplResult<Option<String>> {
+    let mut lines: Vec<String> = Vec::new();
+
+    loop {
+      let prompt = if lines.is_empty() {
+        t!("repl-prompt")
+      } else {
+        t!("repl-continuation-prompt")
+      };
+      let line = match self.read_line(prompt).await? {
+        Ok(line) => line,
+        Err(ReadlineError::Interrupted) => return Ok(Some(String::new())),
+        Err(ReadlineError::Eof) => return Ok(None),
+        Err(e) => return Err(ReplError::Editor(e.to_string())),
+      };
+
+      if let Some(editor) = &mut self.editor
+        && !line.trim().is_empty()
+      {
+        let _ = editor.add_history_entry(line.as_str());
+      }
+
+      match line.strip_suffix('\\') {
+        Some(continued) => lines.push(continued.to_string()),
+        None => {
+          lines.push(line);
+          return Ok(Some(lines.join("\n")));
+        }
+      }
+    }
+  }
+
+  /// Reads a line without blocking the runtime.
+  async fn read_line(
+    &mut self,
+    prompt: String,
+  ) -> ReplResult<Result<String, ReadlineError>> {
+    let mut editor = self
+      .editor
+      .take()
+      .ok_or_else(|| ReplError::Editor(String::from("editor unavailable")))?;
+
+    let (editor, line) = tokio::task::spawn_blocking(move || {
+      let line = editor.readline(&prompt);
+      return (editor, line);
+    })
+    .await
+    .map_err(|e| ReplError::Editor(e.to_string()))?;
+
+    self.editor = Some(editor);
+    return Ok(line);
+  }
+
+  /// Refines a paragraph and prints the result.
+  ///
+  /// Ctrl-C cancels the request and returns to the prompt.
+  async fn refine(&mut self, paragraph: &str) {
+    let refinement = usage::track(
+      &self.config,
+      "repl",
+      request_id::scope(
+        request_id::generate(),
+        self.refiner.refine_in_context(paragraph, &self.context),
+      ),
+    );
+    let result = tokio::select! {
+      result = refinement => result,
+      _ = tokio::signal::ctrl_c() => {
+        eprintln!("{}", t!("repl-cancelled"));
+        return;
+      }
+    };
+
+    match result {
+      Ok(refined) => {
+        let refined =
+          self.config.get_style_unicode_punctuation().apply(&refined);
+        println!("{}", refined);
+        self.context.push(paragraph.to_string(), refined.clone());
+        self.paragraphs.push(refined);
+      }
+      Err(e) => eprintln!("{}", e),
+    }
+  }
+
+  /// Runs a session command.
+  ///
+  /// # Arguments
+  ///
+  /// * `command` - The command line without the leading `:`
+  ///
+  /// # Returns
+  ///
+  /// `false` if the session should end.
+  async fn run_command(&mut self, command: &str) -> bool {
+    let (name, argument) = match command.split_once(char::is_whitespace) {
+      Some((name, argument)) => (name, argument.trim()),
+      None => (command, ""),
+    };
+
+    match (name, argument) {
+      ("q" | "quit", _) => return false,
+      ("h" | "help", _) => println!("{}", t!("repl-help")),
+      ("dict", "") => self.list_dictionary(),
+      ("dict", argument) => self.change_dictionary(argument),
+      ("undo", _) => match self.paragraphs.pop() {
+        Some(paragraph) => {
+          self.context.pop();
+          println!("{}", t!("repl-dropped", paragraph = paragraph));
+        }
+        None => eprintln!("{}", t!("repl-nothing-to-undo")),
+      },
+      ("show", _) => println!("{}", self.text()),
+      ("write", "") => eprintln!("{}", t!("repl-write-usage")),
+      ("write", path) => {
+        let content = format!("{}\n", self.text());
+        match operations::write_string_atomic(path, &content).await {
+          Ok(()) => println!("{}", t!("repl-written", path = path)),
+          Err(e) => eprintln!("{}", e),
+        }
+      }
+      _ => eprintln!("{}", t!("repl-unknown-command", name = name)),
+    }
+    return true;
+  }
+
+  /// Prints the dictionary words.
+  fn list_dictionary(&self) {
+    let dictionary = self.refiner.dictionary();
+    if dictionary.is_empty() {
+      println!("{}", t!("repl-dictionary-empty"));
+    } else {
+      let entries: Vec<String> =
+        dictionary.iter().map(|entry| entry.to_string()).collect();
+      println!("{}", entries.join(", "));
+    }
+  }
+
+  /// Runs a `:dict add` or `:dict remove` command.
+  fn change_dictionary(&mut self, argument: &str) {
+    let (action, word) = match argument.split_once(char::is_whitespace) {
+      Some((action, word)) => (action, word.trim()),
+      None => (argument, ""),
+    };
+
+    match (action, word) {
+      (_, "") => eprintln!("{}", t!("repl-dictionary-usage")),
+      ("add", word) => {
+        if self.refiner.add_dictionary_word(word.to_string()) {
+          println!("{}", t!("repl-word-added", word = word));
+        } else {
+          eprintln!("{}", t!("repl-word-exists", word = word));
+        }
+      }
+      ("remove", word) => {
+        if self.refiner.remove_dictionary_word(word) {
+          println!("{}", t!("repl-word-removed", word = word));
+        } else {
+          eprintln!("{}", t!("repl-word-missing", word = word));
+        }
+      }
+      _ => eprintln!("{}", t!("repl-dictionary-usage")),
+    }
+  }
+
+  /// Gets the refined text of the session.
+  fn text(&self) -> String {
+    return self.paragraphs.join("\n\n");
+  }
+}
+
+/// Gets the path of the history file, creating its directory.
+fn history_path() -> Option<PathBuf> {
+  return BaseDirectories::with_prefix(STATE_DIRECTORY)
+    .place_state_file(HISTORY_FILE)
+    .ok();
+}
@@ -0,0 +1,48 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Secret retrieval errors.
+///
+/// Represents errors that can occur when storing or fetching credentials
+/// from the system keyring or an external command.
+#[derive(Error, Debug)]
+pub enum SecretError {
+  #[error("{}", t!("error-secrets-keyring", error = .0.as_str()))]
+  Keyring(String),
+
+  #[error("{}", t!("error-secrets-not-found"))]
+  NotFound,
+
+  #[error(
+    "{}",
+    t!(
+      "error-secrets-command",
+      command = .command.as_str(),
+      error = .error.as_str()
+    )
+  )]
+  CommandFailed { command: String, error: String },
+
+  #[error(
+    "{}",
+    t!("error-secrets-empty-command-output", command = .0.as_str())
+  )]
+  EmptyCommandOutput(String),
+
+  #[error(
+    "{}",
+    t!(
+      "error-secrets-file-read",
+      path = .path.as_str(),
+      error = .error.as_str()
+    )
+  )]
+  FileRead { path: String, error: String },
+
+  #[error("{}", t!("error-secrets-empty-file", path = .0.as_str()))]
+  EmptyFile(String),
+}
+
+/// Result type for secret operations.
+pub type SecretResult<T> = Result<T, SecretError>;
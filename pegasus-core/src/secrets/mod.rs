@@ -0,0 +1,321 @@
+//! Secret storage and retrieval module.
+//!
+//! Keeps the API keys of the LLM and speech-to-text services out of the
+//! plaintext configuration file by fetching them from the system keyring, a
+//! dedicated secrets file, the `PEGASUS_LLM_API_KEY` and
+//! `PEGASUS_TRANSCRIPTION_API_KEY` environment variables, or from the output
+//! of a user-defined command (for example `pass show openai`).
+//!
+//! ## Main Components
+//!
+//! - [`Secret`]: Which API key is read
+//! - [`ApiKeySource`]: Where the API key is read from
+//! - [`SecretError`]: Error types for secret operations
+//! - [`SecretResult<T>`]: Result type alias for secret operations
+
+pub mod errors;
+
+use crate::files::operations;
+use crate::secrets::errors::{SecretError, SecretResult};
+use crate::{elog, logging, vlog};
+
+const KEYRING_SERVICE: &str = "pegasus";
+
+/// Environment variable that overrides every configured LLM API key source.
+pub const API_KEY_ENV_VAR: &str = "PEGASUS_LLM_API_KEY";
+
+/// Environment variable that overrides every configured speech-to-text API
+/// key source.
+pub const TRANSCRIPTION_API_KEY_ENV_VAR: &str = "PEGASUS_TRANSCRIPTION_API_KEY";
+
+/// An API key read by Pegasus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret {
+  /// The API key of the LLM service
+  LlmApiKey,
+  /// The API key of the speech-to-text service
+  TranscriptionApiKey,
+}
+
+impl Secret {
+  /// Gets the environment variable that overrides the configured source.
+  ///
+  /// # Returns
+  ///
+  /// The name of the environment variable.
+  pub fn env_var(self) -> &'static str {
+    return match self {
+      Secret::LlmApiKey => API_KEY_ENV_VAR,
+      Secret::TranscriptionApiKey => TRANSCRIPTION_API_KEY_ENV_VAR,
+    };
+  }
+
+  /// Gets the user name of the keyring entry holding the key.
+  fn keyring_user(self) -> &'static str {
+    return match self {
+      Secret::LlmApiKey => "llm-api-key",
+      Secret::TranscriptionApiKey => "transcription-api-key",
+    };
+  }
+}
+
+/// Source of the LLM API key.
+#[derive(
+  Debug,
+  Clone,
+  Copy,
+  Default,
+  PartialEq,
+  Eq,
+  serde::Deserialize,
+  serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeySource {
+  /// The `api_key` value in the configuration file
+  #[default]
+  Config,
+  /// The system keyring, managed with `pegasus auth set/remove`
+  Keyring,
+  /// The output of `api_key_command`
+  Command,
+  /// The contents of `api_key_file`
+  File,
+}
+
+/// Resolves an API key from its configured source.
+///
+/// The environment variable of the secret takes precedence over every
+/// configured source.
+///
+/// # Arguments
+///
+/// * `secret` - Which API key to read
+/// * `source` - Where the API key is configured to be read from
+/// * `api_key` - The API key in the configuration file
+/// * `command` - The command printing the API key
+/// * `file` - Path of the file containing the API key
+///
+/// # Returns
+///
+/// A `SecretResult<String>` containing the API key (possibly empty) or an
+/// error if the configured source could not provide one.
+pub async fn resolve_api_key(
+  secret: Secret,
+  source: ApiKeySource,
+  api_key: String,
+  command: &str,
+  file: &str,
+) -> SecretResult<String> {
+  if let Some(api_key) = get_env_api_key(secret) {
+    return Ok(api_key);
+  }
+
+  return match source {
+    ApiKeySource::Config => Ok(api_key),
+    ApiKeySource::Keyring => get_keyring_api_key(secret).await,
+    ApiKeySource::Command => run_api_key_command(command).await,
+    ApiKeySource::File => read_api_key_file(file).await,
+  };
+}
+
+/// Reads an API key from its environment variable.
+///
+/// # Arguments
+///
+/// * `secret` - Which API key to read
+///
+/// # Returns
+///
+/// The API key, or `None` if the variable is unset or empty.
+pub fn get_env_api_key(secret: Secret) -> Option<String> {
+  let api_key = std::env::var(secret.env_var()).ok()?.trim().to_string();
+  if api_key.is_empty() {
+    return None;
+  }
+  vlog!("Using API key from {}", secret.env_var());
+  return Some(api_key);
+}
+
+/// Reads the API key from a secrets file.
+///
+/// Warns on stderr when the file is readable or writable by group or others,
+/// similar to how `ssh` treats private keys.
+///
+/// # Arguments
+///
+/// * `path` - Path to the file containing the API key
+///
+/// # Returns
+///
+/// A `SecretResult<String>` containing the API key or an error.
+pub async fn read_api_key_file(path: &str) -> SecretResult<String> {
+  vlog!("Reading API key from file: {}", path);
+
+  warn_if_permissive(path).await;
+
+  let content = operations::read_to_string(path).await.map_err(|e| {
+    SecretError::FileRead {
+      path: path.to_string(),
+      error: e.to_string(),
+    }
+  })?;
+
+  let api_key = content
+    .lines()
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .to_string();
+  if api_key.is_empty() {
+    return Err(SecretError::EmptyFile(path.to_string()));
+  }
+
+  return Ok(api_key);
+}
+
+/// Prints a warning if a secrets file is accessible by group or others.
+///
+/// # Arguments
+///
+/// * `path` - Path to the secrets file
+#[cfg(unix)]
+async fn warn_if_permissive(path: &str) {
+  use std::os::unix::fs::PermissionsExt;
+
+  let Ok(metadata) = tokio::fs::metadata(path).await else {
+    return;
+  };
+
+  let mode = metadata.permissions().mode() & 0o777;
+  if mode & 0o077 != 0 {
+    elog!(
+      logging::WARNING,
+      "Warning: API key file '{}' has permissions {:o}; consider `chmod 600 {}`",
+      path,
+      mode,
+      path
+    );
+  }
+}
+
+#[cfg(not(unix))]
+async fn warn_if_permissive(_path: &str) {}
+
+/// Opens the keyring entry holding an API key.
+///
+/// # Returns
+///
+/// A `SecretResult<keyring::Entry>` for the API key.
+fn keyring_entry(secret: Secret) -> SecretResult<keyring::Entry> {
+  return keyring::Entry::new(KEYRING_SERVICE, secret.keyring_user())
+    .map_err(|e| SecretError::Keyring(e.to_string()));
+}
+
+/// Reads an API key from the system keyring.
+///
+/// # Arguments
+///
+/// * `secret` - Which API key to read
+///
+/// # Returns
+///
+/// A `SecretResult<String>` containing the API key or an error.
+pub async fn get_keyring_api_key(secret: Secret) -> SecretResult<String> {
+  vlog!("Reading API key from system keyring");
+
+  return tokio::task::spawn_blocking(move || {
+    return keyring_entry(secret)?.get_password().map_err(|e| match e {
+      keyring::Error::NoEntry => SecretError::NotFound,
+      e => SecretError::Keyring(e.to_string()),
+    });
+  })
+  .await
+  .map_err(|e| SecretError::Keyring(e.to_string()))?;
+}
+
+/// Stores an API key in the system keyring.
+///
+/// # Arguments
+///
+/// * `secret` - Which API key to store
+/// * `api_key` - The API key to store
+///
+/// # Returns
+///
+/// A `SecretResult<()>` indicating success or failure.
+pub async fn set_keyring_api_key(
+  secret: Secret,
+  api_key: String,
+) -> SecretResult<()> {
+  return tokio::task::spawn_blocking(move || {
+    return keyring_entry(secret)?
+      .set_password(&api_key)
+      .map_err(|e| SecretError::Keyring(e.to_string()));
+  })
+  .await
+  .map_err(|e| SecretError::Keyring(e.to_string()))?;
+}
+
+/// Removes an API key from the system keyring.
+///
+/// # Arguments
+///
+/// * `secret` - Which API key to remove
+///
+/// # Returns
+///
+/// A `SecretResult<()>` indicating success or failure.
+pub async fn remove_keyring_api_key(secret: Secret) -> SecretResult<()> {
+  return tokio::task::spawn_blocking(move || {
+    return keyring_entry(secret)?
+      .delete_credential()
+      .map_err(|e| match e {
+        keyring::Error::NoEntry => SecretError::NotFound,
+        e => SecretError::Keyring(e.to_string()),
+      });
+  })
+  .await
+  .map_err(|e| SecretError::Keyring(e.to_string()))?;
+}
+
+/// Runs a shell command and returns its trimmed standard output as the key.
+///
+/// # Arguments
+///
+/// * `command` - The shell command to run (e.g. `pass show openai`)
+///
+/// # Returns
+///
+/// A `SecretResult<String>` containing the API key or an error.
+pub async fn run_api_key_command(command: &str) -> SecretResult<String> {
+  vlog!("Fetching API key from command: {}", command);
+
+  let output = tokio::process::Command::new("sh")
+    .arg("-c")
+    .arg(command)
+    .stdin(std::process::Stdio::null())
+    .output()
+    .await
+    .map_err(|e| SecretError::CommandFailed {
+      command: command.to_string(),
+      error: e.to_string(),
+    })?;
+
+  if !output.status.success() {
+    return Err(SecretError::CommandFailed {
+      command: command.to_string(),
+      error: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    });
+  }
+
+  // Like `git credential` helpers, only the first line carries the secret.
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  let api_key = stdout.lines().next().unwrap_or_default().trim().to_string();
+
+  if api_key.is_empty() {
+    return Err(SecretError::EmptyCommandOutput(command.to_string()));
+  }
+
+  return Ok(api_key);
+}
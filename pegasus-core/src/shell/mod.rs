@@ -0,0 +1,112 @@
+//! Command lines configured as a single string.
+//!
+//! Settings such as `[transcription] silence_command` hold a whole command
+//! line. It is split into words with shell quoting rules but run without a
+//! shell, so the command and its placeholders cannot expand into other
+//! commands. Use `sh -c '...'` explicitly for pipes or redirections.
+//!
+//! Commands that do run through `sh`, such as command sinks, get their
+//! values quoted with [`quote`].
+
+/// Splits a command line into its program and arguments.
+///
+/// Single and double quotes and backslashes group and escape words like
+/// in `sh`; nothing is expanded.
+///
+/// # Arguments
+///
+/// * `command` - The command line
+///
+/// # Returns
+///
+/// The words of the command, or `None` if the quoting is unbalanced or the
+/// command is empty.
+pub fn split(command: &str) -> Option<Vec<String>> {
+  return shlex::split(command).filter(|words| !words.is_empty());
+}
+
+/// Splits a command line and replaces a placeholder in every word.
+///
+/// The value is substituted after splitting, so it always stays inside the
+/// word it appears in, whatever characters it contains.
+///
+/// # Arguments
+///
+/// * `command` - The command line
+/// * `placeholder` - The placeholder, e.g. `{input}`
+/// * `value` - The value replacing the placeholder
+///
+/// # Returns
+///
+/// The words of the command, or `None` if it cannot be split.
+pub fn split_with(
+  command: &str,
+  placeholder: &str,
+  value: &str,
+) -> Option<Vec<String>> {
+  return split(command).map(|words| {
+    words
+      .into_iter()
+      .map(|word| word.replace(placeholder, value))
+      .collect()
+  });
+}
+
+/// Quotes a value as a single word for `sh`.
+///
+/// Used for commands that run through a shell on purpose, such as command
+/// sinks and dictation hooks.
+///
+/// # Arguments
+///
+/// * `value` - The value
+///
+/// # Returns
+///
+/// The value in single quotes, with its own single quotes escaped.
+pub fn quote(value: &str) -> String {
+  return format!("'{}'", value.replace('\'', "'\\''"));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn splits_quoted_words() {
+    assert_eq!(
+      split("ffmpeg -i 'my file.wav' -af \"a b\""),
+      Some(vec![
+        String::from("ffmpeg"),
+        String::from("-i"),
+        String::from("my file.wav"),
+        String::from("-af"),
+        String::from("a b"),
+      ])
+    );
+  }
+
+  #[test]
+  fn rejects_unbalanced_quotes_and_empty_commands() {
+    assert_eq!(split("ffmpeg -i 'open"), None);
+    assert_eq!(split("   "), None);
+  }
+
+  #[test]
+  fn keeps_substituted_values_in_one_word() {
+    assert_eq!(
+      split_with("vad {input}", "{input}", "a b; rm -rf ~"),
+      Some(vec![String::from("vad"), String::from("a b; rm -rf ~")])
+    );
+  }
+
+  #[test]
+  fn quotes_values_for_sh() {
+    assert_eq!(quote("a b"), "'a b'");
+    assert_eq!(quote("it's $HOME"), "'it'\\''s $HOME'");
+    assert_eq!(
+      split(&format!("echo {}", quote("it's"))).unwrap()[1],
+      "it's"
+    );
+  }
+}
@@ -0,0 +1,24 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// systemd integration errors.
+///
+/// Represents errors that can occur while installing the user service.
+#[derive(Error, Debug)]
+pub enum SystemdError {
+  #[error("{}", t!("error-systemd-executable", error = .0.as_str()))]
+  Executable(String),
+
+  #[error("{}", t!("error-systemd-directory", error = .0.as_str()))]
+  Directory(String),
+
+  #[error("{}", t!("error-systemd-exists", path = .0.as_str()))]
+  Exists(String),
+
+  #[error("{}", t!("error-systemd-write", error = .0.as_str()))]
+  Write(String),
+}
+
+/// Result type for systemd operations.
+pub type SystemdResult<T> = Result<T, SystemdError>;
@@ -0,0 +1,111 @@
+//! systemd service integration.
+//!
+//! Implements the `sd_notify` protocol so `pegasus daemon` can run as a
+//! `Type=notify` service: readiness is reported once the socket is bound,
+//! and the watchdog is pinged when the unit sets `WatchdogSec=`. Both are
+//! no-ops when the process was not started by systemd.
+//!
+//! ## Components
+//!
+//! - [`notify`]: Send a state change such as `READY=1` to systemd
+//! - [`spawn_watchdog`]: Ping the service watchdog in the background
+//! - [`unit`]: Write a user unit file for `pegasus daemon`
+
+pub mod errors;
+pub mod unit;
+
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+use crate::vlog;
+
+/// Sends a state change to systemd.
+///
+/// Does nothing unless `NOTIFY_SOCKET` is set.
+///
+/// # Arguments
+///
+/// * `state` - Newline-separated assignments, e.g. `READY=1`
+pub fn notify(state: &str) {
+  let Ok(socket_path) = std::env::var("NOTIFY_SOCKET") else {
+    return;
+  };
+
+  let result = UnixDatagram::unbound()
+    .and_then(|socket| send(&socket, &socket_path, state.as_bytes()));
+  if let Err(e) = result {
+    vlog!("Cannot notify systemd at {}: {}", socket_path, e);
+  }
+}
+
+/// Pings the service watchdog at half its timeout until the process exits.
+///
+/// Does nothing unless systemd set `WATCHDOG_USEC` for this process.
+pub fn spawn_watchdog() {
+  let Some(interval) = watchdog_interval() else {
+    return;
+  };
+
+  vlog!("Pinging the systemd watchdog every {:?}", interval);
+  tokio::spawn(async move {
+    loop {
+      notify("WATCHDOG=1");
+      tokio::time::sleep(interval).await;
+    }
+  });
+}
+
+/// Gets how often the watchdog must be pinged.
+///
+/// # Returns
+///
+/// Half the watchdog timeout, or `None` if the watchdog is not enabled for
+/// this process.
+fn watchdog_interval() -> Option<Duration> {
+  let timeout: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+  if timeout == 0 {
+    return None;
+  }
+
+  // The watchdog variables are inherited by child processes; only the
+  // process systemd started may answer.
+  if let Ok(pid) = std::env::var("WATCHDOG_PID")
+    && pid.parse::<u32>().ok() != Some(std::process::id())
+  {
+    return None;
+  }
+
+  return Some(Duration::from_micros(timeout / 2));
+}
+
+/// Sends a datagram to the notification socket, which may be a path or,
+/// when it starts with `@`, an abstract socket name.
+fn send(
+  socket: &UnixDatagram,
+  socket_path: &str,
+  message: &[u8],
+) -> std::io::Result<()> {
+  let Some(name) = socket_path.strip_prefix('@') else {
+    socket.send_to(message, socket_path)?;
+    return Ok(());
+  };
+
+  #[cfg(target_os = "linux")]
+  {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    let address = SocketAddr::from_abstract_name(name.as_bytes())?;
+    socket.send_to_addr(message, &address)?;
+    return Ok(());
+  }
+
+  #[cfg(not(target_os = "linux"))]
+  {
+    let _ = name;
+    return Err(std::io::Error::new(
+      std::io::ErrorKind::Unsupported,
+      "abstract sockets are only supported on Linux",
+    ));
+  }
+}
@@ -0,0 +1,69 @@
+//! systemd user unit installation.
+
+use std::path::{Path, PathBuf};
+
+use xdg::BaseDirectories;
+
+use crate::files::operations;
+use crate::systemd::errors::{SystemdError, SystemdResult};
+
+/// Name of the installed unit file.
+pub const UNIT_NAME: &str = "pegasus.service";
+
+/// Writes a user unit that runs `pegasus daemon` under systemd.
+///
+/// The unit is written to `$XDG_CONFIG_HOME/systemd/user/pegasus.service`
+/// and starts the currently running executable.
+///
+/// # Arguments
+///
+/// * `force` - Whether to overwrite an existing unit file
+///
+/// # Returns
+///
+/// A `SystemdResult<PathBuf>` containing the path of the unit file.
+pub async fn install(force: bool) -> SystemdResult<PathBuf> {
+  let executable = std::env::current_exe()
+    .map_err(|e| SystemdError::Executable(e.to_string()))?;
+
+  let xdg_dirs = BaseDirectories::new();
+  let unit_path = xdg_dirs
+    .place_config_file(PathBuf::from("systemd/user").join(UNIT_NAME))
+    .map_err(|e| SystemdError::Directory(e.to_string()))?;
+  let unit_name = unit_path.to_string_lossy().to_string();
+
+  if unit_path.exists() && !force {
+    return Err(SystemdError::Exists(unit_name));
+  }
+
+  operations::write_string_atomic(&unit_name, &render(&executable))
+    .await
+    .map_err(|e| SystemdError::Write(e.to_string()))?;
+
+  return Ok(unit_path);
+}
+
+/// Renders the unit file.
+///
+/// # Arguments
+///
+/// * `executable` - Path to the pegasus executable
+///
+/// # Returns
+///
+/// The unit file content.
+fn render(executable: &Path) -> String {
+  return format!(
+    "[Unit]\n\
+     Description=Pegasus transcript refinement daemon\n\n\
+     [Service]\n\
+     Type=notify\n\
+     ExecStart=\"{}\" daemon --systemd\n\
+     Restart=on-failure\n\
+     SuccessExitStatus=143\n\
+     WatchdogSec=30\n\n\
+     [Install]\n\
+     WantedBy=default.target\n",
+    executable.display()
+  );
+}
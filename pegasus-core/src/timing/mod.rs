@@ -0,0 +1,235 @@
+//! Per-phase wall-clock timing for `--timing`.
+//!
+//! Shows whether the latency of a run comes from the model or from Pegasus
+//! itself. Like the verbose flag, timing is enabled once at startup and
+//! recorded in a process-wide table, so phases can be measured wherever
+//! they happen without passing a timer through function signatures.
+//! Durations can also be recorded without being printed, for the run
+//! report of `--report` (see [`record_only`]).
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use pegasus_core::timing::{self, Phase};
+//!
+//! timing::set_enabled(true);
+//!
+//! let timer = timing::start(Phase::Prompt);
+//! let prompt = String::from("Please refine the following text");
+//! drop(timer);
+//!
+//! eprintln!("{}", timing::report());
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::{Value, json};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+static TIMINGS: LazyLock<Mutex<Timings>> =
+  LazyLock::new(|| Mutex::new(Timings::default()));
+
+/// A phase of a refinement run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+  /// Reading, decompressing and decoding the input
+  Input,
+  /// Loading the custom dictionary
+  Dictionary,
+  /// Building the prompts
+  Prompt,
+  /// Waiting for the LLM service, including fallback retries
+  Network,
+  /// Extracting, joining and formatting the refined text
+  PostProcessing,
+}
+
+const PHASES: [Phase; 5] = [
+  Phase::Input,
+  Phase::Dictionary,
+  Phase::Prompt,
+  Phase::Network,
+  Phase::PostProcessing,
+];
+
+impl Phase {
+  /// Gets the name of the phase in reports.
+  ///
+  /// # Returns
+  ///
+  /// The phase name in snake case.
+  pub fn name(self) -> &'static str {
+    return match self {
+      Phase::Input => "input",
+      Phase::Dictionary => "dictionary",
+      Phase::Prompt => "prompt",
+      Phase::Network => "network",
+      Phase::PostProcessing => "post_processing",
+    };
+  }
+}
+
+/// Time spent in each phase since timing was enabled.
+#[derive(Debug, Default)]
+struct Timings {
+  started: Option<Instant>,
+  phases: [Duration; PHASES.len()],
+}
+
+/// Measures a phase until it is dropped.
+pub struct PhaseTimer {
+  phase: Phase,
+  started: Instant,
+}
+
+impl Drop for PhaseTimer {
+  fn drop(&mut self) {
+    record(self.phase, self.started.elapsed());
+  }
+}
+
+/// Enables or disables timing.
+///
+/// Enabling timing starts the clock for the total duration.
+///
+/// # Arguments
+///
+/// * `value` - Whether to record phase durations
+pub fn set_enabled(value: bool) {
+  if value {
+    let mut timings = TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+    timings.started = Some(Instant::now());
+  }
+  ENABLED.store(value, Ordering::Relaxed);
+}
+
+/// Records phase durations without enabling timing.
+///
+/// The durations are available with [`phases`], but not added to output
+/// as with `--timing`. Starts the clock for the total duration, unless
+/// timing is enabled already.
+pub fn record_only() {
+  if !is_enabled() {
+    let mut timings = TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+    timings.started = Some(Instant::now());
+  }
+  RECORDING.store(true, Ordering::Relaxed);
+}
+
+/// Checks if timing is enabled.
+///
+/// # Returns
+///
+/// `true` if phase durations are recorded, `false` otherwise.
+pub fn is_enabled() -> bool {
+  return ENABLED.load(Ordering::Relaxed);
+}
+
+/// Starts measuring a phase.
+///
+/// # Arguments
+///
+/// * `phase` - The phase being measured
+///
+/// # Returns
+///
+/// A `PhaseTimer` that records the phase's duration when dropped.
+pub fn start(phase: Phase) -> PhaseTimer {
+  return PhaseTimer {
+    phase,
+    started: Instant::now(),
+  };
+}
+
+/// Adds time spent in a phase.
+///
+/// # Arguments
+///
+/// * `phase` - The phase
+/// * `duration` - The time spent in it
+pub fn record(phase: Phase, duration: Duration) {
+  if !is_enabled() && !RECORDING.load(Ordering::Relaxed) {
+    return;
+  }
+  let mut timings = TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+  timings.phases[phase as usize] += duration;
+}
+
+/// Gets the recorded durations.
+///
+/// # Returns
+///
+/// Each phase with the time spent in it, followed by the total time since
+/// timing was enabled.
+fn durations() -> (Vec<(Phase, Duration)>, Duration) {
+  let timings = TIMINGS.lock().unwrap_or_else(|e| e.into_inner());
+  let phases = PHASES
+    .iter()
+    .map(|phase| (*phase, timings.phases[*phase as usize]))
+    .collect();
+  let total = timings
+    .started
+    .map(|started| started.elapsed())
+    .unwrap_or_default();
+  return (phases, total);
+}
+
+/// Renders the recorded durations for people.
+///
+/// # Returns
+///
+/// One line per phase and the total, in milliseconds.
+pub fn report() -> String {
+  let (phases, total) = durations();
+  let mut lines: Vec<String> = phases
+    .iter()
+    .map(|(phase, duration)| {
+      format!("{:<16} {:>10.1} ms", phase.name(), milliseconds(*duration))
+    })
+    .collect();
+  lines.push(format!("{:<16} {:>10.1} ms", "total", milliseconds(total)));
+  return lines.join("\n");
+}
+
+/// Renders the recorded durations as JSON.
+///
+/// # Returns
+///
+/// An object with the milliseconds spent in each phase and in total.
+pub fn to_json() -> Value {
+  let (phases, total) = durations();
+  let mut object = serde_json::Map::new();
+  for (phase, duration) in phases {
+    object.insert(
+      format!("{}_ms", phase.name()),
+      json!(milliseconds(duration)),
+    );
+  }
+  object.insert(String::from("total_ms"), json!(milliseconds(total)));
+  return Value::Object(object);
+}
+
+/// Gets the recorded durations in milliseconds.
+///
+/// # Returns
+///
+/// The name of each phase with the milliseconds spent in it, followed by
+/// `total`.
+pub fn phases() -> Vec<(&'static str, f64)> {
+  let (phases, total) = durations();
+  let mut milliseconds_by_phase: Vec<(&'static str, f64)> = phases
+    .iter()
+    .map(|(phase, duration)| (phase.name(), milliseconds(*duration)))
+    .collect();
+  milliseconds_by_phase.push(("total", milliseconds(total)));
+  return milliseconds_by_phase;
+}
+
+/// Converts a duration to fractional milliseconds.
+fn milliseconds(duration: Duration) -> f64 {
+  return duration.as_secs_f64() * 1000.0;
+}
@@ -0,0 +1,24 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Interactive review errors.
+///
+/// Represents errors that stop the review interface.
+#[derive(Error, Debug)]
+pub enum TuiError {
+  #[error("{}", t!("error-tui-not-a-terminal"))]
+  NotATerminal,
+
+  #[error("{}", t!("error-tui-terminal", error = .0.as_str()))]
+  Terminal(String),
+
+  #[error("{0}")]
+  Load(String),
+
+  #[error("{}", t!("error-tui-record", error = .0.as_str()))]
+  Record(String),
+}
+
+/// Result type for interactive review operations.
+pub type TuiResult<T> = Result<T, TuiError>;
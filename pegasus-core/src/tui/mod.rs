@@ -0,0 +1,423 @@
+//! Interactive review of Whisper transcriptions in the terminal.
+//!
+//! `pegasus tui --file transcript.json` shows one segment at a time: the
+//! original words colored by confidence, the refined suggestion for the
+//! segment, and the decision taken. Suggestions are requested from the LLM
+//! as segments are visited. The reviewed text is written with `w`, which
+//! also records the words replaced in edited and rejected segments for
+//! `pegasus dictionary learn`.
+//!
+//! ## Keys
+//!
+//! - `↑`/`k`, `↓`/`j`: previous and next segment
+//! - `a`: accept the suggestion
+//! - `r`: reject the suggestion and keep the original text
+//! - `e`: edit the segment in `$VISUAL` or `$EDITOR`
+//! - `w`: write the reviewed text
+//! - `q`: quit
+//! - Ctrl-C: cancel the suggestion being requested, or quit
+//!
+//! Other keys pressed while a suggestion is requested are discarded, so
+//! nothing typed ahead is applied to a suggestion that was not shown.
+
+pub mod errors;
+pub mod review;
+pub mod terminal;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Paragraph, Wrap};
+use tokio_util::sync::CancellationToken;
+
+use crate::app::App;
+use crate::app::errors::RuntimeError;
+use crate::app::refiner::Refiner;
+use crate::dictionary::learn;
+use crate::files::operations;
+use crate::files::temporary::TemporaryFile;
+use crate::tui::errors::{TuiError, TuiResult};
+use crate::tui::review::{Decision, Review, Suggestion};
+use crate::tui::terminal::{Key, RawTerminal};
+
+/// How often keys are checked while a suggestion is requested.
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const HELP: &str =
+  "↑/k previous  ↓/j next  a accept  r reject  e edit  w write  q quit";
+
+/// The state of the review interface.
+struct ReviewScreen<'a> {
+  review: Review,
+  refiner: Refiner,
+  terminal: &'a mut RawTerminal,
+  title: String,
+  output_path: PathBuf,
+  threshold: f64,
+  selected: usize,
+  status: String,
+  unsaved: bool,
+  written: bool,
+  record_corrections: bool,
+  /// The decisions whose corrections were recorded, per segment
+  recorded: Vec<Decision>,
+  cancellation: CancellationToken,
+}
+
+/// Runs the review interface until the user quits.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+/// * `file_path` - Path to the Whisper JSON transcription
+/// * `output_path` - Where to write the reviewed text; defaults to
+///   `<name>.reviewed.txt` next to the transcription
+///
+/// # Returns
+///
+/// A `TuiResult<Option<PathBuf>>` containing the path the reviewed text was
+/// last written to, if it was written.
+pub async fn run(
+  app: &App,
+  file_path: String,
+  output_path: Option<String>,
+) -> TuiResult<Option<PathBuf>> {
+  let transcription = app
+    .read_whisper_transcription(None, Some(file_path.clone()))
+    .await
+    .map_err(|e| TuiError::Load(e.to_string()))?;
+  let refiner = app
+    .create_refiner()
+    .await
+    .map_err(|e| TuiError::Load(e.to_string()))?;
+
+  let output_path = output_path
+    .map(PathBuf::from)
+    .unwrap_or_else(|| default_output_path(Path::new(&file_path)));
+
+  let mut terminal = RawTerminal::enter()?;
+  let review = Review::new(transcription);
+  let recorded = vec![Decision::Pending; review.segments.len()];
+  let mut screen = ReviewScreen {
+    review,
+    refiner,
+    terminal: &mut terminal,
+    title: file_path,
+    output_path,
+    threshold: app.config().get_whisper_probability_threshold(),
+    selected: 0,
+    status: String::new(),
+    unsaved: false,
+    written: false,
+    record_corrections: app.config().get_dictionary_record_corrections(),
+    recorded,
+    cancellation: app.cancellation().clone(),
+  };
+  return screen.run().await;
+}
+
+impl ReviewScreen<'_> {
+  /// Handles key presses until the user quits.
+  async fn run(&mut self) -> TuiResult<Option<PathBuf>> {
+    let mut confirm_quit = false;
+
+    loop {
+      self.refine_selected().await?;
+      self.draw()?;
+
+      let key = self.terminal.read_key().await?;
+      let quitting = matches!(key, Key::Char('q') | Key::Interrupt);
+      self.status.clear();
+
+      match key {
+        Key::Up | Key::Char('k') => {
+          self.selected = self.selected.saturating_sub(1);
+        }
+        Key::Down | Key::Char('j') => self.select_next(),
+        Key::Char('a') => {
+          if let Suggestion::Ready(_) = self.current().suggestion {
+            self.decide(Decision::Accepted);
+          } else {
+            self.status = String::from("No suggestion to accept");
+          }
+        }
+        Key::Char('r') => self.decide(Decision::Rejected),
+        Key::Char('e') => self.edit().await?,
+        Key::Char('w') => self.write().await,
+        Key::Char('q') | Key::Interrupt => {
+          if !self.unsaved || confirm_quit {
+            return Ok(self.written.then(|| self.output_path.clone()));
+          }
+          self.status =
+            String::from("Unsaved decisions; press w to write or q to quit");
+        }
+        _ => {}
+      }
+      confirm_quit = quitting;
+    }
+  }
+
+  /// Draws the screen.
+  fn draw(&mut self) -> TuiResult<()> {
+    let lines = self.body();
+    let status = self.status.clone();
+    return self.terminal.draw(|frame| render(frame, lines, status));
+  }
+
+  /// Gets the selected segment.
+  fn current(&self) -> &review::ReviewSegment {
+    return &self.review.segments[self.selected];
+  }
+
+  /// Moves to the next segment, if any.
+  fn select_next(&mut self) {
+    if self.selected + 1 < self.review.segments.len() {
+      self.selected += 1;
+    }
+  }
+
+  /// Records a decision for the selected segment and moves on.
+  fn decide(&mut self, decision: Decision) {
+    self.review.segments[self.selected].decision = decision;
+    self.unsaved = true;
+    self.select_next();
+  }
+
+  /// Requests the suggestion for the selected segment if it has none yet.
+  ///
+  /// Keys are checked meanwhile: Ctrl-C cancels the request, and other
+  /// keys are discarded.
+  async fn refine_selected(&mut self) -> TuiResult<()> {
+    if !matches!(self.current().suggestion, Suggestion::Pending) {
+      return Ok(());
+    }
+
+    self.status = String::from("Refining segment...");
+    self.draw()?;
+    self.status.clear();
+
+    let transcription = self.review.segment_transcription(self.selected);
+    let cancellation = self.cancellation.child_token();
+    let refinement = cancellation
+      .run_until_cancelled(self.refiner.refine_whisper(&transcription));
+    tokio::pin!(refinement);
+    let result = loop {
+      tokio::select! {
+        result = &mut refinement => break result,
+        _ = tokio::time::sleep(KEY_POLL_INTERVAL) => {
+          while let Some(key) = self.terminal.poll_key()? {
+            if key == Key::Interrupt {
+              cancellation.cancel();
+            }
+          }
+        }
+      }
+    };
+    self.terminal.drain()?;
+    let suggestion = match result {
+      Some(Ok(text)) => Suggestion::Ready(text),
+      Some(Err(e)) => Suggestion::Failed(e.to_string()),
+      None => {
+        Suggestion::Failed(RuntimeError::Cancelled(String::new()).to_string())
+      }
+    };
+    self.review.segments[self.selected].suggestion = suggestion;
+    return Ok(());
+  }
+
+  /// Edits the selected segment in the user's editor.
+  async fn edit(&mut self) -> TuiResult<()> {
+    let segment = self.current();
+    let draft_text = match (&segment.decision, &segment.suggestion) {
+      (Decision::Edited(text), _) | (_, Suggestion::Ready(text)) => {
+        text.clone()
+      }
+      _ => segment.segment.text.trim().to_string(),
+    };
+
+    let draft = TemporaryFile::create_in_cache_with_content(
+      "segment",
+      "txt",
+      &draft_text,
+    )
+    .await
+    .map_err(|e| TuiError::Terminal(e.to_string()))?;
+
+    self.terminal.suspend()?;
+    let edit_result = operations::edit_in_editor(draft.path()).await;
+    self.terminal.resume()?;
+
+    if let Err(e) = edit_result {
+      self.status = e.to_string();
+      return Ok(());
+    }
+
+    match operations::read_to_string(&draft.path_string()).await {
+      Ok(text) if !text.trim().is_empty() => {
+        self.decide(Decision::Edited(text.trim().to_string()));
+      }
+      Ok(_) => self.status = String::from("Empty edit discarded"),
+      Err(e) => self.status = e.to_string(),
+    }
+    return Ok(());
+  }
+
+  /// Writes the reviewed text to the output path.
+  async fn write(&mut self) {
+    let output_path = self.output_path.to_string_lossy().to_string();
+    let content = format!("{}\n", self.review.final_text());
+
+    match operations::write_string_atomic(&output_path, &content).await {
+      Ok(()) => {
+        self.unsaved = false;
+        self.written = true;
+        self.status = format!("Wrote {}", output_path);
+        if let Err(e) = self.record_corrections().await {
+          self.status = format!("{}; {}", self.status, e);
+        }
+      }
+      Err(e) => self.status = e.to_string(),
+    };
+  }
+
+  /// Records the words replaced in segments decided since the last write.
+  async fn record_corrections(&mut self) -> TuiResult<()> {
+    if !self.record_corrections {
+      return Ok(());
+    }
+    let mut corrections = Vec::new();
+    for (segment, recorded) in
+      self.review.segments.iter().zip(self.recorded.iter_mut())
+    {
+      if segment.decision == *recorded {
+        continue;
+      }
+      let original = segment.segment.text.trim();
+      match (&segment.decision, &segment.suggestion) {
+        (Decision::Edited(text), Suggestion::Ready(suggestion)) => {
+          corrections.extend(learn::find_corrections(suggestion, text));
+        }
+        (Decision::Edited(text), _) => {
+          corrections.extend(learn::find_corrections(original, text));
+        }
+        (Decision::Rejected, Suggestion::Ready(suggestion)) => {
+          corrections.extend(learn::find_corrections(suggestion, original));
+        }
+        _ => {}
+      }
+      *recorded = segment.decision.clone();
+    }
+    return learn::record(&corrections)
+      .await
+      .map_err(|e| TuiError::Record(e.to_string()));
+  }
+
+  /// Gets the lines describing the selected segment.
+  fn body(&self) -> Vec<Line<'static>> {
+    let segment = self.current();
+    let bold = Style::new().bold();
+
+    let mut lines = vec![
+      Line::from(vec![
+        Span::styled("Pegasus review", bold),
+        Span::raw(format!(
+          " {}  segment {}/{}, {} pending",
+          self.title,
+          self.selected + 1,
+          self.review.segments.len(),
+          self.review.pending_count()
+        )),
+      ]),
+      Line::default(),
+      Line::styled("Original", bold),
+      self.original(),
+      Line::default(),
+      Line::styled("Suggestion", bold),
+    ];
+    match &segment.suggestion {
+      Suggestion::Pending => lines.push(Line::from("...".dim())),
+      Suggestion::Ready(text) => lines.extend(plain(text)),
+      Suggestion::Failed(message) => {
+        lines.push(Line::from(message.clone().red()));
+      }
+    }
+
+    lines.push(Line::default());
+    let decision = match &segment.decision {
+      Decision::Pending => "pending".dim(),
+      Decision::Accepted => "accepted".green(),
+      Decision::Rejected => "rejected".yellow(),
+      Decision::Edited(_) => "edited".green(),
+    };
+    lines.push(Line::from(vec![
+      Span::styled("Decision", bold),
+      Span::raw(" "),
+      decision,
+    ]));
+    if let Decision::Edited(text) = &segment.decision {
+      lines.extend(plain(text));
+    }
+    return lines;
+  }
+
+  /// Gets the original segment with words colored by confidence.
+  fn original(&self) -> Line<'static> {
+    let segment = &self.current().segment;
+    if segment.words.is_empty() {
+      return Line::raw(segment.text.trim().to_string());
+    }
+
+    let uncertain = self.threshold + (1.0 - self.threshold) / 2.0;
+    let mut spans = Vec::new();
+    for word in &segment.words {
+      let word_text = word.word.trim().to_string();
+      let span = if word.probability < self.threshold {
+        word_text.red()
+      } else if word.probability < uncertain {
+        word_text.yellow()
+      } else {
+        Span::raw(word_text)
+      };
+      if !spans.is_empty() {
+        spans.push(Span::raw(" "));
+      }
+      spans.push(span);
+    }
+    return Line::from(spans);
+  }
+}
+
+/// Renders the screen: the segment, then the help and status lines at the
+/// bottom.
+fn render(frame: &mut Frame, lines: Vec<Line>, status: String) {
+  let [body, help, status_area] = Layout::vertical([
+    Constraint::Min(0),
+    Constraint::Length(1),
+    Constraint::Length(1),
+  ])
+  .areas(frame.area());
+  frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), body);
+  frame.render_widget(Paragraph::new(HELP.dim()), help);
+  frame.render_widget(Paragraph::new(status), status_area);
+}
+
+/// Splits plain text into lines, keeping its paragraph breaks.
+fn plain(text: &str) -> Vec<Line<'static>> {
+  return text
+    .lines()
+    .map(|line| Line::raw(line.to_string()))
+    .collect();
+}
+
+/// Gets the default output path, `<name>.reviewed.txt` next to the input.
+fn default_output_path(file_path: &Path) -> PathBuf {
+  let stem = file_path
+    .file_stem()
+    .map(|stem| stem.to_string_lossy().to_string())
+    .unwrap_or_else(|| String::from("transcript"));
+  return file_path.with_file_name(format!("{}.reviewed.txt", stem));
+}
@@ -0,0 +1,248 @@
+//! Review state: per-segment suggestions and the user's decisions.
+
+use crate::input::sentences;
+use crate::input::transcription::{WhisperSegment, WhisperTranscription};
+
+/// The refined suggestion for a segment.
+#[derive(Debug, Clone)]
+pub enum Suggestion {
+  /// Not requested yet
+  Pending,
+  /// The refined text
+  Ready(String),
+  /// Refinement failed with the given message
+  Failed(String),
+}
+
+/// What the user decided for a segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+  /// Not reviewed yet; the original text is kept
+  Pending,
+  /// The suggestion is used
+  Accepted,
+  /// The original text is kept
+  Rejected,
+  /// The user's own text is used
+  Edited(String),
+}
+
+/// A segment under review.
+#[derive(Debug, Clone)]
+pub struct ReviewSegment {
+  /// The transcribed segment
+  pub segment: WhisperSegment,
+  /// The refined suggestion
+  pub suggestion: Suggestion,
+  /// The user's decision
+  pub decision: Decision,
+}
+
+impl ReviewSegment {
+  /// Gets the text this segment contributes to the result.
+  ///
+  /// # Returns
+  ///
+  /// The accepted suggestion, the edited text, or the original text.
+  pub fn final_text(&self) -> String {
+    return match (&self.decision, &self.suggestion) {
+      (Decision::Accepted, Suggestion::Ready(text)) => text.clone(),
+      (Decision::Edited(text), _) => text.clone(),
+      _ => self.segment.text.trim().to_string(),
+    };
+  }
+}
+
+/// A transcription under review.
+#[derive(Debug, Clone)]
+pub struct Review {
+  /// The segments in order
+  pub segments: Vec<ReviewSegment>,
+  language: Option<String>,
+  /// The whitespace between each segment and the next
+  separators: Vec<String>,
+}
+
+impl Review {
+  /// Creates a review of a transcription.
+  ///
+  /// A transcription without segments is reviewed sentence by sentence.
+  ///
+  /// # Arguments
+  ///
+  /// * `transcription` - The parsed Whisper transcription
+  ///
+  /// # Returns
+  ///
+  /// A new `Review` with every segment pending.
+  pub fn new(transcription: WhisperTranscription) -> Self {
+    let (segments, separators) = match transcription.segments {
+      Some(segments) if !segments.is_empty() => {
+        let separators = vec![String::from("\n"); segments.len() - 1];
+        (segments, separators)
+      }
+      _ => split_sentences(&transcription.text.clone().unwrap_or_default()),
+    };
+
+    return Review {
+      segments: segments
+        .into_iter()
+        .map(|segment| ReviewSegment {
+          segment,
+          suggestion: Suggestion::Pending,
+          decision: Decision::Pending,
+        })
+        .collect(),
+      language: transcription.language,
+      separators,
+    };
+  }
+
+  /// Builds a transcription holding a single segment, for refining it on
+  /// its own.
+  ///
+  /// # Arguments
+  ///
+  /// * `index` - The segment index
+  ///
+  /// # Returns
+  ///
+  /// A `WhisperTranscription` containing only that segment.
+  pub fn segment_transcription(&self, index: usize) -> WhisperTranscription {
+    let segment = self.segments[index].segment.clone();
+    return WhisperTranscription {
+      text: Some(segment.text.trim().to_string()),
+      language: self.language.clone(),
+      duration: None,
+      segments: Some(vec![segment]),
+    };
+  }
+
+  /// Counts the segments the user has not decided on yet.
+  ///
+  /// # Returns
+  ///
+  /// The number of pending segments.
+  pub fn pending_count(&self) -> usize {
+    return self
+      .segments
+      .iter()
+      .filter(|segment| segment.decision == Decision::Pending)
+      .count();
+  }
+
+  /// Gets the reviewed text, one segment per line, or the sentences of a
+  /// transcription without segments in their original paragraphs.
+  ///
+  /// # Returns
+  ///
+  /// The final text.
+  pub fn final_text(&self) -> String {
+    let mut text = String::new();
+    for (index, segment) in self.segments.iter().enumerate() {
+      if index > 0 {
+        text.push_str(&self.separators[index - 1]);
+      }
+      text.push_str(&segment.final_text());
+    }
+    return text;
+  }
+}
+
+/// Splits the text of a transcription without segments into one segment
+/// per sentence.
+///
+/// Lines are split on their own, so a line without final punctuation still
+/// ends its sentence.
+///
+/// # Returns
+///
+/// The segments, at least one, and the whitespace between them: a space
+/// within a paragraph, or the line breaks of the text.
+fn split_sentences(text: &str) -> (Vec<WhisperSegment>, Vec<String>) {
+  let mut starts = Vec::new();
+  let mut offset = 0;
+  for line in text.split_inclusive('\n') {
+    starts.extend(
+      sentences::sentence_starts(line)
+        .into_iter()
+        .map(|start| offset + start),
+    );
+    offset += line.len();
+  }
+  starts.push(text.len());
+
+  let mut segments = Vec::new();
+  let mut separators = Vec::new();
+  let mut previous_end = 0;
+  for range in starts.windows(2) {
+    let part = &text[range[0]..range[1]];
+    let sentence = part.trim();
+    if sentence.is_empty() {
+      continue;
+    }
+    let start = range[0] + part.len() - part.trim_start().len();
+    if !segments.is_empty() {
+      let breaks = text[previous_end..start].matches('\n').count();
+      separators.push(match breaks {
+        0 => String::from(" "),
+        _ => "\n".repeat(breaks),
+      });
+    }
+    previous_end = start + sentence.len();
+    segments.push(segment(sentence));
+  }
+  if segments.is_empty() {
+    segments.push(segment(""));
+  }
+  return (segments, separators);
+}
+
+/// Creates a segment without timestamps.
+fn segment(text: &str) -> WhisperSegment {
+  return WhisperSegment {
+    start: None,
+    end: None,
+    text: text.to_string(),
+    words: Vec::new(),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn review(text: &str) -> Review {
+    return Review::new(WhisperTranscription {
+      text: Some(text.to_string()),
+      language: None,
+      duration: None,
+      segments: None,
+    });
+  }
+
+  #[test]
+  fn reviews_a_text_sentence_by_sentence() {
+    let review = review("First one. Second one!\n\nA heading\nLast one.");
+    let texts: Vec<&str> = review
+      .segments
+      .iter()
+      .map(|segment| segment.segment.text.as_str())
+      .collect();
+    assert_eq!(
+      texts,
+      ["First one.", "Second one!", "A heading", "Last one."]
+    );
+  }
+
+  #[test]
+  fn keeps_the_paragraph_breaks_of_the_text() {
+    let mut edited = review("First one. Second one!\n\nA heading\nLast one.");
+    edited.segments[1].decision = Decision::Edited(String::from("Second."));
+    assert_eq!(
+      edited.final_text(),
+      "First one. Second.\n\nA heading\nLast one."
+    );
+    assert_eq!(review("").final_text(), "");
+  }
+}
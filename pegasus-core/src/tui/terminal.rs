@@ -0,0 +1,182 @@
+//! Terminal handling with crossterm, drawn with ratatui.
+
+use std::io::{IsTerminal, Stdout};
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, terminal};
+use ratatui::Frame;
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+
+use crate::tui::errors::{TuiError, TuiResult};
+
+/// A key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+  Up,
+  Down,
+  Char(char),
+  Interrupt,
+  /// Any other key, or a resize of the terminal
+  Other,
+}
+
+/// The terminal in raw mode on the alternate screen.
+///
+/// The original terminal settings are restored when it is dropped.
+pub struct RawTerminal {
+  terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl RawTerminal {
+  /// Switches the terminal to raw mode and the alternate screen.
+  ///
+  /// # Returns
+  ///
+  /// A `TuiResult<RawTerminal>` containing the raw terminal or an error.
+  pub fn enter() -> TuiResult<Self> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+      return Err(TuiError::NotATerminal);
+    }
+
+    let terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))
+      .map_err(terminal_error)?;
+    let mut terminal = RawTerminal { terminal };
+    terminal.resume()?;
+    return Ok(terminal);
+  }
+
+  /// Restores the normal terminal, e.g. while an editor runs.
+  ///
+  /// # Returns
+  ///
+  /// A `TuiResult<()>` indicating success or failure.
+  pub fn suspend(&mut self) -> TuiResult<()> {
+    execute!(
+      self.terminal.backend_mut(),
+      terminal::LeaveAlternateScreen,
+      cursor::Show
+    )
+    .map_err(terminal_error)?;
+    return terminal::disable_raw_mode().map_err(terminal_error);
+  }
+
+  /// Switches back to raw mode and the alternate screen.
+  ///
+  /// # Returns
+  ///
+  /// A `TuiResult<()>` indicating success or failure.
+  pub fn resume(&mut self) -> TuiResult<()> {
+    terminal::enable_raw_mode().map_err(terminal_error)?;
+    execute!(
+      self.terminal.backend_mut(),
+      terminal::EnterAlternateScreen,
+      cursor::Hide
+    )
+    .map_err(terminal_error)?;
+    // The screen is redrawn in full after an editor ran on it.
+    return self.terminal.clear().map_err(terminal_error);
+  }
+
+  /// Draws a frame.
+  ///
+  /// # Arguments
+  ///
+  /// * `render` - Renders the widgets of the frame
+  ///
+  /// # Returns
+  ///
+  /// A `TuiResult<()>` indicating success or failure.
+  pub fn draw(&mut self, render: impl FnOnce(&mut Frame)) -> TuiResult<()> {
+    self.terminal.draw(render).map_err(terminal_error)?;
+    return Ok(());
+  }
+
+  /// Waits for a key press on a blocking thread.
+  ///
+  /// # Returns
+  ///
+  /// A `TuiResult<Key>` containing the key or an error.
+  pub async fn read_key(&self) -> TuiResult<Key> {
+    return tokio::task::spawn_blocking(|| {
+      loop {
+        if let Some(key) = to_key(event::read().map_err(terminal_error)?) {
+          return Ok(key);
+        }
+      }
+    })
+    .await
+    .map_err(|e| TuiError::Terminal(e.to_string()))?;
+  }
+
+  /// Reads a key press that is already waiting, without blocking.
+  ///
+  /// # Returns
+  ///
+  /// A `TuiResult<Option<Key>>` containing the key, if one was pressed.
+  pub fn poll_key(&self) -> TuiResult<Option<Key>> {
+    while event::poll(Duration::ZERO).map_err(terminal_error)? {
+      if let Some(key) = to_key(event::read().map_err(terminal_error)?) {
+        return Ok(Some(key));
+      }
+    }
+    return Ok(None);
+  }
+
+  /// Discards the key presses waiting to be read.
+  ///
+  /// # Returns
+  ///
+  /// A `TuiResult<()>` indicating success or failure.
+  pub fn drain(&self) -> TuiResult<()> {
+    while self.poll_key()?.is_some() {}
+    return Ok(());
+  }
+}
+
+impl Drop for RawTerminal {
+  fn drop(&mut self) {
+    let _ = self.suspend();
+  }
+}
+
+/// Gets the size of the controlling terminal.
+///
+/// # Returns
+///
+/// The number of rows and columns, or 24x80 if unknown.
+pub fn size() -> (usize, usize) {
+  return match terminal::size() {
+    Ok((columns, rows)) if rows > 0 && columns > 0 => {
+      (rows as usize, columns as usize)
+    }
+    _ => (24, 80),
+  };
+}
+
+/// Gets the key of a terminal event.
+///
+/// Resizes are reported as [`Key::Other`] so the screen is redrawn; key
+/// releases and other events are skipped.
+fn to_key(event: Event) -> Option<Key> {
+  let key = match event {
+    Event::Key(key) if key.kind == KeyEventKind::Press => key,
+    Event::Resize(_, _) => return Some(Key::Other),
+    _ => return None,
+  };
+  return Some(match key.code {
+    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+      Key::Interrupt
+    }
+    KeyCode::Up => Key::Up,
+    KeyCode::Down => Key::Down,
+    KeyCode::Char(c) => Key::Char(c),
+    _ => Key::Other,
+  });
+}
+
+/// Wraps an I/O error of the terminal.
+fn terminal_error(e: std::io::Error) -> TuiError {
+  return TuiError::Terminal(e.to_string());
+}
@@ -0,0 +1,51 @@
+use thiserror::Error;
+
+use crate::app::errors::ErrorKind;
+use crate::t;
+
+/// Self-update errors.
+///
+/// Represents errors that keep the binary from being checked or replaced.
+#[derive(Error, Debug)]
+pub enum UpdateError {
+  #[error("{}", t!("error-update-fetch", error = .0.as_str()))]
+  Fetch(String),
+
+  #[error(
+    "{}",
+    t!("error-update-missing-asset", release = .0.as_str(), asset = .1.as_str())
+  )]
+  MissingAsset(String, String),
+
+  #[error("{}", t!("error-update-checksum-mismatch", asset = .0.as_str()))]
+  ChecksumMismatch(String),
+
+  #[error("{}", t!("error-update-invalid-signature", error = .0.as_str()))]
+  InvalidSignature(String),
+
+  #[error(
+    "{}",
+    t!("error-update-replace", path = .0.as_str(), error = .1.as_str())
+  )]
+  Replace(String, String),
+}
+
+impl UpdateError {
+  /// Gets the category of the error.
+  ///
+  /// # Returns
+  ///
+  /// The `ErrorKind` deciding the exit status.
+  pub fn kind(&self) -> ErrorKind {
+    return match self {
+      UpdateError::Fetch(_) => ErrorKind::Network,
+      UpdateError::MissingAsset(_, _)
+      | UpdateError::ChecksumMismatch(_)
+      | UpdateError::InvalidSignature(_)
+      | UpdateError::Replace(_, _) => ErrorKind::Other,
+    };
+  }
+}
+
+/// Result type for self-update operations.
+pub type UpdateResult<T> = Result<T, UpdateError>;
@@ -0,0 +1,339 @@
+//! Self-update from GitHub releases.
+//!
+//! `pegasus self-update` looks up the latest release of the repository and
+//! replaces the running binary with the release asset for this platform,
+//! named `pegasus-<arch>-<os>` (e.g. `pegasus-x86_64-linux`, with `.exe`
+//! on Windows). The release must carry a `SHA256SUMS` asset listing the
+//! checksum of every binary; a download that does not match is discarded.
+//!
+//! Builds made with `PEGASUS_UPDATE_PUBLIC_KEY` set to a hex-encoded
+//! Ed25519 public key also require a `SHA256SUMS.sig` asset holding the
+//! hex-encoded signature of `SHA256SUMS` made with the matching key.
+//! Without a built-in key, only the checksum is verified.
+//!
+//! ## Main Components
+//!
+//! - [`check`]: Compares the running version with the latest release
+//! - [`install`]: Downloads, verifies and installs a release
+//! - [`UpdateError`](errors::UpdateError): Error types for self-updates
+
+pub mod errors;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::clock;
+use crate::config::Config;
+use crate::network::HttpClient;
+use crate::update::errors::{UpdateError, UpdateResult};
+use crate::{elog, logging, vlog};
+
+/// Repository releases are looked up in.
+pub const REPOSITORY: &str = "MahanRahmati/pegasus";
+
+/// Base URL of the GitHub API.
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Name of the checksum asset.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// Name of the signature asset.
+const SIGNATURE_ASSET: &str = "SHA256SUMS.sig";
+
+/// Public key release checksums are signed with, if built in.
+const PUBLIC_KEY: Option<&str> = option_env!("PEGASUS_UPDATE_PUBLIC_KEY");
+
+/// A file attached to a release.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ReleaseAsset {
+  /// File name of the asset
+  pub name: String,
+  /// URL the asset is downloaded from
+  pub browser_download_url: String,
+}
+
+/// A GitHub release.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct Release {
+  tag_name: String,
+  assets: Vec<ReleaseAsset>,
+}
+
+/// Outcome of comparing the running version with the latest release.
+#[derive(Debug, Clone)]
+pub struct UpdateCheck {
+  /// Version of the running binary
+  pub current: String,
+  /// Version of the latest release, without a leading `v`
+  pub latest: String,
+  /// Tag of the latest release
+  pub tag: String,
+  /// Assets of the latest release
+  pub assets: Vec<ReleaseAsset>,
+}
+
+impl UpdateCheck {
+  /// Checks whether the latest release is newer than the running binary.
+  ///
+  /// # Returns
+  ///
+  /// `true` if an update is available.
+  pub fn is_newer(&self) -> bool {
+    return version_parts(&self.latest) > version_parts(&self.current);
+  }
+
+  /// Finds a release asset by name.
+  fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+    return self.assets.iter().find(|asset| asset.name == name);
+  }
+}
+
+/// Gets the name of the release asset for this platform.
+///
+/// # Returns
+///
+/// A `String` like `pegasus-x86_64-linux`.
+pub fn asset_name() -> String {
+  let name = format!(
+    "pegasus-{}-{}",
+    std::env::consts::ARCH,
+    std::env::consts::OS
+  );
+  if cfg!(windows) {
+    return format!("{}.exe", name);
+  }
+  return name;
+}
+
+/// Looks up the latest release.
+///
+/// # Arguments
+///
+/// * `config` - Configuration with the proxy settings
+///
+/// # Returns
+///
+/// An `UpdateResult<UpdateCheck>` comparing the versions, or an error if
+/// the release cannot be fetched.
+pub async fn check(config: &Config) -> UpdateResult<UpdateCheck> {
+  let client = create_client(config, String::from(GITHUB_API_URL))?;
+  let endpoint = format!("repos/{}/releases/latest", REPOSITORY);
+  let release: Release = client
+    .fetch_json(&endpoint, None, Some(headers()))
+    .await
+    .map_err(|e| UpdateError::Fetch(e.to_string()))?;
+
+  let check = UpdateCheck {
+    current: env!("CARGO_PKG_VERSION").to_string(),
+    latest: release.tag_name.trim_start_matches('v').to_string(),
+    tag: release.tag_name,
+    assets: release.assets,
+  };
+  vlog!(
+    "Latest release is {}, running {}",
+    check.latest,
+    check.current
+  );
+  return Ok(check);
+}
+
+/// Downloads the release for this platform, verifies it and replaces the
+/// running binary with it.
+///
+/// The new binary is written next to the running one and renamed over
+/// it, so an interrupted update leaves the old binary in place.
+///
+/// # Arguments
+///
+/// * `config` - Configuration with the proxy settings
+/// * `check` - The release to install, from [`check`]
+///
+/// # Returns
+///
+/// An `UpdateResult<PathBuf>` with the path of the replaced binary, or an
+/// error if an asset is missing, verification fails or the binary cannot
+/// be replaced.
+pub async fn install(
+  config: &Config,
+  check: &UpdateCheck,
+) -> UpdateResult<PathBuf> {
+  let name = asset_name();
+  let missing = |asset: &str| {
+    return UpdateError::MissingAsset(check.tag.clone(), asset.to_string());
+  };
+  let binary_asset = check.asset(&name).ok_or_else(|| missing(&name))?;
+  let checksums_asset = check
+    .asset(CHECKSUMS_ASSET)
+    .ok_or_else(|| missing(CHECKSUMS_ASSET))?;
+
+  let checksums = download(config, checksums_asset).await?;
+  match PUBLIC_KEY {
+    Some(public_key) => {
+      let signature_asset = check
+        .asset(SIGNATURE_ASSET)
+        .ok_or_else(|| missing(SIGNATURE_ASSET))?;
+      let signature = download(config, signature_asset).await?;
+      verify_signature(public_key, &checksums, &signature)?;
+      vlog!("Signature of {} is valid", CHECKSUMS_ASSET);
+    }
+    None => elog!(
+      logging::WARNING,
+      "This build has no release signing key; verifying the checksum only"
+    ),
+  }
+
+  let binary = download(config, binary_asset).await?;
+  verify_checksum(&name, &binary, &String::from_utf8_lossy(&checksums))?;
+  vlog!("Checksum of {} matches", name);
+
+  let executable = std::env::current_exe()
+    .and_then(|path| path.canonicalize())
+    .map_err(|e| UpdateError::Replace(String::from("?"), e.to_string()))?;
+  replace_binary(&executable, &binary).await?;
+  return Ok(executable);
+}
+
+/// Creates an HTTP client honoring the proxy settings.
+fn create_client(config: &Config, url: String) -> UpdateResult<HttpClient> {
+  let client =
+    HttpClient::new(url).map_err(|e| UpdateError::Fetch(e.to_string()))?;
+  let proxy = config.get_proxy();
+  if proxy.is_empty() {
+    return Ok(client);
+  }
+  return client
+    .with_proxy(&proxy, &config.get_no_proxy())
+    .map_err(|e| UpdateError::Fetch(e.to_string()));
+}
+
+/// Gets the headers GitHub expects from API clients.
+fn headers() -> HashMap<String, String> {
+  return HashMap::from([
+    (
+      String::from("User-Agent"),
+      format!("pegasus/{}", env!("CARGO_PKG_VERSION")),
+    ),
+    (
+      String::from("Accept"),
+      String::from("application/vnd.github+json"),
+    ),
+  ]);
+}
+
+/// Downloads a release asset.
+async fn download(
+  config: &Config,
+  asset: &ReleaseAsset,
+) -> UpdateResult<Vec<u8>> {
+  vlog!("Downloading {}", asset.name);
+  let client = create_client(config, asset.browser_download_url.clone())?;
+  return client
+    .fetch_bytes("", Some(headers()))
+    .await
+    .map_err(|e| UpdateError::Fetch(format!("{}: {}", asset.name, e)));
+}
+
+/// Checks a download against its line in `SHA256SUMS`, written as
+/// `<hex digest>  <file name>` by `sha256sum`.
+fn verify_checksum(
+  name: &str,
+  content: &[u8],
+  checksums: &str,
+) -> UpdateResult<()> {
+  let expected = checksums.lines().find_map(|line| {
+    let (digest, file) = line.split_once(char::is_whitespace)?;
+    let file = file.trim_start().trim_start_matches('*');
+    return (file == name).then(|| digest.to_lowercase());
+  });
+  let Some(expected) = expected else {
+    return Err(UpdateError::ChecksumMismatch(name.to_string()));
+  };
+  let actual: String = Sha256::digest(content)
+    .iter()
+    .map(|byte| format!("{:02x}", byte))
+    .collect();
+  if actual != expected {
+    return Err(UpdateError::ChecksumMismatch(name.to_string()));
+  }
+  return Ok(());
+}
+
+/// Checks the Ed25519 signature of `SHA256SUMS`.
+fn verify_signature(
+  public_key: &str,
+  checksums: &[u8],
+  signature: &[u8],
+) -> UpdateResult<()> {
+  let invalid = |reason: &str| {
+    return UpdateError::InvalidSignature(reason.to_string());
+  };
+  let public_key =
+    decode_hex(public_key).ok_or_else(|| invalid("malformed public key"))?;
+  let signature = decode_hex(&String::from_utf8_lossy(signature))
+    .ok_or_else(|| invalid("malformed signature"))?;
+  return ring::signature::UnparsedPublicKey::new(
+    &ring::signature::ED25519,
+    public_key,
+  )
+  .verify(checksums, &signature)
+  .map_err(|_| invalid("it was not made with the release signing key"));
+}
+
+/// Decodes a hex string, ignoring surrounding whitespace.
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+  let text = text.trim();
+  if !text.len().is_multiple_of(2) {
+    return None;
+  }
+  return (0..text.len())
+    .step_by(2)
+    .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+    .collect();
+}
+
+/// Writes the new binary next to the old one and renames it over it.
+async fn replace_binary(executable: &Path, binary: &[u8]) -> UpdateResult<()> {
+  let display = executable.display().to_string();
+  let failed =
+    |e: std::io::Error| UpdateError::Replace(display.clone(), e.to_string());
+  let mut staged = executable.as_os_str().to_owned();
+  staged.push(format!(
+    ".update.{}-{:x}",
+    std::process::id(),
+    clock::random() as u32
+  ));
+  let staged = PathBuf::from(staged);
+
+  let result = async {
+    tokio::fs::write(&staged, binary).await?;
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+
+      let permissions = std::fs::Permissions::from_mode(0o755);
+      tokio::fs::set_permissions(&staged, permissions).await?;
+    }
+    return tokio::fs::rename(&staged, executable).await;
+  }
+  .await;
+
+  if let Err(e) = result {
+    let _ = tokio::fs::remove_file(&staged).await;
+    return Err(failed(e));
+  }
+  return Ok(());
+}
+
+/// Splits a version into its numeric parts for comparison, so `0.10.0`
+/// sorts after `0.9.0`. Pre-release suffixes are ignored.
+fn version_parts(version: &str) -> Vec<u64> {
+  return version
+    .split(['-', '+'])
+    .next()
+    .unwrap_or_default()
+    .split('.')
+    .map(|part| part.parse().unwrap_or(0))
+    .collect();
+}
@@ -0,0 +1,212 @@
+//! Anonymous run counters, kept only with `[usage] counters = true`.
+//!
+//! Self-hosting teams can see how much Pegasus is used without any
+//! telemetry: the counters are kept in `$XDG_STATE_HOME/pegasus/
+//! counters.json` and never leave the machine. Unlike the usage records,
+//! they hold no texts, tokens or timestamps of single runs, only how many
+//! runs there were, which models they used and the categories of the
+//! failures, and they count every run, including those that failed before
+//! a request was sent.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Local};
+use xdg::BaseDirectories;
+
+use crate::app::errors::{ErrorKind, RuntimeError};
+use crate::bench::errors::BenchError;
+use crate::clock;
+use crate::config::Config;
+use crate::files::operations;
+use crate::output::format::OutputFormat;
+use crate::queue::JobStatus;
+use crate::usage::SERVER_DEFAULT_MODEL;
+use crate::usage::errors::{UsageError, UsageResult};
+use crate::{elog, logging};
+
+const STATE_DIRECTORY: &str = "pegasus";
+const COUNTERS_FILE: &str = "counters.json";
+
+/// The outcome of a run, as far as the counters are concerned.
+pub trait Outcome {
+  /// Gets the category of the failure of the run.
+  ///
+  /// # Returns
+  ///
+  /// The category, or `None` if the run succeeded.
+  fn failure(&self) -> Option<ErrorKind>;
+}
+
+impl Outcome for () {
+  fn failure(&self) -> Option<ErrorKind> {
+    return None;
+  }
+}
+
+/// Whether a unit of line mode was refined.
+impl Outcome for bool {
+  fn failure(&self) -> Option<ErrorKind> {
+    return (!self).then_some(ErrorKind::Other);
+  }
+}
+
+impl<T> Outcome for Result<T, RuntimeError> {
+  fn failure(&self) -> Option<ErrorKind> {
+    return self.as_ref().err().map(RuntimeError::kind);
+  }
+}
+
+impl<T> Outcome for Result<T, BenchError> {
+  fn failure(&self) -> Option<ErrorKind> {
+    return self.as_ref().err().map(BenchError::kind);
+  }
+}
+
+/// A queue job, failed with its status and message.
+impl<T> Outcome for Result<T, (JobStatus, String)> {
+  fn failure(&self) -> Option<ErrorKind> {
+    return self.as_ref().err().map(|(status, _)| match status {
+      JobStatus::Refused => ErrorKind::Refusal,
+      _ => ErrorKind::Other,
+    });
+  }
+}
+
+/// A server request, failed with its JSON-RPC error code and message.
+impl<T> Outcome for Result<T, (i64, String)> {
+  fn failure(&self) -> Option<ErrorKind> {
+    return self.as_ref().err().map(|_| ErrorKind::Other);
+  }
+}
+
+/// Runs counted since the counters were started.
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RunCounters {
+  /// When the first run was counted, as a Unix timestamp
+  pub since: i64,
+  /// Number of runs
+  pub runs: u64,
+  /// Number of runs that failed
+  pub failed: u64,
+  /// Runs per model
+  pub models: BTreeMap<String, u64>,
+  /// Failed runs per category
+  pub failures: BTreeMap<String, u64>,
+}
+
+/// Counts a run, if the counters are enabled.
+///
+/// Counters that cannot be updated are logged and otherwise ignored, like
+/// usage records.
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the run
+/// * `failure` - The category of the failure, or `None` if the run
+///   succeeded
+pub(crate) async fn count(config: &Config, failure: Option<ErrorKind>) {
+  if !config.get_usage_counters() {
+    return;
+  }
+  let mut model = config.get_llm_model();
+  if model.is_empty() {
+    model = String::from(SERVER_DEFAULT_MODEL);
+  }
+  let update = async {
+    let path = path()?;
+    let file = path.to_string_lossy().to_string();
+    let _lock = operations::lock_file(&file)
+      .await
+      .map_err(|e| UsageError::Write(e.to_string()))?;
+    let mut counters = load().await?.unwrap_or_else(|| RunCounters {
+      since: clock::timestamp(),
+      ..RunCounters::default()
+    });
+    counters.runs += 1;
+    *counters.models.entry(model).or_default() += 1;
+    if let Some(kind) = failure {
+      counters.failed += 1;
+      *counters
+        .failures
+        .entry(kind.name().to_string())
+        .or_default() += 1;
+    }
+    let content = serde_json::to_string_pretty(&counters)
+      .map_err(|e| UsageError::Write(e.to_string()))?;
+    return operations::write_string_atomic(&file, &content)
+      .await
+      .map_err(|e| UsageError::Write(e.to_string()));
+  };
+  if let Err(e) = update.await {
+    elog!(logging::WARNING, "Failed to count the run: {}", e);
+  }
+}
+
+/// Reads the run counters.
+///
+/// # Returns
+///
+/// A `UsageResult<Option<RunCounters>>` containing the counters, `None` if
+/// no run was counted yet, or an error if the counters cannot be read.
+pub async fn load() -> UsageResult<Option<RunCounters>> {
+  let path = path()?;
+  let content = match tokio::fs::read_to_string(&path).await {
+    Ok(content) => content,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+    Err(e) => {
+      return Err(UsageError::Read(path.display().to_string(), e.to_string()));
+    }
+  };
+  return serde_json::from_str(&content)
+    .map(Some)
+    .map_err(|e| UsageError::Read(path.display().to_string(), e.to_string()));
+}
+
+/// Formats the run counters.
+///
+/// # Arguments
+///
+/// * `counters` - The counters, or `None` if no run was counted
+/// * `format` - The desired output format
+///
+/// # Returns
+///
+/// The runs, models and failure categories as text, or a JSON object
+/// (`null` if no run was counted).
+pub fn format_counters(
+  counters: Option<&RunCounters>,
+  format: OutputFormat,
+) -> String {
+  if format == OutputFormat::Json {
+    return serde_json::to_string(&counters).unwrap_or_default();
+  }
+  let Some(counters) = counters else {
+    return String::from(
+      "No runs counted. Set `[usage] counters = true` to count them.",
+    );
+  };
+  let since = DateTime::from_timestamp(counters.since, 0)
+    .map(|time| time.with_timezone(&Local).format("%Y-%m-%d").to_string())
+    .unwrap_or_default();
+  let list = |counts: &BTreeMap<String, u64>| {
+    return counts
+      .iter()
+      .map(|(name, count)| format!("  {:<24} {:>8}", name, count))
+      .collect::<Vec<_>>();
+  };
+  let mut lines = vec![
+    format!("Runs since {}: {}", since, counters.runs),
+    String::from("Models:"),
+  ];
+  lines.extend(list(&counters.models));
+  lines.push(format!("Failed: {}", counters.failed));
+  lines.extend(list(&counters.failures));
+  return lines.join("\n");
+}
+
+/// Gets the path of the counters file, creating its directory.
+fn path() -> UsageResult<std::path::PathBuf> {
+  return BaseDirectories::with_prefix(STATE_DIRECTORY)
+    .place_state_file(COUNTERS_FILE)
+    .map_err(|e| UsageError::Location(e.to_string()));
+}
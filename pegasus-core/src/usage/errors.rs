@@ -0,0 +1,24 @@
+use thiserror::Error;
+
+use crate::t;
+
+/// Usage statistics errors.
+///
+/// Represents errors that occur while recording or reading runs.
+#[derive(Error, Debug)]
+pub enum UsageError {
+  #[error("{}", t!("error-usage-location", error = .0.as_str()))]
+  Location(String),
+
+  #[error(
+    "{}",
+    t!("error-usage-read", path = .0.as_str(), error = .1.as_str())
+  )]
+  Read(String, String),
+
+  #[error("{0}")]
+  Write(String),
+}
+
+/// Result type for usage statistics operations.
+pub type UsageResult<T> = Result<T, UsageError>;
@@ -0,0 +1,556 @@
+//! Usage statistics of refinement runs.
+//!
+//! Every run that sends requests to the LLM service appends a record to
+//! `$XDG_STATE_HOME/pegasus/usage.jsonl`: the tokens the service reported,
+//! the cost estimated from `[llm] input_price` and `output_price`, how
+//! long the run took and how many words were refined. `pegasus usage` adds
+//! the records up per model and `[usage] profile`, optionally per month. A
+//! run is one CLI invocation, or one request to the daemon or `--stdio`
+//! server.
+//!
+//! The daemon and CLI invocations may record runs at the same time, so
+//! every change of the usage file is made under its lock: records are
+//! appended one line at a time, and [`compact`] and [`prune`] rewrite the
+//! file atomically, never losing a record appended meanwhile.
+//!
+//! Like request IDs, the counts of a run are held in a task-local set up by
+//! [`track`], so the LLM client records them wherever requests are made
+//! without passing a counter through function signatures.
+//!
+//! ## Main Components
+//!
+//! - [`track`]: Runs a future and records its usage
+//! - [`UsageRecord`]: The usage of one run
+//! - [`load`]: Reads the recorded runs
+//! - [`summarize`]: Adds up runs per model and profile
+//! - [`compact`] and [`prune`]: Maintenance of the usage file
+//! - [`counters`]: Opt-in anonymous counts of runs, models and failures
+//! - [`UsageError`](errors::UsageError): Error types for usage statistics
+
+pub mod counters;
+pub mod errors;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use chrono::{DateTime, Local, NaiveDate};
+use xdg::BaseDirectories;
+
+use crate::clock;
+use crate::config::Config;
+use crate::files::operations;
+use crate::logging;
+use crate::output::format::OutputFormat;
+use crate::usage::counters::Outcome;
+use crate::usage::errors::{UsageError, UsageResult};
+use crate::{elog, vlog};
+
+const STATE_DIRECTORY: &str = "pegasus";
+const USAGE_FILE: &str = "usage.jsonl";
+
+/// Name of the model in reports when none is configured.
+pub(crate) const SERVER_DEFAULT_MODEL: &str = "(server default)";
+
+tokio::task_local! {
+  static RUN: RefCell<RunUsage>;
+}
+
+/// Tokens and estimated cost of the run in progress, for run reports.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize)]
+pub struct RunCost {
+  /// Requests sent to the LLM service
+  pub requests: u64,
+  /// Prompt tokens reported by the LLM service
+  pub prompt_tokens: u64,
+  /// Completion tokens reported by the LLM service
+  pub completion_tokens: u64,
+  /// Estimated cost in the currency of the configured prices
+  pub cost: f64,
+}
+
+/// Counts of the run in progress.
+#[derive(Debug, Default, Clone, Copy)]
+struct RunUsage {
+  requests: u64,
+  prompt_tokens: u64,
+  completion_tokens: u64,
+  input_words: u64,
+  output_words: u64,
+}
+
+/// The usage of one run.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct UsageRecord {
+  /// When the run finished, as a Unix timestamp
+  pub timestamp: i64,
+  /// The command or server method of the run
+  pub command: String,
+  /// The model requests were sent to (empty for the server default)
+  pub model: String,
+  /// The `[usage] profile` of the run
+  pub profile: String,
+  /// Requests sent to the LLM service
+  pub requests: u64,
+  /// Prompt tokens reported by the LLM service
+  pub prompt_tokens: u64,
+  /// Completion tokens reported by the LLM service
+  pub completion_tokens: u64,
+  /// Estimated cost in the currency of the configured prices
+  pub cost: f64,
+  /// How long the run took in seconds
+  pub seconds: f64,
+  /// Words of the refined input
+  pub input_words: u64,
+  /// Words of the refined output
+  pub output_words: u64,
+}
+
+/// Runs added up per model and profile.
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct UsageTotals {
+  /// The month of the runs (`YYYY-MM`), if totals are per month
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub month: Option<String>,
+  /// The model requests were sent to
+  pub model: String,
+  /// The profile of the runs
+  pub profile: String,
+  /// Number of runs
+  pub runs: u64,
+  /// Requests sent to the LLM service
+  pub requests: u64,
+  /// Prompt tokens reported by the LLM service
+  pub prompt_tokens: u64,
+  /// Completion tokens reported by the LLM service
+  pub completion_tokens: u64,
+  /// Estimated cost
+  pub cost: f64,
+  /// How long the runs took in seconds
+  pub seconds: f64,
+  /// Words of the refined inputs
+  pub input_words: u64,
+  /// Words of the refined outputs
+  pub output_words: u64,
+}
+
+impl UsageTotals {
+  /// Adds a run to the totals.
+  fn add(&mut self, record: &UsageRecord) {
+    self.runs += 1;
+    self.requests += record.requests;
+    self.prompt_tokens += record.prompt_tokens;
+    self.completion_tokens += record.completion_tokens;
+    self.cost += record.cost;
+    self.seconds += record.seconds;
+    self.input_words += record.input_words;
+    self.output_words += record.output_words;
+  }
+}
+
+/// Runs a future as one run, recording its usage when it completes.
+///
+/// The counts of the run are kept either way, for [`current`]; nothing is
+/// recorded when `[usage] enabled` is off or the run sent no requests to
+/// the LLM service. A record that cannot be written is logged
+/// and otherwise ignored, so usage statistics never fail a refinement.
+/// With `[usage] counters` on, the run and its outcome are also counted
+/// (see [`counters`]).
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the run
+/// * `command` - The command or server method of the run
+/// * `future` - The run
+///
+/// # Returns
+///
+/// The output of the future.
+pub async fn track<F>(config: &Config, command: &str, future: F) -> F::Output
+where
+  F: Future,
+  F::Output: Outcome,
+{
+  let started = Instant::now();
+  let (output, usage) = RUN
+    .scope(RefCell::new(RunUsage::default()), async {
+      let output = future.await;
+      return (output, RUN.with(|run| *run.borrow()));
+    })
+    .await;
+  counters::count(config, output.failure()).await;
+  if !config.get_usage_enabled() || usage.requests == 0 {
+    return output;
+  }
+
+  let record = UsageRecord {
+    timestamp: clock::timestamp(),
+    command: command.to_string(),
+    model: config.get_llm_model(),
+    profile: config.get_usage_profile(),
+    requests: usage.requests,
+    prompt_tokens: usage.prompt_tokens,
+    completion_tokens: usage.completion_tokens,
+    cost: estimate_cost(config, &usage),
+    seconds: started.elapsed().as_secs_f64(),
+    input_words: usage.input_words,
+    output_words: usage.output_words,
+  };
+  if let Err(e) = append(&record).await {
+    elog!(logging::WARNING, "Failed to record usage: {}", e);
+  }
+  return output;
+}
+
+/// Gets the tokens and estimated cost of the run in progress.
+///
+/// # Arguments
+///
+/// * `config` - The configuration with the prices of the run
+///
+/// # Returns
+///
+/// The counts so far, or `None` outside of a run set up by [`track`].
+pub fn current(config: &Config) -> Option<RunCost> {
+  let usage = RUN.try_with(|run| *run.borrow()).ok()?;
+  return Some(RunCost {
+    requests: usage.requests,
+    prompt_tokens: usage.prompt_tokens,
+    completion_tokens: usage.completion_tokens,
+    cost: estimate_cost(config, &usage),
+  });
+}
+
+/// Estimates the cost of a run from the configured prices per million
+/// tokens.
+fn estimate_cost(config: &Config, usage: &RunUsage) -> f64 {
+  let prompt_cost =
+    usage.prompt_tokens as f64 * config.get_llm_input_price() / 1_000_000.0;
+  let completion_cost = usage.completion_tokens as f64
+    * config.get_llm_output_price()
+    / 1_000_000.0;
+  return prompt_cost + completion_cost;
+}
+
+/// Records a request to the LLM service in the current run.
+///
+/// # Arguments
+///
+/// * `prompt_tokens` - Tokens in the prompt (0 if not reported)
+/// * `completion_tokens` - Tokens in the completion (0 if not reported)
+pub fn record_request(prompt_tokens: u64, completion_tokens: u64) {
+  let _ = RUN.try_with(|run| {
+    let mut run = run.borrow_mut();
+    run.requests += 1;
+    run.prompt_tokens += prompt_tokens;
+    run.completion_tokens += completion_tokens;
+  });
+}
+
+/// Records a refined text in the current run.
+///
+/// # Arguments
+///
+/// * `input` - The text sent for refinement
+/// * `output` - The refined text
+pub fn record_words(input: &str, output: &str) {
+  let _ = RUN.try_with(|run| {
+    let mut run = run.borrow_mut();
+    run.input_words += input.split_whitespace().count() as u64;
+    run.output_words += output.split_whitespace().count() as u64;
+  });
+}
+
+/// Gets the path of the usage file, creating its directory.
+///
+/// # Returns
+///
+/// A `UsageResult<PathBuf>` containing the path, or an error if the state
+/// directory cannot be created.
+pub fn path() -> UsageResult<PathBuf> {
+  return BaseDirectories::with_prefix(STATE_DIRECTORY)
+    .place_state_file(USAGE_FILE)
+    .map_err(|e| UsageError::Location(e.to_string()));
+}
+
+/// Reads the recorded runs.
+///
+/// Lines that cannot be parsed, such as one cut short by a full disk, are
+/// skipped with a warning.
+///
+/// # Arguments
+///
+/// * `since` - Only read runs from this Unix timestamp on (see
+///   [`start_of_day`])
+///
+/// # Returns
+///
+/// A `UsageResult<Vec<UsageRecord>>` containing the runs in the order they
+/// finished, or an error if the usage file cannot be read.
+pub async fn load(since: Option<i64>) -> UsageResult<Vec<UsageRecord>> {
+  let path = path()?;
+  let content = match tokio::fs::read_to_string(&path).await {
+    Ok(content) => content,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+    Err(e) => {
+      return Err(UsageError::Read(path.display().to_string(), e.to_string()));
+    }
+  };
+
+  let since = since.unwrap_or(i64::MIN);
+  let mut records = Vec::new();
+  for (number, line) in content.lines().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    match serde_json::from_str::<UsageRecord>(line) {
+      Ok(record) if record.timestamp >= since => records.push(record),
+      Ok(_) => {}
+      Err(e) => elog!(
+        logging::WARNING,
+        "Skipping line {} of {}: {}",
+        number + 1,
+        path.display(),
+        e
+      ),
+    }
+  }
+  vlog!("Read {} runs from {}", records.len(), path.display());
+  return Ok(records);
+}
+
+/// Gets the start of a day in local time.
+///
+/// # Arguments
+///
+/// * `date` - The day as `YYYY-MM-DD`
+///
+/// # Returns
+///
+/// The Unix timestamp of midnight of the day, or `None` if the date is
+/// malformed.
+pub fn start_of_day(date: &str) -> Option<i64> {
+  return NaiveDate::parse_from_str(date, "%Y-%m-%d")
+    .ok()?
+    .and_hms_opt(0, 0, 0)?
+    .and_local_timezone(Local)
+    .earliest()
+    .map(|time| time.timestamp());
+}
+
+/// Adds up runs per model and profile.
+///
+/// # Arguments
+///
+/// * `records` - The runs
+/// * `monthly` - Whether to keep the months of the runs apart, by local
+///   time
+///
+/// # Returns
+///
+/// The totals, sorted by month, model and profile.
+pub fn summarize(records: &[UsageRecord], monthly: bool) -> Vec<UsageTotals> {
+  let mut totals: BTreeMap<(Option<String>, String, String), UsageTotals> =
+    BTreeMap::new();
+  for record in records {
+    let month = if monthly {
+      DateTime::from_timestamp(record.timestamp, 0)
+        .map(|time| time.with_timezone(&Local).format("%Y-%m").to_string())
+    } else {
+      None
+    };
+    let key = (month, record.model.clone(), record.profile.clone());
+    totals
+      .entry(key.clone())
+      .or_insert_with(|| UsageTotals {
+        month: key.0,
+        model: key.1,
+        profile: key.2,
+        ..UsageTotals::default()
+      })
+      .add(record);
+  }
+  return totals.into_values().collect();
+}
+
+/// Formats usage totals.
+///
+/// # Arguments
+///
+/// * `totals` - The totals per model and profile
+/// * `format` - The desired output format
+///
+/// # Returns
+///
+/// A table of the totals with a grand total, or a JSON array.
+pub fn format_report(totals: &[UsageTotals], format: OutputFormat) -> String {
+  if format == OutputFormat::Json {
+    return serde_json::to_string(totals).unwrap_or_default();
+  }
+  if totals.is_empty() {
+    return String::from("No runs recorded.");
+  }
+
+  let mut grand_total = UsageTotals {
+    model: String::from("Total"),
+    ..UsageTotals::default()
+  };
+  for total in totals {
+    grand_total.runs += total.runs;
+    grand_total.requests += total.requests;
+    grand_total.prompt_tokens += total.prompt_tokens;
+    grand_total.completion_tokens += total.completion_tokens;
+    grand_total.cost += total.cost;
+    grand_total.seconds += total.seconds;
+    grand_total.input_words += total.input_words;
+    grand_total.output_words += total.output_words;
+  }
+
+  let monthly = totals.iter().any(|total| total.month.is_some());
+  let mut rows = vec![
+    [
+      "Month",
+      "Model",
+      "Profile",
+      "Runs",
+      "Prompt",
+      "Completion",
+      "Cost",
+      "Time",
+      "Words",
+    ]
+    .map(String::from),
+  ];
+  for total in totals.iter().chain(std::iter::once(&grand_total)) {
+    let model = if total.model.is_empty() {
+      SERVER_DEFAULT_MODEL
+    } else {
+      &total.model
+    };
+    rows.push([
+      total.month.clone().unwrap_or_default(),
+      model.to_string(),
+      total.profile.clone(),
+      total.runs.to_string(),
+      total.prompt_tokens.to_string(),
+      total.completion_tokens.to_string(),
+      format!("{:.4}", total.cost),
+      format!("{:.1}s", total.seconds),
+      total.input_words.to_string(),
+    ]);
+  }
+
+  let skip = if monthly { 0 } else { 1 };
+  let widths: Vec<usize> = (0..rows[0].len())
+    .map(|column| {
+      return rows
+        .iter()
+        .map(|row| row[column].chars().count())
+        .max()
+        .unwrap_or_default();
+    })
+    .collect();
+  return rows
+    .iter()
+    .map(|row| {
+      return row
+        .iter()
+        .zip(&widths)
+        .enumerate()
+        .skip(skip)
+        .map(|(column, (cell, width))| {
+          // Text columns are left-aligned, numbers right-aligned.
+          return if column < 3 {
+            format!("{:<width$}", cell)
+          } else {
+            format!("{:>width$}", cell)
+          };
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+        .trim_end()
+        .to_string();
+    })
+    .collect::<Vec<_>>()
+    .join("\n");
+}
+
+/// Rewrites the usage file without the lines that cannot be parsed, such
+/// as ones cut short by a full disk.
+///
+/// # Returns
+///
+/// A `UsageResult<usize>` containing the number of lines removed, or an
+/// error if the usage file cannot be read or written.
+pub async fn compact() -> UsageResult<usize> {
+  return rewrite(|_| true).await;
+}
+
+/// Removes the runs older than an age, and the lines that cannot be
+/// parsed.
+///
+/// # Arguments
+///
+/// * `age` - How long ago, in seconds, the runs to keep finished at most
+///
+/// # Returns
+///
+/// A `UsageResult<usize>` containing the number of lines removed, or an
+/// error if the usage file cannot be read or written.
+pub async fn prune(age: i64) -> UsageResult<usize> {
+  let before = clock::timestamp() - age;
+  return rewrite(|record| record.timestamp >= before).await;
+}
+
+/// Rewrites the usage file under its lock, keeping the runs that `keep`
+/// accepts.
+async fn rewrite(keep: impl Fn(&UsageRecord) -> bool) -> UsageResult<usize> {
+  let path = path()?;
+  let file = path.to_string_lossy().to_string();
+  let _lock = operations::lock_file(&file)
+    .await
+    .map_err(|e| UsageError::Write(e.to_string()))?;
+  let content = match tokio::fs::read_to_string(&path).await {
+    Ok(content) => content,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+    Err(e) => return Err(UsageError::Read(file, e.to_string())),
+  };
+
+  let mut kept = String::new();
+  let mut removed = 0;
+  for line in content.lines().filter(|line| !line.trim().is_empty()) {
+    match serde_json::from_str::<UsageRecord>(line) {
+      Ok(record) if keep(&record) => {
+        kept.push_str(line);
+        kept.push('\n');
+      }
+      _ => removed += 1,
+    }
+  }
+  if removed > 0 {
+    operations::write_string_atomic(&file, &kept)
+      .await
+      .map_err(|e| UsageError::Write(e.to_string()))?;
+  }
+  vlog!("Removed {} lines from {}", removed, file);
+  return Ok(removed);
+}
+
+/// Appends a run to the usage file.
+async fn append(record: &UsageRecord) -> UsageResult<()> {
+  let path = path()?;
+  let file = path.to_string_lossy();
+  let line = serde_json::to_string(record)
+    .map_err(|e| UsageError::Write(e.to_string()))?;
+  vlog!("Recording usage in {}", path.display());
+  // Without the lock, a run appended while the file is being rewritten
+  // would be lost with the replaced file.
+  let _lock = operations::lock_file(&file)
+    .await
+    .map_err(|e| UsageError::Write(e.to_string()))?;
+  return operations::append_string(&file, &format!("{}\n", line))
+    .await
+    .map_err(|e| UsageError::Write(e.to_string()));
+}
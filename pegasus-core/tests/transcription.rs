@@ -0,0 +1,237 @@
+//! Property-based tests of the transcription parsers.
+//!
+//! The generators build SRT, WebVTT and Whisper JSON documents that are
+//! well-formed, truncated at any point or filled with malformed segment
+//! data, and the properties check that parsing and everything run on a
+//! parsed transcription reject such input with an error instead of
+//! panicking. The fuzz targets in `fuzz/` feed the same entry points with
+//! arbitrary bytes.
+
+use proptest::prelude::*;
+use serde_json::{Value, json};
+
+use pegasus_core::input::transcript_format::TranscriptFormat;
+use pegasus_core::input::transcription::WhisperTranscription;
+use pegasus_core::input::validation;
+
+/// Runs everything the pipeline does with a parsed transcription.
+fn exercise(mut transcription: WhisperTranscription) {
+  let _ = validation::check_transcription(&transcription);
+  let _ = transcription.timeline_issues(1.0);
+  let _ = transcription.confidence_summary(0.5);
+  let _ = transcription.get_low_probability_words(0.5);
+  let _ = transcription.word_count();
+  let _ = transcription.full_text();
+  let _ = transcription.collapse_repeated_segments();
+}
+
+/// Parses content in every format, and in the detected one.
+fn parse_all(content: &str) {
+  for format in TranscriptFormat::ALL {
+    if let Ok(transcription) = format.parse(content) {
+      exercise(transcription);
+    }
+  }
+  if let Ok(transcription) = TranscriptFormat::detect(content).parse(content) {
+    exercise(transcription);
+  }
+}
+
+/// Formats seconds as an SRT (`,`) or WebVTT (`.`) timestamp.
+fn format_timestamp(milliseconds: u64, separator: char) -> String {
+  return format!(
+    "{:02}:{:02}:{:02}{}{:03}",
+    milliseconds / 3_600_000,
+    milliseconds / 60_000 % 60,
+    milliseconds / 1000 % 60,
+    separator,
+    milliseconds % 1000
+  );
+}
+
+/// Text of a cue line, including markup.
+fn cue_text() -> impl Strategy<Value = String> {
+  return "[A-Za-z]([A-Za-z ,.'!?/&]|<i>|</i>|<v Ana>|<00:00:01\\.000>|\\{\\\\an8\\}){0,40}";
+}
+
+/// Cues with start and end in milliseconds.
+fn cues() -> impl Strategy<Value = Vec<(u64, u64, String)>> {
+  return prop::collection::vec(
+    (0u64..36_000_000, 0u64..600_000, cue_text())
+      .prop_map(|(start, length, text)| (start, start + length, text)),
+    1..20,
+  );
+}
+
+/// Builds subtitles from cues.
+fn subtitles(cues: &[(u64, u64, String)], format: TranscriptFormat) -> String {
+  let (header, separator) = match format {
+    TranscriptFormat::Vtt => ("WEBVTT\n\n", '.'),
+    _ => ("", ','),
+  };
+  let blocks: Vec<String> = cues
+    .iter()
+    .enumerate()
+    .map(|(index, (start, end, text))| {
+      return format!(
+        "{}\n{} --> {}\n{}",
+        index + 1,
+        format_timestamp(*start, separator),
+        format_timestamp(*end, separator),
+        text
+      );
+    })
+    .collect();
+  return format!("{}{}\n", header, blocks.join("\n\n"));
+}
+
+/// Timing lines that are not valid timestamps.
+fn malformed_timing() -> impl Strategy<Value = String> {
+  return prop_oneof![
+    "[0-9:.,-]{0,16} --> [0-9:.,-]{0,16}",
+    "[^\n]{0,20}-->[^\n]{0,20}",
+    Just(String::from("NaN:00:00,000 --> inf:00:00,000")),
+    Just(String::from("-1:00:00,000 --> 00:00:01,000")),
+    Just(String::from("1e309:00 --> 00:01")),
+    Just(String::from("00:00:00,000 -->")),
+  ];
+}
+
+/// Numbers a hostile Whisper JSON file may hold where seconds or
+/// probabilities belong.
+fn json_number() -> impl Strategy<Value = Value> {
+  return prop_oneof![
+    Just(Value::Null),
+    any::<f64>().prop_map(|number| json!(number)),
+    any::<i64>().prop_map(|number| json!(number)),
+    Just(json!("12.5")),
+    Just(json!([])),
+  ];
+}
+
+/// Whisper JSON segments with missing, mistyped or nonsensical fields.
+fn whisper_segment() -> impl Strategy<Value = Value> {
+  let word = (".{0,8}", json_number()).prop_map(|(word, probability)| {
+    return json!({ "word": word, "probability": probability });
+  });
+  return (
+    json_number(),
+    json_number(),
+    prop::option::of(".{0,40}"),
+    prop::collection::vec(word, 0..6),
+  )
+    .prop_map(|(start, end, text, words)| {
+      let mut segment = json!({ "start": start, "end": end, "words": words });
+      if let Some(text) = text {
+        segment["text"] = json!(text);
+      }
+      return segment;
+    });
+}
+
+/// Whisper JSON documents built from such segments.
+fn whisper_json() -> impl Strategy<Value = String> {
+  return (
+    prop::collection::vec(whisper_segment(), 0..10),
+    json_number(),
+    prop::option::of("[a-z]{2}"),
+  )
+    .prop_map(|(segments, duration, language)| {
+      return json!({
+        "segments": segments,
+        "duration": duration,
+        "language": language,
+      })
+      .to_string();
+    });
+}
+
+/// Checks that a timestamp is present, finite and not negative.
+fn is_valid_time(time: Option<f64>) -> bool {
+  return time.is_some_and(|time| time.is_finite() && time >= 0.0);
+}
+
+/// Cuts content at a character boundary, as a partly written file is.
+fn truncate(content: &str, fraction: f64) -> &str {
+  let mut end = (content.len() as f64 * fraction) as usize;
+  while !content.is_char_boundary(end) {
+    end -= 1;
+  }
+  return &content[..end];
+}
+
+proptest! {
+  #[test]
+  fn arbitrary_text_never_panics(content in any::<String>()) {
+    parse_all(&content);
+  }
+
+  #[test]
+  fn subtitles_keep_every_cue(
+    cues in cues(),
+    vtt in any::<bool>(),
+  ) {
+    let format = if vtt { TranscriptFormat::Vtt } else { TranscriptFormat::Srt };
+    let content = subtitles(&cues, format);
+    prop_assert_eq!(TranscriptFormat::detect(&content), format);
+    let transcription = format.parse(&content).unwrap();
+    let segments = transcription.segments.unwrap();
+    prop_assert_eq!(segments.len(), cues.len());
+    for (segment, (start, end, _)) in segments.iter().zip(&cues) {
+      let start = *start as f64 / 1000.0;
+      let end = *end as f64 / 1000.0;
+      prop_assert!((segment.start.unwrap() - start).abs() < 0.001);
+      prop_assert!((segment.end.unwrap() - end).abs() < 0.001);
+      let has_markup = segment.text.contains(['<', '{']);
+      prop_assert!(!has_markup);
+    }
+  }
+
+  #[test]
+  fn truncated_subtitles_parse_or_fail(
+    cues in cues(),
+    vtt in any::<bool>(),
+    fraction in 0.0f64..1.0,
+  ) {
+    let format = if vtt { TranscriptFormat::Vtt } else { TranscriptFormat::Srt };
+    let content = subtitles(&cues, format);
+    parse_all(truncate(&content, fraction));
+  }
+
+  #[test]
+  fn malformed_timing_lines_are_rejected(
+    cues in cues(),
+    timing in malformed_timing(),
+    position in any::<prop::sample::Index>(),
+  ) {
+    let content = subtitles(&cues, TranscriptFormat::Srt);
+    let mut blocks: Vec<&str> = content.split("\n\n").collect();
+    let block = format!("0\n{}\nText", timing);
+    blocks.insert(position.index(blocks.len() + 1), &block);
+    let content = blocks.join("\n\n");
+    match TranscriptFormat::Srt.parse(&content) {
+      Ok(transcription) => {
+        let segments = transcription.segments.unwrap_or_default();
+        let timed = segments.iter().all(|segment| {
+          return is_valid_time(segment.start) && is_valid_time(segment.end);
+        });
+        prop_assert!(timed);
+        exercise(TranscriptFormat::Srt.parse(&content).unwrap());
+      }
+      Err(e) => prop_assert!(e.to_string().contains("invalid timing line")),
+    }
+  }
+
+  #[test]
+  fn malformed_whisper_segments_never_panic(content in whisper_json()) {
+    parse_all(&content);
+  }
+
+  #[test]
+  fn truncated_whisper_json_never_panics(
+    content in whisper_json(),
+    fraction in 0.0f64..1.0,
+  ) {
+    parse_all(truncate(&content, fraction));
+  }
+}
@@ -0,0 +1,302 @@
+//! Man page and Markdown reference generation.
+//!
+//! `pegasus generate-man` renders the clap definitions of [`Cli`] as a
+//! man page, or as a Markdown reference with `--markdown`, so the
+//! documentation shipped by packagers cannot drift from the options the
+//! binary accepts. Global options are documented once, at the top level.
+//!
+//! The standard sections of the man page are rendered by [`clap_mangen`];
+//! every command is documented in the same page, in a COMMANDS section
+//! written with [`roff`], followed by the environment, files and exit
+//! statuses.
+//!
+//! [`Cli`]: crate::cli::Cli
+
+use clap::{Arg, Command};
+use clap_mangen::Man;
+use roff::{Roff, bold, italic, roman};
+
+/// Name of the binary in synopses.
+const BIN_NAME: &str = "pegasus";
+
+/// What the binary does, for the NAME section.
+const DESCRIPTION: &str =
+  "refine speech transcriptions and dictated text with a language model";
+
+/// Exit statuses, as listed in the module documentation of the CLI.
+const EXIT_STATUSES: [(i32, &str); 10] = [
+  (0, "Success"),
+  (1, "Other failure"),
+  (2, "Invalid command-line usage"),
+  (3, "Configuration could not be read, parsed or written"),
+  (4, "Input could not be read or parsed"),
+  (5, "LLM service could not be reached"),
+  (6, "LLM service returned an unusable response"),
+  (7, "Invalid configuration value or argument"),
+  (8, "The model refused to refine the text"),
+  (130, "Interrupted"),
+];
+
+/// Environment variables read by every command.
+const ENVIRONMENT: [(&str, &str); 4] = [
+  (
+    "PEGASUS_<SECTION>_<KEY>",
+    "Overrides a configuration value, like --set section.key=value",
+  ),
+  (
+    "XDG_CONFIG_HOME",
+    "Base directory of the user configuration file",
+  ),
+  (
+    "PAGER",
+    "Pager for long results, unless [output] pager is set",
+  ),
+  ("VISUAL, EDITOR", "Editor for config edit and tui"),
+];
+
+/// Files read by every command.
+const FILES: [(&str, &str); 3] = [
+  ("$XDG_CONFIG_HOME/pegasus/config.toml", "User configuration"),
+  (
+    "$XDG_CONFIG_DIRS/pegasus/config.toml",
+    "System configuration",
+  ),
+  (
+    ".pegasus.toml",
+    "Project configuration, in the current directory or an ancestor",
+  ),
+];
+
+/// Renders a command as a man page.
+///
+/// # Arguments
+///
+/// * `command` - The command-line definition
+///
+/// # Returns
+///
+/// A `std::io::Result<String>` containing the man page in roff.
+pub fn man_page(command: Command) -> std::io::Result<String> {
+  let command = prepare(command)
+    .display_name(BIN_NAME)
+    .about(DESCRIPTION)
+    .long_about(None);
+  let version = command.get_version().unwrap_or_default().to_string();
+  let man = Man::new(command.clone())
+    .title(BIN_NAME.to_uppercase())
+    .source(format!("{} {}", BIN_NAME, version))
+    .manual("User Commands");
+  let mut page = Vec::new();
+  man.render_title(&mut page)?;
+  man.render_name_section(&mut page)?;
+  man.render_synopsis_section(&mut page)?;
+  man.render_options_section(&mut page)?;
+
+  let mut roff = Roff::new();
+  roff.control("SH", ["COMMANDS"]);
+  for subcommand in subcommands(&command) {
+    roff.control("SS", [bin_name(subcommand).as_str()]);
+    roff.text([roman(about(subcommand))]);
+    roff.control("PP", []);
+    roff.text([bold(usage(subcommand))]);
+    for arg in arguments(subcommand, false) {
+      roff.control("TP", []);
+      roff.text([bold(signature(arg))]);
+      roff.text([roman(description(arg))]);
+    }
+  }
+
+  roff.control("SH", ["ENVIRONMENT"]);
+  for (name, description) in ENVIRONMENT {
+    roff.control("TP", []);
+    roff.text([bold(name)]);
+    roff.text([roman(description)]);
+  }
+  roff.control("SH", ["FILES"]);
+  for (path, description) in FILES {
+    roff.control("TP", []);
+    roff.text([italic(path)]);
+    roff.text([roman(description)]);
+  }
+  roff.control("SH", ["EXIT STATUS"]);
+  for (status, description) in EXIT_STATUSES {
+    roff.control("TP", []);
+    roff.text([bold(status.to_string())]);
+    roff.text([roman(description)]);
+  }
+  roff.to_writer(&mut page)?;
+  return Ok(String::from_utf8_lossy(&page).to_string());
+}
+
+/// Renders a command as a Markdown reference.
+///
+/// # Arguments
+///
+/// * `command` - The command-line definition
+///
+/// # Returns
+///
+/// The reference in Markdown.
+pub fn markdown(command: Command) -> String {
+  let command = prepare(command);
+  let mut reference = vec![
+    format!("# {}", BIN_NAME),
+    String::new(),
+    format!("{}{}.", DESCRIPTION[..1].to_uppercase(), &DESCRIPTION[1..]),
+    String::new(),
+    format!("```\n{}\n```", usage(&command)),
+    String::new(),
+    String::from("## Options"),
+    String::new(),
+  ];
+  for arg in arguments(&command, true) {
+    reference.push(markdown_argument(arg));
+  }
+
+  reference.push(String::new());
+  reference.push(String::from("## Commands"));
+  for subcommand in subcommands(&command) {
+    reference.push(String::new());
+    reference.push(format!("### `{}`", bin_name(subcommand)));
+    reference.push(String::new());
+    reference.push(about(subcommand));
+    reference.push(String::new());
+    reference.push(format!("```\n{}\n```", usage(subcommand)));
+    let args = arguments(subcommand, false);
+    if !args.is_empty() {
+      reference.push(String::new());
+    }
+    for arg in args {
+      reference.push(markdown_argument(arg));
+    }
+  }
+
+  reference.push(String::new());
+  reference.push(String::from("## Exit status"));
+  reference.push(String::new());
+  for (status, description) in EXIT_STATUSES {
+    reference.push(format!("- {}: {}", status, description));
+  }
+  return reference.join("\n") + "\n";
+}
+
+/// Names the binary and builds the command, so generated options and
+/// subcommand names are filled in.
+fn prepare(command: Command) -> Command {
+  let mut command = command.bin_name(BIN_NAME);
+  command.build();
+  return command;
+}
+
+/// Collects the subcommands of a command and theirs, depth first.
+fn subcommands(command: &Command) -> Vec<&Command> {
+  let mut collected = Vec::new();
+  for subcommand in command.get_subcommands() {
+    if subcommand.is_hide_set() || subcommand.get_name() == "help" {
+      continue;
+    }
+    collected.push(subcommand);
+    collected.extend(subcommands(subcommand));
+  }
+  return collected;
+}
+
+/// Collects the documented arguments of a command.
+///
+/// Global arguments are only collected for the top-level command.
+fn arguments(command: &Command, top_level: bool) -> Vec<&Arg> {
+  return command
+    .get_arguments()
+    .filter(|arg| !arg.is_hide_set())
+    .filter(|arg| top_level || !arg.is_global_set())
+    .filter(|arg| top_level || arg.get_id() != "help")
+    .collect();
+}
+
+/// Gets the full name of a command, like `pegasus config show`.
+fn bin_name(command: &Command) -> String {
+  return command
+    .get_bin_name()
+    .unwrap_or(command.get_name())
+    .to_string();
+}
+
+/// Gets the one-line description of a command.
+fn about(command: &Command) -> String {
+  return command
+    .get_about()
+    .map(|about| about.to_string())
+    .unwrap_or_default();
+}
+
+/// Gets the usage line of a command, without its `Usage:` label.
+fn usage(command: &Command) -> String {
+  let usage = command.clone().render_usage().to_string();
+  return usage
+    .strip_prefix("Usage: ")
+    .unwrap_or(&usage)
+    .trim()
+    .to_string();
+}
+
+/// Formats how an argument is written, like `-f, --file <FILE>`.
+fn signature(arg: &Arg) -> String {
+  let mut names = Vec::new();
+  if let Some(short) = arg.get_short() {
+    names.push(format!("-{}", short));
+  }
+  if let Some(long) = arg.get_long() {
+    names.push(format!("--{}", long));
+  }
+  let values: Vec<String> = arg
+    .get_value_names()
+    .map(|names| names.iter().map(|name| format!("<{}>", name)).collect())
+    .unwrap_or_else(|| {
+      return vec![format!("<{}>", arg.get_id().as_str().to_uppercase())];
+    });
+  if arg.is_positional() {
+    return values.join(" ");
+  }
+  let mut signature = names.join(", ");
+  if arg.get_action().takes_values() {
+    signature = format!("{} {}", signature, values.join(" "));
+  }
+  return signature;
+}
+
+/// Gets the description of an argument, with its accepted and default
+/// values.
+fn description(arg: &Arg) -> String {
+  let mut description = arg
+    .get_long_help()
+    .or(arg.get_help())
+    .map(|help| help.to_string())
+    .unwrap_or_default();
+  if !arg.get_action().takes_values() {
+    return description;
+  }
+  let possible: Vec<String> = arg
+    .get_possible_values()
+    .iter()
+    .filter(|value| !value.is_hide_set())
+    .map(|value| value.get_name().to_string())
+    .collect();
+  if !possible.is_empty() {
+    description =
+      format!("{} [possible values: {}]", description, possible.join(", "));
+  }
+  let defaults: Vec<String> = arg
+    .get_default_values()
+    .iter()
+    .map(|value| value.to_string_lossy().to_string())
+    .collect();
+  if !defaults.is_empty() {
+    description = format!("{} [default: {}]", description, defaults.join(","));
+  }
+  return description;
+}
+
+/// Renders an argument as a Markdown list item.
+fn markdown_argument(arg: &Arg) -> String {
+  return format!("- `{}`: {}", signature(arg), description(arg));
+}
@@ -8,11 +8,135 @@
 //!
 //! - `--input <text>`: Refine the input text
 //! - `--file <path>`: Refine the input text from a file
-//! - `reset-config`: Reset configuration to default values
+//! - `--lines <start:end>`, `--bytes <start:end>`, `--regex-range
+//!   <regex>`: Refine only the given lines, bytes, or first match of a
+//!   regular expression of the input and splice the result back into the
+//!   rest, which is kept as it is
+//! - `--with-summary`: Also generate a title and three-sentence summary,
+//!   added to JSON output or prepended as a Markdown header (also for
+//!   `whisper-transcribe`)
+//! - `--encoding <label>`: Read input files in the given encoding
+//! - `--transcript-format <format>`: Read transcriptions as `whisper`,
+//!   `whisper-cpp`, `srt`, `vtt` or `text` instead of detecting the format
+//! - `-v`, `-vv`: Log progress, or also request details, to stderr
+//! - `-q`: Only log errors
+//! - `--timing`: Print per-phase durations to stderr (and include them in
+//!   JSON output)
+//! - `--readability`: Print the Flesch reading ease and grade level of the
+//!   output to stderr (and include them in JSON output)
+//! - `--errors-json`: Print failures to stderr as JSON
+//! - `--report <path>`: Write a run report (input statistics, settings,
+//!   confidence histogram, changes, corrections, timings and cost) as
+//!   HTML, Markdown or JSON, by the extension of the path, for refinements,
+//!   `whisper-transcribe`, `transcribe` and `chapters`
+//! - `--side-by-side <path>`: Write an HTML page showing the input and the
+//!   refined text side by side, with changes highlighted and low-confidence
+//!   words marked (for the same commands as `--report`)
+//! - `--keep-verbatim-tokens`: Keep timestamps, speaker tags and bracketed
+//!   annotations like `[laughter]` exactly as written in plain text
+//! - `--wrap <WIDTH>`: Rewrap paragraphs of the output to a column width
+//! - `--no-reflow`: Keep the model's line breaks when wrapping
+//! - `--no-pager`: Print long results directly instead of through `$PAGER`
+//! - `--sink <sink>`: Write the result to stdout, `file:<path>`,
+//!   `clipboard`, `type`, `notify` or `webhook`; repeat for several
+//! - `--line-mode [line|paragraph]`: Refine each stdin line, or each
+//!   blank-line-delimited paragraph, on its own and write each result as
+//!   soon as it is ready
+//! - `--stdio`: Serve JSON-RPC requests on stdin/stdout for editor plugins
+//! - `tui --file <path> [--output <path>]`: Review a Whisper JSON
+//!   transcription segment by segment in the terminal
+//! - `repl`: Refine text interactively, line by line
+//! - `dictate`: Record, transcribe, refine and deliver utterances until
+//!   interrupted, triggered by voice or `SIGUSR1` (see `[dictation]`)
+//! - `reset-config`: Reset configuration to a commented template listing
+//!   every option with its default value
+//! - `daemon`: Keep a warm refinement server on a Unix socket; other
+//!   invocations forward their requests to it
+//! - `daemon --systemd`: Format log messages for the systemd journal
+//! - `install-service [--force]`: Write a systemd user unit for the daemon
+//! - `config show [--origins]`: Print the effective configuration
+//! - `config migrate`: Update the configuration file to the current layout
+//! - `config edit`: Edit the configuration file in the user's editor
+//! - `auth set [key]`: Store the LLM API key in the system keyring
+//! - `auth remove`: Remove the LLM API key from the system keyring
+//! - `queue add <files>... [--kind text|whisper|audio] [--output-dir
+//!   <dir>] [--recursive] [--ext <ext,...>] [--force]`: Add files, or the
+//!   files of directories, to the persistent job queue, skipping those
+//!   with an up-to-date result; results of directories mirror their layout
+//! - `queue run [--limit <n>] [--retry-failed] [--force]`: Refine the
+//!   queued files, resuming where an earlier run stopped and skipping
+//!   files refined before from the same input and settings
+//! - `queue status [-j]`: Print how many jobs are pending, done, refused
+//!   and failed
+//! - `dictionary import <file> [--format csv|json|text] [--column <n>]
+//!   [--header] [--language <code>]`: Add the terms of a spreadsheet export
+//!   or glossary to the `[dictionary] path` file, in the section of a
+//!   language with `--language`
+//! - `dictionary export [--format text|csv|json]`: Print the dictionary
+//!   terms
+//! - `dictionary learn [--min-count <n>] [--add]`: Propose the words
+//!   repeatedly corrected in `tui` reviews as dictionary terms, adding
+//!   them with `--add`
+//! - `bench <dir> [-j]`: Refine each `<name>.txt` (or `.json`, `.srt`,
+//!   `.vtt`) input in a directory and report word and character error
+//!   rates against its `<name>.expected.txt`; compare models, prompts and
+//!   presets by running it with different `--set` overrides
+//! - `wer <reference> <hypothesis> [-j]`: Print the word alignment and
+//!   the word and character error rates of a transcript
+//! - `usage [--since <YYYY-MM-DD>] [--monthly] [-j]`: Print the tokens,
+//!   estimated cost and time of recorded runs per model and `[usage]
+//!   profile`
+//! - `usage --counters [-j]`: Print the runs, models and failure
+//!   categories counted with `[usage] counters`
+//! - `history compact`: Remove unreadable lines from the recorded runs
+//! - `history prune --older-than <age>`: Remove recorded runs older than
+//!   an age like `90d`, `12w` or `36h`
+//! - `self-update [--check-only]`: Replace the binary with the latest
+//!   GitHub release for this platform, after verifying its checksum (and
+//!   signature, for builds with a release signing key)
+//! - `generate-man [--markdown]`: Print a man page (or a Markdown
+//!   reference) generated from these definitions (see [`docs`])
 //! - `whisper-transcribe --input <json>`: Refine using Whisper JSON transcription with confidence scores from the input text.
 //! - `whisper-transcribe --file <path>`: Refine using Whisper JSON transcription with confidence scores from a file
+//!   (whisper.cpp JSON, SRT, WebVTT and plain text are detected too)
+//! - `whisper-transcribe --threshold <probability>`: Flag words below the
+//!   given probability instead of the configured threshold
+//! - `whisper-transcribe --no-dedup`: Keep repeated segments instead of
+//!   collapsing them before refinement
+//! - `whisper-transcribe --strict`: Report every structural problem of the
+//!   transcription and fail before refining (also for `chapters`)
+//! - `chapters --file <path>`: Split a Whisper JSON transcription into
+//!   refined, titled chapters (Markdown, or a JSON chapter list with `-j`)
+//! - `transcribe <audio>`: Transcribe a recording with the speech-to-text
+//!   service in `[transcription]`, then refine it like Whisper JSON
+//!
+//! ## Exit Status
+//!
+//! - 0: Success
+//! - 1: Other failure
+//! - 2: Invalid command-line usage
+//! - 3: Configuration could not be read, parsed or written
+//! - 4: Input could not be read or parsed
+//! - 5: LLM service could not be reached
+//! - 6: LLM service returned an unusable response
+//! - 7: Invalid configuration value or argument
+//! - 8: The model refused to refine the text
+//! - 130: Interrupted
+
+pub mod docs;
 
-use clap::{Parser, Subcommand};
+use clap::{
+  ArgAction, Command, CommandFactory, FromArgMatches, Parser, Subcommand,
+};
+use pegasus_core::dictionary::formats::DictionaryFormat;
+use pegasus_core::i18n;
+use pegasus_core::input::range::InputRange;
+use pegasus_core::input::stream::StreamUnit;
+use pegasus_core::input::transcript_format::TranscriptFormat;
+use pegasus_core::output::report::ReportFormat;
+use pegasus_core::output::sink;
+use pegasus_core::queue::JobKind;
+use pegasus_core::usage;
 
 #[derive(Parser)]
 #[command(name = "Pegasus")]
@@ -30,31 +154,802 @@ pub struct Cli {
   #[arg(short, long, conflicts_with = "input")]
   pub file: Option<String>,
 
-  /// Use verbose output
-  #[arg(short, long, default_value_t = false, global = true)]
-  pub verbose: bool,
+  /// Log progress to stderr; repeat (-vv) for request details
+  #[arg(short, long, action = ArgAction::Count, global = true)]
+  pub verbose: u8,
+
+  /// Only log errors
+  #[arg(
+    short,
+    long,
+    default_value_t = false,
+    global = true,
+    conflicts_with = "verbose"
+  )]
+  pub quiet: bool,
 
   /// Output result in JSON format
   #[arg(short = 'j', long, default_value_t = false)]
   pub output_json: bool,
+
+  /// Refine only lines START to END (counted from 1) and keep the rest of
+  /// the input as it is
+  #[arg(
+    long,
+    value_name = "START:END",
+    value_parser = parse_lines,
+    conflicts_with_all = ["bytes", "regex_range", "stdio", "line_mode", "with_summary"]
+  )]
+  pub lines: Option<InputRange>,
+
+  /// Refine only bytes START up to END (counted from 0) and keep the rest
+  /// of the input as it is
+  #[arg(
+    long,
+    value_name = "START:END",
+    value_parser = parse_bytes,
+    conflicts_with_all = ["regex_range", "stdio", "line_mode", "with_summary"]
+  )]
+  pub bytes: Option<InputRange>,
+
+  /// Refine only the first match of a regular expression (use `(?s)` to
+  /// match across lines) and keep the rest of the input as it is
+  #[arg(
+    long,
+    value_name = "REGEX",
+    value_parser = parse_regex_range,
+    conflicts_with_all = ["stdio", "line_mode", "with_summary"]
+  )]
+  pub regex_range: Option<InputRange>,
+
+  /// Also generate a title and short summary of the refined text
+  #[arg(long, default_value_t = false)]
+  pub with_summary: bool,
+
+  /// Encoding of the input file (e.g. `windows-1252`, `utf-16le`);
+  /// detected automatically when not set
+  #[arg(long, value_name = "LABEL", global = true)]
+  pub encoding: Option<String>,
+
+  /// Format of transcriptions: whisper, whisper-cpp, srt, vtt or text;
+  /// detected from the content when not set
+  #[arg(
+    long,
+    value_name = "FORMAT",
+    value_parser = parse_transcript_format,
+    global = true
+  )]
+  pub transcript_format: Option<TranscriptFormat>,
+
+  /// Serve JSON-RPC requests on stdin/stdout for editor integration
+  #[arg(long, default_value_t = false, conflicts_with_all = ["input", "file"])]
+  pub stdio: bool,
+
+  /// Refine each stdin line, or each paragraph, on its own and write the
+  /// results as they complete
+  #[arg(
+    long,
+    value_name = "UNIT",
+    num_args = 0..=1,
+    default_missing_value = "line",
+    value_parser = parse_stream_unit,
+    conflicts_with_all = ["input", "file", "stdio", "with_summary"]
+  )]
+  pub line_mode: Option<StreamUnit>,
+
+  /// Print how long each phase took to stderr
+  #[arg(long, default_value_t = false, global = true)]
+  pub timing: bool,
+
+  /// Print readability metrics of the output to stderr
+  #[arg(long, default_value_t = false, global = true)]
+  pub readability: bool,
+
+  /// Print failures to stderr as JSON objects
+  #[arg(long, default_value_t = false, global = true)]
+  pub errors_json: bool,
+
+  /// Write a report of the run to a .html, .md or .json file
+  #[arg(
+    long,
+    value_name = "PATH",
+    value_parser = parse_report,
+    global = true
+  )]
+  pub report: Option<String>,
+
+  /// Write the original and refined text side by side to an HTML file
+  #[arg(long, value_name = "PATH", global = true)]
+  pub side_by_side: Option<String>,
+
+  /// Keep timestamps, speaker tags and bracketed annotations verbatim
+  #[arg(long, default_value_t = false, global = true)]
+  pub keep_verbatim_tokens: bool,
+
+  /// Rewrap refined output to this many characters per line
+  #[arg(long, value_name = "WIDTH", global = true)]
+  pub wrap: Option<usize>,
+
+  /// Keep the model's line breaks, only breaking lines longer than --wrap
+  #[arg(long, default_value_t = false, global = true)]
+  pub no_reflow: bool,
+
+  /// Never pipe long results into a pager
+  #[arg(long, default_value_t = false, global = true)]
+  pub no_pager: bool,
+
+  /// Refine locally even if a daemon is running
+  #[arg(long, default_value_t = false, global = true)]
+  pub no_daemon: bool,
+
+  /// Write the result to a sink: stdout, file:<path>, clipboard, type,
+  /// notify or webhook; repeat for several (default: `[output] sinks`)
+  #[arg(
+    long = "sink",
+    value_name = "SINK",
+    value_parser = parse_sink,
+    global = true
+  )]
+  pub sinks: Vec<String>,
+
+  /// Override a configuration value (e.g. `--set llm.model=qwen2.5`)
+  #[arg(long = "set", value_name = "SECTION.KEY=VALUE", global = true)]
+  pub overrides: Vec<String>,
+}
+
+impl Cli {
+  /// Parses the command line, with its help translated to the user's
+  /// language where a translation is plugged in (see
+  /// [`pegasus_core::i18n`]).
+  ///
+  /// # Returns
+  ///
+  /// The parsed command line; exits with usage errors like
+  /// [`Parser::parse`].
+  pub fn parse_localized() -> Self {
+    let matches = localize(Cli::command(), ROOT_HELP_ID).get_matches();
+    return Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+  }
+
+  /// Checks whether the invocation keeps serving requests, each of which
+  /// gets its own request ID, rather than making a single refinement.
+  ///
+  /// # Returns
+  ///
+  /// `true` for `--stdio`, `--line-mode`, `daemon`, `repl`, `dictate` and
+  /// `queue run`.
+  pub fn serves_requests(&self) -> bool {
+    return self.stdio
+      || self.line_mode.is_some()
+      || matches!(
+        self.command,
+        Some(
+          Commands::Daemon { .. }
+            | Commands::Repl
+            | Commands::Dictate { .. }
+            | Commands::Queue {
+              action: QueueCommands::Run { .. }
+            }
+        )
+      );
+  }
+}
+
+/// Message ID of the description of the top-level command.
+const ROOT_HELP_ID: &str = "help-pegasus";
+
+/// Replaces the help of a command, its arguments and its subcommands with
+/// their translations.
+///
+/// The description of a command is the message `help-<command>`, as in
+/// `help-queue-add`, and the help of an argument `help-<command>--<long
+/// flag or name>`, as in `help-queue-add--force`.
+///
+/// # Arguments
+///
+/// * `command` - The command to translate
+/// * `id` - The message ID of its description
+///
+/// # Returns
+///
+/// The command with the translated help.
+fn localize(mut command: Command, id: &str) -> Command {
+  if let Some(about) = i18n::translation(id) {
+    command = command.about(about.clone()).long_about(about);
+  }
+  let arguments: Vec<(String, String)> = command
+    .get_arguments()
+    .map(|arg| {
+      let name = arg.get_long().unwrap_or(arg.get_id().as_str());
+      return (arg.get_id().to_string(), name.to_string());
+    })
+    .collect();
+  for (arg_id, name) in arguments {
+    if let Some(help) = i18n::translation(&format!("{}--{}", id, name)) {
+      command =
+        command.mut_arg(arg_id, |arg| arg.help(help.clone()).long_help(help));
+    }
+  }
+  let subcommands: Vec<String> = command
+    .get_subcommands()
+    .map(|subcommand| subcommand.get_name().to_string())
+    .collect();
+  for name in subcommands {
+    let subcommand_id = if id == ROOT_HELP_ID {
+      format!("help-{}", name)
+    } else {
+      format!("{}-{}", id, name)
+    };
+    command = command
+      .mut_subcommand(name, |subcommand| localize(subcommand, &subcommand_id));
+  }
+  return command;
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
   WhisperTranscribe {
-    /// Input text from Whisper JSON transcription to refine
+    /// Transcription to refine: Whisper or whisper.cpp JSON, SRT, WebVTT
+    /// or plain text
     #[arg(short, long, conflicts_with = "file")]
     input: Option<String>,
 
-    /// Path to the Whisper JSON transcription file to refine
+    /// Path to the transcription file to refine
     #[arg(short, long, conflicts_with = "input")]
     file: Option<String>,
 
     /// Output result in JSON format
     #[arg(short = 'j', long, default_value_t = false)]
     output_json: bool,
+
+    /// Flag words below this probability (0.0 to 1.0), overriding
+    /// `[whisper] probability_threshold`
+    #[arg(long, value_name = "PROBABILITY", value_parser = parse_probability)]
+    threshold: Option<f64>,
+
+    /// Keep runs of repeated segments instead of collapsing them,
+    /// overriding `[whisper] deduplicate_segments`
+    #[arg(long, default_value_t = false)]
+    no_dedup: bool,
+
+    /// Also generate a title and short summary of the refined text
+    #[arg(long, default_value_t = false)]
+    with_summary: bool,
+
+    /// Report all structural problems of the transcription (timestamps out
+    /// of order, probabilities out of range, empty segments) and fail
+    /// before refining; plain text is rejected
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+  },
+
+  /// Split a Whisper JSON transcription into refined, titled chapters
+  Chapters {
+    /// Input text from Whisper JSON transcription to split
+    #[arg(short, long, conflicts_with = "file")]
+    input: Option<String>,
+
+    /// Path to the Whisper JSON transcription file to split
+    #[arg(short, long, conflicts_with = "input")]
+    file: Option<String>,
+
+    /// Output the chapter list in JSON format
+    #[arg(short = 'j', long, default_value_t = false)]
+    output_json: bool,
+
+    /// Fail on structural problems of the transcription before splitting
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+  },
+
+  /// Transcribe a recording with the configured speech-to-text service
+  /// and refine the transcription
+  Transcribe {
+    /// Path to the audio file to transcribe
+    file: String,
+
+    /// Output result in JSON format
+    #[arg(short = 'j', long, default_value_t = false)]
+    output_json: bool,
+
+    /// Also generate a title and short summary of the refined text
+    #[arg(long, default_value_t = false)]
+    with_summary: bool,
+  },
+
+  /// Detect the context window and features of the LLM endpoint
+  Probe {
+    /// Output the capabilities in JSON format
+    #[arg(short = 'j', long, default_value_t = false)]
+    output_json: bool,
+  },
+
+  /// Review a Whisper JSON transcription segment by segment
+  Tui {
+    /// Path to the Whisper JSON transcription file to review
+    #[arg(short, long)]
+    file: String,
+
+    /// Where to write the reviewed text (default: <name>.reviewed.txt)
+    #[arg(short, long)]
+    output: Option<String>,
+  },
+
+  /// Refine text interactively, line by line
+  Repl,
+
+  /// Dictate: record, transcribe and refine utterances until interrupted
+  Dictate {
+    /// Run the dictation loop as a detached background process
+    #[arg(long, default_value_t = false, conflicts_with_all = ["toggle", "stop"])]
+    background: bool,
+
+    /// Start or stop recording in the running dictation loop
+    #[arg(long, default_value_t = false, conflicts_with = "stop")]
+    toggle: bool,
+
+    /// Stop the running dictation loop
+    #[arg(long, default_value_t = false)]
+    stop: bool,
   },
 
   /// Reset configuration to default values
   ResetConfig,
+
+  /// Serve refinement requests on $XDG_RUNTIME_DIR/pegasus.sock
+  Daemon {
+    /// Format log messages for the systemd journal
+    #[arg(long, default_value_t = false)]
+    systemd: bool,
+  },
+
+  /// Write a systemd user unit that runs the daemon
+  InstallService {
+    /// Overwrite an existing unit file
+    #[arg(long, default_value_t = false)]
+    force: bool,
+  },
+
+  /// Inspect the configuration
+  Config {
+    #[command(subcommand)]
+    action: ConfigCommands,
+  },
+
+  /// Manage the LLM API key stored in the system keyring
+  Auth {
+    #[command(subcommand)]
+    action: AuthCommands,
+  },
+
+  /// Queue files and refine them over one or more sessions
+  Queue {
+    #[command(subcommand)]
+    action: QueueCommands,
+  },
+
+  /// Import terms into the custom dictionary or export them
+  Dictionary {
+    #[command(subcommand)]
+    action: DictionaryCommands,
+  },
+
+  /// Score refinements of a directory of cases against references
+  Bench {
+    /// Directory of <name>.txt (or .json, .srt, .vtt) inputs and
+    /// <name>.expected.txt references
+    directory: String,
+
+    /// Output as JSON
+    #[arg(short = 'j', long = "json")]
+    output_json: bool,
+  },
+
+  /// Print tokens, estimated cost and time used per model and profile
+  Usage {
+    /// Only count runs from this day on (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    since: Option<i64>,
+
+    /// Add up each month separately
+    #[arg(long)]
+    monthly: bool,
+
+    /// Print the anonymous run counters (`[usage] counters`) instead
+    #[arg(long, conflicts_with_all = ["since", "monthly"])]
+    counters: bool,
+
+    /// Output as JSON
+    #[arg(short = 'j', long = "json")]
+    output_json: bool,
+  },
+
+  /// Maintain the recorded runs counted by `usage`
+  History {
+    #[command(subcommand)]
+    action: HistoryCommands,
+  },
+
+  /// Update the binary to the latest release
+  SelfUpdate {
+    /// Only report whether an update is available
+    #[arg(long)]
+    check_only: bool,
+  },
+
+  /// Print a man page generated from the command-line definitions
+  GenerateMan {
+    /// Print a Markdown reference instead
+    #[arg(long)]
+    markdown: bool,
+  },
+
+  /// Compute the word and character error rates of a transcript
+  Wer {
+    /// File with the correct text
+    reference: String,
+
+    /// File with the text to score
+    hypothesis: String,
+
+    /// Output as JSON
+    #[arg(short = 'j', long = "json")]
+    output_json: bool,
+  },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+  /// Print the effective configuration
+  Show {
+    /// Annotate each value with the layer it came from
+    #[arg(long, default_value_t = false)]
+    origins: bool,
+  },
+
+  /// Update the user configuration file to the current layout
+  Migrate,
+
+  /// Edit the user configuration file in $VISUAL or $EDITOR
+  Edit,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommands {
+  /// Remove lines that cannot be read, such as ones cut short by a full
+  /// disk
+  Compact,
+
+  /// Remove runs older than an age
+  Prune {
+    /// Age in days, weeks or hours (e.g. `90d`, `12w`, `36h`)
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    older_than: i64,
+  },
+}
+
+#[derive(Subcommand)]
+pub enum DictionaryCommands {
+  /// Add the terms of a CSV, JSON or text file to the dictionary
+  Import {
+    /// File with the terms
+    file: String,
+
+    /// Format of the file: csv, json or text (default: from the file
+    /// extension)
+    #[arg(long, value_parser = parse_dictionary_format)]
+    format: Option<DictionaryFormat>,
+
+    /// Column of a CSV file holding the terms, counted from 1
+    #[arg(long, default_value_t = 1)]
+    column: usize,
+
+    /// Skip the first row of a CSV file
+    #[arg(long)]
+    header: bool,
+
+    /// Language section for terms the file does not assign to one, as an
+    /// ISO 639-1 code
+    #[arg(long)]
+    language: Option<String>,
+  },
+
+  /// Print the dictionary terms
+  Export {
+    /// Format to print: text, csv or json
+    #[arg(
+      long,
+      default_value = "text",
+      value_parser = parse_dictionary_format
+    )]
+    format: DictionaryFormat,
+  },
+
+  /// Propose words repeatedly corrected during review as terms
+  Learn {
+    /// How often a word must have been corrected to be proposed
+    #[arg(long, default_value_t = 2)]
+    min_count: usize,
+
+    /// Add the proposed terms to the dictionary
+    #[arg(long)]
+    add: bool,
+  },
+}
+
+#[derive(Subcommand)]
+pub enum AuthCommands {
+  /// Store the API key in the system keyring
+  Set {
+    /// API key to store (read from stdin when omitted)
+    key: Option<String>,
+
+    /// Store the API key of the speech-to-text service instead
+    #[arg(long)]
+    transcription: bool,
+  },
+
+  /// Remove the API key from the system keyring
+  Remove {
+    /// Remove the API key of the speech-to-text service instead
+    #[arg(long)]
+    transcription: bool,
+  },
+}
+
+#[derive(Subcommand)]
+pub enum QueueCommands {
+  /// Add files to the queue
+  Add {
+    /// Paths of the files (or with --recursive, directories) to refine
+    #[arg(required = true)]
+    files: Vec<String>,
+
+    /// Add the files in directories and their subdirectories
+    #[arg(short, long, default_value_t = false)]
+    recursive: bool,
+
+    /// Only add files with these extensions from directories (e.g.
+    /// `txt,json`)
+    #[arg(long, value_name = "EXT", value_delimiter = ',')]
+    ext: Vec<String>,
+
+    /// What the files contain: text, whisper (JSON transcriptions) or
+    /// audio (recordings to transcribe first)
+    #[arg(long, default_value = "text", value_parser = parse_job_kind)]
+    kind: JobKind,
+
+    /// Directory to write the refined files to, mirroring the layout of
+    /// added directories (default: next to each input, as
+    /// <name>.refined.txt)
+    #[arg(short, long, visible_alias = "out-dir", value_name = "DIR")]
+    output_dir: Option<String>,
+
+    /// Add files even if their refined file is newer than the file
+    #[arg(long, default_value_t = false)]
+    force: bool,
+  },
+
+  /// Refine the pending files in the queue
+  Run {
+    /// Stop after this many files
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Queue failed and refused files again before running
+    #[arg(long, default_value_t = false)]
+    retry_failed: bool,
+
+    /// Refine files again even if they and the settings are unchanged
+    /// since their last run
+    #[arg(long, default_value_t = false)]
+    force: bool,
+  },
+
+  /// Print how many files are pending, done and failed
+  Status {
+    /// Output the status in JSON format
+    #[arg(short = 'j', long, default_value_t = false)]
+    output_json: bool,
+  },
+}
+
+/// Parses a probability between 0.0 and 1.0.
+///
+/// # Arguments
+///
+/// * `value` - The command-line value
+///
+/// # Returns
+///
+/// The probability, or a message explaining why the value is invalid.
+fn parse_probability(value: &str) -> Result<f64, String> {
+  let probability: f64 = value
+    .parse()
+    .map_err(|_| format!("'{}' is not a number", value))?;
+  if !(0.0..=1.0).contains(&probability) {
+    return Err(format!("must be between 0.0 and 1.0, got {}", value));
+  }
+  return Ok(probability);
+}
+
+/// Parses a `--lines` range.
+///
+/// # Arguments
+///
+/// * `value` - The range as `START:END`
+///
+/// # Returns
+///
+/// The range, or a message explaining why the value is invalid.
+fn parse_lines(value: &str) -> Result<InputRange, String> {
+  return InputRange::lines(value).map_err(|e| e.to_string());
+}
+
+/// Parses a `--bytes` range.
+///
+/// # Arguments
+///
+/// * `value` - The range as `START:END`
+///
+/// # Returns
+///
+/// The range, or a message explaining why the value is invalid.
+fn parse_bytes(value: &str) -> Result<InputRange, String> {
+  return InputRange::bytes(value).map_err(|e| e.to_string());
+}
+
+/// Parses a `--regex-range` pattern.
+///
+/// # Arguments
+///
+/// * `value` - The regular expression
+///
+/// # Returns
+///
+/// The range, or a message explaining why the pattern is invalid.
+fn parse_regex_range(value: &str) -> Result<InputRange, String> {
+  return InputRange::regex(value).map_err(|e| e.to_string());
+}
+
+/// Parses a `--line-mode` unit.
+///
+/// # Arguments
+///
+/// * `value` - The unit name
+///
+/// # Returns
+///
+/// The unit, or a message explaining why the value is invalid.
+fn parse_stream_unit(value: &str) -> Result<StreamUnit, String> {
+  return StreamUnit::from_name(value).ok_or_else(|| {
+    format!("'{}' is not a unit; use 'line' or 'paragraph'", value)
+  });
+}
+
+/// Parses a `--transcript-format` value.
+///
+/// # Arguments
+///
+/// * `value` - The format name
+///
+/// # Returns
+///
+/// The format, or a message explaining why the value is invalid.
+fn parse_transcript_format(value: &str) -> Result<TranscriptFormat, String> {
+  return TranscriptFormat::from_name(value).ok_or_else(|| {
+    format!(
+      "'{}' is not a format; use 'whisper', 'whisper-cpp', 'srt', 'vtt' or \
+       'text'",
+      value
+    )
+  });
+}
+
+/// Parses a `queue add --kind` value.
+///
+/// # Arguments
+///
+/// * `value` - The kind name
+///
+/// # Returns
+///
+/// The kind, or a message explaining why the value is invalid.
+fn parse_job_kind(value: &str) -> Result<JobKind, String> {
+  return JobKind::from_name(value).ok_or_else(|| {
+    format!(
+      "'{}' is not a kind; use 'text', 'whisper' or 'audio'",
+      value
+    )
+  });
+}
+
+/// Parses a dictionary `--format`.
+///
+/// # Arguments
+///
+/// * `value` - The format name
+///
+/// # Returns
+///
+/// The format, or a message explaining why the value is invalid.
+fn parse_dictionary_format(value: &str) -> Result<DictionaryFormat, String> {
+  return DictionaryFormat::from_name(value).ok_or_else(|| {
+    format!("'{}' is not a format; use 'csv', 'json' or 'text'", value)
+  });
+}
+
+/// Parses a `--since` day.
+///
+/// # Arguments
+///
+/// * `value` - The day as `YYYY-MM-DD`
+///
+/// # Returns
+///
+/// The Unix timestamp of the start of the day in local time, or a message
+/// explaining why the value is invalid.
+fn parse_date(value: &str) -> Result<i64, String> {
+  return usage::start_of_day(value)
+    .ok_or_else(|| format!("'{}' is not a date like 2024-01-31", value));
+}
+
+/// Parses an age like `90d`, `12w` or `36h`.
+///
+/// # Arguments
+///
+/// * `value` - The command-line value
+///
+/// # Returns
+///
+/// The age in seconds, or a message explaining why the value is invalid.
+fn parse_age(value: &str) -> Result<i64, String> {
+  let invalid = || format!("'{}' is not an age like 90d, 12w or 36h", value);
+  let value = value.trim();
+  let (number, unit) = value.split_at(value.len().saturating_sub(1));
+  let seconds = match unit {
+    "h" => 60 * 60,
+    "d" => 24 * 60 * 60,
+    "w" => 7 * 24 * 60 * 60,
+    _ => return Err(invalid()),
+  };
+  let number: i64 = number.parse().map_err(|_| invalid())?;
+  return number
+    .checked_mul(seconds)
+    .filter(|age| *age >= 0)
+    .ok_or_else(invalid);
+}
+
+/// Parses a `--report` path.
+///
+/// # Arguments
+///
+/// * `value` - The report path
+///
+/// # Returns
+///
+/// The path, or a message explaining why its extension is not a report
+/// format.
+fn parse_report(value: &str) -> Result<String, String> {
+  return match ReportFormat::from_path(value) {
+    Some(_) => Ok(value.to_string()),
+    None => Err(format!(
+      "'{}' is not a report file; use a .html, .md or .json extension",
+      value
+    )),
+  };
+}
+
+/// Parses an output sink spec.
+///
+/// # Arguments
+///
+/// * `value` - The command-line value
+///
+/// # Returns
+///
+/// The spec, or a message explaining why it is not a known sink.
+fn parse_sink(value: &str) -> Result<String, String> {
+  sink::check_spec(value).map_err(|e| e.to_string())?;
+  return Ok(value.to_string());
 }
@@ -1,67 +1,1113 @@
-mod app;
 mod cli;
-mod config;
-mod files;
-mod input;
-mod llm;
-mod logging;
-mod network;
-mod output;
-
-use clap::Parser;
-
-use crate::app::App;
-use crate::cli::{Cli, Commands};
-use crate::config::Config;
-use crate::logging::set_verbose;
-use crate::output::format::OutputFormat;
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::CommandFactory;
+use pegasus_core::CancellationToken;
+use pegasus_core::app::App;
+use pegasus_core::app::errors::{ErrorKind, RuntimeError, RuntimeResult};
+use pegasus_core::backend;
+use pegasus_core::bench;
+use pegasus_core::config::Config;
+use pegasus_core::config::resolver::ConfigResolver;
+#[cfg(unix)]
+use pegasus_core::config::{discovery, resolver};
+use pegasus_core::dictation;
+use pegasus_core::dictionary::formats::{
+  self, DictionaryFormat, ImportOptions,
+};
+use pegasus_core::dictionary::learn;
+use pegasus_core::dictionary::{self, DictionaryEntry};
+use pegasus_core::elog;
+use pegasus_core::files::temporary;
+use pegasus_core::input::{InputOptions, InputReader};
+use pegasus_core::ipc;
+#[cfg(unix)]
+use pegasus_core::ipc::client::DaemonClient;
+use pegasus_core::logging::{self, request_id};
+use pegasus_core::logging::{Verbosity, set_journald, set_verbosity};
+use pegasus_core::output::accuracy::{self, Accuracy};
+use pegasus_core::output::format::OutputFormat;
+use pegasus_core::output::sink::{self, OutputSink};
+use pegasus_core::queue::{self, AddOptions, Queue};
+use pegasus_core::repl;
+use pegasus_core::secrets::{self, Secret};
+#[cfg(unix)]
+use pegasus_core::systemd;
+use pegasus_core::t;
+use pegasus_core::timing;
+#[cfg(unix)]
+use pegasus_core::tui;
+use pegasus_core::update;
+use pegasus_core::usage;
+#[cfg(unix)]
+use pegasus_core::vlog;
+
+use crate::cli::{
+  AuthCommands, Cli, Commands, ConfigCommands, DictionaryCommands,
+  HistoryCommands, QueueCommands,
+};
+
+static ERRORS_JSON: AtomicBool = AtomicBool::new(false);
 
 #[tokio::main]
 async fn main() {
-  let cli = Cli::parse();
+  let cli = Cli::parse_localized();
+  if cli.serves_requests() {
+    run(cli).await;
+  } else {
+    request_id::scope(request_id::generate(), run(cli)).await;
+  }
+}
 
-  set_verbose(cli.verbose);
+/// Runs the command given on the command line.
+///
+/// # Arguments
+///
+/// * `cli` - The parsed command line
+async fn run(mut cli: Cli) {
+  set_verbosity(Verbosity::from_flags(cli.quiet, cli.verbose));
+  #[cfg(unix)]
+  spawn_termination_handler();
+  if cli.keep_verbatim_tokens {
+    cli
+      .overrides
+      .push(String::from("llm.keep_verbatim_tokens=true"));
+  }
+  if let Some(width) = cli.wrap {
+    cli.overrides.push(format!("output.wrap={}", width));
+  }
+  if cli.no_reflow {
+    cli.overrides.push(String::from("output.reflow=false"));
+  }
+  if cli.no_pager {
+    cli.overrides.push(String::from("output.use_pager=false"));
+  }
+  timing::set_enabled(cli.timing);
+  if cli.report.is_some() {
+    timing::record_only();
+  }
+  ERRORS_JSON.store(cli.errors_json, Ordering::Relaxed);
 
-  let config = match Config::load().await {
-    Ok(config) => config,
-    Err(e) => {
-      eprintln!("Configuration Error: {}", e);
-      std::process::exit(1);
+  let sinks = cli.sinks.clone();
+  let report = cli.report.clone();
+  let side_by_side = cli.side_by_side.clone();
+  let range = cli
+    .lines
+    .take()
+    .or(cli.bytes.take())
+    .or(cli.regex_range.take());
+  let (app, format, result) = match cli.command {
+    Some(Commands::GenerateMan { markdown }) => {
+      let command = Cli::command();
+      if markdown {
+        print!("{}", cli::docs::markdown(command));
+      } else {
+        match cli::docs::man_page(command) {
+          Ok(page) => print!("{}", page),
+          Err(e) => fail(ErrorKind::Other, e),
+        }
+      }
+      return;
+    }
+    Some(Commands::SelfUpdate { check_only }) => {
+      let app = load_app(&cli.overrides).await;
+      self_update(app.config(), check_only).await;
+      return;
     }
-  };
-
-  let app = App::new(config);
-
-  let result = match cli.command {
     Some(Commands::ResetConfig) => match Config::reset_to_defaults().await {
       Ok(_) => {
-        println!("Configuration has been reset to default values.");
+        println!("{}", t!("config-reset"));
+        return;
+      }
+      Err(e) => {
+        fail(
+          ErrorKind::Config,
+          t!("config-reset-failed", error = e.to_string()),
+        );
+      }
+    },
+    Some(Commands::Config {
+      action: ConfigCommands::Show { origins },
+    }) => match ConfigResolver::resolve(&cli.overrides).await {
+      Ok(resolver) => {
+        println!("{}", resolver.describe(origins));
         return;
       }
       Err(e) => {
-        eprintln!("Failed to reset configuration: {}", e);
-        std::process::exit(1);
+        fail(e.kind(), t!("config-error", error = e.to_string()));
+      }
+    },
+    Some(Commands::Config {
+      action: ConfigCommands::Migrate,
+    }) => match Config::migrate_user_config().await {
+      Ok(notices) if notices.is_empty() => {
+        println!("{}", t!("config-up-to-date"));
+        return;
+      }
+      Ok(notices) => {
+        for notice in notices {
+          println!("{}", t!("config-migrated-notice", notice = notice));
+        }
+        println!("{}", t!("config-migrated"));
+        return;
+      }
+      Err(e) => {
+        fail(
+          ErrorKind::Config,
+          t!("config-migrate-failed", error = e.to_string()),
+        );
+      }
+    },
+    Some(Commands::Config {
+      action: ConfigCommands::Edit,
+    }) => match Config::edit_user_config().await {
+      Ok(true) => {
+        println!("{}", t!("config-updated"));
+        return;
+      }
+      Ok(false) => {
+        println!("{}", t!("config-unchanged"));
+        return;
+      }
+      Err(e) => {
+        fail(
+          ErrorKind::Config,
+          t!("config-edit-failed", error = e.to_string()),
+        );
+      }
+    },
+    Some(Commands::Auth { action }) => {
+      let secret = |transcription: bool| match transcription {
+        true => Secret::TranscriptionApiKey,
+        false => Secret::LlmApiKey,
+      };
+      let result = match action {
+        AuthCommands::Set { key, transcription } => {
+          let key = key.unwrap_or_else(read_api_key_from_stdin);
+          secrets::set_keyring_api_key(secret(transcription), key)
+            .await
+            .map(|_| t!("api-key-stored"))
+        }
+        AuthCommands::Remove { transcription } => {
+          secrets::remove_keyring_api_key(secret(transcription))
+            .await
+            .map(|_| t!("api-key-removed"))
+        }
+      };
+      match result {
+        Ok(message) => {
+          println!("{}", message);
+          return;
+        }
+        Err(e) => {
+          fail(ErrorKind::Other, e);
+        }
+      }
+    }
+    Some(Commands::Dictionary { action }) => {
+      let app = load_app(&cli.overrides).await;
+      run_dictionary_command(app.config(), action).await;
+      return;
+    }
+    Some(Commands::Queue { action }) => {
+      let queue = match Queue::open() {
+        Ok(queue) => queue,
+        Err(e) => fail(e.kind(), e),
+      };
+      run_queue_command(&cli.overrides, &queue, action).await;
+      return;
+    }
+    Some(Commands::Bench {
+      directory,
+      output_json,
+    }) => {
+      let cancellation = spawn_interrupt_handler();
+      let app = load_app(&cli.overrides)
+        .await
+        .with_cancellation(cancellation);
+      let run = bench::run(&app, Path::new(&directory));
+      let report = match usage::track(app.config(), "bench", run).await {
+        Ok(report) => report,
+        Err(e) => fail(e.kind(), e),
+      };
+      println!("{}", report.format(OutputFormat::from_flags(output_json)));
+      if app.cancellation().is_cancelled() {
+        fail(
+          ErrorKind::Cancelled,
+          t!("bench-cancelled", cases = report.cases.len()),
+        );
       }
+      let failed = report.failed();
+      if failed > 0 {
+        fail(
+          ErrorKind::Other,
+          t!("bench-failed", failed = failed, cases = report.cases.len()),
+        );
+      }
+      return;
+    }
+    Some(Commands::Usage {
+      counters: true,
+      output_json,
+      ..
+    }) => match usage::counters::load().await {
+      Ok(counters) => {
+        let format = OutputFormat::from_flags(output_json);
+        println!(
+          "{}",
+          usage::counters::format_counters(counters.as_ref(), format)
+        );
+        return;
+      }
+      Err(e) => fail(ErrorKind::Other, e),
+    },
+    Some(Commands::Usage {
+      since,
+      monthly,
+      output_json,
+      ..
+    }) => match usage::load(since).await {
+      Ok(records) => {
+        let totals = usage::summarize(&records, monthly);
+        let format = OutputFormat::from_flags(output_json);
+        println!("{}", usage::format_report(&totals, format));
+        return;
+      }
+      Err(e) => fail(ErrorKind::Other, e),
     },
+    Some(Commands::History { action }) => {
+      let removed = match action {
+        HistoryCommands::Compact => usage::compact().await,
+        HistoryCommands::Prune { older_than } => usage::prune(older_than).await,
+      };
+      match removed {
+        Ok(removed) => {
+          println!("{}", t!("history-removed", removed = removed));
+          return;
+        }
+        Err(e) => fail(ErrorKind::Other, e),
+      }
+    }
+    Some(Commands::Wer {
+      reference,
+      hypothesis,
+      output_json,
+    }) => {
+      let options = InputOptions {
+        max_size: 0,
+        encoding: cli.encoding.clone(),
+      };
+      let (reference, hypothesis) = match tokio::try_join!(
+        InputReader::read_input(None, Some(reference), &options),
+        InputReader::read_input(None, Some(hypothesis), &options),
+      ) {
+        Ok(texts) => texts,
+        Err(e) => fail(ErrorKind::Input, e),
+      };
+      let reference_words = accuracy::words(&reference);
+      let hypothesis_words = accuracy::words(&hypothesis);
+      let alignment = accuracy::align(&reference_words, &hypothesis_words);
+      if alignment.is_none() {
+        elog!(logging::WARNING, "{}", t!("wer-too-long"));
+      }
+      println!(
+        "{}",
+        accuracy::format_report(
+          &Accuracy::measure(&reference, &hypothesis),
+          alignment.as_deref(),
+          OutputFormat::from_flags(output_json),
+        )
+      );
+      return;
+    }
     Some(Commands::WhisperTranscribe {
       input,
       file,
       output_json,
+      threshold,
+      no_dedup,
+      with_summary,
+      strict,
     }) => {
+      let cancellation = spawn_interrupt_handler();
+      let mut overrides = cli.overrides.clone();
+      if let Some(threshold) = threshold {
+        overrides.push(format!("whisper.probability_threshold={}", threshold));
+      }
+      if no_dedup {
+        overrides.push(String::from("whisper.deduplicate_segments=false"));
+      }
+      let app = load_app(&overrides)
+        .await
+        .with_cancellation(cancellation)
+        .with_input_encoding(cli.encoding.clone())
+        .with_transcript_format(cli.transcript_format)
+        .with_strict(strict)
+        .with_summary(with_summary)
+        .with_readability(cli.readability)
+        .with_report(report.clone())
+        .with_side_by_side(side_by_side.clone());
       let format = OutputFormat::from_flags(output_json);
-      app.refine_whisper_transcription(input, file, format).await
+      #[cfg(unix)]
+      if let Some(mut client) = connect_daemon(
+        &app,
+        cli.no_daemon
+          || with_summary
+          || report.is_some()
+          || side_by_side.is_some(),
+        &overrides,
+      )
+      .await
+      {
+        let result = app
+          .refine_whisper_on_daemon(&mut client, input, file, format)
+          .await;
+        print_result(&app, &sinks, format, result).await;
+        return;
+      }
+      let refinement = app.refine_whisper_transcription(input, file, format);
+      let result =
+        usage::track(app.config(), "whisper-transcribe", refinement).await;
+      (app, format, result)
+    }
+    Some(Commands::Chapters {
+      input,
+      file,
+      output_json,
+      strict,
+    }) => {
+      let cancellation = spawn_interrupt_handler();
+      let app = load_app(&cli.overrides)
+        .await
+        .with_cancellation(cancellation)
+        .with_input_encoding(cli.encoding.clone())
+        .with_transcript_format(cli.transcript_format)
+        .with_strict(strict)
+        .with_readability(cli.readability)
+        .with_report(report.clone())
+        .with_side_by_side(side_by_side.clone());
+      let format = OutputFormat::from_flags(output_json);
+      let chapters = app.chapter_whisper_transcription(input, file, format);
+      let result = usage::track(app.config(), "chapters", chapters).await;
+      (app, format, result)
+    }
+    Some(Commands::Transcribe {
+      file,
+      output_json,
+      with_summary,
+    }) => {
+      let cancellation = spawn_interrupt_handler();
+      let app = load_app(&cli.overrides)
+        .await
+        .with_cancellation(cancellation)
+        .with_summary(with_summary)
+        .with_readability(cli.readability)
+        .with_report(report.clone())
+        .with_side_by_side(side_by_side.clone());
+      let format = OutputFormat::from_flags(output_json);
+      let transcription = app.transcribe_audio(file, format);
+      let result =
+        usage::track(app.config(), "transcribe", transcription).await;
+      (app, format, result)
+    }
+    Some(Commands::Probe { output_json }) => {
+      let app = load_app(&cli.overrides).await;
+      let format = OutputFormat::from_flags(output_json);
+      let result = app.probe_capabilities(format).await;
+      (app, format, result)
+    }
+    #[cfg(unix)]
+    Some(Commands::Daemon { systemd }) => {
+      set_journald(systemd);
+      let cancellation = spawn_interrupt_handler();
+      let app = load_app(&cli.overrides)
+        .await
+        .with_cancellation(cancellation);
+      if let Err(e) = ipc::daemon::serve(app, cli.overrides).await {
+        fail(ErrorKind::Other, e);
+      }
+      return;
+    }
+    #[cfg(not(unix))]
+    Some(Commands::Daemon { .. }) => {
+      fail(ErrorKind::Other, t!("unsupported-daemon"));
+    }
+    #[cfg(unix)]
+    Some(Commands::Tui { file, output }) => {
+      let app = load_app(&cli.overrides)
+        .await
+        .with_input_encoding(cli.encoding.clone())
+        .with_transcript_format(cli.transcript_format);
+      match tui::run(&app, file, output).await {
+        Ok(Some(output_path)) => {
+          println!(
+            "{}",
+            t!("tui-written", path = output_path.display().to_string())
+          );
+          return;
+        }
+        Ok(None) => return,
+        Err(e) => {
+          fail(ErrorKind::Other, e);
+        }
+      }
+    }
+    #[cfg(not(unix))]
+    Some(Commands::Tui { .. }) => {
+      fail(ErrorKind::Other, t!("unsupported-tui"));
+    }
+    Some(Commands::Repl) => {
+      let app = load_app(&cli.overrides).await;
+      if let Err(e) = repl::run(&app).await {
+        fail(ErrorKind::Other, e);
+      }
+      return;
+    }
+    Some(Commands::Dictate { stop: true, .. }) => {
+      match dictation::control::send("TERM").await {
+        Ok(pid) => println!("{}", t!("dictate-stopped", pid = pid)),
+        Err(e) => fail(ErrorKind::Other, e),
+      }
+      return;
+    }
+    Some(Commands::Dictate { toggle: true, .. }) => {
+      if let Err(e) = dictation::control::send("USR1").await {
+        fail(ErrorKind::Other, e);
+      }
+      return;
+    }
+    Some(Commands::Dictate {
+      background: true, ..
+    }) => match dictation::control::spawn_background() {
+      Ok((pid, log_path)) => {
+        println!(
+          "{}",
+          t!(
+            "dictate-background",
+            pid = pid,
+            path = log_path.display().to_string()
+          )
+        );
+        return;
+      }
+      Err(e) => fail(ErrorKind::Other, e),
+    },
+    Some(Commands::Dictate { .. }) => {
+      let cancellation = spawn_interrupt_handler();
+      let app = load_app(&cli.overrides)
+        .await
+        .with_cancellation(cancellation);
+      if let Err(e) = dictation::run(&app).await {
+        fail(ErrorKind::Other, e);
+      }
+      return;
+    }
+    #[cfg(unix)]
+    Some(Commands::InstallService { force }) => {
+      match systemd::unit::install(force).await {
+        Ok(unit_path) => {
+          println!(
+            "{}",
+            t!("service-written", path = unit_path.display().to_string())
+          );
+          println!("{}", t!("service-enable", unit = systemd::unit::UNIT_NAME));
+          return;
+        }
+        Err(e) => {
+          fail(ErrorKind::Other, e);
+        }
+      }
+    }
+    #[cfg(not(unix))]
+    Some(Commands::InstallService { .. }) => {
+      fail(ErrorKind::Other, t!("unsupported-service"));
+    }
+    None if cli.stdio => {
+      let cancellation = spawn_interrupt_handler();
+      let app = load_app(&cli.overrides)
+        .await
+        .with_cancellation(cancellation);
+      match ipc::serve_stdio(app, cli.overrides).await {
+        Ok(_) => return,
+        Err(e) => {
+          fail(ErrorKind::Other, e);
+        }
+      }
+    }
+    None if cli.line_mode.is_some() => {
+      let cancellation = spawn_interrupt_handler();
+      let unit = cli.line_mode.unwrap_or_default();
+      let app = load_app(&cli.overrides)
+        .await
+        .with_cancellation(cancellation)
+        .with_readability(cli.readability);
+      let format = OutputFormat::from_flags(cli.output_json);
+      let sinks = create_sinks(&app, &sinks, format);
+      let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+      match app.refine_stream(stdin, unit, format, &sinks).await {
+        Ok(0) => return,
+        Ok(failed) => fail(
+          ErrorKind::Other,
+          t!("line-mode-failed", failed = failed, unit = unit.to_string()),
+        ),
+        Err(e) => fail(e.kind(), e),
+      }
     }
     None => {
+      let cancellation = spawn_interrupt_handler();
+      let app = load_app(&cli.overrides)
+        .await
+        .with_cancellation(cancellation)
+        .with_input_encoding(cli.encoding.clone())
+        .with_summary(cli.with_summary)
+        .with_readability(cli.readability)
+        .with_report(report.clone())
+        .with_side_by_side(side_by_side.clone())
+        .with_range(range.clone());
       let format = OutputFormat::from_flags(cli.output_json);
-      app.refine_text(cli.input, cli.file, format).await
+      #[cfg(unix)]
+      if let Some(mut client) = connect_daemon(
+        &app,
+        cli.no_daemon
+          || cli.with_summary
+          || report.is_some()
+          || side_by_side.is_some()
+          || range.is_some(),
+        &cli.overrides,
+      )
+      .await
+      {
+        let result = app
+          .refine_text_on_daemon(&mut client, cli.input, cli.file, format)
+          .await;
+        print_result(&app, &sinks, format, result).await;
+        return;
+      }
+      let refinement = app.refine_text(cli.input, cli.file, format);
+      let result = usage::track(app.config(), "refine", refinement).await;
+      (app, format, result)
+    }
+  };
+
+  print_result(&app, &sinks, format, result).await;
+}
+
+/// Runs a `queue` subcommand, exiting with an error status on failure.
+///
+/// # Arguments
+///
+/// * `overrides` - Command-line configuration overrides
+/// * `queue` - The job queue
+/// * `action` - The subcommand
+async fn run_queue_command(
+  overrides: &[String],
+  queue: &Queue,
+  action: QueueCommands,
+) {
+  match action {
+    QueueCommands::Add {
+      files,
+      recursive,
+      ext,
+      kind,
+      output_dir,
+      force,
+    } => {
+      let options = AddOptions {
+        kind,
+        output_dir: output_dir.map(PathBuf::from),
+        recursive,
+        extensions: ext,
+        force,
+      };
+      match queue.add(&files, &options).await {
+        Ok(summary) => println!(
+          "{}",
+          t!(
+            "queue-added",
+            added = summary.added,
+            found = summary.found,
+            path = queue.path().display().to_string(),
+            up_to_date = summary.up_to_date
+          )
+        ),
+        Err(e) => fail(e.kind(), e),
+      }
+    }
+    QueueCommands::Run {
+      limit,
+      retry_failed,
+      force,
+    } => {
+      let cancellation = spawn_interrupt_handler();
+      let app = load_app(overrides).await.with_cancellation(cancellation);
+      if retry_failed && let Err(e) = queue.retry_failed().await {
+        fail(e.kind(), e);
+      }
+      match queue::run(&app, queue, limit, force).await {
+        Ok(summary) if app.cancellation().is_cancelled() => fail(
+          ErrorKind::Cancelled,
+          t!("queue-cancelled", done = summary.done),
+        ),
+        Ok(summary) if summary.failed + summary.refused == 0 => {
+          println!(
+            "{}",
+            t!(
+              "queue-refined",
+              done = summary.done,
+              unchanged = summary.unchanged
+            )
+          );
+        }
+        Ok(summary) => fail(
+          match summary.failed {
+            0 => ErrorKind::Refusal,
+            _ => ErrorKind::Other,
+          },
+          t!(
+            "queue-failed",
+            failed = summary.failed + summary.refused,
+            attempted = summary.attempted(),
+            refused = summary.refused
+          ),
+        ),
+        Err(e) => fail(e.kind(), e),
+      }
+    }
+    QueueCommands::Status { output_json } => match queue.jobs().await {
+      Ok(jobs) => println!(
+        "{}",
+        queue::format_status(&jobs, OutputFormat::from_flags(output_json))
+      ),
+      Err(e) => fail(e.kind(), e),
+    },
+  }
+}
+
+/// Runs a `dictionary` subcommand, exiting with the error's status on
+/// failure.
+///
+/// # Arguments
+///
+/// * `config` - Configuration with the dictionary path
+/// * `action` - The subcommand to run
+async fn run_dictionary_command(config: &Config, action: DictionaryCommands) {
+  let path = match dictionary::configured_path(config) {
+    Ok(path) => path,
+    Err(e) => fail(e.kind(), e),
+  };
+  match action {
+    DictionaryCommands::Import {
+      file,
+      format,
+      column,
+      header,
+      language,
+    } => {
+      let content = match tokio::fs::read_to_string(&file).await {
+        Ok(content) => content,
+        Err(e) => fail(
+          ErrorKind::Input,
+          t!(
+            "file-read-failed",
+            path = file.clone(),
+            error = e.to_string()
+          ),
+        ),
+      };
+      let options = ImportOptions {
+        format: format.unwrap_or_else(|| DictionaryFormat::from_path(&file)),
+        column,
+        header,
+      };
+      let mut terms = match formats::import(&file, &content, options) {
+        Ok(terms) => terms,
+        Err(e) => fail(e.kind(), e),
+      };
+      if let Some(language) = language {
+        let language = dictionary::language::normalize(&language);
+        for term in terms.iter_mut().filter(|term| term.language.is_none()) {
+          term.language = Some(language.clone());
+        }
+      }
+      match dictionary::add_terms(&path, &terms).await {
+        Ok(added) => {
+          println!(
+            "{}",
+            t!(
+              "dictionary-imported",
+              added = added,
+              total = terms.len(),
+              path = path.clone()
+            )
+          )
+        }
+        Err(e) => fail(e.kind(), e),
+      }
+    }
+    DictionaryCommands::Export { format } => {
+      let exported = dictionary::load(&path, false)
+        .await
+        .and_then(|terms| formats::export(&terms, format));
+      match exported {
+        Ok(exported) => print!("{}", exported),
+        Err(e) => fail(e.kind(), e),
+      }
+    }
+    DictionaryCommands::Learn { min_count, add } => {
+      learn_dictionary_terms(&path, min_count, add).await
+    }
+  }
+}
+
+/// Proposes the words repeatedly corrected during review as dictionary
+/// terms, adding them if `add` is set, and exits on failure.
+async fn learn_dictionary_terms(path: &str, min_count: usize, add: bool) {
+  let corrections = match learn::load().await {
+    Ok(corrections) => corrections,
+    Err(e) => fail(e.kind(), e),
+  };
+  let entries = if Path::new(path).is_file() {
+    match dictionary::load(path, false).await {
+      Ok(entries) => entries,
+      Err(e) => fail(e.kind(), e),
+    }
+  } else {
+    Vec::new()
+  };
+
+  let proposals = learn::propose(&corrections, &entries, min_count);
+  if proposals.is_empty() {
+    println!(
+      "{}",
+      t!(
+        "dictionary-nothing-learned",
+        min_count = min_count,
+        path = path
+      )
+    );
+    return;
+  }
+  for proposal in &proposals {
+    println!(
+      "{} ({}x, replacing {})",
+      proposal.term,
+      proposal.count,
+      proposal.variants.join(", ")
+    );
+  }
+  if !add {
+    println!("{}", t!("dictionary-learn-hint", path = path));
+    return;
+  }
+
+  let terms: Vec<DictionaryEntry> = proposals
+    .into_iter()
+    .map(|proposal| DictionaryEntry::new(proposal.term))
+    .collect();
+  match dictionary::add_terms(path, &terms).await {
+    Ok(added) => {
+      println!("{}", t!("dictionary-learned", added = added, path = path))
     }
+    Err(e) => fail(e.kind(), e),
+  }
+}
+
+/// Checks for a newer release and installs it unless `check_only` is set,
+/// exiting with the error's status on failure.
+///
+/// # Arguments
+///
+/// * `config` - Configuration with the proxy settings
+/// * `check_only` - Whether to only report an available update
+async fn self_update(config: &Config, check_only: bool) {
+  let check = match update::check(config).await {
+    Ok(check) => check,
+    Err(e) => fail(e.kind(), e),
   };
+  if !check.is_newer() {
+    println!(
+      "{}",
+      t!("update-current", version = check.current.to_string())
+    );
+    return;
+  }
+  if check_only {
+    println!(
+      "{}",
+      t!(
+        "update-available",
+        latest = check.latest.to_string(),
+        current = check.current.to_string()
+      )
+    );
+    return;
+  }
+  match update::install(config, &check).await {
+    Ok(path) => println!(
+      "{}",
+      t!(
+        "update-installed",
+        current = check.current.to_string(),
+        latest = check.latest.to_string(),
+        path = path.display().to_string()
+      )
+    ),
+    Err(e) => fail(e.kind(), e),
+  }
+}
 
-  match result {
-    Ok(output) => println!("{}", output),
+/// Writes the refinement result to the output sinks, exiting with the
+/// error's status on failure.
+///
+/// The sinks are those given with `--sink`, or `[output] sinks` if none
+/// are. A cancelled run still writes what was refined before it stopped.
+/// With `--timing`, the phase durations are printed to stderr.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+/// * `sinks` - The `--sink` specs
+/// * `format` - The output format, applied to a partial result and
+///   passed to the sinks
+/// * `result` - The refinement result
+async fn print_result(
+  app: &App,
+  sinks: &[String],
+  format: OutputFormat,
+  result: RuntimeResult<String>,
+) {
+  let mut written = Ok(());
+  match &result {
+    Ok(output) => {
+      written =
+        sink::write_all(&create_sinks(app, sinks, format), output).await;
+    }
+    // Keep what was refined before the run was cancelled.
+    Err(RuntimeError::Cancelled(partial)) if !partial.is_empty() => {
+      let partial = match app.format_output(partial.clone(), format) {
+        Ok(partial) => partial,
+        Err(e) => fail(e.kind(), e),
+      };
+      written =
+        sink::write_all(&create_sinks(app, sinks, format), &partial).await;
+    }
+    Err(_) => {}
+  }
+  if timing::is_enabled() {
+    eprintln!("{}", timing::report());
+  }
+  if let Err(e) = result {
+    fail(e.kind(), e);
+  }
+  if let Err(e) = written {
+    fail(ErrorKind::Other, e);
+  }
+}
+
+/// Creates the output sinks, exiting if one cannot be created.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+/// * `sinks` - The `--sink` specs, or none to use `[output] sinks`
+/// * `format` - The format of the results written
+///
+/// # Returns
+///
+/// The output sinks.
+fn create_sinks(
+  app: &App,
+  sinks: &[String],
+  format: OutputFormat,
+) -> Vec<Box<dyn OutputSink>> {
+  let specs = if sinks.is_empty() {
+    app.config().get_output_sinks()
+  } else {
+    sinks.to_vec()
+  };
+  return match sink::create_all(&specs, app.config(), format) {
+    Ok(sinks) => sinks,
+    Err(e) => fail(ErrorKind::Validation, e),
+  };
+}
+
+/// Reports a failure on stderr and exits with the status of its kind.
+///
+/// With `--errors-json`, the failure is printed as a JSON object with its
+/// kind, exit status and message.
+///
+/// # Arguments
+///
+/// * `kind` - The category of the failure
+/// * `message` - The error message
+fn fail(kind: ErrorKind, message: impl Display) -> ! {
+  if ERRORS_JSON.load(Ordering::Relaxed) {
+    let error = serde_json::json!({
+      "error": {
+        "kind": kind.name(),
+        "code": kind.exit_code(),
+        "message": message.to_string(),
+      }
+    });
+    eprintln!("{}", error);
+  } else {
+    eprintln!("{}", message);
+  }
+  exit(kind.exit_code());
+}
+
+/// Connects to a running daemon unless the invocation must run locally.
+///
+/// Invocations with `--set` overrides (including those implied by flags
+/// like `--threshold`) or `--no-daemon` are never forwarded, since the
+/// daemon runs with its own configuration. Neither are those with
+/// `--with-summary`, `--report`, `--side-by-side` or a range like
+/// `--lines`, which the daemon protocol does not carry. `PEGASUS_*`
+/// environment variables are forwarded with every request, unless one
+/// sets a key the daemon does not accept, such as `PEGASUS_LLM_URL`.
+///
+/// # Arguments
+///
+/// * `app` - The configured application
+/// * `no_daemon` - Whether the invocation must run locally
+/// * `overrides` - The `--set` overrides
+///
+/// # Returns
+///
+/// The daemon connection, or `None` to refine locally.
+#[cfg(unix)]
+async fn connect_daemon(
+  app: &App,
+  no_daemon: bool,
+  overrides: &[String],
+) -> Option<DaemonClient> {
+  if no_daemon || !overrides.is_empty() {
+    return None;
+  }
+  let forwarded = resolver::environment_overrides().ok()?;
+  if let Some(assignment) = forwarded
+    .iter()
+    .find(|assignment| discovery::is_untrusted_override(assignment))
+  {
+    let key = assignment.split_once('=').map_or("", |(key, _)| key);
+    vlog!("Refining locally, since the daemon does not accept {}", key);
+    return None;
+  }
+  let client =
+    DaemonClient::connect(&app.config().get_server_client_token()).await?;
+  return Some(
+    client
+      .with_cancellation(app.cancellation().clone())
+      .with_overrides(forwarded),
+  );
+}
+
+/// Loads the configuration and creates the application.
+///
+/// Exits the process with an error message if the configuration is invalid.
+///
+/// # Arguments
+///
+/// * `overrides` - Command-line configuration overrides
+///
+/// # Returns
+///
+/// The configured `App`.
+async fn load_app(overrides: &[String]) -> App {
+  let config = match Config::load(overrides).await {
+    Ok(config) => config,
     Err(e) => {
-      eprintln!("{}", e);
-      std::process::exit(1);
+      fail(e.kind(), t!("config-error", error = e.to_string()));
+    }
+  };
+
+  return App::new(config);
+}
+
+/// Exits the process after removing any remaining temporary files and
+/// stopping any LLM server it started.
+///
+/// `std::process::exit` does not run destructors, so temporary file guards
+/// and backends still alive on this path would otherwise leak.
+///
+/// # Arguments
+///
+/// * `code` - The process exit code
+fn exit(code: i32) -> ! {
+  temporary::remove_all();
+  backend::stop_all();
+  dictation::stop_all();
+  std::process::exit(code);
+}
+
+/// Exits when the process is terminated or its terminal hangs up.
+///
+/// Without a handler these signals end the process without any cleanup, so
+/// LLM servers it started would keep running and temporary files would be
+/// left behind.
+#[cfg(unix)]
+fn spawn_termination_handler() {
+  use tokio::signal::unix::{SignalKind, signal};
+
+  for (kind, code) in
+    [(SignalKind::terminate(), 143), (SignalKind::hangup(), 129)]
+  {
+    let Ok(mut signals) = signal(kind) else {
+      continue;
+    };
+    tokio::spawn(async move {
+      if signals.recv().await.is_some() {
+        exit(code);
+      }
+    });
+  }
+}
+
+/// Cancels the run when the process is interrupted, and exits when it is
+/// interrupted again.
+///
+/// The first Ctrl-C cancels the returned token: the run drops its requests
+/// in flight, prints what was refined until then and exits with status
+/// 130. A second Ctrl-C removes temporary files and exits at once.
+///
+/// Only installed for refinement runs: commands that hand the terminal to a
+/// child process, like `config edit`, must not exit under it.
+///
+/// # Returns
+///
+/// The token to run the application with.
+fn spawn_interrupt_handler() -> CancellationToken {
+  let cancellation = CancellationToken::new();
+  let interrupted = cancellation.clone();
+  tokio::spawn(async move {
+    if tokio::signal::ctrl_c().await.is_err() {
+      return;
+    }
+    elog!(logging::WARNING, "{}", t!("interrupted"));
+    interrupted.cancel();
+    if tokio::signal::ctrl_c().await.is_ok() {
+      exit(130);
     }
+  });
+  return cancellation;
+}
+
+/// Reads an API key from the first line of standard input.
+///
+/// Keeps the key out of shell history when `auth set` is called without an
+/// argument.
+///
+/// # Returns
+///
+/// The trimmed API key.
+fn read_api_key_from_stdin() -> String {
+  let mut key = String::new();
+  if std::io::stdin().read_line(&mut key).is_err() {
+    fail(ErrorKind::Input, t!("api-key-stdin-failed"));
   }
+  return key.trim().to_string();
 }
@@ -1,6 +0,0 @@
-//! Output format handling for refined text results.
-//!
-//! ## Components
-//! - [`OutputFormat`]: Enum for text/JSON output formats
-
-pub mod format;
@@ -0,0 +1,574 @@
+//! End-to-end tests of the `pegasus` binary against a mock LLM server.
+
+mod support;
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+
+use predicates::prelude::*;
+use tempfile::TempDir;
+
+use support::{
+  CHAT_COMPLETIONS, DEFAULT_ANSWER, MockServer, Reply, pegasus, pegasus_process,
+};
+
+#[test]
+fn refines_text_with_the_chat_completion_api() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+
+  pegasus(&server, &home)
+    .args(["--set", "llm.model=mock-model"])
+    .args(["--input", "hello world"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(DEFAULT_ANSWER));
+
+  let requests = server.requests_to(CHAT_COMPLETIONS);
+  assert_eq!(requests.len(), 1);
+  let body = requests[0].json();
+  assert_eq!(body["model"], "mock-model");
+  let messages = body["messages"].to_string();
+  assert!(messages.contains("hello world"), "{}", messages);
+}
+
+#[test]
+fn sends_the_api_key_as_a_bearer_token() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+
+  pegasus(&server, &home)
+    .args(["--set", "llm.api_key=secret-key"])
+    .args(["--input", "hello world"])
+    .assert()
+    .success();
+
+  let requests = server.requests_to(CHAT_COMPLETIONS);
+  assert_eq!(
+    requests[0].header("Authorization"),
+    Some("Bearer secret-key")
+  );
+}
+
+#[test]
+fn sends_no_authorization_without_an_api_key() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+
+  pegasus(&server, &home)
+    .args(["--input", "hello world"])
+    .assert()
+    .success();
+
+  let requests = server.requests_to(CHAT_COMPLETIONS);
+  assert_eq!(requests[0].header("Authorization"), None);
+}
+
+#[test]
+fn sends_the_request_id_when_enabled() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+
+  pegasus(&server, &home)
+    .args(["--set", "network.request_id_header=true"])
+    .args(["--input", "hello world"])
+    .assert()
+    .success();
+
+  let requests = server.requests_to(CHAT_COMPLETIONS);
+  let request_id = requests[0].header("X-Request-Id").unwrap_or_default();
+  assert!(!request_id.is_empty());
+}
+
+#[test]
+fn fails_with_the_network_status_on_server_errors() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server.reply(Reply::Status(500, String::from("model crashed")));
+
+  pegasus(&server, &home)
+    .args(["--set", "network.retries=0"])
+    .args(["--input", "hello world"])
+    .assert()
+    .code(5)
+    .stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn rejects_prompts_that_leave_no_room_for_the_answer() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+
+  pegasus(&server, &home)
+    .args(["--set", "llm.context_window=64"])
+    .args(["--input", "hello world"])
+    .assert()
+    .code(4)
+    .stderr(predicate::str::contains("lower `[input] chunk_size`"));
+
+  assert!(server.requests_to(CHAT_COMPLETIONS).is_empty());
+}
+
+#[test]
+fn reads_a_summary_wrapped_in_a_code_fence() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server.reply(Reply::Completion(String::from("Hello world.")));
+  server.reply(Reply::Completion(String::from(
+    "Here it is:\n```json\n{\"title\": \"Greeting\", \"summary\": \"A hello.\"}\n```",
+  )));
+
+  let output = pegasus(&server, &home)
+    .args(["--with-summary", "--output-json", "--input", "hello world"])
+    .assert()
+    .success()
+    .get_output()
+    .stdout
+    .clone();
+
+  let output: serde_json::Value = serde_json::from_slice(&output).unwrap();
+  assert_eq!(output["title"], "Greeting");
+  assert_eq!(output["summary"], "A hello.");
+}
+
+#[test]
+fn retries_server_errors_with_a_backoff() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server
+    .reply(Reply::Status(503, String::from("overloaded")))
+    .reply(Reply::Status(429, String::from("slow down")));
+
+  pegasus(&server, &home)
+    .args(["--input", "hello world"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(DEFAULT_ANSWER))
+    .stderr(predicate::str::contains("retrying in 500ms (1/2)"))
+    .stderr(predicate::str::contains("retrying in 1000ms (2/2)"));
+
+  assert_eq!(server.requests_to(CHAT_COMPLETIONS).len(), 3);
+}
+
+#[test]
+fn fails_when_the_api_key_is_rejected() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server.reply(Reply::Status(401, String::from("invalid API key")));
+
+  pegasus(&server, &home)
+    .args(["--set", "llm.api_key=wrong-key"])
+    .args(["--input", "hello world"])
+    .assert()
+    .code(7)
+    .stdout(predicate::str::is_empty());
+
+  // Client errors are not retried.
+  assert_eq!(server.requests_to(CHAT_COMPLETIONS).len(), 1);
+}
+
+#[test]
+fn fails_with_the_llm_status_when_the_service_rejects_the_request() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server.reply(Reply::Status(404, String::from("model not found")));
+
+  pegasus(&server, &home)
+    .args(["--input", "hello world"])
+    .assert()
+    .code(6)
+    .stderr(predicate::str::contains("HTTP 404"));
+}
+
+#[test]
+fn uploads_recordings_with_the_transcription_api_key_file() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  let recording = home.path().join("clip.wav");
+  std::fs::write(&recording, b"RIFF audio").unwrap();
+  let key_file = home.path().join("transcription.key");
+  std::fs::write(&key_file, "secret-key\n").unwrap();
+  server.reply(Reply::Status(
+    200,
+    String::from(r#"{"text": "hello world"}"#),
+  ));
+
+  pegasus(&server, &home)
+    .args(["--set", &format!("transcription.url={}", server.url())])
+    .args(["--set", "transcription.provider=whisper-cpp"])
+    .args([
+      "--set",
+      &format!("transcription.api_key_file={}", key_file.display()),
+    ])
+    .args(["transcribe", recording.to_str().unwrap()])
+    .assert()
+    .success();
+
+  let uploads = server.requests_to("/inference");
+  assert_eq!(uploads.len(), 1);
+  assert_eq!(
+    uploads[0].header("Authorization"),
+    Some("Bearer secret-key")
+  );
+  assert!(
+    uploads[0]
+      .header("Content-Type")
+      .is_some_and(|value| value.starts_with("multipart/form-data"))
+  );
+  assert!(uploads[0].body.contains("filename=\"clip.wav\""));
+  assert!(uploads[0].body.contains("Content-Type: audio/wav"));
+  assert!(uploads[0].body.contains("RIFF audio"));
+}
+
+#[test]
+fn ignores_commands_and_urls_in_a_project_configuration() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  let project = TempDir::new().unwrap();
+  let marker = project.path().join("marker");
+  std::fs::write(
+    project.path().join(".pegasus.toml"),
+    format!(
+      "[llm]\nurl = \"http://127.0.0.1:9\"\napi_key_command = \"touch {}\"\n",
+      marker.display()
+    ),
+  )
+  .unwrap();
+
+  pegasus(&server, &home)
+    .current_dir(project.path())
+    .args(["--input", "hello world"])
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Ignoring llm.api_key_command"));
+
+  assert!(!marker.exists());
+  assert_eq!(server.requests_to(CHAT_COMPLETIONS).len(), 1);
+}
+
+#[test]
+fn switches_to_the_fallback_endpoint_when_the_primary_fails() {
+  let server = MockServer::start();
+  let fallback = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server.reply(Reply::Status(503, String::from("overloaded")));
+  fallback.reply(Reply::Completion(String::from("From the fallback.")));
+
+  pegasus(&server, &home)
+    .args(["--set", &format!("llm.fallback_url={}", fallback.url())])
+    .args(["--input", "hello world"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("From the fallback."));
+
+  assert_eq!(server.requests_to(CHAT_COMPLETIONS).len(), 1);
+  assert_eq!(fallback.requests_to(CHAT_COMPLETIONS).len(), 1);
+}
+
+#[test]
+fn retries_refusals_with_a_neutral_framing() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server.reply(Reply::Completion(String::from(
+    "I can't help with that request because it violates my guidelines.",
+  )));
+
+  pegasus(&server, &home)
+    .args(["--input", "hello world"])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(DEFAULT_ANSWER));
+
+  let requests = server.requests_to(CHAT_COMPLETIONS);
+  assert_eq!(requests.len(), 2);
+  assert_ne!(
+    requests[0].json()["messages"][0],
+    requests[1].json()["messages"][0]
+  );
+}
+
+#[test]
+fn fails_on_refusals_when_configured() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server.reply(Reply::Completion(String::from(
+    "I can't help with that request because it violates my guidelines.",
+  )));
+
+  pegasus(&server, &home)
+    .args(["--set", "llm.refusal=fail"])
+    .args(["--input", "hello world"])
+    .assert()
+    .code(8);
+
+  assert_eq!(server.requests_to(CHAT_COMPLETIONS).len(), 1);
+}
+
+#[test]
+fn rejects_a_streamed_answer_it_did_not_ask_for() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server.reply(Reply::Stream(vec![
+    String::from("Hello, "),
+    String::from("world."),
+  ]));
+
+  pegasus(&server, &home)
+    .args(["--input", "hello world"])
+    .assert()
+    .failure()
+    .stdout(predicate::str::is_empty());
+
+  let requests = server.requests_to(CHAT_COMPLETIONS);
+  assert_ne!(requests[0].json()["stream"], true);
+}
+
+#[test]
+fn retries_the_webhook_until_it_accepts_the_result() {
+  let server = MockServer::start();
+  let webhook = MockServer::start();
+  let home = TempDir::new().unwrap();
+  webhook.reply(Reply::Status(503, String::from("try again")));
+
+  pegasus(&server, &home)
+    .args([
+      "--set",
+      &format!("output.webhook_url={}/hook", webhook.url()),
+    ])
+    .args(["--set", "output.webhook_retries=2"])
+    .args(["--sink", "webhook"])
+    .args(["--input", "hello world"])
+    .assert()
+    .success();
+
+  let posts = webhook.requests_to("/hook");
+  assert_eq!(posts.len(), 2);
+  assert_eq!(posts[1].json()["output"], DEFAULT_ANSWER);
+}
+
+#[test]
+fn does_not_retry_a_webhook_that_rejects_the_result() {
+  let server = MockServer::start();
+  let webhook = MockServer::start();
+  let home = TempDir::new().unwrap();
+  webhook.reply(Reply::Status(400, String::from("bad payload")));
+
+  pegasus(&server, &home)
+    .args([
+      "--set",
+      &format!("output.webhook_url={}/hook", webhook.url()),
+    ])
+    .args(["--set", "output.webhook_retries=2"])
+    .args(["--sink", "webhook"])
+    .args(["--output-json", "--input", "hello world"])
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("HTTP 400"));
+
+  let posts = webhook.requests_to("/hook");
+  assert_eq!(posts.len(), 1);
+  let body = posts[0].json();
+  assert_eq!(body["format"], "json");
+  assert_eq!(body["output"]["text"], DEFAULT_ANSWER);
+}
+
+#[cfg(unix)]
+#[test]
+fn prints_the_chunks_refined_before_an_interrupt() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  server
+    .reply(Reply::Completion(String::from("First paragraph.")))
+    .reply(Reply::Stall);
+
+  let child = pegasus_process(&server, &home)
+    .args(["--set", "input.chunk_size=10"])
+    .args(["--input", "first paragraph\n\nsecond paragraph"])
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap();
+  assert!(server.wait_for(CHAT_COMPLETIONS, 2));
+  let interrupted = std::process::Command::new("kill")
+    .args(["-INT", &child.id().to_string()])
+    .status()
+    .unwrap();
+  assert!(interrupted.success());
+
+  let output = child.wait_with_output().unwrap();
+  assert_eq!(output.status.code(), Some(130));
+  assert_eq!(
+    String::from_utf8_lossy(&output.stdout),
+    "First paragraph.\n"
+  );
+}
+
+#[test]
+fn pauses_refinement_while_the_client_reads_nothing() {
+  const PARAGRAPHS: usize = 60;
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  let paragraph = "some words spoken here ".repeat(400);
+  for _ in 0..PARAGRAPHS {
+    server.reply(Reply::Completion(paragraph.clone()));
+  }
+
+  let mut child = pegasus_process(&server, &home)
+    .arg("--stdio")
+    .args(["--set", "input.chunk_size=5000"])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+  let mut stdin = child.stdin.take().unwrap();
+  let text = vec![paragraph.trim(); PARAGRAPHS].join("\n\n");
+  let request = serde_json::json!({
+    "jsonrpc": "2.0",
+    "id": 1,
+    "method": "refine",
+    "params": { "text": text },
+  });
+  writeln!(stdin, "{}", request).unwrap();
+
+  // The partial notifications fill the pipe and the queue, after which
+  // no further chunk is requested.
+  thread::sleep(Duration::from_secs(2));
+  let paused = server.requests_to(CHAT_COMPLETIONS).len();
+  assert!(
+    0 < paused && paused < PARAGRAPHS,
+    "{} chunks refined",
+    paused
+  );
+  thread::sleep(Duration::from_secs(1));
+  assert_eq!(server.requests_to(CHAT_COMPLETIONS).len(), paused);
+
+  let mut partials = 0;
+  for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+    let message: serde_json::Value = serde_json::from_str(&line.unwrap())
+      .expect("The server wrote invalid JSON");
+    if message["method"] == "partial" {
+      partials += 1;
+    } else if message["id"] == 1 {
+      assert!(message["result"]["text"].is_string());
+      break;
+    }
+  }
+  assert_eq!(partials, PARAGRAPHS);
+  assert_eq!(server.requests_to(CHAT_COMPLETIONS).len(), PARAGRAPHS);
+
+  drop(stdin);
+  assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn applies_the_overrides_of_a_request() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+
+  let mut child = pegasus_process(&server, &home)
+    .arg("--stdio")
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+  let mut stdin = child.stdin.take().unwrap();
+  for (id, overrides) in [
+    (1, vec!["llm.model=overridden-model"]),
+    (2, vec!["llm.url=http://127.0.0.1:9"]),
+  ] {
+    let request = serde_json::json!({
+      "jsonrpc": "2.0",
+      "id": id,
+      "method": "refine",
+      "params": { "text": "hello world", "overrides": overrides },
+    });
+    writeln!(stdin, "{}", request).unwrap();
+  }
+
+  let mut responses = Vec::new();
+  for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+    let message: serde_json::Value = serde_json::from_str(&line.unwrap())
+      .expect("The server wrote invalid JSON");
+    if message["id"].is_number() {
+      responses.push(message);
+    }
+    if responses.len() == 2 {
+      break;
+    }
+  }
+  responses.sort_by_key(|response| response["id"].as_i64());
+  assert_eq!(responses[0]["result"]["text"], DEFAULT_ANSWER);
+  assert_eq!(responses[1]["error"]["code"], -32005);
+
+  let requests = server.requests_to(CHAT_COMPLETIONS);
+  assert_eq!(requests.len(), 1);
+  assert_eq!(requests[0].json()["model"], "overridden-model");
+
+  drop(stdin);
+  assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn rejects_queued_files_with_the_same_result() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  let inputs = TempDir::new().unwrap();
+  std::fs::write(inputs.path().join("talk.txt"), "hello world\n").unwrap();
+  std::fs::write(inputs.path().join("talk.json"), "{}\n").unwrap();
+
+  pegasus(&server, &home)
+    .args(["queue", "add", "--recursive"])
+    .arg(inputs.path())
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("talk.refined.txt"));
+
+  let queue = home.path().join("state/pegasus/queue.json");
+  let jobs = || -> usize {
+    let queue: serde_json::Value =
+      serde_json::from_str(&std::fs::read_to_string(&queue).unwrap()).unwrap();
+    return queue["jobs"].as_array().unwrap().len();
+  };
+  pegasus(&server, &home)
+    .args(["queue", "add", "--recursive", "--ext", "txt"])
+    .arg(inputs.path())
+    .assert()
+    .success();
+  pegasus(&server, &home)
+    .args(["queue", "run"])
+    .assert()
+    .success();
+  assert!(inputs.path().join("talk.refined.txt").exists());
+  assert_eq!(jobs(), 1);
+
+  // The next run removes the job done by the last one.
+  pegasus(&server, &home)
+    .args(["queue", "run"])
+    .assert()
+    .success();
+  assert_eq!(jobs(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn skips_links_to_directories_when_adding_recursively() {
+  let server = MockServer::start();
+  let home = TempDir::new().unwrap();
+  let inputs = TempDir::new().unwrap();
+  std::fs::create_dir(inputs.path().join("nested")).unwrap();
+  std::fs::write(inputs.path().join("nested/talk.txt"), "hello\n").unwrap();
+  std::os::unix::fs::symlink(inputs.path(), inputs.path().join("nested/loop"))
+    .unwrap();
+
+  pegasus(&server, &home)
+    .args(["queue", "add", "--recursive"])
+    .arg(inputs.path())
+    .timeout(Duration::from_secs(10))
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Added 1 of 1 files"));
+}
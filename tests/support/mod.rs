@@ -0,0 +1,385 @@
+//! Test support for end-to-end tests of the `pegasus` binary.
+//!
+//! [`MockServer`] is an in-process HTTP server standing in for an
+//! OpenAI-compatible LLM service. It answers `POST /v1/chat/completions`
+//! (and any other POST, such as a webhook) with scripted [`Reply`]s in
+//! order, falling back to [`DEFAULT_ANSWER`] once the script runs out, and
+//! records every request so tests can check paths, headers and bodies.
+//! Capability probes get `404 Not Found`, so the binary treats the server
+//! as a plain OpenAI-compatible endpoint.
+//!
+//! [`pegasus`] builds a command for the binary that talks to a mock server
+//! and keeps its configuration, cache and state in a temporary directory;
+//! [`pegasus_process`] builds the same command for tests that signal the
+//! running process.
+
+#![allow(dead_code)]
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use assert_cmd::Command;
+use tempfile::TempDir;
+
+/// The refined text answered once the scripted replies run out.
+pub const DEFAULT_ANSWER: &str = "Hello, world.";
+
+/// Path of the chat completion API.
+pub const CHAT_COMPLETIONS: &str = "/v1/chat/completions";
+
+/// Paths probed for the capabilities of the endpoint.
+const PROBE_PATHS: [&str; 2] = ["/props", "/api/show"];
+
+/// A scripted answer of the mock server.
+#[derive(Debug, Clone)]
+pub enum Reply {
+  /// A chat completion with the given content
+  Completion(String),
+  /// An error status with the given body
+  Status(u16, String),
+  /// A chat completion streamed as server-sent events, one chunk of
+  /// content per event
+  Stream(Vec<String>),
+  /// No answer at all, as from a server that hangs
+  Stall,
+}
+
+/// A request received by the mock server.
+#[derive(Debug, Clone)]
+pub struct Request {
+  /// The request method, such as `POST`
+  pub method: String,
+  /// The request path, such as `/v1/chat/completions`
+  pub path: String,
+  /// The headers, with lowercase names
+  pub headers: HashMap<String, String>,
+  /// The body
+  pub body: String,
+}
+
+impl Request {
+  /// Gets a header.
+  ///
+  /// # Arguments
+  ///
+  /// * `name` - The header name, in any case
+  ///
+  /// # Returns
+  ///
+  /// The header value, or `None` if the request has no such header.
+  pub fn header(&self, name: &str) -> Option<&str> {
+    return self.headers.get(&name.to_lowercase()).map(String::as_str);
+  }
+
+  /// Parses the body as JSON.
+  ///
+  /// # Returns
+  ///
+  /// The JSON value, or `Value::Null` if the body is not JSON.
+  pub fn json(&self) -> serde_json::Value {
+    return serde_json::from_str(&self.body).unwrap_or_default();
+  }
+}
+
+/// Replies and requests shared with the connection threads.
+#[derive(Debug, Default)]
+struct State {
+  replies: VecDeque<Reply>,
+  requests: Vec<Request>,
+}
+
+/// An in-process mock of an OpenAI-compatible server.
+///
+/// The server runs until the test process exits.
+#[derive(Debug, Clone)]
+pub struct MockServer {
+  address: SocketAddr,
+  state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+  /// Starts a mock server on a free port of the loopback interface.
+  ///
+  /// # Returns
+  ///
+  /// The running server.
+  pub fn start() -> Self {
+    let listener =
+      TcpListener::bind("127.0.0.1:0").expect("Cannot bind the mock server");
+    let address = listener.local_addr().expect("Mock server has no address");
+    let state = Arc::new(Mutex::new(State::default()));
+    let shared = state.clone();
+    thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        let state = shared.clone();
+        thread::spawn(move || handle(stream, &state));
+      }
+    });
+    return MockServer { address, state };
+  }
+
+  /// Gets the base URL of the server.
+  ///
+  /// # Returns
+  ///
+  /// A URL like `http://127.0.0.1:41234`.
+  pub fn url(&self) -> String {
+    return format!("http://{}", self.address);
+  }
+
+  /// Adds a reply to the script.
+  ///
+  /// # Arguments
+  ///
+  /// * `reply` - The reply to the next request not answered by the
+  ///   replies added before
+  ///
+  /// # Returns
+  ///
+  /// The server, for chaining.
+  pub fn reply(&self, reply: Reply) -> &Self {
+    self.lock().replies.push_back(reply);
+    return self;
+  }
+
+  /// Gets the requests received so far, including capability probes.
+  ///
+  /// # Returns
+  ///
+  /// The requests in the order they were received.
+  pub fn requests(&self) -> Vec<Request> {
+    return self.lock().requests.clone();
+  }
+
+  /// Gets the requests received so far for a path.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The request path, such as [`CHAT_COMPLETIONS`]
+  ///
+  /// # Returns
+  ///
+  /// The requests in the order they were received.
+  pub fn requests_to(&self, path: &str) -> Vec<Request> {
+    return self
+      .requests()
+      .into_iter()
+      .filter(|request| request.path == path)
+      .collect();
+  }
+
+  /// Waits until the server has received requests for a path.
+  ///
+  /// # Arguments
+  ///
+  /// * `path` - The request path, such as [`CHAT_COMPLETIONS`]
+  /// * `count` - The number of requests to wait for
+  ///
+  /// # Returns
+  ///
+  /// `true` if they arrived within ten seconds.
+  pub fn wait_for(&self, path: &str, count: usize) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(10) {
+      if self.requests_to(path).len() >= count {
+        return true;
+      }
+      thread::sleep(Duration::from_millis(20));
+    }
+    return false;
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+    return self.state.lock().unwrap_or_else(|e| e.into_inner());
+  }
+}
+
+/// Builds a command for the `pegasus` binary talking to a mock server.
+///
+/// The command runs without the daemon and with its configuration, cache
+/// and state in `home`, so tests neither see nor change the user's files.
+///
+/// # Arguments
+///
+/// * `server` - The server used as `[llm] url`
+/// * `home` - The directory standing in for the home directory
+///
+/// # Returns
+///
+/// The command, to which the test adds its arguments.
+pub fn pegasus(server: &MockServer, home: &TempDir) -> Command {
+  return Command::from_std(pegasus_process(server, home));
+}
+
+/// Builds a command for the `pegasus` binary talking to a mock server, to
+/// be spawned as a process.
+///
+/// # Arguments
+///
+/// * `server` - The server used as `[llm] url`
+/// * `home` - The directory standing in for the home directory
+///
+/// # Returns
+///
+/// The command, to which the test adds its arguments.
+pub fn pegasus_process(
+  server: &MockServer,
+  home: &TempDir,
+) -> std::process::Command {
+  let home = home.path();
+  let mut command =
+    std::process::Command::new(assert_cmd::cargo::cargo_bin("pegasus"));
+  command
+    .env_clear()
+    .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+    .env("HOME", home)
+    .env("XDG_CONFIG_HOME", home.join("config"))
+    .env("XDG_CACHE_HOME", home.join("cache"))
+    .env("XDG_STATE_HOME", home.join("state"))
+    .env("XDG_DATA_HOME", home.join("data"))
+    .env("XDG_RUNTIME_DIR", home.join("runtime"))
+    .env("LANG", "C")
+    .arg("--no-daemon")
+    .arg("--set")
+    .arg(format!("llm.url={}", server.url()));
+  return command;
+}
+
+/// Serves the requests of one connection.
+fn handle(stream: TcpStream, state: &Mutex<State>) {
+  let mut reader = BufReader::new(stream);
+  while let Some(request) = read_request(&mut reader) {
+    let reply = if request.method != "POST"
+      || PROBE_PATHS.contains(&request.path.as_str())
+    {
+      Reply::Status(404, String::from("Not Found"))
+    } else {
+      let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+      state
+        .replies
+        .pop_front()
+        .unwrap_or_else(|| Reply::Completion(DEFAULT_ANSWER.to_string()))
+    };
+    state
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .requests
+      .push(request);
+    if let Reply::Stall = reply {
+      thread::sleep(Duration::from_secs(60));
+      return;
+    }
+    let keep_alive = !matches!(reply, Reply::Stream(_));
+    if write_reply(reader.get_mut(), &reply).is_err() || !keep_alive {
+      return;
+    }
+  }
+}
+
+/// Reads a request, or returns `None` when the connection is closed.
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<Request> {
+  let mut line = String::new();
+  if reader.read_line(&mut line).ok()? == 0 {
+    return None;
+  }
+  let mut parts = line.split_whitespace();
+  let method = parts.next()?.to_string();
+  let path = parts.next()?.to_string();
+
+  let mut headers = HashMap::new();
+  loop {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+      return None;
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = line.split_once(':') {
+      headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+  }
+
+  let length = headers
+    .get("content-length")
+    .and_then(|length| length.parse().ok())
+    .unwrap_or(0);
+  let mut body = vec![0; length];
+  reader.read_exact(&mut body).ok()?;
+  return Some(Request {
+    method,
+    path,
+    headers,
+    body: String::from_utf8_lossy(&body).to_string(),
+  });
+}
+
+/// Writes a reply; streamed replies end the connection.
+fn write_reply(stream: &mut TcpStream, reply: &Reply) -> std::io::Result<()> {
+  let (status, content_type, body) = match reply {
+    Reply::Completion(content) => {
+      let body = serde_json::json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion",
+        "choices": [{
+          "index": 0,
+          "message": { "role": "assistant", "content": content },
+          "finish_reason": "stop",
+        }],
+        "usage": {
+          "prompt_tokens": 100,
+          "completion_tokens": 10,
+          "total_tokens": 110,
+        },
+      });
+      (200, "application/json", body.to_string())
+    }
+    Reply::Status(status, body) => (*status, "text/plain", body.clone()),
+    Reply::Stall => return Ok(()),
+    Reply::Stream(chunks) => {
+      let mut body = String::new();
+      for chunk in chunks {
+        let event = serde_json::json!({
+          "id": "chatcmpl-mock",
+          "object": "chat.completion.chunk",
+          "choices": [{ "index": 0, "delta": { "content": chunk } }],
+        });
+        body.push_str(&format!("data: {}\n\n", event));
+      }
+      body.push_str("data: [DONE]\n\n");
+      let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                  Connection: close\r\n\r\n";
+      stream.write_all(head.as_bytes())?;
+      stream.write_all(body.as_bytes())?;
+      return stream.flush();
+    }
+  };
+  let head = format!(
+    "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+    status,
+    reason(status),
+    content_type,
+    body.len()
+  );
+  stream.write_all(head.as_bytes())?;
+  stream.write_all(body.as_bytes())?;
+  return stream.flush();
+}
+
+/// Gets the reason phrase of a status.
+fn reason(status: u16) -> &'static str {
+  return match status {
+    200 => "OK",
+    401 => "Unauthorized",
+    404 => "Not Found",
+    429 => "Too Many Requests",
+    500 => "Internal Server Error",
+    503 => "Service Unavailable",
+    _ => "Unknown",
+  };
+}